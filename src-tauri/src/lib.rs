@@ -0,0 +1,17218 @@
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{Manager, Runtime, State, Window};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::api::path::{data_dir, download_dir};
+
+// Database models
+#[derive(Debug, Serialize, Deserialize)]
+struct User {
+    id: Option<i32>,
+    username: String,
+    password_hash: String,
+    role: String,
+    fullname: Option<String>,
+    session_id: Option<String>,
+    /// Contraseña temporal (p. ej. de `import_users_csv`) que debe cambiarse
+    #[serde(default)]
+    must_change_password: bool,
+}
+
+/// Ventas por producto. Con `group_by_family` las variantes suman en la fila
+/// de su producto padre.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_sales_by_product(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    order_by: Option<String>,
+    category: Option<String>,
+    limit: Option<i32>,
+    include_archive: Option<bool>,
+    group_by_family: Option<bool>,
+) -> Result<Vec<SalesByProduct>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let lim = limit.unwrap_or(5);
+    let order_col = match order_by.as_deref() {
+        Some("qty") => "total_qty",
+        _ => "total_revenue",
+    };
+    let key_col = if group_by_family.unwrap_or(false) {
+        "COALESCE(v.parent_id, i.product_id)"
+    } else {
+        "i.product_id"
+    };
+    let sql = format!(
+        "SELECT {key} as key_id, COALESCE(p.name, '') as name,
+                COALESCE(SUM(i.quantity),0) as total_qty,
+                COALESCE(SUM(i.line_total),0.0) as total_revenue
+         FROM sale_items i
+         JOIN {table} s ON s.id = i.id
+         LEFT JOIN products v ON v.id = i.product_id
+         LEFT JOIN products p ON p.id = {key}
+         WHERE (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+           AND (?3 IS NULL OR v.category_id = (SELECT id FROM categories WHERE name = TRIM(?3)))
+         GROUP BY key_id, p.name
+         ORDER BY {order} DESC
+         LIMIT ?4",
+        key = key_col,
+        table = valid_sales(include_archive.unwrap_or(false)),
+        order = order_col
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date, category, lim], |row| {
+            Ok(SalesByProduct {
+                product_id: row.get(0)?,
+                name: row.get(1)?,
+                total_qty: row.get(2)?,
+                total_revenue: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesTotals {
+    total_units: f64,
+    total_revenue: f64,
+    /// `total_revenue` sin el impuesto incluido
+    net_revenue: f64,
+    /// Impuesto cobrado; las ventas anteriores a `tax_amount` cuentan como sin impuesto
+    tax_collected: f64,
+    /// Lo que se dejó de cobrar por descuentos; `total_revenue` ya lo tiene restado
+    total_discounts: f64,
+}
+
+#[tauri::command]
+fn get_sales_totals(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    category: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<SalesTotals, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    sales_totals(&conn, start_date.as_deref(), end_date.as_deref(), category.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+fn sales_totals(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    category: Option<&str>,
+    include_archive: bool,
+) -> Result<SalesTotals> {
+    // Las líneas de los comprobantes; la fila de `sales` aporta la fecha, el
+    // impuesto y si está archivada
+    let sql = format!(
+        "SELECT COALESCE(SUM(i.quantity),0) as total_units,
+                COALESCE(SUM(i.line_total),0.0) as total_revenue,
+                COALESCE(SUM(s.tax_amount),0.0) as tax_collected,
+                COALESCE(SUM({}),0.0) as total_discounts
+         FROM sale_items i
+         JOIN {} s ON s.id = i.id
+         LEFT JOIN products p ON p.id = i.product_id
+         WHERE (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+           AND (?3 IS NULL OR p.category_id = (SELECT id FROM categories WHERE name = TRIM(?3)))",
+        LINE_DISCOUNT_SQL,
+        valid_sales(include_archive)
+    );
+    conn.query_row(&sql, rusqlite::params![start_date, end_date, category], |row| {
+        let total_revenue: f64 = row.get(1)?;
+        let tax_collected: f64 = row.get(2)?;
+        Ok(SalesTotals {
+            total_units: row.get(0)?,
+            total_revenue,
+            net_revenue: round_money(total_revenue - tax_collected),
+            tax_collected: round_money(tax_collected),
+            total_discounts: round_money(row.get(3)?),
+        })
+    })
+}
+
+/// Impuesto del rango por mes, para declararlo: base imponible e impuesto de
+/// las ventas con `tax_amount`, y aparte las exoneradas (tasa 0, o anteriores
+/// a que se guardara el impuesto). Usa el impuesto guardado en cada venta, así
+/// que cambiar la tasa después no altera los meses ya vendidos.
+#[tauri::command]
+fn get_tax_summary(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<TaxSummaryMonth>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    tax_summary(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+fn tax_summary(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    include_archive: bool,
+) -> Result<Vec<TaxSummaryMonth>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT substr(sale_date,1,7) AS month, COUNT(*),
+                COALESCE(SUM(CASE WHEN COALESCE(tax_amount,0) > 0 THEN sale_price - tax_amount END),0.0),
+                COALESCE(SUM(tax_amount),0.0),
+                COALESCE(SUM(CASE WHEN COALESCE(tax_amount,0) > 0 THEN 0 ELSE sale_price END),0.0),
+                COALESCE(SUM(sale_price),0.0)
+         FROM {}
+         WHERE (?1 IS NULL OR substr(sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(sale_date,1,10) <= ?2)
+         GROUP BY month
+         ORDER BY month",
+        valid_sales(include_archive)
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            Ok(TaxSummaryMonth {
+                month: row.get(0)?,
+                sales_count: row.get(1)?,
+                taxable_base: round_money(row.get(2)?),
+                tax_collected: round_money(row.get(3)?),
+                exempt_sales: round_money(row.get(4)?),
+                total: round_money(row.get(5)?),
+            })
+        })?
+        .collect();
+    rows
+}
+
+/// Importe descontado en una línea de `sale_items i`: el subtotal sin
+/// descuento menos lo cobrado. `line_total` ya viene neto del porcentaje.
+const LINE_DISCOUNT_SQL: &str =
+    "CASE WHEN COALESCE(i.discount,0) > 0 THEN MAX(i.unit_price * i.quantity - i.line_total, 0) ELSE 0 END";
+
+/// Ventas por medio de pago en el rango (por día, como los demás reportes).
+/// Las ventas sin medio de pago cuentan como efectivo.
+#[tauri::command]
+fn get_sales_by_payment_method(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<SalesByPaymentMethod>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT COALESCE(payment_method, 'efectivo') AS method, COUNT(*), COALESCE(SUM(sale_price),0.0)
+             FROM {}
+             WHERE (?1 IS NULL OR substr(sale_date,1,10) >= ?1)
+               AND (?2 IS NULL OR substr(sale_date,1,10) <= ?2)
+             GROUP BY method
+             ORDER BY 3 DESC, method",
+            valid_sales(false)
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            Ok(SalesByPaymentMethod {
+                payment_method: row.get(0)?,
+                sales_count: row.get(1)?,
+                total_revenue: round_money(row.get(2)?),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+/// Canal con que se agrupan las ventas sin canal.
+const NO_CHANNEL: &str = "sin canal";
+
+/// Unidades, ingreso y participación de cada canal en el rango, del que más
+/// vende al que menos. Los canales se agrupan sin distinguir mayúsculas y
+/// `category` filtra como en `get_sales_totals`.
+#[tauri::command]
+fn get_sales_by_channel(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    category: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<SalesByChannel>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    sales_by_channel(&conn, start_date.as_deref(), end_date.as_deref(), category.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+fn sales_by_channel(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    category: Option<&str>,
+    include_archive: bool,
+) -> Result<Vec<SalesByChannel>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(NULLIF(LOWER(TRIM(s.channel)), ''), ?4) AS channel_key, COUNT(*),
+                COALESCE(SUM(s.quantity),0), COALESCE(SUM(s.sale_price),0.0)
+         FROM {} s
+         LEFT JOIN products p ON p.id = s.product_id
+         WHERE (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+           AND (?3 IS NULL OR p.category_id = (SELECT id FROM categories WHERE name = TRIM(?3)))
+         GROUP BY channel_key
+         ORDER BY 4 DESC, channel_key",
+        valid_sales(include_archive)
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date, category, NO_CHANNEL], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?, row.get::<_, f64>(3)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let total: f64 = rows.iter().map(|r| r.3).sum();
+    Ok(rows
+        .into_iter()
+        .map(|(channel, sales_count, total_units, revenue)| SalesByChannel {
+            channel,
+            sales_count,
+            total_units,
+            total_revenue: round_money(revenue),
+            share_percent: if total > 0.0 { round_money(revenue / total * 100.0) } else { 0.0 },
+        })
+        .collect())
+}
+
+/// Nombre con que se agrupan las ventas sin `created_by`.
+const NO_USER: &str = "sin usuario";
+
+/// Ventas, unidades e ingreso de cada vendedor en el rango, del que más
+/// vende al que menos. Las ventas sin usuario se juntan en `NO_USER` y las
+/// de un usuario ya borrado quedan con su id como nombre.
+#[tauri::command]
+fn get_sales_by_user(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<SalesByUser>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    sales_by_user(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+fn sales_by_user(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    include_archive: bool,
+) -> Result<Vec<SalesByUser>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT s.created_by, COALESCE(u.username, '#' || s.created_by, ?3), u.fullname, COUNT(*),
+                COALESCE(SUM(s.quantity),0), COALESCE(SUM(s.sale_price),0.0)
+         FROM {} s
+         LEFT JOIN users u ON u.id = s.created_by
+         WHERE (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+         GROUP BY s.created_by
+         ORDER BY 6 DESC, s.created_by IS NULL, s.created_by",
+        valid_sales(include_archive)
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date, NO_USER], |row| {
+            Ok(SalesByUser {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                fullname: row.get(2)?,
+                sales_count: row.get(3)?,
+                total_units: row.get(4)?,
+                total_revenue: round_money(row.get(5)?),
+            })
+        })?
+        .collect();
+    rows
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"];
+
+/// Ventas e ingreso por día de la semana y hora, tomada de `sale_date`. Solo
+/// devuelve los casilleros con ventas, ordenados por día y hora; las ventas
+/// sin hora quedan en el casillero `hour = None` de su día y las de fecha
+/// inválida no cuentan. Con `group_by = "weekday"` agrupa solo por día.
+#[tauri::command]
+fn get_sales_heatmap(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    group_by: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<HeatmapBucket>, String> {
+    let by_hour = match group_by.as_deref().map(str::trim).unwrap_or("hour") {
+        "hour" | "" => true,
+        "weekday" => false,
+        other => return Err(format!("group_by inválido: '{}'. Usa hour o weekday", other)),
+    };
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT (CAST(strftime('%w', substr(sale_date,1,10)) AS INTEGER) + 6) % 7 + 1 AS weekday,
+                    CASE WHEN ?3 AND substr(sale_date,12,2) GLOB '[0-2][0-9]' AND CAST(substr(sale_date,12,2) AS INTEGER) < 24
+                         THEN CAST(substr(sale_date,12,2) AS INTEGER) END AS hour,
+                    COUNT(*), COALESCE(SUM(sale_price),0.0)
+             FROM {}
+             WHERE strftime('%w', substr(sale_date,1,10)) IS NOT NULL
+               AND (?1 IS NULL OR substr(sale_date,1,10) >= ?1)
+               AND (?2 IS NULL OR substr(sale_date,1,10) <= ?2)
+             GROUP BY weekday, hour
+             ORDER BY weekday, hour IS NULL, hour",
+            valid_sales(include_archive.unwrap_or(false))
+        ))
+        .map_err(|e| e.to_string())?;
+    let buckets = stmt
+        .query_map(rusqlite::params![start_date, end_date, by_hour], |row| {
+            let weekday: i32 = row.get(0)?;
+            Ok(HeatmapBucket {
+                weekday,
+                weekday_name: WEEKDAY_NAMES[(weekday - 1) as usize].to_string(),
+                hour: row.get(1)?,
+                sales_count: row.get(2)?,
+                total_revenue: round_money(row.get(3)?),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(buckets)
+}
+
+/// Ventas por día de los últimos `days` días. Los días ya cerrados salen de
+/// `daily_sales_summary`; hoy se consulta en vivo. Los resúmenes incluyen lo
+/// archivado, así que sin `include_archive` los días que alcanzó el archivo
+/// también se calculan sobre la tabla viva. Las ventas con fecha futura se
+/// juntan en un único punto `ATTENTION_DAY`, que queda al final.
+#[tauri::command]
+fn get_sales_trend(
+    state: State<AppState>,
+    days: Option<i32>,
+    include_archive: Option<bool>,
+) -> Result<Vec<SalesTrendPoint>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    sales_trend(&conn, days.unwrap_or(7), include_archive.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+fn sales_trend(conn: &Connection, days: i32, include_archive: bool) -> Result<Vec<SalesTrendPoint>> {
+    let modifier = format!("-{} day", days.max(0));
+    let archived_until: Option<String> = if include_archive {
+        None
+    } else {
+        conn.query_row("SELECT MAX(substr(sale_date,1,10)) FROM sales_archive", [], |row| row.get(0))?
+    };
+    let sql = format!(
+        "SELECT day, tickets AS sales_count, revenue AS total_revenue
+         FROM daily_sales_summary
+         WHERE day >= date('now', ?1) AND day < date('now') AND (?2 IS NULL OR day > ?2)
+         UNION ALL
+         SELECT {day} as day,
+                COUNT(*) as sales_count,
+                COALESCE(SUM(sale_price),0.0) as total_revenue
+         FROM {live}
+         WHERE sale_date >= date('now')
+         GROUP BY day
+         UNION ALL
+         SELECT substr(sale_date,1,10) as day,
+                COUNT(*) as sales_count,
+                COALESCE(SUM(sale_price),0.0) as total_revenue
+         FROM {live}
+         WHERE ?2 IS NOT NULL AND substr(sale_date,1,10) BETWEEN date('now', ?1) AND ?2
+         GROUP BY day
+         ORDER BY day ASC",
+        day = summary_day_sql("sale_date"),
+        live = valid_sales(include_archive)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(rusqlite::params![modifier, archived_until], |row| {
+            Ok(SalesTrendPoint {
+                date: row.get(0)?,
+                sales_count: row.get(1)?,
+                total_revenue: row.get(2)?,
+            })
+        })?
+        .collect();
+    rows
+}
+#[derive(Debug, Serialize, Deserialize)]
+struct StockBalance {
+    product_id: i32,
+    current_stock: f64,
+}
+
+#[tauri::command]
+fn get_stock_balances(state: State<AppState>) -> Result<Vec<StockBalance>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT product_id, COALESCE(SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END),0) as balance FROM stock_movements GROUP BY product_id")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(StockBalance {
+                product_id: row.get(0)?,
+                current_stock: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductStats {
+    /// Productos del catálogo (sin los eliminados)
+    total: i64,
+    active: i64,
+    /// En cualquier otro estado (descontinuado, agotado por proveedor…)
+    inactive: i64,
+    without_sku: i64,
+    without_image: i64,
+    /// Sin costo o con costo 0: no se puede calcular su margen
+    without_cost: i64,
+    /// Stock × costo; el stock negativo no resta
+    catalog_value: f64,
+    /// Los mismos que devuelve `get_low_stock_products` sin filtros
+    below_min_stock: i64,
+}
+
+/// Cifras del encabezado de productos y del aviso de stock bajo en una sola
+/// consulta.
+#[tauri::command]
+fn get_product_stats(state: State<AppState>) -> Result<ProductStats, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(status = ?1), 0),
+                    COALESCE(SUM(TRIM(COALESCE(sku, '')) = ''), 0),
+                    COALESCE(SUM(TRIM(COALESCE(image_path, '')) = ''), 0),
+                    COALESCE(SUM(COALESCE(cost_price, 0) <= 0), 0),
+                    COALESCE(SUM(MAX(current_stock, 0) * COALESCE(cost_price, 0)), 0.0),
+                    COALESCE(SUM(status = ?1 AND min_stock IS NOT NULL AND NOT is_bundle AND current_stock <= min_stock), 0)
+             FROM (
+                SELECT p.sku, p.image_path, p.cost_price, p.min_stock, COALESCE(p.status, ?1) AS status,
+                       EXISTS(SELECT 1 FROM product_bundles b WHERE b.bundle_id = p.id) AS is_bundle,
+                       {stock} AS current_stock
+                FROM products p
+                WHERE p.deleted_at IS NULL
+             )",
+            stock = stock_sql("p.id")
+        ),
+        [PRODUCT_STATUS_ACTIVE],
+        |row| {
+            let total: i64 = row.get(0)?;
+            let active: i64 = row.get(1)?;
+            Ok(ProductStats {
+                total,
+                active,
+                inactive: total - active,
+                without_sku: row.get(2)?,
+                without_image: row.get(3)?,
+                without_cost: row.get(4)?,
+                catalog_value: round_money(row.get(5)?),
+                below_min_stock: row.get(6)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LowStockProduct {
+    product_id: i32,
+    sku: Option<String>,
+    name: String,
+    current_stock: f64,
+    min_stock: i32,
+    /// Unidades que faltan para volver al mínimo (0 si está justo en él)
+    shortfall: f64,
+    /// Proveedor habitual, para repartir el pedido de reposición
+    default_supplier: Option<String>,
+}
+
+/// Productos cuyo stock llegó a su mínimo, de mayor a menor faltante. Los que
+/// no tienen `min_stock` no se consideran, ni los packs (su stock sale de los
+/// componentes). `only_active` (por defecto sí) deja fuera los productos que
+/// no están en estado `activo`.
+#[tauri::command]
+fn get_low_stock_products(
+    state: State<AppState>,
+    category: Option<String>,
+    only_active: Option<bool>,
+) -> Result<Vec<LowStockProduct>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let category = category.filter(|c| !c.trim().is_empty());
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, sku, name, current_stock, min_stock, min_stock - current_stock AS shortfall, default_supplier
+             FROM (
+                SELECT p.id, p.sku, p.name, p.min_stock, p.default_supplier, {stock} AS current_stock
+                FROM products p
+                WHERE p.deleted_at IS NULL AND p.min_stock IS NOT NULL
+                  AND NOT EXISTS(SELECT 1 FROM product_bundles b WHERE b.bundle_id = p.id)
+                  AND (?1 IS NULL OR p.category = TRIM(?1) COLLATE NOCASE)
+                  AND (?2 = 0 OR p.status = ?3)
+             )
+             WHERE current_stock <= min_stock
+             ORDER BY shortfall DESC, name COLLATE NOCASE, id",
+            stock = stock_sql("p.id")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params![category, only_active.unwrap_or(true), PRODUCT_STATUS_ACTIVE],
+            |row| {
+                Ok(LowStockProduct {
+                    product_id: row.get(0)?,
+                    sku: row.get(1)?,
+                    name: row.get(2)?,
+                    current_stock: row.get(3)?,
+                    min_stock: row.get(4)?,
+                    shortfall: row.get(5)?,
+                    default_supplier: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OverstockProduct {
+    product_id: i32,
+    sku: Option<String>,
+    name: String,
+    current_stock: f64,
+    max_stock: i32,
+    /// Unidades por encima del máximo
+    excess: f64,
+    cost_price: Option<f64>,
+    /// `excess` valorizado al costo: dinero inmovilizado en el estante
+    excess_value: f64,
+}
+
+/// Productos con stock por encima de su `max_stock`, con el mismo filtro que
+/// `get_low_stock_products`. Sin máximo (NULL o 0) no se consideran. Ordena
+/// por el valor del excedente, lo que más dinero inmoviliza primero.
+fn overstock_products(
+    conn: &Connection,
+    category: Option<String>,
+    only_active: Option<bool>,
+) -> Result<Vec<OverstockProduct>, String> {
+    let category = category.filter(|c| !c.trim().is_empty());
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, sku, name, current_stock, max_stock, current_stock - max_stock AS excess, cost_price,
+                    (current_stock - max_stock) * COALESCE(cost_price, 0) AS excess_value
+             FROM (
+                SELECT p.id, p.sku, p.name, p.max_stock, p.cost_price, {stock} AS current_stock
+                FROM products p
+                WHERE p.deleted_at IS NULL AND p.max_stock > 0
+                  AND NOT EXISTS(SELECT 1 FROM product_bundles b WHERE b.bundle_id = p.id)
+                  AND (?1 IS NULL OR p.category = TRIM(?1) COLLATE NOCASE)
+                  AND (?2 = 0 OR p.status = ?3)
+             )
+             WHERE current_stock > max_stock
+             ORDER BY excess_value DESC, excess DESC, name COLLATE NOCASE, id",
+            stock = stock_sql("p.id")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params![category, only_active.unwrap_or(true), PRODUCT_STATUS_ACTIVE],
+            |row| {
+                Ok(OverstockProduct {
+                    product_id: row.get(0)?,
+                    sku: row.get(1)?,
+                    name: row.get(2)?,
+                    current_stock: row.get(3)?,
+                    max_stock: row.get(4)?,
+                    excess: row.get(5)?,
+                    cost_price: row.get(6)?,
+                    excess_value: round_money(row.get(7)?),
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+#[tauri::command]
+fn get_overstock_products(
+    state: State<AppState>,
+    category: Option<String>,
+    only_active: Option<bool>,
+) -> Result<Vec<OverstockProduct>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    overstock_products(&conn, category, only_active)
+}
+
+/// `get_overstock_products` en CSV, en la carpeta de exportación.
+#[tauri::command]
+fn export_overstock_report(
+    state: State<AppState>,
+    category: Option<String>,
+    only_active: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "overstock_report",
+            &[
+                category.as_deref(),
+                only_active.map(|a| if a { "activos" } else { "todos" }),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rows = overstock_products(&conn, category, only_active)?;
+    let mut csv = String::from("product_id,sku,name,current_stock,max_stock,excess,cost_price,excess_value\n");
+    for row in &rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            row.product_id,
+            csv_field(&row.sku.clone().unwrap_or_default()),
+            csv_field(&row.name),
+            row.current_stock,
+            row.max_stock,
+            row.excess,
+            money(row.cost_price),
+            format_amount(row.excess_value)
+        ));
+    }
+    write_export("overstock_report", "csv", csv.as_bytes())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExpiringProduct {
+    product_id: i32,
+    sku: Option<String>,
+    name: String,
+    /// Tal como está guardada; en `invalid_dates` puede no ser una fecha
+    expiry_date: String,
+    /// Días hasta el vencimiento (negativo si ya venció); `None` si la fecha es inválida
+    days_left: Option<i64>,
+    current_stock: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExpiryReport {
+    days: i32,
+    /// Ya vencidos, del más antiguo al más reciente
+    expired: Vec<ExpiringProduct>,
+    /// Vencen entre hoy y `days` días, el más próximo primero
+    expiring: Vec<ExpiringProduct>,
+    /// Con `expiry_date` que no es AAAA-MM-DD válida; hay que corregirlos a mano
+    invalid_dates: Vec<ExpiringProduct>,
+}
+
+/// Días de anticipación de `get_expiring_products` si no se indica.
+const DEFAULT_EXPIRY_HORIZON_DAYS: i32 = 30;
+
+fn expiry_report(conn: &Connection, days: Option<i32>) -> Result<ExpiryReport, String> {
+    let days = days.unwrap_or(DEFAULT_EXPIRY_HORIZON_DAYS).max(0);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, sku, name, TRIM(expiry_date),
+                    CASE WHEN date(TRIM(expiry_date)) = TRIM(expiry_date)
+                         THEN CAST(julianday(TRIM(expiry_date)) - julianday(date('now')) AS INTEGER) END AS days_left,
+                    {stock}
+             FROM products p
+             WHERE deleted_at IS NULL AND TRIM(COALESCE(expiry_date, '')) <> ''
+             ORDER BY days_left IS NULL, days_left, name COLLATE NOCASE, id",
+            stock = stock_sql("p.id")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ExpiringProduct {
+                product_id: row.get(0)?,
+                sku: row.get(1)?,
+                name: row.get(2)?,
+                expiry_date: row.get(3)?,
+                days_left: row.get(4)?,
+                current_stock: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut report = ExpiryReport { days, expired: Vec::new(), expiring: Vec::new(), invalid_dates: Vec::new() };
+    for row in rows {
+        match row.days_left {
+            None => report.invalid_dates.push(row),
+            Some(left) if left < 0 => report.expired.push(row),
+            Some(left) if left <= days as i64 => report.expiring.push(row),
+            Some(_) => {}
+        }
+    }
+    Ok(report)
+}
+
+/// Productos que vencen en los próximos `days` días (30 por defecto) con su
+/// stock, los ya vencidos aparte y los que tienen una fecha de vencimiento
+/// que no se puede leer.
+#[tauri::command]
+fn get_expiring_products(state: State<AppState>, days: Option<i32>) -> Result<ExpiryReport, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    expiry_report(&conn, days)
+}
+
+/// `get_expiring_products` en CSV; `status` es vencido, por_vencer o
+/// fecha_invalida.
+#[tauri::command]
+fn export_expiry_report(state: State<AppState>, days: Option<i32>) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key("expiry_report", &[days.map(|d| d.to_string()).as_deref()]),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let report = expiry_report(&conn, days)?;
+    let mut csv = String::from("status,product_id,sku,name,expiry_date,days_left,current_stock\n");
+    for (status, rows) in [
+        ("vencido", &report.expired),
+        ("por_vencer", &report.expiring),
+        ("fecha_invalida", &report.invalid_dates),
+    ] {
+        for row in rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                status,
+                row.product_id,
+                csv_field(&row.sku.clone().unwrap_or_default()),
+                csv_field(&row.name),
+                csv_field(&row.expiry_date),
+                row.days_left.map(|d| d.to_string()).unwrap_or_default(),
+                row.current_stock
+            ));
+        }
+    }
+    write_export("expiry_report", "csv", csv.as_bytes())
+}
+
+/// Escapa un valor de texto para CSV: lo encierra entre comillas si contiene
+/// comas, comillas o saltos de línea.
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') || value.contains('\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Margen de espacio libre que se deja además del tamaño del archivo.
+const DISK_SPACE_MARGIN: u64 = 1024 * 1024;
+
+/// Carpeta de exportación: `Descargas/VitaSport`, o la indicada en
+/// `VITASPORT_EXPORT_DIR` (la usan las pruebas de integración).
+fn export_dir() -> Result<PathBuf, String> {
+    let out_dir = match std::env::var_os("VITASPORT_EXPORT_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => download_dir()
+            .ok_or("No se pudo obtener carpeta Descargas")?
+            .join("VitaSport"),
+    };
+    fs::create_dir_all(&out_dir).map_err(|e| io_error(&out_dir, e))?;
+    Ok(out_dir)
+}
+
+/// Mensaje de un error de E/S que incluye la ruta afectada, para que quien
+/// lo lea sepa qué archivo o carpeta falló.
+fn io_error(path: &Path, e: std::io::Error) -> String {
+    format!("{}: {}", path.display(), e)
+}
+
+/// Convierte una ruta en texto solo para devolverla al frontend. Las rutas
+/// se manejan como `PathBuf` hasta aquí; si no es Unicode válido se informa
+/// en vez de devolver una versión con caracteres reemplazados que no existe.
+fn path_to_string(path: &Path) -> Result<String, String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("La ruta {} contiene caracteres no válidos", path.display()))
+}
+
+fn insufficient_space(dir: &Path, required: u64, available: u64) -> String {
+    format!(
+        "Espacio insuficiente en disco en {}: se necesitan {} bytes y hay {} disponibles",
+        dir.display(),
+        required,
+        available
+    )
+}
+
+/// Verifica que `dir` tenga al menos `required` bytes libres (más un margen).
+fn ensure_free_space(dir: &Path, required: u64) -> Result<(), String> {
+    let available = fs2::available_space(dir).map_err(|e| io_error(dir, e))?;
+    if available < required.saturating_add(DISK_SPACE_MARGIN) {
+        return Err(insufficient_space(dir, required, available));
+    }
+    Ok(())
+}
+
+/// Escribe un archivo mediante un temporal en la misma carpeta y lo renombra
+/// al terminar, para que nunca quede un archivo truncado con el nombre final.
+/// `write` recibe la ruta del temporal.
+fn write_atomically<F>(path: &Path, required: u64, write: F) -> Result<(), String>
+where
+    F: FnOnce(&Path) -> std::io::Result<()>,
+{
+    let dir = path.parent().ok_or("Ruta de destino inválida")?;
+    ensure_free_space(dir, required)?;
+
+    // El nombre del temporal se arma sobre el `OsStr` original, sin pasar
+    // por texto, para que el rename apunte exactamente al mismo archivo
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().ok_or("Ruta de destino inválida")?);
+    tmp_name.push(".tmp");
+    let tmp_path = dir.join(tmp_name);
+    let result = write(&tmp_path).and_then(|_| fs::rename(&tmp_path, path));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        if e.kind() == std::io::ErrorKind::StorageFull {
+            let available = fs2::available_space(dir).unwrap_or(0);
+            return Err(insufficient_space(dir, required, available));
+        }
+        return Err(io_error(path, e));
+    }
+    Ok(())
+}
+
+/// Ruta libre `<dir>/<prefix>_<timestamp>[.<extension>]`, con un sufijo
+/// aleatorio si ese nombre ya existe: dos exportaciones en el mismo segundo
+/// no deben pisarse.
+fn unique_export_path(dir: &Path, prefix: &str, extension: Option<&str>) -> Result<PathBuf, String> {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let name = |suffix: Option<u16>| {
+        let mut name = format!("{}_{}", prefix, ts);
+        if let Some(suffix) = suffix {
+            name.push_str(&format!("_{:04x}", suffix));
+        }
+        if let Some(extension) = extension {
+            name.push('.');
+            name.push_str(extension);
+        }
+        name
+    };
+    let mut path = dir.join(name(None));
+    while path.exists() {
+        path = dir.join(name(Some(rand::random())));
+    }
+    Ok(path)
+}
+
+fn write_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    write_atomically(path, content.len() as u64, |tmp| {
+        let mut file = fs::File::create(tmp)?;
+        file.write_all(content)?;
+        file.sync_all()
+    })
+}
+
+/// Guarda un reporte en la carpeta de exportación como
+/// `<prefix>_<timestamp>.<extension>` (con un sufijo aleatorio si ese nombre
+/// ya existe) y devuelve su ruta.
+fn write_export(prefix: &str, extension: &str, content: &[u8]) -> Result<String, String> {
+    let path = unique_export_path(&export_dir()?, prefix, Some(extension))?;
+    write_file(&path, content)?;
+    path_to_string(&path)
+}
+
+/// Fila de `export_sales_report`: id, producto, cantidad, precio, descuento,
+/// canal, fecha, usuario, depósito, cliente, número de comprobante, medio de
+/// pago, id del cliente registrado y nota.
+type SalesReportRow = (
+    i32,
+    i32,
+    f64,
+    f64,
+    Option<f64>,
+    Option<String>,
+    String,
+    Option<i32>,
+    Option<f64>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+);
+
+const SALES_REPORT_HEADER: &str =
+    "id,product_id,quantity,sale_price,discount,channel,sale_date,created_by,deposit_amount,customer,receipt_number,payment_method,customer_id,note\n";
+
+/// Máximo de meses que `export_sales_report` divide en archivos.
+const SALES_REPORT_MAX_MONTHS: usize = 120;
+
+fn sales_report_line(row: SalesReportRow) -> String {
+    let (id, pid, qty, price, disc, channel, date, created_by, deposit, customer, receipt_number, payment_method, customer_id, note) = row;
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+        id,
+        pid,
+        qty,
+        format_amount(price),
+        disc.map(|d| d.to_string()).unwrap_or_default(),
+        csv_field(&channel.unwrap_or_default()),
+        date,
+        created_by.map(|c| c.to_string()).unwrap_or_default(),
+        money(deposit),
+        csv_field(&customer.unwrap_or_default()),
+        receipt_number.unwrap_or_default(),
+        payment_method.unwrap_or_else(|| CASH_PAYMENT_METHOD.to_string()),
+        customer_id.map(|c| c.to_string()).unwrap_or_default(),
+        csv_field(&note.unwrap_or_default())
+    )
+}
+
+/// Ventas que escribe `export_sales_report`, de la más reciente a la más
+/// antigua. El rango solo se aplica si vienen las dos fechas.
+fn sales_report_rows(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    include_archive: bool,
+) -> Result<Vec<SalesReportRow>> {
+    let range = start_date.zip(end_date);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, product_id, quantity, sale_price, discount, channel, sale_date, created_by, deposit_amount, customer,
+                (SELECT h.receipt_number FROM sale_items i JOIN sale_headers h ON h.id = i.sale_id WHERE i.id = s.id),
+                payment_method, customer_id, note
+         FROM {} s
+         WHERE ?1 IS NULL OR substr(sale_date,1,10) BETWEEN ?1 AND ?2
+         ORDER BY sale_date DESC",
+        valid_sales(include_archive)
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params![range.map(|r| r.0), range.map(|r| r.1)], |row| {
+            Ok((
+                row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?, row.get(8)?, row.get(9)?,
+                row.get(10)?, row.get(11)?, row.get(12)?, row.get(13)?,
+            ))
+        })?
+        .collect();
+    rows
+}
+
+/// Contenido de `export_sales_report` en un solo archivo (también va en
+/// `export_all_reports`).
+fn sales_report_csv(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    include_archive: bool,
+) -> Result<String, String> {
+    let rows = sales_report_rows(conn, start_date, end_date, include_archive).map_err(|e| e.to_string())?;
+    let mut csv = String::from(SALES_REPORT_HEADER);
+    for row in rows {
+        csv.push_str(&sales_report_line(row));
+    }
+    Ok(csv)
+}
+
+/// Archivo mensual de `export_sales_report` con `split_by = "month"`.
+#[derive(Debug, Serialize)]
+struct MonthlyExport {
+    month: String,
+    path: String,
+    rows: usize,
+}
+
+/// Resultado de `export_sales_report`: la ruta del archivo, como siempre, o
+/// la lista de archivos mensuales si se pidió dividir por mes.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum SalesReportOutput {
+    File(String),
+    Months(Vec<MonthlyExport>),
+}
+
+/// Con `split_by = "month"` escribe un CSV por mes calendario del rango (o de
+/// los meses con ventas, si no hay rango) en una subcarpeta propia; los meses
+/// sin ventas quedan con solo el encabezado para que la serie esté completa.
+#[tauri::command]
+fn export_sales_report(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+    split_by: Option<String>,
+) -> Result<SalesReportOutput, String> {
+    let by_month = match split_by.as_deref().map(str::trim).unwrap_or("none") {
+        "none" | "" => false,
+        "month" => true,
+        other => return Err(format!("split_by inválido: '{}'. Usa month o none", other)),
+    };
+    if by_month {
+        for date in [&start_date, &end_date].into_iter().flatten() {
+            if !is_valid_date(date) {
+                return Err("Rango de fechas inválido. Usa el formato AAAA-MM-DD.".to_string());
+            }
+        }
+    }
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "sales_report",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+                by_month.then_some("month"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if !by_month {
+        let csv = sales_report_csv(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))?;
+        return write_export("sales_report", "csv", csv.as_bytes()).map(SalesReportOutput::File);
+    }
+    let rows = sales_report_rows(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    // Meses del rango pedido (o de las ventas encontradas), en orden
+    let month_of = |date: &str| date.get(..7).unwrap_or(date).to_string();
+    let range = match (&start_date, &end_date) {
+        (Some(start), Some(end)) => Some((month_of(start), month_of(end))),
+        _ => {
+            let found: Vec<String> = rows.iter().map(|r| month_of(&r.6)).collect();
+            found.iter().min().cloned().zip(found.iter().max().cloned())
+        }
+    };
+    let mut months = Vec::new();
+    if let Some((first, last)) = range {
+        let mut month = first;
+        while month <= last {
+            if months.len() == SALES_REPORT_MAX_MONTHS {
+                return Err(format!(
+                    "El rango abarca más de {} meses; acórtalo para dividirlo por mes",
+                    SALES_REPORT_MAX_MONTHS
+                ));
+            }
+            let next = add_months(&month, 1)?;
+            months.push(month);
+            month = next;
+        }
+    }
+
+    let mut by_month_rows: HashMap<String, Vec<SalesReportRow>> = HashMap::new();
+    for row in rows {
+        by_month_rows.entry(month_of(&row.6)).or_default().push(row);
+    }
+    let dir = unique_export_path(&export_dir()?, "sales_report_por_mes", None)?;
+    fs::create_dir(&dir).map_err(|e| io_error(&dir, e))?;
+    let mut files = Vec::new();
+    for month in months {
+        let month_rows = by_month_rows.remove(&month).unwrap_or_default();
+        let count = month_rows.len();
+        let mut csv = String::from(SALES_REPORT_HEADER);
+        for row in month_rows {
+            csv.push_str(&sales_report_line(row));
+        }
+        let path = dir.join(format!("sales_report_{}.csv", month));
+        write_file(&path, csv.as_bytes())?;
+        files.push(MonthlyExport { month, path: path_to_string(&path)?, rows: count });
+    }
+    Ok(SalesReportOutput::Months(files))
+}
+
+#[tauri::command]
+fn export_inventory_report(state: State<AppState>) -> Result<String, String> {
+    let _job = lock_export(&state, "inventory_report")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let csv = inventory_report_csv(&conn)?;
+    write_export("inventory_report", "csv", csv.as_bytes())
+}
+
+/// Contenido de `export_inventory_report` (también va dentro del ZIP del
+/// catálogo).
+fn inventory_report_csv(conn: &Connection) -> Result<String, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, sku, name, sale_price, cost_price, brand, category, presentation, flavor, weight, expiry_date, lot_number, min_stock, max_stock, location, status, unit, purchase_unit, conversion_factor, description, {} FROM products p WHERE deleted_at IS NULL",
+            local_cost_sql("p", "date('now','localtime')")
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("id,sku,name,sale_price,cost_price,brand,category,presentation,flavor,weight,expiry_date,lot_number,min_stock,max_stock,location,status,current_stock,unit,purchase_unit,purchase_unit_stock,margin_percent,allowed_channels,tags,stock_config_issue,description\n");
+    let channels = all_product_channels(conn)?;
+    let tags = all_product_tags(conn)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,                // id
+                row.get::<_, Option<String>>(1)?,     // sku
+                row.get::<_, String>(2)?,             // name
+                row.get::<_, Option<f64>>(3)?,        // sale_price
+                row.get::<_, Option<f64>>(4)?,        // cost_price
+                row.get::<_, Option<String>>(5)?,     // brand
+                row.get::<_, Option<String>>(6)?,     // category
+                row.get::<_, Option<String>>(7)?,     // presentation
+                row.get::<_, Option<String>>(8)?,     // flavor
+                row.get::<_, Option<String>>(9)?,     // weight
+                row.get::<_, Option<String>>(10)?,    // expiry_date
+                row.get::<_, Option<String>>(11)?,    // lot_number (TEXT)
+                row.get::<_, Option<i32>>(12)?,       // min_stock (INTEGER)
+                row.get::<_, Option<i32>>(13)?,       // max_stock (INTEGER)
+                row.get::<_, Option<String>>(14)?,    // location
+                row.get::<_, Option<String>>(15)?,    // status
+                row.get::<_, Option<String>>(16)?,    // unit (base)
+                row.get::<_, Option<String>>(17)?,    // purchase_unit
+                row.get::<_, Option<f64>>(18)?,       // conversion_factor
+                row.get::<_, Option<String>>(19)?,    // description
+                row.get::<_, Option<f64>>(20)?,       // costo en moneda local
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    for r in rows {
+        let (id, sku, name, sale_price, cost_price, brand, category, presentation, flavor, weight, expiry_date, lot_number, min_stock, max_stock, location, status, unit, purchase_unit, conversion_factor, description, local_cost) = r.map_err(|e| e.to_string())?;
+
+        let ingreso: f64 = conn.query_row(
+            "SELECT COALESCE(SUM(quantity),0) FROM stock_movements WHERE product_id=?1 AND type='ingreso'",
+            rusqlite::params![id],
+            |row| row.get(0),
+        ).unwrap_or(0.0);
+        let egreso: f64 = conn.query_row(
+            "SELECT COALESCE(SUM(quantity),0) FROM stock_movements WHERE product_id=?1 AND type='egreso'",
+            rusqlite::params![id],
+            |row| row.get(0),
+        ).unwrap_or(0.0);
+        let current_stock = ingreso - egreso;
+        // El mismo stock en cajas (o la unidad de compra que tenga)
+        let purchase_unit_stock = match (&purchase_unit, conversion_factor) {
+            (Some(_), Some(factor)) if factor > 0.0 => Some((current_stock / factor * 100.0).round() / 100.0),
+            _ => None,
+        };
+
+        // El margen se calcula con el costo convertido; sin tipo de cambio queda en blanco
+        let margin_percent: Option<f64> = match (sale_price, local_cost) {
+            (Some(sale), Some(cost)) if sale > 0.0 && cost > 0.0 => {
+                let diff = sale - cost;
+                Some(((diff / sale) * 100.0).round())
+            }
+            _ => None,
+        };
+
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            id,
+            csv_field(&sku.unwrap_or_default()),
+            csv_field(&name),
+            money(sale_price),
+            money(cost_price),
+            csv_field(&brand.unwrap_or_default()),
+            csv_field(&category.unwrap_or_default()),
+            csv_field(&presentation.unwrap_or_default()),
+            csv_field(&flavor.unwrap_or_default()),
+            csv_field(&weight.unwrap_or_default()),
+            expiry_date.unwrap_or_default(),
+            csv_field(&lot_number.unwrap_or_default()),
+            min_stock.map(|v| v.to_string()).unwrap_or_default(),
+            max_stock.map(|v| v.to_string()).unwrap_or_default(),
+            csv_field(&location.unwrap_or_default()),
+            csv_field(&status.unwrap_or_default()),
+            current_stock,
+            unit.unwrap_or_else(|| "unidad".to_string()),
+            csv_field(&purchase_unit.unwrap_or_default()),
+            purchase_unit_stock.map(|v| v.to_string()).unwrap_or_default(),
+            margin_percent.map(|v| format!("{:.0}", v)).unwrap_or_default(),
+            channels.get(&id).map(|c| c.join("|")).unwrap_or_default(),
+            csv_field(&tags.get(&id).map(|t| t.join(";")).unwrap_or_default()),
+            stock_levels_issue(min_stock, max_stock).unwrap_or_default(),
+            // Puede traer comas, comillas y saltos de línea
+            csv_field(&description.unwrap_or_default()),
+        ));
+    }
+
+    Ok(csv)
+}
+
+/// Imagen de un producto para el ZIP del catálogo.
+struct CatalogImage {
+    source: PathBuf,
+    entry: String,
+}
+
+/// Inventario (`inventory.csv`) y las imágenes de los productos
+/// (`images/{sku}.{ext}`) en un ZIP para publicar el catálogo en la web. Las
+/// imágenes se copian al ZIP por partes, sin cargarlas enteras en memoria; las
+/// que no se encuentran se listan en `manifest.txt` en vez de cancelar la
+/// exportación.
+#[tauri::command]
+fn export_catalog_zip(state: State<AppState>) -> Result<String, String> {
+    let _job = lock_export(&state, "catalog_zip")?;
+    let (csv, products) = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let csv = inventory_report_csv(&conn)?;
+        let mut stmt = conn
+            .prepare("SELECT id, sku, image_path FROM products WHERE deleted_at IS NULL AND image_path IS NOT NULL AND image_path <> '' ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let products = stmt
+            .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?)))
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+        (csv, products)
+    };
+
+    let mut images = Vec::new();
+    let mut missing = Vec::new();
+    let mut used_names = std::collections::HashSet::new();
+    let mut required = csv.len() as u64;
+    for (id, sku, stored) in products {
+        let source = resolve_image_path(&stored);
+        let Ok(metadata) = fs::metadata(&source) else {
+            missing.push(format!("{}\t{}\t{}", id, sku.unwrap_or_default(), stored));
+            continue;
+        };
+        // Sin SKU (o si dos se limpian igual) se usa el id para no repetir nombres
+        let stem = sku.as_deref().map(file_safe).filter(|s| !s.is_empty()).unwrap_or_else(|| format!("producto_{}", id));
+        let stem = if used_names.insert(stem.clone()) { stem } else { format!("{}_{}", stem, id) };
+        let extension = source.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_ascii_lowercase();
+        required += metadata.len();
+        images.push(CatalogImage { source, entry: format!("images/{}.{}", stem, extension) });
+    }
+
+    let mut manifest = format!("Imágenes incluidas: {}\nImágenes no encontradas: {}\n", images.len(), missing.len());
+    if !missing.is_empty() {
+        manifest.push_str("\nproduct_id\tsku\timage_path\n");
+        for line in &missing {
+            manifest.push_str(line);
+            manifest.push('\n');
+        }
+    }
+
+    let path = unique_export_path(&export_dir()?, "catalog", Some("zip"))?;
+    write_atomically(&path, required, |tmp| {
+        let file = std::io::BufWriter::new(fs::File::create(tmp)?);
+        let mut zip = zip::ZipWriter::new(file);
+        let deflated = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        // JPEG, PNG y WebP ya vienen comprimidos
+        let stored = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("inventory.csv", deflated)?;
+        zip.write_all(csv.as_bytes())?;
+        zip.start_file("manifest.txt", deflated)?;
+        zip.write_all(manifest.as_bytes())?;
+        for image in &images {
+            zip.start_file(image.entry.as_str(), stored)?;
+            let mut source = fs::File::open(&image.source)?;
+            std::io::copy(&mut source, &mut zip)?;
+        }
+        zip.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()
+    })?;
+    path_to_string(&path)
+}
+
+#[tauri::command]
+fn export_top_products_report(
+    state: State<AppState>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "top_products_report",
+            &[include_archive.unwrap_or(false).then_some("archivo")],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let csv = top_products_csv(&conn, include_archive.unwrap_or(false))?;
+    write_export("top_products_report", "csv", csv.as_bytes())
+}
+
+/// Contenido de `export_top_products_report` (también va en `export_all_reports`).
+fn top_products_csv(conn: &Connection, include_archive: bool) -> Result<String, String> {
+    let sql = format!(
+        "SELECT s.product_id,
+                COALESCE(p.sku, '') as sku,
+                COALESCE(p.name, '') as name,
+                COALESCE(p.category, '') as category,
+                COALESCE(SUM(s.quantity), 0) as total_qty,
+                COALESCE(SUM(s.sale_price), 0.0) as total_revenue
+         FROM {} s
+         LEFT JOIN products p ON p.id = s.product_id
+         GROUP BY s.product_id, sku, name, category
+         ORDER BY total_revenue DESC
+         LIMIT 50",
+        valid_sales(include_archive)
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("product_id,sku,name,category,total_qty,total_revenue\n");
+    for r in rows {
+        let (pid, sku, name, category, qty, revenue) = r.map_err(|e| e.to_string())?;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            pid,
+            csv_field(&sku),
+            csv_field(&name),
+            csv_field(&category),
+            qty,
+            format_amount(revenue),
+        ));
+    }
+
+    Ok(csv)
+}
+
+/// `get_sales_by_channel` en CSV.
+#[tauri::command]
+fn export_sales_by_channel_report(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    category: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "sales_by_channel_report",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                category.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rows = sales_by_channel(&conn, start_date.as_deref(), end_date.as_deref(), category.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("channel,sales_count,total_units,total_revenue,share_percent\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_field(&row.channel),
+            row.sales_count,
+            row.total_units,
+            format_amount(row.total_revenue),
+            format_amount(row.share_percent),
+        ));
+    }
+
+    write_export("sales_by_channel_report", "csv", csv.as_bytes())
+}
+
+/// `get_sales_by_user` en CSV.
+#[tauri::command]
+fn export_sales_by_user_report(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "sales_by_user_report",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rows = sales_by_user(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("user_id,username,fullname,sales_count,total_units,total_revenue\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            row.user_id.map(|id| id.to_string()).unwrap_or_default(),
+            csv_field(&row.username),
+            csv_field(&row.fullname.unwrap_or_default()),
+            row.sales_count,
+            row.total_units,
+            format_amount(row.total_revenue),
+        ));
+    }
+
+    write_export("sales_by_user_report", "csv", csv.as_bytes())
+}
+
+/// `get_tax_summary` en CSV, un mes por fila.
+#[tauri::command]
+fn export_tax_report(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "tax_report",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rows = tax_summary(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("month,sales_count,taxable_base,tax_collected,exempt_sales,total\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            row.month,
+            row.sales_count,
+            format_amount(row.taxable_base),
+            format_amount(row.tax_collected),
+            format_amount(row.exempt_sales),
+            format_amount(row.total),
+        ));
+    }
+
+    write_export("tax_report", "csv", csv.as_bytes())
+}
+
+#[tauri::command]
+fn export_stock_movements_report(
+    state: State<AppState>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "stock_movements_report",
+            &[include_archive.unwrap_or(false).then_some("archivo")],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let csv = stock_movements_csv(&conn, include_archive.unwrap_or(false))?;
+    write_export("stock_movements_report", "csv", csv.as_bytes())
+}
+
+/// Contenido de `export_stock_movements_report` (también va en `export_all_reports`).
+fn stock_movements_csv(conn: &Connection, include_archive: bool) -> Result<String, String> {
+    let sql = format!(
+        "SELECT id, product_id, type, quantity, note, created_by, created_at
+         FROM {}
+         ORDER BY created_at DESC, id DESC",
+        source_table("stock_movements", include_archive)
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<i32>>(5)?,
+                row.get::<_, String>(6)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("id,product_id,type,quantity,note,created_by,created_at\n");
+    for r in rows {
+        let (id, pid, movement_type, quantity, note, created_by, created_at) =
+            r.map_err(|e| e.to_string())?;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            id,
+            pid,
+            movement_type,
+            quantity,
+            csv_field(&note.unwrap_or_default()),
+            created_by.map(|v| v.to_string()).unwrap_or_default(),
+            created_at,
+        ));
+    }
+
+    Ok(csv)
+}
+
+#[tauri::command]
+fn export_profitability_report(
+    state: State<AppState>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "profitability_report",
+            &[include_archive.unwrap_or(false).then_some("archivo")],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let csv = profitability_csv(&conn, include_archive.unwrap_or(false))?;
+    write_export("profitability_report", "csv", csv.as_bytes())
+}
+
+/// Contenido de `export_profitability_report` (también va en `export_all_reports`).
+fn profitability_csv(conn: &Connection, include_archive: bool) -> Result<String, String> {
+    // Los costos en otra moneda se convierten con el tipo de cambio vigente
+    // en la fecha de cada venta; unit_cost, con el de hoy
+    let sql = format!(
+        "SELECT p.id,
+                COALESCE(p.sku, '') as sku,
+                p.name,
+                {unit_cost} as unit_cost,
+                COALESCE(SUM(s.quantity), 0) as total_qty,
+                COALESCE(SUM(s.sale_price), 0.0) as total_revenue,
+                COALESCE(SUM(s.quantity * {sale_cost}), 0.0) as estimated_total_cost,
+                p.currency,
+                p.cost_price IS NOT NULL AND p.currency IS NOT NULL AND {unit_cost} IS NULL as missing_rate,
+                COALESCE(SUM(s.id IS NOT NULL AND p.cost_price IS NOT NULL AND {sale_cost} IS NULL), 0) as sales_missing_rate
+         FROM products p
+         LEFT JOIN {sales} s ON s.product_id = p.id
+         GROUP BY p.id, sku, p.name, p.cost_price
+         ORDER BY total_revenue DESC",
+        unit_cost = local_cost_sql("p", "date('now','localtime')"),
+        sale_cost = local_cost_sql("p", "substr(s.sale_date,1,10)"),
+        sales = valid_sales(include_archive)
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, f64>(5)?,
+                row.get::<_, f64>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, bool>(8)?,
+                row.get::<_, i64>(9)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let local = local_currency(conn);
+    let mut csv = String::from("product_id,sku,name,unit_cost,total_qty_sold,total_revenue,estimated_total_cost,gross_profit,margin_percent,cost_currency,warning\n");
+    for r in rows {
+        let (pid, sku, name, unit_cost_opt, total_qty, total_revenue, estimated_total_cost, currency, missing_rate, sales_missing_rate) =
+            r.map_err(|e| e.to_string())?;
+        // Sin tipo de cambio el costo queda en blanco con el aviso, en vez de
+        // tomar los dólares como soles
+        let warning = match (&currency, sales_missing_rate) {
+            (Some(currency), n) if n > 0 => format!(
+                "Sin tipo de cambio {} para {} {}",
+                currency,
+                n,
+                if n == 1 { "venta" } else { "ventas" }
+            ),
+            (Some(currency), _) if missing_rate => format!("Sin tipo de cambio {} vigente", currency),
+            _ => String::new(),
+        };
+        let unit_cost = if missing_rate { String::new() } else { format_amount(unit_cost_opt.unwrap_or(0.0)) };
+        let (estimated_total_cost, gross_profit, margin_percent) = if sales_missing_rate > 0 {
+            (String::new(), String::new(), String::new())
+        } else {
+            let gross_profit = total_revenue - estimated_total_cost;
+            let margin_percent: Option<f64> = if total_revenue > 0.0 {
+                Some(((gross_profit / total_revenue) * 100.0).round())
+            } else {
+                None
+            };
+            (
+                format_amount(estimated_total_cost),
+                format_amount(gross_profit),
+                margin_percent.map(|v| format!("{:.0}", v)).unwrap_or_default(),
+            )
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            pid,
+            csv_field(&sku),
+            csv_field(&name),
+            unit_cost,
+            total_qty,
+            format_amount(total_revenue),
+            estimated_total_cost,
+            gross_profit,
+            margin_percent,
+            currency.unwrap_or_else(|| local.clone()),
+            warning,
+        ));
+    }
+
+    Ok(csv)
+}
+
+#[tauri::command]
+fn export_financial_report(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "financial_report",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let csv = financial_report_csv(&conn, start_date, end_date, include_archive.unwrap_or(false))?;
+    write_export("financial_report", "csv", csv.as_bytes())
+}
+
+/// Contenido de `export_financial_report` (también va en `export_all_reports`).
+fn financial_report_csv(
+    conn: &Connection,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    with_archive: bool,
+) -> Result<String, String> {
+    let sales = valid_sales(with_archive);
+    let cash = source_table("cash_movements", with_archive);
+    // Con el archivo incluido, los saldos iniciales generados al archivar
+    // duplicarían los movimientos originales, por eso se omiten.
+    let opening_filter = if with_archive { " AND COALESCE(category,'') <> 'archivo'" } else { "" };
+
+    // El rango solo aplica si vienen ambas fechas
+    let (start, end) = match (start_date, end_date) {
+        (Some(s), Some(e)) => (Some(s), Some(e)),
+        _ => (None, None),
+    };
+    let sum = |sql: String| -> f64 {
+        conn.query_row(&sql, rusqlite::params![start, end], |row| row.get(0))
+            .unwrap_or(0.0)
+    };
+    let in_range = |column: &str| {
+        format!(
+            "(?1 IS NULL OR substr({c},1,10) BETWEEN ?1 AND ?2)",
+            c = column
+        )
+    };
+
+    let sales_income = sum(format!(
+        "SELECT COALESCE(SUM(sale_price),0.0) FROM {} WHERE {}",
+        sales,
+        in_range("sale_date")
+    ));
+    // Impuesto incluido en las ventas: no cambia los ingresos, solo los desglosa
+    let sales_tax = sum(format!(
+        "SELECT COALESCE(SUM(tax_amount),0.0) FROM {} WHERE {}",
+        sales,
+        in_range("sale_date")
+    ));
+    // Cobradas con tarjeta, Yape o transferencia: son ingreso pero no pasan
+    // por el cajón
+    let non_cash_sales = sum(format!(
+        "SELECT COALESCE(SUM(sale_price),0.0) FROM {} WHERE COALESCE(is_credit,0) = 0 AND NOT ({}) AND {}",
+        sales,
+        CASH_SALE_SQL,
+        in_range("sale_date")
+    ));
+    // Las ventas al crédito entran al cajón a medida que se cobran
+    let credit_sales = sum(format!(
+        "SELECT COALESCE(SUM(sale_price),0.0) FROM {} WHERE is_credit = 1 AND {}",
+        sales,
+        in_range("sale_date")
+    ));
+    let credit_collected = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE payment_method = 'efectivo' AND {}",
+        source_table("sale_payments", with_archive),
+        in_range("payment_date")
+    ));
+    // Informativo: los ingresos por ventas ya vienen netos del descuento
+    let sales_discounts = sum(format!(
+        "SELECT COALESCE(SUM({}),0.0) FROM sale_items i JOIN {} s ON s.id = i.id WHERE {}",
+        LINE_DISCOUNT_SQL,
+        sales,
+        in_range("s.sale_date")
+    ));
+    let other_income = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE movement_type='ingreso' AND COALESCE(category,'') <> 'tarjeta_regalo' AND {}",
+        cash,
+        in_range("movement_date")
+    ));
+    let expense = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE movement_type='egreso' AND {}",
+        cash,
+        in_range("movement_date")
+    ));
+    let opening = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE movement_type='saldo_inicial' AND {}{}",
+        cash,
+        in_range("movement_date"),
+        opening_filter
+    ));
+    // Con rango, el saldo inicial arrastra además todo lo acumulado antes del inicio
+    let opening = match start.as_deref() {
+        Some(start) => opening + cash_balance(conn, with_archive, Some(start))?,
+        None => opening,
+    };
+    // La emisión de tarjetas de regalo es dinero recibido; su canje es una venta
+    // que no trae dinero nuevo, así que se resta para no contarla dos veces.
+    let credits_issued = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE movement_type='ingreso' AND category='tarjeta_regalo' AND {}",
+        cash,
+        in_range("movement_date")
+    ));
+    let credits_redeemed = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM store_credit_redemptions WHERE {}",
+        in_range("created_at")
+    ));
+    // Del cajón solo salen las de contado: las demás ya se restan como ventas
+    // sin efectivo o al crédito
+    let cash_credits_redeemed = sum(format!(
+        "SELECT COALESCE(SUM(r.amount),0.0) FROM store_credit_redemptions r JOIN {} s ON s.id = r.sale_id
+         WHERE {} AND {}",
+        sales,
+        CASH_SALE_SQL,
+        in_range("s.sale_date")
+    ));
+
+    // Los depósitos retornables son un pasivo: entran y salen de caja sin ser
+    // ingreso ni gasto.
+    let deposits_collected = sum(format!(
+        "SELECT COALESCE(SUM(deposit_amount),0.0) FROM {} WHERE {}",
+        sales,
+        in_range("sale_date")
+    ));
+    let deposits_refunded = sum(format!(
+        "SELECT COALESCE(SUM(amount),0.0) FROM {} WHERE movement_type='egreso' AND category='deposito' AND {}",
+        cash,
+        in_range("movement_date")
+    ));
+    let expense = expense - deposits_refunded;
+    let deposits_outstanding = outstanding_deposits_total(conn).unwrap_or(0.0);
+
+    let total_income = sales_income + other_income + credits_issued - credits_redeemed;
+    let balance = opening + total_income + credits_redeemed - cash_credits_redeemed - expense + deposits_collected
+        - deposits_refunded - non_cash_sales - credit_sales + credit_collected;
+
+    let mut csv = String::from("type,label,amount\n");
+    csv.push_str(&format!("opening,Saldo inicial,{}\n", format_amount(opening)));
+    csv.push_str(&format!("income,Ingresos por ventas,{}\n", format_amount(sales_income)));
+    csv.push_str(&format!("tax,Ventas sin impuesto,{}\n", format_amount(sales_income - sales_tax)));
+    csv.push_str(&format!("tax,Impuesto cobrado,{}\n", format_amount(sales_tax)));
+    csv.push_str(&format!("discount,Descuentos otorgados,{}\n", format_amount(sales_discounts)));
+    csv.push_str(&format!("income,Otros ingresos,{}\n", format_amount(other_income)));
+    csv.push_str(&format!("income,Emisión de tarjetas de regalo,{}\n", format_amount(credits_issued)));
+    csv.push_str(&format!("adjustment,Ventas pagadas con tarjeta de regalo,{}\n", format_amount(-credits_redeemed)));
+    csv.push_str(&format!("adjustment,Ventas cobradas sin efectivo,{}\n", format_amount(-non_cash_sales)));
+    csv.push_str(&format!("adjustment,Ventas al crédito,{}\n", format_amount(-credit_sales)));
+    csv.push_str(&format!("adjustment,Cobros en efectivo de ventas al crédito,{}\n", format_amount(credit_collected)));
+    csv.push_str(&format!("expense,Gastos / Egresos,{}\n", format_amount(expense)));
+    csv.push_str(&format!("deposit,Depósitos cobrados,{}\n", format_amount(deposits_collected)));
+    csv.push_str(&format!("deposit,Depósitos devueltos,{}\n", format_amount(deposits_refunded)));
+    csv.push_str(&format!("summary,Total ingresos,{}\n", format_amount(total_income)));
+    csv.push_str(&format!("summary,Balance,{}\n", format_amount(balance)));
+    csv.push_str(&format!("liability,Depósitos pendientes de devolución,{}\n", format_amount(deposits_outstanding)));
+
+    Ok(csv)
+}
+
+/// Exportación registrada en `AppState::exports` mientras se genera.
+#[derive(Debug, Clone, Serialize)]
+struct ExportJob {
+    id: String,
+    report: String,
+    started_at: u64,
+}
+
+/// Reserva de un reporte en el registro de exportaciones; al soltarse lo
+/// libera, también si la exportación termina con error.
+struct ExportLock<'a> {
+    state: &'a AppState,
+    report: String,
+    job: ExportJob,
+}
+
+impl Drop for ExportLock<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut exports) = self.state.exports.lock() {
+            exports.remove(&self.report);
+        }
+    }
+}
+
+/// Registra la exportación de `report`. Si ya hay una en curso devuelve ese
+/// trabajo en lugar de iniciar otro.
+fn try_lock_export<'a>(state: &'a AppState, report: &str) -> Result<Result<ExportLock<'a>, ExportJob>, String> {
+    let mut exports = state.exports.lock().map_err(|e| e.to_string())?;
+    if let Some(running) = exports.get(report) {
+        return Ok(Err(running.clone()));
+    }
+    let job = ExportJob {
+        id: new_token()[..12].to_string(),
+        report: report.to_string(),
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs(),
+    };
+    exports.insert(report.to_string(), job.clone());
+    Ok(Ok(ExportLock { state, report: report.to_string(), job }))
+}
+
+/// Como `try_lock_export`, pero rechaza la exportación si ya hay otra igual
+/// en curso.
+fn lock_export<'a>(state: &'a AppState, report: &str) -> Result<ExportLock<'a>, String> {
+    try_lock_export(state, report)?.map_err(|running| {
+        format!("El reporte {} ya se está exportando (trabajo {})", report, running.id)
+    })
+}
+
+/// Clave de `lock_export` para un reporte con parámetros, como `quote_{id}`:
+/// solo choca con otra exportación del mismo reporte y los mismos argumentos,
+/// así dos rangos de fechas distintos pueden exportarse a la vez.
+fn export_key(report: &str, args: &[Option<&str>]) -> String {
+    let args: Vec<&str> = args.iter().map(|arg| arg.unwrap_or("-")).collect();
+    format!("{}_{}", report, args.join("_"))
+}
+
+/// Resultado de `export_all_reports`. Si ya había una exportación completa en
+/// curso, `already_running` es verdadero, `job_id` es el de esa exportación y
+/// `paths` queda vacío.
+#[derive(Debug, Serialize)]
+struct ExportAllResult {
+    job_id: String,
+    already_running: bool,
+    paths: Vec<String>,
+}
+
+/// Se ejecuta fuera del hilo principal para que un segundo clic en
+/// "Exportar todo" encuentre el trabajo en curso en vez de encolarse detrás.
+#[tauri::command(async)]
+fn export_all_reports(
+    state: State<AppState>,
+    include_archive: Option<bool>,
+) -> Result<ExportAllResult, String> {
+    let with_archive = include_archive.unwrap_or(false);
+    let key = export_key("all_reports", &[with_archive.then_some("archivo")]);
+    let lock = match try_lock_export(&state, &key)? {
+        Ok(lock) => lock,
+        Err(running) => {
+            return Ok(ExportAllResult { job_id: running.id, already_running: true, paths: Vec::new() });
+        }
+    };
+    // Arma cada reporte directamente, sin tomar sus candados: una exportación
+    // suelta del mismo reporte en curso no debe abortar el paquete.
+    let files = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        [
+            ("inventory_report", inventory_report_csv(&conn)?),
+            ("sales_report", sales_report_csv(&conn, None, None, with_archive)?),
+            ("top_products_report", top_products_csv(&conn, with_archive)?),
+            ("stock_movements_report", stock_movements_csv(&conn, with_archive)?),
+            ("profitability_report", profitability_csv(&conn, with_archive)?),
+            ("financial_report", financial_report_csv(&conn, None, None, with_archive)?),
+        ]
+    };
+    let mut paths = Vec::new();
+    for (name, csv) in files {
+        paths.push(write_export(name, "csv", csv.as_bytes())?);
+    }
+    Ok(ExportAllResult { job_id: lock.job.id.clone(), already_running: false, paths })
+}
+
+/// Exportaciones que se están generando ahora, de la más antigua a la más
+/// reciente.
+#[tauri::command]
+fn get_running_exports(state: State<AppState>) -> Result<Vec<ExportJob>, String> {
+    let exports = state.exports.lock().map_err(|e| e.to_string())?;
+    let mut jobs: Vec<ExportJob> = exports.values().cloned().collect();
+    jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.report.cmp(&b.report)));
+    Ok(jobs)
+}
+
+/// Texto apto para nombre de archivo: solo letras, números, `-` y `_`.
+fn file_safe(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn money(value: Option<f64>) -> String {
+    value.map(format_amount).unwrap_or_default()
+}
+
+/// Expediente de un producto para adjuntar a un ticket de soporte o a un
+/// reclamo al proveedor: ficha, kardex con saldo acumulado, ventas, compras,
+/// historial de precios (incluido lo archivado) y nombres de los adjuntos, en
+/// un CSV por secciones.
+#[tauri::command]
+fn export_product_dossier(state: State<AppState>, product_id: i32) -> Result<String, String> {
+    let _job = lock_export(&state, &format!("product_dossier_{}", product_id))?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let product = conn
+        .query_row(
+            &format!("SELECT {} FROM products WHERE id = ?1", PRODUCT_COLUMNS),
+            rusqlite::params![product_id],
+            product_from_row,
+        )
+        .map_err(|_| "Producto no encontrado".to_string())?;
+    let channels = product_channels(&conn, product_id)?;
+    let current_stock: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END),0) FROM stock_movements WHERE product_id=?1",
+            rusqlite::params![product_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("[product]\nfield,value\n");
+    let fields = [
+        ("id", product_id.to_string()),
+        ("sku", product.sku.clone().unwrap_or_default()),
+        ("name", product.name.clone()),
+        ("brand", product.brand.unwrap_or_default()),
+        ("category", product.category.unwrap_or_default()),
+        ("presentation", product.presentation.unwrap_or_default()),
+        ("flavor", product.flavor.unwrap_or_default()),
+        ("weight", product.weight.unwrap_or_default()),
+        ("unit", product.unit.unwrap_or_else(|| "unidad".to_string())),
+        ("sale_price", money(product.sale_price)),
+        ("cost_price", money(product.cost_price)),
+        ("deposit_amount", money(product.deposit_amount)),
+        ("min_stock", product.min_stock.map(|v| v.to_string()).unwrap_or_default()),
+        ("max_stock", product.max_stock.map(|v| v.to_string()).unwrap_or_default()),
+        ("location", product.location.unwrap_or_default()),
+        ("status", product.status.unwrap_or_default()),
+        ("expiry_date", product.expiry_date.unwrap_or_default()),
+        ("lot_number", product.lot_number.unwrap_or_default()),
+        ("allowed_channels", channels.join("|")),
+        ("current_stock", current_stock.to_string()),
+    ];
+    for (field, value) in fields {
+        csv.push_str(&format!("{},{}\n", field, csv_field(&value)));
+    }
+
+    // Kardex: los saldos iniciales que deja el archivado repetirían los
+    // movimientos archivados, así que se omiten al unir ambas tablas.
+    csv.push_str("\n[stock_movements]\ncreated_at,id,type,quantity,balance,note,created_by\n");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT m.created_at, m.id, m.type, m.quantity, m.note, u.username
+             FROM {} m LEFT JOIN users u ON u.id = m.created_by
+             WHERE m.product_id = ?1
+               AND NOT (m.created_by IS NULL AND COALESCE(m.note,'') LIKE 'Saldo inicial al % (archivo)')
+             ORDER BY m.created_at, m.id",
+            source_table("stock_movements", true)
+        ))
+        .map_err(|e| e.to_string())?;
+    let movements = stmt
+        .query_map(rusqlite::params![product_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut balance = 0.0;
+    for (created_at, id, movement_type, quantity, note, user) in movements {
+        match movement_type.as_str() {
+            "ingreso" => balance += quantity,
+            "egreso" => balance -= quantity,
+            _ => {}
+        }
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            created_at,
+            id,
+            movement_type,
+            quantity,
+            (balance * 1000.0).round() / 1000.0,
+            csv_field(&note.unwrap_or_default()),
+            csv_field(&user.unwrap_or_default()),
+        ));
+    }
+
+    csv.push_str("\n[sales]\nsale_date,id,quantity,sale_price,discount,channel,customer,created_by,below_cost\n");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT s.sale_date, s.id, s.quantity, s.sale_price, s.discount, s.channel, s.customer, u.username, s.below_cost
+             FROM {} s LEFT JOIN users u ON u.id = s.created_by
+             WHERE s.product_id = ?1
+             ORDER BY s.sale_date, s.id",
+            valid_sales(true)
+        ))
+        .map_err(|e| e.to_string())?;
+    let sales = stmt
+        .query_map(rusqlite::params![product_id], |row| {
+            Ok(format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+                format_amount(row.get::<_, f64>(3)?),
+                row.get::<_, Option<f64>>(4)?.map(|d| d.to_string()).unwrap_or_default(),
+                csv_field(&row.get::<_, Option<String>>(5)?.unwrap_or_default()),
+                csv_field(&row.get::<_, Option<String>>(6)?.unwrap_or_default()),
+                csv_field(&row.get::<_, Option<String>>(7)?.unwrap_or_default()),
+                row.get::<_, Option<bool>>(8)?.unwrap_or(false),
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    csv.push_str(&sales.concat());
+
+    csv.push_str("\n[purchases]\npurchase_date,id,supplier,purchase_price,discount,expected_replenish_days\n");
+    let mut stmt = conn
+        .prepare(
+            "SELECT purchase_date, id, supplier, purchase_price, discount, expected_replenish_days
+             FROM purchases WHERE product_id = ?1 ORDER BY purchase_date, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let purchases = stmt
+        .query_map(rusqlite::params![product_id], |row| {
+            Ok(format!(
+                "{},{},{},{},{},{}\n",
+                row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                row.get::<_, i64>(1)?,
+                csv_field(&row.get::<_, Option<String>>(2)?.unwrap_or_default()),
+                money(row.get(3)?),
+                row.get::<_, Option<f64>>(4)?.map(|d| d.to_string()).unwrap_or_default(),
+                row.get::<_, Option<i32>>(5)?.map(|d| d.to_string()).unwrap_or_default(),
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    csv.push_str(&purchases.concat());
+
+    csv.push_str("\n[price_history]\nchanged_at,field,old_value,new_value,changed_by,source\n");
+    let mut stmt = conn
+        .prepare(
+            "SELECT h.changed_at, h.field, h.old_value, h.new_value, u.username, h.source
+             FROM price_history h LEFT JOIN users u ON u.id = h.changed_by
+             WHERE h.product_id = ?1 ORDER BY h.changed_at, h.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let changes = stmt
+        .query_map(rusqlite::params![product_id], |row| {
+            Ok(format!(
+                "{},{},{},{},{},{}\n",
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                money(row.get(2)?),
+                money(row.get(3)?),
+                csv_field(&row.get::<_, Option<String>>(4)?.unwrap_or_default()),
+                csv_field(&row.get::<_, Option<String>>(5)?.unwrap_or_default()),
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    csv.push_str(&changes.concat());
+
+    // Solo los nombres: los archivos quedan en la carpeta de adjuntos
+    csv.push_str("\n[attachments]\ncreated_at,entity,entity_id,filename,uploaded_by\n");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT a.created_at, a.entity, a.entity_id, a.filename, u.username
+             FROM attachments a LEFT JOIN users u ON u.id = a.uploaded_by
+             WHERE {}
+             ORDER BY a.created_at, a.id",
+            product_attachments_filter()
+        ))
+        .map_err(|e| e.to_string())?;
+    let attachments = stmt
+        .query_map(rusqlite::params![product_id], |row| {
+            Ok(format!(
+                "{},{},{},{},{}\n",
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                csv_field(&row.get::<_, String>(3)?),
+                csv_field(&row.get::<_, Option<String>>(4)?.unwrap_or_default()),
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    csv.push_str(&attachments.concat());
+
+    let label = product
+        .sku
+        .as_deref()
+        .map(file_safe)
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| product_id.to_string());
+    write_export(&format!("product_dossier_{}", label), "csv", csv.as_bytes())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Product {
+    id: Option<i32>,
+    sku: Option<String>,
+    name: String,
+    sale_price: Option<f64>,
+    cost_price: Option<f64>,
+    brand: Option<String>,
+    category: Option<String>,
+    presentation: Option<String>,
+    flavor: Option<String>,
+    weight: Option<String>,
+    image_path: Option<String>,
+    /// Miniatura (~200 px) que genera `save_product_image`; no se edita a mano
+    thumbnail_path: Option<String>,
+    expiry_date: Option<String>,
+    lot_number: Option<String>,
+    min_stock: Option<i32>,
+    max_stock: Option<i32>,
+    location: Option<String>,
+    status: Option<String>,
+    deposit_amount: Option<f64>,
+    /// Unidad de medida: "unidad", "kg", "g" o "porción" (NULL equivale a "unidad").
+    /// Es la unidad base: el stock y las ventas se llevan en ella
+    unit: Option<String>,
+    /// Unidad en que se compra, p. ej. "caja"; NULL si se compra en la unidad base
+    purchase_unit: Option<String>,
+    /// Unidades base por cada `purchase_unit` (12 barras por caja)
+    conversion_factor: Option<f64>,
+    /// Canales de venta permitidos; vacío significa todos
+    allowed_channels: Option<Vec<String>>,
+    /// Fecha de baja lógica; `None` mientras el producto está en el catálogo
+    deleted_at: Option<String>,
+    /// Producto padre si es una variante (talla, sabor…); lo fija
+    /// `add_product_variant` y `update_product` no lo cambia
+    parent_id: Option<i32>,
+    /// Categoría de la tabla `categories`. Si viene `category` se usa la de ese
+    /// nombre (creándola si no existe); `category` queda siempre con su nombre
+    category_id: Option<i32>,
+    /// Marca de la tabla `brands`, con el mismo criterio que `category_id`
+    brand_id: Option<i32>,
+    /// Código de barras, único. Si tiene 13 dígitos se valida como EAN-13
+    barcode: Option<String>,
+    /// Descripción larga (ingredientes, modo de uso) para la ficha y las etiquetas
+    description: Option<String>,
+    /// Notas internas del negocio; no se imprimen ni se exportan
+    internal_notes: Option<String>,
+    /// Proveedor habitual, en texto libre como `purchases.supplier`; sirve
+    /// para armar los pedidos de reposición por proveedor
+    default_supplier: Option<String>,
+    /// Tasa de impuesto en porcentaje (18 para el IGV, 0 si está exonerado);
+    /// `None` usa el ajuste `default_tax_rate`
+    tax_rate: Option<f64>,
+    /// Moneda de `cost_price` (código ISO, p. ej. "USD"); `None` es la moneda
+    /// local del ajuste `currency`. Los márgenes convierten con `exchange_rates`
+    currency: Option<String>,
+    /// Solo al crear: unidades (en la unidad base) del ingreso "stock inicial".
+    /// No es una columna; `max_stock` es un umbral y no mueve stock
+    #[serde(default, skip_serializing)]
+    initial_stock: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StockMovement {
+    id: Option<i32>,
+    product_id: i32,
+    movement_type: String, // "ingreso" or "egreso"
+    quantity: f64,
+    note: Option<String>,
+    created_by: Option<i32>,
+    /// Unidad de `quantity` al registrar: la unidad base (por defecto) o la
+    /// de compra del producto, que se convierte a la base antes de guardar
+    #[serde(default, skip_serializing)]
+    unit: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesByProduct {
+    product_id: i32,
+    name: String,
+    total_qty: f64,
+    total_revenue: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesByPaymentMethod {
+    payment_method: String,
+    sales_count: i64,
+    total_revenue: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TaxSummaryMonth {
+    /// AAAA-MM
+    month: String,
+    sales_count: i64,
+    /// Ventas gravadas sin el impuesto incluido
+    taxable_base: f64,
+    tax_collected: f64,
+    /// Ventas sin impuesto: productos exonerados y ventas anteriores a `tax_amount`
+    exempt_sales: f64,
+    /// Lo cobrado: base + impuesto + exoneradas
+    total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesByChannel {
+    /// En minúsculas; `NO_CHANNEL` para las ventas sin canal
+    channel: String,
+    sales_count: i64,
+    total_units: f64,
+    total_revenue: f64,
+    /// Porcentaje del ingreso del rango
+    share_percent: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HeatmapBucket {
+    /// 1 = lunes … 7 = domingo
+    weekday: i32,
+    weekday_name: String,
+    /// 0 a 23; `None` si la venta no tiene hora (o en el modo por día)
+    hour: Option<i32>,
+    sales_count: i64,
+    total_revenue: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesByUser {
+    /// `None` para las ventas sin usuario
+    user_id: Option<i32>,
+    /// `NO_USER` para las ventas sin usuario
+    username: String,
+    fullname: Option<String>,
+    sales_count: i64,
+    total_units: f64,
+    total_revenue: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesTrendPoint {
+    date: String,
+    sales_count: i64,
+    total_revenue: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Purchase {
+    id: Option<i32>,
+    product_id: i32,
+    supplier: Option<String>,
+    purchase_price: Option<f64>,
+    purchase_date: Option<String>,
+    discount: Option<f64>,
+    expected_replenish_days: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Sale {
+    id: Option<i32>,
+    product_id: i32,
+    quantity: f64,
+    /// Total de la línea, no precio unitario: precio × cantidad menos el
+    /// descuento porcentual (`line_total_cents`). Los reportes lo suman tal cual
+    /// Sin precio (o nulo) `add_sale` lo toma de la lista y `update_sale`
+    /// conserva el que tenía
+    #[serde(default)]
+    sale_price: Option<f64>,
+    discount: Option<f64>,
+    channel: Option<String>,
+    sale_date: String,
+    created_by: Option<i32>,
+    store_credit_code: Option<String>,
+    deposit_amount: Option<f64>,
+    customer: Option<String>,
+    /// Cliente registrado; la venta guarda su nombre en `customer`
+    customer_id: Option<i64>,
+    /// Precio de catálogo por unidad cuando se aplicó una promoción
+    original_price: Option<f64>,
+    promotion_id: Option<i64>,
+    /// Plan de pagos: vencimientos e importes que suman el total de la venta
+    installments: Option<Vec<InstallmentInput>>,
+    /// Lista de precios aplicada. Si la venta no trae `sale_price`, el total
+    /// sale del precio de la lista, la cantidad y el descuento
+    price_list_id: Option<i64>,
+    /// Un Administrador autoriza vender un producto que no está activo
+    #[serde(default, skip_serializing)]
+    allow_inactive: bool,
+    /// Un Administrador autoriza en `add_sale` un total distinto del de lista
+    #[serde(default, rename = "override", skip_serializing)]
+    price_override: bool,
+    /// Número del comprobante (V-000123); lo asigna el sistema al registrarla
+    #[serde(default)]
+    receipt_number: Option<String>,
+    /// Uno de `payment_methods`; sin indicar es efectivo. En una venta al
+    /// crédito es el medio del pago inicial
+    payment_method: Option<String>,
+    /// Se cobra después con `register_sale_payment`; necesita el cliente
+    #[serde(default)]
+    is_credit: bool,
+    /// Cobrado hasta ahora de una venta al crédito; al registrarla, el pago
+    /// inicial (0 si no viene)
+    amount_paid: Option<f64>,
+    /// Nota libre, hasta `SALE_NOTE_MAX_CHARS` caracteres
+    #[serde(default)]
+    note: Option<String>,
+}
+
+impl Sale {
+    /// Total de la línea ya resuelto por `record_sale` o `update_sale`.
+    fn total(&self) -> Result<f64, String> {
+        self.sale_price.ok_or_else(|| "Falta el precio de venta".to_string())
+    }
+}
+
+/// Cuota de un plan de pagos tal como la envía el frontend.
+#[derive(Debug, Serialize, Deserialize)]
+struct InstallmentInput {
+    due_date: String,
+    amount: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CashMovement {
+    id: Option<i32>,
+    movement_type: String,
+    amount: f64,
+    category: Option<String>,
+    description: Option<String>,
+    movement_date: String,
+    created_by: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CashSummary {
+    /// Saldos iniciales (no cuentan como ingreso, sí en el balance)
+    opening_balance: f64,
+    total_income: f64,
+    total_expense: f64,
+    balance: f64,
+}
+
+// Database state
+struct AppState {
+    db: Mutex<Connection>,
+    sessions: Mutex<HashMap<String, Session>>,
+    /// Exportaciones en curso, por reporte.
+    exports: Mutex<HashMap<String, ExportJob>>,
+}
+
+impl AppState {
+    fn new(db: Connection) -> Self {
+        AppState {
+            db: Mutex::new(db),
+            sessions: Mutex::new(HashMap::new()),
+            exports: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Sesión iniciada en una ventana concreta (una caja registradora).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    session_id: String,
+    user_id: i32,
+    username: String,
+    role: String,
+    window_label: String,
+    created_at: u64,
+}
+
+/// Evento emitido cuando un comando modifica datos, para que las demás
+/// ventanas se refresquen y la ventana de origen pueda ignorarlo.
+#[derive(Debug, Clone, Serialize)]
+struct DataChanged {
+    entity: String,
+    id: Option<i64>,
+    origin_window: String,
+}
+
+// Initialize database
+fn init_database() -> Result<Connection> {
+    let conn = Connection::open("vitasport.db")?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+/// Base de datos en memoria con el esquema completo y el admin por defecto,
+/// usada por las pruebas de integración.
+pub fn init_memory_database() -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+/// Versión del esquema de la base; se guarda en `PRAGMA user_version`. Subirla
+/// con cada cambio de `init_schema`.
+const SCHEMA_VERSION: i32 = 35;
+
+/// Crea o migra las tablas sobre `conn` e inserta el usuario admin si no hay usuarios.
+fn init_schema(conn: &Connection) -> Result<()> {
+    // Asegurar integridad referencial
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+    // Create users table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT UNIQUE NOT NULL,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            fullname TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    // Meta mensual de ventas del usuario para el ranking; NULL = sin meta
+    if !table_columns(conn, "users")?.iter().any(|(name, _)| name == "monthly_target") {
+        conn.execute("ALTER TABLE users ADD COLUMN monthly_target REAL", [])?;
+    }
+    // Contraseña temporal: el usuario debe cambiarla al entrar
+    if !table_columns(conn, "users")?.iter().any(|(name, _)| name == "must_change_password") {
+        conn.execute("ALTER TABLE users ADD COLUMN must_change_password INTEGER NOT NULL DEFAULT 0", [])?;
+    }
+
+    // Create products table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sku TEXT UNIQUE,
+            name TEXT NOT NULL,
+            sale_price REAL,
+            cost_price REAL,
+            brand TEXT,
+            category TEXT,
+            presentation TEXT,
+            flavor TEXT,
+            weight TEXT,
+            image_path TEXT,
+            expiry_date TEXT,
+            lot_number TEXT,
+            min_stock INTEGER,
+            max_stock INTEGER,
+            location TEXT,
+            status TEXT,
+            unit TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    {
+        let mut stmt = conn.prepare("PRAGMA table_info(products)")?;
+        let mut rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+        let mut col_names: Vec<String> = Vec::new();
+        for r in rows {
+            if let Ok(name) = r { col_names.push(name); }
+        }
+        if !col_names.iter().any(|c| c == "sale_price") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN sale_price REAL", []);
+        }
+        if !col_names.iter().any(|c| c == "cost_price") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN cost_price REAL", []);
+        }
+        if !col_names.iter().any(|c| c == "max_stock") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN max_stock INTEGER", []);
+        }
+        if !col_names.iter().any(|c| c == "deposit_amount") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN deposit_amount REAL", []);
+        }
+        if !col_names.iter().any(|c| c == "unit") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN unit TEXT", []);
+        }
+        // SQLite no admite DEFAULT CURRENT_TIMESTAMP en ALTER TABLE; las filas
+        // existentes quedan en NULL hasta ejecutar `repair_timestamps`.
+        if !col_names.iter().any(|c| c == "created_at") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN created_at TIMESTAMP", []);
+        }
+        if !col_names.iter().any(|c| c == "updated_at") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN updated_at TIMESTAMP", []);
+        }
+        // Baja lógica: `delete_product` marca la fecha y conserva la fila para
+        // que ventas y movimientos históricos sigan mostrando el nombre
+        if !col_names.iter().any(|c| c == "deleted_at") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN deleted_at TIMESTAMP", []);
+        }
+        if !col_names.iter().any(|c| c == "thumbnail_path") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN thumbnail_path TEXT", []);
+        }
+        // Variantes: ventas y stock siguen apuntando a la variante; el padre
+        // solo agrupa la familia en los reportes
+        if !col_names.iter().any(|c| c == "parent_id") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN parent_id INTEGER REFERENCES products(id)", []);
+        }
+        // La categoría pasa a la tabla `categories`; `category` conserva el
+        // nombre para lo que todavía filtra por texto
+        if !col_names.iter().any(|c| c == "category_id") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN category_id INTEGER REFERENCES categories(id)", []);
+        }
+        // Igual que la categoría: `brand` conserva el nombre de la marca
+        if !col_names.iter().any(|c| c == "brand_id") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN brand_id INTEGER REFERENCES brands(id)", []);
+        }
+        // Código de barras (EAN-13 del proveedor o interno), aparte del SKU
+        if !col_names.iter().any(|c| c == "barcode") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN barcode TEXT", []);
+        }
+        // Unidad de compra (caja) y cuántas unidades base trae
+        if !col_names.iter().any(|c| c == "purchase_unit") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN purchase_unit TEXT", []);
+        }
+        if !col_names.iter().any(|c| c == "conversion_factor") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN conversion_factor REAL", []);
+        }
+        if !col_names.iter().any(|c| c == "description") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN description TEXT", []);
+        }
+        if !col_names.iter().any(|c| c == "internal_notes") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN internal_notes TEXT", []);
+        }
+        if !col_names.iter().any(|c| c == "default_supplier") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN default_supplier TEXT", []);
+        }
+        if !col_names.iter().any(|c| c == "tax_rate") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN tax_rate REAL", []);
+        }
+        if !col_names.iter().any(|c| c == "currency") {
+            let _ = conn.execute("ALTER TABLE products ADD COLUMN currency TEXT", []);
+        }
+    }
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_products_barcode ON products(barcode) WHERE barcode IS NOT NULL",
+        [],
+    )?;
+
+    // Categorías de productos; el nombre no distingue mayúsculas para que
+    // "proteinas" y "Proteinas" no sean dos categorías
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            description TEXT,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    migrate_product_categories(conn)?;
+
+    // Marcas de productos, con el mismo criterio que las categorías
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS brands (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    migrate_product_brands(conn)?;
+
+    // Listas de precios (minorista, mayorista…) y el precio de cada producto
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_lists (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_prices (
+            product_id INTEGER NOT NULL,
+            price_list_id INTEGER NOT NULL,
+            price REAL NOT NULL,
+            PRIMARY KEY (product_id, price_list_id),
+            FOREIGN KEY (product_id) REFERENCES products(id),
+            FOREIGN KEY (price_list_id) REFERENCES price_lists(id)
+        )",
+        [],
+    )?;
+    migrate_retail_price_list(conn)?;
+
+    // Tipos de cambio: unidades de moneda local por cada unidad de `currency`
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS exchange_rates (
+            currency TEXT NOT NULL,
+            rate_date TEXT NOT NULL,
+            rate REAL NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (currency, rate_date)
+        )",
+        [],
+    )?;
+    // Estados escritos a mano ("Activo", "ACTIVO ") a su forma canónica
+    migrate_product_statuses(conn)?;
+
+    // Create stock_movements table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            type TEXT NOT NULL,
+            quantity REAL NOT NULL,
+            note TEXT,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (product_id) REFERENCES products(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    // Egreso generado por una venta; NULL en movimientos manuales y en los
+    // anteriores a la columna
+    if !table_columns(conn, "stock_movements")?.iter().any(|(name, _)| name == "sale_id") {
+        conn.execute("ALTER TABLE stock_movements ADD COLUMN sale_id INTEGER", [])?;
+    }
+
+    // Create purchases table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS purchases (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            supplier TEXT,
+            purchase_price REAL,
+            purchase_date TEXT,
+            discount REAL,
+            expected_replenish_days INTEGER,
+            FOREIGN KEY (product_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+
+    // Clientes registrados; las ventas los referencian con `customer_id`
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS customers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            phone TEXT,
+            email TEXT,
+            document_id TEXT,
+            notes TEXT,
+            active INTEGER NOT NULL DEFAULT 1,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_customers_document ON customers(document_id) WHERE document_id IS NOT NULL",
+        [],
+    )?;
+
+    // Create sales table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sales (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            sale_price REAL NOT NULL,
+            discount REAL,
+            channel TEXT,
+            sale_date TEXT NOT NULL,
+            created_by INTEGER,
+            FOREIGN KEY (product_id) REFERENCES products(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+
+    {
+        let col_names: Vec<String> = table_columns(conn, "sales")?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        // Depósito retornable cobrado en la venta (no es ingreso)
+        if !col_names.iter().any(|c| c == "deposit_amount") {
+            conn.execute("ALTER TABLE sales ADD COLUMN deposit_amount REAL", [])?;
+        }
+        if !col_names.iter().any(|c| c == "customer") {
+            conn.execute("ALTER TABLE sales ADD COLUMN customer TEXT", [])?;
+        }
+        // Promoción aplicada y precio de catálogo al momento de la venta
+        if !col_names.iter().any(|c| c == "original_price") {
+            conn.execute("ALTER TABLE sales ADD COLUMN original_price REAL", [])?;
+        }
+        if !col_names.iter().any(|c| c == "promotion_id") {
+            conn.execute("ALTER TABLE sales ADD COLUMN promotion_id INTEGER", [])?;
+        }
+        // Venta por debajo del costo y el costo unitario vigente en ese momento
+        if !col_names.iter().any(|c| c == "below_cost") {
+            conn.execute("ALTER TABLE sales ADD COLUMN below_cost INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+        if !col_names.iter().any(|c| c == "unit_cost") {
+            conn.execute("ALTER TABLE sales ADD COLUMN unit_cost REAL", [])?;
+        }
+        // Precio neto por unidad esperado (lista o promoción, menos el
+        // descuento) y si el vendedor cobró otro
+        if !col_names.iter().any(|c| c == "reference_price") {
+            conn.execute("ALTER TABLE sales ADD COLUMN reference_price REAL", [])?;
+        }
+        if !col_names.iter().any(|c| c == "price_override") {
+            conn.execute("ALTER TABLE sales ADD COLUMN price_override INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+        // Administrador que autorizó el precio o el descuento fuera de regla
+        if !col_names.iter().any(|c| c == "override_approved_by") {
+            conn.execute("ALTER TABLE sales ADD COLUMN override_approved_by INTEGER", [])?;
+        }
+        // Impuesto incluido en `sale_price` según la tasa vigente al vender;
+        // NULL en las ventas anteriores a este campo
+        if !col_names.iter().any(|c| c == "tax_amount") {
+            conn.execute("ALTER TABLE sales ADD COLUMN tax_amount REAL", [])?;
+        }
+        // Lista de precios con que se cobró; NULL es el precio de la ficha
+        if !col_names.iter().any(|c| c == "price_list_id") {
+            conn.execute("ALTER TABLE sales ADD COLUMN price_list_id INTEGER", [])?;
+        }
+        // Última corrección con update_sale; el detalle queda en audit_log
+        if !col_names.iter().any(|c| c == "edited_by") {
+            conn.execute("ALTER TABLE sales ADD COLUMN edited_by INTEGER", [])?;
+        }
+        if !col_names.iter().any(|c| c == "edited_at") {
+            conn.execute("ALTER TABLE sales ADD COLUMN edited_at TIMESTAMP", [])?;
+        }
+        // Anulación con void_sale: la fila se conserva pero deja de contar
+        if !col_names.iter().any(|c| c == "voided_at") {
+            conn.execute("ALTER TABLE sales ADD COLUMN voided_at TIMESTAMP", [])?;
+        }
+        if !col_names.iter().any(|c| c == "void_reason") {
+            conn.execute("ALTER TABLE sales ADD COLUMN void_reason TEXT", [])?;
+        }
+        if !col_names.iter().any(|c| c == "voided_by") {
+            conn.execute("ALTER TABLE sales ADD COLUMN voided_by INTEGER", [])?;
+        }
+        // Medio de pago de `payment_methods`; NULL en las ventas anteriores,
+        // que cuentan como efectivo
+        if !col_names.iter().any(|c| c == "payment_method") {
+            conn.execute("ALTER TABLE sales ADD COLUMN payment_method TEXT", [])?;
+        }
+        // Venta al crédito: `amount_paid` suma sus `sale_payments`; NULL en
+        // las ventas de contado
+        if !col_names.iter().any(|c| c == "is_credit") {
+            conn.execute("ALTER TABLE sales ADD COLUMN is_credit INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+        if !col_names.iter().any(|c| c == "amount_paid") {
+            conn.execute("ALTER TABLE sales ADD COLUMN amount_paid REAL", [])?;
+        }
+        // Cliente registrado; `customer` guarda su nombre al momento de la venta
+        if !col_names.iter().any(|c| c == "customer_id") {
+            conn.execute("ALTER TABLE sales ADD COLUMN customer_id INTEGER", [])?;
+        }
+        // Nota libre del vendedor ("entrega a domicilio el viernes")
+        if !col_names.iter().any(|c| c == "note") {
+            conn.execute("ALTER TABLE sales ADD COLUMN note TEXT", [])?;
+        }
+    }
+
+    // Comprobantes de venta: la cabecera agrupa las líneas que se cobraron
+    // juntas. Cada línea de `sale_items` comparte el id de su fila de `sales`
+    // (o `sales_archive`), que sigue llevando el stock y las devoluciones.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_headers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            customer TEXT,
+            channel TEXT,
+            sale_date TEXT NOT NULL,
+            created_by INTEGER,
+            total REAL NOT NULL DEFAULT 0,
+            tax_amount REAL NOT NULL DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            receipt_number TEXT,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    {
+        let mut stmt = conn.prepare("PRAGMA table_info(sale_headers)")?;
+        let col_names: Vec<String> = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>>>()?;
+        if !col_names.iter().any(|c| c == "receipt_number") {
+            conn.execute("ALTER TABLE sale_headers ADD COLUMN receipt_number TEXT", [])?;
+        }
+    }
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_sale_headers_receipt ON sale_headers(receipt_number)",
+        [],
+    )?;
+    // Último número entregado por serie de comprobantes (V-000123)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS receipt_counters (
+            series TEXT PRIMARY KEY,
+            last_number INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_items (
+            id INTEGER PRIMARY KEY,
+            sale_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            unit_price REAL NOT NULL,
+            discount REAL,
+            line_total REAL NOT NULL,
+            FOREIGN KEY (sale_id) REFERENCES sale_headers(id)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_sale_items_sale ON sale_items(sale_id)", [])?;
+
+    // Cotizaciones (proformas): mismas líneas que un comprobante, sin tocar el
+    // stock hasta que `convert_quote_to_sale` las vuelve una venta (`sale_id`)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quotes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            customer TEXT,
+            customer_id INTEGER,
+            channel TEXT,
+            price_list_id INTEGER,
+            status TEXT NOT NULL DEFAULT 'borrador',
+            valid_until TEXT NOT NULL,
+            note TEXT,
+            total REAL NOT NULL DEFAULT 0,
+            sale_id INTEGER,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            converted_at TIMESTAMP,
+            FOREIGN KEY (customer_id) REFERENCES customers(id),
+            FOREIGN KEY (price_list_id) REFERENCES price_lists(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quote_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            quote_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            unit_price REAL NOT NULL,
+            discount REAL,
+            line_total REAL NOT NULL,
+            FOREIGN KEY (quote_id) REFERENCES quotes(id),
+            FOREIGN KEY (product_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_quote_items_quote ON quote_items(quote_id)", [])?;
+
+    // Devoluciones de depósitos retornables (envases, shakers, coolers)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS deposit_refunds (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            amount REAL NOT NULL,
+            cash_movement_id INTEGER,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cash_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            movement_type TEXT NOT NULL,
+            amount REAL NOT NULL,
+            category TEXT,
+            description TEXT,
+            movement_date TEXT NOT NULL,
+            created_by INTEGER,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+
+    // Tarjetas de regalo / saldo a favor y sus canjes
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS store_credits (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            code TEXT UNIQUE NOT NULL,
+            initial_amount REAL NOT NULL,
+            balance REAL NOT NULL,
+            customer TEXT,
+            expiry_date TEXT,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS store_credit_redemptions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            credit_id INTEGER NOT NULL,
+            sale_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (credit_id) REFERENCES store_credits(id)
+        )",
+        [],
+    )?;
+
+    // Promociones por producto o categoría con vigencia
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS promotions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            product_id INTEGER,
+            category TEXT,
+            promo_price REAL,
+            percent_off REAL,
+            starts_at TEXT NOT NULL,
+            ends_at TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Canales de venta permitidos por producto (sin filas = todos)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_channels (
+            product_id INTEGER NOT NULL,
+            channel TEXT NOT NULL,
+            PRIMARY KEY (product_id, channel),
+            FOREIGN KEY (product_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+
+    // Etiquetas libres por producto ("vegano", "sin-lactosa"), en minúsculas
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_tags (
+            product_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (product_id, tag),
+            FOREIGN KEY (product_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_product_tags_tag ON product_tags(tag)", [])?;
+
+    // Historial de cambios de precio de venta y costo
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            field TEXT NOT NULL,
+            old_value REAL,
+            new_value REAL,
+            changed_by INTEGER,
+            source TEXT,
+            changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (product_id) REFERENCES products(id),
+            FOREIGN KEY (changed_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+
+    // Configuración clave/valor
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Notificaciones generadas por tareas programadas y alertas
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            title TEXT NOT NULL,
+            message TEXT NOT NULL,
+            product_id INTEGER,
+            dedupe_key TEXT UNIQUE,
+            read INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Webhooks hacia servicios externos y su cola de entregas
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            url TEXT NOT NULL,
+            secret TEXT,
+            events TEXT NOT NULL,
+            min_amount REAL,
+            active INTEGER NOT NULL DEFAULT 1,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            webhook_id INTEGER NOT NULL,
+            event TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            status_code INTEGER,
+            error TEXT,
+            next_attempt_at TIMESTAMP,
+            last_attempt_at TIMESTAMP,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (webhook_id) REFERENCES webhooks(id)
+        )",
+        [],
+    )?;
+
+    // Meses cerrados contablemente y log de auditoría
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS closed_periods (
+            month TEXT PRIMARY KEY,
+            closed_by INTEGER,
+            closed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (closed_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            action TEXT NOT NULL,
+            entity TEXT NOT NULL,
+            entity_id INTEGER,
+            details TEXT,
+            flag TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    // Permisos adicionales por rol (el Administrador los tiene todos)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS role_permissions (
+            role TEXT NOT NULL,
+            permission TEXT NOT NULL,
+            PRIMARY KEY (role, permission)
+        )",
+        [],
+    )?;
+
+    // Ventas agregadas por día para el tablero (ver refresh_daily_summaries)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS daily_sales_summary (
+            day TEXT PRIMARY KEY,
+            revenue REAL NOT NULL,
+            units REAL NOT NULL,
+            tickets INTEGER NOT NULL,
+            by_category TEXT NOT NULL,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Cuotas de las ventas con plan de pagos
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payment_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            due_date TEXT NOT NULL,
+            amount REAL NOT NULL,
+            paid_amount REAL NOT NULL DEFAULT 0,
+            paid_at TIMESTAMP,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_payment_schedules_sale ON payment_schedules(sale_id)",
+        [],
+    )?;
+
+    // Pagos parciales de las ventas al crédito
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            payment_date TEXT NOT NULL,
+            payment_method TEXT NOT NULL,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_sale_payments_sale ON sale_payments(sale_id)", [])?;
+
+    // Packs: componentes (y cantidad por pack) que descuenta cada venta del pack
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_bundles (
+            bundle_id INTEGER NOT NULL,
+            component_id INTEGER NOT NULL,
+            quantity REAL NOT NULL,
+            PRIMARY KEY (bundle_id, component_id),
+            FOREIGN KEY(bundle_id) REFERENCES products(id),
+            FOREIGN KEY(component_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+
+    // Archivos adjuntos a ventas, movimientos, compras o productos; el archivo
+    // se copia a `attachments_dir()` y aquí queda su ruta
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            filename TEXT NOT NULL,
+            stored_path TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            uploaded_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(uploaded_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_attachments_entity ON attachments(entity, entity_id)",
+        [],
+    )?;
+
+    // Importaciones por tramos: cada tramo se confirma junto con el avance del
+    // trabajo, así una importación interrumpida se reanuda sin duplicar filas
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            path TEXT NOT NULL,
+            file_hash TEXT NOT NULL,
+            mapping TEXT,
+            total_rows INTEGER NOT NULL,
+            processed_rows INTEGER NOT NULL DEFAULT 0,
+            imported INTEGER NOT NULL DEFAULT 0,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            blocked_count INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL,
+            created_by INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            finished_at TIMESTAMP,
+            FOREIGN KEY(created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_job_chunks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            job_id INTEGER NOT NULL,
+            first_line INTEGER NOT NULL,
+            last_line INTEGER NOT NULL,
+            imported INTEGER NOT NULL,
+            errors TEXT NOT NULL,
+            blocked TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(job_id) REFERENCES import_jobs(id)
+        )",
+        [],
+    )?;
+
+    // Tablas de archivo con el mismo esquema que las tablas vivas
+    ensure_archive_tables(conn)?;
+
+    // Cantidades fraccionarias (kg, g): las tablas creadas con quantity INTEGER
+    // se reconstruyen con quantity REAL conservando los valores.
+    for table in ["sales", "stock_movements", "deposit_refunds", "sales_archive", "stock_movements_archive"] {
+        migrate_quantity_to_real(conn, table)?;
+    }
+
+    // Después de la migración, que reconstruye las tablas y perdería los índices
+    for table in ["sales", "sales_archive"] {
+        conn.execute(
+            &format!("CREATE INDEX IF NOT EXISTS idx_{t}_sale_date ON {t}(sale_date)", t = table),
+            [],
+        )?;
+    }
+    // Ventas anteriores a los comprobantes: un comprobante de una línea cada una
+    migrate_sale_items(conn)?;
+    // Bases anteriores a los resúmenes diarios: se calculan una vez
+    let needs_summaries: bool = conn.query_row(
+        "SELECT NOT EXISTS(SELECT 1 FROM daily_sales_summary)
+            AND (EXISTS(SELECT 1 FROM sales) OR EXISTS(SELECT 1 FROM sales_archive))",
+        [],
+        |row| row.get(0),
+    )?;
+    if needs_summaries {
+        refresh_daily_summaries(conn, None)?;
+    }
+
+    // Una base abierta antes por una versión más nueva conserva su número
+    let user_version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if user_version < 24 {
+        migrate_max_stock_ingresos(conn)?;
+    }
+    if user_version < SCHEMA_VERSION {
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+
+    // Insertar usuario admin por defecto si no existe
+    let user_count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM users",
+        [],
+        |row| row.get(0)
+    ).unwrap_or(0);
+    
+    if user_count == 0 {
+        // Hash seguro de la contraseña "admin" con bcrypt
+        let admin_password_hash = hash(DEFAULT_ADMIN_PASSWORD, DEFAULT_COST).expect("Failed to hash password");
+        
+        conn.execute(
+            "INSERT INTO users (username, password_hash, role, fullname) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![DEFAULT_ADMIN_USERNAME, admin_password_hash, "Administrador", "Administrador del Sistema"],
+        )?;
+        println!("✅ Usuario admin por defecto creado con contraseña encriptada");
+    } else {
+        // Base de una versión sin asistente: la contraseña por defecto sigue
+        // sirviendo durante el período de gracia
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value)
+             SELECT ?1, date('now') WHERE NOT EXISTS (SELECT 1 FROM settings WHERE key = ?2)",
+            rusqlite::params![SETTING_SETUP_GRACE_FROM, SETTING_SETUP_COMPLETED],
+        )?;
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES (?1, 'false')",
+        [SETTING_SETUP_COMPLETED],
+    )?;
+
+    Ok(())
+}
+
+/// Elimina todos los datos de la base de datos y la reinicializa
+/// dejando solo el usuario admin por defecto (que debe pasar de nuevo por la
+/// configuración inicial). Solo el Administrador puede hacerlo; cierra todas
+/// las sesiones.
+#[tauri::command]
+fn reset_database<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<(), String> = (|| {
+        conn.execute("DELETE FROM quote_items", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM quotes", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM stock_movements", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM sales", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM sale_items", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM sale_headers", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM receipt_counters", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM purchases", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM cash_movements", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM store_credit_redemptions", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM store_credits", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM deposit_refunds", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM notifications", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM price_history", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM product_channels", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM product_tags", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM product_bundles", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM promotions", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM product_prices", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM price_lists", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM webhook_deliveries", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM closed_periods", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM daily_sales_summary", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM attachments", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM sale_payments", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM payment_schedules", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM import_job_chunks", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM import_jobs", [])
+            .map_err(|e| e.to_string())?;
+        for (table, _) in ARCHIVABLE_TABLES {
+            conn.execute(&format!("DELETE FROM {}_archive", table), [])
+                .map_err(|e| e.to_string())?;
+        }
+        conn.execute("DELETE FROM products", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM categories", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM brands", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM customers", [])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM users", [])
+            .map_err(|e| e.to_string())?;
+
+        // Recrear usuario admin; el asistente de configuración vuelve a aparecer
+        let admin_password_hash =
+            hash(DEFAULT_ADMIN_PASSWORD, DEFAULT_COST).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO users (username, password_hash, role, fullname) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                DEFAULT_ADMIN_USERNAME,
+                admin_password_hash,
+                "Administrador",
+                "Administrador del Sistema"
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        // La lista minorista siempre existe
+        migrate_retail_price_list(&conn).map_err(|e| e.to_string())?;
+        set_setting(&conn, SETTING_SETUP_COMPLETED, "false")?;
+        conn.execute("DELETE FROM settings WHERE key = ?1", [SETTING_SETUP_GRACE_FROM])
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            // Las sesiones abiertas apuntan a usuarios que ya no existen
+            state.sessions.lock().map_err(|e| e.to_string())?.clear();
+            emit_data_changed(&window, "database", None);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Crea una copia de seguridad completa de la base de datos en la carpeta de
+/// exportación y devuelve su ruta.
+#[tauri::command]
+fn backup_database(state: State<AppState>) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let db_size: i64 = conn
+        .query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let out_dir = export_dir()?;
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let path = out_dir.join(format!("vitasport_backup_{}.db", ts));
+    write_atomically(&path, db_size.max(0) as u64, |tmp| {
+        // SQLite recibe la ruta como texto UTF-8
+        let tmp = tmp
+            .to_str()
+            .ok_or_else(|| std::io::Error::other("la ruta contiene caracteres no válidos"))?;
+        conn.execute("VACUUM INTO ?1", rusqlite::params![tmp])
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    })?;
+    path_to_string(&path)
+}
+
+// ============================================
+// VOLCADO SQL
+// ============================================
+
+/// Primera línea de todo volcado; `import_sql_dump` rechaza otros archivos.
+const SQL_DUMP_HEADER: &str = "-- VitaSport SQL dump";
+
+/// Tablas cuyo contenido hace que la base deje de contar como vacía.
+const SQL_DUMP_DATA_TABLES: &[&str] = &["products", "sales", "stock_movements", "cash_movements", "purchases"];
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SqlDumpTable {
+    name: String,
+    rows: i64,
+    /// SHA-256 de las filas en formato SQL, en orden de `rowid`
+    checksum: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SqlDump {
+    path: String,
+    tables: Vec<SqlDumpTable>,
+}
+
+fn sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Literal SQL de un valor: texto entre comillas simples (duplicadas), BLOB
+/// en hexadecimal y reales con su representación exacta más corta.
+fn sql_literal(value: rusqlite::types::ValueRef) -> String {
+    use rusqlite::types::ValueRef;
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) if f.is_nan() => "NULL".to_string(),
+        ValueRef::Real(f) if f.is_infinite() => if f > 0.0 { "9e999" } else { "-9e999" }.to_string(),
+        ValueRef::Real(f) => format!("{:?}", f),
+        // Un NUL cortaría el literal: se escribe como BLOB convertido a texto
+        ValueRef::Text(t) if t.contains(&0) => format!("CAST(X'{}' AS TEXT)", hex(t)),
+        ValueRef::Text(t) => format!("'{}'", String::from_utf8_lossy(t).replace('\'', "''")),
+        ValueRef::Blob(b) => format!("X'{}'", hex(b)),
+    }
+}
+
+/// Tablas de la base (sin las internas de SQLite) con su CREATE.
+fn sql_dump_tables(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name, sql FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid")
+        .map_err(|e| e.to_string())?;
+    let tables = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tables)
+}
+
+/// Escribe en `out` un INSERT por fila de `table` y devuelve cuántas filas
+/// tiene y su checksum.
+fn dump_table_rows(conn: &Connection, table: &str, out: &mut String) -> Result<SqlDumpTable, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {} ORDER BY rowid", sql_identifier(table)))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt.column_count();
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut hash = hmac_sha256::Hash::new();
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let values = (0..columns)
+            .map(|i| row.get_ref(i).map(sql_literal))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+            .join(",");
+        hash.update(values.as_bytes());
+        hash.update(b"\n");
+        out.push_str(&format!("INSERT INTO {} VALUES({});\n", sql_identifier(table), values));
+        count += 1;
+    }
+    Ok(SqlDumpTable {
+        name: table.to_string(),
+        rows: count,
+        checksum: hash.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+    })
+}
+
+/// Volcado completo en SQL (esquema y datos) para guardar en otro sistema de
+/// respaldo. Devuelve la ruta y, por tabla, filas y checksum para verificar
+/// la restauración.
+#[tauri::command]
+fn export_sql_dump(state: State<AppState>) -> Result<SqlDump, String> {
+    let _job = lock_export(&state, "sql_dump")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let created_at: String = conn
+        .query_row("SELECT datetime('now', 'localtime')", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let mut sql = format!(
+        "{}\n-- Esquema {}, generado el {}\nPRAGMA foreign_keys=OFF;\n",
+        SQL_DUMP_HEADER, SCHEMA_VERSION, created_at
+    );
+    let mut tables = Vec::new();
+    for (name, create) in sql_dump_tables(&conn)? {
+        sql.push_str(&format!("\n{};\n", create));
+        tables.push(dump_table_rows(&conn, &name, &mut sql)?);
+    }
+
+    // Contadores AUTOINCREMENT, para no reutilizar ids borrados
+    let has_sequence: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'sqlite_sequence')", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if has_sequence {
+        sql.push_str("\nDELETE FROM sqlite_sequence;\n");
+        dump_table_rows(&conn, "sqlite_sequence", &mut sql)?;
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT sql FROM sqlite_master WHERE type IN ('index', 'trigger', 'view') AND sql IS NOT NULL ORDER BY rowid")
+        .map_err(|e| e.to_string())?;
+    let schema = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    sql.push('\n');
+    for statement in schema {
+        sql.push_str(&format!("{};\n", statement));
+    }
+
+    let path = write_export("vitasport_dump", "sql", sql.as_bytes())?;
+    Ok(SqlDump { path, tables })
+}
+
+/// Restaura un volcado de `export_sql_dump`. Solo sobre una base sin datos
+/// (instalación nueva, con el admin por defecto); reemplaza todas las tablas
+/// en una transacción y después aplica las migraciones pendientes. Sin sesión
+/// (antes de la configuración inicial) o con una de Administrador.
+#[tauri::command]
+fn import_sql_dump<R: Runtime>(window: Window<R>, state: State<AppState>, path: String) -> Result<SqlDump, String> {
+    if let Some(session) = window_session(&state, window.label()) {
+        if session.role != "Administrador" {
+            return Err("Solo un Administrador puede realizar esta acción".to_string());
+        }
+    }
+    let source = Path::new(&path);
+    let dump = fs::read_to_string(source).map_err(|e| io_error(source, e))?;
+    if !dump.starts_with(SQL_DUMP_HEADER) {
+        return Err("El archivo no es un volcado SQL de VitaSport".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut has_data = false;
+    for table in SQL_DUMP_DATA_TABLES {
+        has_data |= conn
+            .query_row(&format!("SELECT EXISTS(SELECT 1 FROM {})", table), [], |row| row.get::<_, bool>(0))
+            .map_err(|e| e.to_string())?;
+    }
+    let users: i64 = conn
+        .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if has_data || users > 1 {
+        return Err("La base ya tiene datos. El volcado solo se importa en una instalación nueva.".to_string());
+    }
+
+    conn.execute_batch("PRAGMA foreign_keys = OFF; BEGIN IMMEDIATE TRANSACTION;")
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<SqlDumpTable>, String> = (|| {
+        let mut stmt = conn
+            .prepare("SELECT type, name FROM sqlite_master WHERE type IN ('view', 'trigger', 'table') AND name NOT LIKE 'sqlite_%'")
+            .map_err(|e| e.to_string())?;
+        let existing = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for (kind, name) in existing {
+            conn.execute(&format!("DROP {} IF EXISTS {}", kind.to_uppercase(), sql_identifier(&name)), [])
+                .map_err(|e| e.to_string())?;
+        }
+        conn.execute_batch(&dump)
+            .map_err(|e| format!("El volcado no se pudo aplicar: {}", e))?;
+        let mut scratch = String::new();
+        sql_dump_tables(&conn)?
+            .into_iter()
+            .map(|(name, _)| dump_table_rows(&conn, &name, &mut scratch))
+            .collect()
+    })();
+
+    let tables = match result {
+        Ok(tables) => {
+            conn.execute_batch("COMMIT; PRAGMA foreign_keys = ON;")
+                .map_err(|e| e.to_string())?;
+            tables
+        }
+        Err(err) => {
+            let _ = conn.execute_batch("ROLLBACK; PRAGMA foreign_keys = ON;");
+            return Err(err);
+        }
+    };
+    // Un volcado de una versión anterior queda con el esquema actual
+    init_schema(&conn).map_err(|e| e.to_string())?;
+    // Las sesiones abiertas apuntan a usuarios que ya no existen
+    state.sessions.lock().map_err(|e| e.to_string())?.clear();
+    emit_data_changed(&window, "database", None);
+    Ok(SqlDump { path, tables })
+}
+
+// ============================================
+// ARCHIVO DE DATOS HISTÓRICOS
+// ============================================
+
+/// Tablas transaccionales que se pueden archivar, con la expresión que da
+/// su fecha efectiva (AAAA-MM-DD). Los cobros de ventas al crédito se
+/// archivan por su fecha de pago: los anteriores al corte ya están en el saldo
+/// inicial de caja.
+const ARCHIVABLE_TABLES: [(&str, &str); 4] = [
+    ("sales", "substr(sale_date,1,10)"),
+    ("stock_movements", "substr(created_at,1,10)"),
+    ("cash_movements", "substr(movement_date,1,10)"),
+    ("sale_payments", "substr(payment_date,1,10)"),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpeningStock {
+    product_id: i32,
+    quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveReport {
+    before_date: String,
+    dry_run: bool,
+    sales: i64,
+    stock_movements: i64,
+    cash_movements: i64,
+    sale_payments: i64,
+    opening_stock: Vec<OpeningStock>,
+    opening_cash: f64,
+}
+
+/// Devuelve los nombres y tipos de las columnas de una tabla.
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let cols = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(cols)
+}
+
+/// Reconstruye `table` con `quantity REAL` si fue creada con una columna
+/// entera. SQLite no permite cambiar el tipo con ALTER TABLE, así que se copia
+/// a una tabla nueva con el mismo esquema y se renombra.
+fn migrate_quantity_to_real(conn: &Connection, table: &str) -> Result<()> {
+    let needs_migration = table_columns(conn, table)?
+        .iter()
+        .any(|(name, col_type)| name == "quantity" && !col_type.eq_ignore_ascii_case("REAL"));
+    if !needs_migration {
+        return Ok(());
+    }
+    let sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        rusqlite::params![table],
+        |row| row.get(0),
+    )?;
+    let columns = match sql.find('(') {
+        Some(pos) => sql[pos..]
+            .replacen("quantity INTEGER", "quantity REAL", 1)
+            .replacen("quantity INT", "quantity REAL", 1),
+        None => return Ok(()),
+    };
+
+    conn.execute_batch("PRAGMA foreign_keys = OFF; BEGIN IMMEDIATE TRANSACTION;")?;
+    let result: Result<()> = (|| {
+        // Conservar el contador AUTOINCREMENT para no reutilizar ids borrados
+        let sequence: Option<i64> = conn
+            .query_row(
+                "SELECT seq FROM sqlite_sequence WHERE name = ?1",
+                rusqlite::params![table],
+                |row| row.get(0),
+            )
+            .ok();
+        conn.execute_batch(&format!(
+            "CREATE TABLE {t}__new {cols};
+             INSERT INTO {t}__new SELECT * FROM {t};
+             DROP TABLE {t};
+             ALTER TABLE {t}__new RENAME TO {t};",
+            t = table,
+            cols = columns
+        ))?;
+        if let Some(seq) = sequence {
+            let updated = conn.execute(
+                "UPDATE sqlite_sequence SET seq = MAX(seq, ?2) WHERE name = ?1",
+                rusqlite::params![table, seq],
+            )?;
+            if updated == 0 {
+                conn.execute(
+                    "INSERT INTO sqlite_sequence (name, seq) VALUES (?1, ?2)",
+                    rusqlite::params![table, seq],
+                )?;
+            }
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => conn.execute_batch("COMMIT; PRAGMA foreign_keys = ON;"),
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK; PRAGMA foreign_keys = ON;");
+            Err(e)
+        }
+    }
+}
+
+/// Crea las tablas `*_archive` y les agrega las columnas que las tablas vivas
+/// hayan ganado por migraciones, para que ambas puedan unirse con UNION ALL.
+fn ensure_archive_tables(conn: &Connection) -> Result<()> {
+    for (table, _) in ARCHIVABLE_TABLES {
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {t}_archive AS SELECT * FROM {t} WHERE 0",
+                t = table
+            ),
+            [],
+        )?;
+        let archived: Vec<String> = table_columns(conn, &format!("{}_archive", table))?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        for (name, col_type) in table_columns(conn, table)? {
+            if !archived.contains(&name) {
+                conn.execute(
+                    &format!("ALTER TABLE {}_archive ADD COLUMN {} {}", table, name, col_type),
+                    [],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Origen de datos para los reportes: la tabla viva o, si se pide, la tabla
+/// viva unida a su archivo.
+fn source_table(table: &str, include_archive: bool) -> String {
+    if include_archive {
+        format!("(SELECT * FROM {t} UNION ALL SELECT * FROM {t}_archive)", t = table)
+    } else {
+        table.to_string()
+    }
+}
+
+/// Ventas que cuentan en reportes y exportaciones: como
+/// `source_table("sales", …)`, sin las anuladas con `void_sale`.
+fn valid_sales(include_archive: bool) -> String {
+    if include_archive {
+        "(SELECT * FROM sales WHERE voided_at IS NULL UNION ALL SELECT * FROM sales_archive WHERE voided_at IS NULL)".to_string()
+    } else {
+        "(SELECT * FROM sales WHERE voided_at IS NULL)".to_string()
+    }
+}
+
+/// Valida una fecha con formato AAAA-MM-DD (incluye años bisiestos).
+fn is_valid_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return false;
+    }
+    let (year, month, day) = match (
+        parts[0].parse::<i32>(),
+        parts[1].parse::<u32>(),
+        parts[2].parse::<u32>(),
+    ) {
+        (Ok(y), Ok(m), Ok(d)) => (y, m, d),
+        _ => return false,
+    };
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    day >= 1 && day <= days_in_month
+}
+
+/// Mueve ventas, movimientos de stock, movimientos de caja y cobros de ventas
+/// al crédito anteriores a `before_date` a sus tablas `*_archive`. Para no
+/// alterar los saldos, deja un movimiento de saldo inicial por producto y uno
+/// de caja (`cash_balance` al corte) a la fecha de corte. Por defecto es una
+/// simulación (`dry_run`) que solo informa lo que haría. Solo el Administrador
+/// puede ejecutarlo.
+#[tauri::command]
+fn archive_old_data<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    before_date: String,
+    dry_run: Option<bool>,
+) -> Result<ArchiveReport, String> {
+    if !is_valid_date(&before_date) {
+        return Err("Fecha de corte inválida. Usa el formato AAAA-MM-DD.".to_string());
+    }
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let dry = dry_run.unwrap_or(true);
+
+    let mut counts = [0i64; 4];
+    for (i, (table, date_expr)) in ARCHIVABLE_TABLES.iter().enumerate() {
+        counts[i] = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {} WHERE {} < ?1", table, date_expr),
+                rusqlite::params![before_date],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT product_id,
+                    SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END) as balance
+             FROM stock_movements
+             WHERE substr(created_at,1,10) < ?1
+             GROUP BY product_id
+             HAVING ABS(balance) >= 0.0005
+             ORDER BY product_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let opening_stock = stmt
+        .query_map(rusqlite::params![before_date], |row| {
+            Ok(OpeningStock {
+                product_id: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    // Lo mismo que muestra la caja hasta el día anterior al corte, así el
+    // archivo no cambia el balance
+    let opening_cash = cash_balance(&conn, false, Some(&before_date))?;
+
+    let report = ArchiveReport {
+        before_date: before_date.clone(),
+        dry_run: dry,
+        sales: counts[0],
+        stock_movements: counts[1],
+        cash_movements: counts[2],
+        sale_payments: counts[3],
+        opening_stock,
+        opening_cash,
+    };
+    if dry {
+        return Ok(report);
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<(), String> = (|| {
+        for (table, date_expr) in ARCHIVABLE_TABLES {
+            let cols = table_columns(&conn, table)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            conn.execute(
+                &format!(
+                    "INSERT INTO {t}_archive ({c}) SELECT {c} FROM {t} WHERE {d} < ?1",
+                    t = table,
+                    c = cols,
+                    d = date_expr
+                ),
+                rusqlite::params![before_date],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute(
+                &format!("DELETE FROM {} WHERE {} < ?1", table, date_expr),
+                rusqlite::params![before_date],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let opening_at = format!("{} 00:00:00", before_date);
+        let note = format!("Saldo inicial al {} (archivo)", before_date);
+        for opening in &report.opening_stock {
+            let movement_type = if opening.quantity > 0.0 { "ingreso" } else { "egreso" };
+            conn.execute(
+                "INSERT INTO stock_movements (product_id, type, quantity, note, created_by, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?5)",
+                rusqlite::params![
+                    opening.product_id,
+                    movement_type,
+                    opening.quantity.abs(),
+                    note,
+                    opening_at
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        if report.opening_cash != 0.0 {
+            conn.execute(
+                "INSERT INTO cash_movements (movement_type, amount, category, description, movement_date, created_by)
+                 VALUES ('saldo_inicial', ?1, 'archivo', ?2, ?3, NULL)",
+                rusqlite::params![report.opening_cash, note, before_date],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            Ok(report)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TimestampRepairReport {
+    stock_movements_updated_at: usize,
+    users_updated_at: usize,
+    products_created_at: usize,
+    products_updated_at: usize,
+    products_without_history: i64,
+}
+
+/// Corrige marcas de tiempo inconsistentes en bases migradas: `updated_at`
+/// nunca anterior a `created_at`, y `products.created_at` tomado del primer
+/// movimiento de stock o venta del producto cuando falta o es posterior.
+/// Devuelve cuántas filas cambió en cada tabla.
+#[tauri::command]
+fn repair_timestamps(state: State<AppState>) -> Result<TimestampRepairReport, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<TimestampRepairReport, String> = (|| {
+        let fix_updated_at = |table: &str| -> Result<usize, String> {
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET updated_at = created_at
+                     WHERE created_at IS NOT NULL AND (updated_at IS NULL OR updated_at < created_at)",
+                    table
+                ),
+                [],
+            )
+            .map_err(|e| e.to_string())
+        };
+
+        let stock_movements_updated_at =
+            fix_updated_at("stock_movements")? + fix_updated_at("stock_movements_archive")?;
+        let users_updated_at = fix_updated_at("users")?;
+
+        // Primera actividad conocida del producto; las ventas solo tienen fecha
+        let first_activity = format!(
+            "SELECT MIN(t) FROM (
+                SELECT MIN(created_at) AS t FROM {} WHERE product_id = products.id
+                UNION ALL
+                SELECT MIN(CASE WHEN length(sale_date) = 10 THEN sale_date || ' 00:00:00' ELSE sale_date END)
+                FROM {} WHERE product_id = products.id
+            )",
+            source_table("stock_movements", true),
+            source_table("sales", true)
+        );
+        let products_created_at = conn
+            .execute(
+                &format!(
+                    "UPDATE products SET created_at = ({fa})
+                     WHERE ({fa}) IS NOT NULL AND (created_at IS NULL OR created_at > ({fa}))",
+                    fa = first_activity
+                ),
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        let products_updated_at = fix_updated_at("products")?;
+
+        let products_without_history: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM products WHERE created_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(TimestampRepairReport {
+            stock_movements_updated_at,
+            users_updated_at,
+            products_created_at,
+            products_updated_at,
+            products_without_history,
+        })
+    })();
+
+    match result {
+        Ok(report) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            Ok(report)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Unidades de medida admitidas. Solo las de peso aceptan cantidades fraccionarias.
+const PRODUCT_UNITS: [&str; 4] = ["unidad", "kg", "g", "porción"];
+const FRACTIONAL_UNITS: [&str; 2] = ["kg", "g"];
+
+/// Decimales admitidos en una cantidad (gramos cuando la unidad es kg).
+const QUANTITY_DECIMALS: i32 = 3;
+
+/// Tolerancia al comparar cantidades REAL (stock, depósitos pendientes).
+const QUANTITY_EPSILON: f64 = 0.0005;
+
+/// Estados de un producto. Solo los activos se pueden vender.
+const PRODUCT_STATUSES: [&str; 4] = ["activo", "inactivo", "descontinuado", "eliminado"];
+const PRODUCT_STATUS_ACTIVE: &str = "activo";
+
+/// Valida el estado de un producto sin distinguir mayúsculas y lo devuelve en
+/// su forma canónica (en minúsculas); vacío equivale a "activo".
+fn normalize_status(status: Option<&str>) -> Result<String, String> {
+    let status = status.map(str::trim).unwrap_or_default();
+    if status.is_empty() {
+        return Ok(PRODUCT_STATUS_ACTIVE.to_string());
+    }
+    PRODUCT_STATUSES
+        .iter()
+        .find(|s| s.eq_ignore_ascii_case(status))
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("Estado inválido: '{}'. Usa {}", status, PRODUCT_STATUSES.join(", ")))
+}
+
+/// Filtro por estado de los listados: vacío no filtra; si viene, debe ser
+/// un estado válido.
+fn status_filter(status: Option<&str>) -> Result<Option<String>, String> {
+    match status.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(status) => normalize_status(Some(status)).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Nota del ingreso que crea `add_product` con `initial_stock`.
+const INITIAL_STOCK_NOTE: &str = "stock inicial";
+
+/// Nota que reciben los ingresos que `add_product` creaba por `max_stock`
+/// antes de existir `initial_stock`, para poder encontrarlos y corregirlos.
+const LEGACY_MAX_STOCK_NOTE: &str = "stock inicial (automático por max_stock)";
+
+/// Marca con `LEGACY_MAX_STOCK_NOTE` los ingresos automáticos de versiones
+/// anteriores: el primer movimiento del producto, sin nota y con la cantidad
+/// de `max_stock`. Corre una sola vez, al pasar a la versión 24 del esquema.
+fn migrate_max_stock_ingresos(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE stock_movements SET note = ?1
+         WHERE type = 'ingreso' AND note IS NULL
+           AND id = (SELECT MIN(m.id) FROM stock_movements m WHERE m.product_id = stock_movements.product_id)
+           AND quantity = (SELECT p.max_stock FROM products p WHERE p.id = stock_movements.product_id AND p.max_stock > 0)",
+        [LEGACY_MAX_STOCK_NOTE],
+    )?;
+    Ok(())
+}
+
+/// Lleva los estados guardados a la forma de `PRODUCT_STATUSES` (los vacíos
+/// quedan "activo"). Los que no corresponden a ningún estado se dejan como
+/// están: no se pueden vender hasta que alguien elija uno válido.
+fn migrate_product_statuses(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE products SET status = ?1 WHERE TRIM(COALESCE(status, '')) = ''",
+        [PRODUCT_STATUS_ACTIVE],
+    )?;
+    for status in PRODUCT_STATUSES {
+        conn.execute(
+            "UPDATE products SET status = ?1 WHERE LOWER(TRIM(status)) = LOWER(?1) AND status <> ?1",
+            [status],
+        )?;
+    }
+    Ok(())
+}
+
+/// Prefijo de los códigos internos. GS1 reserva 200–299 para uso dentro de
+/// la tienda, así que no choca con el EAN-13 de ningún proveedor.
+const INTERNAL_BARCODE_PREFIX: &str = "200";
+
+/// Dígito verificador EAN-13 de los primeros 12 dígitos.
+fn ean13_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .take(12)
+        .enumerate()
+        .map(|(i, d)| u32::from(*d) * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Código de barras de un producto sin espacios alrededor (vacío = ninguno).
+/// Los de 13 dígitos deben tener un dígito verificador EAN-13 correcto, y
+/// ningún otro producto (salvo `product_id`) puede tener el mismo.
+fn validate_barcode(conn: &Connection, barcode: Option<&str>, product_id: Option<i32>) -> Result<Option<String>, String> {
+    let Some(barcode) = barcode.map(str::trim).filter(|b| !b.is_empty()) else {
+        return Ok(None);
+    };
+    if barcode.len() == 13 && barcode.bytes().all(|b| b.is_ascii_digit()) {
+        let digits: Vec<u8> = barcode.bytes().map(|b| b - b'0').collect();
+        if ean13_check_digit(&digits) != digits[12] {
+            return Err(format!(
+                "El código de barras {} no es un EAN-13 válido: el dígito verificador debería ser {}",
+                barcode,
+                ean13_check_digit(&digits)
+            ));
+        }
+    }
+    let taken: Option<String> = conn
+        .query_row(
+            "SELECT name FROM products WHERE barcode = ?1 AND id IS NOT ?2",
+            rusqlite::params![barcode, product_id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    match taken {
+        Some(name) => Err(format!("El código de barras {} ya está asignado a {}", barcode, name)),
+        None => Ok(Some(barcode.to_string())),
+    }
+}
+
+/// Asigna al producto un EAN-13 interno (prefijo `INTERNAL_BARCODE_PREFIX`,
+/// luego el id del producto y el dígito verificador) para etiquetar lo que
+/// no trae código del proveedor, como los bultos a granel. Si ese código ya
+/// está tomado prueba los siguientes. Devuelve el código asignado.
+#[tauri::command]
+fn generate_internal_barcode<R: Runtime>(window: Window<R>, state: State<AppState>, product_id: i32) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (current, deleted): (Option<String>, bool) = conn
+        .query_row(
+            "SELECT barcode, deleted_at IS NOT NULL FROM products WHERE id = ?1",
+            [product_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if deleted {
+        return Err("El producto fue eliminado del catálogo".to_string());
+    }
+    if let Some(current) = current {
+        return Err(format!("El producto ya tiene el código de barras {}", current));
+    }
+    let mut sequence = product_id as u64;
+    let barcode = loop {
+        let body = format!("{}{:09}", INTERNAL_BARCODE_PREFIX, sequence);
+        if body.len() != 12 {
+            return Err("No quedan códigos internos disponibles".to_string());
+        }
+        let digits: Vec<u8> = body.bytes().map(|b| b - b'0').collect();
+        let candidate = format!("{}{}", body, ean13_check_digit(&digits));
+        let taken: bool = conn
+            .query_row("SELECT EXISTS(SELECT 1 FROM products WHERE barcode = ?1)", [&candidate], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if !taken {
+            break candidate;
+        }
+        sequence += 1;
+    };
+    conn.execute(
+        "UPDATE products SET barcode = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        rusqlite::params![product_id, barcode],
+    )
+    .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "products", Some(product_id as i64));
+    Ok(barcode)
+}
+
+/// Valida la unidad de un producto; vacía equivale a "unidad".
+fn normalize_unit(unit: Option<&str>) -> Result<String, String> {
+    let unit = unit.map(|u| u.trim().to_lowercase()).unwrap_or_default();
+    if unit.is_empty() {
+        return Ok("unidad".to_string());
+    }
+    let unit = if unit == "porcion" { "porción".to_string() } else { unit };
+    if PRODUCT_UNITS.contains(&unit.as_str()) {
+        Ok(unit)
+    } else {
+        Err(format!(
+            "Unidad de medida inválida: '{}'. Usa {}",
+            unit,
+            PRODUCT_UNITS.join(", ")
+        ))
+    }
+}
+
+/// Unidad de compra y factor de conversión de un producto, ya validados:
+/// ambos o ninguno, el factor mayor a 0 y la unidad de compra distinta de la
+/// base (`unit`, ya normalizada).
+fn normalize_purchase_unit(product: &Product, unit: &str) -> Result<(Option<String>, Option<f64>), String> {
+    let purchase_unit = product
+        .purchase_unit
+        .as_deref()
+        .map(|u| u.trim().to_lowercase())
+        .filter(|u| !u.is_empty());
+    if let Some(factor) = product.conversion_factor {
+        if !factor.is_finite() || factor <= 0.0 {
+            return Err(format!("El factor de conversión debe ser mayor a 0 (se recibió {})", factor));
+        }
+    }
+    match (purchase_unit, product.conversion_factor) {
+        (None, None) => Ok((None, None)),
+        (Some(purchase_unit), _) if purchase_unit == unit => Err(format!(
+            "La unidad de compra no puede ser la misma que la unidad base ({})",
+            unit
+        )),
+        (Some(purchase_unit), Some(factor)) => Ok((Some(purchase_unit), Some(factor))),
+        (Some(purchase_unit), None) => Err(format!(
+            "Indica cuántas unidades ({}) trae cada {}",
+            unit, purchase_unit
+        )),
+        (None, Some(_)) => Err("Indica la unidad de compra del factor de conversión".to_string()),
+    }
+}
+
+/// Convierte `quantity`, expresada en `unit`, a la unidad base del producto.
+/// Sin `unit`, o con la unidad base, la cantidad ya está en la base.
+fn to_base_quantity(conn: &Connection, product_id: i32, quantity: f64, unit: Option<&str>) -> Result<f64, String> {
+    let Some(unit) = unit.map(|u| u.trim().to_lowercase()).filter(|u| !u.is_empty()) else {
+        return Ok(quantity);
+    };
+    let (base, purchase_unit, factor): (Option<String>, Option<String>, Option<f64>) = conn
+        .query_row(
+            "SELECT unit, purchase_unit, conversion_factor FROM products WHERE id = ?1",
+            [product_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    let base = base.unwrap_or_else(|| "unidad".to_string());
+    if unit == base || (unit == "porcion" && base == "porción") {
+        return Ok(quantity);
+    }
+    match (purchase_unit, factor) {
+        (Some(purchase_unit), Some(factor)) if purchase_unit == unit => Ok(quantity * factor),
+        (Some(purchase_unit), _) => Err(format!(
+            "Unidad '{}' no válida para este producto. Usa {} o {}",
+            unit, base, purchase_unit
+        )),
+        (None, _) => Err(format!(
+            "Unidad '{}' no válida para este producto. Usa {}",
+            unit, base
+        )),
+    }
+}
+
+/// El descuento de una línea es un porcentaje (10 = 10%), no un importe ni una
+/// fracción: 0.10 es un descuento del 0.1%.
+fn validate_discount(discount: Option<f64>) -> Result<(), String> {
+    match discount {
+        Some(d) if !d.is_finite() || !(0.0..=100.0).contains(&d) => Err(format!(
+            "El descuento es un porcentaje entre 0 y 100 ({}); usa 10 para el 10%",
+            d
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Medios de pago aceptados cuando no está configurado `payment_methods`.
+const DEFAULT_PAYMENT_METHODS: &str = "efectivo,tarjeta,yape,transferencia";
+
+/// Único medio de pago que entra al cajón. Las ventas sin medio de pago
+/// (anteriores a la columna) cuentan como efectivo.
+const CASH_PAYMENT_METHOD: &str = "efectivo";
+
+/// Condición SQL de las ventas de contado cobradas en efectivo. Las ventas al
+/// crédito entran al cajón con cada pago en efectivo de `sale_payments`.
+const CASH_SALE_SQL: &str = "COALESCE(is_credit, 0) = 0 AND COALESCE(payment_method, 'efectivo') = 'efectivo'";
+
+/// Medios de pago del ajuste `payment_methods` (separados por comas), en
+/// minúsculas.
+fn payment_methods(conn: &Connection) -> Vec<String> {
+    let configured = get_setting(conn, "payment_methods").unwrap_or_default();
+    let methods: Vec<String> = configured
+        .split(',')
+        .map(|m| m.trim().to_lowercase())
+        .filter(|m| !m.is_empty())
+        .collect();
+    if methods.is_empty() {
+        DEFAULT_PAYMENT_METHODS.split(',').map(String::from).collect()
+    } else {
+        methods
+    }
+}
+
+/// Medio de pago de una venta: uno de `payment_methods`, o efectivo si no
+/// viene.
+fn sale_payment_method(conn: &Connection, method: Option<&str>) -> Result<String, String> {
+    let method = method.map(|m| m.trim().to_lowercase()).filter(|m| !m.is_empty());
+    let Some(method) = method else {
+        return Ok(CASH_PAYMENT_METHOD.to_string());
+    };
+    let methods = payment_methods(conn);
+    if methods.contains(&method) {
+        Ok(method)
+    } else {
+        Err(format!("Medio de pago '{}' no válido. Usa {}", method, methods.join(", ")))
+    }
+}
+
+/// Valida una cantidad de venta o movimiento: positiva, con a lo sumo
+/// `QUANTITY_DECIMALS` decimales y entera salvo en productos por peso.
+fn validate_quantity(conn: &Connection, product_id: i32, quantity: f64) -> Result<(), String> {
+    if !quantity.is_finite() || quantity <= 0.0 {
+        return Err("La cantidad debe ser mayor a 0".to_string());
+    }
+    let scale = 10f64.powi(QUANTITY_DECIMALS);
+    if ((quantity * scale).round() - quantity * scale).abs() > 1e-6 {
+        return Err(format!(
+            "La cantidad admite como máximo {} decimales",
+            QUANTITY_DECIMALS
+        ));
+    }
+    let (unit, deleted): (Option<String>, bool) = conn
+        .query_row(
+            "SELECT unit, deleted_at IS NOT NULL FROM products WHERE id = ?1",
+            rusqlite::params![product_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| "Producto no encontrado".to_string())?;
+    if deleted {
+        return Err("El producto fue eliminado del catálogo".to_string());
+    }
+    let unit = unit.unwrap_or_else(|| "unidad".to_string());
+    if quantity.fract() != 0.0 && !FRACTIONAL_UNITS.contains(&unit.as_str()) {
+        return Err(format!(
+            "El producto se vende por {}; la cantidad debe ser entera",
+            unit
+        ));
+    }
+    Ok(())
+}
+
+/// Columnas de `products` en el orden que espera `product_from_row`.
+const PRODUCT_COLUMNS: &str = "id, sku, name, sale_price, cost_price, brand, category, presentation, flavor, weight, image_path, expiry_date, lot_number, min_stock, max_stock, location, status, deposit_amount, unit, deleted_at, thumbnail_path, parent_id, category_id, brand_id, barcode, purchase_unit, conversion_factor, description, internal_notes, default_supplier, tax_rate, currency";
+
+fn product_from_row(row: &rusqlite::Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        id: row.get(0)?,
+        sku: row.get(1)?,
+        name: row.get(2)?,
+        sale_price: row.get(3)?,
+        cost_price: row.get(4)?,
+        brand: row.get(5)?,
+        category: row.get(6)?,
+        presentation: row.get(7)?,
+        flavor: row.get(8)?,
+        weight: row.get(9)?,
+        image_path: row.get(10)?,
+        thumbnail_path: row
+            .get::<_, Option<String>>(20)?
+            .map(|path| resolve_image_path(&path).to_string_lossy().into_owned()),
+        expiry_date: row.get(11)?,
+        lot_number: row.get(12)?,
+        min_stock: row.get(13)?,
+        max_stock: row.get(14)?,
+        location: row.get(15)?,
+        status: row.get(16)?,
+        deposit_amount: row.get(17)?,
+        unit: row.get(18)?,
+        allowed_channels: None,
+        deleted_at: row.get(19)?,
+        parent_id: row.get(21)?,
+        category_id: row.get(22)?,
+        brand_id: row.get(23)?,
+        barcode: row.get(24)?,
+        purchase_unit: row.get(25)?,
+        conversion_factor: row.get(26)?,
+        description: row.get(27)?,
+        internal_notes: row.get(28)?,
+        default_supplier: row.get(29)?,
+        tax_rate: row.get(30)?,
+        currency: row.get(31)?,
+        initial_stock: None,
+    })
+}
+
+/// Proveedor habitual sin espacios sobrantes; vacío se guarda como NULL.
+fn product_supplier(product: &Product) -> Option<&str> {
+    product.default_supplier.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+// Tauri commands
+/// Columnas por las que puede ordenar `get_products`; el valor se interpola en
+/// el SQL, así que solo se aceptan estas.
+const PRODUCT_SORT_COLUMNS: &[(&str, &str)] = &[
+    ("name", "name COLLATE NOCASE"),
+    ("sku", "sku COLLATE NOCASE"),
+    ("sale_price", "sale_price"),
+    ("category", "category COLLATE NOCASE"),
+    ("created", "id"),
+];
+
+/// Una página de `get_products` y el total de productos que cumplen el filtro.
+#[derive(Debug, Serialize)]
+struct ProductPage {
+    items: Vec<Product>,
+    total_count: i64,
+}
+
+/// Resultado de `get_products`: la lista completa, como siempre, o una página
+/// si se pidió `limit` u `offset`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ProductList {
+    All(Vec<Product>),
+    Page(ProductPage),
+}
+
+/// Catálogo completo, o solo los productos de `brand_id` o en `status`. Los
+/// productos eliminados solo aparecen con `include_deleted`, que queda
+/// reservado al Administrador. `sort_by` (name, sku, sale_price, category o
+/// created, el orden de alta por defecto) y `sort_dir` (asc o desc) ordenan;
+/// con `limit` u `offset` devuelve una `ProductPage`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_products<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    include_deleted: Option<bool>,
+    brand_id: Option<i32>,
+    status: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    sort_by: Option<String>,
+    sort_dir: Option<String>,
+) -> Result<ProductList, String> {
+    let include_deleted = include_deleted.unwrap_or(false);
+    if include_deleted {
+        require_admin(&state, &window)?;
+    }
+    let status = status_filter(status.as_deref())?;
+    let sort_key = sort_by.as_deref().map(str::trim).unwrap_or("created");
+    let order_col = PRODUCT_SORT_COLUMNS
+        .iter()
+        .find(|(key, _)| *key == sort_key)
+        .map(|(_, col)| *col)
+        .ok_or_else(|| format!("Orden '{}' no válido. Usa name, sku, sale_price, category o created", sort_key))?;
+    let dir = match sort_dir.as_deref().map(|d| d.trim().to_lowercase()).as_deref() {
+        None | Some("asc") => "ASC",
+        Some("desc") => "DESC",
+        Some(other) => return Err(format!("Dirección '{}' no válida. Usa asc o desc", other)),
+    };
+    let paged = limit.is_some() || offset.is_some();
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let filter = format!(
+        "(?1 IS NULL OR brand_id = ?1) AND (?2 IS NULL OR status = ?2){}",
+        if include_deleted { "" } else { " AND deleted_at IS NULL" }
+    );
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM products WHERE {} ORDER BY {} {}, id {} LIMIT ?3 OFFSET ?4",
+            PRODUCT_COLUMNS, filter, order_col, dir, dir
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let mut products = stmt
+        .query_map(
+            rusqlite::params![brand_id, status, limit.unwrap_or(-1).max(-1), offset.unwrap_or(0).max(0)],
+            product_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut channels = all_product_channels(&conn)?;
+    for product in products.iter_mut() {
+        let list = product.id.and_then(|id| channels.remove(&id)).unwrap_or_default();
+        product.allowed_channels = Some(list);
+    }
+
+    if !paged {
+        return Ok(ProductList::All(products));
+    }
+    let total_count: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM products WHERE {}", filter),
+            rusqlite::params![brand_id, status],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(ProductList::Page(ProductPage { items: products, total_count }))
+}
+
+/// Productos del catálogo cuyo proveedor habitual es `supplier` (sin
+/// distinguir mayúsculas ni espacios alrededor), por nombre.
+#[tauri::command]
+fn get_products_by_supplier(state: State<AppState>, supplier: String) -> Result<Vec<Product>, String> {
+    let supplier = supplier.trim();
+    if supplier.is_empty() {
+        return Err("Indica el proveedor".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM products
+             WHERE deleted_at IS NULL AND TRIM(default_supplier) = ?1 COLLATE NOCASE
+             ORDER BY name COLLATE NOCASE, id",
+            PRODUCT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut products = stmt
+        .query_map([supplier], product_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut channels = all_product_channels(&conn)?;
+    for product in products.iter_mut() {
+        let list = product.id.and_then(|id| channels.remove(&id)).unwrap_or_default();
+        product.allowed_channels = Some(list);
+    }
+    Ok(products)
+}
+
+/// Un producto por id, con las mismas columnas que `get_products`. También
+/// devuelve los eliminados (con `deleted_at`) para las vistas de historial.
+#[tauri::command]
+fn get_product_by_id(state: State<AppState>, id: i32) -> Result<Product, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut product = conn
+        .query_row(
+            &format!("SELECT {} FROM products WHERE id = ?1", PRODUCT_COLUMNS),
+            rusqlite::params![id],
+            product_from_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    product.allowed_channels = Some(product_channels(&conn, id)?);
+    Ok(product)
+}
+
+/// Comienzo del error de `get_product_by_sku` cuando ningún producto tiene ese
+/// código; la interfaz lo distingue para ofrecer crear el producto.
+const UNKNOWN_SKU: &str = "Código no registrado";
+
+/// Producto cuyo SKU o código de barras coincide exactamente con `code` (lo
+/// que envía el lector), sin espacios alrededor; si un SKU y un código de
+/// barras coinciden, gana el SKU. Usa los índices únicos de ambas columnas.
+/// Si no existe o está eliminado, el error empieza con `UNKNOWN_SKU`.
+#[tauri::command]
+fn get_product_by_sku(state: State<AppState>, code: String) -> Result<Product, String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err("El código está vacío".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut product = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM products WHERE (sku = ?1 OR barcode = ?1) AND deleted_at IS NULL
+                 ORDER BY sku IS ?1 DESC LIMIT 1",
+                PRODUCT_COLUMNS
+            ),
+            rusqlite::params![code],
+            product_from_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("{}: {}", UNKNOWN_SKU, code),
+            e => e.to_string(),
+        })?;
+    if let Some(id) = product.id {
+        product.allowed_channels = Some(product_channels(&conn, id)?);
+    }
+    Ok(product)
+}
+
+/// Una página de `search_products` y el total de coincidencias.
+#[derive(Debug, Serialize)]
+struct ProductSearchResult {
+    products: Vec<Product>,
+    /// Stock de cada producto, en el mismo orden que `products`
+    stock: Vec<ProductAvailability>,
+    total: i64,
+}
+
+/// Stock que puede prometer el punto de venta. Un pack no tiene stock propio:
+/// `available` son los packs completos que alcanzan a armarse con sus
+/// componentes.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductAvailability {
+    product_id: i32,
+    physical: f64,
+    available: f64,
+}
+
+/// Stock de `product` (una expresión SQL con su id) según sus movimientos.
+fn stock_sql(product: &str) -> String {
+    format!(
+        "COALESCE((SELECT SUM(CASE WHEN m.type='ingreso' THEN m.quantity WHEN m.type='egreso' THEN -m.quantity ELSE 0 END)
+                   FROM stock_movements m WHERE m.product_id = {}), 0)",
+        product
+    )
+}
+
+/// `ProductAvailability::available` de `product` en SQL: su stock o, si es
+/// un pack, los packs completos que alcanzan sus componentes.
+fn available_stock_sql(product: &str) -> String {
+    format!(
+        "CASE WHEN EXISTS(SELECT 1 FROM product_bundles WHERE bundle_id = {product})
+              THEN (SELECT MIN(CAST(MAX({component}, 0) / b.quantity AS INTEGER))
+                    FROM product_bundles b WHERE b.bundle_id = {product})
+              ELSE {physical} END",
+        product = product,
+        component = stock_sql("b.component_id"),
+        physical = stock_sql(product)
+    )
+}
+
+/// Busca productos por texto libre (nombre, SKU, código de barras o marca,
+/// sin distinguir mayúsculas), categoría, marca (`brand_id`), estado y
+/// etiquetas (`tags`: debe tenerlas todas), ordenados por nombre y paginados con
+/// `limit`/`offset`. Sin texto ni filtros devuelve todo el catálogo paginado
+/// (sin los productos eliminados). Incluye el stock físico y disponible de
+/// cada producto, calculados en la misma consulta.
+#[tauri::command]
+fn search_products(
+    state: State<AppState>,
+    query: Option<String>,
+    category: Option<String>,
+    status: Option<String>,
+    brand_id: Option<i32>,
+    tags: Option<Vec<String>>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<ProductSearchResult, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let lim = limit.unwrap_or(50).max(1);
+    let off = offset.unwrap_or(0).max(0);
+    // % y _ escritos por el usuario se buscan literalmente
+    let pattern = query
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+    let category = category.filter(|c| !c.trim().is_empty());
+    let status = status_filter(status.as_deref())?;
+    // Todas las etiquetas pedidas (AND), como arreglo JSON para `json_each`
+    let tags = normalize_tags(&tags.unwrap_or_default());
+    let tag_count = tags.len() as i64;
+    let tags = serde_json::to_string(&tags).map_err(|e| e.to_string())?;
+    let filter = "deleted_at IS NULL
+                  AND (?1 IS NULL OR name LIKE ?1 ESCAPE '\\' OR sku LIKE ?1 ESCAPE '\\' OR barcode LIKE ?1 ESCAPE '\\' OR brand LIKE ?1 ESCAPE '\\')
+                  AND (?2 IS NULL OR category = TRIM(?2) COLLATE NOCASE)
+                  AND (?3 IS NULL OR status = ?3)
+                  AND (?4 IS NULL OR brand_id = ?4)
+                  AND (?5 = 0 OR (SELECT COUNT(*) FROM product_tags t
+                                  WHERE t.product_id = products.id AND t.tag IN (SELECT value FROM json_each(?6))) = ?5)";
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM products WHERE {}", filter),
+            rusqlite::params![pattern, category, status, brand_id, tag_count, tags],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {columns}, {physical}, {available}
+             FROM products WHERE {filter} ORDER BY name COLLATE NOCASE, id LIMIT ?7 OFFSET ?8",
+            columns = PRODUCT_COLUMNS,
+            physical = stock_sql("products.id"),
+            available = available_stock_sql("products.id"),
+            filter = filter
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![pattern, category, status, brand_id, tag_count, tags, lim, off], |row| {
+            let product = product_from_row(row)?;
+            let stock = ProductAvailability {
+                product_id: row.get(0)?,
+                physical: row.get(32)?,
+                available: row.get(33)?,
+            };
+            Ok((product, stock))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let (mut products, stock): (Vec<Product>, Vec<ProductAvailability>) = rows.into_iter().unzip();
+    for product in products.iter_mut() {
+        if let Some(id) = product.id {
+            product.allowed_channels = Some(product_channels(&conn, id)?);
+        }
+    }
+    Ok(ProductSearchResult { products, stock, total })
+}
+
+/// Coincidencia de `search_products_quick`: solo lo que muestra la lista del
+/// buscador del punto de venta.
+#[derive(Debug, Serialize)]
+struct QuickSearchHit {
+    id: i32,
+    sku: Option<String>,
+    name: String,
+    price: Option<f64>,
+    available: f64,
+}
+
+/// Máximo de coincidencias de `search_products_quick`.
+const QUICK_SEARCH_MAX_RESULTS: i32 = 50;
+
+/// Búsqueda mientras se escribe en el punto de venta, pensada para prefijos
+/// de 2 o 3 letras. Ordena primero el SKU o código de barras exacto, después
+/// los nombres que empiezan con `query` y al final los que la contienen (en
+/// el nombre o el SKU). El stock disponible se calcula solo para las
+/// coincidencias devueltas, no para todo el catálogo.
+#[tauri::command]
+fn search_products_quick(state: State<AppState>, query: String, limit: Option<i32>) -> Result<Vec<QuickSearchHit>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let lim = limit.unwrap_or(10).clamp(1, QUICK_SEARCH_MAX_RESULTS);
+    // % y _ escritos por el usuario se buscan literalmente
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "WITH hits AS (
+                SELECT id, sku, name, sale_price,
+                       CASE WHEN sku = ?1 COLLATE NOCASE OR barcode = ?1 THEN 0
+                            WHEN name LIKE ?2 ESCAPE '\\' THEN 1
+                            ELSE 2 END AS rank
+                FROM products
+                WHERE deleted_at IS NULL
+                  AND (sku = ?1 COLLATE NOCASE OR barcode = ?1 OR name LIKE ?3 ESCAPE '\\' OR sku LIKE ?3 ESCAPE '\\')
+                ORDER BY rank, name COLLATE NOCASE, id
+                LIMIT ?4
+             )
+             SELECT id, sku, name, sale_price, {available} FROM hits ORDER BY rank, name COLLATE NOCASE, id",
+            available = available_stock_sql("hits.id")
+        ))
+        .map_err(|e| e.to_string())?;
+    let hits = stmt
+        .query_map(
+            rusqlite::params![query, format!("{}%", escaped), format!("%{}%", escaped), lim],
+            |row| {
+                Ok(QuickSearchHit {
+                    id: row.get(0)?,
+                    sku: row.get(1)?,
+                    name: row.get(2)?,
+                    price: row.get(3)?,
+                    available: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(hits)
+}
+
+/// Resultado de dar de alta un producto: `sku` es el enviado o, si vino
+/// vacío, el generado.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductCreated {
+    id: i64,
+    sku: String,
+}
+
+/// Intentos de generar un SKU libre antes de rendirse (otro proceso puede
+/// tomar el mismo número entre la consulta y el INSERT).
+const SKU_GENERATION_ATTEMPTS: i64 = 5;
+
+/// Prefijo de los SKU generados para una categoría: el ajuste
+/// `sku_prefix.<categoría>` o, si no existe, sus tres primeras letras sin
+/// tildes ("PRO-" para Proteínas). Sin categoría, "SKU-".
+fn sku_prefix(conn: &Connection, category: Option<&str>) -> String {
+    let category = category.map(str::trim).filter(|c| !c.is_empty());
+    let Some(category) = category else {
+        return "SKU-".to_string();
+    };
+    if let Some(prefix) = get_setting(conn, &format!("sku_prefix.{}", category)) {
+        if !prefix.trim().is_empty() {
+            return prefix.trim().to_string();
+        }
+    }
+    let letters: String = category
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| match c.to_lowercase().next().unwrap_or(c) {
+            'á' | 'à' | 'ä' => 'A',
+            'é' | 'è' | 'ë' => 'E',
+            'í' | 'ì' | 'ï' => 'I',
+            'ó' | 'ò' | 'ö' => 'O',
+            'ú' | 'ù' | 'ü' => 'U',
+            'ñ' => 'N',
+            other => other.to_ascii_uppercase(),
+        })
+        .take(3)
+        .collect();
+    if letters.is_empty() {
+        "SKU-".to_string()
+    } else {
+        format!("{}-", letters)
+    }
+}
+
+/// Siguiente SKU `<prefijo><número de 5 dígitos>`, a partir del mayor número
+/// ya usado con ese prefijo (incluidos productos eliminados). `offset` salta
+/// números en los reintentos.
+fn next_sku(conn: &Connection, prefix: &str, offset: i64) -> Result<String, String> {
+    let last: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(CAST(substr(sku, length(?1) + 1) AS INTEGER)), 0) FROM products
+             WHERE substr(sku, 1, length(?1)) = ?1
+               AND length(sku) > length(?1)
+               AND substr(sku, length(?1) + 1) NOT GLOB '*[^0-9]*'",
+            [prefix],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(format!("{}{:05}", prefix, last + 1 + offset))
+}
+
+/// Da de alta un producto. Si `sku` viene vacío se genera uno con el prefijo de
+/// su categoría (ver `sku_prefix`). Las variantes se crean con
+/// `add_product_variant`.
+#[tauri::command]
+fn add_product<R: Runtime>(window: Window<R>, state: State<AppState>, product: Product) -> Result<ProductCreated, String> {
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let created = insert_product(&conn, &product, None, created_by, true)?;
+    emit_data_changed(&window, "products", Some(created.id));
+    Ok(created)
+}
+
+/// Inserta un producto (o la variante de `parent_id`) con sus canales y, si
+/// `initial_stock`, el ingreso "stock inicial" de `product.initial_stock`.
+fn insert_product(
+    conn: &Connection,
+    product: &Product,
+    parent_id: Option<i32>,
+    created_by: Option<i32>,
+    initial_stock: bool,
+) -> Result<ProductCreated, String> {
+    validate_product(product).map_err(|errors| errors.join("\n"))?;
+    let unit = normalize_unit(product.unit.as_deref())?;
+    let status = normalize_status(product.status.as_deref())?;
+    let barcode = validate_barcode(conn, product.barcode.as_deref(), None)?;
+    let (purchase_unit, conversion_factor) = normalize_purchase_unit(product, &unit)?;
+    let (category_id, category) = resolve_category(conn, product.category_id, product.category.as_deref())?;
+    let (brand_id, brand) = resolve_brand(conn, product.brand_id, product.brand.as_deref())?;
+
+    let explicit_sku = product.sku.clone().filter(|sku| !sku.trim().is_empty());
+    if let Some(ref sku_val) = explicit_sku {
+        let existing = conn.query_row(
+            "SELECT deleted_at IS NOT NULL FROM products WHERE sku = ?1 LIMIT 1",
+            rusqlite::params![sku_val],
+            |row| row.get::<_, bool>(0),
+        );
+        match existing {
+            Ok(true) => {
+                return Err("El SKU pertenece a un producto eliminado. Púrgalo antes de reutilizar el SKU.".to_string());
+            }
+            Ok(false) => {
+                return Err("El SKU ya existe. Usa otro SKU o edita el producto existente.".to_string());
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let cost_currency = product_currency(conn, product);
+    let insert = |sku: &str| {
+        conn.execute(
+            "INSERT INTO products (sku, name, sale_price, cost_price, brand, category, presentation, flavor, weight, image_path, expiry_date, lot_number, min_stock, max_stock, location, status, deposit_amount, unit, parent_id, category_id, brand_id, barcode, purchase_unit, conversion_factor, description, internal_notes, default_supplier, tax_rate, currency, created_at, updated_at) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            rusqlite::params![
+                sku,
+                product.name,
+                product.sale_price,
+                product.cost_price,
+                brand,
+                category,
+                product.presentation,
+                product.flavor,
+                product.weight,
+                product.image_path,
+                product.expiry_date,
+                product.lot_number,
+                product.min_stock,
+                product.max_stock,
+                product.location,
+                status,
+                product.deposit_amount,
+                unit,
+                parent_id,
+                category_id,
+                brand_id,
+                barcode,
+                purchase_unit,
+                conversion_factor,
+                product.description,
+                product.internal_notes,
+                product_supplier(product),
+                product.tax_rate,
+                cost_currency,
+            ],
+        )
+        .map_err(|e| e.to_string())
+    };
+    let sku_conflict = |msg: &str| msg.contains("UNIQUE constraint failed: products.sku");
+    let sku = match explicit_sku {
+        Some(sku) => {
+            insert(&sku).map_err(|msg| {
+                if sku_conflict(&msg) {
+                    "El SKU ya existe. Usa otro SKU o edita el producto existente.".to_string()
+                } else {
+                    msg
+                }
+            })?;
+            sku
+        }
+        None => {
+            let prefix = sku_prefix(conn, category.as_deref());
+            let mut attempt = 0;
+            loop {
+                let sku = next_sku(conn, &prefix, attempt)?;
+                match insert(&sku) {
+                    Ok(_) => break sku,
+                    Err(msg) if sku_conflict(&msg) && attempt + 1 < SKU_GENERATION_ATTEMPTS => attempt += 1,
+                    Err(msg) => return Err(msg),
+                }
+            }
+        }
+    };
+
+    let new_id = conn.last_insert_rowid();
+    sync_retail_price(conn, new_id, product.sale_price)?;
+
+    if let Some(ref channels) = product.allowed_channels {
+        set_product_channels(conn, new_id as i32, channels)?;
+    }
+
+    if let Some(qty) = product.initial_stock.filter(|qty| initial_stock && *qty > 0) {
+        conn.execute(
+            "INSERT INTO stock_movements (product_id, type, quantity, note, created_by) VALUES (?1, 'ingreso', ?2, ?3, ?4)",
+            rusqlite::params![new_id as i32, qty, INITIAL_STOCK_NOTE, created_by],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(ProductCreated { id: new_id, sku })
+}
+
+/// Crea una variante (tamaño, sabor…) de `parent_id`. Hereda marca y
+/// categoría si no vienen; ventas y stock se registran sobre la variante.
+#[tauri::command]
+fn add_product_variant<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    parent_id: i32,
+    mut product: Product,
+) -> Result<ProductCreated, String> {
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let parent = conn
+        .query_row(
+            &format!("SELECT {} FROM products WHERE id = ?1", PRODUCT_COLUMNS),
+            [parent_id],
+            product_from_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto padre no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if parent.deleted_at.is_some() {
+        return Err("El producto padre fue eliminado del catálogo".to_string());
+    }
+    if parent.parent_id.is_some() {
+        return Err("Una variante no puede tener variantes; usa su producto padre".to_string());
+    }
+    if product.brand.is_none() && product.brand_id.is_none() {
+        product.brand = parent.brand;
+        product.brand_id = parent.brand_id;
+    }
+    if product.category.is_none() && product.category_id.is_none() {
+        product.category = parent.category;
+        product.category_id = parent.category_id;
+    }
+    let created = insert_product(&conn, &product, Some(parent_id), created_by, true)?;
+    emit_data_changed(&window, "products", Some(created.id));
+    Ok(created)
+}
+
+/// Crea un producto a partir de `source_id` (por ejemplo, otro sabor del
+/// mismo suplemento): copia la ficha, los canales, las etiquetas y los precios
+/// de las listas y aplica los campos no nulos de `overrides` (un `name` vacío
+/// conserva el del original).
+/// SKU y código de barras no se copian; sin SKU se genera uno. Si el original
+/// es una variante, el clon es variante del mismo padre. No ingresa stock. La
+/// foto se comparte con el original, o con `copy_image` se copia a archivos
+/// propios del clon.
+#[tauri::command]
+fn clone_product<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    source_id: i32,
+    overrides: Product,
+    copy_image: Option<bool>,
+) -> Result<ProductCreated, String> {
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let source = conn
+        .query_row(
+            &format!("SELECT {} FROM products WHERE id = ?1", PRODUCT_COLUMNS),
+            [source_id],
+            product_from_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if source.deleted_at.is_some() {
+        return Err("El producto fue eliminado del catálogo".to_string());
+    }
+    // `product_from_row` resuelve la miniatura; aquí hacen falta las rutas guardadas
+    let (image_path, thumbnail_path): (Option<String>, Option<String>) = conn
+        .query_row("SELECT image_path, thumbnail_path FROM products WHERE id = ?1", [source_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let (category, category_id) = if overrides.category.is_none() && overrides.category_id.is_none() {
+        (source.category, source.category_id)
+    } else {
+        (overrides.category, overrides.category_id)
+    };
+    let (brand, brand_id) = if overrides.brand.is_none() && overrides.brand_id.is_none() {
+        (source.brand, source.brand_id)
+    } else {
+        (overrides.brand, overrides.brand_id)
+    };
+    let product = Product {
+        id: None,
+        sku: overrides.sku,
+        name: if overrides.name.trim().is_empty() { source.name } else { overrides.name },
+        sale_price: overrides.sale_price.or(source.sale_price),
+        cost_price: overrides.cost_price.or(source.cost_price),
+        brand,
+        category,
+        presentation: overrides.presentation.or(source.presentation),
+        flavor: overrides.flavor.or(source.flavor),
+        weight: overrides.weight.or(source.weight),
+        image_path: None,
+        thumbnail_path: None,
+        expiry_date: overrides.expiry_date.or(source.expiry_date),
+        lot_number: overrides.lot_number.or(source.lot_number),
+        min_stock: overrides.min_stock.or(source.min_stock),
+        max_stock: overrides.max_stock.or(source.max_stock),
+        location: overrides.location.or(source.location),
+        status: overrides.status.or(source.status),
+        deposit_amount: overrides.deposit_amount.or(source.deposit_amount),
+        unit: overrides.unit.or(source.unit),
+        purchase_unit: overrides.purchase_unit.or(source.purchase_unit),
+        conversion_factor: overrides.conversion_factor.or(source.conversion_factor),
+        allowed_channels: Some(match overrides.allowed_channels {
+            Some(channels) => channels,
+            None => product_channels(&conn, source_id)?,
+        }),
+        deleted_at: None,
+        parent_id: None,
+        category_id,
+        brand_id,
+        barcode: overrides.barcode,
+        description: overrides.description.or(source.description),
+        internal_notes: overrides.internal_notes.or(source.internal_notes),
+        default_supplier: overrides.default_supplier.or(source.default_supplier),
+        tax_rate: overrides.tax_rate.or(source.tax_rate),
+        currency: overrides.currency.or(source.currency),
+        initial_stock: None,
+    };
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let mut copied: Vec<PathBuf> = Vec::new();
+    let result: Result<ProductCreated, String> = (|| {
+        let created = insert_product(&conn, &product, source.parent_id, created_by, false)?;
+        let new_id = created.id as i32;
+        conn.execute(
+            "INSERT INTO product_tags (product_id, tag) SELECT ?1, tag FROM product_tags WHERE product_id = ?2",
+            rusqlite::params![new_id, source_id],
+        )
+        .map_err(|e| e.to_string())?;
+        // La minorista ya quedó con el precio de venta del clon
+        conn.execute(
+            "INSERT INTO product_prices (product_id, price_list_id, price)
+             SELECT ?1, pp.price_list_id, pp.price FROM product_prices pp JOIN price_lists l ON l.id = pp.price_list_id
+             WHERE pp.product_id = ?2 AND l.name <> ?3",
+            rusqlite::params![new_id, source_id, RETAIL_PRICE_LIST],
+        )
+        .map_err(|e| e.to_string())?;
+        let (image_path, thumbnail_path) = if copy_image.unwrap_or(false) {
+            // Solo se copian los archivos de la carpeta de imágenes; una ruta
+            // cargada a mano es un archivo del usuario y se sigue compartiendo
+            let mut copy = |stored: Option<String>, name: String| -> Result<Option<String>, String> {
+                let Some(stored) = stored else {
+                    return Ok(None);
+                };
+                let Some(file) = managed_image_files(&[Some(stored.clone())]).pop() else {
+                    return Ok(Some(stored));
+                };
+                let target = product_images_dir()?.join(name);
+                fs::copy(&file, &target).map_err(|e| io_error(&file, e))?;
+                copied.push(target.clone());
+                managed_image_path(&target).map(Some)
+            };
+            let extension = image_path
+                .as_deref()
+                .and_then(|path| Path::new(path).extension())
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("img")
+                .to_string();
+            (
+                copy(image_path, format!("product_{}.{}", new_id, extension))?,
+                copy(thumbnail_path, format!("product_{}_mini.jpg", new_id))?,
+            )
+        } else {
+            (image_path, thumbnail_path)
+        };
+        conn.execute(
+            "UPDATE products SET image_path = ?2, thumbnail_path = ?3 WHERE id = ?1",
+            rusqlite::params![new_id, image_path, thumbnail_path],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(created)
+    })();
+    match result {
+        Ok(created) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "products", Some(created.id));
+            Ok(created)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            for file in copied {
+                let _ = fs::remove_file(file);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Variantes vigentes de un producto, por nombre.
+#[tauri::command]
+fn get_product_variants(state: State<AppState>, parent_id: i32) -> Result<Vec<Product>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM products WHERE parent_id = ?1 AND deleted_at IS NULL ORDER BY name, id",
+            PRODUCT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut variants = stmt
+        .query_map([parent_id], product_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut channels = all_product_channels(&conn)?;
+    for variant in variants.iter_mut() {
+        let list = variant.id.and_then(|id| channels.remove(&id)).unwrap_or_default();
+        variant.allowed_channels = Some(list);
+    }
+    Ok(variants)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductFamily {
+    product_id: i32,
+    sku: Option<String>,
+    name: String,
+    category: Option<String>,
+    variant_count: i64,
+    /// Stock del producto padre más el de sus variantes
+    stock: f64,
+    units_sold: f64,
+    revenue: f64,
+}
+
+/// Familias de productos: cada producto que no es variante, con el stock y
+/// las ventas del período sumados sobre sus variantes (y él mismo).
+#[tauri::command]
+fn get_product_families(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<ProductFamily>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let sql = format!(
+        "WITH members AS (
+             SELECT id, COALESCE(parent_id, id) AS family_id FROM products
+         ),
+         stock AS (
+             SELECT m.family_id,
+                    SUM(CASE WHEN sm.type = 'ingreso' THEN sm.quantity ELSE -sm.quantity END) AS qty
+             FROM stock_movements sm
+             JOIN members m ON m.id = sm.product_id
+             GROUP BY m.family_id
+         ),
+         sold AS (
+             SELECT m.family_id, SUM(s.quantity) AS qty, SUM(s.sale_price) AS revenue
+             FROM {sales} s
+             JOIN members m ON m.id = s.product_id
+             WHERE (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+               AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+             GROUP BY m.family_id
+         )
+         SELECT p.id, p.sku, p.name, p.category,
+                (SELECT COUNT(*) FROM products v WHERE v.parent_id = p.id AND v.deleted_at IS NULL),
+                COALESCE(st.qty, 0), COALESCE(so.qty, 0), COALESCE(so.revenue, 0.0)
+         FROM products p
+         LEFT JOIN stock st ON st.family_id = p.id
+         LEFT JOIN sold so ON so.family_id = p.id
+         WHERE p.parent_id IS NULL AND p.deleted_at IS NULL
+         ORDER BY p.name, p.id",
+        sales = valid_sales(include_archive.unwrap_or(false))
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let families = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            Ok(ProductFamily {
+                product_id: row.get(0)?,
+                sku: row.get(1)?,
+                name: row.get(2)?,
+                category: row.get(3)?,
+                variant_count: row.get(4)?,
+                stock: row.get(5)?,
+                units_sold: row.get(6)?,
+                revenue: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(families)
+}
+
+/// Actualiza un producto. Los cambios de `sale_price` o `cost_price` quedan en
+/// `price_history` a nombre del usuario de la sesión.
+#[tauri::command]
+fn update_product<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    product: Product,
+) -> Result<(), String> {
+    validate_product(&product).map_err(|errors| errors.join("\n"))?;
+    let unit = normalize_unit(product.unit.as_deref())?;
+    let status = normalize_status(product.status.as_deref())?;
+    let (purchase_unit, conversion_factor) = normalize_purchase_unit(&product, &unit)?;
+    let changed_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let old_prices: Option<(Option<f64>, Option<f64>)> = match conn.query_row(
+        "SELECT sale_price, cost_price FROM products WHERE id = ?1",
+        rusqlite::params![product.id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(prices) => Some(prices),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        let (category_id, category) = resolve_category(&conn, product.category_id, product.category.as_deref())?;
+        let (brand_id, brand) = resolve_brand(&conn, product.brand_id, product.brand.as_deref())?;
+        let barcode = validate_barcode(&conn, product.barcode.as_deref(), product.id)?;
+        let cost_currency = product_currency(&conn, &product);
+        conn.execute(
+            "UPDATE products SET sku=?1, name=?2, sale_price=?3, cost_price=?4, brand=?5, category=?6, presentation=?7, flavor=?8, weight=?9, image_path=?10, thumbnail_path=CASE WHEN image_path IS ?10 THEN thumbnail_path END, expiry_date=?11, lot_number=?12, min_stock=?13, max_stock=?14, location=?15, status=?16, deposit_amount=?17, unit=?18, category_id=?20, brand_id=?21, barcode=?22, purchase_unit=?23, conversion_factor=?24, description=?25, internal_notes=?26, default_supplier=?27, tax_rate=?28, currency=?29, updated_at=CURRENT_TIMESTAMP 
+             WHERE id=?19",
+            rusqlite::params![
+                product.sku,
+                product.name,
+                product.sale_price,
+                product.cost_price,
+                brand,
+                category,
+                product.presentation,
+                product.flavor,
+                product.weight,
+                product.image_path,
+                product.expiry_date,
+                product.lot_number,
+                product.min_stock,
+                product.max_stock,
+                product.location,
+                status,
+                product.deposit_amount,
+                unit,
+                product.id,
+                category_id,
+                brand_id,
+                barcode,
+                purchase_unit,
+                conversion_factor,
+                product.description,
+                product.internal_notes,
+                product_supplier(&product),
+                product.tax_rate,
+                cost_currency,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        if let Some(id) = product.id {
+            sync_retail_price(&conn, id as i64, product.sale_price)?;
+        }
+        if let (Some(id), Some((old_sale, old_cost))) = (product.id, old_prices) {
+            record_price_change(&conn, id, "sale_price", old_sale, product.sale_price, changed_by, "update_product")?;
+            record_price_change(&conn, id, "cost_price", old_cost, product.cost_price, changed_by, "update_product")?;
+        }
+
+        // Sin `allowed_channels` se conservan los canales actuales
+        if let (Some(id), Some(ref channels)) = (product.id, &product.allowed_channels) {
+            set_product_channels(&conn, id, channels)?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "products", product.id.map(i64::from));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Baja lógica: el producto deja de aparecer en el catálogo, las búsquedas y
+/// el punto de venta, pero su fila se conserva para que los reportes de ventas
+/// y movimientos sigan mostrando el nombre. Para borrarlo del todo está
+/// `purge_product`.
+#[tauri::command]
+fn delete_product<R: Runtime>(window: Window<R>, state: State<AppState>, id: i32) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let images: (Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT image_path, thumbnail_path FROM products WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    // Las fotos que copió la aplicación se borran; una ruta cargada a mano
+    // apunta a un archivo del usuario y se conserva
+    let managed_image = !managed_image_files(std::slice::from_ref(&images.0)).is_empty();
+    conn.execute(
+        "UPDATE products SET deleted_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP,
+                image_path = CASE WHEN ?2 THEN NULL ELSE image_path END, thumbnail_path = NULL
+         WHERE id = ?1",
+        rusqlite::params![id, managed_image],
+    )
+    .map_err(|e| e.to_string())?;
+    for file in unshared_image_files(&conn, id, &[images.0, images.1])? {
+        let _ = fs::remove_file(file);
+    }
+    emit_data_changed(&window, "products", Some(id as i64));
+    Ok(())
+}
+
+/// Ventas, movimientos de stock (ambos con lo archivado) y compras que hacen
+/// referencia a un producto.
+fn product_references(conn: &Connection, id: i32) -> Result<(i64, i64, i64), String> {
+    conn.query_row(
+        &format!(
+            "SELECT (SELECT COUNT(*) FROM {} WHERE product_id=?1),
+                    (SELECT COUNT(*) FROM {} WHERE product_id=?1),
+                    (SELECT COUNT(*) FROM purchases WHERE product_id=?1)",
+            source_table("sales", true),
+            source_table("stock_movements", true)
+        ),
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// "El producto tiene 12 ventas y 30 movimientos asociados", con solo las
+/// cantidades distintas de cero; `None` si no hay referencias.
+fn references_message(sales: i64, movements: i64, purchases: i64) -> Option<String> {
+    let plural = |n: i64, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let parts: Vec<String> = [
+        (sales, "venta", "ventas"),
+        (movements, "movimiento", "movimientos"),
+        (purchases, "compra", "compras"),
+    ]
+    .iter()
+    .filter(|(n, ..)| *n > 0)
+    .map(|(n, one, many)| plural(*n, one, many))
+    .collect();
+    let list = match parts.split_last() {
+        None => return None,
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} y {}", rest.join(", "), last),
+    };
+    let adjective = match (movements > 0, sales + movements + purchases == 1) {
+        (true, true) => "asociado",
+        (true, false) => "asociados",
+        (false, true) => "asociada",
+        (false, false) => "asociadas",
+    };
+    Some(format!("El producto tiene {} {}", list, adjective))
+}
+
+/// Borra definitivamente un producto (eliminado o no) junto con su historial
+/// de precios, canales, promociones, adjuntos y composición de packs; sus
+/// variantes pasan a ser productos sueltos. Solo el Administrador puede hacerlo. Si
+/// alguna venta, movimiento de stock (incluido lo archivado) o compra hace
+/// referencia a él, lo rechaza con las cantidades para que la interfaz sugiera
+/// eliminarlo en su lugar; con `force` borra también esas filas (con sus canjes
+/// de saldo a favor, que vuelve a la tarjeta, y devoluciones de depósito) en
+/// la misma transacción, recalcula los resúmenes diarios y lo deja en la
+/// auditoría. Las ventas de un periodo cerrado exigen el permiso de ajuste.
+#[tauri::command]
+fn purge_product<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    id: i32,
+    force: Option<bool>,
+) -> Result<(), String> {
+    let session = require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name: String = conn
+        .query_row("SELECT name FROM products WHERE id=?1", [id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    let (sales, movements, purchases) = product_references(&conn, id)?;
+    let references = references_message(sales, movements, purchases);
+    let force = force.unwrap_or(false);
+    if let (Some(message), false) = (&references, force) {
+        return Err(message.clone());
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<PathBuf>, String> = (|| {
+        // Los adjuntos se borran con sus filas; los archivos (y las imágenes
+        // del producto), tras el COMMIT
+        let mut stmt = conn
+            .prepare(&format!("SELECT a.id, a.stored_path FROM attachments a WHERE {}", product_attachments_filter()))
+            .map_err(|e| e.to_string())?;
+        let attachments = stmt
+            .query_map([id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for (attachment_id, _) in &attachments {
+            conn.execute("DELETE FROM attachments WHERE id=?1", [attachment_id])
+                .map_err(|e| e.to_string())?;
+        }
+        // Borrar ventas de un periodo cerrado exige el permiso de ajuste
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT DISTINCT substr(sale_date,1,7) FROM {} WHERE product_id=?1",
+                source_table("sales", true)
+            ))
+            .map_err(|e| e.to_string())?;
+        let sale_months = stmt
+            .query_map([id], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let mut closed_months: Vec<String> = Vec::new();
+        for month in &sale_months {
+            if let Some(month) = check_period_open(&conn, Some(&session), month)? {
+                closed_months.push(month);
+            }
+        }
+        let (first_day, last_day): (Option<String>, Option<String>) = conn
+            .query_row(
+                &format!(
+                    "SELECT MIN(substr(sale_date,1,10)), MAX(substr(sale_date,1,10)) FROM {}
+                     WHERE product_id=?1 AND sale_date GLOB '{}'",
+                    source_table("sales", true),
+                    SALE_DAY_GLOB
+                ),
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        // El saldo a favor canjeado en esas ventas vuelve a su tarjeta
+        conn.execute(
+            &format!(
+                "UPDATE store_credits SET balance = balance + (
+                     SELECT COALESCE(SUM(r.amount),0.0) FROM store_credit_redemptions r
+                     WHERE r.credit_id = store_credits.id
+                       AND r.sale_id IN (SELECT id FROM {t} WHERE product_id=?1))
+                 WHERE id IN (SELECT credit_id FROM store_credit_redemptions
+                              WHERE sale_id IN (SELECT id FROM {t} WHERE product_id=?1))",
+                t = source_table("sales", true)
+            ),
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+        for linked in [
+            "payment_schedules",
+            "sale_payments",
+            "sale_payments_archive",
+            "store_credit_redemptions",
+            "deposit_refunds",
+        ] {
+            conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE sale_id IN (SELECT id FROM {} WHERE product_id=?1)",
+                    linked,
+                    source_table("sales", true)
+                ),
+                [id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        remove_sale_items(&conn, "product_id = ?1", [id])?;
+        for table in [
+            "sales",
+            "sales_archive",
+            "stock_movements",
+            "stock_movements_archive",
+            "purchases",
+            "price_history",
+            "product_channels",
+            "product_tags",
+            "product_prices",
+            "promotions",
+            "quote_items",
+        ] {
+            conn.execute(&format!("DELETE FROM {} WHERE product_id=?1", table), [id])
+                .map_err(|e| e.to_string())?;
+        }
+        let images: (Option<String>, Option<String>) = conn
+            .query_row("SELECT image_path, thumbnail_path FROM products WHERE id=?1", [id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        // Sus variantes quedan como productos sueltos y sale de los packs
+        conn.execute("DELETE FROM product_bundles WHERE bundle_id=?1 OR component_id=?1", [id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("UPDATE products SET parent_id=NULL WHERE parent_id=?1", [id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM products WHERE id=?1", [id])
+            .map_err(|e| e.to_string())?;
+        if let (Some(from), Some(to)) = (first_day, last_day) {
+            refresh_daily_summaries(&conn, Some((&from, &to))).map_err(|e| e.to_string())?;
+        }
+        if let Some(message) = &references {
+            audit(
+                &conn,
+                Some(session.user_id),
+                "purge_product",
+                "products",
+                Some(id as i64),
+                &format!("Purga forzada de {}. {}", name, message),
+                Some("purga_forzada"),
+            )?;
+        }
+        for month in &closed_months {
+            audit_post_close(&conn, Some(&session), "purge_product", "products", id as i64, month)?;
+        }
+        let mut files: Vec<PathBuf> = attachments.into_iter().map(|(_, path)| PathBuf::from(path)).collect();
+        files.extend(unshared_image_files(&conn, id, &[images.0, images.1])?);
+        Ok(files)
+    })();
+
+    match result {
+        Ok(files) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            for file in files {
+                let _ = fs::remove_file(file);
+            }
+            emit_data_changed(&window, "products", Some(id as i64));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+#[tauri::command]
+fn get_stock_movements(state: State<AppState>) -> Result<Vec<StockMovement>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, product_id, type, quantity, note, created_by FROM stock_movements ORDER BY created_at DESC LIMIT 100")
+        .map_err(|e| e.to_string())?;
+
+    let movements = stmt
+        .query_map([], |row| {
+            Ok(StockMovement {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                movement_type: row.get(2)?,
+                quantity: row.get(3)?,
+                note: row.get(4)?,
+                created_by: row.get(5)?,
+                unit: None,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(movements)
+}
+
+/// Registra un ingreso o egreso manual. Con `unit` igual a la unidad de
+/// compra del producto (p. ej. "caja") la cantidad se convierte a la unidad
+/// base antes de guardarse.
+#[tauri::command]
+fn add_stock_movement<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    movement: StockMovement,
+) -> Result<i64, String> {
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let quantity = to_base_quantity(&conn, movement.product_id, movement.quantity, movement.unit.as_deref())?;
+    validate_quantity(&conn, movement.product_id, quantity)?;
+    if is_bundle(&conn, movement.product_id)? {
+        return Err("Un pack no tiene stock propio; registra el movimiento en sus componentes".to_string());
+    }
+    // Los movimientos toman la fecha actual
+    let closed_month = check_period_open(&conn, session.as_ref(), &today_key(&conn)?)?;
+    conn.execute(
+        "INSERT INTO stock_movements (product_id, type, quantity, note, created_by) 
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            movement.product_id,
+            movement.movement_type,
+            quantity,
+            movement.note,
+            created_by,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let id = conn.last_insert_rowid();
+    if let Some(month) = closed_month {
+        audit_post_close(&conn, session.as_ref(), "add_stock_movement", "stock_movements", id, &month)?;
+    }
+    if movement.movement_type == "egreso" {
+        queue_out_of_stock(&conn, movement.product_id, quantity)?;
+    }
+    emit_data_changed(&window, "stock_movements", Some(id));
+    Ok(id)
+}
+
+/// Una página de `get_sales` o `get_sales_detailed` y el total de ventas que
+/// cumplen el filtro.
+#[derive(Debug, Serialize)]
+struct SalePage<T> {
+    items: Vec<T>,
+    total_count: i64,
+}
+
+/// Resultado de `get_sales`: las últimas 100 ventas, como siempre, o una
+/// página si se pidió `limit` u `offset`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum SaleList {
+    Latest(Vec<Sale>),
+    Page(SalePage<Sale>),
+}
+
+/// Filtro común de `get_sales` y `get_sales_detailed` sobre `sales s`:
+/// desde, hasta, producto, canal y vendedor (?1 a ?5).
+const SALE_LIST_FILTER: &str = "(?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+               AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+               AND (?3 IS NULL OR s.product_id = ?3)
+               AND (?4 IS NULL OR LOWER(TRIM(s.channel)) = LOWER(?4))
+               AND (?5 IS NULL OR s.created_by = ?5)";
+
+/// Ventas de la más reciente a la más antigua. Las fechas se comparan por
+/// día (`substr(sale_date,1,10)`) como en los reportes, y `channel` sin
+/// distinguir mayúsculas. Con `limit` u `offset` devuelve una `SalePage`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_sales(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    product_id: Option<i32>,
+    channel: Option<String>,
+    created_by: Option<i32>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<SaleList, String> {
+    let paged = limit.is_some() || offset.is_some();
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let lim = limit.unwrap_or(100).max(1);
+    let off = offset.unwrap_or(0).max(0);
+    let channel = channel.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT s.id, s.product_id, s.quantity, s.sale_price, s.discount, s.channel, s.sale_date, s.created_by,
+                    (SELECT c.code FROM store_credit_redemptions r
+                     JOIN store_credits c ON c.id = r.credit_id
+                     WHERE r.sale_id = s.id LIMIT 1) as store_credit_code,
+                    s.deposit_amount, s.customer, s.original_price, s.promotion_id, s.price_list_id, s.payment_method,
+                    s.is_credit, s.amount_paid,
+                    (SELECT h.receipt_number FROM sale_items i JOIN sale_headers h ON h.id = i.sale_id WHERE i.id = s.id),
+                    s.customer_id, s.note
+             FROM sales s
+             WHERE {}
+             ORDER BY s.sale_date DESC, s.id DESC
+             LIMIT ?6 OFFSET ?7",
+            SALE_LIST_FILTER
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let sales = stmt
+        .query_map(rusqlite::params![start_date, end_date, product_id, channel, created_by, lim, off], |row| {
+            Ok(Sale {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                quantity: row.get(2)?,
+                sale_price: row.get(3)?,
+                discount: row.get(4)?,
+                channel: row.get(5)?,
+                sale_date: row.get(6)?,
+                created_by: row.get(7)?,
+                store_credit_code: row.get(8)?,
+                deposit_amount: row.get(9)?,
+                customer: row.get(10)?,
+                customer_id: row.get(18)?,
+                original_price: row.get(11)?,
+                promotion_id: row.get(12)?,
+                installments: None,
+                price_list_id: row.get(13)?,
+                allow_inactive: false,
+                price_override: false,
+                receipt_number: row.get(17)?,
+                payment_method: row.get(14)?,
+                is_credit: row.get(15)?,
+                amount_paid: row.get(16)?,
+                note: row.get(19)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    if !paged {
+        return Ok(SaleList::Latest(sales));
+    }
+    let total_count: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM sales s WHERE {}", SALE_LIST_FILTER),
+            rusqlite::params![start_date, end_date, product_id, channel, created_by],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(SaleList::Page(SalePage { items: sales, total_count }))
+}
+
+/// Texto en lugar del nombre cuando el producto de una venta ya no existe.
+const MISSING_PRODUCT_NAME: &str = "(producto eliminado)";
+
+/// Venta con los datos del producto y del vendedor, para listarla sin
+/// cruzarla con el catálogo en la interfaz.
+#[derive(Debug, Serialize)]
+struct SaleDetail {
+    id: i64,
+    receipt_number: Option<String>,
+    product_id: i32,
+    /// `MISSING_PRODUCT_NAME` si el producto fue purgado
+    product_name: String,
+    sku: Option<String>,
+    category: Option<String>,
+    /// El producto fue eliminado del catálogo o ya no existe
+    product_deleted: bool,
+    quantity: f64,
+    sale_price: f64,
+    discount: Option<f64>,
+    channel: Option<String>,
+    sale_date: String,
+    created_by: Option<i32>,
+    seller: Option<String>,
+    customer: Option<String>,
+    payment_method: Option<String>,
+}
+
+/// Columnas de `SaleDetail` sobre `sales s` con `products p` y `users u`
+/// unidos por LEFT JOIN; se leen con `sale_detail_from_row`.
+const SALE_DETAIL_COLUMNS: &str = "s.id, s.product_id, p.name, p.sku, p.category, p.id IS NULL OR p.deleted_at IS NOT NULL,
+                    s.quantity, s.sale_price, s.discount, s.channel, s.sale_date, s.created_by, u.username, s.customer, s.payment_method,
+                    (SELECT h.receipt_number FROM sale_items i JOIN sale_headers h ON h.id = i.sale_id WHERE i.id = s.id)";
+
+fn sale_detail_from_row(row: &rusqlite::Row) -> rusqlite::Result<SaleDetail> {
+    Ok(SaleDetail {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        product_name: row.get::<_, Option<String>>(2)?.unwrap_or_else(|| MISSING_PRODUCT_NAME.to_string()),
+        sku: row.get(3)?,
+        category: row.get(4)?,
+        product_deleted: row.get(5)?,
+        quantity: row.get(6)?,
+        sale_price: row.get(7)?,
+        discount: row.get(8)?,
+        channel: row.get(9)?,
+        sale_date: row.get(10)?,
+        created_by: row.get(11)?,
+        seller: row.get(12)?,
+        customer: row.get(13)?,
+        payment_method: row.get(14)?,
+        receipt_number: row.get(15)?,
+    })
+}
+
+/// Como `get_sales`, con el nombre, SKU y categoría del producto y el usuario
+/// que registró cada venta. Siempre pagina (100 por defecto).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_sales_detailed(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    product_id: Option<i32>,
+    channel: Option<String>,
+    created_by: Option<i32>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<SalePage<SaleDetail>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let lim = limit.unwrap_or(100).max(1);
+    let off = offset.unwrap_or(0).max(0);
+    let channel = channel.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}
+             FROM sales s
+             LEFT JOIN products p ON p.id = s.product_id
+             LEFT JOIN users u ON u.id = s.created_by
+             WHERE {}
+             ORDER BY s.sale_date DESC, s.id DESC
+             LIMIT ?6 OFFSET ?7",
+            SALE_DETAIL_COLUMNS, SALE_LIST_FILTER
+        ))
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map(
+            rusqlite::params![start_date, end_date, product_id, channel, created_by, lim, off],
+            sale_detail_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let total_count: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM sales s WHERE {}", SALE_LIST_FILTER),
+            rusqlite::params![start_date, end_date, product_id, channel, created_by],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(SalePage { items, total_count })
+}
+
+#[tauri::command]
+fn add_sale<R: Runtime>(window: Window<R>, state: State<AppState>, mut sale: Sale) -> Result<SaleResult, String> {
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let inserted = record_sale(&conn, session.as_ref(), "add_sale", &mut sale, created_by)?;
+    emit_data_changed(&window, "sales", Some(inserted.id));
+    Ok(inserted)
+}
+
+/// Registra una venta suelta en su propia transacción, como `add_sale`:
+/// normaliza la fecha, toma el total del precio de lista si no viene, exige
+/// ese total salvo autorización (`check_price_override`) y revisa el periodo;
+/// `action` es el comando que la registra, para la auditoría. Quien la llama
+/// avisa del cambio.
+fn record_sale(
+    conn: &Connection,
+    session: Option<&Session>,
+    action: &str,
+    sale: &mut Sale,
+    created_by: Option<i32>,
+) -> Result<SaleResult, String> {
+    sale.sale_date = normalize_sale_date(conn, &sale.sale_date)?;
+    // Antes de calcular el total: fuera de 0–100 no hay descuento que aplicar
+    validate_discount(sale.discount)?;
+    let (_, list_price) = sale_list_price(conn, sale)?;
+    let sale_price = match sale.sale_price {
+        Some(price) => price,
+        None => {
+            let unit_price = list_price.ok_or("El producto no tiene precio de venta")?;
+            let total = line_total_cents(&RoundingRule::load(conn), unit_price, sale.quantity, sale.discount.unwrap_or(0.0));
+            from_cents(total)
+        }
+    };
+    // Se guarda al centavo, igual que lo muestra `compute_sale_totals`
+    sale.sale_price = Some(round_money(sale_price));
+    check_price_override(conn, sale, list_price, session)?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result = (|| {
+        let closed_month = check_period_open(conn, session, &sale.sale_date)?;
+        let inserted = insert_sale(conn, sale, created_by, session)?;
+        if let Some(month) = closed_month {
+            audit_post_close(conn, session, action, "sales", inserted.id, &month)?;
+        }
+        Ok(inserted)
+    })();
+    match result {
+        Ok(inserted) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            Ok(inserted)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Comienzo del error de venta cuando no alcanza el stock (del producto o de
+/// los componentes de un pack); la caja lo distingue de `UNKNOWN_SKU`.
+const INSUFFICIENT_STOCK: &str = "Stock insuficiente";
+
+/// Resultado de `sell_by_sku`: la venta y lo que muestra la caja al escanear.
+#[derive(Debug, Serialize)]
+struct SkuSaleResult {
+    sale_id: i64,
+    receipt_number: Option<String>,
+    product_id: i32,
+    product_name: String,
+    sale_price: f64,
+    /// Stock disponible después de la venta (packs completos si es un pack)
+    remaining_stock: f64,
+    warning: Option<String>,
+}
+
+/// Venta rápida desde el lector de códigos: busca el producto por SKU o
+/// código de barras como `get_product_by_sku`, cobra su precio de ficha por
+/// `quantity` (1 si no viene) con la fecha y hora actuales y la registra igual
+/// que `add_sale`. Si el código no existe el error empieza con `UNKNOWN_SKU`;
+/// si falta stock, con `INSUFFICIENT_STOCK`.
+#[tauri::command]
+fn sell_by_sku<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    sku: String,
+    quantity: Option<f64>,
+    payment_method: Option<String>,
+) -> Result<SkuSaleResult, String> {
+    let code = sku.trim();
+    if code.is_empty() {
+        return Err("El código está vacío".to_string());
+    }
+    let quantity = quantity.unwrap_or(1.0);
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (product_id, product_name, unit_price, now): (i32, String, f64, String) = conn
+        .query_row(
+            &format!(
+                "SELECT id, name, sale_price, strftime('{}', 'now') FROM products
+                 WHERE (sku = ?1 OR barcode = ?1) AND deleted_at IS NULL
+                 ORDER BY sku IS ?1 DESC LIMIT 1",
+                SALE_DATETIME_FORMAT
+            ),
+            [code],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("{}: {}", UNKNOWN_SKU, code),
+            e => e.to_string(),
+        })?;
+    let sale_price = from_cents(line_total_cents(&RoundingRule::load(&conn), unit_price, quantity, 0.0));
+    let mut sale = Sale {
+        id: None,
+        product_id,
+        quantity,
+        sale_price: Some(sale_price),
+        discount: None,
+        channel: None,
+        sale_date: now,
+        created_by,
+        store_credit_code: None,
+        deposit_amount: None,
+        customer: None,
+        customer_id: None,
+        original_price: None,
+        promotion_id: None,
+        installments: None,
+        price_list_id: None,
+        allow_inactive: false,
+        price_override: false,
+        receipt_number: None,
+        payment_method,
+        is_credit: false,
+        amount_paid: None,
+        note: None,
+    };
+    let inserted = record_sale(&conn, session.as_ref(), "sell_by_sku", &mut sale, created_by)?;
+    let remaining_stock: f64 = conn
+        .query_row(&format!("SELECT {}", available_stock_sql("?1")), [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "sales", Some(inserted.id));
+    Ok(SkuSaleResult {
+        sale_id: inserted.id,
+        receipt_number: inserted.receipt_number,
+        product_id,
+        product_name,
+        sale_price,
+        remaining_stock,
+        warning: inserted.warning,
+    })
+}
+
+/// Corrige una venta registrada: producto, cantidad, precio, descuento, canal,
+/// cliente o fecha. El egreso de la venta se ajusta a la nueva cantidad (si
+/// cambia el producto, el stock vuelve al anterior y se descuenta del nuevo)
+/// y la fila guarda quién la editó y cuándo; el detalle va a la auditoría.
+/// No se editan ventas anuladas, de packs ni archivadas; en un periodo cerrado
+/// hace falta el permiso de ajuste y el cambio queda marcado en la auditoría.
+#[tauri::command]
+fn update_sale<R: Runtime>(window: Window<R>, state: State<AppState>, mut sale: Sale) -> Result<SaleResult, String> {
+    let session = window_session(&state, window.label()).ok_or("No hay una sesión activa en esta ventana")?;
+    let id = sale.id.ok_or("Falta el id de la venta")? as i64;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (old_product, old_quantity, old_price, old_date, voided): (i32, f64, f64, String, bool) = conn
+        .query_row(
+            "SELECT product_id, quantity, sale_price, sale_date, voided_at IS NOT NULL FROM sales WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Venta #{} no encontrada", id),
+            e => e.to_string(),
+        })?;
+    if voided {
+        return Err(format!("La venta #{} está anulada", id));
+    }
+    sale.sale_date = normalize_sale_date(&conn, &sale.sale_date)?;
+    // Sin precio se conserva el que tenía
+    let sale_price = round_money(sale.sale_price.unwrap_or(old_price));
+    if !sale_price.is_finite() || sale_price < 0.0 {
+        return Err("El precio de venta no puede ser negativo".to_string());
+    }
+    sale.sale_price = Some(sale_price);
+    // La promoción de la venta no se aplica a otro producto
+    if sale.product_id != old_product {
+        sale.promotion_id = None;
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result: Result<SaleResult, String> = (|| {
+        // Tanto el mes de origen como el de destino deben admitir el cambio
+        let mut closed_months: Vec<String> = Vec::new();
+        for date in [&old_date, &sale.sale_date] {
+            if let Some(month) = check_period_open(&conn, Some(&session), date)? {
+                if !closed_months.contains(&month) {
+                    closed_months.push(month);
+                }
+            }
+        }
+        validate_quantity(&conn, sale.product_id, sale.quantity)?;
+        validate_discount(sale.discount)?;
+        let payment_method = match sale.payment_method.as_deref() {
+            Some(method) => Some(sale_payment_method(&conn, Some(method))?),
+            None => None,
+        };
+        // Una venta al crédito no puede quedar por debajo de lo ya cobrado
+        let (amount_paid, old_customer_id, old_reference, old_discount): (Option<f64>, Option<i64>, Option<f64>, Option<f64>) =
+            conn.query_row(
+                "SELECT amount_paid, customer_id, reference_price, discount FROM sales WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let customer = sale_customer(
+            &conn,
+            sale.customer_id,
+            sale.customer.as_deref(),
+            sale.customer_id == old_customer_id,
+        )?;
+        let note = sale_note(sale.note.as_deref())?;
+        if let Some(paid) = amount_paid.filter(|paid| sale_price < paid - 0.005) {
+            return Err(format!("El total no puede quedar por debajo de lo ya cobrado ({:.2})", paid));
+        }
+        if is_bundle(&conn, old_product)? || is_bundle(&conn, sale.product_id)? {
+            return Err("Las ventas de packs no se pueden editar".to_string());
+        }
+        if sale.product_id != old_product {
+            check_sale_status(&conn, &sale, Some(&session))?;
+        }
+        check_sale_channel(&conn, sale.product_id, sale.channel.as_deref())?;
+        let movement = sale_movement(&conn, id, None)?
+            .ok_or_else(|| format!("La venta #{} no tiene un egreso de stock asociado; corrígela a mano", id))?;
+        // Lo que falta descontar: la diferencia, o todo si cambió el producto
+        let needed = if sale.product_id == old_product { sale.quantity - old_quantity } else { sale.quantity };
+        if needed > QUANTITY_EPSILON {
+            let available = product_stock(&conn, sale.product_id)?;
+            if needed > available + QUANTITY_EPSILON {
+                return Err(format!("{}. Disponible: {}, solicitado: {}", INSUFFICIENT_STOCK, available, needed));
+            }
+        }
+        // El precio de lista es el que tenía la venta (sin su descuento) o,
+        // si cambió el producto, el del nuevo
+        let list_price = if sale.product_id == old_product {
+            let old_discount = old_discount.unwrap_or(0.0).clamp(0.0, 100.0);
+            old_reference.filter(|_| old_discount < 100.0).map(|r| r / (1.0 - old_discount / 100.0))
+        } else {
+            sale_list_price(&conn, &sale)?.1
+        };
+        let repriced = sale.product_id != old_product
+            || (sale.quantity - old_quantity).abs() > QUANTITY_EPSILON
+            || sale.discount.unwrap_or(0.0) != old_discount.unwrap_or(0.0)
+            || to_cents(sale_price) != to_cents(old_price);
+        if repriced {
+            check_price_override(&conn, &sale, list_price, Some(&session))?;
+        }
+        let reference = price_reference(&conn, &sale, list_price);
+        conn.execute(
+            "UPDATE stock_movements SET product_id = ?1, quantity = ?2, sale_id = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?4",
+            rusqlite::params![sale.product_id, sale.quantity, id, movement],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let below_cost = check_below_cost(&conn, &sale, Some(&session))?;
+        let tax_amount = included_tax(sale_price, effective_tax_rate(&conn, sale.product_id)?);
+        conn.execute(
+            "UPDATE sales SET product_id = ?1, quantity = ?2, sale_price = ?3, discount = ?4, channel = ?5,
+                              customer = ?6, sale_date = ?7, below_cost = ?8, unit_cost = ?9, tax_amount = ?10,
+                              edited_by = ?11, edited_at = CURRENT_TIMESTAMP, payment_method = COALESCE(?13, payment_method),
+                              customer_id = ?14, note = ?15, reference_price = ?16, price_override = ?17,
+                              override_approved_by = CASE WHEN ?18 THEN ?19 ELSE override_approved_by END
+             WHERE id = ?12",
+            rusqlite::params![
+                sale.product_id,
+                sale.quantity,
+                sale_price,
+                sale.discount,
+                sale.channel,
+                customer,
+                sale.sale_date,
+                below_cost.is_some(),
+                below_cost.as_ref().map(|b| b.unit_cost),
+                tax_amount,
+                session.user_id,
+                id,
+                payment_method,
+                sale.customer_id,
+                note,
+                reference.map(|(price, _)| price),
+                reference.is_some_and(|(_, overridden)| overridden),
+                repriced,
+                needs_override(&conn, &sale, list_price).then_some(session.user_id),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        if let Some((reference_price, true)) = reference.filter(|_| repriced) {
+            notify_price_override(&conn, &sale, id, reference_price)?;
+        }
+        conn.execute(
+            "UPDATE sale_items SET product_id = ?1, quantity = ?2, unit_price = ?3, discount = ?4, line_total = ?5 WHERE id = ?6",
+            rusqlite::params![
+                sale.product_id,
+                sale.quantity,
+                implied_unit_price(sale_price, sale.quantity, sale.discount),
+                sale.discount,
+                sale_price,
+                id,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let (header_id, receipt_number): (i64, Option<String>) = conn
+            .query_row(
+                "SELECT i.sale_id, h.receipt_number FROM sale_items i JOIN sale_headers h ON h.id = i.sale_id WHERE i.id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        // Un comprobante de una sola línea sigue a su venta
+        conn.execute(
+            "UPDATE sale_headers SET customer = ?1, channel = ?2, sale_date = ?3
+             WHERE id = ?4 AND (SELECT COUNT(*) FROM sale_items WHERE sale_id = ?4) = 1",
+            rusqlite::params![customer, sale.channel, sale.sale_date, header_id],
+        )
+        .map_err(|e| e.to_string())?;
+        refresh_sale_header(&conn, header_id)?;
+        for date in [&old_date, &sale.sale_date] {
+            if let Some(day) = date.get(..10).filter(|day| is_valid_date(day)) {
+                refresh_daily_summaries(&conn, Some((day, day))).map_err(|e| e.to_string())?;
+            }
+        }
+        queue_out_of_stock(&conn, sale.product_id, needed)?;
+        audit(
+            &conn,
+            Some(session.user_id),
+            "update_sale",
+            "sales",
+            Some(id),
+            &format!(
+                "Venta #{}: producto {} → {}, cantidad {} → {}, total {} → {}, fecha {} → {}",
+                id,
+                old_product,
+                sale.product_id,
+                old_quantity,
+                sale.quantity,
+                format_amount(old_price),
+                format_amount(sale_price),
+                old_date,
+                sale.sale_date
+            ),
+            None,
+        )?;
+        for month in &closed_months {
+            audit_post_close(&conn, Some(&session), "update_sale", "sales", id, month)?;
+        }
+        Ok(SaleResult {
+            id,
+            header_id,
+            receipt_number,
+            below_cost: below_cost.is_some(),
+            warning: below_cost.map(|b| b.warning()),
+            tax_amount,
+        })
+    })();
+    match result {
+        Ok(updated) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "sales", Some(id));
+            Ok(updated)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Anula una venta: la fila queda con fecha, motivo y usuario de la
+/// anulación (no se borra), cada egreso de la venta se compensa con un
+/// ingreso y deja de contar en totales, tendencias y exportaciones. Devuelve
+/// el stock resultante de los productos repuestos.
+#[tauri::command]
+fn void_sale<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    sale_id: i64,
+    reason: String,
+) -> Result<Vec<StockBalance>, String> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("Indica el motivo de la anulación".to_string());
+    }
+    let voided_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (sale_date, voided): (String, bool) = conn
+        .query_row(
+            "SELECT sale_date, voided_at IS NOT NULL FROM sales WHERE id = ?1",
+            [sale_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Venta #{} no encontrada", sale_id),
+            e => e.to_string(),
+        })?;
+    if voided {
+        return Err(format!("La venta #{} ya fue anulada", sale_id));
+    }
+    let linked: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM store_credit_redemptions WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM deposit_refunds WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM payment_schedules WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM sale_payments WHERE sale_id = ?1)",
+            [sale_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if linked {
+        return Err(format!(
+            "La venta #{} tiene saldo a favor, cuotas, pagos o devoluciones de depósito asociados; corrígela a mano",
+            sale_id
+        ));
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result: Result<Vec<StockBalance>, String> = (|| {
+        let closed_month = check_period_open(&conn, session.as_ref(), &sale_date)?;
+        // Los egresos de la venta (varios si es un pack); las ventas antiguas
+        // sin `sale_id` en el movimiento se buscan como en `resolve_duplicate`
+        let mut egresos: Vec<(i32, f64)> = {
+            let mut stmt = conn
+                .prepare("SELECT product_id, quantity FROM stock_movements WHERE sale_id = ?1 AND type = 'egreso' ORDER BY id")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([sale_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+        };
+        if egresos.is_empty() {
+            if let Some(movement) = sale_movement(&conn, sale_id, None)? {
+                egresos.push(
+                    conn.query_row("SELECT product_id, quantity FROM stock_movements WHERE id = ?1", [movement], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .map_err(|e| e.to_string())?,
+                );
+            }
+        }
+        let note = format!("Anulación de la venta #{}: {}", sale_id, reason);
+        let mut restored = Vec::new();
+        for (product_id, quantity) in egresos {
+            conn.execute(
+                "INSERT INTO stock_movements (product_id, type, quantity, note, created_by, sale_id)
+                 VALUES (?1, 'ingreso', ?2, ?3, ?4, ?5)",
+                rusqlite::params![product_id, quantity, note, voided_by, sale_id],
+            )
+            .map_err(|e| e.to_string())?;
+            restored.push(product_id);
+        }
+        conn.execute(
+            "UPDATE sales SET voided_at = CURRENT_TIMESTAMP, void_reason = ?1, voided_by = ?2 WHERE id = ?3",
+            rusqlite::params![reason, voided_by, sale_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let header_id: Option<i64> = conn
+            .query_row("SELECT sale_id FROM sale_items WHERE id = ?1", [sale_id], |row| row.get(0))
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e.to_string()),
+            })?;
+        if let Some(header_id) = header_id {
+            refresh_sale_header(&conn, header_id)?;
+        }
+        if let Some(day) = sale_date.get(..10).filter(|day| is_valid_date(day)) {
+            refresh_daily_summaries(&conn, Some((day, day))).map_err(|e| e.to_string())?;
+        }
+        audit(&conn, voided_by, "void_sale", "sales", Some(sale_id), &note, None)?;
+        if let Some(month) = closed_month {
+            audit_post_close(&conn, session.as_ref(), "void_sale", "sales", sale_id, &month)?;
+        }
+        restored.dedup();
+        restored
+            .into_iter()
+            .map(|product_id| {
+                Ok(StockBalance {
+                    product_id,
+                    current_stock: product_stock(&conn, product_id)?,
+                })
+            })
+            .collect()
+    })();
+    match result {
+        Ok(balances) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "sales", Some(sale_id));
+            Ok(balances)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Línea de un comprobante de venta.
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleItem {
+    /// El mismo id de su fila en `sales`
+    id: i64,
+    product_id: i32,
+    quantity: f64,
+    /// Precio por unidad antes del descuento
+    unit_price: f64,
+    /// Descuento porcentual, como en `sales.discount`
+    discount: Option<f64>,
+    line_total: f64,
+}
+
+/// Comprobante de venta con sus líneas.
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleReceipt {
+    id: i64,
+    receipt_number: Option<String>,
+    customer: Option<String>,
+    channel: Option<String>,
+    sale_date: String,
+    created_by: Option<i32>,
+    total: f64,
+    tax_amount: f64,
+    items: Vec<SaleItem>,
+    /// Avisos de las líneas vendidas bajo costo
+    #[serde(default)]
+    warnings: Vec<String>,
+}
+
+/// Línea de `create_sale` tal como la envía el frontend.
+#[derive(Debug, Serialize, Deserialize)]
+struct NewSaleItem {
+    product_id: i32,
+    quantity: f64,
+    /// Sin precio se usa el de `price_list_id` o, si no hay lista, el de la ficha
+    unit_price: Option<f64>,
+    discount: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NewSale {
+    customer: Option<String>,
+    /// Cliente registrado; su nombre reemplaza a `customer`
+    customer_id: Option<i64>,
+    channel: Option<String>,
+    sale_date: String,
+    created_by: Option<i32>,
+    price_list_id: Option<i64>,
+    /// Serie del número de comprobante; `DEFAULT_RECEIPT_SERIES` si no viene
+    receipt_series: Option<String>,
+    /// Medio de pago de todas las líneas
+    payment_method: Option<String>,
+    /// Todas las líneas quedan al crédito, sin pago inicial
+    #[serde(default)]
+    is_credit: bool,
+    /// Nota de todas las líneas
+    #[serde(default)]
+    note: Option<String>,
+    /// Un Administrador autoriza precios unitarios distintos de los de lista
+    #[serde(default, rename = "override")]
+    price_override: bool,
+    items: Vec<NewSaleItem>,
+}
+
+/// Registra un comprobante con varias líneas. Revisa el stock de todas antes
+/// de escribir (un pack cuenta por sus componentes) y guarda la cabecera, las
+/// líneas y sus egresos en una sola transacción.
+#[tauri::command]
+fn create_sale<R: Runtime>(window: Window<R>, state: State<AppState>, mut sale: NewSale) -> Result<SaleReceipt, String> {
+    if sale.items.is_empty() {
+        return Err("La venta no tiene productos".to_string());
+    }
+    let series = receipt_series(sale.receipt_series.as_deref())?;
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    sale.sale_date = normalize_sale_date(&conn, &sale.sale_date)?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result = insert_new_sale(&conn, session.as_ref(), "create_sale", &sale, &series, created_by);
+    match result {
+        Ok(receipt) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "sales", Some(receipt.id));
+            Ok(receipt)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Guarda un comprobante de `create_sale` dentro de la transacción abierta
+/// por quien la llama (el comando `action`): revisa el periodo y el stock de
+/// todas las líneas y registra la cabecera, las líneas y sus egresos.
+fn insert_new_sale(
+    conn: &Connection,
+    session: Option<&Session>,
+    action: &str,
+    sale: &NewSale,
+    series: &str,
+    created_by: Option<i32>,
+) -> Result<SaleReceipt, String> {
+    let closed_month = check_period_open(conn, session, &sale.sale_date)?;
+    check_sale_stock(conn, &sale.items)?;
+    let customer = sale_customer(conn, sale.customer_id, sale.customer.as_deref(), false)?;
+    let (header_id, _) = insert_sale_header(
+        conn,
+        series,
+        customer.as_deref(),
+        sale.channel.as_deref(),
+        &sale.sale_date,
+        created_by,
+    )?;
+    let rule = RoundingRule::load(conn);
+    let mut warnings = Vec::new();
+    for item in &sale.items {
+        let unit_price = item_unit_price(conn, item, sale.price_list_id)?;
+        validate_discount(item.discount)?;
+        let line = Sale {
+            id: None,
+            product_id: item.product_id,
+            quantity: item.quantity,
+            sale_price: Some(from_cents(line_total_cents(&rule, unit_price, item.quantity, item.discount.unwrap_or(0.0)))),
+            discount: item.discount,
+            channel: sale.channel.clone(),
+            sale_date: sale.sale_date.clone(),
+            created_by,
+            store_credit_code: None,
+            deposit_amount: None,
+            customer: customer.clone(),
+            customer_id: sale.customer_id,
+            original_price: None,
+            promotion_id: None,
+            installments: None,
+            price_list_id: sale.price_list_id,
+            allow_inactive: false,
+            price_override: sale.price_override,
+            receipt_number: None,
+            payment_method: sale.payment_method.clone(),
+            is_credit: sale.is_credit,
+            amount_paid: None,
+            note: sale.note.clone(),
+        };
+        // Un precio escrito a mano sigue la misma regla que en `add_sale`
+        if item.unit_price.is_some() {
+            let (_, list_price) = sale_list_price(conn, &line)?;
+            check_price_override(conn, &line, list_price, session)?;
+        } else {
+            check_discount_override(conn, &line, session)?;
+        }
+        let inserted = insert_sale_line(conn, &line, created_by, session)?;
+        insert_sale_item(conn, header_id, inserted.id, &line, unit_price)?;
+        warnings.extend(inserted.warning);
+    }
+    refresh_sale_header(conn, header_id)?;
+    if let Some(month) = closed_month {
+        audit_post_close(conn, session, action, "sale_headers", header_id, &month)?;
+    }
+    let mut receipt = load_sale_receipt(conn, header_id)?;
+    receipt.warnings = warnings;
+    Ok(receipt)
+}
+
+/// Precio unitario de una línea: el que trae, el de la lista `price_list_id`
+/// o el de la ficha del producto.
+fn item_unit_price(conn: &Connection, item: &NewSaleItem, price_list_id: Option<i64>) -> Result<f64, String> {
+    let unit_price = match (item.unit_price, price_list_id) {
+        (Some(price), _) => price,
+        (None, Some(price_list_id)) => product_list_price(conn, item.product_id, price_list_id)?,
+        (None, None) => conn
+            .query_row("SELECT sale_price FROM products WHERE id = ?1", [item.product_id], |row| row.get::<_, Option<f64>>(0))
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "El producto no tiene precio de venta".to_string())?,
+    };
+    if !unit_price.is_finite() || unit_price < 0.0 {
+        return Err("El precio unitario no puede ser negativo".to_string());
+    }
+    Ok(unit_price)
+}
+
+/// Stock de todas las líneas de un comprobante a la vez: un producto que se
+/// repite en varias líneas suma sus cantidades, y un pack pide sus
+/// componentes. Informa todos los faltantes juntos.
+fn check_sale_stock(conn: &Connection, items: &[NewSaleItem]) -> Result<(), String> {
+    let mut needed: Vec<(i32, f64)> = Vec::new();
+    let mut need = |product_id: i32, quantity: f64| match needed.iter_mut().find(|(id, _)| *id == product_id) {
+        Some((_, total)) => *total += quantity,
+        None => needed.push((product_id, quantity)),
+    };
+    for item in items {
+        validate_quantity(conn, item.product_id, item.quantity)?;
+        let components = bundle_components(conn, item.product_id)?;
+        if components.is_empty() {
+            need(item.product_id, item.quantity);
+        }
+        for component in components {
+            need(component.component_id, component.quantity * item.quantity);
+        }
+    }
+    let mut missing = Vec::new();
+    for (product_id, quantity) in needed {
+        let stock = product_stock(conn, product_id)?;
+        if quantity > stock + QUANTITY_EPSILON {
+            let name: String = conn
+                .query_row("SELECT name FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            missing.push(format!("{}: disponible {}, solicitado {}", name, stock, quantity));
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{}. {}", INSUFFICIENT_STOCK, missing.join("; ")))
+    }
+}
+
+fn load_sale_receipt(conn: &Connection, header_id: i64) -> Result<SaleReceipt, String> {
+    let mut receipt = conn
+        .query_row(
+            "SELECT id, customer, channel, sale_date, created_by, total, tax_amount, receipt_number FROM sale_headers WHERE id = ?1",
+            [header_id],
+            |row| {
+                Ok(SaleReceipt {
+                    id: row.get(0)?,
+                    receipt_number: row.get(7)?,
+                    customer: row.get(1)?,
+                    channel: row.get(2)?,
+                    sale_date: row.get(3)?,
+                    created_by: row.get(4)?,
+                    total: row.get(5)?,
+                    tax_amount: row.get(6)?,
+                    items: Vec::new(),
+                    warnings: Vec::new(),
+                })
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Venta #{} no encontrada", header_id),
+            e => e.to_string(),
+        })?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, product_id, quantity, unit_price, discount, line_total
+             FROM sale_items WHERE sale_id = ?1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    receipt.items = stmt
+        .query_map([header_id], |row| {
+            Ok(SaleItem {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                quantity: row.get(2)?,
+                unit_price: row.get(3)?,
+                discount: row.get(4)?,
+                line_total: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(receipt)
+}
+
+/// Solo se venden productos activos, salvo que un Administrador lo autorice
+/// con `allow_inactive`.
+fn check_sale_status(conn: &Connection, sale: &Sale, session: Option<&Session>) -> Result<(), String> {
+    let status: Option<String> = conn
+        .query_row("SELECT status FROM products WHERE id = ?1", [sale.product_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    let status = status.unwrap_or_else(|| PRODUCT_STATUS_ACTIVE.to_string());
+    if status == PRODUCT_STATUS_ACTIVE {
+        return Ok(());
+    }
+    match session {
+        Some(s) if sale.allow_inactive && s.role == "Administrador" => Ok(()),
+        _ if sale.allow_inactive => Err("Solo un Administrador puede vender un producto que no está activo".to_string()),
+        _ => Err(format!("El producto está en estado {}: no se puede vender", status)),
+    }
+}
+
+/// Registra una venta de un producto como comprobante de una sola línea. Debe
+/// ejecutarse dentro de una transacción abierta por quien la llama.
+fn insert_sale(
+    conn: &Connection,
+    sale: &Sale,
+    created_by: Option<i32>,
+    session: Option<&Session>,
+) -> Result<SaleResult, String> {
+    let (header_id, receipt_number) = insert_sale_header(
+        conn,
+        DEFAULT_RECEIPT_SERIES,
+        sale.customer.as_deref(),
+        sale.channel.as_deref(),
+        &sale.sale_date,
+        created_by,
+    )?;
+    let mut inserted = insert_sale_line(conn, sale, created_by, session)?;
+    let unit_price = implied_unit_price(sale.total()?, sale.quantity, sale.discount);
+    insert_sale_item(conn, header_id, inserted.id, sale, unit_price)?;
+    refresh_sale_header(conn, header_id)?;
+    inserted.header_id = header_id;
+    inserted.receipt_number = Some(receipt_number);
+    Ok(inserted)
+}
+
+/// Precio por unidad que corresponde a la venta: el de su promoción, que debe
+/// seguir vigente, el de su lista de precios o el de la ficha. Con promoción
+/// devuelve también el precio de catálogo (`original_price`).
+fn sale_list_price(conn: &Connection, sale: &Sale) -> Result<(Option<f64>, Option<f64>), String> {
+    if let Some(promotion_id) = sale.promotion_id {
+        let applied = active_promotions_for(conn, sale.product_id)?
+            .into_iter()
+            .find(|(promo, ..)| promo.id == Some(promotion_id));
+        return match applied {
+            Some((_, catalog_price, promo_price)) => Ok((Some(catalog_price), Some(promo_price))),
+            None => Err("La promoción ya no está vigente para este producto".to_string()),
+        };
+    }
+    if let Some(price_list_id) = sale.price_list_id {
+        return Ok((None, Some(product_list_price(conn, sale.product_id, price_list_id)?)));
+    }
+    let catalog_price: Option<f64> = conn
+        .query_row("SELECT sale_price FROM products WHERE id = ?1", [sale.product_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    Ok((None, catalog_price))
+}
+
+/// Registra una línea de venta en `sales` con su egreso de stock; la línea
+/// de `sale_items` la agrega quien la llama.
+fn insert_sale_line(
+    conn: &Connection,
+    sale: &Sale,
+    created_by: Option<i32>,
+    session: Option<&Session>,
+) -> Result<SaleResult, String> {
+    validate_quantity(conn, sale.product_id, sale.quantity)?;
+    validate_discount(sale.discount)?;
+    let sale_price = sale.total()?;
+    let payment_method = sale_payment_method(conn, sale.payment_method.as_deref())?;
+    let customer = sale_customer(conn, sale.customer_id, sale.customer.as_deref(), false)?;
+    let note = sale_note(sale.note.as_deref())?;
+    check_sale_status(conn, sale, session)?;
+    check_sale_channel(conn, sale.product_id, sale.channel.as_deref())?;
+    if let Some(ref installments) = sale.installments {
+        validate_installments(installments, sale_price, &sale.sale_date)?;
+    }
+    let amount_paid = if sale.is_credit {
+        Some(validate_credit_sale(sale)?)
+    } else {
+        None
+    };
+    // Un pack descuenta el stock de sus componentes
+    let components = bundle_components(conn, sale.product_id)?;
+    if components.is_empty() {
+        let current_stock = product_stock(conn, sale.product_id)?;
+        if sale.quantity > current_stock + QUANTITY_EPSILON {
+            return Err(format!("{}. Disponible: {}, solicitado: {}", INSUFFICIENT_STOCK, current_stock, sale.quantity));
+        }
+    } else {
+        let missing: Vec<String> = components
+            .iter()
+            .filter(|c| c.quantity * sale.quantity > c.stock + QUANTITY_EPSILON)
+            .map(|c| format!("{}: disponible {}, necesario {}", c.name, c.stock, c.quantity * sale.quantity))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("{} para el pack. {}", INSUFFICIENT_STOCK, missing.join("; ")));
+        }
+    }
+    // El depósito retornable se cobra aparte y no forma parte del ingreso
+    let unit_deposit: Option<f64> = conn
+        .query_row(
+            "SELECT deposit_amount FROM products WHERE id=?1",
+            rusqlite::params![sale.product_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let deposit_amount = unit_deposit
+        .filter(|d| *d > 0.0)
+        .map(|d| d * sale.quantity);
+    let (original_price, list_price) = sale_list_price(conn, sale)?;
+    let below_cost = check_below_cost(conn, sale, session)?;
+    let reference = price_reference(conn, sale, list_price);
+    // `check_price_override` ya exigió un Administrador si hacía falta
+    let approved_by = session.filter(|_| needs_override(conn, sale, list_price)).map(|s| s.user_id);
+    let tax_amount = included_tax(sale_price, effective_tax_rate(conn, sale.product_id)?);
+    conn.execute(
+        "INSERT INTO sales (product_id, quantity, sale_price, discount, channel, sale_date, created_by, deposit_amount, customer, original_price, promotion_id, below_cost, unit_cost, reference_price, price_override, tax_amount, price_list_id, payment_method, is_credit, amount_paid, customer_id, note, override_approved_by) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+        rusqlite::params![
+            sale.product_id,
+            sale.quantity,
+            sale_price,
+            sale.discount,
+            sale.channel,
+            sale.sale_date,
+            created_by,
+            deposit_amount,
+            customer,
+            original_price,
+            sale.promotion_id,
+            below_cost.is_some(),
+            below_cost.as_ref().map(|b| b.unit_cost),
+            reference.map(|(price, _)| price),
+            reference.is_some_and(|(_, overridden)| overridden),
+            tax_amount,
+            sale.price_list_id,
+            payment_method,
+            sale.is_credit,
+            amount_paid,
+            sale.customer_id,
+            note,
+            approved_by,
+        ],
+    ).map_err(|e| e.to_string())?;
+    let sale_id = conn.last_insert_rowid();
+    if let Some(down_payment) = amount_paid.filter(|a| *a > 0.0) {
+        conn.execute(
+            "INSERT INTO sale_payments (sale_id, amount, payment_date, payment_method, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![sale_id, down_payment, sale.sale_date.get(..10).unwrap_or(&sale.sale_date), payment_method, created_by],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some((reference_price, true)) = reference {
+        notify_price_override(conn, sale, sale_id, reference_price)?;
+    }
+    if let Some(day) = sale.sale_date.get(..10).filter(|day| is_valid_date(day)) {
+        refresh_daily_summaries(conn, Some((day, day))).map_err(|e| e.to_string())?;
+    }
+    if let Some(ref code) = sale.store_credit_code {
+        redeem_store_credit(conn, code, sale_price, sale_id)?;
+    }
+    for installment in sale.installments.iter().flatten() {
+        conn.execute(
+            "INSERT INTO payment_schedules (sale_id, due_date, amount) VALUES (?1, ?2, ?3)",
+            rusqlite::params![sale_id, installment.due_date, installment.amount],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if components.is_empty() {
+        conn.execute(
+            "INSERT INTO stock_movements (product_id, type, quantity, note, created_by, sale_id)
+             VALUES (?1, 'egreso', ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                sale.product_id,
+                sale.quantity,
+                Option::<String>::None,
+                created_by,
+                sale_id,
+            ],
+        ).map_err(|e| e.to_string())?;
+    }
+    for component in &components {
+        conn.execute(
+            "INSERT INTO stock_movements (product_id, type, quantity, note, created_by, sale_id)
+             VALUES (?1, 'egreso', ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                component.component_id,
+                component.quantity * sale.quantity,
+                format!("Pack #{}", sale.product_id),
+                created_by,
+                sale_id,
+            ],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    queue_webhook_event(
+        conn,
+        "sale.created",
+        Some(sale_price),
+        serde_json::json!({
+            "sale_id": sale_id,
+            "product_id": sale.product_id,
+            "quantity": sale.quantity,
+            "total": sale_price,
+            "channel": sale.channel,
+            "sale_date": sale.sale_date,
+        }),
+    )?;
+    if components.is_empty() {
+        queue_out_of_stock(conn, sale.product_id, sale.quantity)?;
+    }
+    for component in &components {
+        queue_out_of_stock(conn, component.component_id, component.quantity * sale.quantity)?;
+    }
+    Ok(SaleResult {
+        id: sale_id,
+        header_id: 0,
+        receipt_number: None,
+        below_cost: below_cost.is_some(),
+        warning: below_cost.map(|b| b.warning()),
+        tax_amount,
+    })
+}
+
+/// Abre la cabecera de un comprobante con el siguiente número de `series`;
+/// `refresh_sale_header` le pone los totales cuando ya tiene sus líneas.
+/// Devuelve el id y el número asignado.
+fn insert_sale_header(
+    conn: &Connection,
+    series: &str,
+    customer: Option<&str>,
+    channel: Option<&str>,
+    sale_date: &str,
+    created_by: Option<i32>,
+) -> Result<(i64, String), String> {
+    let receipt_number = take_receipt_number(conn, series).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO sale_headers (customer, channel, sale_date, created_by, receipt_number) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![customer, channel, sale_date, created_by, receipt_number],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok((conn.last_insert_rowid(), receipt_number))
+}
+
+/// Serie de los comprobantes cuando la venta no indica otra.
+const DEFAULT_RECEIPT_SERIES: &str = "V";
+
+/// Serie de comprobantes en mayúsculas: de 1 a 4 letras o números.
+fn receipt_series(series: Option<&str>) -> Result<String, String> {
+    let series = series.map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty());
+    match series {
+        None => Ok(DEFAULT_RECEIPT_SERIES.to_string()),
+        Some(s) if s.len() <= 4 && s.chars().all(|c| c.is_ascii_alphanumeric()) => Ok(s),
+        Some(s) => Err(format!("Serie de comprobante '{}' no válida: usa de 1 a 4 letras o números", s)),
+    }
+}
+
+fn format_receipt_number(series: &str, number: i64) -> String {
+    format!("{}-{:06}", series, number)
+}
+
+/// Avanza el contador de `series` y devuelve el número tomado. Debe correr
+/// en la misma transacción que inserta el comprobante: si esta se revierte,
+/// el número vuelve a quedar libre y la serie no tiene huecos.
+fn take_receipt_number(conn: &Connection, series: &str) -> Result<String> {
+    conn.execute(
+        "INSERT INTO receipt_counters (series, last_number) VALUES (?1, 1)
+         ON CONFLICT(series) DO UPDATE SET last_number = last_number + 1",
+        [series],
+    )?;
+    let number: i64 = conn.query_row("SELECT last_number FROM receipt_counters WHERE series = ?1", [series], |row| row.get(0))?;
+    Ok(format_receipt_number(series, number))
+}
+
+/// Número que recibirá la próxima venta de `series` (por defecto
+/// `DEFAULT_RECEIPT_SERIES`), sin reservarlo.
+#[tauri::command]
+fn get_next_receipt_number(state: State<AppState>, series: Option<String>) -> Result<String, String> {
+    let series = receipt_series(series.as_deref())?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let last: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(last_number), 0) FROM receipt_counters WHERE series = ?1",
+            [&series],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(format_receipt_number(&series, last + 1))
+}
+
+fn insert_sale_item(conn: &Connection, header_id: i64, line_id: i64, sale: &Sale, unit_price: f64) -> Result<(), String> {
+    let line_total = sale.total()?;
+    conn.execute(
+        "INSERT INTO sale_items (id, sale_id, product_id, quantity, unit_price, discount, line_total)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![line_id, header_id, sale.product_id, sale.quantity, unit_price, sale.discount, line_total],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Recalcula el total y el impuesto de un comprobante desde sus líneas no
+/// anuladas, y lo borra si ya no le queda ninguna línea.
+fn refresh_sale_header(conn: &Connection, header_id: i64) -> Result<(), String> {
+    let remaining = conn
+        .execute(
+            &format!(
+                "UPDATE sale_headers SET
+                    total = COALESCE((SELECT ROUND(SUM(i.line_total), 2) FROM sale_items i
+                                      JOIN {sales} s ON s.id = i.id WHERE i.sale_id = ?1), 0),
+                    tax_amount = COALESCE((SELECT ROUND(SUM(s.tax_amount), 2) FROM sale_items i
+                                           JOIN {sales} s ON s.id = i.id WHERE i.sale_id = ?1), 0)
+                 WHERE id = ?1 AND EXISTS(SELECT 1 FROM sale_items WHERE sale_id = ?1)",
+                sales = valid_sales(true)
+            ),
+            [header_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if remaining == 0 {
+        conn.execute("DELETE FROM sale_headers WHERE id = ?1", [header_id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Quita las líneas de `sale_items` de las filas de `sales` que se borraron y
+/// ajusta sus comprobantes.
+fn remove_sale_items(conn: &Connection, line_filter: &str, params: impl rusqlite::Params + Copy) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT DISTINCT sale_id FROM sale_items WHERE {}", line_filter))
+        .map_err(|e| e.to_string())?;
+    let headers = stmt
+        .query_map(params, |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    conn.execute(&format!("DELETE FROM sale_items WHERE {}", line_filter), params)
+        .map_err(|e| e.to_string())?;
+    for header_id in headers {
+        refresh_sale_header(conn, header_id)?;
+    }
+    Ok(())
+}
+
+/// Precio unitario antes del descuento porcentual que da `sale_price` para
+/// esa cantidad. Con descuento total no se puede deducir y queda en 0.
+fn implied_unit_price(sale_price: f64, quantity: f64, discount: Option<f64>) -> f64 {
+    let factor = quantity * (1.0 - discount.unwrap_or(0.0).clamp(0.0, 100.0) / 100.0);
+    if factor > 0.0 {
+        round_money(sale_price / factor)
+    } else {
+        0.0
+    }
+}
+
+/// Pasa cada venta sin línea en `sale_items` (las anteriores a los
+/// comprobantes) a un comprobante propio de una línea. El precio unitario se
+/// deduce como en `implied_unit_price`.
+fn migrate_sale_items(conn: &Connection) -> Result<()> {
+    let all_sales = "(SELECT * FROM sales UNION ALL SELECT * FROM sales_archive)";
+    let pending: Vec<i64> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT s.id FROM {} s WHERE NOT EXISTS(SELECT 1 FROM sale_items i WHERE i.id = s.id) ORDER BY s.id",
+            all_sales
+        ))?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect::<Result<Vec<_>>>()?
+    };
+    for id in pending {
+        conn.execute(
+            &format!(
+                "INSERT INTO sale_headers (customer, channel, sale_date, created_by, total, tax_amount)
+                 SELECT customer, channel, sale_date, created_by, sale_price, COALESCE(tax_amount, 0) FROM {} WHERE id = ?1",
+                all_sales
+            ),
+            [id],
+        )?;
+        conn.execute(
+            &format!(
+                "INSERT INTO sale_items (id, sale_id, product_id, quantity, unit_price, discount, line_total)
+                 SELECT id, ?2, product_id, quantity,
+                        CASE WHEN factor > 0 THEN ROUND(sale_price / factor, 2) ELSE 0 END,
+                        discount, sale_price
+                 FROM (SELECT *, quantity * (1 - MIN(MAX(COALESCE(discount, 0), 0), 100) / 100.0) AS factor FROM {})
+                 WHERE id = ?1",
+                all_sales
+            ),
+            [id, conn.last_insert_rowid()],
+        )?;
+    }
+    // Comprobantes anteriores a la numeración, en el orden en que se crearon
+    let unnumbered: Vec<i64> = {
+        let mut stmt = conn.prepare("SELECT id FROM sale_headers WHERE receipt_number IS NULL ORDER BY id")?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect::<Result<Vec<_>>>()?
+    };
+    for id in unnumbered {
+        let receipt_number = take_receipt_number(conn, DEFAULT_RECEIPT_SERIES)?;
+        conn.execute("UPDATE sale_headers SET receipt_number = ?1 WHERE id = ?2", rusqlite::params![receipt_number, id])?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_cash_movements(state: State<AppState>) -> Result<Vec<CashMovement>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, movement_type, amount, category, description, movement_date, created_by FROM cash_movements ORDER BY movement_date DESC, id DESC LIMIT 100")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(CashMovement {
+                id: row.get(0)?,
+                movement_type: row.get(1)?,
+                amount: row.get(2)?,
+                category: row.get(3)?,
+                description: row.get(4)?,
+                movement_date: row.get(5)?,
+                created_by: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+#[tauri::command]
+fn add_cash_movement<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    movement: CashMovement,
+) -> Result<i64, String> {
+    if movement.movement_type != "ingreso" && movement.movement_type != "egreso" {
+        return Err(
+            "Tipo de movimiento inválido: usa ingreso o egreso (el saldo inicial se configura aparte)"
+                .to_string(),
+        );
+    }
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let closed_month = check_period_open(&conn, session.as_ref(), &movement.movement_date)?;
+    conn.execute(
+        "INSERT INTO cash_movements (movement_type, amount, category, description, movement_date, created_by) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            movement.movement_type,
+            movement.amount,
+            movement.category,
+            movement.description,
+            movement.movement_date,
+            created_by,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let id = conn.last_insert_rowid();
+    if let Some(month) = closed_month {
+        audit_post_close(&conn, session.as_ref(), "add_cash_movement", "cash_movements", id, &month)?;
+    }
+    emit_data_changed(&window, "cash_movements", Some(id));
+    Ok(id)
+}
+
+#[tauri::command]
+fn get_cash_summary(state: State<AppState>) -> Result<CashSummary, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+
+    // Solo las ventas en efectivo entran al cajón
+    let total_sales_income: f64 = conn
+        .query_row(
+            &format!("SELECT COALESCE(SUM(sale_price),0.0) FROM sales WHERE voided_at IS NULL AND {}", CASH_SALE_SQL),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    let total_credit_collected: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount),0.0) FROM sale_payments WHERE payment_method = 'efectivo'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    let total_other_income: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount),0.0) FROM cash_movements WHERE movement_type='ingreso'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    let total_expense: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount),0.0) FROM cash_movements WHERE movement_type='egreso' AND COALESCE(category,'') <> 'deposito'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    // Depósitos retornables en caja: cobrados menos devueltos
+    let deposits_held: f64 = conn
+        .query_row(
+            "SELECT (SELECT COALESCE(SUM(deposit_amount),0.0) FROM sales WHERE voided_at IS NULL)
+                  - (SELECT COALESCE(SUM(amount),0.0) FROM cash_movements WHERE movement_type='egreso' AND category='deposito')",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    let opening_balance: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount),0.0) FROM cash_movements WHERE movement_type='saldo_inicial'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    // Las ventas pagadas con tarjeta de regalo ya se cobraron al emitirla; solo
+    // se restan las de contado, las demás no entraron en total_sales_income
+    let total_credits_redeemed: f64 = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM(r.amount),0.0) FROM store_credit_redemptions r
+                 JOIN sales s ON s.id = r.sale_id
+                 WHERE s.voided_at IS NULL AND {}",
+                CASH_SALE_SQL
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0.0);
+
+    let income = total_sales_income + total_credit_collected + total_other_income - total_credits_redeemed;
+
+    Ok(CashSummary {
+        opening_balance,
+        total_income: income,
+        total_expense,
+        balance: opening_balance + income - total_expense + deposits_held,
+    })
+}
+
+/// Saldo de caja: saldos iniciales, ventas (con depósitos cobrados) e
+/// ingresos menos egresos, sin contar dos veces las ventas de contado pagadas
+/// con tarjeta de regalo. Con `before` solo suma lo anterior a esa fecha (AAAA-MM-DD).
+fn cash_balance(conn: &Connection, with_archive: bool, before: Option<&str>) -> Result<f64, String> {
+    let sales = valid_sales(with_archive);
+    let cash = source_table("cash_movements", with_archive);
+    // Con el archivo incluido, los saldos iniciales generados al archivar
+    // duplicarían los movimientos originales
+    let opening_filter = if with_archive { " AND COALESCE(category,'') <> 'archivo'" } else { "" };
+    conn.query_row(
+        &format!(
+            "SELECT
+                (SELECT COALESCE(SUM(amount),0.0) FROM {cash}
+                 WHERE movement_type='saldo_inicial'{opening_filter} AND (?1 IS NULL OR substr(movement_date,1,10) < ?1))
+              + (SELECT COALESCE(SUM(CASE WHEN {cash_sale} THEN sale_price ELSE 0 END + COALESCE(deposit_amount,0)),0.0)
+                 FROM {sales} WHERE ?1 IS NULL OR substr(sale_date,1,10) < ?1)
+              + (SELECT COALESCE(SUM(amount),0.0) FROM {payments}
+                 WHERE payment_method = 'efectivo' AND (?1 IS NULL OR payment_date < ?1))
+              + (SELECT COALESCE(SUM(CASE movement_type WHEN 'ingreso' THEN amount WHEN 'egreso' THEN -amount ELSE 0 END),0.0)
+                 FROM {cash} WHERE ?1 IS NULL OR substr(movement_date,1,10) < ?1)
+              - (SELECT COALESCE(SUM(r.amount),0.0) FROM store_credit_redemptions r
+                 JOIN {sales} s ON s.id = r.sale_id
+                 WHERE {cash_sale} AND (?1 IS NULL OR substr(s.sale_date,1,10) < ?1))",
+            cash = cash,
+            sales = sales,
+            payments = source_table("sale_payments", with_archive),
+            cash_sale = CASH_SALE_SQL,
+            opening_filter = opening_filter
+        ),
+        rusqlite::params![before],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Registra el dinero que ya había en caja al empezar a usar el sistema. Se
+/// guarda como un movimiento `saldo_inicial` (categoría `apertura`), que entra
+/// en el balance pero no en ingresos ni egresos; un monto 0 lo elimina.
+/// Cambiarlo cuando ya hay ventas o movimientos requiere Administrador y queda
+/// en la auditoría.
+#[tauri::command]
+fn set_opening_balance<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    amount: f64,
+    date: String,
+) -> Result<(), String> {
+    if !amount.is_finite() || amount < 0.0 {
+        return Err("El saldo inicial no puede ser negativo".to_string());
+    }
+    if !is_valid_date(&date) {
+        return Err("Fecha inválida. Usa el formato AAAA-MM-DD.".to_string());
+    }
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let admin = require_admin(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<Option<i64>, String> = (|| {
+        let existing: Option<(i64, f64, String)> = conn
+            .query_row(
+                "SELECT id, amount, movement_date FROM cash_movements
+                 WHERE movement_type='saldo_inicial' AND category='apertura'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .ok();
+        let has_activity: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sales)
+                     OR EXISTS(SELECT 1 FROM cash_movements WHERE movement_type <> 'saldo_inicial')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let audited = existing.is_some() || has_activity;
+        if audited {
+            admin.map_err(|_| {
+                "Ya hay movimientos registrados: solo un Administrador puede cambiar el saldo inicial"
+                    .to_string()
+            })?;
+        }
+        let mut closed_month = check_period_open(&conn, session.as_ref(), &date)?;
+        if let Some((_, _, ref old_date)) = existing {
+            closed_month = closed_month.or(check_period_open(&conn, session.as_ref(), old_date)?);
+        }
+
+        let id = match (&existing, amount > 0.0) {
+            (Some((id, ..)), true) => {
+                conn.execute(
+                    "UPDATE cash_movements SET amount = ?1, movement_date = ?2, created_by = ?3 WHERE id = ?4",
+                    rusqlite::params![amount, date, created_by, id],
+                )
+                .map_err(|e| e.to_string())?;
+                Some(*id)
+            }
+            (Some((id, ..)), false) => {
+                conn.execute("DELETE FROM cash_movements WHERE id = ?1", rusqlite::params![id])
+                    .map_err(|e| e.to_string())?;
+                None
+            }
+            (None, true) => {
+                conn.execute(
+                    "INSERT INTO cash_movements (movement_type, amount, category, description, movement_date, created_by)
+                     VALUES ('saldo_inicial', ?1, 'apertura', 'Saldo inicial de caja', ?2, ?3)",
+                    rusqlite::params![amount, date, created_by],
+                )
+                .map_err(|e| e.to_string())?;
+                Some(conn.last_insert_rowid())
+            }
+            (None, false) => return Ok(None),
+        };
+
+        if audited {
+            let before = existing
+                .as_ref()
+                .map(|(_, old_amount, old_date)| format!("{:.2} al {}", old_amount, old_date))
+                .unwrap_or_else(|| "sin saldo inicial".to_string());
+            audit(
+                &conn,
+                created_by,
+                "saldo_inicial",
+                "cash_movements",
+                id.or(existing.as_ref().map(|(id, ..)| *id)),
+                &format!("Saldo inicial: {} → {:.2} al {}", before, amount, date),
+                Some("saldo_inicial"),
+            )?;
+        }
+        if let (Some(month), Some(id)) = (closed_month, id) {
+            audit_post_close(&conn, session.as_ref(), "set_opening_balance", "cash_movements", id, &month)?;
+        }
+        Ok(id)
+    })();
+
+    match result {
+        Ok(id) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "cash_movements", id);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// USER COMMANDS
+// ============================================
+
+#[tauri::command]
+fn get_users(state: State<AppState>) -> Result<Vec<User>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, username, role, fullname FROM users")
+        .map_err(|e| e.to_string())?;
+
+    let users = stmt
+        .query_map([], |row| {
+            Ok(User {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                password_hash: String::new(), // No exponer contraseñas
+                role: row.get(2)?,
+                fullname: row.get(3)?,
+                session_id: None,
+                must_change_password: false,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(users)
+}
+
+/// Rol del catálogo de `known_roles` que coincide con `role` (sin distinguir
+/// mayúsculas), con su grafía canónica.
+fn valid_role(conn: &Connection, role: &str) -> Result<String, String> {
+    let roles = known_roles(conn)?;
+    let role = role.trim();
+    roles
+        .iter()
+        .find(|r| r.eq_ignore_ascii_case(role))
+        .cloned()
+        .ok_or_else(|| format!("Rol desconocido: '{}'. Roles válidos: {}", role, roles.join(", ")))
+}
+
+/// Crea un usuario (solo administradores).
+#[tauri::command]
+fn add_user<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    username: String,
+    fullname: String,
+    password: String,
+    role: String,
+) -> Result<i64, String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let role = valid_role(&conn, &role)?;
+
+    // Hash seguro de la contraseña con bcrypt
+    let password_hash = hash(&password, DEFAULT_COST).map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "INSERT INTO users (username, fullname, password_hash, role) 
+         VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![
+            username,
+            fullname,
+            password_hash,
+            role,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+/// Modifica un usuario (solo administradores). Sus sesiones abiertas toman el
+/// rol nuevo de inmediato.
+#[tauri::command]
+fn update_user<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    id: i32,
+    username: String,
+    fullname: String,
+    role: String,
+    password: Option<String>,
+) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let role = valid_role(&conn, &role)?;
+    
+    if let Some(pwd) = password {
+        // Si se proporciona contraseña, hashearla y actualizarla; deja de ser temporal
+        let password_hash = hash(&pwd, DEFAULT_COST).map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE users SET username = ?1, fullname = ?2, role = ?3, password_hash = ?4, must_change_password = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?5",
+            rusqlite::params![username, fullname, role, password_hash, id],
+        )
+        .map_err(|e| e.to_string())?;
+    } else {
+        // Solo actualizar username, fullname y role (mantener contraseña actual)
+        conn.execute(
+            "UPDATE users SET username = ?1, fullname = ?2, role = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?4",
+            rusqlite::params![username, fullname, role, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let mut sessions = state.sessions.lock().map_err(|e| e.to_string())?;
+    for session in sessions.values_mut().filter(|s| s.user_id == id) {
+        session.username = username.clone();
+        session.role = role.clone();
+    }
+    Ok(())
+}
+
+/// Elimina un usuario (solo administradores) y cierra sus sesiones.
+#[tauri::command]
+fn delete_user<R: Runtime>(window: Window<R>, state: State<AppState>, id: i32) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM users WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    state.sessions.lock().map_err(|e| e.to_string())?.retain(|_, s| s.user_id != id);
+    Ok(())
+}
+
+/// Verifica las credenciales de login contra la base de datos
+/// Retorna el usuario si las credenciales son correctas, error si no
+#[tauri::command]
+fn verify_login<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    username: String,
+    password: String,
+) -> Result<User, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    
+    // Buscar usuario por username
+    let result = conn.query_row(
+        "SELECT id, username, password_hash, role, fullname, must_change_password FROM users WHERE username = ?1",
+        rusqlite::params![username],
+        |row| {
+            Ok(User {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                password_hash: row.get(2)?,
+                role: row.get(3)?,
+                fullname: row.get(4)?,
+                session_id: None,
+                must_change_password: row.get(5)?,
+            })
+        },
+    );
+    
+    match result {
+        Ok(user) => {
+            // Verificar contraseña con bcrypt
+            let is_valid = verify(&password, &user.password_hash)
+                .map_err(|e| format!("Error verificando contraseña: {}", e))?;
+            
+            if is_valid {
+                if user.username == DEFAULT_ADMIN_USERNAME
+                    && password == DEFAULT_ADMIN_PASSWORD
+                    && !default_password_allowed(&conn)
+                {
+                    return Err(DEFAULT_PASSWORD_DISABLED.to_string());
+                }
+                let session = open_session(&state, &window, &user)?;
+                // No enviar el hash de contraseña al frontend
+                Ok(User {
+                    id: user.id,
+                    username: user.username,
+                    password_hash: String::new(), // Vacío por seguridad
+                    role: user.role,
+                    fullname: user.fullname,
+                    session_id: Some(session.session_id),
+                    must_change_password: user.must_change_password,
+                })
+            } else {
+                Err("Contraseña incorrecta".to_string())
+            }
+        }
+        Err(_) => Err("Usuario no encontrado".to_string()),
+    }
+}
+
+// ============================================
+// CONFIGURACIÓN INICIAL
+// ============================================
+
+/// Usuario y contraseña con los que se crea el administrador por defecto.
+const DEFAULT_ADMIN_USERNAME: &str = "admin";
+const DEFAULT_ADMIN_PASSWORD: &str = "admin";
+
+/// "true" cuando se completó el asistente de configuración inicial.
+const SETTING_SETUP_COMPLETED: &str = "setup_completed";
+/// Fecha en que una base anterior al asistente se abrió por primera vez con
+/// esta versión; desde ahí corre el período de gracia.
+const SETTING_SETUP_GRACE_FROM: &str = "setup_grace_from";
+/// Días de gracia para las bases actualizadas; se cambia con el ajuste
+/// `default_password_grace_days`.
+const DEFAULT_PASSWORD_GRACE_DAYS: f64 = 30.0;
+
+const DEFAULT_PASSWORD_DISABLED: &str =
+    "La contraseña predeterminada del administrador está deshabilitada. Completa la configuración inicial para elegir una nueva.";
+
+/// Largo mínimo de la contraseña que se elige en el asistente.
+const MIN_ADMIN_PASSWORD_LEN: usize = 8;
+
+/// Plantilla de catálogo para el asistente: categorías y canales de venta
+/// iniciales.
+struct SetupTemplate {
+    name: &'static str,
+    categories: &'static [&'static str],
+    channels: &'static [&'static str],
+}
+
+const SETUP_TEMPLATES: &[SetupTemplate] = &[
+    SetupTemplate {
+        name: "suplementos",
+        categories: &["Proteínas", "Creatinas", "Aminoácidos", "Pre-entrenos", "Vitaminas", "Accesorios"],
+        channels: &["Mostrador", "WhatsApp", "Tienda online"],
+    },
+    SetupTemplate {
+        name: "gimnasio",
+        categories: &["Suplementos", "Bebidas", "Snacks", "Indumentaria", "Accesorios"],
+        channels: &["Recepción", "Mostrador"],
+    },
+];
+
+fn setup_completed(conn: &Connection) -> bool {
+    get_setting(conn, SETTING_SETUP_COMPLETED).as_deref() == Some("true")
+}
+
+/// La contraseña por defecto solo sirve con la configuración inicial completa
+/// (si alguien la volvió a poner) o dentro del período de gracia de una base
+/// actualizada.
+fn default_password_allowed(conn: &Connection) -> bool {
+    if setup_completed(conn) {
+        return true;
+    }
+    let Some(from) = get_setting(conn, SETTING_SETUP_GRACE_FROM) else {
+        return false;
+    };
+    let days = setting_f64(conn, "default_password_grace_days", DEFAULT_PASSWORD_GRACE_DAYS);
+    conn.query_row(
+        "SELECT date(?1, ?2) > date('now')",
+        rusqlite::params![from, format!("+{} days", days.max(0.0).floor())],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+/// `true` en una instalación nueva: sin configuración inicial, sin productos y
+/// con el admin por defecto como único usuario. También en una base
+/// actualizada cuyo período de gracia venció mientras el admin conserva la
+/// contraseña por defecto: el asistente es la única forma de volver a entrar.
+#[tauri::command]
+fn is_first_run(state: State<AppState>) -> Result<bool, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if setup_completed(&conn) {
+        return Ok(false);
+    }
+    if !default_password_allowed(&conn) {
+        let admin_hash: Option<String> = conn
+            .query_row(
+                "SELECT password_hash FROM users WHERE username = ?1",
+                [DEFAULT_ADMIN_USERNAME],
+                |row| row.get(0),
+            )
+            .ok();
+        if admin_hash.map(|h| verify(DEFAULT_ADMIN_PASSWORD, &h).unwrap_or(false)).unwrap_or(false) {
+            return Ok(true);
+        }
+    }
+    conn.query_row(
+        "SELECT NOT EXISTS (SELECT 1 FROM products)
+            AND (SELECT COUNT(*) FROM users) = 1
+            AND EXISTS (SELECT 1 FROM users WHERE username = ?1)",
+        [DEFAULT_ADMIN_USERNAME],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct InitialSetup {
+    company_name: String,
+    company_tax_id: Option<String>,
+    company_address: Option<String>,
+    company_phone: Option<String>,
+    /// Contraseña nueva del usuario admin
+    admin_password: String,
+    /// Nombre de una de `SETUP_TEMPLATES`
+    template: Option<String>,
+}
+
+/// Completa el asistente en una sola transacción: datos de la empresa,
+/// contraseña nueva del admin y, opcionalmente, categorías y canales de una
+/// plantilla. Sin sesión solo se acepta mientras el admin conserva la
+/// contraseña por defecto.
+#[tauri::command]
+fn complete_initial_setup<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    setup: InitialSetup,
+) -> Result<(), String> {
+    let company_name = setup.company_name.trim();
+    if company_name.is_empty() {
+        return Err("El nombre de la empresa es obligatorio".to_string());
+    }
+    if setup.admin_password.chars().count() < MIN_ADMIN_PASSWORD_LEN {
+        return Err(format!(
+            "La contraseña del administrador debe tener al menos {} caracteres",
+            MIN_ADMIN_PASSWORD_LEN
+        ));
+    }
+    if setup.admin_password == DEFAULT_ADMIN_PASSWORD {
+        return Err("Elige una contraseña distinta de la predeterminada".to_string());
+    }
+    let template = match setup.template.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        Some(name) => Some(SETUP_TEMPLATES.iter().find(|t| t.name == name).ok_or_else(|| {
+            let names: Vec<&str> = SETUP_TEMPLATES.iter().map(|t| t.name).collect();
+            format!("Plantilla desconocida: {}. Opciones: {}", name, names.join(", "))
+        })?),
+        None => None,
+    };
+    let admin_session = window_session(&state, window.label()).filter(|s| s.role == "Administrador");
+    let password_hash = hash(&setup.admin_password, DEFAULT_COST).map_err(|e| e.to_string())?;
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if setup_completed(&conn) {
+        return Err("La configuración inicial ya se completó".to_string());
+    }
+    let (admin_id, current_hash): (i32, String) = conn
+        .query_row(
+            "SELECT id, password_hash FROM users WHERE username = ?1",
+            [DEFAULT_ADMIN_USERNAME],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| "No se encontró el usuario admin".to_string())?;
+    if admin_session.is_none() && !verify(DEFAULT_ADMIN_PASSWORD, &current_hash).unwrap_or(false) {
+        return Err("Solo un Administrador puede completar la configuración inicial".to_string());
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        set_setting(&conn, "company_name", company_name)?;
+        for (key, value) in [
+            ("company_tax_id", &setup.company_tax_id),
+            ("company_address", &setup.company_address),
+            ("company_phone", &setup.company_phone),
+        ] {
+            if let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+                set_setting(&conn, key, value)?;
+            }
+        }
+        conn.execute(
+            "UPDATE users SET password_hash = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+            rusqlite::params![password_hash, admin_id],
+        )
+        .map_err(|e| e.to_string())?;
+        if let Some(template) = template {
+            set_setting(&conn, "catalog_categories", &template.categories.join(", "))?;
+            set_setting(&conn, "sales_channels", &template.channels.join(", "))?;
+        }
+        set_setting(&conn, SETTING_SETUP_COMPLETED, "true")?;
+        conn.execute("DELETE FROM settings WHERE key = ?1", [SETTING_SETUP_GRACE_FROM])
+            .map_err(|e| e.to_string())?;
+        audit(
+            &conn,
+            Some(admin_session.map(|s| s.user_id).unwrap_or(admin_id)),
+            "complete_initial_setup",
+            "settings",
+            None,
+            &format!(
+                "Empresa: {}; plantilla: {}",
+                company_name,
+                template.map(|t| t.name).unwrap_or("ninguna")
+            ),
+            None,
+        )
+    })();
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(err);
+        }
+    }
+    emit_data_changed(&window, "settings", None);
+    Ok(())
+}
+
+// ============================================
+// TARJETAS DE REGALO / SALDO A FAVOR
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreCredit {
+    id: i32,
+    code: String,
+    initial_amount: f64,
+    balance: f64,
+    customer: Option<String>,
+    expiry_date: Option<String>,
+    created_at: String,
+    expired: bool,
+}
+
+/// Busca una tarjeta por código sin distinguir mayúsculas ni espacios: se
+/// emiten en mayúsculas pero el cliente puede dictarlas de cualquier forma.
+fn load_store_credit(conn: &Connection, code: &str) -> Result<StoreCredit, String> {
+    conn.query_row(
+        "SELECT id, code, initial_amount, balance, customer, expiry_date, created_at,
+                (expiry_date IS NOT NULL AND expiry_date < date('now')) as expired
+         FROM store_credits WHERE code = ?1 COLLATE NOCASE",
+        rusqlite::params![code.trim()],
+        |row| {
+            Ok(StoreCredit {
+                id: row.get(0)?,
+                code: row.get(1)?,
+                initial_amount: row.get(2)?,
+                balance: row.get(3)?,
+                customer: row.get(4)?,
+                expiry_date: row.get(5)?,
+                created_at: row.get(6)?,
+                expired: row.get(7)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Tarjeta de regalo no encontrada".to_string(),
+        other => other.to_string(),
+    })
+}
+
+/// Descuenta `amount` del saldo de la tarjeta dentro de la transacción de la
+/// venta. El UPDATE condicionado evita sobregiros aunque haya ventas simultáneas.
+fn redeem_store_credit(conn: &Connection, code: &str, amount: f64, sale_id: i64) -> Result<(), String> {
+    let credit = load_store_credit(conn, code)?;
+    if credit.expired {
+        return Err(format!("La tarjeta de regalo {} está vencida", credit.code));
+    }
+    let updated = conn
+        .execute(
+            "UPDATE store_credits SET balance = balance - ?1 WHERE id = ?2 AND balance >= ?1",
+            rusqlite::params![amount, credit.id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!(
+            "Saldo insuficiente en la tarjeta de regalo. Disponible: {:.2}, requerido: {:.2}",
+            credit.balance, amount
+        ));
+    }
+    conn.execute(
+        "INSERT INTO store_credit_redemptions (credit_id, sale_id, amount) VALUES (?1, ?2, ?3)",
+        rusqlite::params![credit.id, sale_id, amount],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Emite una tarjeta de regalo y registra su venta como ingreso de caja
+/// (categoría `tarjeta_regalo`). Si no se indica código se genera uno.
+#[tauri::command]
+fn issue_store_credit<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    amount: f64,
+    customer: Option<String>,
+    expiry_date: Option<String>,
+    code: Option<String>,
+) -> Result<StoreCredit, String> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err("El monto de la tarjeta debe ser mayor a 0".to_string());
+    }
+    if let Some(ref date) = expiry_date {
+        if !is_valid_date(date) {
+            return Err("Fecha de vencimiento inválida. Usa el formato AAAA-MM-DD.".to_string());
+        }
+    }
+    let code = match code.map(|c| c.trim().to_uppercase()) {
+        Some(c) if !c.is_empty() => c,
+        _ => format!("GC-{}", &new_token()[..8].to_uppercase()),
+    };
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<(), String> = (|| {
+        conn.execute(
+            "INSERT INTO store_credits (code, initial_amount, balance, customer, expiry_date, created_by)
+             VALUES (?1, ?2, ?2, ?3, ?4, ?5)",
+            rusqlite::params![code, amount, customer, expiry_date, created_by],
+        )
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("UNIQUE constraint failed: store_credits.code") {
+                "Ya existe una tarjeta de regalo con ese código".to_string()
+            } else {
+                msg
+            }
+        })?;
+        conn.execute(
+            "INSERT INTO cash_movements (movement_type, amount, category, description, movement_date, created_by)
+             VALUES ('ingreso', ?1, 'tarjeta_regalo', ?2, date('now'), ?3)",
+            rusqlite::params![amount, format!("Emisión tarjeta de regalo {}", code), created_by],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "store_credits", None);
+            load_store_credit(&conn, &code)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Consulta el saldo y vencimiento de una tarjeta de regalo.
+#[tauri::command]
+fn check_credit(state: State<AppState>, code: String) -> Result<StoreCredit, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    load_store_credit(&conn, &code)
+}
+
+// ============================================
+// DEPÓSITOS RETORNABLES
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OutstandingDeposit {
+    customer: String,
+    quantity: f64,
+    amount: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DepositRefund {
+    refunded_quantity: f64,
+    amount: f64,
+    cash_movement_id: i64,
+}
+
+/// Ventas con depósito pendiente: (sale_id, unidades pendientes, depósito por unidad).
+/// Incluye ventas archivadas, porque el pasivo sigue vigente.
+fn pending_deposit_sales(
+    conn: &Connection,
+    sale_id: Option<i64>,
+    customer: Option<&str>,
+) -> Result<Vec<(i64, f64, f64)>, String> {
+    let sql = format!(
+        "SELECT s.id,
+                s.quantity - COALESCE((SELECT SUM(r.quantity) FROM deposit_refunds r WHERE r.sale_id = s.id), 0) as pending,
+                s.deposit_amount / s.quantity as unit_deposit
+         FROM {} s
+         WHERE s.deposit_amount > 0 AND s.quantity > 0
+           AND (?1 IS NULL OR s.id = ?1)
+           AND (?2 IS NULL OR s.customer = ?2)
+           AND pending >= 0.0005
+         ORDER BY s.sale_date ASC, s.id ASC",
+        valid_sales(true)
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![sale_id, customer], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+/// Total de depósitos cobrados que aún no se han devuelto.
+fn outstanding_deposits_total(conn: &Connection) -> Result<f64, String> {
+    Ok(pending_deposit_sales(conn, None, None)?
+        .iter()
+        .map(|(_, pending, unit)| pending * unit)
+        .sum())
+}
+
+/// Depósitos pendientes de devolución agrupados por cliente.
+#[tauri::command]
+fn get_outstanding_deposits(state: State<AppState>) -> Result<Vec<OutstandingDeposit>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let sql = format!(
+        "SELECT COALESCE(s.customer, 'sin cliente') as customer,
+                SUM(s.quantity - COALESCE((SELECT SUM(r.quantity) FROM deposit_refunds r WHERE r.sale_id = s.id), 0)) as pending,
+                SUM((s.quantity - COALESCE((SELECT SUM(r.quantity) FROM deposit_refunds r WHERE r.sale_id = s.id), 0)) * s.deposit_amount / s.quantity) as amount
+         FROM {} s
+         WHERE s.deposit_amount > 0 AND s.quantity > 0
+         GROUP BY customer
+         HAVING pending > 0
+         ORDER BY amount DESC",
+        valid_sales(true)
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(OutstandingDeposit {
+                customer: row.get(0)?,
+                quantity: row.get(1)?,
+                amount: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+/// Devuelve depósitos de una venta o, si se indica un cliente, de sus ventas
+/// más antiguas primero. Registra el egreso de caja (categoría `deposito`)
+/// y descuenta el pasivo.
+#[tauri::command]
+fn refund_deposit<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    sale_id: Option<i64>,
+    customer: Option<String>,
+    quantity: f64,
+) -> Result<DepositRefund, String> {
+    if !quantity.is_finite() || quantity <= 0.0 {
+        return Err("La cantidad a devolver debe ser mayor a 0".to_string());
+    }
+    if sale_id.is_none() && customer.is_none() {
+        return Err("Indica la venta o el cliente del depósito".to_string());
+    }
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<DepositRefund, String> = (|| {
+        let closed_month = check_period_open(&conn, session.as_ref(), &today_key(&conn)?)?;
+        let pending = pending_deposit_sales(&conn, sale_id, customer.as_deref())?;
+        let available: f64 = pending.iter().map(|(_, p, _)| p).sum();
+        if quantity > available + QUANTITY_EPSILON {
+            return Err(format!(
+                "No hay suficientes depósitos pendientes. Pendientes: {}, solicitados: {}",
+                available, quantity
+            ));
+        }
+
+        let mut remaining = quantity;
+        let mut refunds: Vec<(i64, f64, f64)> = Vec::new();
+        for (sid, pending_qty, unit) in pending {
+            if remaining < QUANTITY_EPSILON {
+                break;
+            }
+            let take = remaining.min(pending_qty);
+            refunds.push((sid, take, take * unit));
+            remaining -= take;
+        }
+        let amount: f64 = refunds.iter().map(|(_, _, a)| a).sum();
+
+        conn.execute(
+            "INSERT INTO cash_movements (movement_type, amount, category, description, movement_date, created_by)
+             VALUES ('egreso', ?1, 'deposito', ?2, date('now'), ?3)",
+            rusqlite::params![
+                amount,
+                format!("Devolución de {} depósito(s)", quantity),
+                created_by
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let cash_movement_id = conn.last_insert_rowid();
+        if let Some(month) = closed_month {
+            audit_post_close(&conn, session.as_ref(), "refund_deposit", "cash_movements", cash_movement_id, &month)?;
+        }
+
+        for (sid, qty, refund_amount) in refunds {
+            conn.execute(
+                "INSERT INTO deposit_refunds (sale_id, quantity, amount, cash_movement_id, created_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![sid, qty, refund_amount, cash_movement_id, created_by],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        Ok(DepositRefund {
+            refunded_quantity: quantity,
+            amount,
+            cash_movement_id,
+        })
+    })();
+
+    match result {
+        Ok(refund) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "cash_movements", Some(refund.cash_movement_id));
+            Ok(refund)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// SESIONES POR VENTANA
+// ============================================
+
+/// Genera un identificador aleatorio de 32 caracteres hexadecimales.
+fn new_token() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Abre una sesión para el usuario en la ventana indicada, reemplazando la
+/// sesión anterior de esa misma ventana.
+fn open_session<R: Runtime>(state: &AppState, window: &Window<R>, user: &User) -> Result<Session, String> {
+    let mut sessions = state.sessions.lock().map_err(|e| e.to_string())?;
+    sessions.retain(|_, s| s.window_label != window.label());
+    let session = Session {
+        session_id: new_token(),
+        user_id: user.id.ok_or("Usuario sin id")?,
+        username: user.username.clone(),
+        role: user.role.clone(),
+        window_label: window.label().to_string(),
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs(),
+    };
+    sessions.insert(session.session_id.clone(), session.clone());
+    Ok(session)
+}
+
+/// Sesión activa de una ventana, si la hay.
+fn window_session(state: &AppState, window_label: &str) -> Option<Session> {
+    let sessions = state.sessions.lock().ok()?;
+    sessions
+        .values()
+        .find(|s| s.window_label == window_label)
+        .cloned()
+}
+
+/// Usuario que actúa desde la ventana: el de su sesión. Sin sesión no hay
+/// usuario; el `created_by` que envía el frontend no se toma en cuenta.
+fn acting_user<R: Runtime>(state: &AppState, window: &Window<R>) -> Option<i32> {
+    window_session(state, window.label()).map(|s| s.user_id)
+}
+
+/// Exige que la ventana tenga una sesión de administrador.
+fn require_admin<R: Runtime>(state: &AppState, window: &Window<R>) -> Result<Session, String> {
+    match window_session(state, window.label()) {
+        Some(session) if session.role == "Administrador" => Ok(session),
+        Some(_) => Err("Solo un Administrador puede realizar esta acción".to_string()),
+        None => Err("No hay una sesión activa en esta ventana".to_string()),
+    }
+}
+
+/// Notifica a todas las ventanas que cambió una entidad, indicando la ventana
+/// de origen.
+fn emit_data_changed<R: Runtime>(window: &Window<R>, entity: &str, id: Option<i64>) {
+    let _ = window.emit_all(
+        "data-changed",
+        DataChanged {
+            entity: entity.to_string(),
+            id,
+            origin_window: window.label().to_string(),
+        },
+    );
+}
+
+/// Cierra la sesión de la ventana actual.
+#[tauri::command]
+fn logout<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<(), String> {
+    let mut sessions = state.sessions.lock().map_err(|e| e.to_string())?;
+    sessions.retain(|_, s| s.window_label != window.label());
+    Ok(())
+}
+
+/// Lista las sesiones abiertas en todas las ventanas (solo administradores).
+#[tauri::command]
+fn get_active_sessions<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<Vec<Session>, String> {
+    require_admin(&state, &window)?;
+    let sessions = state.sessions.lock().map_err(|e| e.to_string())?;
+    let mut list: Vec<Session> = sessions.values().cloned().collect();
+    list.sort_by(|a, b| a.window_label.cmp(&b.window_label));
+    Ok(list)
+}
+
+/// Cierra una sesión de otra caja y avisa a su ventana (solo administradores).
+#[tauri::command]
+fn force_logout<R: Runtime>(window: Window<R>, state: State<AppState>, session_id: String) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let removed = state
+        .sessions
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&session_id)
+        .ok_or("Sesión no encontrada")?;
+    let _ = window.emit_to(&removed.window_label, "session-ended", removed.session_id);
+    Ok(())
+}
+
+// ============================================
+// CONFIGURACIÓN
+// ============================================
+
+/// Lee un valor de configuración.
+fn get_setting(conn: &Connection, key: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        rusqlite::params![key],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+/// Lee un valor numérico de configuración, o `default` si no existe o no es válido.
+fn setting_f64(conn: &Connection, key: &str, default: f64) -> f64 {
+    get_setting(conn, key)
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        rusqlite::params![key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Result<HashMap<String, String>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM settings")
+        .map_err(|e| e.to_string())?;
+    let settings = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(settings)
+}
+
+#[tauri::command]
+fn update_setting<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    set_setting(&conn, &key, &value)?;
+    emit_data_changed(&window, "settings", None);
+    Ok(())
+}
+
+// ============================================
+// NOTIFICACIONES
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Notification {
+    id: i64,
+    kind: String,
+    title: String,
+    message: String,
+    product_id: Option<i32>,
+    read: bool,
+    created_at: String,
+}
+
+/// Registra una notificación. `dedupe_key` evita repetir el mismo aviso
+/// (por ejemplo, una alerta por producto y día). Devuelve `true` si se creó.
+fn notify(
+    conn: &Connection,
+    kind: &str,
+    title: &str,
+    message: &str,
+    product_id: Option<i32>,
+    dedupe_key: &str,
+) -> Result<bool, String> {
+    let inserted = conn
+        .execute(
+            "INSERT OR IGNORE INTO notifications (kind, title, message, product_id, dedupe_key)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![kind, title, message, product_id, dedupe_key],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(inserted > 0)
+}
+
+#[tauri::command]
+fn get_notifications(
+    state: State<AppState>,
+    unread_only: Option<bool>,
+) -> Result<Vec<Notification>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, kind, title, message, product_id, read, created_at
+             FROM notifications
+             WHERE (?1 = 0 OR read = 0)
+             ORDER BY created_at DESC, id DESC
+             LIMIT 200",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![unread_only.unwrap_or(false)], |row| {
+            Ok(Notification {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                title: row.get(2)?,
+                message: row.get(3)?,
+                product_id: row.get(4)?,
+                read: row.get::<_, i64>(5)? != 0,
+                created_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+#[tauri::command]
+fn mark_notification_read(state: State<AppState>, id: i64) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE notifications SET read = 1 WHERE id = ?1",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ============================================
+// TAREAS PROGRAMADAS
+// ============================================
+
+/// Tarea programada; devuelve cuántas notificaciones creó.
+type ScheduledJob = fn(&Connection) -> Result<usize, String>;
+
+/// Tareas del planificador con cada cuántos días se ejecutan.
+const SCHEDULED_JOBS: &[(&str, u32, ScheduledJob)] = &[
+    ("velocity_alerts", 1, run_velocity_check),
+    ("daily_close", 1, run_daily_close),
+    ("overdue_installments", 1, run_overdue_installments_check),
+    ("prune_history", 7, run_scheduled_prune),
+];
+
+/// Cada cuánto revisa el planificador si hay tareas pendientes.
+const SCHEDULER_TICK: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Fecha actual (AAAA-MM-DD) según SQLite, la misma que usan las consultas.
+fn today_key(conn: &Connection) -> Result<String, String> {
+    conn.query_row("SELECT date('now')", [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Ejecuta cada tarea de `SCHEDULED_JOBS` que cumplió su intervalo. La fecha
+/// de la última ejecución se guarda en `settings` (`job.<nombre>.last_run`),
+/// así que una tarea no se repite al reabrir la aplicación.
+fn run_due_jobs(conn: &Connection) -> usize {
+    let today = match today_key(conn) {
+        Ok(d) => d,
+        Err(_) => return 0,
+    };
+    let mut created = 0;
+    for (name, every_days, job) in SCHEDULED_JOBS {
+        let key = format!("job.{}.last_run", name);
+        if let Some(last_run) = get_setting(conn, &key) {
+            let due = conn
+                .query_row(
+                    "SELECT ?1 <= date(?2, ?3)",
+                    rusqlite::params![last_run, today, format!("-{} days", every_days)],
+                    |row| row.get::<_, bool>(0),
+                )
+                .unwrap_or(true);
+            if !due {
+                continue;
+            }
+        }
+        match job(conn) {
+            Ok(n) => {
+                created += n;
+                let _ = set_setting(conn, &key, &today);
+            }
+            Err(e) => eprintln!("❌ Error en tarea programada {}: {}", name, e),
+        }
+    }
+    created
+}
+
+/// Registros con fecha posterior a la actual: ventas con `sale_date` desde
+/// `FUTURE_SALE_FROM` y filas creadas más de una hora en el futuro. Suelen
+/// aparecer cuando el reloj del equipo estuvo adelantado.
+const FUTURE_RECORDS_SQL: &str = "SELECT COUNT(*), MAX(latest) FROM (
+        SELECT sale_date AS latest FROM sales WHERE sale_date >= date('now','localtime','+2 day')
+        UNION ALL SELECT created_at FROM stock_movements WHERE created_at > datetime('now','+1 hour')
+        UNION ALL SELECT created_at FROM products WHERE created_at > datetime('now','+1 hour')
+        UNION ALL SELECT movement_date FROM cash_movements WHERE movement_date >= date('now','localtime','+2 day')
+    )";
+
+/// Compara las fechas guardadas con el reloj actual. Si hay registros en el
+/// futuro deja una notificación (una por fecha más lejana encontrada) y, si
+/// el resumen diario todavía los tiene en un día normal o ya dejaron de ser
+/// futuros, lo recalcula. Corre al iniciar y en cada vuelta del planificador,
+/// porque un cambio de reloj no espera al día siguiente.
+fn run_clock_check(conn: &Connection) -> Result<usize, String> {
+    let (count, latest): (i64, Option<String>) = conn
+        .query_row(FUTURE_RECORDS_SQL, [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    let stale_summary: bool = conn
+        .query_row(
+            &format!(
+                "SELECT EXISTS(SELECT 1 FROM daily_sales_summary WHERE day >= {future} AND day <> '{attention}')
+                     OR EXISTS(SELECT 1 FROM daily_sales_summary WHERE day = '{attention}')
+                        <> EXISTS(SELECT 1 FROM sales WHERE sale_date >= {future})",
+                future = FUTURE_SALE_FROM,
+                attention = ATTENTION_DAY
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if stale_summary {
+        refresh_daily_summaries(conn, None).map_err(|e| e.to_string())?;
+    }
+    let Some(latest) = latest else {
+        return Ok(0);
+    };
+    let created = notify(
+        conn,
+        "clock_skew",
+        "Registros con fecha futura",
+        &format!(
+            "Hay {} registro(s) con fecha posterior a hoy (la más lejana es {}). Revisa la fecha y hora del equipo \
+             y corrígelos desde el reporte de calidad de datos; mientras tanto el tablero los muestra en \"{}\".",
+            count, latest, ATTENTION_DAY
+        ),
+        None,
+        &format!("clock_skew:{}", latest),
+    )?;
+    Ok(created as usize)
+}
+
+/// Lanza el hilo del planificador. Avisa a las ventanas con el evento
+/// `notifications-updated` cuando una tarea crea notificaciones.
+fn start_scheduler(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        let created = {
+            let state = app.state::<AppState>();
+            let conn = match state.db.lock() {
+                Ok(conn) => conn,
+                Err(_) => break,
+            };
+            let clock = run_clock_check(&conn).unwrap_or_else(|e| {
+                eprintln!("❌ Error al revisar fechas futuras: {}", e);
+                0
+            });
+            clock + run_due_jobs(&conn)
+        };
+        if created > 0 {
+            let _ = app.emit_all("notifications-updated", created);
+        }
+        std::thread::sleep(SCHEDULER_TICK);
+    });
+}
+
+// ============================================
+// RETENCIÓN DE HISTORIAL
+// ============================================
+
+/// Tabla que recorta `prune_history`: las filas con `created_at` anterior a
+/// la retención (ajuste `retention_days.<tabla>`; 0 las conserva todas) y que
+/// cumplen `filter`.
+struct RetentionRule {
+    table: &'static str,
+    default_days: f64,
+    filter: &'static str,
+}
+
+const RETENTION_RULES: &[RetentionRule] = &[
+    // Las no leídas siguen pendientes, por viejas que sean
+    RetentionRule { table: "notifications", default_days: 90.0, filter: "read = 1" },
+    RetentionRule { table: "audit_log", default_days: 730.0, filter: "1" },
+    // Las pendientes todavía se van a reintentar
+    RetentionRule { table: "webhook_deliveries", default_days: 30.0, filter: "status <> 'pendiente'" },
+];
+
+/// Filas borradas por transacción, para no bloquear la base con una sola
+/// transacción enorme.
+const PRUNE_BATCH_SIZE: i64 = 1000;
+
+#[derive(Debug, Serialize)]
+struct PrunedTable {
+    table: String,
+    retention_days: f64,
+    /// Fecha y hora límite; `None` si la tabla no se recortó
+    cutoff: Option<String>,
+    deleted: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct PruneReport {
+    tables: Vec<PrunedTable>,
+}
+
+/// Borra en lotes (una transacción por lote) las filas de `rule` anteriores a
+/// `cutoff` y devuelve cuántas borró.
+fn prune_table(conn: &Connection, rule: &RetentionRule, cutoff: &str) -> Result<usize, String> {
+    let sql = format!(
+        "DELETE FROM {t} WHERE id IN (SELECT id FROM {t} WHERE created_at < ?1 AND {f} LIMIT ?2)",
+        t = rule.table,
+        f = rule.filter
+    );
+    let mut deleted = 0;
+    loop {
+        conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+            .map_err(|e| e.to_string())?;
+        match conn.execute(&sql, rusqlite::params![cutoff, PRUNE_BATCH_SIZE]) {
+            Ok(n) => {
+                conn.execute("COMMIT", [])
+                    .map_err(|e| e.to_string())?;
+                deleted += n;
+                if (n as i64) < PRUNE_BATCH_SIZE {
+                    return Ok(deleted);
+                }
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(e.to_string());
+            }
+        }
+    }
+}
+
+/// Recorta las tablas de `RETENTION_RULES`. El registro de auditoría solo con
+/// `include_audit_log`, y deja en él una entrada con lo que se borró.
+fn run_prune_history(conn: &Connection, include_audit_log: bool, user_id: Option<i32>) -> Result<PruneReport, String> {
+    let mut tables = Vec::new();
+    for rule in RETENTION_RULES {
+        let retention_days = setting_f64(conn, &format!("retention_days.{}", rule.table), rule.default_days);
+        let skip = retention_days <= 0.0 || (rule.table == "audit_log" && !include_audit_log);
+        let cutoff: Option<String> = if skip {
+            None
+        } else {
+            Some(
+                conn.query_row("SELECT datetime('now', ?1)", [format!("-{} days", retention_days)], |row| row.get(0))
+                    .map_err(|e| e.to_string())?,
+            )
+        };
+        let deleted = match &cutoff {
+            Some(cutoff) => prune_table(conn, rule, cutoff)?,
+            None => 0,
+        };
+        if rule.table == "audit_log" {
+            if let Some(cutoff) = &cutoff {
+                audit(
+                    conn,
+                    user_id,
+                    "prune_history",
+                    "audit_log",
+                    None,
+                    &format!("Se eliminaron {} registros anteriores a {}", deleted, cutoff),
+                    None,
+                )?;
+            }
+        }
+        tables.push(PrunedTable { table: rule.table.to_string(), retention_days, cutoff, deleted });
+    }
+    Ok(PruneReport { tables })
+}
+
+/// Tarea semanal del planificador; nunca toca el registro de auditoría.
+fn run_scheduled_prune(conn: &Connection) -> Result<usize, String> {
+    run_prune_history(conn, false, None).map(|_| 0)
+}
+
+/// Mantenimiento: borra notificaciones leídas y, con `confirm_audit_log`,
+/// registros de auditoría más viejos que su retención (`retention_days.*`).
+/// Solo Administrador.
+#[tauri::command]
+fn prune_history<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    confirm_audit_log: Option<bool>,
+) -> Result<PruneReport, String> {
+    let session = require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let report = run_prune_history(&conn, confirm_audit_log.unwrap_or(false), Some(session.user_id))?;
+    if report.tables.iter().any(|t| t.deleted > 0) {
+        emit_data_changed(&window, "notifications", None);
+    }
+    Ok(report)
+}
+
+// ============================================
+// ALERTAS DE VELOCIDAD DE VENTA
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VelocityAlert {
+    product_id: i32,
+    product_name: String,
+    /// "pico" si vende muy por encima de lo normal, "caida" si muy por debajo
+    kind: String,
+    recent_units: f64,
+    baseline_units: f64,
+    recent_daily: f64,
+    baseline_daily: f64,
+}
+
+/// Días de la ventana reciente y de la línea base que la precede.
+const VELOCITY_RECENT_DAYS: i64 = 7;
+const VELOCITY_BASELINE_DAYS: i64 = 60;
+
+/// Compara la venta diaria de los últimos 7 días con la de los 60 anteriores.
+/// Solo se consideran productos con al menos `velocity_min_sales` unidades en
+/// la ventana que dispara la alerta, para que los de poco volumen no hagan ruido.
+fn compute_velocity_alerts(conn: &Connection) -> Result<Vec<VelocityAlert>, String> {
+    let factor = setting_f64(conn, "velocity_alert_factor", 3.0).max(1.0);
+    let min_sales = setting_f64(conn, "velocity_min_sales", 5.0);
+
+    let sql = format!(
+        "SELECT p.id, p.name,
+                COALESCE(SUM(CASE WHEN substr(s.sale_date,1,10) > date('now', ?1) THEN s.quantity END), 0) as recent,
+                COALESCE(SUM(CASE WHEN substr(s.sale_date,1,10) <= date('now', ?1) THEN s.quantity END), 0) as baseline
+         FROM products p
+         LEFT JOIN {} s ON s.product_id = p.id
+              AND substr(s.sale_date,1,10) > date('now', ?2)
+              AND substr(s.sale_date,1,10) <= date('now')
+         WHERE p.deleted_at IS NULL
+         GROUP BY p.id, p.name",
+        valid_sales(true)
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params![
+                format!("-{} days", VELOCITY_RECENT_DAYS),
+                format!("-{} days", VELOCITY_RECENT_DAYS + VELOCITY_BASELINE_DAYS)
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut alerts = Vec::new();
+    for (product_id, product_name, recent, baseline) in rows {
+        let recent_daily = recent / VELOCITY_RECENT_DAYS as f64;
+        let baseline_daily = baseline / VELOCITY_BASELINE_DAYS as f64;
+        let kind = if baseline > 0.0 && recent >= min_sales && recent_daily >= baseline_daily * factor {
+            "pico"
+        } else if baseline >= min_sales && recent_daily <= baseline_daily / factor {
+            "caida"
+        } else {
+            continue;
+        };
+        alerts.push(VelocityAlert {
+            product_id,
+            product_name,
+            kind: kind.to_string(),
+            recent_units: recent,
+            baseline_units: baseline,
+            recent_daily,
+            baseline_daily,
+        });
+    }
+    Ok(alerts)
+}
+
+/// Tarea diaria: crea una notificación por cada anomalía nueva.
+fn run_velocity_check(conn: &Connection) -> Result<usize, String> {
+    let today = today_key(conn)?;
+    let mut created = 0;
+    for alert in compute_velocity_alerts(conn)? {
+        let title = if alert.kind == "pico" {
+            format!("Pico de ventas: {}", alert.product_name)
+        } else {
+            format!("Caída de ventas: {}", alert.product_name)
+        };
+        let message = format!(
+            "Últimos {} días: {:.2} u/día. Promedio de los {} días anteriores: {:.2} u/día.",
+            VELOCITY_RECENT_DAYS, alert.recent_daily, VELOCITY_BASELINE_DAYS, alert.baseline_daily
+        );
+        let dedupe_key = format!("velocity:{}:{}:{}", alert.product_id, alert.kind, today);
+        if notify(conn, "velocity", &title, &message, Some(alert.product_id), &dedupe_key)? {
+            created += 1;
+        }
+    }
+    Ok(created)
+}
+
+/// Anomalías de velocidad de venta vigentes hoy.
+#[tauri::command]
+fn get_velocity_alerts(state: State<AppState>) -> Result<Vec<VelocityAlert>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    compute_velocity_alerts(&conn)
+}
+
+// ============================================
+// DINERO
+// ============================================
+
+/// Importe en centavos enteros, redondeando la mitad hacia arriba (en valor
+/// absoluto). Corrige primero el ruido de coma flotante para que 1.005 (que
+/// en binario es 1.00499…) dé 101 centavos y no 100.
+fn to_cents(amount: f64) -> i64 {
+    let cents = amount * 100.0;
+    let cents = (cents * 1e6).round() / 1e6;
+    cents.round() as i64
+}
+
+fn from_cents(cents: i64) -> f64 {
+    cents as f64 / 100.0
+}
+
+/// Importe redondeado al centavo, como se guarda en la base.
+fn round_money(amount: f64) -> f64 {
+    from_cents(to_cents(amount))
+}
+
+/// Importe con dos decimales y punto, sin separador de miles, para CSV y
+/// mensajes. Sale de los centavos enteros, así que coincide con lo guardado.
+fn format_amount(amount: f64) -> String {
+    let cents = to_cents(amount);
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+/// Moneda si no hay ajuste `currency`.
+const DEFAULT_CURRENCY: &str = "PEN";
+
+/// Símbolo de una moneda ISO 4217; las desconocidas se muestran con su código.
+fn currency_symbol(currency: &str) -> String {
+    match currency {
+        "PEN" => "S/".to_string(),
+        "USD" => "$".to_string(),
+        "EUR" => "€".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Importe para mostrar: símbolo, separador de miles y dos decimales
+/// ("S/ 1,234.50"; negativos "-S/ 12.00").
+fn format_money_in(amount: f64, currency: &str) -> String {
+    let cents = to_cents(amount);
+    let units = (cents.abs() / 100).to_string();
+    let mut grouped = String::new();
+    for (i, digit) in units.chars().enumerate() {
+        if i > 0 && (units.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!(
+        "{}{} {}.{:02}",
+        if cents < 0 { "-" } else { "" },
+        currency_symbol(currency),
+        grouped,
+        cents.abs() % 100
+    )
+}
+
+/// Formatea un importe como lo muestran tickets y pantallas. Sin `currency`
+/// usa el ajuste `currency` (por defecto PEN).
+#[tauri::command]
+fn format_money(state: State<AppState>, amount: f64, currency: Option<String>) -> Result<String, String> {
+    if !amount.is_finite() {
+        return Err("Importe inválido".to_string());
+    }
+    let currency = match currency.map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()) {
+        Some(currency) => currency,
+        None => {
+            let conn = state.db.lock().map_err(|e| e.to_string())?;
+            get_setting(&conn, "currency").unwrap_or_else(|| DEFAULT_CURRENCY.to_string())
+        }
+    };
+    Ok(format_money_in(amount, &currency))
+}
+
+/// Total de una línea de venta en centavos: precio por cantidad menos el
+/// descuento porcentual, con la regla de redondeo configurada. Es lo que
+/// `add_sale` espera en `sale_price` para esa línea.
+fn line_total_cents(rule: &RoundingRule, unit_price: f64, quantity: f64, discount: f64) -> i64 {
+    let subtotal = unit_price * quantity;
+    let net = (subtotal - subtotal * discount.clamp(0.0, 100.0) / 100.0).max(0.0);
+    to_cents(rule.apply(net))
+}
+
+/// Línea de `compute_sale_totals`.
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleTotalsItem {
+    /// Producto de la línea; su tasa (`effective_tax_rate`) da el impuesto
+    #[serde(default)]
+    product_id: Option<i32>,
+    quantity: f64,
+    unit_price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleTotalsLine {
+    subtotal: f64,
+    discount_amount: f64,
+    /// Impuesto incluido en `total`, el que guarda la venta como `tax_amount`
+    tax_amount: f64,
+    /// Lo que se guarda como `sale_price` de la venta de esta línea
+    total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleTotals {
+    lines: Vec<SaleTotalsLine>,
+    subtotal: f64,
+    discount_total: f64,
+    /// Total sin impuesto: `total` menos `tax_amount`
+    net_total: f64,
+    tax_amount: f64,
+    /// La suma de los `total` de las líneas; ya incluye el impuesto
+    total: f64,
+}
+
+/// Una tasa de impuesto es un porcentaje entre 0 y 100 (18 es el 18%).
+fn valid_tax_rate(rate: f64) -> bool {
+    rate.is_finite() && (0.0..=100.0).contains(&rate)
+}
+
+/// Tasa que se aplica a las ventas de un producto: la suya o, si no tiene,
+/// el ajuste `default_tax_rate` (0 si no está configurado).
+fn effective_tax_rate(conn: &Connection, product_id: i32) -> Result<f64, String> {
+    let rate: Option<f64> = conn
+        .query_row("SELECT tax_rate FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let rate = rate.unwrap_or_else(|| setting_f64(conn, "default_tax_rate", 0.0));
+    if !valid_tax_rate(rate) {
+        return Err(format!(
+            "La tasa de impuesto configurada ({}) no es válida: debe ser un porcentaje entre 0 y 100",
+            rate
+        ));
+    }
+    Ok(rate)
+}
+
+/// Impuesto contenido en un precio final: los precios de venta ya lo
+/// incluyen, así que con 18% un total de 118 lleva 18 de impuesto.
+fn included_tax(total: f64, rate: f64) -> f64 {
+    round_money(total * rate / (100.0 + rate))
+}
+
+/// Totales de una venta calculados igual que al guardarla, para que la
+/// interfaz muestre exactamente lo que quedará registrado. `discounts` son
+/// porcentajes por línea en el mismo orden que `items` (los que falten
+/// valen 0) y se rechazan fuera de 0–100, como al guardar. El impuesto va incluido en el precio, como en `add_sale`: cada
+/// línea usa la tasa de su producto y `tax` (o `default_tax_rate`) es la de
+/// las líneas sin producto.
+#[tauri::command]
+fn compute_sale_totals(
+    state: State<AppState>,
+    items: Vec<SaleTotalsItem>,
+    discounts: Option<Vec<f64>>,
+    tax: Option<f64>,
+) -> Result<SaleTotals, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rule = RoundingRule::load(&conn);
+    let discounts = discounts.unwrap_or_default();
+    let tax = tax.unwrap_or_else(|| setting_f64(&conn, "default_tax_rate", 0.0));
+    if !valid_tax_rate(tax) {
+        return Err("La tasa de impuesto debe ser un porcentaje entre 0 y 100".to_string());
+    }
+    let mut lines = Vec::new();
+    let (mut subtotal, mut discount_total, mut total, mut tax_amount) = (0i64, 0i64, 0i64, 0i64);
+    for (i, item) in items.iter().enumerate() {
+        if !item.unit_price.is_finite() || item.unit_price < 0.0 {
+            return Err(format!("Línea {}: precio inválido", i + 1));
+        }
+        if !item.quantity.is_finite() || item.quantity <= 0.0 {
+            return Err(format!("Línea {}: la cantidad debe ser mayor a 0", i + 1));
+        }
+        let discount = discounts.get(i).copied().unwrap_or(0.0);
+        validate_discount(Some(discount)).map_err(|e| format!("Línea {}: {}", i + 1, e))?;
+        let line_subtotal = to_cents(item.unit_price * item.quantity);
+        let line_total = line_total_cents(&rule, item.unit_price, item.quantity, discount);
+        let rate = match item.product_id {
+            Some(product_id) => effective_tax_rate(&conn, product_id).map_err(|e| format!("Línea {}: {}", i + 1, e))?,
+            None => tax,
+        };
+        let line_tax = to_cents(included_tax(from_cents(line_total), rate));
+        subtotal += line_subtotal;
+        total += line_total;
+        tax_amount += line_tax;
+        discount_total += line_subtotal - line_total;
+        lines.push(SaleTotalsLine {
+            subtotal: from_cents(line_subtotal),
+            discount_amount: from_cents(line_subtotal - line_total),
+            tax_amount: from_cents(line_tax),
+            total: from_cents(line_total),
+        });
+    }
+    Ok(SaleTotals {
+        lines,
+        subtotal: from_cents(subtotal),
+        discount_total: from_cents(discount_total),
+        net_total: from_cents(total - tax_amount),
+        tax_amount: from_cents(tax_amount),
+        total: from_cents(total),
+    })
+}
+
+// ============================================
+// PRECIOS Y REDONDEO
+// ============================================
+
+/// Regla de redondeo configurada en `settings`: `price_rounding_step`
+/// (0.10, 0.50 o 1.00; sin configurar redondea al centavo) y
+/// `price_rounding_mode` (`up`, `down` o `nearest`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RoundingRule {
+    step: f64,
+    mode: String,
+}
+
+impl RoundingRule {
+    fn load(conn: &Connection) -> RoundingRule {
+        let step = setting_f64(conn, "price_rounding_step", 0.01);
+        let mode = get_setting(conn, "price_rounding_mode").unwrap_or_else(|| "nearest".to_string());
+        RoundingRule {
+            step: if step > 0.0 { step } else { 0.01 },
+            mode,
+        }
+    }
+
+    /// Redondea al múltiplo de `step` según el modo. Trabaja en centavos
+    /// enteros para que 37.50 no termine en 38.00 por un error de coma flotante.
+    fn apply(&self, value: f64) -> f64 {
+        let cents = to_cents(value) as f64;
+        let step = to_cents(self.step).max(1) as f64;
+        let units = cents / step;
+        let units = match self.mode.as_str() {
+            "up" => units.ceil(),
+            "down" => units.floor(),
+            _ => units.round(),
+        };
+        from_cents((units * step) as i64)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceCalculation {
+    product_id: i32,
+    quantity: f64,
+    unit_price: f64,
+    discount: f64,
+    subtotal: f64,
+    discount_amount: f64,
+    /// Total antes de aplicar la regla de redondeo
+    unrounded_total: f64,
+    total: f64,
+    rounded: bool,
+    /// Precio de catálogo cuando `unit_price` viene de una promoción
+    original_price: Option<f64>,
+    promotion_id: Option<i64>,
+    promotion_name: Option<String>,
+}
+
+/// Calcula el total de una línea de venta: precio por cantidad, descuento
+/// porcentual y redondeo configurado. Sin `unit_price` usa el precio del
+/// producto o el de su promoción vigente; con `unit_price` respeta el precio
+/// que ajustó el vendedor.
+#[tauri::command]
+fn calculate_price(
+    state: State<AppState>,
+    product_id: i32,
+    quantity: f64,
+    unit_price: Option<f64>,
+    discount: Option<f64>,
+) -> Result<PriceCalculation, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    validate_quantity(&conn, product_id, quantity)?;
+    let mut promotion: Option<Promotion> = None;
+    let mut original_price = None;
+    let unit_price = match unit_price {
+        Some(price) => price,
+        None => match active_promotion_for(&conn, product_id)? {
+            Some((promo, catalog_price, promo_price)) => {
+                promotion = Some(promo);
+                original_price = Some(catalog_price);
+                promo_price
+            }
+            None => conn
+                .query_row(
+                    "SELECT sale_price FROM products WHERE id = ?1",
+                    rusqlite::params![product_id],
+                    |row| row.get::<_, Option<f64>>(0),
+                )
+                .map_err(|_| "Producto no encontrado".to_string())?
+                .ok_or_else(|| "El producto no tiene precio de venta".to_string())?,
+        },
+    };
+    let discount = discount.unwrap_or(0.0).clamp(0.0, 100.0);
+
+    let subtotal = unit_price * quantity;
+    let discount_amount = subtotal * discount / 100.0;
+    let unrounded_total = (subtotal - discount_amount).max(0.0);
+    let total = from_cents(line_total_cents(&RoundingRule::load(&conn), unit_price, quantity, discount));
+
+    Ok(PriceCalculation {
+        product_id,
+        quantity,
+        unit_price,
+        discount,
+        subtotal,
+        discount_amount,
+        unrounded_total,
+        total,
+        rounded: (total - unrounded_total).abs() >= 0.005,
+        original_price,
+        promotion_id: promotion.as_ref().and_then(|p| p.id),
+        promotion_name: promotion.map(|p| p.name),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceChange {
+    product_id: i32,
+    name: String,
+    old_price: f64,
+    unrounded_price: f64,
+    new_price: f64,
+}
+
+/// Ajusta en `percent` por ciento el precio de venta de los productos de una
+/// categoría (o de todos), aplicando la regla de redondeo. Con `dry_run`
+/// solo devuelve los cambios que haría. Cada cambio queda en `price_history`.
+#[tauri::command]
+fn bulk_update_prices<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    category: Option<String>,
+    percent: f64,
+    dry_run: Option<bool>,
+) -> Result<Vec<PriceChange>, String> {
+    if percent <= -100.0 {
+        return Err("El porcentaje debe ser mayor a -100".to_string());
+    }
+    let changed_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let rule = RoundingRule::load(&conn);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, sale_price FROM products
+             WHERE sale_price IS NOT NULL AND deleted_at IS NULL AND (?1 IS NULL OR category = ?1)
+             ORDER BY name",
+        )
+        .map_err(|e| e.to_string())?;
+    let changes = stmt
+        .query_map(rusqlite::params![category], |row| {
+            let old_price: f64 = row.get(2)?;
+            let unrounded_price = old_price * (1.0 + percent / 100.0);
+            Ok(PriceChange {
+                product_id: row.get(0)?,
+                name: row.get(1)?,
+                old_price,
+                unrounded_price,
+                new_price: rule.apply(unrounded_price),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    if dry_run.unwrap_or(true) {
+        return Ok(changes);
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        for change in &changes {
+            conn.execute(
+                "UPDATE products SET sale_price = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+                rusqlite::params![change.new_price, change.product_id],
+            )
+            .map_err(|e| e.to_string())?;
+            sync_retail_price(&conn, change.product_id as i64, Some(change.new_price))?;
+            record_price_change(
+                &conn,
+                change.product_id,
+                "sale_price",
+                Some(change.old_price),
+                Some(change.new_price),
+                changed_by,
+                &format!("ajuste masivo {:+}%", percent),
+            )?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "products", None);
+            Ok(changes)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// HISTORIAL DE PRECIOS
+// ============================================
+
+/// Registra un cambio de `sale_price` o `cost_price`. No deja filas cuando el
+/// valor no cambió.
+fn record_price_change(
+    conn: &Connection,
+    product_id: i32,
+    field: &str,
+    old_value: Option<f64>,
+    new_value: Option<f64>,
+    changed_by: Option<i32>,
+    source: &str,
+) -> Result<(), String> {
+    let unchanged = match (old_value, new_value) {
+        (Some(old), Some(new)) => (old - new).abs() < 0.005,
+        (None, None) => true,
+        _ => false,
+    };
+    if unchanged {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO price_history (product_id, field, old_value, new_value, changed_by, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![product_id, field, old_value, new_value, changed_by, source],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceHistoryEntry {
+    id: i64,
+    /// "sale_price" o "cost_price"
+    field: String,
+    old_value: Option<f64>,
+    new_value: Option<f64>,
+    changed_by: Option<i32>,
+    changed_by_name: Option<String>,
+    /// Origen del cambio: "update_product", "ajuste masivo +10%", "proveedor: …"
+    source: Option<String>,
+    changed_at: String,
+}
+
+/// Cambios de precio de venta y costo de un producto, del más reciente al
+/// más antiguo.
+#[tauri::command]
+fn get_price_history(state: State<AppState>, product_id: i32) -> Result<Vec<PriceHistoryEntry>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT h.id, h.field, h.old_value, h.new_value, h.changed_by, u.username, h.source, h.changed_at
+             FROM price_history h LEFT JOIN users u ON u.id = h.changed_by
+             WHERE h.product_id = ?1
+             ORDER BY h.changed_at DESC, h.id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map([product_id], |row| {
+            Ok(PriceHistoryEntry {
+                id: row.get(0)?,
+                field: row.get(1)?,
+                old_value: row.get(2)?,
+                new_value: row.get(3)?,
+                changed_by: row.get(4)?,
+                changed_by_name: row.get(5)?,
+                source: row.get(6)?,
+                changed_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+// ============================================
+// IMPORTACIÓN DE PRECIOS DE PROVEEDOR
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SupplierPriceChange {
+    line: usize,
+    product_id: i32,
+    sku: String,
+    name: String,
+    old_cost: Option<f64>,
+    new_cost: f64,
+    sale_price: Option<f64>,
+    /// Margen sobre el precio de venta con el costo nuevo, en porcentaje
+    new_margin: Option<f64>,
+    margin_below_threshold: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductSuggestion {
+    product_id: i32,
+    name: String,
+    score: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UnmatchedSupplierRow {
+    line: usize,
+    sku: String,
+    description: String,
+    cost: f64,
+    suggestion: Option<ProductSuggestion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SupplierPriceImport {
+    supplier: String,
+    dry_run: bool,
+    min_margin: f64,
+    matched: Vec<SupplierPriceChange>,
+    unmatched: Vec<UnmatchedSupplierRow>,
+    errors: Vec<String>,
+    updated: usize,
+}
+
+/// Similitud mínima para sugerir un producto por nombre.
+const NAME_MATCH_THRESHOLD: f64 = 0.6;
+
+/// Convierte un importe escrito como "12.50", "12,50", "1.234,50" o
+/// "S/ 12.50". El último separador es el decimal y el otro agrupa miles; si se
+/// repite, agrupa miles. Rechaza lo que no se puede leer sin ambigüedad, como
+/// "S/. 12.50" (el punto del símbolo quedaría delante del número).
+fn parse_amount(raw: &str) -> Option<f64> {
+    let cleaned: String = raw
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',' || *c == '-')
+        .collect();
+    let (sign, digits) = match cleaned.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", cleaned.as_str()),
+    };
+    if digits.is_empty()
+        || digits.contains('-')
+        || digits.starts_with(['.', ','])
+        || digits.ends_with(['.', ','])
+    {
+        return None;
+    }
+    let (integer, fraction) = match digits.rfind(['.', ',']) {
+        Some(pos) if digits.matches(&digits[pos..pos + 1]).count() == 1 => (&digits[..pos], &digits[pos + 1..]),
+        _ => (digits, ""),
+    };
+    let groups: Vec<&str> = integer.split(['.', ',']).collect();
+    if groups[1..].iter().any(|g| g.len() != 3) {
+        return None;
+    }
+    format!("{}{}.{}", sign, groups.concat(), if fraction.is_empty() { "0" } else { fraction }).parse().ok()
+}
+
+/// Archivo CSV leído en memoria: encabezados tal como vienen (sin espacios
+/// alrededor) y filas con su número de línea (la 1 es el encabezado).
+struct CsvTable {
+    headers: Vec<String>,
+    rows: Vec<(usize, Vec<String>)>,
+    delimiter: u8,
+    encoding: &'static str,
+}
+
+impl CsvTable {
+    /// Posición de la primera columna cuyo encabezado (sin distinguir
+    /// mayúsculas) esté en `names`.
+    fn column(&self, names: &[&str]) -> Option<usize> {
+        self.headers.iter().position(|h| names.contains(&h.to_lowercase().as_str()))
+    }
+}
+
+/// Texto de un archivo a importar: UTF-8 (con o sin BOM) o, si no lo es,
+/// Latin-1, que es como Excel en Windows guarda los CSV con tildes.
+fn decode_csv_bytes(bytes: Vec<u8>) -> (String, &'static str) {
+    match String::from_utf8(bytes) {
+        Ok(text) => match text.strip_prefix('\u{feff}') {
+            Some(stripped) => (stripped.to_string(), "UTF-8"),
+            None => (text, "UTF-8"),
+        },
+        Err(e) => (e.into_bytes().into_iter().map(char::from).collect(), "Latin-1"),
+    }
+}
+
+/// Separador más frecuente en el encabezado entre `,`, `;`, tabulador y `|`;
+/// ante un empate, la coma.
+fn sniff_delimiter(first_line: &str) -> u8 {
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .max_by_key(|d| (first_line.bytes().filter(|b| b == d).count(), *d == b','))
+        .unwrap_or(b',')
+}
+
+/// Lee un CSV detectando separador y codificación.
+fn read_csv_table(path: &Path) -> Result<CsvTable, String> {
+    read_csv_table_with_hash(path).map(|(table, _)| table)
+}
+
+/// Como `read_csv_table`, y además el SHA-256 del archivo (en hexadecimal)
+/// para reconocerlo al reanudar una importación.
+fn read_csv_table_with_hash(path: &Path) -> Result<(CsvTable, String), String> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("No se pudo leer el archivo {}: {}", path.display(), e))?;
+    let hash: String = hmac_sha256::Hash::hash(&bytes).iter().map(|b| format!("{:02x}", b)).collect();
+    let (content, encoding) = decode_csv_bytes(bytes);
+    let delimiter = sniff_delimiter(content.lines().next().unwrap_or(""));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        rows.push((i + 2, record.iter().map(|v| v.trim().to_string()).collect()));
+    }
+    Ok((CsvTable { headers, rows, delimiter, encoding }, hash))
+}
+
+/// Lee la primera hoja de un libro de Excel (.xlsx, .xls u .ods) como una
+/// `CsvTable`: la primera fila con datos es el encabezado y cada fila lleva su
+/// número de fila en Excel.
+fn read_xlsx_table(path: &Path) -> Result<CsvTable, String> {
+    use calamine::Reader;
+    let mut workbook = calamine::open_workbook_auto(path)
+        .map_err(|e| format!("No se pudo abrir el libro {}: {}", path.display(), e))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| format!("El libro {} no tiene hojas", path.display()))?
+        .map_err(|e| format!("No se pudo leer la primera hoja de {}: {}", path.display(), e))?;
+    let first_row = range.start().map(|(row, _)| row as usize + 1).unwrap_or(1);
+    let mut rows = range
+        .rows()
+        .enumerate()
+        .map(|(i, cells)| (first_row + i, cells.iter().map(xlsx_cell_text).collect::<Vec<String>>()));
+    let headers = rows.next().map(|(_, headers)| headers).unwrap_or_default();
+    let rows = rows.filter(|(_, values)| values.iter().any(|v| !v.is_empty())).collect();
+    Ok(CsvTable { headers, rows, delimiter: b',', encoding: "XLSX" })
+}
+
+/// Texto de una celda de Excel. Los textos se leen tal cual, con sus ceros a
+/// la izquierda; un número entero queda sin decimales (un SKU numérico es
+/// "123", no "123.0") y una fecha como AAAA-MM-DD.
+fn xlsx_cell_text(cell: &calamine::Data) -> String {
+    use calamine::Data;
+    match cell {
+        Data::String(text) | Data::DateTimeIso(text) | Data::DurationIso(text) => text.trim().to_string(),
+        Data::Int(value) => value.to_string(),
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => format!("{}", *value as i64),
+        Data::Float(value) => value.to_string(),
+        Data::Bool(value) => value.to_string(),
+        Data::DateTime(value) => value
+            .as_datetime()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| value.as_f64().to_string()),
+        Data::Error(error) => error.to_string(),
+        Data::Empty => String::new(),
+    }
+}
+
+/// Campo que espera un importador y los encabezados que lo identifican.
+struct ImportField {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    required: bool,
+}
+
+const SALES_IMPORT_FIELDS: &[ImportField] = &[
+    ImportField { name: "sku", aliases: &["sku", "codigo", "código"], required: true },
+    ImportField { name: "quantity", aliases: &["quantity", "cantidad"], required: true },
+    ImportField { name: "sale_price", aliases: &["sale_price", "total", "precio"], required: true },
+    ImportField { name: "discount", aliases: &["discount", "descuento"], required: false },
+    ImportField { name: "channel", aliases: &["channel", "canal"], required: false },
+    ImportField { name: "sale_date", aliases: &["sale_date", "fecha"], required: false },
+    ImportField { name: "customer", aliases: &["customer", "cliente"], required: false },
+];
+
+const SUPPLIER_PRICE_FIELDS: &[ImportField] = &[
+    ImportField { name: "sku", aliases: &["sku", "codigo", "código"], required: true },
+    ImportField {
+        name: "description",
+        aliases: &["description", "descripcion", "descripción", "producto"],
+        required: false,
+    },
+    ImportField { name: "cost", aliases: &["cost", "costo", "precio", "cost_price"], required: true },
+];
+
+const PRODUCT_IMPORT_FIELDS: &[ImportField] = &[
+    ImportField { name: "name", aliases: &["name", "nombre", "producto"], required: true },
+    ImportField { name: "sku", aliases: &["sku", "codigo", "código"], required: false },
+    ImportField { name: "sale_price", aliases: &["sale_price", "precio", "precio venta", "precio de venta"], required: false },
+    ImportField { name: "cost_price", aliases: &["cost_price", "costo"], required: false },
+    ImportField { name: "brand", aliases: &["brand", "marca"], required: false },
+    ImportField { name: "category", aliases: &["category", "categoria", "categoría"], required: false },
+    ImportField { name: "presentation", aliases: &["presentation", "presentacion", "presentación"], required: false },
+    ImportField { name: "flavor", aliases: &["flavor", "sabor"], required: false },
+    ImportField { name: "weight", aliases: &["weight", "peso"], required: false },
+    ImportField { name: "expiry_date", aliases: &["expiry_date", "vencimiento", "fecha de vencimiento"], required: false },
+    ImportField { name: "lot_number", aliases: &["lot_number", "lote"], required: false },
+    ImportField { name: "min_stock", aliases: &["min_stock", "stock minimo", "stock mínimo"], required: false },
+    ImportField { name: "max_stock", aliases: &["max_stock", "stock maximo", "stock máximo"], required: false },
+    ImportField { name: "location", aliases: &["location", "ubicacion", "ubicación"], required: false },
+    ImportField { name: "status", aliases: &["status", "estado"], required: false },
+    ImportField { name: "unit", aliases: &["unit", "unidad"], required: false },
+    ImportField { name: "barcode", aliases: &["barcode", "codigo de barras", "código de barras", "ean"], required: false },
+    ImportField { name: "description", aliases: &["description", "descripcion", "descripción"], required: false },
+    ImportField { name: "default_supplier", aliases: &["default_supplier", "supplier", "proveedor"], required: false },
+    ImportField { name: "tax_rate", aliases: &["tax_rate", "impuesto", "igv"], required: false },
+    ImportField { name: "currency", aliases: &["currency", "moneda"], required: false },
+];
+
+const USER_IMPORT_FIELDS: &[ImportField] = &[
+    ImportField { name: "username", aliases: &["username", "usuario"], required: true },
+    ImportField { name: "fullname", aliases: &["fullname", "nombre", "nombre completo"], required: false },
+    ImportField { name: "role", aliases: &["role", "rol"], required: true },
+    ImportField {
+        name: "password",
+        aliases: &["password", "contraseña", "clave", "contraseña temporal"],
+        required: false,
+    },
+];
+
+/// Campos de cada importador que acepta un mapeo de columnas.
+fn import_schema(name: &str) -> Result<&'static [ImportField], String> {
+    match name {
+        "sales" => Ok(SALES_IMPORT_FIELDS),
+        "supplier_prices" => Ok(SUPPLIER_PRICE_FIELDS),
+        "users" => Ok(USER_IMPORT_FIELDS),
+        other => Err(format!(
+            "Esquema de importación desconocido: '{}'. Usa sales, supplier_prices o users",
+            other
+        )),
+    }
+}
+
+/// Similitud mínima entre un encabezado y un alias para sugerirlo.
+const HEADER_MATCH_THRESHOLD: f64 = 0.8;
+
+/// Columna de cada campo de `fields`. Con `mapping` (campo → encabezado,
+/// confirmado por el usuario tras `inspect_csv`) se usa tal cual; sin él, se
+/// sugiere por alias exacto o, si no hay, por el encabezado más parecido.
+/// Una columna no se asigna a dos campos.
+fn map_csv_columns(
+    table: &CsvTable,
+    fields: &[ImportField],
+    mapping: Option<&HashMap<String, String>>,
+) -> Result<HashMap<&'static str, usize>, String> {
+    let mut columns: HashMap<&'static str, usize> = HashMap::new();
+    if let Some(mapping) = mapping {
+        for (field, header) in mapping {
+            let field = fields
+                .iter()
+                .find(|f| f.name == field)
+                .ok_or_else(|| format!("Campo desconocido en el mapeo: '{}'", field))?;
+            let col = table
+                .headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(header.trim()))
+                .ok_or_else(|| format!("La columna '{}' no está en el archivo", header))?;
+            columns.insert(field.name, col);
+        }
+        return Ok(columns);
+    }
+
+    let mut taken = vec![false; table.headers.len()];
+    for field in fields {
+        if let Some(col) = table.column(field.aliases).filter(|c| !taken[*c]) {
+            taken[col] = true;
+            columns.insert(field.name, col);
+        }
+    }
+    let unmatched: Vec<&ImportField> = fields.iter().filter(|f| !columns.contains_key(f.name)).collect();
+    for field in unmatched {
+        let best = table
+            .headers
+            .iter()
+            .enumerate()
+            .filter(|(col, _)| !taken[*col])
+            .map(|(col, header)| {
+                let header = header.to_lowercase();
+                let score = field
+                    .aliases
+                    .iter()
+                    .map(|alias| strsim::normalized_levenshtein(alias, &header))
+                    .fold(0.0, f64::max);
+                (col, score)
+            })
+            .filter(|(_, score)| *score >= HEADER_MATCH_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((col, _)) = best {
+            taken[col] = true;
+            columns.insert(field.name, col);
+        }
+    }
+    Ok(columns)
+}
+
+/// Columna de un campo obligatorio, con el mismo mensaje en todos los importadores.
+fn required_column(columns: &HashMap<&'static str, usize>, field: &str) -> Result<usize, String> {
+    columns.get(field).copied().ok_or_else(|| format!("Falta la columna {}", field))
+}
+
+/// Tipo que aparenta una columna según sus valores no vacíos.
+fn detect_column_type<'a>(values: impl Iterator<Item = &'a str>) -> &'static str {
+    let values: Vec<&str> = values.filter(|v| !v.is_empty()).collect();
+    let is_decimal = |v: &str| {
+        v.parse::<f64>().is_ok() || (!v.contains('.') && v.replace(',', ".").parse::<f64>().is_ok())
+    };
+    let is_date = |v: &str| {
+        v.get(..10).map(is_valid_date).unwrap_or(false)
+            && matches!(v.as_bytes().get(10), None | Some(b' ') | Some(b'T'))
+    };
+    if values.is_empty() {
+        "vacia"
+    } else if values.iter().all(|v| is_date(v)) {
+        "fecha"
+    } else if values.iter().all(|v| v.parse::<i64>().is_ok()) {
+        "entero"
+    } else if values.iter().all(|v| is_decimal(v)) {
+        "decimal"
+    } else {
+        "texto"
+    }
+}
+
+/// Filas de ejemplo que devuelve `inspect_csv`.
+const CSV_SAMPLE_ROWS: usize = 10;
+
+#[derive(Debug, Serialize)]
+struct CsvFieldMapping {
+    field: String,
+    required: bool,
+    /// Encabezado sugerido; `None` si ninguno se parece
+    column: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CsvInspection {
+    encoding: String,
+    delimiter: String,
+    headers: Vec<String>,
+    /// "entero", "decimal", "fecha", "texto" o "vacia", por columna
+    column_types: Vec<String>,
+    sample_rows: Vec<Vec<String>>,
+    total_rows: usize,
+    mapping: Vec<CsvFieldMapping>,
+    missing_required: Vec<String>,
+}
+
+/// Primer paso de cualquier importación: lee el CSV sin importar nada y
+/// devuelve codificación y separador detectados, encabezados, tipos por
+/// columna, las primeras filas y el mapeo sugerido contra los campos de
+/// `expected_schema` (`sales`, `supplier_prices` o `users`). El mapeo confirmado se
+/// pasa después al importador como `mapping`.
+#[tauri::command]
+fn inspect_csv(path: String, expected_schema: String) -> Result<CsvInspection, String> {
+    let fields = import_schema(&expected_schema)?;
+    let table = read_csv_table(Path::new(&path))?;
+    let columns = map_csv_columns(&table, fields, None)?;
+
+    let column_types = (0..table.headers.len())
+        .map(|col| {
+            detect_column_type(table.rows.iter().map(|(_, values)| values.get(col).map(String::as_str).unwrap_or("")))
+                .to_string()
+        })
+        .collect();
+    let mapping: Vec<CsvFieldMapping> = fields
+        .iter()
+        .map(|f| CsvFieldMapping {
+            field: f.name.to_string(),
+            required: f.required,
+            column: columns.get(f.name).map(|col| table.headers[*col].clone()),
+        })
+        .collect();
+    let missing_required = mapping
+        .iter()
+        .filter(|m| m.required && m.column.is_none())
+        .map(|m| m.field.clone())
+        .collect();
+
+    Ok(CsvInspection {
+        encoding: table.encoding.to_string(),
+        delimiter: match table.delimiter {
+            b'\t' => "\\t".to_string(),
+            d => char::from(d).to_string(),
+        },
+        column_types,
+        sample_rows: table.rows.iter().take(CSV_SAMPLE_ROWS).map(|(_, values)| values.clone()).collect(),
+        total_rows: table.rows.len(),
+        headers: table.headers,
+        mapping,
+        missing_required,
+    })
+}
+
+/// Lista de precios del proveedor: SKU, descripción y costo nuevo. Acepta
+/// encabezados en español o inglés o el mapeo confirmado en `inspect_csv`;
+/// sin mapeo, las columnas no reconocidas se toman por posición (1.ª, 2.ª, 3.ª).
+fn read_supplier_price_list(
+    path: &Path,
+    mapping: Option<&HashMap<String, String>>,
+) -> Result<Vec<(usize, String, String, String)>, String> {
+    let table = read_csv_table(path)?;
+    let columns = map_csv_columns(&table, SUPPLIER_PRICE_FIELDS, mapping)?;
+    let (sku_col, desc_col, cost_col) = match mapping {
+        Some(_) => (
+            Some(required_column(&columns, "sku")?),
+            columns.get("description").copied(),
+            Some(required_column(&columns, "cost")?),
+        ),
+        None => (
+            Some(columns.get("sku").copied().unwrap_or(0)),
+            Some(columns.get("description").copied().unwrap_or(1)),
+            Some(columns.get("cost").copied().unwrap_or(2)),
+        ),
+    };
+
+    Ok(table
+        .rows
+        .into_iter()
+        .map(|(line, values)| {
+            let get = |col: Option<usize>| col.and_then(|c| values.get(c)).cloned().unwrap_or_default();
+            (line, get(sku_col), get(desc_col), get(cost_col))
+        })
+        .collect())
+}
+
+/// Actualiza `cost_price` desde la lista de precios CSV de un proveedor.
+/// Empareja por SKU; las filas sin coincidencia se informan con el producto
+/// de nombre más parecido. Marca los productos cuyo margen queda por debajo
+/// de `min_margin_percent` (configuración, 15 % por defecto). Por defecto es
+/// una simulación y no modifica nada.
+#[tauri::command]
+fn import_supplier_prices<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    supplier: String,
+    path: String,
+    dry_run: Option<bool>,
+    mapping: Option<HashMap<String, String>>,
+) -> Result<SupplierPriceImport, String> {
+    let dry = dry_run.unwrap_or(true);
+    let rows = read_supplier_price_list(Path::new(&path), mapping.as_ref())?;
+    let changed_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let min_margin = setting_f64(&conn, "min_margin_percent", 15.0);
+
+    // (id, sku, name, cost_price, sale_price)
+    let mut stmt = conn
+        .prepare("SELECT id, sku, name, cost_price, sale_price FROM products WHERE deleted_at IS NULL")
+        .map_err(|e| e.to_string())?;
+    let products = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+                row.get::<_, Option<f64>>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut report = SupplierPriceImport {
+        supplier: supplier.clone(),
+        dry_run: dry,
+        min_margin,
+        matched: Vec::new(),
+        unmatched: Vec::new(),
+        errors: Vec::new(),
+        updated: 0,
+    };
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for (line, sku, description, raw_cost) in rows {
+        let cost = match parse_amount(&raw_cost) {
+            Some(c) if c >= 0.0 => c,
+            _ => {
+                report.errors.push(format!("Línea {}: costo inválido '{}'", line, raw_cost));
+                continue;
+            }
+        };
+        if sku.is_empty() && description.is_empty() {
+            continue;
+        }
+        if !sku.is_empty() {
+            if let Some(prev) = seen.insert(sku.to_uppercase(), line) {
+                report.errors.push(format!(
+                    "Línea {}: SKU {} repetido (ya aparece en la línea {})",
+                    line, sku, prev
+                ));
+                continue;
+            }
+        }
+
+        let product = products.iter().find(|(_, p_sku, ..)| {
+            !sku.is_empty()
+                && p_sku
+                    .as_deref()
+                    .map(|s| s.trim().eq_ignore_ascii_case(&sku))
+                    .unwrap_or(false)
+        });
+        match product {
+            Some((id, _, name, old_cost, sale_price)) => {
+                let new_margin = sale_price
+                    .filter(|p| *p > 0.0)
+                    .map(|p| (p - cost) / p * 100.0);
+                report.matched.push(SupplierPriceChange {
+                    line,
+                    product_id: *id,
+                    sku: sku.clone(),
+                    name: name.clone(),
+                    old_cost: *old_cost,
+                    new_cost: cost,
+                    sale_price: *sale_price,
+                    new_margin,
+                    margin_below_threshold: new_margin.map(|m| m < min_margin).unwrap_or(false),
+                });
+            }
+            None => {
+                let wanted = description.to_lowercase();
+                let suggestion = products
+                    .iter()
+                    .map(|(id, _, name, ..)| {
+                        (id, name, strsim::normalized_levenshtein(&wanted, &name.to_lowercase()))
+                    })
+                    .filter(|(_, _, score)| *score >= NAME_MATCH_THRESHOLD)
+                    .max_by(|a, b| a.2.total_cmp(&b.2))
+                    .map(|(id, name, score)| ProductSuggestion {
+                        product_id: *id,
+                        name: name.clone(),
+                        score,
+                    });
+                report.unmatched.push(UnmatchedSupplierRow {
+                    line,
+                    sku,
+                    description,
+                    cost,
+                    suggestion,
+                });
+            }
+        }
+    }
+
+    if dry {
+        return Ok(report);
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<usize, String> = (|| {
+        let mut updated = 0;
+        for change in &report.matched {
+            if change.old_cost.map(|c| (c - change.new_cost).abs() < 0.005).unwrap_or(false) {
+                continue;
+            }
+            conn.execute(
+                "UPDATE products SET cost_price = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+                rusqlite::params![change.new_cost, change.product_id],
+            )
+            .map_err(|e| e.to_string())?;
+            record_price_change(
+                &conn,
+                change.product_id,
+                "cost_price",
+                change.old_cost,
+                Some(change.new_cost),
+                changed_by,
+                &format!("proveedor: {}", supplier),
+            )?;
+            if change.margin_below_threshold {
+                notify(
+                    &conn,
+                    "margin",
+                    &format!("Margen bajo: {}", change.name),
+                    &format!(
+                        "Con el costo nuevo de {} ({:.2}) el margen queda en {:.1} %, por debajo del {:.1} %.",
+                        supplier,
+                        change.new_cost,
+                        change.new_margin.unwrap_or(0.0),
+                        min_margin
+                    ),
+                    Some(change.product_id),
+                    &format!("margin:{}:{:.2}", change.product_id, change.new_cost),
+                )?;
+            }
+            updated += 1;
+        }
+        Ok(updated)
+    })();
+
+    match result {
+        Ok(updated) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            report.updated = updated;
+            emit_data_changed(&window, "products", None);
+            Ok(report)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// CANALES DE VENTA POR PRODUCTO
+// ============================================
+
+fn product_channels(conn: &Connection, product_id: i32) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT channel FROM product_channels WHERE product_id = ?1 ORDER BY channel")
+        .map_err(|e| e.to_string())?;
+    let channels = stmt
+        .query_map(rusqlite::params![product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(channels)
+}
+
+/// Canales permitidos de todos los productos con restricciones.
+fn all_product_channels(conn: &Connection) -> Result<HashMap<i32, Vec<String>>, String> {
+    let mut stmt = conn
+        .prepare("SELECT product_id, channel FROM product_channels ORDER BY product_id, channel")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut map: HashMap<i32, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (product_id, channel) = row.map_err(|e| e.to_string())?;
+        map.entry(product_id).or_default().push(channel);
+    }
+    Ok(map)
+}
+
+/// Reemplaza los canales permitidos de un producto. Una lista vacía lo deja
+/// disponible en todos los canales.
+fn set_product_channels(conn: &Connection, product_id: i32, channels: &[String]) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM product_channels WHERE product_id = ?1",
+        rusqlite::params![product_id],
+    )
+    .map_err(|e| e.to_string())?;
+    for channel in channels.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        conn.execute(
+            "INSERT OR IGNORE INTO product_channels (product_id, channel) VALUES (?1, ?2)",
+            rusqlite::params![product_id, channel],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Rechaza la venta si el producto está restringido a otros canales.
+fn check_sale_channel(conn: &Connection, product_id: i32, channel: Option<&str>) -> Result<(), String> {
+    let allowed = product_channels(conn, product_id)?;
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    let channel = channel.unwrap_or("").trim();
+    if allowed.iter().any(|c| c.eq_ignore_ascii_case(channel)) {
+        return Ok(());
+    }
+    let name: String = conn
+        .query_row(
+            "SELECT name FROM products WHERE id = ?1",
+            rusqlite::params![product_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| format!("#{}", product_id));
+    Err(format!(
+        "El producto '{}' no se puede vender por el canal '{}'. Canales permitidos: {}",
+        name,
+        channel,
+        allowed.join(", ")
+    ))
+}
+
+// ============================================
+// ETIQUETAS DE PRODUCTOS
+// ============================================
+
+/// Etiquetas en minúsculas y sin espacios alrededor, sin vacías ni repetidas,
+/// ordenadas.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn product_tags(conn: &Connection, product_id: i32) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT tag FROM product_tags WHERE product_id = ?1 ORDER BY tag")
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map([product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+/// Etiquetas de todos los productos que tienen alguna.
+fn all_product_tags(conn: &Connection) -> Result<HashMap<i32, Vec<String>>, String> {
+    let mut stmt = conn
+        .prepare("SELECT product_id, tag FROM product_tags ORDER BY product_id, tag")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut map: HashMap<i32, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (product_id, tag) = row.map_err(|e| e.to_string())?;
+        map.entry(product_id).or_default().push(tag);
+    }
+    Ok(map)
+}
+
+/// Reemplaza las etiquetas de un producto; se guardan normalizadas (ver
+/// `normalize_tags`). Devuelve las etiquetas que quedaron.
+#[tauri::command]
+fn set_product_tags<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    product_id: i32,
+    tags: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let tags = normalize_tags(&tags);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM products WHERE id = ?1)", [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("Producto no encontrado".to_string());
+    }
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        conn.execute("DELETE FROM product_tags WHERE product_id = ?1", [product_id])
+            .map_err(|e| e.to_string())?;
+        for tag in &tags {
+            conn.execute(
+                "INSERT INTO product_tags (product_id, tag) VALUES (?1, ?2)",
+                rusqlite::params![product_id, tag],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = conn.execute("ROLLBACK", []);
+        return Err(err);
+    }
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "products", Some(product_id as i64));
+    Ok(tags)
+}
+
+#[tauri::command]
+fn get_product_tags(state: State<AppState>, product_id: i32) -> Result<Vec<String>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    product_tags(&conn, product_id)
+}
+
+/// Productos del catálogo (sin los eliminados) con la etiqueta `tag`, sin
+/// distinguir mayúsculas, ordenados por nombre.
+#[tauri::command]
+fn get_products_by_tag(state: State<AppState>, tag: String) -> Result<Vec<Product>, String> {
+    let tag = tag.trim().to_lowercase();
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM products
+             WHERE deleted_at IS NULL AND id IN (SELECT product_id FROM product_tags WHERE tag = ?1)
+             ORDER BY name COLLATE NOCASE, id",
+            PRODUCT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut products = stmt
+        .query_map([tag], product_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut channels = all_product_channels(&conn)?;
+    for product in products.iter_mut() {
+        let list = product.id.and_then(|id| channels.remove(&id)).unwrap_or_default();
+        product.allowed_channels = Some(list);
+    }
+    Ok(products)
+}
+
+// ============================================
+// CATEGORÍAS
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Category {
+    id: Option<i64>,
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    sort_order: i32,
+    /// Productos del catálogo en la categoría (solo lectura)
+    #[serde(default)]
+    product_count: i64,
+}
+
+/// Pasa las categorías de texto libre a `categories`: una por nombre (sin
+/// distinguir mayúsculas, gana la forma más usada) y cada producto queda con su
+/// `category_id` y el nombre de la categoría. Se ejecuta en cada arranque y
+/// solo toca los productos sin `category_id`.
+fn migrate_product_categories(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO categories (name)
+         SELECT TRIM(category) FROM products
+         WHERE category_id IS NULL AND TRIM(COALESCE(category, '')) <> ''
+         GROUP BY TRIM(category)
+         ORDER BY COUNT(*) DESC, MIN(id)",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE products
+         SET category_id = (SELECT id FROM categories WHERE name = TRIM(products.category)),
+             category = (SELECT name FROM categories WHERE name = TRIM(products.category))
+         WHERE category_id IS NULL AND TRIM(COALESCE(category, '')) <> ''",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Categoría de un producto: el nombre se busca sin distinguir mayúsculas y,
+/// si no existe, se crea; sin nombre vale `category_id`. El nombre manda
+/// porque el formulario edita el texto y reenvía el `category_id` anterior.
+/// Devuelve el id y el nombre tal como está en `categories` (o `None` para un
+/// producto sin categoría).
+fn resolve_category(
+    conn: &Connection,
+    category_id: Option<i32>,
+    name: Option<&str>,
+) -> Result<(Option<i32>, Option<String>), String> {
+    let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) else {
+        let Some(id) = category_id else {
+            return Ok((None, None));
+        };
+        let name: String = conn
+            .query_row("SELECT name FROM categories WHERE id = ?1", [id], |row| row.get(0))
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => "Categoría no encontrada".to_string(),
+                e => e.to_string(),
+            })?;
+        return Ok((Some(id), Some(name)));
+    };
+    conn.execute("INSERT OR IGNORE INTO categories (name) VALUES (?1)", [name])
+        .map_err(|e| e.to_string())?;
+    let (id, name) = conn
+        .query_row("SELECT id, name FROM categories WHERE name = ?1", [name], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    Ok((Some(id), Some(name)))
+}
+
+/// Nombre sin espacios de más; rechaza vacíos y nombres ya usados por otra
+/// categoría (sin distinguir mayúsculas).
+fn validate_category(conn: &Connection, category: &Category) -> Result<String, String> {
+    let name = category.name.trim();
+    if name.is_empty() {
+        return Err("La categoría necesita un nombre".to_string());
+    }
+    let taken: Option<String> = conn
+        .query_row(
+            "SELECT name FROM categories WHERE name = ?1 AND id IS NOT ?2",
+            rusqlite::params![name, category.id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    if let Some(existing) = taken {
+        return Err(format!("Ya existe la categoría {}", existing));
+    }
+    Ok(name.to_string())
+}
+
+/// Categorías por `sort_order` y nombre, con la cantidad de productos del
+/// catálogo de cada una.
+#[tauri::command]
+fn get_categories(state: State<AppState>) -> Result<Vec<Category>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.id, c.name, c.description, c.sort_order,
+                    (SELECT COUNT(*) FROM products p WHERE p.category_id = c.id AND p.deleted_at IS NULL)
+             FROM categories c
+             ORDER BY c.sort_order, c.name, c.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let categories = stmt
+        .query_map([], |row| {
+            Ok(Category {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                sort_order: row.get(3)?,
+                product_count: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(categories)
+}
+
+#[tauri::command]
+fn add_category<R: Runtime>(window: Window<R>, state: State<AppState>, category: Category) -> Result<i64, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = validate_category(&conn, &category)?;
+    conn.execute(
+        "INSERT INTO categories (name, description, sort_order) VALUES (?1, ?2, ?3)",
+        rusqlite::params![name, category.description, category.sort_order],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "categories", Some(id));
+    Ok(id)
+}
+
+/// Cambiar el nombre actualiza también el texto `category` de sus productos y
+/// promociones, y el ajuste `sku_prefix.<categoría>`.
+#[tauri::command]
+fn update_category<R: Runtime>(window: Window<R>, state: State<AppState>, category: Category) -> Result<(), String> {
+    let id = category.id.ok_or("Falta el id de la categoría")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = validate_category(&conn, &category)?;
+    let old_name: String = conn
+        .query_row("SELECT name FROM categories WHERE id = ?1", [id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Categoría no encontrada".to_string(),
+            e => e.to_string(),
+        })?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        conn.execute(
+            "UPDATE categories SET name = ?2, description = ?3, sort_order = ?4 WHERE id = ?1",
+            rusqlite::params![id, name, category.description, category.sort_order],
+        )
+        .map_err(|e| e.to_string())?;
+        if name != old_name {
+            conn.execute(
+                "UPDATE products SET category = ?2, updated_at = CURRENT_TIMESTAMP WHERE category_id = ?1",
+                rusqlite::params![id, name],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE promotions SET category = ?2 WHERE category = ?1 COLLATE NOCASE",
+                rusqlite::params![old_name, name],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE OR REPLACE settings SET key = ?2 WHERE key = ?1",
+                rusqlite::params![format!("sku_prefix.{}", old_name), format!("sku_prefix.{}", name)],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "categories", Some(id));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Borra una categoría. Si algún producto (incluso eliminado) la usa, hay que
+/// indicar `reassign_to`: sus productos y promociones pasan a esa categoría.
+/// Sirve también para unir dos categorías que solo difieren en un acento.
+#[tauri::command]
+fn delete_category<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    id: i64,
+    reassign_to: Option<i64>,
+) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (name, products): (String, i64) = conn
+        .query_row(
+            "SELECT name, (SELECT COUNT(*) FROM products WHERE category_id = ?1) FROM categories WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Categoría no encontrada".to_string(),
+            e => e.to_string(),
+        })?;
+    let target: Option<String> = match reassign_to {
+        Some(target) if target == id => {
+            return Err("Elige una categoría distinta de la que se elimina".to_string());
+        }
+        Some(target) => Some(
+            conn.query_row("SELECT name FROM categories WHERE id = ?1", [target], |row| row.get(0))
+                .map_err(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => "La categoría de destino no existe".to_string(),
+                    e => e.to_string(),
+                })?,
+        ),
+        None if products > 0 => {
+            return Err(format!(
+                "La categoría {} tiene {} {}; elige otra categoría para reasignarlos",
+                name,
+                products,
+                if products == 1 { "producto" } else { "productos" }
+            ));
+        }
+        None => None,
+    };
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        if let Some(target_name) = &target {
+            conn.execute(
+                "UPDATE products SET category_id = ?2, category = ?3, updated_at = CURRENT_TIMESTAMP WHERE category_id = ?1",
+                rusqlite::params![id, reassign_to, target_name],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE promotions SET category = ?2 WHERE category = ?1 COLLATE NOCASE",
+                rusqlite::params![name, target_name],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        conn.execute("DELETE FROM categories WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "categories", Some(id));
+            if target.is_some() && products > 0 {
+                emit_data_changed(&window, "products", None);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// MARCAS
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Brand {
+    id: Option<i64>,
+    name: String,
+    /// Productos del catálogo de la marca (solo lectura)
+    #[serde(default)]
+    product_count: i64,
+}
+
+/// Nombre de marca sin espacios al principio, al final ni repetidos:
+/// "Optimum  Nutrition " queda "Optimum Nutrition".
+fn normalize_brand_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Pasa las marcas de texto libre a `brands`. Los nombres que solo difieren
+/// en mayúsculas o espacios quedan en una marca (gana la forma más usada) y
+/// cada producto queda con su `brand_id` y el nombre normalizado. Solo toca
+/// los productos sin `brand_id`, así que en la práctica corre una vez.
+fn migrate_product_brands(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT brand, COUNT(*) FROM products
+         WHERE brand_id IS NULL AND TRIM(COALESCE(brand, '')) <> ''
+         GROUP BY brand ORDER BY COUNT(*) DESC, MIN(id)",
+    )?;
+    let pending = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    for brand in pending {
+        let name = normalize_brand_name(&brand);
+        conn.execute("INSERT OR IGNORE INTO brands (name) VALUES (?1)", [&name])?;
+        conn.execute(
+            "UPDATE products
+             SET brand_id = (SELECT id FROM brands WHERE name = ?2),
+                 brand = (SELECT name FROM brands WHERE name = ?2)
+             WHERE brand_id IS NULL AND brand = ?1",
+            rusqlite::params![brand, name],
+        )?;
+    }
+    Ok(())
+}
+
+/// Marca de un producto, con el mismo criterio que `resolve_category`.
+fn resolve_brand(conn: &Connection, brand_id: Option<i32>, name: Option<&str>) -> Result<(Option<i32>, Option<String>), String> {
+    let name = name.map(normalize_brand_name).filter(|n| !n.is_empty());
+    let Some(name) = name else {
+        let Some(id) = brand_id else {
+            return Ok((None, None));
+        };
+        let name: String = conn
+            .query_row("SELECT name FROM brands WHERE id = ?1", [id], |row| row.get(0))
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => "Marca no encontrada".to_string(),
+                e => e.to_string(),
+            })?;
+        return Ok((Some(id), Some(name)));
+    };
+    conn.execute("INSERT OR IGNORE INTO brands (name) VALUES (?1)", [&name])
+        .map_err(|e| e.to_string())?;
+    let (id, name) = conn
+        .query_row("SELECT id, name FROM brands WHERE name = ?1", [&name], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    Ok((Some(id), Some(name)))
+}
+
+/// Nombre normalizado; rechaza vacíos y nombres ya usados por otra marca.
+fn validate_brand(conn: &Connection, brand: &Brand) -> Result<String, String> {
+    let name = normalize_brand_name(&brand.name);
+    if name.is_empty() {
+        return Err("La marca necesita un nombre".to_string());
+    }
+    let taken: Option<String> = conn
+        .query_row(
+            "SELECT name FROM brands WHERE name = ?1 AND id IS NOT ?2",
+            rusqlite::params![name, brand.id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    if let Some(existing) = taken {
+        return Err(format!("Ya existe la marca {}", existing));
+    }
+    Ok(name)
+}
+
+/// Marcas por nombre, con la cantidad de productos del catálogo de cada una.
+#[tauri::command]
+fn get_brands(state: State<AppState>) -> Result<Vec<Brand>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT b.id, b.name,
+                    (SELECT COUNT(*) FROM products p WHERE p.brand_id = b.id AND p.deleted_at IS NULL)
+             FROM brands b
+             ORDER BY b.name, b.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let brands = stmt
+        .query_map([], |row| {
+            Ok(Brand {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                product_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(brands)
+}
+
+#[tauri::command]
+fn add_brand<R: Runtime>(window: Window<R>, state: State<AppState>, brand: Brand) -> Result<i64, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = validate_brand(&conn, &brand)?;
+    conn.execute("INSERT INTO brands (name) VALUES (?1)", [&name])
+        .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "brands", Some(id));
+    Ok(id)
+}
+
+/// Cambiar el nombre actualiza también el texto `brand` de sus productos.
+#[tauri::command]
+fn update_brand<R: Runtime>(window: Window<R>, state: State<AppState>, brand: Brand) -> Result<(), String> {
+    let id = brand.id.ok_or("Falta el id de la marca")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = validate_brand(&conn, &brand)?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        let updated = conn
+            .execute("UPDATE brands SET name = ?2 WHERE id = ?1", rusqlite::params![id, name])
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err("Marca no encontrada".to_string());
+        }
+        conn.execute(
+            "UPDATE products SET brand = ?2, updated_at = CURRENT_TIMESTAMP WHERE brand_id = ?1 AND brand IS NOT ?2",
+            rusqlite::params![id, name],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "brands", Some(id));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Borra una marca. Si algún producto (incluso eliminado) la usa, hay que
+/// indicar `reassign_to` y sus productos pasan a esa marca en la misma
+/// transacción.
+#[tauri::command]
+fn delete_brand<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    id: i64,
+    reassign_to: Option<i64>,
+) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (name, products): (String, i64) = conn
+        .query_row(
+            "SELECT name, (SELECT COUNT(*) FROM products WHERE brand_id = ?1) FROM brands WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Marca no encontrada".to_string(),
+            e => e.to_string(),
+        })?;
+    let target: Option<String> = match reassign_to {
+        Some(target) if target == id => {
+            return Err("Elige una marca distinta de la que se elimina".to_string());
+        }
+        Some(target) => Some(
+            conn.query_row("SELECT name FROM brands WHERE id = ?1", [target], |row| row.get(0))
+                .map_err(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => "La marca de destino no existe".to_string(),
+                    e => e.to_string(),
+                })?,
+        ),
+        None if products > 0 => {
+            return Err(format!(
+                "La marca {} tiene {} {}; elige otra marca para reasignarlos",
+                name,
+                products,
+                if products == 1 { "producto" } else { "productos" }
+            ));
+        }
+        None => None,
+    };
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        if let Some(target_name) = &target {
+            conn.execute(
+                "UPDATE products SET brand_id = ?2, brand = ?3, updated_at = CURRENT_TIMESTAMP WHERE brand_id = ?1",
+                rusqlite::params![id, reassign_to, target_name],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        conn.execute("DELETE FROM brands WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "brands", Some(id));
+            if target.is_some() && products > 0 {
+                emit_data_changed(&window, "products", None);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// LISTAS DE PRECIOS
+// ============================================
+
+/// Lista que siguen los precios de venta de la ficha; no se puede borrar ni
+/// editar por producto.
+const RETAIL_PRICE_LIST: &str = "Minorista";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceList {
+    id: Option<i64>,
+    name: String,
+    /// Productos del catálogo con precio en la lista (solo lectura)
+    #[serde(default)]
+    product_count: i64,
+}
+
+/// Precio de un producto en una lista; `price` es `None` si no tiene.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductListPrice {
+    price_list_id: i64,
+    name: String,
+    price: Option<f64>,
+}
+
+/// Crea la lista minorista con los precios de venta actuales. Solo copia
+/// precios la primera vez, cuando la lista aún no existía.
+fn migrate_retail_price_list(conn: &Connection) -> Result<()> {
+    let created = conn.execute("INSERT OR IGNORE INTO price_lists (name) VALUES (?1)", [RETAIL_PRICE_LIST])?;
+    if created > 0 {
+        conn.execute(
+            "INSERT INTO product_prices (product_id, price_list_id, price)
+             SELECT id, ?1, sale_price FROM products WHERE sale_price IS NOT NULL",
+            [conn.last_insert_rowid()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Copia el precio de venta de la ficha a la lista minorista. Lo llaman
+/// todos los que cambian `products.sale_price`.
+fn sync_retail_price(conn: &Connection, product_id: i64, sale_price: Option<f64>) -> Result<(), String> {
+    match sale_price {
+        Some(price) => conn.execute(
+            "INSERT INTO product_prices (product_id, price_list_id, price)
+             SELECT ?1, id, ?2 FROM price_lists WHERE name = ?3
+             ON CONFLICT(product_id, price_list_id) DO UPDATE SET price = excluded.price",
+            rusqlite::params![product_id, price, RETAIL_PRICE_LIST],
+        ),
+        None => conn.execute(
+            "DELETE FROM product_prices
+             WHERE product_id = ?1 AND price_list_id = (SELECT id FROM price_lists WHERE name = ?2)",
+            rusqlite::params![product_id, RETAIL_PRICE_LIST],
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn price_list_name(conn: &Connection, price_list_id: i64) -> Result<String, String> {
+    conn.query_row("SELECT name FROM price_lists WHERE id = ?1", [price_list_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Lista de precios no encontrada".to_string(),
+            e => e.to_string(),
+        })
+}
+
+/// Precio unitario de un producto en una lista. Un producto sin precio en la
+/// lista es un error: venderlo al precio minorista por descuido es justo lo
+/// que la lista evita.
+fn product_list_price(conn: &Connection, product_id: i32, price_list_id: i64) -> Result<f64, String> {
+    let list = price_list_name(conn, price_list_id)?;
+    let price: Option<f64> = conn
+        .query_row(
+            "SELECT price FROM product_prices WHERE product_id = ?1 AND price_list_id = ?2",
+            rusqlite::params![product_id, price_list_id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    price.ok_or_else(|| format!("El producto no tiene precio en la lista {}", list))
+}
+
+/// Nombre normalizado; rechaza vacíos y nombres ya usados por otra lista.
+fn validate_price_list(conn: &Connection, list: &PriceList) -> Result<String, String> {
+    let name = list.name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err("La lista de precios necesita un nombre".to_string());
+    }
+    let taken: Option<String> = conn
+        .query_row(
+            "SELECT name FROM price_lists WHERE name = ?1 AND id IS NOT ?2",
+            rusqlite::params![name, list.id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    if let Some(existing) = taken {
+        return Err(format!("Ya existe la lista de precios {}", existing));
+    }
+    Ok(name)
+}
+
+/// Listas por nombre, con la cantidad de productos que tienen precio en cada una.
+#[tauri::command]
+fn get_price_lists(state: State<AppState>) -> Result<Vec<PriceList>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT l.id, l.name,
+                    (SELECT COUNT(*) FROM product_prices pp JOIN products p ON p.id = pp.product_id
+                     WHERE pp.price_list_id = l.id AND p.deleted_at IS NULL)
+             FROM price_lists l
+             ORDER BY l.name, l.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let lists = stmt
+        .query_map([], |row| {
+            Ok(PriceList {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                product_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(lists)
+}
+
+#[tauri::command]
+fn add_price_list<R: Runtime>(window: Window<R>, state: State<AppState>, price_list: PriceList) -> Result<i64, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = validate_price_list(&conn, &price_list)?;
+    conn.execute("INSERT INTO price_lists (name) VALUES (?1)", [&name])
+        .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "price_lists", Some(id));
+    Ok(id)
+}
+
+#[tauri::command]
+fn update_price_list<R: Runtime>(window: Window<R>, state: State<AppState>, price_list: PriceList) -> Result<(), String> {
+    let id = price_list.id.ok_or("Falta el id de la lista de precios")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if price_list_name(&conn, id)? == RETAIL_PRICE_LIST {
+        return Err(format!("La lista {} no se puede renombrar", RETAIL_PRICE_LIST));
+    }
+    let name = validate_price_list(&conn, &price_list)?;
+    conn.execute("UPDATE price_lists SET name = ?2 WHERE id = ?1", rusqlite::params![id, name])
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "price_lists", Some(id));
+    Ok(())
+}
+
+/// Borra una lista y sus precios. La minorista y las que ya se usaron en
+/// ventas se conservan, para que cada venta siga diciendo con qué lista se
+/// cobró.
+#[tauri::command]
+fn delete_price_list<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = price_list_name(&conn, id)?;
+    if name == RETAIL_PRICE_LIST {
+        return Err(format!("La lista {} no se puede eliminar", RETAIL_PRICE_LIST));
+    }
+    let sales: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE price_list_id = ?1", source_table("sales", true)),
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if sales > 0 {
+        return Err(format!(
+            "La lista {} se usó en {} {}; no se puede eliminar",
+            name,
+            sales,
+            if sales == 1 { "venta" } else { "ventas" }
+        ));
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        conn.execute("DELETE FROM product_prices WHERE price_list_id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM price_lists WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "price_lists", Some(id));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Precios de un producto en todas las listas.
+#[tauri::command]
+fn get_product_prices(state: State<AppState>, product_id: i32) -> Result<Vec<ProductListPrice>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT l.id, l.name, pp.price
+             FROM price_lists l
+             LEFT JOIN product_prices pp ON pp.price_list_id = l.id AND pp.product_id = ?1
+             ORDER BY l.name, l.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let prices = stmt
+        .query_map([product_id], |row| {
+            Ok(ProductListPrice {
+                price_list_id: row.get(0)?,
+                name: row.get(1)?,
+                price: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(prices)
+}
+
+#[tauri::command]
+fn get_product_price(state: State<AppState>, product_id: i32, price_list_id: i64) -> Result<f64, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    product_list_price(&conn, product_id, price_list_id)
+}
+
+/// Fija (o con `price` vacío quita) el precio de un producto en una lista.
+/// Los de la lista minorista se cambian con el precio de venta de la ficha.
+#[tauri::command]
+fn set_product_price<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    product_id: i32,
+    price_list_id: i64,
+    price: Option<f64>,
+) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let name = price_list_name(&conn, price_list_id)?;
+    if name == RETAIL_PRICE_LIST {
+        return Err(format!(
+            "Los precios de la lista {} se cambian con el precio de venta del producto",
+            RETAIL_PRICE_LIST
+        ));
+    }
+    let exists: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM products WHERE id = ?1 AND deleted_at IS NULL)", [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("Producto no encontrado".to_string());
+    }
+    match price {
+        Some(price) if !price.is_finite() || price < 0.0 => {
+            return Err(format!("El precio no puede ser negativo ({})", price));
+        }
+        Some(price) => conn.execute(
+            "INSERT INTO product_prices (product_id, price_list_id, price) VALUES (?1, ?2, ?3)
+             ON CONFLICT(product_id, price_list_id) DO UPDATE SET price = excluded.price",
+            rusqlite::params![product_id, price_list_id, round_money(price)],
+        ),
+        None => conn.execute(
+            "DELETE FROM product_prices WHERE product_id = ?1 AND price_list_id = ?2",
+            rusqlite::params![product_id, price_list_id],
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "products", Some(product_id as i64));
+    Ok(())
+}
+
+// ============================================
+// TIPOS DE CAMBIO
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExchangeRate {
+    currency: String,
+    /// Desde cuándo rige (AAAA-MM-DD); vale hasta el siguiente de la misma moneda
+    rate_date: String,
+    /// Moneda local por cada unidad de `currency`
+    rate: f64,
+}
+
+fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn local_currency(conn: &Connection) -> String {
+    get_setting(conn, "currency").unwrap_or_else(|| DEFAULT_CURRENCY.to_string())
+}
+
+/// Moneda del costo tal como se guarda: en mayúsculas, y `None` si es la
+/// moneda local.
+fn product_currency(conn: &Connection, product: &Product) -> Option<String> {
+    product
+        .currency
+        .as_deref()
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| !c.is_empty() && *c != local_currency(conn))
+}
+
+/// `cost_price` de `product` (alias de `products`) en moneda local, con el
+/// tipo de cambio vigente en `date` (expresión SQL). Es NULL si no hay costo
+/// o si falta el tipo de cambio: nunca se convierte 1 a 1.
+fn local_cost_sql(product: &str, date: &str) -> String {
+    format!(
+        "CASE WHEN {p}.currency IS NULL THEN {p}.cost_price
+              ELSE {p}.cost_price * (SELECT r.rate FROM exchange_rates r
+                                     WHERE r.currency = {p}.currency AND r.rate_date <= {d}
+                                     ORDER BY r.rate_date DESC LIMIT 1) END",
+        p = product,
+        d = date
+    )
+}
+
+/// Registra el tipo de cambio de `currency` desde `date` (hoy si no viene);
+/// si ya había uno ese día, lo reemplaza.
+#[tauri::command]
+fn set_exchange_rate<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    currency: String,
+    rate: f64,
+    date: Option<String>,
+) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let currency = currency.trim().to_uppercase();
+    if !is_currency_code(&currency) {
+        return Err(format!("La moneda '{}' no es un código de 3 letras como USD", currency));
+    }
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err("El tipo de cambio debe ser mayor a 0".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if currency == local_currency(&conn) {
+        return Err(format!("{} es la moneda local; no necesita tipo de cambio", currency));
+    }
+    let date = match date.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()) {
+        Some(date) if is_valid_date(&date) => date,
+        Some(_) => return Err("Fecha inválida. Usa el formato AAAA-MM-DD.".to_string()),
+        None => conn
+            .query_row("SELECT date('now','localtime')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    conn.execute(
+        "INSERT INTO exchange_rates (currency, rate_date, rate) VALUES (?1, ?2, ?3)
+         ON CONFLICT(currency, rate_date) DO UPDATE SET rate = excluded.rate, created_at = CURRENT_TIMESTAMP",
+        rusqlite::params![currency, date, rate],
+    )
+    .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "exchange_rates", None);
+    Ok(())
+}
+
+/// Tipos de cambio registrados, el más reciente primero; con `currency`, solo
+/// los de esa moneda.
+#[tauri::command]
+fn get_exchange_rates(state: State<AppState>, currency: Option<String>) -> Result<Vec<ExchangeRate>, String> {
+    let currency = currency.map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT currency, rate_date, rate FROM exchange_rates
+             WHERE ?1 IS NULL OR currency = ?1
+             ORDER BY rate_date DESC, currency",
+        )
+        .map_err(|e| e.to_string())?;
+    let rates = stmt
+        .query_map([currency], |row| {
+            Ok(ExchangeRate {
+                currency: row.get(0)?,
+                rate_date: row.get(1)?,
+                rate: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rates)
+}
+
+// ============================================
+// IMPORTACIÓN DE VENTAS
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportRowIssue {
+    line: usize,
+    sku: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SalesImportReport {
+    dry_run: bool,
+    valid_rows: usize,
+    imported: usize,
+    /// Filas rechazadas por restricción de canal
+    blocked: Vec<ImportRowIssue>,
+    errors: Vec<ImportRowIssue>,
+    /// Trabajo en `import_jobs` de una importación real
+    job_id: Option<i64>,
+}
+
+impl SalesImportReport {
+    fn new(dry_run: bool) -> Self {
+        SalesImportReport {
+            dry_run,
+            valid_rows: 0,
+            imported: 0,
+            blocked: Vec::new(),
+            errors: Vec::new(),
+            job_id: None,
+        }
+    }
+}
+
+/// Columnas de un CSV de ventas.
+struct SalesImportColumns {
+    sku: usize,
+    quantity: usize,
+    sale_price: usize,
+    discount: Option<usize>,
+    channel: Option<usize>,
+    sale_date: Option<usize>,
+    customer: Option<usize>,
+}
+
+fn sales_import_columns(table: &CsvTable, mapping: Option<&HashMap<String, String>>) -> Result<SalesImportColumns, String> {
+    let columns = map_csv_columns(table, SALES_IMPORT_FIELDS, mapping)?;
+    Ok(SalesImportColumns {
+        sku: required_column(&columns, "sku")?,
+        quantity: required_column(&columns, "quantity")?,
+        sale_price: required_column(&columns, "sale_price")?,
+        discount: columns.get("discount").copied(),
+        channel: columns.get("channel").copied(),
+        sale_date: columns.get("sale_date").copied(),
+        customer: columns.get("customer").copied(),
+    })
+}
+
+/// Quién importa y con qué fecha se registran las filas sin fecha.
+struct SalesImportContext<'a> {
+    columns: &'a SalesImportColumns,
+    today: &'a str,
+    created_by: Option<i32>,
+    session: Option<&'a Session>,
+}
+
+/// Valida e inserta una fila del CSV de ventas. Los problemas de la fila
+/// quedan en `report` y la fila se omite; solo devuelve error si falla la
+/// base de datos. Debe ejecutarse dentro de una transacción.
+fn import_sale_row(
+    conn: &Connection,
+    ctx: &SalesImportContext,
+    line: usize,
+    values: &[String],
+    report: &mut SalesImportReport,
+) -> Result<(), String> {
+    let columns = ctx.columns;
+    let get = |col: Option<usize>| {
+        col.and_then(|c| values.get(c))
+            .map(|v| v.to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let sku = get(Some(columns.sku)).unwrap_or_default();
+    let issue = |message: String| ImportRowIssue {
+        line,
+        sku: sku.clone(),
+        message,
+    };
+
+    let product_id: i32 = match conn.query_row(
+        "SELECT id FROM products WHERE UPPER(TRIM(sku)) = UPPER(?1)",
+        rusqlite::params![sku],
+        |row| row.get(0),
+    ) {
+        Ok(id) => id,
+        Err(_) => {
+            report.errors.push(issue(format!("SKU no encontrado: '{}'", sku)));
+            return Ok(());
+        }
+    };
+    let quantity = match get(Some(columns.quantity)).as_deref().and_then(parse_amount) {
+        Some(q) => q,
+        None => {
+            report.errors.push(issue("Cantidad inválida".to_string()));
+            return Ok(());
+        }
+    };
+    if let Err(e) = validate_quantity(conn, product_id, quantity) {
+        report.errors.push(issue(e));
+        return Ok(());
+    }
+    let sale_price = match get(Some(columns.sale_price)).as_deref().and_then(parse_amount) {
+        Some(p) if p >= 0.0 => p,
+        _ => {
+            report.errors.push(issue("Precio inválido".to_string()));
+            return Ok(());
+        }
+    };
+    let channel = get(columns.channel).unwrap_or_else(|| "Online".to_string());
+    if let Err(e) = check_sale_channel(conn, product_id, Some(&channel)) {
+        report.blocked.push(issue(e));
+        return Ok(());
+    }
+    let sale_date = match normalize_sale_date(conn, &get(columns.sale_date).unwrap_or_else(|| ctx.today.to_string())) {
+        Ok(date) => date,
+        Err(e) => {
+            report.errors.push(issue(e));
+            return Ok(());
+        }
+    };
+
+    let sale = Sale {
+        id: None,
+        product_id,
+        quantity,
+        sale_price: Some(round_money(sale_price)),
+        discount: get(columns.discount).as_deref().and_then(parse_amount),
+        channel: Some(channel),
+        sale_date,
+        created_by: ctx.created_by,
+        store_credit_code: None,
+        deposit_amount: None,
+        customer: get(columns.customer),
+        customer_id: None,
+        original_price: None,
+        promotion_id: None,
+        installments: None,
+        price_list_id: None,
+        allow_inactive: false,
+        price_override: false,
+        receipt_number: None,
+        payment_method: None,
+        is_credit: false,
+        amount_paid: None,
+        note: None,
+    };
+    let closed_month = match check_period_open(conn, ctx.session, &sale.sale_date) {
+        Ok(month) => month,
+        Err(e) => {
+            report.blocked.push(issue(e));
+            return Ok(());
+        }
+    };
+    // Cada fila en su propio savepoint para que una falla no deshaga las demás
+    conn.execute("SAVEPOINT import_row", []).map_err(|e| e.to_string())?;
+    let inserted = insert_sale(conn, &sale, ctx.created_by, ctx.session).and_then(|inserted| match closed_month {
+        Some(ref month) => audit_post_close(conn, ctx.session, "import_sales_csv", "sales", inserted.id, month),
+        None => Ok(()),
+    });
+    match inserted {
+        Ok(()) => {
+            report.valid_rows += 1;
+            conn.execute("RELEASE import_row", []).map_err(|e| e.to_string())?;
+        }
+        Err(e) => {
+            report.errors.push(issue(e));
+            conn.execute("ROLLBACK TO import_row", []).map_err(|e| e.to_string())?;
+            conn.execute("RELEASE import_row", []).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Importa ventas desde un CSV de pedidos (columnas sku, quantity,
+/// sale_price, y opcionalmente discount, channel, sale_date, customer).
+/// `sale_price` es el total de la línea, como en `add_sale`; el canal por
+/// defecto es "Online". `mapping` es el mapeo confirmado en `inspect_csv`;
+/// sin él se reconocen los encabezados habituales. Las filas con problemas
+/// se informan y se omiten. Con `dry_run` (por defecto) se validan todas las
+/// filas sin guardar nada; si no, se importa como un trabajo de
+/// `start_import` procesado de una vez, tramo por tramo.
+#[tauri::command]
+fn import_sales_csv<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    path: String,
+    dry_run: Option<bool>,
+    mapping: Option<HashMap<String, String>>,
+) -> Result<SalesImportReport, String> {
+    let dry = dry_run.unwrap_or(true);
+    let (table, file_hash) = read_csv_table_with_hash(Path::new(&path))?;
+    let columns = sales_import_columns(&table, mapping.as_ref())?;
+
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let today = today_key(&conn)?;
+    let ctx = SalesImportContext { columns: &columns, today: &today, created_by, session: session.as_ref() };
+
+    if !dry {
+        let job = create_import_job(&conn, "sales", &path, &file_hash, mapping.as_ref(), table.rows.len(), created_by)?;
+        let mut report = SalesImportReport::new(false);
+        report.job_id = Some(job.id);
+        let mut job = job;
+        while job.status == IMPORT_RUNNING {
+            let (next, chunk) = run_import_chunk(&conn, &job, &table, &ctx)?;
+            emit_import_progress(&window, &next);
+            report.valid_rows += chunk.valid_rows;
+            report.blocked.extend(chunk.blocked);
+            report.errors.extend(chunk.errors);
+            job = next;
+        }
+        report.imported = report.valid_rows;
+        if report.imported > 0 {
+            emit_data_changed(&window, "sales", None);
+        }
+        return Ok(report);
+    }
+
+    let mut report = SalesImportReport::new(true);
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        for (line, values) in &table.rows {
+            import_sale_row(&conn, &ctx, *line, values, &mut report)?;
+        }
+        Ok(())
+    })();
+    let _ = conn.execute("ROLLBACK", []);
+    result.map(|_| report)
+}
+
+// ============================================
+// IMPORTACIÓN DE PRODUCTOS
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductImportReport {
+    dry_run: bool,
+    valid_rows: usize,
+    imported: usize,
+    /// `line` es el número de fila en Excel; una fila puede tener varios problemas
+    errors: Vec<ImportRowIssue>,
+}
+
+/// Producto de una fila de la hoja, o los problemas de la fila.
+fn product_from_import_row(columns: &HashMap<&'static str, usize>, values: &[String]) -> Result<Product, Vec<String>> {
+    let get = |field: &str| {
+        columns
+            .get(field)
+            .and_then(|c| values.get(*c))
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let mut errors = Vec::new();
+    let mut amount = |field: &str| match get(field) {
+        Some(raw) => parse_amount(&raw).or_else(|| {
+            errors.push(format!("{}: '{}' no es un número", field, raw));
+            None
+        }),
+        None => None,
+    };
+    let sale_price = amount("sale_price");
+    let cost_price = amount("cost_price");
+    let min_stock = amount("min_stock");
+    let max_stock = amount("max_stock");
+    let tax_rate = amount("tax_rate");
+    let mut whole = |field: &str, value: Option<f64>| match value {
+        Some(v) if v.fract() != 0.0 => {
+            errors.push(format!("{}: debe ser un número entero ({})", field, v));
+            None
+        }
+        other => other.map(|v| v as i32),
+    };
+    let min_stock = whole("min_stock", min_stock);
+    let max_stock = whole("max_stock", max_stock);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Product {
+        id: None,
+        sku: get("sku"),
+        name: get("name").unwrap_or_default(),
+        sale_price,
+        cost_price,
+        brand: get("brand"),
+        category: get("category"),
+        presentation: get("presentation"),
+        flavor: get("flavor"),
+        weight: get("weight"),
+        image_path: None,
+        thumbnail_path: None,
+        expiry_date: get("expiry_date"),
+        lot_number: get("lot_number"),
+        min_stock,
+        max_stock,
+        location: get("location"),
+        status: get("status"),
+        deposit_amount: None,
+        unit: get("unit"),
+        purchase_unit: None,
+        conversion_factor: None,
+        allowed_channels: None,
+        deleted_at: None,
+        parent_id: None,
+        category_id: None,
+        brand_id: None,
+        barcode: get("barcode"),
+        description: get("description"),
+        internal_notes: None,
+        default_supplier: get("default_supplier"),
+        tax_rate,
+        currency: get("currency"),
+        initial_stock: None,
+    })
+}
+
+/// Importa productos desde la primera hoja de un libro de Excel. Las columnas
+/// se reconocen por el encabezado (name/nombre es la única obligatoria) y
+/// cada fila se valida como en `add_product`. Un SKU escrito como texto
+/// conserva sus ceros a la izquierda. Las filas con problemas se informan con
+/// su número de fila en Excel y se omiten; las demás se guardan en una sola
+/// transacción, sin ingreso de stock. Con `dry_run` (por defecto) solo valida.
+#[tauri::command]
+fn import_products_xlsx<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<ProductImportReport, String> {
+    let dry = dry_run.unwrap_or(true);
+    let table = read_xlsx_table(Path::new(&path))?;
+    let columns = map_csv_columns(&table, PRODUCT_IMPORT_FIELDS, None)?;
+    required_column(&columns, "name")?;
+
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut report = ProductImportReport { dry_run: dry, valid_rows: 0, imported: 0, errors: Vec::new() };
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        for (line, values) in &table.rows {
+            let sku = columns.get("sku").and_then(|c| values.get(*c)).cloned().unwrap_or_default();
+            let mut issues = |messages: Vec<String>| {
+                for message in messages {
+                    report.errors.push(ImportRowIssue { line: *line, sku: sku.clone(), message });
+                }
+            };
+            let product = match product_from_import_row(&columns, values) {
+                Ok(product) => product,
+                Err(errors) => {
+                    issues(errors);
+                    continue;
+                }
+            };
+            // Cada fila en su propio savepoint para que una falla no deshaga las demás
+            conn.execute("SAVEPOINT import_row", []).map_err(|e| e.to_string())?;
+            match insert_product(&conn, &product, None, created_by, false) {
+                Ok(_) => {
+                    report.valid_rows += 1;
+                    conn.execute("RELEASE import_row", []).map_err(|e| e.to_string())?;
+                }
+                Err(e) => {
+                    issues(e.lines().map(str::to_string).collect());
+                    conn.execute("ROLLBACK TO import_row", []).map_err(|e| e.to_string())?;
+                    conn.execute("RELEASE import_row", []).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        Ok(())
+    })();
+    if dry || result.is_err() {
+        let _ = conn.execute("ROLLBACK", []);
+        return result.map(|_| report);
+    }
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+    report.imported = report.valid_rows;
+    if report.imported > 0 {
+        emit_data_changed(&window, "products", None);
+    }
+    Ok(report)
+}
+
+// ============================================
+// IMPORTACIONES POR TRAMOS
+// ============================================
+
+/// Estados de un trabajo de `import_jobs`.
+const IMPORT_RUNNING: &str = "en_curso";
+const IMPORT_CANCELLED: &str = "cancelada";
+const IMPORT_COMPLETED: &str = "completada";
+
+/// Filas por tramo si no hay `import_chunk_rows` en la configuración.
+const DEFAULT_IMPORT_CHUNK_ROWS: f64 = 500.0;
+
+/// Una importación de CSV que avanza por tramos. `processed_rows` es la
+/// cantidad de filas de datos ya confirmadas: al reanudar se sigue desde ahí.
+#[derive(Debug, Serialize)]
+struct ImportJob {
+    id: i64,
+    kind: String,
+    path: String,
+    file_hash: String,
+    total_rows: i64,
+    processed_rows: i64,
+    imported: i64,
+    error_count: i64,
+    blocked_count: i64,
+    status: String,
+    created_by: Option<i32>,
+    created_at: String,
+    updated_at: Option<String>,
+    finished_at: Option<String>,
+}
+
+/// Resultado de un tramo ya confirmado, con las líneas del CSV que abarcó.
+#[derive(Debug, Serialize)]
+struct ImportChunk {
+    first_line: i64,
+    last_line: i64,
+    imported: i64,
+    errors: Vec<ImportRowIssue>,
+    blocked: Vec<ImportRowIssue>,
+    created_at: String,
+}
+
+/// Un trabajo con el detalle de sus tramos.
+#[derive(Debug, Serialize)]
+struct ImportJobDetail {
+    job: ImportJob,
+    chunks: Vec<ImportChunk>,
+}
+
+const IMPORT_JOB_COLUMNS: &str = "id, kind, path, file_hash, total_rows, processed_rows, imported, error_count, blocked_count, status, created_by, created_at, updated_at, finished_at";
+
+fn import_job_from_row(row: &rusqlite::Row) -> rusqlite::Result<ImportJob> {
+    Ok(ImportJob {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        path: row.get(2)?,
+        file_hash: row.get(3)?,
+        total_rows: row.get(4)?,
+        processed_rows: row.get(5)?,
+        imported: row.get(6)?,
+        error_count: row.get(7)?,
+        blocked_count: row.get(8)?,
+        status: row.get(9)?,
+        created_by: row.get(10)?,
+        created_at: row.get(11)?,
+        updated_at: row.get(12)?,
+        finished_at: row.get(13)?,
+    })
+}
+
+fn import_job(conn: &Connection, job_id: i64) -> Result<ImportJob, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM import_jobs WHERE id = ?1", IMPORT_JOB_COLUMNS),
+        [job_id],
+        import_job_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("Importación #{} no encontrada", job_id),
+        e => e.to_string(),
+    })
+}
+
+/// Registra un trabajo nuevo. Un archivo (por su hash) se importa una sola
+/// vez: si ya tiene un trabajo sin terminar hay que reanudarlo.
+fn create_import_job(
+    conn: &Connection,
+    kind: &str,
+    path: &str,
+    file_hash: &str,
+    mapping: Option<&HashMap<String, String>>,
+    total_rows: usize,
+    created_by: Option<i32>,
+) -> Result<ImportJob, String> {
+    let previous: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, status FROM import_jobs WHERE kind = ?1 AND file_hash = ?2 AND status <> ?3
+             ORDER BY id DESC LIMIT 1",
+            rusqlite::params![kind, file_hash, IMPORT_CANCELLED],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+    match previous {
+        Some((id, status)) if status == IMPORT_COMPLETED => {
+            return Err(format!("Este archivo ya se importó (importación #{})", id));
+        }
+        Some((id, _)) => {
+            return Err(format!(
+                "Este archivo tiene una importación sin terminar (#{}); reanúdala en vez de empezar otra",
+                id
+            ));
+        }
+        None => {}
+    }
+    let mapping = match mapping {
+        Some(mapping) => Some(serde_json::to_string(mapping).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let status = if total_rows == 0 { IMPORT_COMPLETED } else { IMPORT_RUNNING };
+    conn.execute(
+        "INSERT INTO import_jobs (kind, path, file_hash, mapping, total_rows, status, created_by, finished_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CASE WHEN ?6 = ?8 THEN CURRENT_TIMESTAMP END)",
+        rusqlite::params![kind, path, file_hash, mapping, total_rows as i64, status, created_by, IMPORT_COMPLETED],
+    )
+    .map_err(|e| e.to_string())?;
+    import_job(conn, conn.last_insert_rowid())
+}
+
+/// Procesa el tramo siguiente de `job` y lo confirma en una transacción junto
+/// con el avance del trabajo: si la aplicación se cierra a mitad de un tramo,
+/// ese tramo no queda ni en las ventas ni en el avance.
+fn run_import_chunk(
+    conn: &Connection,
+    job: &ImportJob,
+    table: &CsvTable,
+    ctx: &SalesImportContext,
+) -> Result<(ImportJob, SalesImportReport), String> {
+    let chunk_rows = setting_f64(conn, "import_chunk_rows", DEFAULT_IMPORT_CHUNK_ROWS).max(1.0) as usize;
+    let start = job.processed_rows as usize;
+    let rows = table.rows.get(start..).unwrap_or_default();
+    let rows = &rows[..rows.len().min(chunk_rows)];
+    let mut report = SalesImportReport::new(false);
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        for (line, values) in rows {
+            import_sale_row(conn, ctx, *line, values, &mut report)?;
+        }
+        let errors = serde_json::to_string(&report.errors).map_err(|e| e.to_string())?;
+        let blocked = serde_json::to_string(&report.blocked).map_err(|e| e.to_string())?;
+        let first_line = rows.first().map(|(line, _)| *line).unwrap_or(0);
+        let last_line = rows.last().map(|(line, _)| *line).unwrap_or(0);
+        conn.execute(
+            "INSERT INTO import_job_chunks (job_id, first_line, last_line, imported, errors, blocked)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![job.id, first_line as i64, last_line as i64, report.valid_rows as i64, errors, blocked],
+        )
+        .map_err(|e| e.to_string())?;
+        let processed = (start + rows.len()) as i64;
+        let done = processed >= job.total_rows;
+        conn.execute(
+            "UPDATE import_jobs
+             SET processed_rows = ?2, imported = imported + ?3, error_count = error_count + ?4,
+                 blocked_count = blocked_count + ?5, status = CASE WHEN ?6 THEN ?7 ELSE status END,
+                 finished_at = CASE WHEN ?6 THEN CURRENT_TIMESTAMP END, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?1",
+            rusqlite::params![
+                job.id,
+                processed,
+                report.valid_rows as i64,
+                report.errors.len() as i64,
+                report.blocked.len() as i64,
+                done,
+                IMPORT_COMPLETED,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            report.imported = report.valid_rows;
+            Ok((import_job(conn, job.id)?, report))
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Avisa a todas las ventanas del avance de una importación.
+fn emit_import_progress<R: Runtime>(window: &Window<R>, job: &ImportJob) {
+    let _ = window.emit_all("import-progress", job);
+}
+
+/// Empieza una importación por tramos (por ahora solo `kind = "sales"`, con
+/// las mismas columnas y `mapping` que `import_sales_csv`). Valida el archivo
+/// y sus columnas, registra el trabajo y no importa nada todavía: la interfaz
+/// llama a `import_next_chunk` hasta que el trabajo termine, mostrando el
+/// avance y pudiendo cancelar entre tramos.
+#[tauri::command]
+fn start_import<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    kind: String,
+    path: String,
+    mapping: Option<HashMap<String, String>>,
+) -> Result<ImportJob, String> {
+    if kind != "sales" {
+        return Err(format!("Tipo de importación no admitido: '{}'. Usa sales", kind));
+    }
+    let (table, file_hash) = read_csv_table_with_hash(Path::new(&path))?;
+    sales_import_columns(&table, mapping.as_ref())?;
+    let created_by = acting_user(&state, &window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let job = create_import_job(&conn, &kind, &path, &file_hash, mapping.as_ref(), table.rows.len(), created_by)?;
+    emit_import_progress(&window, &job);
+    Ok(job)
+}
+
+/// Importa y confirma el tramo siguiente (`import_chunk_rows` filas, 500 por
+/// defecto) de un trabajo en curso y devuelve el trabajo actualizado. Relee
+/// el archivo y exige que no haya cambiado desde que empezó la importación.
+#[tauri::command]
+fn import_next_chunk<R: Runtime>(window: Window<R>, state: State<AppState>, job_id: i64) -> Result<ImportJob, String> {
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    if job.status == IMPORT_COMPLETED {
+        return Ok(job);
+    }
+    if job.status == IMPORT_CANCELLED {
+        return Err(format!("La importación #{} está cancelada; reanúdala para continuar", job_id));
+    }
+    let (table, file_hash) = read_csv_table_with_hash(Path::new(&job.path))?;
+    if file_hash != job.file_hash {
+        return Err(format!(
+            "El archivo {} cambió desde que empezó la importación #{}; no se puede continuar",
+            job.path, job_id
+        ));
+    }
+    let mapping: Option<String> = conn
+        .query_row("SELECT mapping FROM import_jobs WHERE id = ?1", [job_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let mapping: Option<HashMap<String, String>> = match mapping {
+        Some(json) => Some(serde_json::from_str(&json).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let columns = sales_import_columns(&table, mapping.as_ref())?;
+    let today = today_key(&conn)?;
+    let ctx = SalesImportContext { columns: &columns, today: &today, created_by, session: session.as_ref() };
+    let (job, chunk) = run_import_chunk(&conn, &job, &table, &ctx)?;
+    emit_import_progress(&window, &job);
+    if chunk.imported > 0 {
+        emit_data_changed(&window, "sales", None);
+    }
+    Ok(job)
+}
+
+/// Cancela una importación en curso. Lo ya confirmado se conserva; el tramo
+/// que se esté procesando termina antes de que la cancelación tome efecto.
+#[tauri::command]
+fn cancel_import<R: Runtime>(window: Window<R>, state: State<AppState>, job_id: i64) -> Result<ImportJob, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    if job.status == IMPORT_COMPLETED {
+        return Err(format!("La importación #{} ya terminó", job_id));
+    }
+    conn.execute(
+        "UPDATE import_jobs SET status = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        rusqlite::params![job_id, IMPORT_CANCELLED],
+    )
+    .map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    emit_import_progress(&window, &job);
+    Ok(job)
+}
+
+/// Reanuda una importación cancelada o interrumpida (la aplicación se cerró
+/// a mitad): sigue después del último tramo confirmado, sin repetir filas.
+/// El archivo debe ser el mismo que al empezar.
+#[tauri::command]
+fn resume_import<R: Runtime>(window: Window<R>, state: State<AppState>, job_id: i64) -> Result<ImportJob, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    if job.status == IMPORT_COMPLETED {
+        return Err(format!("La importación #{} ya terminó", job_id));
+    }
+    let (_, file_hash) = read_csv_table_with_hash(Path::new(&job.path))?;
+    if file_hash != job.file_hash {
+        return Err(format!(
+            "El archivo {} cambió desde que empezó la importación #{}; no se puede continuar",
+            job.path, job_id
+        ));
+    }
+    conn.execute(
+        "UPDATE import_jobs SET status = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        rusqlite::params![job_id, IMPORT_RUNNING],
+    )
+    .map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    emit_import_progress(&window, &job);
+    Ok(job)
+}
+
+/// Historial de importaciones, de la más reciente a la más antigua.
+#[tauri::command]
+fn get_import_jobs(state: State<AppState>, limit: Option<i64>) -> Result<Vec<ImportJob>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM import_jobs ORDER BY id DESC LIMIT ?1",
+            IMPORT_JOB_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let jobs = stmt
+        .query_map([limit.unwrap_or(50).max(1)], import_job_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(jobs)
+}
+
+/// Un trabajo con el resultado de cada tramo (filas importadas, errores y
+/// bloqueadas con su línea del CSV).
+#[tauri::command]
+fn get_import_job(state: State<AppState>, job_id: i64) -> Result<ImportJobDetail, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let job = import_job(&conn, job_id)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT first_line, last_line, imported, errors, blocked, created_at
+             FROM import_job_chunks WHERE job_id = ?1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([job_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut chunks = Vec::new();
+    for (first_line, last_line, imported, errors, blocked, created_at) in rows {
+        chunks.push(ImportChunk {
+            first_line,
+            last_line,
+            imported,
+            errors: serde_json::from_str(&errors).map_err(|e| e.to_string())?,
+            blocked: serde_json::from_str(&blocked).map_err(|e| e.to_string())?,
+            created_at,
+        });
+    }
+    Ok(ImportJobDetail { job, chunks })
+}
+
+// ============================================
+// IMPORTACIÓN DE USUARIOS
+// ============================================
+
+/// Roles que la aplicación trae de fábrica.
+const BUILT_IN_ROLES: &[&str] = &["Administrador", "Vendedor", "Almacenero", ROLE_AUDITOR];
+
+/// Roles válidos: los de fábrica más los que tienen permisos en
+/// `role_permissions`.
+fn known_roles(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut roles: Vec<String> = BUILT_IN_ROLES.iter().map(|r| r.to_string()).collect();
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT role FROM role_permissions ORDER BY role")
+        .map_err(|e| e.to_string())?;
+    let extra = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    for role in extra {
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    Ok(roles)
+}
+
+/// Largo de las contraseñas temporales que genera `import_users_csv`.
+const TEMP_PASSWORD_LEN: usize = 10;
+/// Sin 0/O ni 1/l/I para que se puedan dictar sin confusiones.
+const TEMP_PASSWORD_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+fn temp_password() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..TEMP_PASSWORD_LEN)
+        .map(|_| char::from(TEMP_PASSWORD_ALPHABET[rng.gen_range(0..TEMP_PASSWORD_ALPHABET.len())]))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportedUser {
+    line: usize,
+    username: String,
+    role: String,
+    /// Contraseña generada (solo si el archivo no traía una y no es simulación);
+    /// no se guarda en ningún lado, hay que entregarla ahora
+    generated_password: Option<String>,
+}
+
+/// Fila válida del CSV, pendiente de crear.
+struct PendingUser {
+    line: usize,
+    username: String,
+    fullname: Option<String>,
+    role: String,
+    password: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserImportIssue {
+    line: usize,
+    username: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserImportReport {
+    dry_run: bool,
+    valid_rows: usize,
+    imported: usize,
+    users: Vec<ImportedUser>,
+    errors: Vec<UserImportIssue>,
+}
+
+/// Alta masiva de usuarios desde un CSV (columnas username, role y
+/// opcionalmente fullname y password). Sin contraseña se genera una temporal
+/// que solo aparece en el resultado. Todos quedan con `must_change_password`.
+/// Es todo o nada: si alguna fila tiene errores (rol desconocido, usuario
+/// repetido en el archivo o ya existente) no se crea ninguno. Solo el
+/// Administrador; con `dry_run` (por defecto) solo valida.
+#[tauri::command]
+fn import_users_csv<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    path: String,
+    dry_run: Option<bool>,
+    mapping: Option<HashMap<String, String>>,
+) -> Result<UserImportReport, String> {
+    let session = require_admin(&state, &window)?;
+    let dry = dry_run.unwrap_or(true);
+    let table = read_csv_table(Path::new(&path))?;
+    let columns = map_csv_columns(&table, USER_IMPORT_FIELDS, mapping.as_ref())?;
+    let username_col = required_column(&columns, "username")?;
+    let role_col = required_column(&columns, "role")?;
+    let fullname_col = columns.get("fullname").copied();
+    let password_col = columns.get("password").copied();
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let roles = known_roles(&conn)?;
+    let mut report = UserImportReport {
+        dry_run: dry,
+        valid_rows: 0,
+        imported: 0,
+        users: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut seen: Vec<String> = Vec::new();
+    let mut pending: Vec<PendingUser> = Vec::new();
+    for (line, values) in &table.rows {
+        let get = |col: Option<usize>| {
+            col.and_then(|c| values.get(c))
+                .map(|v| v.to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let username = get(Some(username_col)).unwrap_or_default();
+        let issue = |message: String| UserImportIssue {
+            line: *line,
+            username: username.clone(),
+            message,
+        };
+        if username.is_empty() {
+            report.errors.push(issue("Falta el usuario".to_string()));
+            continue;
+        }
+        let role = get(Some(role_col)).unwrap_or_default();
+        let Some(role) = roles.iter().find(|r| r.eq_ignore_ascii_case(&role)).cloned() else {
+            report.errors.push(issue(format!("Rol desconocido: '{}'. Roles válidos: {}", role, roles.join(", "))));
+            continue;
+        };
+        let key = username.to_lowercase();
+        if seen.contains(&key) {
+            report.errors.push(issue("Usuario repetido en el archivo".to_string()));
+            continue;
+        }
+        seen.push(key);
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM users WHERE username = ?1 COLLATE NOCASE",
+                [&username],
+                |_| Ok(()),
+            )
+            .is_ok();
+        if exists {
+            report.errors.push(issue(format!("El usuario '{}' ya existe", username)));
+            continue;
+        }
+        pending.push(PendingUser {
+            line: *line,
+            username: username.clone(),
+            fullname: get(fullname_col),
+            role,
+            password: get(password_col),
+        });
+    }
+    report.valid_rows = pending.len();
+    if dry || !report.errors.is_empty() {
+        report.users = pending
+            .into_iter()
+            .map(|u| ImportedUser { line: u.line, username: u.username, role: u.role, generated_password: None })
+            .collect();
+        return Ok(report);
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<ImportedUser>, String> = (|| {
+        let mut created = Vec::new();
+        for user in pending {
+            let generated = user.password.is_none().then(temp_password);
+            let password = user.password.as_deref().or(generated.as_deref()).unwrap_or_default();
+            let password_hash = hash(password, DEFAULT_COST).map_err(|e| e.to_string())?;
+            conn.execute(
+                "INSERT INTO users (username, fullname, password_hash, role, must_change_password)
+                 VALUES (?1, ?2, ?3, ?4, 1)",
+                rusqlite::params![user.username, user.fullname, password_hash, user.role],
+            )
+            .map_err(|e| format!("Línea {}: {}", user.line, e))?;
+            created.push(ImportedUser {
+                line: user.line,
+                username: user.username,
+                role: user.role,
+                generated_password: generated,
+            });
+        }
+        let names: Vec<&str> = created.iter().map(|u| u.username.as_str()).collect();
+        audit(
+            &conn,
+            Some(session.user_id),
+            "import_users",
+            "users",
+            None,
+            &format!("{} usuarios importados: {}", created.len(), names.join(", ")),
+            None,
+        )?;
+        Ok(created)
+    })();
+
+    match result {
+        Ok(created) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            report.imported = created.len();
+            report.users = created;
+            emit_data_changed(&window, "users", None);
+            Ok(report)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// PROMOCIONES
+// ============================================
+
+/// Promoción por producto o por categoría: precio fijo (`promo_price`) o
+/// porcentaje de descuento (`percent_off`). La vigencia se compara con la
+/// hora local del equipo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Promotion {
+    id: Option<i64>,
+    name: String,
+    product_id: Option<i32>,
+    category: Option<String>,
+    promo_price: Option<f64>,
+    percent_off: Option<f64>,
+    starts_at: String,
+    ends_at: String,
+}
+
+impl Promotion {
+    /// Precio por unidad con la promoción aplicada.
+    fn unit_price(&self, catalog_price: f64) -> f64 {
+        match (self.promo_price, self.percent_off) {
+            (Some(price), _) => price,
+            (None, Some(percent)) => catalog_price * (1.0 - percent / 100.0),
+            (None, None) => catalog_price,
+        }
+    }
+}
+
+const PROMOTION_COLUMNS: &str =
+    "id, name, product_id, category, promo_price, percent_off, starts_at, ends_at";
+
+fn promotion_from_row(row: &rusqlite::Row) -> rusqlite::Result<Promotion> {
+    Ok(Promotion {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        product_id: row.get(2)?,
+        category: row.get(3)?,
+        promo_price: row.get(4)?,
+        percent_off: row.get(5)?,
+        starts_at: row.get(6)?,
+        ends_at: row.get(7)?,
+    })
+}
+
+/// Normaliza una fecha u hora a `AAAA-MM-DD HH:MM:SS`. Una fecha sin hora
+/// de fin cubre el día completo.
+fn normalize_datetime(conn: &Connection, value: &str, end_of_day: bool) -> Result<String, String> {
+    let value = value.trim();
+    let value = if end_of_day && is_valid_date(value) {
+        format!("{} 23:59:59", value)
+    } else {
+        value.to_string()
+    };
+    conn.query_row("SELECT datetime(?1)", rusqlite::params![value], |row| {
+        row.get::<_, Option<String>>(0)
+    })
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Fecha inválida: '{}'", value))
+}
+
+fn validate_promotion(conn: &Connection, promotion: &mut Promotion) -> Result<(), String> {
+    if promotion.name.trim().is_empty() {
+        return Err("La promoción necesita un nombre".to_string());
+    }
+    if promotion.product_id.is_some() == promotion.category.is_some() {
+        return Err("Indica un producto o una categoría (solo uno)".to_string());
+    }
+    match (promotion.promo_price, promotion.percent_off) {
+        (Some(price), None) if price >= 0.0 => {}
+        (None, Some(percent)) if percent > 0.0 && percent <= 100.0 => {}
+        _ => {
+            return Err(
+                "Indica un precio promocional o un porcentaje de descuento entre 0 y 100 (solo uno)"
+                    .to_string(),
+            )
+        }
+    }
+    promotion.starts_at = normalize_datetime(conn, &promotion.starts_at, false)?;
+    promotion.ends_at = normalize_datetime(conn, &promotion.ends_at, true)?;
+    if promotion.starts_at >= promotion.ends_at {
+        return Err("La fecha de fin debe ser posterior a la de inicio".to_string());
+    }
+    Ok(())
+}
+
+/// Promociones vigentes para un producto, cada una con el precio de catálogo
+/// y el precio promocional por unidad.
+fn active_promotions_for(conn: &Connection, product_id: i32) -> Result<Vec<(Promotion, f64, f64)>, String> {
+    let catalog_price: Option<f64> = conn
+        .query_row(
+            "SELECT sale_price FROM products WHERE id = ?1",
+            rusqlite::params![product_id],
+            |row| row.get(0),
+        )
+        .map_err(|_| "Producto no encontrado".to_string())?;
+    let catalog_price = match catalog_price {
+        Some(price) => price,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM promotions
+             WHERE starts_at <= datetime('now', 'localtime') AND ends_at >= datetime('now', 'localtime')
+               AND (product_id = ?1
+                    OR category = (SELECT category FROM products WHERE id = ?1))",
+            PROMOTION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let promotions = stmt
+        .query_map(rusqlite::params![product_id], promotion_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|promo| {
+            let price = promo.unit_price(catalog_price).max(0.0);
+            (promo, catalog_price, price)
+        })
+        .collect();
+    Ok(promotions)
+}
+
+/// La promoción vigente más conveniente para el cliente.
+fn active_promotion_for(conn: &Connection, product_id: i32) -> Result<Option<(Promotion, f64, f64)>, String> {
+    Ok(active_promotions_for(conn, product_id)?
+        .into_iter()
+        .min_by(|a, b| a.2.total_cmp(&b.2)))
+}
+
+#[tauri::command]
+fn get_promotions(state: State<AppState>) -> Result<Vec<Promotion>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM promotions ORDER BY starts_at DESC",
+            PROMOTION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let promotions = stmt
+        .query_map([], promotion_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(promotions)
+}
+
+/// Promociones vigentes ahora, para el aviso del punto de venta.
+#[tauri::command]
+fn get_active_promotions(state: State<AppState>) -> Result<Vec<Promotion>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM promotions
+             WHERE starts_at <= datetime('now', 'localtime') AND ends_at >= datetime('now', 'localtime')
+             ORDER BY ends_at ASC",
+            PROMOTION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let promotions = stmt
+        .query_map([], promotion_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(promotions)
+}
+
+#[tauri::command]
+fn add_promotion<R: Runtime>(window: Window<R>, state: State<AppState>, promotion: Promotion) -> Result<i64, String> {
+    let mut promotion = promotion;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    validate_promotion(&conn, &mut promotion)?;
+    conn.execute(
+        "INSERT INTO promotions (name, product_id, category, promo_price, percent_off, starts_at, ends_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            promotion.name,
+            promotion.product_id,
+            promotion.category,
+            promotion.promo_price,
+            promotion.percent_off,
+            promotion.starts_at,
+            promotion.ends_at,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "promotions", Some(id));
+    Ok(id)
+}
+
+#[tauri::command]
+fn update_promotion<R: Runtime>(window: Window<R>, state: State<AppState>, promotion: Promotion) -> Result<(), String> {
+    let mut promotion = promotion;
+    let id = promotion.id.ok_or("Falta el id de la promoción")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    validate_promotion(&conn, &mut promotion)?;
+    let updated = conn
+        .execute(
+            "UPDATE promotions SET name=?1, product_id=?2, category=?3, promo_price=?4, percent_off=?5, starts_at=?6, ends_at=?7
+             WHERE id=?8",
+            rusqlite::params![
+                promotion.name,
+                promotion.product_id,
+                promotion.category,
+                promotion.promo_price,
+                promotion.percent_off,
+                promotion.starts_at,
+                promotion.ends_at,
+                id,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Promoción no encontrada".to_string());
+    }
+    emit_data_changed(&window, "promotions", Some(id));
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_promotion<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM promotions WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "promotions", Some(id));
+    Ok(())
+}
+
+// ============================================
+// CALIDAD DE DATOS
+// ============================================
+
+/// Una verificación del reporte de calidad: `ids` devuelve los ids afectados
+/// y `fix`, si existe, es una corrección que no pierde información.
+struct DataQualityCheck {
+    key: &'static str,
+    table: &'static str,
+    description: &'static str,
+    ids: &'static str,
+    fix: Option<&'static str>,
+}
+
+/// Fecha de venta o de caja que no es AAAA-MM-DD válida.
+const INVALID_DATE: &str = "(date(substr({col},1,10)) IS NULL OR date(substr({col},1,10)) <> substr({col},1,10))";
+
+/// Verificaciones sobre las tablas vivas (el archivo no se revisa ni se corrige).
+const DATA_QUALITY_CHECKS: &[DataQualityCheck] = &[
+    DataQualityCheck {
+        key: "sales_zero_quantity",
+        table: "sales",
+        description: "Ventas con cantidad cero o negativa",
+        ids: "SELECT id FROM sales WHERE quantity <= 0",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "sales_invalid_date",
+        table: "sales",
+        description: "Ventas con fecha vacía o en formato distinto de AAAA-MM-DD",
+        ids: "SELECT id FROM sales WHERE {invalid_date:sale_date}",
+        // DD/MM/AAAA es el único formato ambiguo que se puede convertir sin adivinar
+        fix: Some(
+            "UPDATE sales SET sale_date = substr(sale_date,7,4) || '-' || substr(sale_date,4,2) || '-' || substr(sale_date,1,2) || substr(sale_date,11)
+             WHERE sale_date GLOB '[0-3][0-9]/[01][0-9]/[12][0-9][0-9][0-9]*'",
+        ),
+    },
+    DataQualityCheck {
+        key: "sales_noncanonical_date",
+        table: "sales",
+        description: "Ventas con hora en un formato distinto de AAAA-MM-DDTHH:MM:SS.sssZ",
+        ids: "SELECT id FROM sales
+              WHERE length(sale_date) > 10 AND NOT {invalid_date:sale_date}
+                AND strftime('%Y-%m-%dT%H:%M:%fZ', sale_date) IS NOT sale_date",
+        // Mismo formato que guarda `normalize_sale_date`; la hora que SQLite no entiende se deja
+        fix: Some(
+            "UPDATE sales SET sale_date = strftime('%Y-%m-%dT%H:%M:%fZ', sale_date)
+             WHERE length(sale_date) > 10 AND strftime('%Y-%m-%dT%H:%M:%fZ', sale_date) IS NOT NULL
+               AND strftime('%Y-%m-%dT%H:%M:%fZ', sale_date) <> sale_date
+               AND date(substr(sale_date,1,10)) = substr(sale_date,1,10)",
+        ),
+    },
+    DataQualityCheck {
+        key: "sales_future_date",
+        table: "sales",
+        description: "Ventas con fecha futura",
+        ids: "SELECT id FROM sales WHERE sale_date >= date('now','localtime','+2 day')",
+        // No se sabe qué día fue la venta: se corrige a mano
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "sales_discount_out_of_range",
+        table: "sales",
+        description: "Ventas con descuento fuera de 0-100%",
+        ids: "SELECT id FROM sales WHERE discount < 0 OR discount > 100",
+        fix: Some("UPDATE sales SET discount = MIN(MAX(discount, 0), 100) WHERE discount < 0 OR discount > 100"),
+    },
+    DataQualityCheck {
+        key: "sales_negative_price",
+        table: "sales",
+        description: "Ventas con importe negativo",
+        ids: "SELECT id FROM sales WHERE sale_price < 0",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "sales_orphan_product",
+        table: "sales",
+        description: "Ventas de productos que ya no existen",
+        ids: "SELECT id FROM sales WHERE product_id NOT IN (SELECT id FROM products)",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "stock_zero_quantity",
+        table: "stock_movements",
+        description: "Movimientos de stock con cantidad cero o negativa",
+        ids: "SELECT id FROM stock_movements WHERE quantity <= 0",
+        // Un movimiento de cantidad 0 no altera el saldo y puede borrarse
+        fix: Some("DELETE FROM stock_movements WHERE quantity = 0"),
+    },
+    DataQualityCheck {
+        key: "stock_invalid_type",
+        table: "stock_movements",
+        description: "Movimientos de stock que no son 'ingreso' ni 'egreso'",
+        ids: "SELECT id FROM stock_movements WHERE type NOT IN ('ingreso', 'egreso')",
+        fix: Some(
+            "UPDATE stock_movements SET type = lower(trim(type))
+             WHERE type NOT IN ('ingreso', 'egreso') AND lower(trim(type)) IN ('ingreso', 'egreso')",
+        ),
+    },
+    DataQualityCheck {
+        key: "stock_negative_balance",
+        table: "products",
+        description: "Productos con más egresos que ingresos (saldo negativo)",
+        ids: "SELECT product_id FROM stock_movements GROUP BY product_id
+              HAVING SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END) <= -0.0005",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "stock_future_created_at",
+        table: "stock_movements",
+        description: "Movimientos de stock con fecha de registro en el futuro",
+        ids: "SELECT id FROM stock_movements WHERE created_at > datetime('now','+1 hour')",
+        // Se vuelven a sellar con la hora actual, la más cercana al registro real
+        fix: Some(
+            "UPDATE stock_movements SET created_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+             WHERE created_at > datetime('now','+1 hour')",
+        ),
+    },
+    DataQualityCheck {
+        key: "stock_orphan_product",
+        table: "stock_movements",
+        description: "Movimientos de stock de productos que ya no existen",
+        ids: "SELECT id FROM stock_movements WHERE product_id NOT IN (SELECT id FROM products)",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "cash_nonpositive_amount",
+        table: "cash_movements",
+        description: "Movimientos de caja con monto cero o negativo",
+        ids: "SELECT id FROM cash_movements WHERE amount <= 0",
+        fix: Some("DELETE FROM cash_movements WHERE amount = 0"),
+    },
+    DataQualityCheck {
+        key: "cash_invalid_type",
+        table: "cash_movements",
+        description: "Movimientos de caja que no son 'ingreso', 'egreso' ni 'saldo_inicial'",
+        ids: "SELECT id FROM cash_movements WHERE movement_type NOT IN ('ingreso', 'egreso', 'saldo_inicial')",
+        fix: Some(
+            "UPDATE cash_movements SET movement_type = lower(trim(movement_type))
+             WHERE movement_type NOT IN ('ingreso', 'egreso', 'saldo_inicial')
+               AND lower(trim(movement_type)) IN ('ingreso', 'egreso', 'saldo_inicial')",
+        ),
+    },
+    DataQualityCheck {
+        key: "cash_invalid_date",
+        table: "cash_movements",
+        description: "Movimientos de caja con fecha inválida o futura",
+        ids: "SELECT id FROM cash_movements
+              WHERE {invalid_date:movement_date} OR substr(movement_date,1,10) > date('now','localtime','+1 day')",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "products_negative_price",
+        table: "products",
+        description: "Productos con precio de venta o costo negativo",
+        ids: "SELECT id FROM products WHERE sale_price < 0 OR cost_price < 0",
+        fix: None,
+    },
+    DataQualityCheck {
+        key: "products_empty_sku",
+        table: "products",
+        description: "Productos con SKU vacío (choca con el índice único)",
+        ids: "SELECT id FROM products WHERE sku IS NOT NULL AND trim(sku) = ''",
+        fix: Some("UPDATE products SET sku = NULL WHERE sku IS NOT NULL AND trim(sku) = ''"),
+    },
+    DataQualityCheck {
+        key: "products_future_timestamps",
+        table: "products",
+        description: "Productos con fecha de alta o de modificación en el futuro",
+        ids: "SELECT id FROM products WHERE created_at > datetime('now','+1 hour') OR updated_at > datetime('now','+1 hour')",
+        fix: Some(
+            "UPDATE products SET created_at = MIN(created_at, CURRENT_TIMESTAMP), updated_at = CURRENT_TIMESTAMP
+             WHERE created_at > datetime('now','+1 hour') OR updated_at > datetime('now','+1 hour')",
+        ),
+    },
+    DataQualityCheck {
+        key: "products_min_above_max",
+        table: "products",
+        description: "Productos con stock mínimo mayor al máximo",
+        ids: "SELECT id FROM products WHERE max_stock > 0 AND min_stock > max_stock",
+        fix: None,
+    },
+];
+
+/// Ids que se devuelven por verificación; `count` siempre es el total.
+const DATA_QUALITY_MAX_IDS: usize = 500;
+
+impl DataQualityCheck {
+    fn ids_sql(&self) -> String {
+        let mut sql = self.ids.to_string();
+        for col in ["sale_date", "movement_date"] {
+            sql = sql.replace(
+                &format!("{{invalid_date:{}}}", col),
+                &INVALID_DATE.replace("{col}", col),
+            );
+        }
+        sql
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DataQualityIssue {
+    check: String,
+    table: String,
+    description: String,
+    count: usize,
+    row_ids: Vec<i64>,
+    fixable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DataQualityReport {
+    total_issues: usize,
+    issues: Vec<DataQualityIssue>,
+}
+
+fn compute_data_quality(conn: &Connection) -> Result<DataQualityReport, String> {
+    let mut issues = Vec::new();
+    for check in DATA_QUALITY_CHECKS {
+        let mut stmt = conn.prepare(&check.ids_sql()).map_err(|e| e.to_string())?;
+        let mut ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if ids.is_empty() {
+            continue;
+        }
+        let count = ids.len();
+        ids.truncate(DATA_QUALITY_MAX_IDS);
+        issues.push(DataQualityIssue {
+            check: check.key.to_string(),
+            table: check.table.to_string(),
+            description: check.description.to_string(),
+            count,
+            row_ids: ids,
+            fixable: check.fix.is_some(),
+        });
+    }
+    Ok(DataQualityReport {
+        total_issues: issues.iter().map(|i| i.count).sum(),
+        issues,
+    })
+}
+
+/// Revisa ventas, movimientos de stock, caja y productos en busca de datos
+/// inconsistentes. Solo incluye las verificaciones con problemas.
+#[tauri::command]
+fn get_data_quality_report(state: State<AppState>) -> Result<DataQualityReport, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    compute_data_quality(&conn)
+}
+
+#[tauri::command]
+fn export_data_quality_report(state: State<AppState>) -> Result<String, String> {
+    let _job = lock_export(&state, "data_quality_report")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let report = compute_data_quality(&conn)?;
+    let mut csv = String::from("check,table,description,count,fixable,row_ids\n");
+    for issue in &report.issues {
+        let ids: Vec<String> = issue.row_ids.iter().map(|id| id.to_string()).collect();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            issue.check,
+            issue.table,
+            csv_field(&issue.description),
+            issue.count,
+            issue.fixable,
+            csv_field(&ids.join(" "))
+        ));
+    }
+    write_export("data_quality_report", "csv", csv.as_bytes())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DataQualityFix {
+    check: String,
+    rows_changed: usize,
+}
+
+/// Aplica las correcciones seguras de las verificaciones indicadas (o de todas
+/// las que tienen corrección). Los problemas sin corrección automática quedan
+/// para revisión manual. Solo para administradores.
+#[tauri::command]
+fn repair_data_quality<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    checks: Option<Vec<String>>,
+) -> Result<Vec<DataQualityFix>, String> {
+    require_admin(&state, &window)?;
+    if let Some(ref keys) = checks {
+        for key in keys {
+            match DATA_QUALITY_CHECKS.iter().find(|c| c.key == key) {
+                Some(check) if check.fix.is_none() => {
+                    return Err(format!("La verificación '{}' no tiene corrección automática", key))
+                }
+                Some(_) => {}
+                None => return Err(format!("Verificación desconocida: '{}'", key)),
+            }
+        }
+    }
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<Vec<DataQualityFix>, String> = (|| {
+        let mut fixes = Vec::new();
+        for check in DATA_QUALITY_CHECKS {
+            let fix = match check.fix {
+                Some(fix) => fix,
+                None => continue,
+            };
+            if let Some(ref keys) = checks {
+                if !keys.iter().any(|k| k == check.key) {
+                    continue;
+                }
+            }
+            let rows_changed = conn.execute(fix, []).map_err(|e| e.to_string())?;
+            fixes.push(DataQualityFix {
+                check: check.key.to_string(),
+                rows_changed,
+            });
+        }
+        // Las correcciones de fecha mueven ventas de día
+        refresh_daily_summaries(&conn, None).map_err(|e| e.to_string())?;
+        Ok(fixes)
+    })();
+
+    match result {
+        Ok(fixes) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "data_quality", None);
+            Ok(fixes)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HealthReport {
+    database_size_bytes: i64,
+    integrity_ok: bool,
+    products: i64,
+    sales: i64,
+    stock_movements: i64,
+    cash_movements: i64,
+    unread_notifications: i64,
+    /// Total de problemas del reporte de calidad de datos
+    data_quality_issues: usize,
+    /// Días cuyo resumen diario no coincide con las ventas; se corrigen con
+    /// `rebuild_daily_summaries`
+    daily_summary_mismatches: Vec<String>,
+    app: AppInfo,
+}
+
+/// Resumen del estado de la base: tamaño, integridad, volumen de datos,
+/// cantidad de problemas de calidad pendientes, consistencia de los
+/// resúmenes diarios de ventas y versión de la aplicación.
+#[tauri::command]
+fn get_health_report(state: State<AppState>) -> Result<HealthReport, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let count = |sql: &str| -> Result<i64, String> {
+        conn.query_row(sql, [], |row| row.get(0))
+            .map_err(|e| e.to_string())
+    };
+    let integrity: String = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    Ok(HealthReport {
+        database_size_bytes: count(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )?,
+        integrity_ok: integrity == "ok",
+        products: count("SELECT COUNT(*) FROM products")?,
+        sales: count("SELECT COUNT(*) FROM sales")?,
+        stock_movements: count("SELECT COUNT(*) FROM stock_movements")?,
+        cash_movements: count("SELECT COUNT(*) FROM cash_movements")?,
+        unread_notifications: count("SELECT COUNT(*) FROM notifications WHERE read = 0")?,
+        data_quality_issues: compute_data_quality(&conn)?.total_issues,
+        daily_summary_mismatches: daily_summary_mismatches(&conn).map_err(|e| e.to_string())?,
+        app: app_info(&conn)?,
+    })
+}
+
+// ============================================
+// WEBHOOKS
+// ============================================
+
+/// Eventos que se pueden enviar a un webhook.
+const WEBHOOK_EVENTS: [&str; 3] = ["sale.created", "product.out_of_stock", "daily.close"];
+
+/// Espera antes de cada reintento; agotados, la entrega queda como fallida.
+const WEBHOOK_RETRY_DELAYS_SECS: [i64; 4] = [60, 300, 1800, 7200];
+
+/// Cada cuánto revisa el hilo de entregas si hay envíos pendientes.
+const WEBHOOK_TICK: std::time::Duration = std::time::Duration::from_secs(10);
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Webhook {
+    id: Option<i64>,
+    url: String,
+    /// Clave para firmar el cuerpo (HMAC-SHA256); al editar, vacía conserva la actual
+    secret: Option<String>,
+    events: Vec<String>,
+    /// Importe mínimo de la venta o del cierre diario para disparar el evento
+    min_amount: Option<f64>,
+    active: bool,
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WebhookDelivery {
+    id: i64,
+    webhook_id: i64,
+    event: String,
+    /// "pendiente", "entregado" o "fallido"
+    status: String,
+    attempts: i64,
+    status_code: Option<i64>,
+    error: Option<String>,
+    created_at: Option<String>,
+    last_attempt_at: Option<String>,
+    /// Próximo reintento de una entrega pendiente
+    next_attempt_at: Option<String>,
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, secret, events, min_amount, active, created_at";
+
+fn webhook_from_row(row: &rusqlite::Row) -> rusqlite::Result<Webhook> {
+    let events: String = row.get(3)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        secret: row.get(2)?,
+        events: events.split(',').filter(|e| !e.is_empty()).map(String::from).collect(),
+        min_amount: row.get(4)?,
+        active: row.get::<_, i64>(5)? != 0,
+        created_at: row.get(6)?,
+    })
+}
+
+fn validate_webhook(webhook: &Webhook) -> Result<(), String> {
+    let url = webhook.url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("La URL del webhook debe empezar con http:// o https://".to_string());
+    }
+    if webhook.events.is_empty() {
+        return Err("Selecciona al menos un evento".to_string());
+    }
+    if let Some(event) = webhook.events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(format!(
+            "Evento desconocido: '{}'. Usa {}",
+            event,
+            WEBHOOK_EVENTS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Firma `body` con HMAC-SHA256 y la devuelve en hexadecimal.
+fn webhook_signature(secret: &str, body: &str) -> String {
+    hmac_sha256::HMAC::mac(body.as_bytes(), secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Encola `event` para cada webhook activo suscrito a él cuyo importe mínimo
+/// se cumpla. No hace llamadas de red: el hilo de entregas envía la cola, así
+/// que puede usarse dentro de la transacción que origina el evento.
+fn queue_webhook_event(
+    conn: &Connection,
+    event: &str,
+    amount: Option<f64>,
+    data: serde_json::Value,
+) -> Result<usize, String> {
+    let created_at: String = conn
+        .query_row("SELECT datetime('now')", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let payload = serde_json::json!({
+        "event": event,
+        "created_at": created_at,
+        "data": data,
+    })
+    .to_string();
+    conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, status, attempts, next_attempt_at)
+         SELECT id, ?1, ?2, 'pendiente', 0, datetime('now')
+         FROM webhooks
+         WHERE active = 1
+           AND (',' || events || ',') LIKE '%,' || ?1 || ',%'
+           AND (min_amount IS NULL OR ?3 IS NULL OR ?3 >= min_amount)",
+        rusqlite::params![event, payload, amount],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Encola `product.out_of_stock` si la salida de `removed` unidades dejó sin
+/// stock a un producto que tenía; uno que ya estaba en cero no se avisa de nuevo.
+fn queue_out_of_stock(conn: &Connection, product_id: i32, removed: f64) -> Result<(), String> {
+    if removed <= QUANTITY_EPSILON {
+        return Ok(());
+    }
+    let (name, sku, stock): (String, Option<String>, f64) = conn
+        .query_row(
+            "SELECT p.name, p.sku,
+                    COALESCE((SELECT SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END)
+                              FROM stock_movements WHERE product_id = p.id), 0)
+             FROM products p WHERE p.id = ?1",
+            rusqlite::params![product_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    if stock < QUANTITY_EPSILON && stock + removed > QUANTITY_EPSILON {
+        queue_webhook_event(
+            conn,
+            "product.out_of_stock",
+            None,
+            serde_json::json!({ "product_id": product_id, "name": name, "sku": sku, "stock": stock }),
+        )?;
+    }
+    Ok(())
+}
+
+/// Tarea diaria: envía el cierre del día anterior (ventas y caja).
+fn run_daily_close(conn: &Connection) -> Result<usize, String> {
+    let (day, today, sales_count, units, revenue): (String, String, i64, f64, f64) = conn
+        .query_row(
+            "SELECT date('now','-1 day'), date('now'), COUNT(*), COALESCE(SUM(quantity),0), COALESCE(SUM(sale_price),0)
+             FROM sales WHERE voided_at IS NULL AND substr(sale_date,1,10) = date('now','-1 day')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    // Saldo al cierre del día, incluido el saldo inicial de caja
+    let cash_balance = cash_balance(conn, false, Some(&today))?;
+    let (cash_in, cash_out): (f64, f64) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(CASE WHEN movement_type='ingreso' THEN amount END),0),
+                    COALESCE(SUM(CASE WHEN movement_type='egreso' THEN amount END),0)
+             FROM cash_movements WHERE substr(movement_date,1,10) = date('now','-1 day')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    queue_webhook_event(
+        conn,
+        "daily.close",
+        Some(revenue),
+        serde_json::json!({
+            "date": day,
+            "sales": sales_count,
+            "units": units,
+            "revenue": revenue,
+            "cash_in": cash_in,
+            "cash_out": cash_out,
+            "cash_balance": cash_balance,
+        }),
+    )?;
+    // No crea notificaciones
+    Ok(0)
+}
+
+/// Envía un cuerpo JSON firmado. Devuelve el código HTTP (si hubo respuesta)
+/// y el error, si lo hubo.
+fn send_webhook(url: &str, secret: Option<&str>, event: &str, body: &str) -> (Option<i64>, Option<String>) {
+    let mut request = ureq::post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .set("X-VitaSport-Event", event);
+    if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+        request = request.set(
+            "X-VitaSport-Signature",
+            &format!("sha256={}", webhook_signature(secret, body)),
+        );
+    }
+    match request.send_string(body) {
+        Ok(response) => (Some(response.status() as i64), None),
+        Err(ureq::Error::Status(code, _)) => (Some(code as i64), Some(format!("HTTP {}", code))),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+/// Registra el resultado de un intento y programa el reintento si corresponde.
+fn record_webhook_attempt(
+    conn: &Connection,
+    delivery_id: i64,
+    attempts: i64,
+    status_code: Option<i64>,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let (status, retry_secs) = if error.is_none() {
+        ("entregado", None)
+    } else {
+        match WEBHOOK_RETRY_DELAYS_SECS.get((attempts - 1) as usize) {
+            Some(secs) => ("pendiente", Some(*secs)),
+            None => ("fallido", None),
+        }
+    };
+    conn.execute(
+        "UPDATE webhook_deliveries
+         SET status = ?2, attempts = ?3, status_code = ?4, error = ?5,
+             last_attempt_at = datetime('now'),
+             next_attempt_at = CASE WHEN ?6 IS NULL THEN NULL ELSE datetime('now', '+' || ?6 || ' seconds') END
+         WHERE id = ?1",
+        rusqlite::params![delivery_id, status, attempts, status_code, error, retry_secs],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Envía las entregas pendientes cuya hora de reintento llegó. La conexión
+/// solo se bloquea para leer la cola y guardar cada resultado, nunca durante
+/// la llamada HTTP.
+fn process_webhook_deliveries(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let pending: Vec<(i64, String, Option<String>, String, String, i64)> = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT d.id, w.url, w.secret, d.event, d.payload, d.attempts
+                 FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+                 WHERE d.status = 'pendiente' AND w.active = 1 AND d.next_attempt_at <= datetime('now')
+                 ORDER BY d.id LIMIT 20",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    for (id, url, secret, event, payload, attempts) in pending {
+        let (status_code, error) = send_webhook(&url, secret.as_deref(), &event, &payload);
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        record_webhook_attempt(&conn, id, attempts + 1, status_code, error.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Lanza el hilo que entrega los webhooks en segundo plano.
+fn start_webhook_worker(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = process_webhook_deliveries(&app) {
+            eprintln!("❌ Error entregando webhooks: {}", e);
+        }
+        std::thread::sleep(WEBHOOK_TICK);
+    });
+}
+
+#[tauri::command]
+fn get_webhooks<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<Vec<Webhook>, String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM webhooks ORDER BY id", WEBHOOK_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let webhooks = stmt
+        .query_map([], webhook_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(webhooks)
+}
+
+#[tauri::command]
+fn add_webhook<R: Runtime>(window: Window<R>, state: State<AppState>, webhook: Webhook) -> Result<i64, String> {
+    require_admin(&state, &window)?;
+    validate_webhook(&webhook)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO webhooks (url, secret, events, min_amount, active) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            webhook.url.trim(),
+            webhook.secret.filter(|s| !s.is_empty()),
+            webhook.events.join(","),
+            webhook.min_amount,
+            webhook.active,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "webhooks", Some(id));
+    Ok(id)
+}
+
+#[tauri::command]
+fn update_webhook<R: Runtime>(window: Window<R>, state: State<AppState>, webhook: Webhook) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    validate_webhook(&webhook)?;
+    let id = webhook.id.ok_or("Falta el id del webhook")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE webhooks SET url = ?1, secret = COALESCE(?2, secret), events = ?3, min_amount = ?4, active = ?5
+             WHERE id = ?6",
+            rusqlite::params![
+                webhook.url.trim(),
+                webhook.secret.filter(|s| !s.is_empty()),
+                webhook.events.join(","),
+                webhook.min_amount,
+                webhook.active,
+                id,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Webhook no encontrado".to_string());
+    }
+    emit_data_changed(&window, "webhooks", Some(id));
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_webhook<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<(), String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM webhook_deliveries WHERE webhook_id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM webhooks WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    emit_data_changed(&window, "webhooks", Some(id));
+    Ok(())
+}
+
+/// Envía un evento `test` al webhook en el momento y devuelve el resultado,
+/// que también queda en el registro de entregas (sin reintentos).
+#[tauri::command]
+fn test_webhook<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<WebhookDelivery, String> {
+    require_admin(&state, &window)?;
+    let (webhook, body) = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let webhook = conn
+            .query_row(
+                &format!("SELECT {} FROM webhooks WHERE id = ?1", WEBHOOK_COLUMNS),
+                rusqlite::params![id],
+                webhook_from_row,
+            )
+            .map_err(|_| "Webhook no encontrado".to_string())?;
+        let created_at: String = conn
+            .query_row("SELECT datetime('now')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let body = serde_json::json!({
+            "event": "test",
+            "created_at": created_at,
+            "data": { "message": "Webhook de prueba de VitaSport" },
+        })
+        .to_string();
+        (webhook, body)
+    };
+
+    let (status_code, error) = send_webhook(&webhook.url, webhook.secret.as_deref(), "test", &body);
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, status, attempts, status_code, error, last_attempt_at)
+         VALUES (?1, 'test', ?2, ?3, 1, ?4, ?5, datetime('now'))",
+        rusqlite::params![
+            id,
+            body,
+            if error.is_none() { "entregado" } else { "fallido" },
+            status_code,
+            error,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let delivery_id = conn.last_insert_rowid();
+    conn.query_row(
+        &format!("SELECT {} FROM webhook_deliveries WHERE id = ?1", WEBHOOK_DELIVERY_COLUMNS),
+        rusqlite::params![delivery_id],
+        webhook_delivery_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+const WEBHOOK_DELIVERY_COLUMNS: &str =
+    "id, webhook_id, event, status, attempts, status_code, error, created_at, last_attempt_at, next_attempt_at";
+
+fn webhook_delivery_from_row(row: &rusqlite::Row) -> rusqlite::Result<WebhookDelivery> {
+    Ok(WebhookDelivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        event: row.get(2)?,
+        status: row.get(3)?,
+        attempts: row.get(4)?,
+        status_code: row.get(5)?,
+        error: row.get(6)?,
+        created_at: row.get(7)?,
+        last_attempt_at: row.get(8)?,
+        next_attempt_at: row.get(9)?,
+    })
+}
+
+/// Últimas entregas (de un webhook o de todos), con su estado, intentos y
+/// el código HTTP del último intento.
+#[tauri::command]
+fn get_webhook_deliveries<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    webhook_id: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<WebhookDelivery>, String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM webhook_deliveries
+             WHERE (?1 IS NULL OR webhook_id = ?1)
+             ORDER BY id DESC LIMIT ?2",
+            WEBHOOK_DELIVERY_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let deliveries = stmt
+        .query_map(
+            rusqlite::params![webhook_id, limit.unwrap_or(100).clamp(1, 1000)],
+            webhook_delivery_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(deliveries)
+}
+
+/// Reintenta una entrega en el momento, aunque su reintento no haya llegado
+/// o ya haya quedado como fallida, y devuelve cómo quedó.
+#[tauri::command]
+fn retry_webhook_delivery<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    id: i64,
+) -> Result<WebhookDelivery, String> {
+    require_admin(&state, &window)?;
+    let (url, secret, event, payload, attempts, status) = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT w.url, w.secret, d.event, d.payload, d.attempts, d.status
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.id = ?1",
+            rusqlite::params![id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            },
+        )
+        .map_err(|_| "Entrega no encontrada".to_string())?
+    };
+    if status == "entregado" {
+        return Err("La entrega ya se realizó".to_string());
+    }
+
+    let (status_code, error) = send_webhook(&url, secret.as_deref(), &event, &payload);
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    record_webhook_attempt(&conn, id, attempts + 1, status_code, error.as_deref())?;
+    conn.query_row(
+        &format!("SELECT {} FROM webhook_deliveries WHERE id = ?1", WEBHOOK_DELIVERY_COLUMNS),
+        rusqlite::params![id],
+        webhook_delivery_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+// ============================================
+// CIERRE DE PERIODOS Y AUDITORÍA
+// ============================================
+
+/// Permiso para registrar cambios en un mes cerrado. El Administrador lo
+/// tiene siempre; a otros roles se les otorga en `role_permissions`.
+const PERM_POST_CLOSE_ADJUSTMENT: &str = "ajuste_periodo_cerrado";
+
+/// Indica si el rol tiene el permiso.
+fn has_permission(conn: &Connection, role: &str, permission: &str) -> bool {
+    role == "Administrador"
+        || conn
+            .query_row(
+                "SELECT 1 FROM role_permissions WHERE role = ?1 AND permission = ?2",
+                rusqlite::params![role, permission],
+                |_| Ok(()),
+            )
+            .is_ok()
+}
+
+/// Registra una acción en el log de auditoría. `flag` marca entradas que
+/// requieren revisión, como los ajustes posteriores a un cierre.
+fn audit(
+    conn: &Connection,
+    user_id: Option<i32>,
+    action: &str,
+    entity: &str,
+    entity_id: Option<i64>,
+    details: &str,
+    flag: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO audit_log (user_id, action, entity, entity_id, details, flag)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![user_id, action, entity, entity_id, details, flag],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Verifica que `date` (AAAA-MM-DD...) no caiga en un mes cerrado. Si cae en
+/// uno y la sesión tiene permiso de ajuste, devuelve el mes para que el cambio
+/// quede marcado en la auditoría; sin permiso, lo rechaza.
+fn check_period_open(
+    conn: &Connection,
+    session: Option<&Session>,
+    date: &str,
+) -> Result<Option<String>, String> {
+    let month: String = date.chars().take(7).collect();
+    let closed = conn
+        .query_row(
+            "SELECT 1 FROM closed_periods WHERE month = ?1",
+            rusqlite::params![month],
+            |_| Ok(()),
+        )
+        .is_ok();
+    if !closed {
+        return Ok(None);
+    }
+    match session {
+        Some(s) if has_permission(conn, &s.role, PERM_POST_CLOSE_ADJUSTMENT) => Ok(Some(month)),
+        _ => Err(format!(
+            "El periodo {} está cerrado. Solo un usuario con permiso de ajuste puede modificarlo",
+            month
+        )),
+    }
+}
+
+/// Deja en la auditoría un cambio hecho en un periodo cerrado, con el comando
+/// que lo hizo como acción (`update_sale`, `void_sale`…).
+fn audit_post_close(
+    conn: &Connection,
+    session: Option<&Session>,
+    action: &str,
+    entity: &str,
+    entity_id: i64,
+    month: &str,
+) -> Result<(), String> {
+    audit(
+        conn,
+        session.map(|s| s.user_id),
+        action,
+        entity,
+        Some(entity_id),
+        &format!("Cambio en el periodo cerrado {}", month),
+        Some("ajuste_post_cierre"),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClosedPeriod {
+    month: String,
+    closed_by: Option<i32>,
+    closed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditEntry {
+    id: i64,
+    user_id: Option<i32>,
+    username: Option<String>,
+    action: String,
+    entity: String,
+    entity_id: Option<i64>,
+    details: Option<String>,
+    flag: Option<String>,
+    created_at: Option<String>,
+}
+
+fn validate_month(month: &str) -> Result<(), String> {
+    if month.len() == 7 && is_valid_date(&format!("{}-01", month)) {
+        Ok(())
+    } else {
+        Err("Mes inválido. Usa el formato AAAA-MM".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_closed_periods(state: State<AppState>) -> Result<Vec<ClosedPeriod>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT month, closed_by, closed_at FROM closed_periods ORDER BY month DESC")
+        .map_err(|e| e.to_string())?;
+    let periods = stmt
+        .query_map([], |row| {
+            Ok(ClosedPeriod {
+                month: row.get(0)?,
+                closed_by: row.get(1)?,
+                closed_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(periods)
+}
+
+/// Cierra un mes (AAAA-MM): desde ahí las ventas, movimientos de stock y de
+/// caja con fecha en ese mes se rechazan, salvo para quien tenga permiso de
+/// ajuste.
+#[tauri::command]
+fn close_period<R: Runtime>(window: Window<R>, state: State<AppState>, month: String) -> Result<(), String> {
+    let session = require_admin(&state, &window)?;
+    validate_month(&month)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<(), String> = (|| {
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO closed_periods (month, closed_by) VALUES (?1, ?2)",
+                rusqlite::params![month, session.user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted == 0 {
+            return Err(format!("El periodo {} ya está cerrado", month));
+        }
+        audit(
+            &conn,
+            Some(session.user_id),
+            "close_period",
+            "closed_periods",
+            None,
+            &format!("Cierre del periodo {}", month),
+            None,
+        )
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "closed_periods", None);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Reabre un mes cerrado. Queda registrado en la auditoría.
+#[tauri::command]
+fn reopen_period<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    month: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let session = require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let result: Result<(), String> = (|| {
+        let deleted = conn
+            .execute(
+                "DELETE FROM closed_periods WHERE month = ?1",
+                rusqlite::params![month],
+            )
+            .map_err(|e| e.to_string())?;
+        if deleted == 0 {
+            return Err(format!("El periodo {} no está cerrado", month));
+        }
+        let details = match reason.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+            Some(r) => format!("Reapertura del periodo {}: {}", month, r),
+            None => format!("Reapertura del periodo {}", month),
+        };
+        audit(
+            &conn,
+            Some(session.user_id),
+            "reopen_period",
+            "closed_periods",
+            None,
+            &details,
+            Some("reapertura"),
+        )
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "closed_periods", None);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Entradas del log de auditoría, de la más reciente a la más antigua.
+/// Solo para administradores.
+#[tauri::command]
+fn get_audit_log<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    flag: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<AuditEntry>, String> {
+    // El Auditor también puede revisar el log
+    if !matches!(window_session(&state, window.label()), Some(s) if s.role == ROLE_AUDITOR) {
+        require_admin(&state, &window)?;
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.id, a.user_id, u.username, a.action, a.entity, a.entity_id, a.details, a.flag, a.created_at
+             FROM audit_log a LEFT JOIN users u ON u.id = a.user_id
+             WHERE (?1 IS NULL OR substr(a.created_at,1,10) >= ?1)
+               AND (?2 IS NULL OR substr(a.created_at,1,10) <= ?2)
+               AND (?3 IS NULL OR a.flag = ?3)
+             ORDER BY a.id DESC LIMIT ?4",
+        )
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(
+            rusqlite::params![start_date, end_date, flag, limit.unwrap_or(200).clamp(1, 5000)],
+            |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    username: row.get(2)?,
+                    action: row.get(3)?,
+                    entity: row.get(4)?,
+                    entity_id: row.get(5)?,
+                    details: row.get(6)?,
+                    flag: row.get(7)?,
+                    created_at: row.get(8)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+// ============================================
+// VENTAS BAJO COSTO
+// ============================================
+
+/// Permiso para vender por debajo del costo cuando la política es "permiso".
+const PERM_BELOW_COST_SALE: &str = "venta_bajo_costo";
+
+/// Resultado de registrar una venta.
+#[derive(Debug, Serialize, Deserialize)]
+struct SaleResult {
+    /// Id de la línea en `sales`
+    id: i64,
+    /// Comprobante (`sale_headers`) al que pertenece la línea
+    header_id: i64,
+    receipt_number: Option<String>,
+    below_cost: bool,
+    /// Aviso para el vendedor cuando la venta se registró bajo costo
+    warning: Option<String>,
+    /// Impuesto incluido en el total de la venta
+    tax_amount: f64,
+}
+
+struct BelowCost {
+    net_unit_price: f64,
+    unit_cost: f64,
+}
+
+impl BelowCost {
+    fn warning(&self) -> String {
+        format!(
+            "Venta por debajo del costo: precio neto {:.2} por unidad, costo {:.2}",
+            self.net_unit_price, self.unit_cost
+        )
+    }
+}
+
+/// Compara el precio neto por unidad (total de la línea con descuento entre
+/// la cantidad) con el costo actual del producto y aplica la política
+/// `below_cost_policy`: "bloquear" rechaza la venta, "permiso" la admite solo
+/// con el permiso `venta_bajo_costo` y "advertir" (por defecto) la admite.
+/// Devuelve los datos para marcar la venta cuando queda bajo costo.
+fn check_below_cost(
+    conn: &Connection,
+    sale: &Sale,
+    session: Option<&Session>,
+) -> Result<Option<BelowCost>, String> {
+    // Un costo en otra moneda sin tipo de cambio no se puede comparar
+    let unit_cost: Option<f64> = conn
+        .query_row(
+            &format!("SELECT {} FROM products p WHERE p.id = ?1", local_cost_sql("p", "substr(?2,1,10)")),
+            rusqlite::params![sale.product_id, sale.sale_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let unit_cost = match unit_cost {
+        Some(cost) if cost > 0.0 => cost,
+        _ => return Ok(None),
+    };
+    let net_unit_price = sale.total()? / sale.quantity;
+    // Tolerancia de medio centavo para redondeos
+    if net_unit_price >= unit_cost - 0.005 {
+        return Ok(None);
+    }
+    let below = BelowCost {
+        net_unit_price,
+        unit_cost,
+    };
+    match get_setting(conn, "below_cost_policy").as_deref() {
+        Some("bloquear") => Err(format!(
+            "{}. La configuración no permite vender bajo costo",
+            below.warning()
+        )),
+        Some("permiso") => match session {
+            Some(s) if has_permission(conn, &s.role, PERM_BELOW_COST_SALE) => Ok(Some(below)),
+            _ => Err(format!(
+                "{}. Se requiere un usuario con permiso para vender bajo costo",
+                below.warning()
+            )),
+        },
+        _ => Ok(Some(below)),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BelowCostSale {
+    sale_id: i64,
+    product_id: i32,
+    product_name: Option<String>,
+    quantity: f64,
+    sale_price: f64,
+    net_unit_price: f64,
+    unit_cost: f64,
+    /// Pérdida frente al costo: (costo - precio neto) * cantidad
+    loss: f64,
+    sale_date: String,
+    created_by: Option<i32>,
+    username: Option<String>,
+}
+
+/// Ventas marcadas como bajo costo en el rango de fechas, para la revisión
+/// semanal del dueño.
+#[tauri::command]
+fn get_below_cost_sales(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<BelowCostSale>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let sql = format!(
+        "SELECT s.id, s.product_id, p.name, s.quantity, s.sale_price, s.unit_cost, s.sale_date, s.created_by, u.username
+         FROM {} s
+         LEFT JOIN products p ON p.id = s.product_id
+         LEFT JOIN users u ON u.id = s.created_by
+         WHERE s.below_cost = 1
+           AND (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+         ORDER BY s.sale_date DESC, s.id DESC",
+        valid_sales(include_archive.unwrap_or(false))
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let sales = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            let quantity: f64 = row.get(3)?;
+            let sale_price: f64 = row.get(4)?;
+            let unit_cost: f64 = row.get::<_, Option<f64>>(5)?.unwrap_or(0.0);
+            let net_unit_price = if quantity > 0.0 { sale_price / quantity } else { 0.0 };
+            Ok(BelowCostSale {
+                sale_id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                quantity,
+                sale_price,
+                net_unit_price,
+                unit_cost,
+                loss: (unit_cost - net_unit_price) * quantity,
+                sale_date: row.get(6)?,
+                created_by: row.get(7)?,
+                username: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(sales)
+}
+
+// ============================================
+// PRECIOS MODIFICADOS EN LA VENTA
+// ============================================
+
+/// Desvío (en porcentaje sobre el precio de referencia) a partir del cual un
+/// precio cobrado a mano genera una notificación; se cambia con el ajuste
+/// `price_override_alert_percent`.
+const DEFAULT_PRICE_OVERRIDE_ALERT_PERCENT: f64 = 10.0;
+
+/// Precio neto por unidad que correspondía a la venta (el de lista o el de la
+/// promoción, menos el descuento registrado) y si el total cobrado difiere
+/// del que daría `calculate_price`. `None` si el producto no tiene precio.
+fn price_reference(conn: &Connection, sale: &Sale, list_price: Option<f64>) -> Option<(f64, bool)> {
+    let discount = sale.discount.unwrap_or(0.0).clamp(0.0, 100.0);
+    let list_price = list_price?;
+    let reference_price = list_price * (1.0 - discount / 100.0);
+    let expected_total = line_total_cents(&RoundingRule::load(conn), list_price, sale.quantity, discount);
+    Some((reference_price, to_cents(sale.sale_price?) != expected_total))
+}
+
+/// Avisa cuando el precio cobrado se aleja de la referencia más que el
+/// porcentaje configurado.
+fn notify_price_override(conn: &Connection, sale: &Sale, sale_id: i64, reference_price: f64) -> Result<(), String> {
+    if reference_price <= 0.0 || sale.quantity <= 0.0 {
+        return Ok(());
+    }
+    let charged_price = sale.total()? / sale.quantity;
+    let delta_percent = (charged_price - reference_price) / reference_price * 100.0;
+    let threshold = setting_f64(conn, "price_override_alert_percent", DEFAULT_PRICE_OVERRIDE_ALERT_PERCENT);
+    if delta_percent.abs() <= threshold {
+        return Ok(());
+    }
+    let name: String = conn
+        .query_row("SELECT name FROM products WHERE id = ?1", [sale.product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    notify(
+        conn,
+        "price_override",
+        &format!("Precio modificado: {}", name),
+        &format!(
+            "Venta #{}: se cobró {:.2} por unidad frente a {:.2} de referencia ({:+.1} %).",
+            sale_id, charged_price, reference_price, delta_percent
+        ),
+        Some(sale.product_id),
+        &format!("price_override:{}", sale_id),
+    )?;
+    Ok(())
+}
+
+/// Descuento (en porcentaje) hasta el que un vendedor puede aplicar sin
+/// autorización; se cambia con el ajuste `max_discount_percent`. En 100 no
+/// hay tope.
+const DEFAULT_MAX_DISCOUNT_PERCENT: f64 = 100.0;
+
+/// Si el descuento de la venta supera `max_discount_percent`.
+fn discount_needs_override(conn: &Connection, sale: &Sale) -> bool {
+    sale.discount.unwrap_or(0.0) > setting_f64(conn, "max_discount_percent", DEFAULT_MAX_DISCOUNT_PERCENT)
+}
+
+/// Si la venta necesitó la autorización de `check_price_override`: otro
+/// total que el de lista o un descuento sobre el tope.
+fn needs_override(conn: &Connection, sale: &Sale, list_price: Option<f64>) -> bool {
+    matches!(price_reference(conn, sale, list_price), Some((_, true))) || discount_needs_override(conn, sale)
+}
+
+/// Un descuento mayor a `max_discount_percent` necesita `override` y un
+/// Administrador conectado en la ventana, como un precio cambiado.
+fn check_discount_override(conn: &Connection, sale: &Sale, session: Option<&Session>) -> Result<(), String> {
+    if !discount_needs_override(conn, sale) {
+        return Ok(());
+    }
+    match session {
+        Some(s) if sale.price_override && s.role == "Administrador" => Ok(()),
+        _ => Err(format!(
+            "Un descuento mayor al {}% necesita que un Administrador marque override",
+            setting_f64(conn, "max_discount_percent", DEFAULT_MAX_DISCOUNT_PERCENT)
+        )),
+    }
+}
+
+/// Una venta cobra el total que da el precio de lista (`sale_list_price`)
+/// con el descuento registrado. Otro total (en `add_sale`, `update_sale` o
+/// una línea de `create_sale` con `unit_price`) necesita `override` y un
+/// Administrador conectado en la ventana; queda marcado en
+/// `sales.price_override`, con el Administrador en `override_approved_by`,
+/// para `get_price_overrides`. Lo mismo vale para un descuento sobre el
+/// tope (`check_discount_override`).
+fn check_price_override(
+    conn: &Connection,
+    sale: &Sale,
+    list_price: Option<f64>,
+    session: Option<&Session>,
+) -> Result<(), String> {
+    check_discount_override(conn, sale, session)?;
+    let Some((_, true)) = price_reference(conn, sale, list_price) else {
+        return Ok(());
+    };
+    let discount = sale.discount.unwrap_or(0.0).clamp(0.0, 100.0);
+    let allowed = line_total_cents(&RoundingRule::load(conn), list_price.unwrap_or(0.0), sale.quantity, discount);
+    match session {
+        Some(s) if sale.price_override && s.role == "Administrador" => Ok(()),
+        _ if sale.price_override => Err(format!(
+            "Solo un Administrador puede cambiar el precio de venta. Precio permitido: {}",
+            format_amount(from_cents(allowed))
+        )),
+        _ => Err(format!(
+            "El precio de la venta debe ser {}, el de lista. Para cobrar otro precio un Administrador debe marcar override",
+            format_amount(from_cents(allowed))
+        )),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceOverride {
+    sale_id: i64,
+    sale_date: String,
+    product_id: i32,
+    product_name: Option<String>,
+    quantity: f64,
+    /// Precio neto por unidad que correspondía
+    reference_price: f64,
+    /// Precio neto por unidad cobrado
+    charged_price: f64,
+    /// `charged_price - reference_price`
+    delta: f64,
+    delta_percent: Option<f64>,
+    discount: Option<f64>,
+    /// Vendedor que registró la venta
+    created_by: Option<i32>,
+    username: Option<String>,
+    /// Administrador que autorizó el precio o el descuento; `None` en las
+    /// ventas anteriores a este registro
+    approved_by: Option<i32>,
+    approved_by_username: Option<String>,
+}
+
+fn price_overrides(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    include_archive: bool,
+) -> Result<Vec<PriceOverride>, String> {
+    let sql = format!(
+        "SELECT s.id, s.sale_date, s.product_id, p.name, s.quantity, s.reference_price, s.sale_price, s.created_by, u.username,
+                s.discount, s.override_approved_by, a.username
+         FROM {} s
+         LEFT JOIN products p ON p.id = s.product_id
+         LEFT JOIN users u ON u.id = s.created_by
+         LEFT JOIN users a ON a.id = s.override_approved_by
+         WHERE (s.price_override = 1 OR s.override_approved_by IS NOT NULL)
+           AND (?1 IS NULL OR substr(s.sale_date,1,10) >= ?1)
+           AND (?2 IS NULL OR substr(s.sale_date,1,10) <= ?2)
+         ORDER BY s.sale_date DESC, s.id DESC",
+        valid_sales(include_archive)
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let overrides = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            let quantity: f64 = row.get(4)?;
+            let reference_price: f64 = row.get::<_, Option<f64>>(5)?.unwrap_or(0.0);
+            let sale_price: f64 = row.get(6)?;
+            let charged_price = if quantity > 0.0 { sale_price / quantity } else { 0.0 };
+            let delta = charged_price - reference_price;
+            Ok(PriceOverride {
+                sale_id: row.get(0)?,
+                sale_date: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                quantity,
+                reference_price,
+                charged_price,
+                delta,
+                delta_percent: (reference_price > 0.0).then(|| delta / reference_price * 100.0),
+                discount: row.get(9)?,
+                created_by: row.get(7)?,
+                username: row.get(8)?,
+                approved_by: row.get(10)?,
+                approved_by_username: row.get(11)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(overrides)
+}
+
+/// Ventas en las que se cobró un precio distinto del de lista o promoción
+/// (descuento aparte) o un descuento sobre el tope, con el vendedor que las
+/// cargó y el Administrador que las autorizó.
+#[tauri::command]
+fn get_price_overrides(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<Vec<PriceOverride>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    price_overrides(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))
+}
+
+/// `get_price_overrides` en CSV.
+#[tauri::command]
+fn export_price_overrides(
+    state: State<AppState>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archive: Option<bool>,
+) -> Result<String, String> {
+    let _job = lock_export(
+        &state,
+        &export_key(
+            "price_overrides",
+            &[
+                start_date.as_deref(),
+                end_date.as_deref(),
+                include_archive.unwrap_or(false).then_some("archivo"),
+            ],
+        ),
+    )?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let overrides = price_overrides(&conn, start_date.as_deref(), end_date.as_deref(), include_archive.unwrap_or(false))?;
+    let mut csv = String::from(
+        "sale_id,sale_date,product_id,product_name,quantity,reference_price,charged_price,delta,delta_percent,discount,username,approved_by\n",
+    );
+    for o in overrides {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{:.2},{:.2},{:.2},{},{},{},{}\n",
+            o.sale_id,
+            o.sale_date,
+            o.product_id,
+            csv_field(&o.product_name.unwrap_or_default()),
+            o.quantity,
+            o.reference_price,
+            o.charged_price,
+            o.delta,
+            o.delta_percent.map(|p| format!("{:.1}", p)).unwrap_or_default(),
+            o.discount.map(|d| d.to_string()).unwrap_or_default(),
+            csv_field(&o.username.unwrap_or_default()),
+            csv_field(&o.approved_by_username.unwrap_or_default()),
+        ));
+    }
+    write_export("price_overrides", "csv", csv.as_bytes())
+}
+
+// ============================================
+// NIVELES DE STOCK (MÍNIMO Y MÁXIMO)
+// ============================================
+
+/// Problema en la configuración de mínimo y máximo de un producto, o `None`
+/// si es coherente. Un máximo vacío o en 0 significa "sin máximo".
+fn stock_levels_issue(min_stock: Option<i32>, max_stock: Option<i32>) -> Option<&'static str> {
+    if min_stock.unwrap_or(0) < 0 {
+        return Some("Stock mínimo negativo");
+    }
+    if max_stock.unwrap_or(0) < 0 {
+        return Some("Stock máximo negativo");
+    }
+    match (min_stock, max_stock) {
+        (Some(min), Some(max)) if max > 0 && min > max => Some("Stock mínimo mayor al máximo"),
+        _ => None,
+    }
+}
+
+/// Valida un producto antes de guardarlo y devuelve todos los problemas a la
+/// vez, cada uno precedido del campo (`min_stock: ...`) para que el
+/// formulario pueda señalarlos.
+fn validate_product(product: &Product) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    if product.name.trim().is_empty() {
+        errors.push("name: El nombre es obligatorio".to_string());
+    }
+    for (field, label, value) in [
+        ("sale_price", "El precio de venta", product.sale_price),
+        ("cost_price", "El costo", product.cost_price),
+        ("deposit_amount", "El depósito", product.deposit_amount),
+    ] {
+        match value {
+            Some(v) if !v.is_finite() => errors.push(format!("{}: {} no es un número válido", field, label)),
+            Some(v) if v < 0.0 => errors.push(format!("{}: {} no puede ser negativo ({})", field, label, v)),
+            _ => {}
+        }
+    }
+    if let Some(rate) = product.tax_rate.filter(|r| !valid_tax_rate(*r)) {
+        errors.push(format!(
+            "tax_rate: La tasa de impuesto debe ser un porcentaje entre 0 y 100 ({}); usa 18 para el 18%",
+            rate
+        ));
+    }
+    if let Some(currency) = product.currency.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        if !is_currency_code(currency) {
+            errors.push(format!("currency: La moneda '{}' no es un código de 3 letras como USD", currency));
+        }
+    }
+    if let Some(date) = product.expiry_date.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        if !is_valid_date(date) {
+            errors.push(format!(
+                "expiry_date: La fecha de vencimiento '{}' no es válida. Usa el formato AAAA-MM-DD.",
+                date
+            ));
+        }
+    }
+    let mut stock_errors = Vec::new();
+    if let Some(min) = product.min_stock.filter(|v| *v < 0) {
+        stock_errors.push(format!("min_stock: El stock mínimo no puede ser negativo ({})", min));
+    }
+    if let Some(max) = product.max_stock.filter(|v| *v < 0) {
+        stock_errors.push(format!("max_stock: El stock máximo no puede ser negativo ({})", max));
+    }
+    if let Some(qty) = product.initial_stock.filter(|v| *v < 0) {
+        stock_errors.push(format!("initial_stock: El stock inicial no puede ser negativo ({})", qty));
+    }
+    if stock_errors.is_empty() && stock_levels_issue(product.min_stock, product.max_stock).is_some() {
+        stock_errors.push(format!(
+            "max_stock: El stock máximo ({}) no puede ser menor que el mínimo ({})",
+            product.max_stock.unwrap_or(0),
+            product.min_stock.unwrap_or(0)
+        ));
+    }
+    errors.extend(stock_errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Días de ventas que se miran para estimar la venta diaria.
+const STOCK_SUGGESTION_DAYS: i64 = 90;
+
+/// Margen de seguridad sobre la venta esperada durante la reposición.
+const STOCK_SAFETY_FACTOR: f64 = 1.5;
+
+#[derive(Debug, Serialize)]
+struct StockLevelSuggestion {
+    product_id: i32,
+    /// Unidades vendidas por día en los últimos `STOCK_SUGGESTION_DAYS` días
+    daily_sales: f64,
+    /// Días de reposición: promedio de las compras del producto o el ajuste
+    /// `stock_lead_days` (7 por defecto)
+    lead_days: f64,
+    /// Mediana de los ingresos de stock, si hay historial
+    typical_lot: Option<f64>,
+    min_stock: i32,
+    max_stock: i32,
+}
+
+/// Propone mínimo y máximo a partir de la velocidad de venta reciente y del
+/// tamaño típico de los ingresos de stock, para prellenar el formulario.
+/// El mínimo cubre la venta esperada durante la reposición con un 50% de
+/// margen; el máximo le suma un lote típico (o un mes de venta si no hay
+/// ingresos registrados).
+#[tauri::command]
+fn suggest_stock_levels(state: State<AppState>, product_id: i32) -> Result<StockLevelSuggestion, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM products WHERE id=?1)", [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("Producto no encontrado".to_string());
+    }
+
+    let sold: f64 = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM(quantity),0) FROM {}
+                 WHERE product_id=?1 AND substr(sale_date,1,10) > date('now', ?2)",
+                valid_sales(true)
+            ),
+            rusqlite::params![product_id, format!("-{} days", STOCK_SUGGESTION_DAYS)],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let daily_sales = sold / STOCK_SUGGESTION_DAYS as f64;
+
+    let lead_days: Option<f64> = conn
+        .query_row(
+            "SELECT AVG(expected_replenish_days) FROM purchases WHERE product_id=?1 AND expected_replenish_days > 0",
+            [product_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let lead_days = lead_days.unwrap_or_else(|| setting_f64(&conn, "stock_lead_days", 7.0).max(1.0));
+
+    // Los saldos iniciales que deja el archivado no son compras reales
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT quantity FROM {} WHERE product_id=?1 AND type='ingreso' AND quantity > 0
+               AND NOT (created_by IS NULL AND COALESCE(note,'') LIKE 'Saldo inicial al % (archivo)')
+             ORDER BY quantity",
+            source_table("stock_movements", true)
+        ))
+        .map_err(|e| e.to_string())?;
+    let lots: Vec<f64> = stmt
+        .query_map([product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let typical_lot = match lots.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(lots[n / 2]),
+        n => Some((lots[n / 2 - 1] + lots[n / 2]) / 2.0),
+    };
+
+    let min_stock = (daily_sales * lead_days * STOCK_SAFETY_FACTOR).ceil() as i32;
+    let restock = typical_lot.unwrap_or(daily_sales * 30.0).ceil() as i32;
+    Ok(StockLevelSuggestion {
+        product_id,
+        daily_sales,
+        lead_days,
+        typical_lot,
+        min_stock,
+        max_stock: min_stock + restock,
+    })
+}
+
+// ============================================
+// ESTACIONALIDAD POR PRODUCTO
+// ============================================
+
+/// Suma `delta` meses a un mes `AAAA-MM`.
+fn add_months(month: &str, delta: i32) -> Result<String, String> {
+    let (year, mon) = month.split_once('-').ok_or("Mes inválido. Usa el formato AAAA-MM")?;
+    let year: i32 = year.parse().map_err(|_| "Mes inválido. Usa el formato AAAA-MM")?;
+    let mon: i32 = mon.parse().map_err(|_| "Mes inválido. Usa el formato AAAA-MM")?;
+    let index = year * 12 + (mon - 1) + delta;
+    Ok(format!("{:04}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1))
+}
+
+/// Un mes del año con las unidades vendidas en los últimos 12 meses y en
+/// los 12 anteriores. `None` significa que el producto aún no existía.
+#[derive(Debug, Serialize)]
+struct SeasonalityMonth {
+    current_month: String,
+    current_units: Option<f64>,
+    previous_month: String,
+    previous_units: Option<f64>,
+}
+
+/// Ventas mensuales de los últimos 24 meses alineadas en dos columnas (este
+/// año y el anterior, mes a mes) para comparar la temporada. Los meses sin
+/// ventas valen 0; los anteriores a la primera actividad del producto (alta,
+/// venta o movimiento de stock) quedan vacíos. Incluye lo archivado.
+#[tauri::command]
+fn get_product_seasonality(state: State<AppState>, product_id: i32) -> Result<Vec<SeasonalityMonth>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM products WHERE id=?1)", [product_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("Producto no encontrado".to_string());
+    }
+
+    let sales = valid_sales(true);
+    let movements = source_table("stock_movements", true);
+    let first_month: Option<String> = conn
+        .query_row(
+            &format!(
+                "SELECT MIN(m) FROM (
+                    SELECT substr(created_at,1,7) AS m FROM products WHERE id=?1
+                    UNION ALL SELECT substr(sale_date,1,7) FROM {} WHERE product_id=?1
+                    UNION ALL SELECT substr(created_at,1,7) FROM {} WHERE product_id=?1
+                 )",
+                sales, movements
+            ),
+            [product_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let this_month: String = conn
+        .query_row("SELECT strftime('%Y-%m','now')", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let oldest = add_months(&this_month, -23)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT substr(sale_date,1,7) AS m, SUM(quantity) FROM {}
+             WHERE product_id=?1 AND substr(sale_date,1,7) BETWEEN ?2 AND ?3
+             GROUP BY m",
+            sales
+        ))
+        .map_err(|e| e.to_string())?;
+    let units: HashMap<String, f64> = stmt
+        .query_map(rusqlite::params![product_id, oldest, this_month], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let units_in = |month: &str| -> Option<f64> {
+        match &first_month {
+            Some(first) if month >= first.as_str() => Some(units.get(month).copied().unwrap_or(0.0)),
+            _ => None,
+        }
+    };
+    (0..12)
+        .map(|i| {
+            let current_month = add_months(&this_month, i - 11)?;
+            let previous_month = add_months(&current_month, -12)?;
+            Ok(SeasonalityMonth {
+                current_units: units_in(&current_month),
+                previous_units: units_in(&previous_month),
+                current_month,
+                previous_month,
+            })
+        })
+        .collect()
+}
+
+// ============================================
+// CATÁLOGO A UNA FECHA
+// ============================================
+
+/// Valor de `field` (`sale_price` o `cost_price`) vigente al cierre de
+/// `date`, reconstruido con el historial de precios: el último cambio hasta
+/// esa fecha o, si no hubo, el valor anterior al primer cambio posterior.
+/// Sin historial vale el precio actual del producto.
+fn price_as_of_sql(field: &str) -> String {
+    format!(
+        "COALESCE(
+            (SELECT h.new_value FROM price_history h
+             WHERE h.product_id = p.id AND h.field = '{f}' AND substr(h.changed_at,1,10) <= ?1
+             ORDER BY h.changed_at DESC, h.id DESC LIMIT 1),
+            (SELECT h.old_value FROM price_history h
+             WHERE h.product_id = p.id AND h.field = '{f}' AND substr(h.changed_at,1,10) > ?1
+             ORDER BY h.changed_at, h.id LIMIT 1),
+            p.{f})",
+        f = field
+    )
+}
+
+/// Catálogo tal como estaba al cierre de `date` (AAAA-MM-DD), para seguros y
+/// auditorías: precio de venta y costo vigentes ese día, stock y valorización
+/// (stock × costo). Quedan fuera los productos creados después. Los productos
+/// no se archivan como las ventas, así que `archived` marca los eliminados
+/// hasta esa fecha y los que hoy están Inactivos o Descontinuados y no se
+/// modificaron después (ese estado ya regía entonces); siguen en el listado
+/// con su stock.
+/// El stock incluye lo archivado y omite los saldos iniciales del archivado.
+#[tauri::command]
+fn export_catalog_snapshot(state: State<AppState>, date: String) -> Result<String, String> {
+    if !is_valid_date(&date) {
+        return Err("Fecha inválida. Usa el formato AAAA-MM-DD.".to_string());
+    }
+    let _job = lock_export(&state, &export_key("catalog_snapshot", &[Some(date.as_str())]))?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT p.id, p.sku, p.name, p.category, p.status,
+                    {sale} AS sale_price,
+                    {cost} AS cost_price,
+                    (SELECT COALESCE(SUM(CASE m.type WHEN 'ingreso' THEN m.quantity ELSE -m.quantity END), 0)
+                     FROM {movements} m
+                     WHERE m.product_id = p.id AND substr(m.created_at,1,10) <= ?1
+                       AND NOT (m.created_by IS NULL AND COALESCE(m.note,'') LIKE 'Saldo inicial al % (archivo)')
+                    ) AS stock,
+                    COALESCE(substr(p.deleted_at,1,10) <= ?1
+                      OR (COALESCE(p.status,'') IN ('inactivo','descontinuado')
+                          AND substr(COALESCE(p.updated_at, p.created_at),1,10) <= ?1), 0) AS archived
+             FROM products p
+             WHERE substr(p.created_at,1,10) <= ?1
+             ORDER BY p.name, p.id",
+            sale = price_as_of_sql("sale_price"),
+            cost = price_as_of_sql("cost_price"),
+            movements = source_table("stock_movements", true),
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([&date], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<f64>>(5)?,
+                row.get::<_, Option<f64>>(6)?,
+                row.get::<_, f64>(7)?,
+                row.get::<_, bool>(8)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("snapshot_date,id,sku,name,category,status,archived,sale_price,cost_price,stock,valuation\n");
+    for (id, sku, name, category, status, sale_price, cost_price, stock, archived) in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            date,
+            id,
+            csv_field(&sku.unwrap_or_default()),
+            csv_field(&name),
+            csv_field(&category.unwrap_or_default()),
+            csv_field(&status.unwrap_or_default()),
+            if archived { 1 } else { 0 },
+            money(sale_price),
+            money(cost_price),
+            stock,
+            money(Some(stock * cost_price.unwrap_or(0.0))),
+        ));
+    }
+
+    write_export(&format!("catalog_snapshot_{}", date), "csv", csv.as_bytes())
+}
+
+// ============================================
+// RESUMEN DIARIO DE VENTAS
+// ============================================
+
+/// Ventas con fecha AAAA-MM-DD; las demás no entran en los resúmenes diarios
+/// (el reporte de calidad de datos ya las señala).
+const SALE_DAY_GLOB: &str = "[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]*";
+
+/// Primer día que se considera futuro para una venta: pasado mañana en hora
+/// local (mañana se tolera por la diferencia entre hora local y UTC).
+/// Comparar `sale_date >= FUTURE_SALE_FROM` usa el índice por fecha.
+const FUTURE_SALE_FROM: &str = "date('now','localtime','+2 day')";
+
+/// Formato en que se guarda una venta con hora: ISO 8601 en UTC, igual que
+/// `toISOString()` en la interfaz.
+const SALE_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%fZ";
+
+/// Fecha de venta tal como se guarda: `AAAA-MM-DD` si viene sin hora, o
+/// `SALE_DATETIME_FORMAT` si la trae (sin zona horaria se toma como UTC).
+/// Rechaza otros formatos y las fechas posteriores a mañana.
+fn normalize_sale_date(conn: &Connection, value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let invalid = || format!("Fecha de venta inválida: '{}'. Usa AAAA-MM-DD o AAAA-MM-DDTHH:MM:SS", value);
+    if !value.get(..10).is_some_and(is_valid_date) {
+        return Err(invalid());
+    }
+    let (normalized, latest): (Option<String>, String) = conn
+        .query_row(
+            &format!(
+                "SELECT CASE WHEN length(?1) = 10 THEN ?1 ELSE strftime('{}', ?1) END, date({}, '-1 day')",
+                SALE_DATETIME_FORMAT, FUTURE_SALE_FROM
+            ),
+            [value],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let normalized = normalized.ok_or_else(invalid)?;
+    if &normalized[..10] > latest.as_str() {
+        return Err(format!(
+            "La fecha de venta {} está en el futuro; se admite hasta el {}",
+            &normalized[..10],
+            latest
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Largo máximo de la nota de una venta.
+const SALE_NOTE_MAX_CHARS: usize = 1000;
+
+/// Nota de una venta sin espacios alrededor; vacía queda en NULL.
+fn sale_note(note: Option<&str>) -> Result<Option<String>, String> {
+    let Some(note) = note.map(str::trim).filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+    let chars = note.chars().count();
+    if chars > SALE_NOTE_MAX_CHARS {
+        return Err(format!(
+            "La nota de la venta admite como máximo {} caracteres (tiene {})",
+            SALE_NOTE_MAX_CHARS, chars
+        ));
+    }
+    Ok(Some(note.to_string()))
+}
+
+/// Día de los resúmenes y tendencias donde caen las ventas con fecha futura,
+/// para no sumarlas en un día que todavía no ocurrió.
+const ATTENTION_DAY: &str = "atención";
+
+/// Día del resumen para `sale_date`: su fecha o `ATTENTION_DAY` si es futura.
+fn summary_day_sql(col: &str) -> String {
+    format!(
+        "CASE WHEN {col} >= {future} THEN '{attention}' ELSE substr({col},1,10) END",
+        col = col,
+        future = FUTURE_SALE_FROM,
+        attention = ATTENTION_DAY
+    )
+}
+
+/// Recalcula `daily_sales_summary` desde las ventas vivas y archivadas: los
+/// días de `range` (AAAA-MM-DD, ambos incluidos) o, sin rango, todos. Cada
+/// venta registrada recalcula solo su día, así el tablero no recorre la tabla
+/// de ventas. `by_category` usa la categoría actual del producto. Las ventas
+/// con fecha futura van al día `ATTENTION_DAY`, que se recalcula siempre.
+/// Debe ejecutarse dentro de la transacción que modificó las ventas. Devuelve
+/// cuántos días quedaron con resumen.
+fn refresh_daily_summaries(conn: &Connection, range: Option<(&str, &str)>) -> Result<usize> {
+    let (delete, filter, params) = match range {
+        Some((from, to)) => (
+            format!("DELETE FROM daily_sales_summary WHERE day BETWEEN ?1 AND ?2 OR day = '{}'", ATTENTION_DAY),
+            // sale_date completo (no substr) para que se use el índice por fecha
+            format!(
+                "((s.sale_date >= ?1 AND s.sale_date < date(?2, '+1 day')) OR s.sale_date >= {})",
+                FUTURE_SALE_FROM
+            ),
+            vec![from, to],
+        ),
+        None => ("DELETE FROM daily_sales_summary".to_string(), "1".to_string(), vec![]),
+    };
+    conn.execute(&delete, rusqlite::params_from_iter(&params))?;
+    conn.execute(
+        &format!(
+            "INSERT INTO daily_sales_summary (day, revenue, units, tickets, by_category, updated_at)
+             SELECT day, SUM(revenue), SUM(units), SUM(tickets),
+                    json_group_object(category, json_object('revenue', revenue, 'units', units, 'tickets', tickets)),
+                    CURRENT_TIMESTAMP
+             FROM (
+                SELECT {day} AS day, COALESCE(p.category,'') AS category,
+                       COALESCE(SUM(s.sale_price),0.0) AS revenue, COALESCE(SUM(s.quantity),0) AS units,
+                       COUNT(*) AS tickets
+                FROM {sales} s LEFT JOIN products p ON p.id = s.product_id
+                WHERE {filter} AND s.sale_date GLOB '{glob}'
+                GROUP BY day, category
+             )
+             GROUP BY day",
+            day = summary_day_sql("s.sale_date"),
+            sales = valid_sales(true),
+            filter = filter,
+            glob = SALE_DAY_GLOB
+        ),
+        rusqlite::params_from_iter(&params),
+    )
+}
+
+/// Días en que el resumen guardado no coincide con las ventas (vivas y
+/// archivadas) en cantidad de ventas, unidades o importe.
+fn daily_summary_mismatches(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH raw AS (
+            SELECT {} AS day, COUNT(*) AS tickets,
+                   ROUND(COALESCE(SUM(sale_price),0.0), 2) AS revenue, ROUND(COALESCE(SUM(quantity),0), 3) AS units
+            FROM {} WHERE sale_date GLOB '{}'
+            GROUP BY day
+         ),
+         summary AS (
+            SELECT day, tickets, ROUND(revenue, 2), ROUND(units, 3) FROM daily_sales_summary
+         )
+         SELECT day FROM (SELECT * FROM raw EXCEPT SELECT * FROM summary)
+         UNION
+         SELECT day FROM (SELECT * FROM summary EXCEPT SELECT * FROM raw)
+         ORDER BY day",
+        summary_day_sql("sale_date"),
+        valid_sales(true),
+        SALE_DAY_GLOB
+    ))?;
+    let days = stmt.query_map([], |row| row.get(0))?.collect();
+    days
+}
+
+/// Reconstruye todos los resúmenes diarios desde las ventas. Solo hace falta
+/// si el reporte de salud muestra días que no coinciden. Solo para
+/// administradores; devuelve cuántos días quedaron con resumen.
+#[tauri::command]
+fn rebuild_daily_summaries<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<usize, String> {
+    require_admin(&state, &window)?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    match refresh_daily_summaries(&conn, None) {
+        Ok(days) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "daily_sales_summary", None);
+            Ok(days)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err.to_string())
+        }
+    }
+}
+
+// ============================================
+// CONSISTENCIA ENTRE REPORTES
+// ============================================
+
+/// Totales de un período según uno de los caminos que los calculan. Los
+/// campos que ese camino no informa quedan vacíos.
+#[derive(Debug, Serialize)]
+struct ReportPathTotals {
+    source: String,
+    tickets: Option<i64>,
+    units: Option<f64>,
+    revenue: f64,
+}
+
+/// Resultado de `verify_report_consistency`: los totales de cada camino y
+/// una línea por cada valor que no coincide con el de los demás.
+#[derive(Debug, Serialize)]
+struct ReportConsistency {
+    start_date: String,
+    end_date: String,
+    paths: Vec<ReportPathTotals>,
+    mismatches: Vec<String>,
+    consistent: bool,
+}
+
+/// Compara una métrica entre caminos (redondeada a `decimals`) contra el
+/// primero que la informa y describe cada diferencia.
+fn report_metric_mismatches(
+    paths: &[ReportPathTotals],
+    metric: &str,
+    decimals: i32,
+    value: impl Fn(&ReportPathTotals) -> Option<f64>,
+) -> Vec<String> {
+    let scale = 10f64.powi(decimals);
+    let round = |v: f64| (v * scale).round() / scale;
+    let mut values = paths.iter().filter_map(|p| value(p).map(|v| (p, round(v))));
+    let Some((reference, expected)) = values.next() else {
+        return Vec::new();
+    };
+    values
+        .filter(|(_, v)| *v != expected)
+        .map(|(p, v)| format!("{}: {} = {}, {} = {}", metric, reference.source, expected, p.source, v))
+        .collect()
+}
+
+/// Calcula las ventas del período por cada camino que las muestra (totales
+/// del tablero, tendencia, exportación CSV y resúmenes diarios) e informa
+/// cualquier diferencia. Todos incluyen lo archivado, porque los resúmenes
+/// diarios siempre lo hacen. Sirve para soporte y para las pruebas de
+/// integración: si un reporte cambia cómo cuenta las ventas, aparece aquí.
+#[tauri::command]
+fn verify_report_consistency(
+    state: State<AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<ReportConsistency, String> {
+    if !is_valid_date(&start_date) || !is_valid_date(&end_date) || start_date > end_date {
+        return Err("Rango de fechas inválido. Usa el formato AAAA-MM-DD.".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut paths = Vec::new();
+
+    let totals = sales_totals(&conn, Some(&start_date), Some(&end_date), None, true).map_err(|e| e.to_string())?;
+    paths.push(ReportPathTotals {
+        source: "get_sales_totals".to_string(),
+        tickets: None,
+        units: Some(totals.total_units),
+        revenue: totals.total_revenue,
+    });
+
+    // La tendencia cuenta hacia atrás desde hoy: se pide desde el inicio del
+    // período y se suman solo sus días
+    let days: i32 = conn
+        .query_row("SELECT CAST(julianday(date('now')) - julianday(?1) AS INTEGER)", [&start_date], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let trend: Vec<SalesTrendPoint> = sales_trend(&conn, days, true)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|p| p.date >= start_date && p.date <= end_date)
+        .collect();
+    paths.push(ReportPathTotals {
+        source: "get_sales_trend".to_string(),
+        tickets: Some(trend.iter().map(|p| p.sales_count).sum()),
+        units: None,
+        revenue: trend.iter().map(|p| p.total_revenue).sum(),
+    });
+
+    let rows = sales_report_rows(&conn, Some(&start_date), Some(&end_date), true).map_err(|e| e.to_string())?;
+    paths.push(ReportPathTotals {
+        source: "export_sales_report".to_string(),
+        tickets: Some(rows.len() as i64),
+        units: Some(rows.iter().map(|r| r.2).sum()),
+        revenue: rows.iter().map(|r| r.3).sum(),
+    });
+
+    let summary = conn
+        .query_row(
+            "SELECT COALESCE(SUM(tickets),0), COALESCE(SUM(units),0), COALESCE(SUM(revenue),0.0)
+             FROM daily_sales_summary WHERE day BETWEEN ?1 AND ?2",
+            [&start_date, &end_date],
+            |row| {
+                Ok(ReportPathTotals {
+                    source: "daily_sales_summary".to_string(),
+                    tickets: Some(row.get(0)?),
+                    units: Some(row.get(1)?),
+                    revenue: row.get(2)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    paths.push(summary);
+
+    let mut mismatches = report_metric_mismatches(&paths, "ventas", 0, |p| p.tickets.map(|t| t as f64));
+    mismatches.extend(report_metric_mismatches(&paths, "unidades", 3, |p| p.units));
+    mismatches.extend(report_metric_mismatches(&paths, "importe", 2, |p| Some(p.revenue)));
+    Ok(ReportConsistency {
+        start_date,
+        end_date,
+        consistent: mismatches.is_empty(),
+        paths,
+        mismatches,
+    })
+}
+
+// ============================================
+// VERSIÓN Y ACTUALIZACIONES
+// ============================================
+
+/// Qué build está ejecutando el cliente, para los reportes de errores.
+#[derive(Debug, Serialize, Deserialize)]
+struct AppInfo {
+    version: String,
+    /// Commit del build (lo agrega build.rs); vacío si se compiló sin git
+    git_hash: Option<String>,
+    build_date: Option<String>,
+    /// `PRAGMA user_version` de la base abierta
+    schema_version: i32,
+}
+
+fn app_info(conn: &Connection) -> Result<AppInfo, String> {
+    let schema_version = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let build_value = |value: Option<&str>| value.filter(|v| !v.is_empty()).map(str::to_string);
+    Ok(AppInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: build_value(option_env!("VITASPORT_GIT_HASH")),
+        build_date: build_value(option_env!("VITASPORT_BUILD_DATE")),
+        schema_version,
+    })
+}
+
+/// Versión, commit, fecha del build y versión del esquema (ventana "Acerca de").
+#[tauri::command]
+fn get_app_info(state: State<AppState>) -> Result<AppInfo, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    app_info(&conn)
+}
+
+/// Espera máxima al consultar el manifiesto de actualizaciones.
+const UPDATE_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Manifiesto publicado en `update_manifest_url`.
+#[derive(Debug, Deserialize)]
+struct UpdateManifest {
+    version: String,
+    url: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdateCheck {
+    current_version: String,
+    latest_version: String,
+    update_available: bool,
+    download_url: Option<String>,
+    notes: Option<String>,
+}
+
+/// Partes numéricas de una versión ("v1.10.2" → 1, 10, 2); lo que no es
+/// número cuenta como 0.
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches(['v', 'V'])
+        .split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().unwrap_or(0)
+        })
+        .collect()
+}
+
+fn is_newer_version(candidate: &str, current: &str) -> bool {
+    let (candidate, current) = (version_parts(candidate), version_parts(current));
+    let len = candidate.len().max(current.len());
+    let part = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| part(&candidate, i).cmp(&part(&current, i)))
+        .find(|o| o.is_ne())
+        .map(|o| o.is_gt())
+        .unwrap_or(false)
+}
+
+/// Consulta el manifiesto JSON (`version`, `url`, `notes`) configurado en
+/// `update_manifest_url` e informa si hay una versión más nueva. No descarga
+/// nada. Corre fuera del hilo principal, con tiempo límite, y solo cuando el
+/// usuario lo pide; nunca al iniciar.
+#[tauri::command(async)]
+fn check_for_updates(state: State<AppState>) -> Result<UpdateCheck, String> {
+    let url = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        get_setting(&conn, "update_manifest_url").filter(|u| !u.trim().is_empty())
+    }
+    .ok_or("No hay una dirección de actualizaciones configurada (update_manifest_url)")?;
+
+    let body = ureq::get(url.trim())
+        .timeout(UPDATE_CHECK_TIMEOUT)
+        .call()
+        .map_err(|e| format!("No se pudo consultar las actualizaciones: {}", e))?
+        .into_string()
+        .map_err(|e| format!("No se pudo consultar las actualizaciones: {}", e))?;
+    let manifest: UpdateManifest = serde_json::from_str(&body)
+        .map_err(|e| format!("El manifiesto de actualizaciones no es válido: {}", e))?;
+
+    let current_version = env!("CARGO_PKG_VERSION").to_string();
+    Ok(UpdateCheck {
+        update_available: is_newer_version(&manifest.version, &current_version),
+        current_version,
+        latest_version: manifest.version,
+        download_url: manifest.url,
+        notes: manifest.notes,
+    })
+}
+
+// ============================================
+// ADJUNTOS
+// ============================================
+
+/// Entidades que admiten adjuntos. Las ventas y los movimientos pueden estar
+/// archivados.
+const ATTACHMENT_ENTITIES: [&str; 4] = ["sales", "stock_movements", "purchases", "products"];
+
+/// Tamaño máximo de un adjunto.
+const ATTACHMENT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Tipos admitidos: extensión, tipo MIME y firma con la que empieza el archivo.
+const ATTACHMENT_TYPES: [(&str, &str, &[u8]); 5] = [
+    ("pdf", "application/pdf", b"%PDF"),
+    ("png", "image/png", b"\x89PNG"),
+    ("jpg", "image/jpeg", b"\xFF\xD8\xFF"),
+    ("jpeg", "image/jpeg", b"\xFF\xD8\xFF"),
+    ("webp", "image/webp", b"RIFF"),
+];
+
+#[derive(Debug, Serialize)]
+struct Attachment {
+    id: i64,
+    entity: String,
+    entity_id: i64,
+    filename: String,
+    stored_path: String,
+    mime_type: String,
+    size: i64,
+    uploaded_by: Option<String>,
+    created_at: String,
+}
+
+/// Carpeta de adjuntos: `VitaSport/adjuntos` dentro de los datos de la
+/// aplicación, o la indicada en `VITASPORT_ATTACHMENTS_DIR` (la usan las
+/// pruebas de integración).
+fn attachments_dir() -> Result<PathBuf, String> {
+    let dir = match std::env::var_os("VITASPORT_ATTACHMENTS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => data_dir()
+            .ok_or("No se pudo obtener la carpeta de datos de la aplicación")?
+            .join("VitaSport")
+            .join("adjuntos"),
+    };
+    fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+    Ok(dir)
+}
+
+/// Condición sobre `attachments a` para los adjuntos de un producto (`?1`):
+/// los suyos y los de sus ventas, movimientos (incluido lo archivado) y compras.
+fn product_attachments_filter() -> String {
+    format!(
+        "(a.entity = 'products' AND a.entity_id = ?1)
+         OR (a.entity = 'sales' AND a.entity_id IN (SELECT id FROM {} WHERE product_id = ?1))
+         OR (a.entity = 'stock_movements' AND a.entity_id IN (SELECT id FROM {} WHERE product_id = ?1))
+         OR (a.entity = 'purchases' AND a.entity_id IN (SELECT id FROM purchases WHERE product_id = ?1))",
+        source_table("sales", true),
+        source_table("stock_movements", true)
+    )
+}
+
+/// Verifica que `entity` admita adjuntos y que exista la fila `entity_id`.
+fn check_attachment_target(conn: &Connection, entity: &str, entity_id: i64) -> Result<(), String> {
+    if !ATTACHMENT_ENTITIES.contains(&entity) {
+        return Err(format!(
+            "No se pueden adjuntar archivos a {}. Usa: {}",
+            entity,
+            ATTACHMENT_ENTITIES.join(", ")
+        ));
+    }
+    let archived = ARCHIVABLE_TABLES.iter().any(|(table, _)| *table == entity);
+    let exists: bool = conn
+        .query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", source_table(entity, archived)),
+            [entity_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err(format!("No existe el registro {} de {}", entity_id, entity));
+    }
+    Ok(())
+}
+
+/// Tipo MIME de `path` según su extensión, comprobando que el contenido
+/// empiece con la firma de ese tipo (un .exe renombrado a .pdf no pasa).
+fn attachment_type(path: &Path, content: &[u8]) -> Result<&'static str, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let (_, mime, magic) = ATTACHMENT_TYPES
+        .iter()
+        .find(|(ext, ..)| *ext == extension)
+        .ok_or_else(|| format!("Tipo de archivo no admitido: {}. Solo PDF, PNG, JPG o WEBP", path.display()))?;
+    if !content.starts_with(magic) {
+        return Err(format!("El contenido de {} no corresponde a un archivo {}", path.display(), extension.to_uppercase()));
+    }
+    Ok(mime)
+}
+
+/// Copia `source_path` a la carpeta de adjuntos y lo asocia a la fila `id`
+/// de `entity` (sales, stock_movements, purchases o products). Admite PDF e
+/// imágenes de hasta 10 MB. Queda en la auditoría.
+#[tauri::command]
+fn add_attachment<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    entity: String,
+    id: i64,
+    source_path: String,
+) -> Result<Attachment, String> {
+    let uploaded_by = acting_user(&state, &window);
+    {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        check_attachment_target(&conn, &entity, id)?;
+    }
+
+    let source = PathBuf::from(&source_path);
+    let size = fs::metadata(&source).map_err(|e| io_error(&source, e))?.len();
+    if size == 0 {
+        return Err(format!("El archivo {} está vacío", source.display()));
+    }
+    if size > ATTACHMENT_MAX_BYTES {
+        return Err(format!(
+            "El archivo {} supera el máximo de {} MB",
+            source.display(),
+            ATTACHMENT_MAX_BYTES / (1024 * 1024)
+        ));
+    }
+    let content = fs::read(&source).map_err(|e| io_error(&source, e))?;
+    let mime_type = attachment_type(&source, &content)?;
+    let filename = source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or("Ruta de archivo inválida")?;
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let stored = unique_export_path(&attachments_dir()?, &format!("{}_{}", entity, id), Some(&extension))?;
+    write_file(&stored, &content)?;
+    let stored_path = path_to_string(&stored)?;
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let result: Result<i64, String> = (|| {
+        conn.execute(
+            "INSERT INTO attachments (entity, entity_id, filename, stored_path, mime_type, size, uploaded_by)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![entity, id, filename, stored_path, mime_type, size as i64, uploaded_by],
+        )
+        .map_err(|e| e.to_string())?;
+        let attachment_id = conn.last_insert_rowid();
+        audit(
+            &conn,
+            uploaded_by,
+            "add_attachment",
+            "attachments",
+            Some(attachment_id),
+            &format!("{} adjuntado a {} #{}", filename, entity, id),
+            None,
+        )?;
+        Ok(attachment_id)
+    })();
+    let attachment_id = match result {
+        Ok(attachment_id) => attachment_id,
+        Err(err) => {
+            let _ = fs::remove_file(&stored);
+            return Err(err);
+        }
+    };
+    emit_data_changed(&window, "attachments", Some(attachment_id));
+    attachment_by_id(&conn, attachment_id)
+}
+
+fn attachment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        entity: row.get(1)?,
+        entity_id: row.get(2)?,
+        filename: row.get(3)?,
+        stored_path: row.get(4)?,
+        mime_type: row.get(5)?,
+        size: row.get(6)?,
+        uploaded_by: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+const ATTACHMENT_SELECT: &str = "SELECT a.id, a.entity, a.entity_id, a.filename, a.stored_path, a.mime_type, a.size, u.username, a.created_at
+     FROM attachments a LEFT JOIN users u ON u.id = a.uploaded_by";
+
+fn attachment_by_id(conn: &Connection, id: i64) -> Result<Attachment, String> {
+    conn.query_row(&format!("{} WHERE a.id = ?1", ATTACHMENT_SELECT), [id], attachment_from_row)
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Adjunto no encontrado".to_string(),
+            e => e.to_string(),
+        })
+}
+
+/// Adjuntos de la fila `id` de `entity`, del más antiguo al más nuevo.
+#[tauri::command]
+fn list_attachments(state: State<AppState>, entity: String, id: i64) -> Result<Vec<Attachment>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE a.entity = ?1 AND a.entity_id = ?2 ORDER BY a.created_at, a.id",
+            ATTACHMENT_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let attachments = stmt
+        .query_map(rusqlite::params![entity, id], attachment_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(attachments)
+}
+
+/// Borra un adjunto y su archivo. Puede hacerlo quien lo subió o un
+/// Administrador; queda en la auditoría.
+#[tauri::command]
+fn delete_attachment<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<(), String> {
+    let session = window_session(&state, window.label()).ok_or("No hay una sesión activa en esta ventana")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let (entity, entity_id, filename, stored_path, uploaded_by): (String, i64, String, String, Option<i32>) = conn
+        .query_row(
+            "SELECT entity, entity_id, filename, stored_path, uploaded_by FROM attachments WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Adjunto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if session.role != "Administrador" && uploaded_by != Some(session.user_id) {
+        return Err("Solo quien subió el adjunto o un Administrador puede eliminarlo".to_string());
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        conn.execute("DELETE FROM attachments WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        audit(
+            &conn,
+            Some(session.user_id),
+            "delete_attachment",
+            "attachments",
+            Some(id),
+            &format!("{} eliminado de {} #{}", filename, entity, entity_id),
+            None,
+        )
+    })();
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(err);
+        }
+    }
+    // Si el archivo ya no estaba, igual se quita el registro
+    let path = Path::new(&stored_path);
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(io_error(path, e));
+        }
+    }
+    emit_data_changed(&window, "attachments", Some(id));
+    Ok(())
+}
+
+// ============================================
+// IMÁGENES DE PRODUCTOS
+// ============================================
+
+/// Lado mayor de las miniaturas de la grilla de productos, en píxeles.
+const THUMBNAIL_SIZE: u32 = 200;
+
+/// Tamaño máximo por defecto de la foto original de un producto, en MB;
+/// se cambia con el ajuste `product_image_max_mb`.
+const DEFAULT_PRODUCT_IMAGE_MAX_MB: f64 = 10.0;
+
+/// Extensiones que acepta `save_product_image`.
+const PRODUCT_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+/// Prefijo de las rutas que guarda la aplicación en `image_path` y
+/// `thumbnail_path`: relativas a la carpeta de imágenes, para que sigan
+/// valiendo si la carpeta de datos cambia de lugar o de equipo.
+const MANAGED_IMAGE_PREFIX: &str = "imagenes/";
+
+/// Carpeta de imágenes de productos: `VitaSport/imagenes` dentro de los datos
+/// de la aplicación, o la indicada en `VITASPORT_IMAGES_DIR` (la usan las
+/// pruebas de integración). No la crea; ver `product_images_dir`.
+fn product_images_path() -> Option<PathBuf> {
+    match std::env::var_os("VITASPORT_IMAGES_DIR") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => data_dir().map(|dir| dir.join("VitaSport").join("imagenes")),
+    }
+}
+
+fn product_images_dir() -> Result<PathBuf, String> {
+    let dir = product_images_path().ok_or("No se pudo obtener la carpeta de datos de la aplicación")?;
+    fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+    Ok(dir)
+}
+
+/// Ruta absoluta de una `image_path`/`thumbnail_path` guardada: las
+/// administradas (`imagenes/...`) se buscan en la carpeta de imágenes y las
+/// demás (cargadas a mano o de versiones anteriores) se usan tal cual.
+fn resolve_image_path(stored: &str) -> PathBuf {
+    match (stored.strip_prefix(MANAGED_IMAGE_PREFIX), product_images_path()) {
+        (Some(name), Some(dir)) => dir.join(name),
+        _ => PathBuf::from(stored),
+    }
+}
+
+/// Valor a guardar para un archivo de la carpeta de imágenes.
+fn managed_image_path(file: &Path) -> Result<String, String> {
+    let name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Nombre de archivo no válido: {}", file.display()))?;
+    Ok(format!("{}{}", MANAGED_IMAGE_PREFIX, name))
+}
+
+/// De las rutas indicadas, las de archivos de la carpeta de imágenes (las que
+/// copió la aplicación y puede borrar), ya resueltas; una `image_path` cargada
+/// a mano apunta a un archivo del usuario y no se toca.
+fn managed_image_files(paths: &[Option<String>]) -> Vec<PathBuf> {
+    let Some(dir) = product_images_path() else {
+        return Vec::new();
+    };
+    paths
+        .iter()
+        .flatten()
+        .map(|path| resolve_image_path(path))
+        .filter(|path| path.parent() == Some(dir.as_path()))
+        .collect()
+}
+
+/// Como `managed_image_files`, sin los archivos que también usa otro producto
+/// (un clon de `clone_product` comparte la foto del original).
+fn unshared_image_files(conn: &Connection, product_id: i32, paths: &[Option<String>]) -> Result<Vec<PathBuf>, String> {
+    let mut unshared = Vec::new();
+    for path in paths.iter().flatten() {
+        let shared: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM products WHERE id <> ?1 AND (image_path = ?2 OR thumbnail_path = ?2))",
+                rusqlite::params![product_id, path],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !shared {
+            unshared.push(Some(path.clone()));
+        }
+    }
+    Ok(managed_image_files(&unshared))
+}
+
+/// Decodifica una imagen PNG, JPEG o WebP; un archivo dañado o de otro
+/// formato devuelve un error con la ruta.
+fn decode_image(path: &Path, content: &[u8]) -> Result<image::DynamicImage, String> {
+    image::load_from_memory(content).map_err(|e| match e {
+        image::ImageError::Unsupported(_) => format!(
+            "{}: formato de imagen no admitido. Usa PNG, JPEG o WebP.",
+            path.display()
+        ),
+        e => format!("{}: la imagen está dañada o no se puede leer ({})", path.display(), e),
+    })
+}
+
+/// Guarda la miniatura JPEG de `image` en la carpeta de imágenes como
+/// `product_<id>_mini.jpg`, reemplazando la anterior.
+fn write_thumbnail(image: &image::DynamicImage, product_id: i32) -> Result<PathBuf, String> {
+    let thumbnail = image::DynamicImage::ImageRgb8(image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8());
+    let mut content = std::io::Cursor::new(Vec::new());
+    thumbnail
+        .write_to(&mut content, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("No se pudo generar la miniatura: {}", e))?;
+    let path = product_images_dir()?.join(format!("product_{}_mini.jpg", product_id));
+    write_file(&path, content.get_ref())?;
+    Ok(path)
+}
+
+#[derive(Debug, Serialize)]
+struct ProductImage {
+    /// Valor guardado en el producto (`imagenes/product_<id>.<ext>`)
+    image_path: String,
+    /// Ruta absoluta de la foto, para mostrarla
+    path: String,
+    /// Ruta absoluta de la miniatura, igual que en `Product`
+    thumbnail_path: String,
+}
+
+/// Copia la foto `source_path` a la carpeta de imágenes como
+/// `product_<id>.<ext>`, genera su miniatura y guarda ambas rutas (relativas a
+/// esa carpeta) en el producto. Las imágenes anteriores que había copiado la
+/// aplicación se borran.
+#[tauri::command]
+fn save_product_image<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    product_id: i32,
+    source_path: String,
+) -> Result<ProductImage, String> {
+    let (previous, max_mb): ((Option<String>, Option<String>), f64) = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let previous = conn
+            .query_row(
+                "SELECT image_path, thumbnail_path FROM products WHERE id = ?1 AND deleted_at IS NULL",
+                [product_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+                e => e.to_string(),
+            })?;
+        (previous, setting_f64(&conn, "product_image_max_mb", DEFAULT_PRODUCT_IMAGE_MAX_MB))
+    };
+
+    let source = PathBuf::from(&source_path);
+    let source_extension = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    if !source_extension.is_some_and(|ext| PRODUCT_IMAGE_EXTENSIONS.contains(&ext.as_str())) {
+        return Err(format!(
+            "{}: solo se admiten imágenes {}",
+            source.display(),
+            PRODUCT_IMAGE_EXTENSIONS.iter().map(|ext| format!(".{}", ext)).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    let size = fs::metadata(&source).map_err(|e| io_error(&source, e))?.len();
+    if size as f64 > max_mb * 1024.0 * 1024.0 {
+        return Err(format!("La imagen {} supera el máximo de {} MB", source.display(), max_mb));
+    }
+    let content = fs::read(&source).map_err(|e| io_error(&source, e))?;
+    let decoded = decode_image(&source, &content)?;
+    let extension = image::guess_format(&content)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("img");
+    let stored = product_images_dir()?.join(format!("product_{}.{}", product_id, extension));
+    write_file(&stored, &content)?;
+    let thumbnail = match write_thumbnail(&decoded, product_id) {
+        Ok(path) => path,
+        Err(err) => {
+            let _ = fs::remove_file(&stored);
+            return Err(err);
+        }
+    };
+    let image = ProductImage {
+        image_path: managed_image_path(&stored)?,
+        path: path_to_string(&stored)?,
+        thumbnail_path: path_to_string(&thumbnail)?,
+    };
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    if let Err(e) = conn.execute(
+        "UPDATE products SET image_path = ?2, thumbnail_path = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        rusqlite::params![product_id, image.image_path, managed_image_path(&thumbnail)?],
+    ) {
+        let _ = fs::remove_file(&stored);
+        let _ = fs::remove_file(&thumbnail);
+        return Err(e.to_string());
+    }
+    // Con el mismo formato el archivo nuevo ya ocupa el nombre del anterior
+    for file in unshared_image_files(&conn, product_id, &[previous.0, previous.1])? {
+        if file != stored && file != thumbnail {
+            let _ = fs::remove_file(file);
+        }
+    }
+    emit_data_changed(&window, "products", Some(product_id as i64));
+    Ok(image)
+}
+
+/// Ruta de la miniatura del producto, o `None` si no tiene o el archivo ya no
+/// existe (ver `regenerate_thumbnails`).
+#[tauri::command]
+fn get_product_thumbnail_path(state: State<AppState>, product_id: i32) -> Result<Option<String>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let thumbnail: Option<String> = conn
+        .query_row("SELECT thumbnail_path FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    Ok(thumbnail
+        .map(|path| resolve_image_path(&path))
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned()))
+}
+
+/// Ruta absoluta de la foto del producto, o `None` si no tiene o el archivo
+/// ya no existe.
+#[tauri::command]
+fn get_product_image(state: State<AppState>, product_id: i32) -> Result<Option<String>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let image: Option<String> = conn
+        .query_row("SELECT image_path FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    Ok(image
+        .filter(|path| !path.is_empty())
+        .map(|path| resolve_image_path(&path))
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned()))
+}
+
+#[derive(Debug, Serialize)]
+struct ThumbnailFailure {
+    product_id: i32,
+    name: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ThumbnailReport {
+    generated: usize,
+    failed: Vec<ThumbnailFailure>,
+}
+
+/// Mantenimiento: genera la miniatura de cada producto con imagen que no la
+/// tiene (o cuyo archivo se perdió). Las imágenes que no se pueden leer se
+/// informan en `failed` y no detienen el resto.
+#[tauri::command]
+fn regenerate_thumbnails<R: Runtime>(window: Window<R>, state: State<AppState>) -> Result<ThumbnailReport, String> {
+    let session = require_admin(&state, &window)?;
+    let pending: Vec<(i32, String, String, Option<String>)> = {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, image_path, thumbnail_path FROM products
+                 WHERE image_path IS NOT NULL AND image_path <> '' ORDER BY id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+
+    let mut report = ThumbnailReport { generated: 0, failed: Vec::new() };
+    for (product_id, name, image_path, thumbnail_path) in pending {
+        if thumbnail_path.is_some_and(|path| resolve_image_path(&path).is_file()) {
+            continue;
+        }
+        let source = resolve_image_path(&image_path);
+        let thumbnail = fs::read(&source)
+            .map_err(|e| io_error(&source, e))
+            .and_then(|content| decode_image(&source, &content))
+            .and_then(|decoded| write_thumbnail(&decoded, product_id))
+            .and_then(|path| managed_image_path(&path));
+        match thumbnail {
+            Ok(path) => {
+                let conn = state.db.lock().map_err(|e| e.to_string())?;
+                conn.execute(
+                    "UPDATE products SET thumbnail_path = ?2 WHERE id = ?1",
+                    rusqlite::params![product_id, path],
+                )
+                .map_err(|e| e.to_string())?;
+                report.generated += 1;
+            }
+            Err(error) => report.failed.push(ThumbnailFailure { product_id, name, error }),
+        }
+    }
+
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    audit(
+        &conn,
+        Some(session.user_id),
+        "regenerate_thumbnails",
+        "products",
+        None,
+        &format!("{} miniaturas generadas, {} imágenes con error", report.generated, report.failed.len()),
+        None,
+    )?;
+    if report.generated > 0 {
+        emit_data_changed(&window, "products", None);
+    }
+    Ok(report)
+}
+
+// ============================================
+// VENTAS DUPLICADAS
+// ============================================
+
+/// Venta de un par sospechoso, con el egreso de stock que generó.
+#[derive(Debug, Serialize)]
+struct DuplicateSale {
+    id: i64,
+    sale_date: String,
+    movement_id: Option<i64>,
+}
+
+/// Dos ventas iguales registradas con pocos segundos de diferencia,
+/// probablemente por un doble clic.
+#[derive(Debug, Serialize)]
+struct DuplicatePair {
+    product_id: i64,
+    product_name: Option<String>,
+    quantity: f64,
+    sale_price: f64,
+    created_by: Option<String>,
+    seconds_apart: f64,
+    first: DuplicateSale,
+    second: DuplicateSale,
+}
+
+/// Egreso que generó la venta `sale_id`. Las ventas anteriores a
+/// `stock_movements.sale_id` no tienen vínculo: se toma el egreso sin nota ni
+/// venta del mismo producto, cantidad y usuario más cercano en el tiempo (para
+/// el stock cualquiera de ellos es equivalente). `exclude` evita devolver el
+/// de la otra venta del par.
+fn sale_movement(conn: &Connection, sale_id: i64, exclude: Option<i64>) -> Result<Option<i64>, String> {
+    match conn.query_row(
+        "SELECT m.id FROM stock_movements m JOIN sales s ON s.id = ?1
+         WHERE m.sale_id = s.id
+            OR (m.sale_id IS NULL AND m.note IS NULL AND m.type = 'egreso'
+                AND m.product_id = s.product_id AND m.quantity = s.quantity
+                AND m.created_by IS s.created_by AND m.id IS NOT ?2)
+         ORDER BY m.sale_id IS NULL, ABS(julianday(m.created_at) - julianday(s.sale_date)), m.id DESC
+         LIMIT 1",
+        rusqlite::params![sale_id, exclude],
+        |row| row.get(0),
+    ) {
+        Ok(id) => Ok(Some(id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Pares de ventas del mismo producto, cantidad, precio y usuario registradas
+/// a `window_seconds` o menos de distancia. Solo compara ventas con hora (las
+/// importadas con fecha sola coincidirían todas) y no mira lo archivado. Solo
+/// informa: cada par se resuelve a mano con `resolve_duplicate`.
+#[tauri::command]
+fn find_probable_duplicates<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    window_seconds: i32,
+) -> Result<Vec<DuplicatePair>, String> {
+    // El Auditor también puede revisar los duplicados
+    if !matches!(window_session(&state, window.label()), Some(s) if s.role == ROLE_AUDITOR) {
+        require_admin(&state, &window)?;
+    }
+    if !(1..=3600).contains(&window_seconds) {
+        return Err("La ventana debe estar entre 1 y 3600 segundos".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.id, a.sale_date, b.id, b.sale_date, a.product_id, p.name, a.quantity, a.sale_price, u.username,
+                    ABS(julianday(b.sale_date) - julianday(a.sale_date)) * 86400 AS gap
+             FROM (SELECT * FROM sales WHERE voided_at IS NULL) a
+             JOIN (SELECT * FROM sales WHERE voided_at IS NULL) b ON b.product_id = a.product_id AND b.id > a.id
+                  AND b.quantity = a.quantity AND b.sale_price = a.sale_price
+                  AND b.created_by IS a.created_by
+             LEFT JOIN products p ON p.id = a.product_id
+             LEFT JOIN users u ON u.id = a.created_by
+             WHERE length(a.sale_date) > 10 AND length(b.sale_date) > 10
+               AND ABS(julianday(b.sale_date) - julianday(a.sale_date)) * 86400 <= ?1
+             ORDER BY a.sale_date, a.id, b.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([window_seconds], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, f64>(6)?,
+                row.get::<_, f64>(7)?,
+                row.get::<_, Option<String>>(8)?,
+                row.get::<_, f64>(9)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut pairs = Vec::new();
+    for (first_id, first_date, second_id, second_date, product_id, product_name, quantity, sale_price, created_by, gap) in rows {
+        let first_movement = sale_movement(&conn, first_id, None)?;
+        let second_movement = sale_movement(&conn, second_id, first_movement)?;
+        pairs.push(DuplicatePair {
+            product_id,
+            product_name,
+            quantity,
+            sale_price,
+            created_by,
+            seconds_apart: (gap * 1000.0).round() / 1000.0,
+            first: DuplicateSale { id: first_id, sale_date: first_date, movement_id: first_movement },
+            second: DuplicateSale { id: second_id, sale_date: second_date, movement_id: second_movement },
+        });
+    }
+    Ok(pairs)
+}
+
+/// Elimina la venta `remove_id`, duplicada de `keep_id`, junto con su egreso
+/// de stock, en una transacción y con registro en la auditoría. Los adjuntos
+/// de la venta eliminada pasan a la que se conserva. Rechaza ventas que no
+/// coinciden en producto, cantidad, precio y usuario, y las que tienen saldo a
+/// favor canjeado, plan de cuotas o depósitos devueltos.
+#[tauri::command]
+fn resolve_duplicate<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    keep_id: i64,
+    remove_id: i64,
+) -> Result<(), String> {
+    let session = require_admin(&state, &window)?;
+    if keep_id == remove_id {
+        return Err("La venta a conservar y la duplicada deben ser distintas".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let sale = |id: i64| {
+        conn.query_row(
+            "SELECT product_id, quantity, sale_price, created_by, sale_date FROM sales WHERE id = ?1",
+            [id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Venta #{} no encontrada", id),
+            e => e.to_string(),
+        })
+    };
+    let kept = sale(keep_id)?;
+    let removed = sale(remove_id)?;
+    if (kept.0, kept.1, kept.2, kept.3) != (removed.0, removed.1, removed.2, removed.3) {
+        return Err(format!(
+            "Las ventas #{} y #{} no coinciden en producto, cantidad, precio y usuario",
+            keep_id, remove_id
+        ));
+    }
+    let linked: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM store_credit_redemptions WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM deposit_refunds WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM payment_schedules WHERE sale_id = ?1)
+                 OR EXISTS(SELECT 1 FROM sale_payments WHERE sale_id = ?1)",
+            [remove_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if linked {
+        return Err(format!(
+            "La venta #{} tiene saldo a favor, cuotas, pagos o devoluciones de depósito asociados; corrígela a mano",
+            remove_id
+        ));
+    }
+    let closed_month = check_period_open(&conn, Some(&session), &removed.4)?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        let keep_movement = sale_movement(&conn, keep_id, None)?;
+        let movement = sale_movement(&conn, remove_id, keep_movement)?;
+        if let Some(movement_id) = movement {
+            conn.execute("DELETE FROM stock_movements WHERE id = ?1", [movement_id])
+                .map_err(|e| e.to_string())?;
+        }
+        // Los demás egresos de la venta, si era un pack
+        conn.execute("DELETE FROM stock_movements WHERE sale_id = ?1", [remove_id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM sales WHERE id = ?1", [remove_id])
+            .map_err(|e| e.to_string())?;
+        remove_sale_items(&conn, "id = ?1", [remove_id])?;
+        conn.execute(
+            "UPDATE attachments SET entity_id = ?1 WHERE entity = 'sales' AND entity_id = ?2",
+            [keep_id, remove_id],
+        )
+        .map_err(|e| e.to_string())?;
+        if let Some(day) = removed.4.get(..10).filter(|day| is_valid_date(day)) {
+            refresh_daily_summaries(&conn, Some((day, day))).map_err(|e| e.to_string())?;
+        }
+        audit(
+            &conn,
+            Some(session.user_id),
+            "resolve_duplicate",
+            "sales",
+            Some(remove_id),
+            &format!(
+                "Venta #{} eliminada por duplicar la #{} ({}). Movimiento: {}",
+                remove_id,
+                keep_id,
+                removed.4,
+                movement.map(|m| format!("#{}", m)).unwrap_or_else(|| "ninguno".to_string())
+            ),
+            None,
+        )?;
+        if let Some(month) = closed_month {
+            audit_post_close(&conn, Some(&session), "resolve_duplicate", "sales", remove_id, &month)?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "sales", Some(remove_id));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// PLANES DE PAGO EN CUOTAS
+// ============================================
+
+/// Diferencia admitida entre la suma de las cuotas y el total (redondeo).
+const INSTALLMENT_TOLERANCE: f64 = 0.01;
+
+/// Valida el plan de una venta: fechas válidas no anteriores a la venta,
+/// importes positivos y suma igual al total.
+fn validate_installments(installments: &[InstallmentInput], total: f64, sale_date: &str) -> Result<(), String> {
+    if installments.is_empty() {
+        return Err("El plan de pagos no tiene cuotas".to_string());
+    }
+    let sale_day = sale_date.get(..10).unwrap_or(sale_date);
+    for installment in installments {
+        if !is_valid_date(&installment.due_date) {
+            return Err(format!("Vencimiento de cuota inválido: {}. Usa el formato AAAA-MM-DD.", installment.due_date));
+        }
+        if installment.due_date.as_str() < sale_day {
+            return Err(format!("La cuota del {} vence antes de la venta", installment.due_date));
+        }
+        if !installment.amount.is_finite() || installment.amount <= 0.0 {
+            return Err("El importe de cada cuota debe ser mayor a 0".to_string());
+        }
+    }
+    let sum: f64 = installments.iter().map(|i| i.amount).sum();
+    if (sum - total).abs() > INSTALLMENT_TOLERANCE {
+        return Err(format!("Las cuotas suman {:.2} y el total de la venta es {:.2}", sum, total));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Installment {
+    id: i64,
+    sale_id: i64,
+    customer: Option<String>,
+    product_name: Option<String>,
+    due_date: String,
+    amount: f64,
+    paid_amount: f64,
+    pending: f64,
+    paid_at: Option<String>,
+    overdue: bool,
+}
+
+/// Columnas para `installment_from_row`; `?1` es la fecha de hoy.
+fn installment_select() -> String {
+    format!(
+        "SELECT c.id, c.sale_id, s.customer, p.name, c.due_date, c.amount, c.paid_amount, c.paid_at,
+                c.paid_amount < c.amount - {tol} AND c.due_date < ?1
+         FROM payment_schedules c
+         LEFT JOIN {sales} s ON s.id = c.sale_id
+         LEFT JOIN products p ON p.id = s.product_id",
+        tol = INSTALLMENT_TOLERANCE,
+        sales = source_table("sales", true)
+    )
+}
+
+fn installment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Installment> {
+    let amount: f64 = row.get(5)?;
+    let paid_amount: f64 = row.get(6)?;
+    Ok(Installment {
+        id: row.get(0)?,
+        sale_id: row.get(1)?,
+        customer: row.get(2)?,
+        product_name: row.get(3)?,
+        due_date: row.get(4)?,
+        amount,
+        paid_amount,
+        pending: ((amount - paid_amount) * 100.0).round() / 100.0,
+        paid_at: row.get(7)?,
+        overdue: row.get(8)?,
+    })
+}
+
+/// Cuotas impagas que vencen dentro de `days` días (7 si no se indica) más
+/// todas las vencidas, de la más antigua a la más nueva.
+#[tauri::command]
+fn get_upcoming_installments(state: State<AppState>, days: Option<i32>) -> Result<Vec<Installment>, String> {
+    let days = days.unwrap_or(7);
+    if days < 0 {
+        return Err("Los días deben ser 0 o más".to_string());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let today = today_key(&conn)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE c.paid_amount < c.amount - {} AND c.due_date <= date(?1, ?2)
+             ORDER BY c.due_date, c.id",
+            installment_select(),
+            INSTALLMENT_TOLERANCE
+        ))
+        .map_err(|e| e.to_string())?;
+    let installments = stmt
+        .query_map(rusqlite::params![today, format!("+{} days", days)], installment_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(installments)
+}
+
+/// Plan de pagos completo de una venta (vacío si se cobró de contado).
+#[tauri::command]
+fn get_payment_schedule(state: State<AppState>, sale_id: i64) -> Result<Vec<Installment>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let today = today_key(&conn)?;
+    let mut stmt = conn
+        .prepare(&format!("{} WHERE c.sale_id = ?2 ORDER BY c.due_date, c.id", installment_select()))
+        .map_err(|e| e.to_string())?;
+    let installments = stmt
+        .query_map(rusqlite::params![today, sale_id], installment_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(installments)
+}
+
+/// Registra un pago de la venta `sale_id`. Sin `installment_id` se aplica a
+/// la cuota abierta más antigua y lo que sobra pasa a las siguientes; con
+/// `installment_id`, solo a esa cuota. No admite pagar más que lo pendiente.
+/// Devuelve el plan actualizado.
+#[tauri::command]
+fn register_installment_payment<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    sale_id: i64,
+    amount: f64,
+    installment_id: Option<i64>,
+) -> Result<Vec<Installment>, String> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err("El pago debe ser mayor a 0".to_string());
+    }
+    let user_id = acting_user(&state, &window);
+    {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, amount - paid_amount FROM payment_schedules
+                 WHERE sale_id = ?1 AND (?2 IS NULL OR id = ?2) AND paid_amount < amount - ?3
+                 ORDER BY due_date, id",
+            )
+            .map_err(|e| e.to_string())?;
+        let open = stmt
+            .query_map(rusqlite::params![sale_id, installment_id, INSTALLMENT_TOLERANCE], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        drop(stmt);
+        if open.is_empty() {
+            return Err(match installment_id {
+                Some(id) => format!("La cuota #{} no existe o ya está pagada", id),
+                None => format!("La venta #{} no tiene cuotas pendientes", sale_id),
+            });
+        }
+        let pending: f64 = open.iter().map(|(_, p)| p).sum();
+        if amount > pending + INSTALLMENT_TOLERANCE {
+            return Err(format!("El pago ({:.2}) supera lo pendiente ({:.2})", amount, pending));
+        }
+
+        conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+            .map_err(|e| e.to_string())?;
+        let result: Result<(), String> = (|| {
+            let mut left = amount;
+            let mut applied = Vec::new();
+            for (id, pending) in open {
+                if left <= INSTALLMENT_TOLERANCE {
+                    break;
+                }
+                let payment = left.min(pending);
+                conn.execute(
+                    "UPDATE payment_schedules
+                     SET paid_amount = paid_amount + ?2,
+                         paid_at = CASE WHEN paid_amount + ?2 >= amount - ?3 THEN CURRENT_TIMESTAMP END
+                     WHERE id = ?1",
+                    rusqlite::params![id, payment, INSTALLMENT_TOLERANCE],
+                )
+                .map_err(|e| e.to_string())?;
+                applied.push(format!("cuota #{}: {:.2}", id, payment));
+                left -= payment;
+            }
+            audit(
+                &conn,
+                user_id,
+                "installment_payment",
+                "sales",
+                Some(sale_id),
+                &format!("Pago de {:.2} ({})", amount, applied.join(", ")),
+                None,
+            )
+        })();
+        match result {
+            Ok(()) => {
+                conn.execute("COMMIT", [])
+                    .map_err(|e| e.to_string())?;
+            }
+            Err(err) => {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(err);
+            }
+        }
+    }
+    emit_data_changed(&window, "payment_schedules", Some(sale_id));
+    get_payment_schedule(state, sale_id)
+}
+
+/// Tarea diaria: avisa una sola vez por cada cuota que quedó vencida e impaga.
+fn run_overdue_installments_check(conn: &Connection) -> Result<usize, String> {
+    let today = today_key(conn)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE c.paid_amount < c.amount - {} AND c.due_date < ?1",
+            installment_select(),
+            INSTALLMENT_TOLERANCE
+        ))
+        .map_err(|e| e.to_string())?;
+    let overdue = stmt
+        .query_map([&today], installment_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut created = 0;
+    for installment in overdue {
+        let title = format!("Cuota vencida: venta #{}", installment.sale_id);
+        let message = format!(
+            "{} debe {:.2} de la cuota que venció el {} ({}).",
+            installment.customer.as_deref().unwrap_or("El cliente"),
+            installment.pending,
+            installment.due_date,
+            installment.product_name.as_deref().unwrap_or("producto eliminado")
+        );
+        let dedupe_key = format!("installment_overdue:{}", installment.id);
+        if notify(conn, "installment_overdue", &title, &message, None, &dedupe_key)? {
+            created += 1;
+        }
+    }
+    Ok(created)
+}
+
+// ============================================
+// VENTAS AL CRÉDITO
+// ============================================
+
+/// Días de plazo de una venta al crédito cuando no está configurado
+/// `credit_days`; pasado el plazo el saldo cuenta como vencido.
+const DEFAULT_CREDIT_DAYS: f64 = 30.0;
+
+/// Valida una venta al crédito y devuelve su pago inicial: necesita el
+/// cliente, no lleva plan de cuotas y no se puede adelantar más que el total.
+fn validate_credit_sale(sale: &Sale) -> Result<f64, String> {
+    if sale.customer_id.is_none() && sale.customer.as_deref().map(str::trim).unwrap_or("").is_empty() {
+        return Err("Una venta al crédito necesita el cliente".to_string());
+    }
+    if sale.installments.is_some() {
+        return Err("Una venta al crédito no lleva plan de cuotas".to_string());
+    }
+    let total = sale.total()?;
+    let down_payment = sale.amount_paid.unwrap_or(0.0);
+    if !down_payment.is_finite() || down_payment < 0.0 || down_payment > total + 0.005 {
+        return Err(format!(
+            "El pago inicial debe estar entre 0 y el total de la venta ({:.2})",
+            total
+        ));
+    }
+    Ok(round_money(down_payment))
+}
+
+#[derive(Debug, Serialize)]
+struct SalePayment {
+    id: i64,
+    sale_id: i64,
+    amount: f64,
+    payment_date: String,
+    payment_method: String,
+    created_by: Option<i32>,
+    created_at: Option<String>,
+}
+
+/// Saldo pendiente de un cliente en sus ventas al crédito.
+#[derive(Debug, Serialize)]
+struct Receivable {
+    customer: String,
+    sales_count: i64,
+    total: f64,
+    amount_paid: f64,
+    outstanding: f64,
+    /// Venta impaga más antigua (AAAA-MM-DD)
+    oldest_sale_date: String,
+    /// Días que pasaron del plazo `credit_days` desde la venta impaga más
+    /// antigua; 0 si aún no vence
+    days_overdue: i64,
+}
+
+/// Registra un pago de una venta al crédito (hoy si no viene `payment_date`)
+/// y actualiza `amount_paid`. No admite pagar más que lo pendiente. Devuelve
+/// los pagos de la venta.
+#[tauri::command]
+fn register_sale_payment<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    sale_id: i64,
+    amount: f64,
+    payment_date: Option<String>,
+    payment_method: Option<String>,
+) -> Result<Vec<SalePayment>, String> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err("El pago debe ser mayor a 0".to_string());
+    }
+    let user_id = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        let date = match payment_date.as_deref().map(str::trim) {
+            Some(date) if is_valid_date(date) => date.to_string(),
+            Some(_) => return Err("Fecha inválida. Usa el formato AAAA-MM-DD.".to_string()),
+            None => today_key(&conn)?,
+        };
+        let method = sale_payment_method(&conn, payment_method.as_deref())?;
+        let (is_credit, voided, total, paid): (bool, bool, f64, f64) = conn
+            .query_row(
+                "SELECT is_credit, voided_at IS NOT NULL, sale_price, COALESCE(amount_paid, 0) FROM sales WHERE id = ?1",
+                [sale_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => format!("Venta #{} no encontrada", sale_id),
+                e => e.to_string(),
+            })?;
+        if !is_credit {
+            return Err(format!("La venta #{} no es al crédito", sale_id));
+        }
+        if voided {
+            return Err(format!("La venta #{} está anulada", sale_id));
+        }
+        let pending = round_money(total - paid);
+        if amount > pending + 0.005 {
+            return Err(format!("El pago ({:.2}) supera lo pendiente ({:.2})", amount, pending));
+        }
+
+        conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+            .map_err(|e| e.to_string())?;
+        let result: Result<(), String> = (|| {
+            let closed_month = check_period_open(&conn, session.as_ref(), &date)?;
+            conn.execute(
+                "INSERT INTO sale_payments (sale_id, amount, payment_date, payment_method, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![sale_id, round_money(amount), date, method, user_id],
+            )
+            .map_err(|e| e.to_string())?;
+            let payment_id = conn.last_insert_rowid();
+            conn.execute(
+                "UPDATE sales SET amount_paid = ROUND(COALESCE(amount_paid, 0) + ?2, 2) WHERE id = ?1",
+                rusqlite::params![sale_id, round_money(amount)],
+            )
+            .map_err(|e| e.to_string())?;
+            audit(
+                &conn,
+                user_id,
+                "sale_payment",
+                "sales",
+                Some(sale_id),
+                &format!("Pago de {:.2} en {}; pendiente {:.2}", amount, method, pending - amount),
+                None,
+            )?;
+            if let Some(month) = closed_month {
+                audit_post_close(&conn, session.as_ref(), "register_sale_payment", "sale_payments", payment_id, &month)?;
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => {
+                conn.execute("COMMIT", [])
+                    .map_err(|e| e.to_string())?;
+            }
+            Err(err) => {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(err);
+            }
+        }
+    }
+    emit_data_changed(&window, "sale_payments", Some(sale_id));
+    get_sale_payments(state, sale_id)
+}
+
+/// Pagos de una venta al crédito, del más antiguo al más nuevo.
+#[tauri::command]
+fn get_sale_payments(state: State<AppState>, sale_id: i64) -> Result<Vec<SalePayment>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, sale_id, amount, payment_date, payment_method, created_by, created_at
+             FROM sale_payments WHERE sale_id = ?1 ORDER BY payment_date, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let payments = stmt
+        .query_map([sale_id], |row| {
+            Ok(SalePayment {
+                id: row.get(0)?,
+                sale_id: row.get(1)?,
+                amount: row.get(2)?,
+                payment_date: row.get(3)?,
+                payment_method: row.get(4)?,
+                created_by: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(payments)
+}
+
+/// Clientes con saldo pendiente en ventas al crédito (incluidas las
+/// archivadas), del que más debe al que menos.
+#[tauri::command]
+fn get_pending_receivables(state: State<AppState>) -> Result<Vec<Receivable>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let credit_days = setting_f64(&conn, "credit_days", DEFAULT_CREDIT_DAYS).max(0.0) as i64;
+    let today = today_key(&conn)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT MIN(TRIM(customer)), COUNT(*), SUM(sale_price), SUM(COALESCE(amount_paid, 0)),
+                    MIN(substr(sale_date,1,10)),
+                    CAST(julianday(?1) - julianday(MIN(substr(sale_date,1,10))) AS INTEGER)
+             FROM {}
+             WHERE is_credit = 1 AND sale_price - COALESCE(amount_paid, 0) > 0.005
+             GROUP BY TRIM(customer) COLLATE NOCASE
+             ORDER BY SUM(sale_price - COALESCE(amount_paid, 0)) DESC, 1",
+            valid_sales(true)
+        ))
+        .map_err(|e| e.to_string())?;
+    let receivables = stmt
+        .query_map([&today], |row| {
+            let total: f64 = row.get(2)?;
+            let amount_paid: f64 = row.get(3)?;
+            let age: i64 = row.get(5)?;
+            Ok(Receivable {
+                customer: row.get(0)?,
+                sales_count: row.get(1)?,
+                total: round_money(total),
+                amount_paid: round_money(amount_paid),
+                outstanding: round_money(total - amount_paid),
+                oldest_sale_date: row.get(4)?,
+                days_overdue: (age - credit_days).max(0),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(receivables)
+}
+
+// ============================================
+// CLIENTES
+// ============================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Customer {
+    id: Option<i64>,
+    name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    /// DNI o RUC; no se repite entre clientes
+    document_id: Option<String>,
+    notes: Option<String>,
+    /// Un cliente con ventas no se borra: queda desactivado
+    #[serde(default = "customer_active")]
+    active: bool,
+    /// Ventas registradas a su nombre, incluidas las archivadas (solo lectura)
+    #[serde(default)]
+    sales_count: i64,
+}
+
+fn customer_active() -> bool {
+    true
+}
+
+/// Texto recortado; vacío es `None`.
+fn customer_field(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// Cliente con los campos recortados; rechaza nombre vacío, correo sin @ y
+/// documentos que ya tiene otro cliente.
+fn validate_customer(conn: &Connection, customer: &Customer) -> Result<Customer, String> {
+    let name = customer.name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err("El cliente necesita un nombre".to_string());
+    }
+    let email = customer_field(&customer.email);
+    if email.as_deref().is_some_and(|e| !e.contains('@')) {
+        return Err(format!("Correo inválido: {}", email.unwrap_or_default()));
+    }
+    let document_id = customer_field(&customer.document_id);
+    if let Some(ref document) = document_id {
+        let taken: Option<String> = conn
+            .query_row(
+                "SELECT name FROM customers WHERE document_id = ?1 AND id IS NOT ?2",
+                rusqlite::params![document, customer.id],
+                |row| row.get(0),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e.to_string()),
+            })?;
+        if let Some(existing) = taken {
+            return Err(format!("El documento {} ya es del cliente {}", document, existing));
+        }
+    }
+    Ok(Customer {
+        id: customer.id,
+        name,
+        phone: customer_field(&customer.phone),
+        email,
+        document_id,
+        notes: customer_field(&customer.notes),
+        active: customer.active,
+        sales_count: 0,
+    })
+}
+
+const CUSTOMER_COLUMNS: &str = "c.id, c.name, c.phone, c.email, c.document_id, c.notes, c.active";
+
+fn customer_from_row(row: &rusqlite::Row) -> rusqlite::Result<Customer> {
+    Ok(Customer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        phone: row.get(2)?,
+        email: row.get(3)?,
+        document_id: row.get(4)?,
+        notes: row.get(5)?,
+        active: row.get(6)?,
+        sales_count: 0,
+    })
+}
+
+/// Nombre con que se guarda el cliente de una venta: el del cliente
+/// registrado si viene `customer_id` (que debe estar activo salvo que ya
+/// fuera el de la venta), o el texto libre tal cual.
+fn sale_customer(
+    conn: &Connection,
+    customer_id: Option<i64>,
+    customer: Option<&str>,
+    allow_inactive: bool,
+) -> Result<Option<String>, String> {
+    let Some(id) = customer_id else {
+        return Ok(customer.map(str::to_string));
+    };
+    let (name, active): (String, bool) = conn
+        .query_row("SELECT name, active FROM customers WHERE id = ?1", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Cliente #{} no encontrado", id),
+            e => e.to_string(),
+        })?;
+    if !active && !allow_inactive {
+        return Err(format!("El cliente {} está desactivado", name));
+    }
+    Ok(Some(name))
+}
+
+/// Clientes por nombre con su cantidad de ventas; los desactivados solo con
+/// `include_inactive`.
+#[tauri::command]
+fn get_customers(state: State<AppState>, include_inactive: Option<bool>) -> Result<Vec<Customer>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, (SELECT COUNT(*) FROM {} s WHERE s.customer_id = c.id)
+             FROM customers c
+             WHERE ?1 OR c.active = 1
+             ORDER BY c.name COLLATE NOCASE, c.id",
+            CUSTOMER_COLUMNS,
+            source_table("sales", true)
+        ))
+        .map_err(|e| e.to_string())?;
+    let customers = stmt
+        .query_map([include_inactive.unwrap_or(false)], |row| {
+            Ok(Customer { sales_count: row.get(7)?, ..customer_from_row(row)? })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(customers)
+}
+
+/// Autocompletado de la pantalla de ventas: clientes activos cuyo nombre,
+/// documento, teléfono o correo contiene `query`, hasta 20.
+#[tauri::command]
+fn search_customers(state: State<AppState>, query: String) -> Result<Vec<Customer>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    // % y _ escritos por el usuario se buscan literalmente
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}
+             FROM customers c
+             WHERE c.active = 1
+               AND (c.name LIKE ?1 ESCAPE '\\' OR c.document_id LIKE ?1 ESCAPE '\\'
+                    OR c.phone LIKE ?1 ESCAPE '\\' OR c.email LIKE ?1 ESCAPE '\\')
+             ORDER BY c.name LIKE ?2 ESCAPE '\\' DESC, c.name COLLATE NOCASE, c.id
+             LIMIT 20",
+            CUSTOMER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let customers = stmt
+        .query_map(rusqlite::params![format!("%{}%", escaped), format!("{}%", escaped)], customer_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(customers)
+}
+
+#[tauri::command]
+fn add_customer<R: Runtime>(window: Window<R>, state: State<AppState>, customer: Customer) -> Result<i64, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let customer = validate_customer(&conn, &Customer { id: None, ..customer })?;
+    conn.execute(
+        "INSERT INTO customers (name, phone, email, document_id, notes, active) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![customer.name, customer.phone, customer.email, customer.document_id, customer.notes, customer.active],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    emit_data_changed(&window, "customers", Some(id));
+    Ok(id)
+}
+
+/// Cambiar el nombre actualiza también el texto `customer` de sus ventas, y
+/// con `active` se reactiva un cliente desactivado.
+#[tauri::command]
+fn update_customer<R: Runtime>(window: Window<R>, state: State<AppState>, customer: Customer) -> Result<(), String> {
+    let id = customer.id.ok_or("Falta el id del cliente")?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let customer = validate_customer(&conn, &customer)?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        let updated = conn
+            .execute(
+                "UPDATE customers
+                 SET name = ?2, phone = ?3, email = ?4, document_id = ?5, notes = ?6, active = ?7,
+                     updated_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1",
+                rusqlite::params![
+                    id,
+                    customer.name,
+                    customer.phone,
+                    customer.email,
+                    customer.document_id,
+                    customer.notes,
+                    customer.active,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err("Cliente no encontrado".to_string());
+        }
+        conn.execute(
+            "UPDATE sales SET customer = ?2 WHERE customer_id = ?1 AND customer IS NOT ?2",
+            rusqlite::params![id, customer.name],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "customers", Some(id));
+            Ok(())
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Borra un cliente sin ventas. Si tiene ventas (aunque estén archivadas) lo
+/// desactiva para que sigan apuntando a él; devuelve `true` solo si se borró.
+#[tauri::command]
+fn delete_customer<R: Runtime>(window: Window<R>, state: State<AppState>, id: i64) -> Result<bool, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let sales: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT (SELECT COUNT(*) FROM {} WHERE customer_id = ?1) FROM customers WHERE id = ?1",
+                source_table("sales", true)
+            ),
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Cliente no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if sales > 0 {
+        conn.execute(
+            "UPDATE customers SET active = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+    } else {
+        // Sus cotizaciones conservan el nombre
+        conn.execute("UPDATE quotes SET customer_id = NULL WHERE customer_id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM customers WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+    }
+    emit_data_changed(&window, "customers", Some(id));
+    Ok(sales == 0)
+}
+
+/// Resumen de `get_customer_history` sobre todas las ventas válidas del
+/// cliente, incluidas las archivadas.
+#[derive(Debug, Serialize)]
+struct CustomerSummary {
+    lifetime_revenue: f64,
+    /// Comprobantes distintos; un comprobante de varias líneas es una compra
+    purchases: i64,
+    last_purchase_date: Option<String>,
+    /// Categoría de la que compró más veces (a igualdad, la de más importe)
+    favorite_category: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomerHistory {
+    customer_id: i64,
+    name: String,
+    summary: CustomerSummary,
+    sales: SalePage<SaleDetail>,
+}
+
+/// Compras de un cliente registrado, de la más reciente a la más antigua
+/// (50 por página), con el resumen de todo su historial. Las ventas anuladas
+/// no cuentan.
+#[tauri::command]
+fn get_customer_history(
+    state: State<AppState>,
+    customer_id: i64,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<CustomerHistory, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let lim = limit.unwrap_or(50).max(1);
+    let off = offset.unwrap_or(0).max(0);
+    let sales_source = valid_sales(true);
+    let (name, total_count, summary) = conn
+        .query_row(
+            &format!(
+                "SELECT c.name, COUNT(s.id),
+                        COUNT(DISTINCT COALESCE((SELECT i.sale_id FROM sale_items i WHERE i.id = s.id), -s.id)),
+                        COALESCE(SUM(s.sale_price), 0), MAX(substr(s.sale_date,1,10)),
+                        (SELECT p.category FROM {src} f JOIN products p ON p.id = f.product_id
+                         WHERE f.customer_id = c.id AND p.category IS NOT NULL
+                         GROUP BY p.category
+                         ORDER BY COUNT(*) DESC, SUM(f.sale_price) DESC, p.category
+                         LIMIT 1)
+                 FROM customers c
+                 LEFT JOIN {src} s ON s.customer_id = c.id
+                 WHERE c.id = ?1
+                 GROUP BY c.id",
+                src = sales_source
+            ),
+            [customer_id],
+            |row| {
+                let revenue: f64 = row.get(3)?;
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    CustomerSummary {
+                        lifetime_revenue: round_money(revenue),
+                        purchases: row.get(2)?,
+                        last_purchase_date: row.get(4)?,
+                        favorite_category: row.get(5)?,
+                    },
+                ))
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Cliente no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}
+             FROM {} s
+             LEFT JOIN products p ON p.id = s.product_id
+             LEFT JOIN users u ON u.id = s.created_by
+             WHERE s.customer_id = ?1
+             ORDER BY s.sale_date DESC, s.id DESC
+             LIMIT ?2 OFFSET ?3",
+            SALE_DETAIL_COLUMNS, sales_source
+        ))
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map(rusqlite::params![customer_id, lim, off], sale_detail_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(CustomerHistory {
+        customer_id,
+        name,
+        summary,
+        sales: SalePage { items, total_count },
+    })
+}
+
+// ============================================
+// COTIZACIONES
+// ============================================
+
+/// Serie con que se numeran las cotizaciones a partir de su id (COT-000001).
+const QUOTE_SERIES: &str = "COT";
+
+/// Estados que se eligen al guardar una cotización; `QUOTE_CONVERTED` solo lo
+/// pone `convert_quote_to_sale`.
+const QUOTE_STATUSES: [&str; 4] = ["borrador", "enviada", "aceptada", "vencida"];
+const QUOTE_CONVERTED: &str = "convertida";
+const QUOTE_EXPIRED: &str = "vencida";
+
+/// Días de validez de una cotización que no indica `valid_until`.
+const QUOTE_VALIDITY_DAYS: i64 = 15;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QuoteItem {
+    product_id: i32,
+    sku: Option<String>,
+    product_name: String,
+    quantity: f64,
+    unit_price: f64,
+    discount: Option<f64>,
+    line_total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Quote {
+    id: i64,
+    /// `QUOTE_SERIES` y el id (COT-000001)
+    quote_number: String,
+    customer: Option<String>,
+    customer_id: Option<i64>,
+    channel: Option<String>,
+    /// Lista de precios de las líneas; la venta la conserva como referencia
+    price_list_id: Option<i64>,
+    /// Uno de `QUOTE_STATUSES` o `QUOTE_CONVERTED`; pasada `valid_until` es
+    /// `vencida` aunque se haya guardado con otro estado
+    status: String,
+    valid_until: String,
+    note: Option<String>,
+    total: f64,
+    /// Comprobante (`sale_headers`) creado al convertirla
+    sale_id: Option<i64>,
+    receipt_number: Option<String>,
+    created_by: Option<i32>,
+    created_at: String,
+    items: Vec<QuoteItem>,
+}
+
+/// Cotización tal como la envía el frontend; las líneas son las de
+/// `create_sale`.
+#[derive(Debug, Serialize, Deserialize)]
+struct NewQuote {
+    id: Option<i64>,
+    customer: Option<String>,
+    /// Cliente registrado; su nombre reemplaza a `customer`
+    customer_id: Option<i64>,
+    channel: Option<String>,
+    /// Lista de precios de las líneas sin `unit_price`
+    price_list_id: Option<i64>,
+    /// `borrador` si no viene
+    status: Option<String>,
+    /// AAAA-MM-DD; sin fecha vale `QUOTE_VALIDITY_DAYS` días desde hoy
+    valid_until: Option<String>,
+    note: Option<String>,
+    created_by: Option<i32>,
+    items: Vec<NewSaleItem>,
+}
+
+/// Columnas de `Quote` sobre `quotes q`, con el estado vencido calculado.
+fn quote_columns() -> String {
+    format!(
+        "q.id, q.customer, q.customer_id, q.channel,
+         CASE WHEN q.status <> '{converted}' AND q.valid_until < date('now','localtime') THEN '{expired}' ELSE q.status END AS status,
+         q.valid_until, q.note, q.total, q.sale_id,
+         (SELECT h.receipt_number FROM sale_headers h WHERE h.id = q.sale_id), q.created_by, q.created_at, q.price_list_id",
+        converted = QUOTE_CONVERTED,
+        expired = QUOTE_EXPIRED
+    )
+}
+
+fn quote_from_row(row: &rusqlite::Row) -> rusqlite::Result<Quote> {
+    let id: i64 = row.get(0)?;
+    Ok(Quote {
+        id,
+        quote_number: format_receipt_number(QUOTE_SERIES, id),
+        customer: row.get(1)?,
+        customer_id: row.get(2)?,
+        channel: row.get(3)?,
+        status: row.get(4)?,
+        valid_until: row.get(5)?,
+        note: row.get(6)?,
+        total: row.get(7)?,
+        sale_id: row.get(8)?,
+        receipt_number: row.get(9)?,
+        created_by: row.get(10)?,
+        created_at: row.get(11)?,
+        price_list_id: row.get(12)?,
+        items: Vec::new(),
+    })
+}
+
+fn load_quote_items(conn: &Connection, quote_id: i64) -> Result<Vec<QuoteItem>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.product_id, p.sku, p.name, i.quantity, i.unit_price, i.discount, i.line_total
+             FROM quote_items i JOIN products p ON p.id = i.product_id
+             WHERE i.quote_id = ?1 ORDER BY i.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map([quote_id], |row| {
+            Ok(QuoteItem {
+                product_id: row.get(0)?,
+                sku: row.get(1)?,
+                product_name: row.get(2)?,
+                quantity: row.get(3)?,
+                unit_price: row.get(4)?,
+                discount: row.get(5)?,
+                line_total: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(items)
+}
+
+fn load_quote(conn: &Connection, quote_id: i64) -> Result<Quote, String> {
+    let mut quote = conn
+        .query_row(&format!("SELECT {} FROM quotes q WHERE q.id = ?1", quote_columns()), [quote_id], quote_from_row)
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Cotización #{} no encontrada", quote_id),
+            e => e.to_string(),
+        })?;
+    quote.items = load_quote_items(conn, quote_id)?;
+    Ok(quote)
+}
+
+/// Estado de una cotización en minúsculas; sin estado es `borrador`.
+fn quote_status(status: Option<&str>) -> Result<String, String> {
+    let status = status.map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty());
+    match status {
+        None => Ok(QUOTE_STATUSES[0].to_string()),
+        Some(s) if QUOTE_STATUSES.contains(&s.as_str()) => Ok(s),
+        Some(s) => Err(format!(
+            "Estado de cotización '{}' no válido. Usa {}",
+            s,
+            QUOTE_STATUSES.join(", ")
+        )),
+    }
+}
+
+/// Guarda la cotización y reemplaza sus líneas, con los precios y el
+/// redondeo de `create_sale`. No revisa ni mueve stock. Debe correr en la
+/// transacción de quien la llama; una cotización convertida no se edita.
+fn save_quote(conn: &Connection, quote: &NewQuote, created_by: Option<i32>) -> Result<i64, String> {
+    if quote.items.is_empty() {
+        return Err("La cotización no tiene productos".to_string());
+    }
+    let status = quote_status(quote.status.as_deref())?;
+    let valid_until = match quote.valid_until.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(date) if is_valid_date(date) => date.to_string(),
+        Some(date) => return Err(format!("Fecha de validez inválida: '{}'. Usa AAAA-MM-DD", date)),
+        None => conn
+            .query_row("SELECT date('now','localtime',?1)", [format!("+{} days", QUOTE_VALIDITY_DAYS)], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    let old_customer_id = match quote.id {
+        Some(id) => {
+            let (sale_id, customer_id): (Option<i64>, Option<i64>) = conn
+                .query_row("SELECT sale_id, customer_id FROM quotes WHERE id = ?1", [id], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => format!("Cotización #{} no encontrada", id),
+                    e => e.to_string(),
+                })?;
+            if sale_id.is_some() {
+                return Err(format!(
+                    "La cotización {} ya se convirtió en venta y no se puede editar",
+                    format_receipt_number(QUOTE_SERIES, id)
+                ));
+            }
+            customer_id
+        }
+        None => None,
+    };
+    // Al editar se conserva el cliente aunque lo hayan desactivado
+    let customer = sale_customer(
+        conn,
+        quote.customer_id,
+        quote.customer.as_deref(),
+        quote.id.is_some() && quote.customer_id == old_customer_id,
+    )?;
+    let note = customer_field(&quote.note);
+
+    let id = match quote.id {
+        Some(id) => {
+            conn.execute(
+                "UPDATE quotes SET customer = ?2, customer_id = ?3, channel = ?4, status = ?5, valid_until = ?6,
+                                  note = ?7, price_list_id = ?8, updated_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1",
+                rusqlite::params![id, customer, quote.customer_id, quote.channel, status, valid_until, note, quote.price_list_id],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute("DELETE FROM quote_items WHERE quote_id = ?1", [id])
+                .map_err(|e| e.to_string())?;
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO quotes (customer, customer_id, channel, status, valid_until, note, created_by, price_list_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![customer, quote.customer_id, quote.channel, status, valid_until, note, created_by, quote.price_list_id],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.last_insert_rowid()
+        }
+    };
+    let rule = RoundingRule::load(conn);
+    for item in &quote.items {
+        validate_quantity(conn, item.product_id, item.quantity)?;
+        validate_discount(item.discount)?;
+        let unit_price = item_unit_price(conn, item, quote.price_list_id)?;
+        let line_total = from_cents(line_total_cents(&rule, unit_price, item.quantity, item.discount.unwrap_or(0.0)));
+        conn.execute(
+            "INSERT INTO quote_items (quote_id, product_id, quantity, unit_price, discount, line_total)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![id, item.product_id, item.quantity, unit_price, item.discount, line_total],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.execute(
+        "UPDATE quotes SET total = (SELECT ROUND(SUM(line_total), 2) FROM quote_items WHERE quote_id = ?1) WHERE id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// `create_quote` y `update_quote`: guarda en una transacción y devuelve la
+/// cotización como queda.
+fn store_quote<R: Runtime>(window: &Window<R>, state: &AppState, quote: &NewQuote) -> Result<Quote, String> {
+    let created_by = acting_user(state, window);
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result = save_quote(&conn, quote, created_by).and_then(|id| load_quote(&conn, id));
+    match result {
+        Ok(quote) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            emit_data_changed(window, "quotes", Some(quote.id));
+            Ok(quote)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Crea una cotización (proforma) para un cliente; no reserva stock.
+#[tauri::command]
+fn create_quote<R: Runtime>(window: Window<R>, state: State<AppState>, quote: NewQuote) -> Result<Quote, String> {
+    store_quote(&window, &state, &NewQuote { id: None, ..quote })
+}
+
+/// Edita una cotización sin convertir: datos, estado, validez y líneas. Una
+/// vencida se reactiva con una nueva `valid_until` y otro estado.
+#[tauri::command]
+fn update_quote<R: Runtime>(window: Window<R>, state: State<AppState>, quote: NewQuote) -> Result<Quote, String> {
+    if quote.id.is_none() {
+        return Err("Falta el id de la cotización".to_string());
+    }
+    store_quote(&window, &state, &quote)
+}
+
+/// Cotizaciones de la más reciente a la más antigua con sus líneas,
+/// filtradas por estado (las vencidas por fecha cuentan como `vencida`) y
+/// cliente registrado.
+#[tauri::command]
+fn get_quotes(state: State<AppState>, status: Option<String>, customer_id: Option<i64>) -> Result<Vec<Quote>, String> {
+    let status = status.map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT * FROM (SELECT {} FROM quotes q) WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR customer_id = ?2)
+             ORDER BY id DESC",
+            quote_columns()
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut quotes = stmt
+        .query_map(rusqlite::params![status, customer_id], quote_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    for quote in &mut quotes {
+        quote.items = load_quote_items(&conn, quote.id)?;
+    }
+    Ok(quotes)
+}
+
+/// Vende una cotización con sus precios, como `create_sale` (revisa el stock
+/// y registra los egresos) con la fecha y hora actuales, y la marca
+/// `convertida` en la misma transacción. Una cotización vencida debe
+/// reactivarse con `update_quote` antes de venderla. Los precios cotizados
+/// distintos de los de lista necesitan `price_override` de un Administrador.
+#[tauri::command]
+fn convert_quote_to_sale<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    quote_id: i64,
+    payment_method: Option<String>,
+    price_override: Option<bool>,
+) -> Result<SaleReceipt, String> {
+    let created_by = acting_user(&state, &window);
+    let session = window_session(&state, window.label());
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    let result: Result<SaleReceipt, String> = (|| {
+        let quote = load_quote(&conn, quote_id)?;
+        if quote.status == QUOTE_CONVERTED {
+            return Err(format!(
+                "La cotización {} ya se convirtió en la venta {}",
+                quote.quote_number,
+                quote.receipt_number.unwrap_or_default()
+            ));
+        }
+        if quote.status == QUOTE_EXPIRED {
+            return Err(format!(
+                "La cotización {} está vencida (válida hasta el {}); reactívala antes de venderla",
+                quote.quote_number, quote.valid_until
+            ));
+        }
+        let sale_date: String = conn
+            .query_row(&format!("SELECT strftime('{}', 'now')", SALE_DATETIME_FORMAT), [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let sale = NewSale {
+            customer: quote.customer,
+            customer_id: quote.customer_id,
+            channel: quote.channel,
+            sale_date,
+            created_by,
+            price_list_id: quote.price_list_id,
+            receipt_series: None,
+            payment_method,
+            is_credit: false,
+            note: Some(format!("Cotización {}", quote.quote_number)),
+            price_override: price_override.unwrap_or(false),
+            items: quote
+                .items
+                .iter()
+                .map(|item| NewSaleItem {
+                    product_id: item.product_id,
+                    quantity: item.quantity,
+                    unit_price: Some(item.unit_price),
+                    discount: item.discount,
+                })
+                .collect(),
+        };
+        let receipt = insert_new_sale(&conn, session.as_ref(), "convert_quote_to_sale", &sale, DEFAULT_RECEIPT_SERIES, created_by)?;
+        conn.execute(
+            "UPDATE quotes SET status = ?2, sale_id = ?3, converted_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?1",
+            rusqlite::params![quote_id, QUOTE_CONVERTED, receipt.id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(receipt)
+    })();
+    match result {
+        Ok(receipt) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "sales", Some(receipt.id));
+            emit_data_changed(&window, "quotes", Some(quote_id));
+            Ok(receipt)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+/// Cotización en CSV para enviarla al cliente: datos generales (con el
+/// documento del cliente registrado) y sus líneas, por secciones como el
+/// expediente de producto.
+#[tauri::command]
+fn export_quote(state: State<AppState>, quote_id: i64) -> Result<String, String> {
+    let _job = lock_export(&state, &format!("quote_{}", quote_id))?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let quote = load_quote(&conn, quote_id)?;
+    let document_id: Option<String> = conn
+        .query_row("SELECT (SELECT document_id FROM customers WHERE id = ?1)", [quote.customer_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut csv = String::from("[quote]\nfield,value\n");
+    let fields = [
+        ("quote_number", quote.quote_number.clone()),
+        ("status", quote.status.clone()),
+        ("customer", quote.customer.clone().unwrap_or_default()),
+        ("document_id", document_id.unwrap_or_default()),
+        ("created_at", quote.created_at.clone()),
+        ("valid_until", quote.valid_until.clone()),
+        ("note", quote.note.clone().unwrap_or_default()),
+        ("total", format_amount(quote.total)),
+    ];
+    for (field, value) in fields {
+        csv.push_str(&format!("{},{}\n", field, csv_field(&value)));
+    }
+    csv.push_str("\n[items]\nproduct_id,sku,name,quantity,unit_price,discount,line_total\n");
+    for item in &quote.items {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            item.product_id,
+            csv_field(item.sku.as_deref().unwrap_or_default()),
+            csv_field(&item.product_name),
+            item.quantity,
+            format_amount(item.unit_price),
+            item.discount.map(|d| d.to_string()).unwrap_or_default(),
+            format_amount(item.line_total),
+        ));
+    }
+
+    write_export(&format!("cotizacion_{}", quote.quote_number), "csv", csv.as_bytes())
+}
+
+// ============================================
+// RANKING DE VENDEDORES
+// ============================================
+
+/// Con este ajuste en "true" los vendedores ven los importes de todos; si no,
+/// solo los propios (el resto ve posición, tickets y avance de la meta).
+const SETTING_LEADERBOARD_AMOUNTS: &str = "leaderboard_show_amounts";
+
+#[derive(Debug, Serialize)]
+struct LeaderboardEntry {
+    rank: usize,
+    user_id: i32,
+    username: String,
+    fullname: Option<String>,
+    tickets: i64,
+    net_revenue: Option<f64>,
+    average_ticket: Option<f64>,
+    monthly_target: Option<f64>,
+    /// Porcentaje de la meta alcanzado (puede pasar de 100)
+    target_progress: Option<f64>,
+}
+
+/// Fija o quita (`monthly_target` vacío) la meta mensual de un usuario.
+#[tauri::command]
+fn set_seller_target<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    user_id: i32,
+    monthly_target: Option<f64>,
+) -> Result<(), String> {
+    let session = require_admin(&state, &window)?;
+    if let Some(target) = monthly_target {
+        if !target.is_finite() || target <= 0.0 {
+            return Err("La meta mensual debe ser mayor a 0".to_string());
+        }
+    }
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE users SET monthly_target = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            rusqlite::params![user_id, monthly_target],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("El usuario #{} no existe", user_id));
+    }
+    let details = match monthly_target {
+        Some(target) => format!("Meta mensual: {:.2}", target),
+        None => "Meta mensual eliminada".to_string(),
+    };
+    audit(&conn, Some(session.user_id), "set_seller_target", "users", Some(user_id as i64), &details, None)?;
+    emit_data_changed(&window, "users", Some(user_id as i64));
+    Ok(())
+}
+
+/// Ranking del mes `period` (AAAA-MM) por ingreso neto (ventas menos depósitos
+/// devueltos); los empates se ordenan por tickets y luego por id de usuario.
+/// Incluye a quien vendió en el mes o tiene meta. Administradores y auditores
+/// ven todos los importes; los demás, solo los suyos salvo que
+/// `leaderboard_show_amounts` lo permita.
+#[tauri::command]
+fn get_seller_leaderboard<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    period: String,
+) -> Result<Vec<LeaderboardEntry>, String> {
+    validate_month(&period)?;
+    let session = window_session(&state, window.label())
+        .ok_or_else(|| "No hay una sesión activa en esta ventana".to_string())?;
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let show_all = matches!(session.role.as_str(), "Administrador" | "Auditor")
+        || get_setting(&conn, SETTING_LEADERBOARD_AMOUNTS).as_deref() == Some("true");
+
+    let sales = valid_sales(true);
+    let mut stmt = conn
+        .prepare(&format!(
+            "WITH period_sales AS (
+                 SELECT s.id, s.created_by, s.sale_price,
+                        COALESCE((SELECT SUM(r.amount) FROM deposit_refunds r WHERE r.sale_id = s.id), 0) AS refunded
+                 FROM {sales} s
+                 WHERE s.created_by IS NOT NULL AND substr(s.sale_date, 1, 7) = ?1
+             )
+             SELECT u.id, u.username, u.fullname, u.monthly_target,
+                    COUNT(ps.id), COALESCE(SUM(ps.sale_price - ps.refunded), 0)
+             FROM users u
+             LEFT JOIN period_sales ps ON ps.created_by = u.id
+             GROUP BY u.id
+             HAVING COUNT(ps.id) > 0 OR u.monthly_target IS NOT NULL
+             ORDER BY 6 DESC, 5 DESC, u.id",
+            sales = sales
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([&period], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let round2 = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, (user_id, username, fullname, target, tickets, revenue))| {
+            let visible = show_all || user_id == session.user_id;
+            LeaderboardEntry {
+                rank: i + 1,
+                user_id,
+                username,
+                fullname,
+                tickets,
+                net_revenue: visible.then(|| round2(revenue)),
+                average_ticket: (visible && tickets > 0).then(|| round2(revenue / tickets as f64)),
+                monthly_target: if visible { target } else { None },
+                target_progress: target.map(|t| (revenue / t * 1000.0).round() / 10.0),
+            }
+        })
+        .collect())
+}
+
+// ============================================
+// PACKS
+// ============================================
+
+/// Componente de un pack con su stock actual.
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleComponent {
+    component_id: i32,
+    name: String,
+    sku: Option<String>,
+    /// Unidades del componente por cada pack vendido
+    quantity: f64,
+    stock: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleComponentInput {
+    component_id: i32,
+    quantity: f64,
+}
+
+/// Saldo de stock de un producto según sus movimientos.
+fn product_stock(conn: &Connection, product_id: i32) -> Result<f64, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN type='ingreso' THEN quantity WHEN type='egreso' THEN -quantity ELSE 0 END),0) FROM stock_movements WHERE product_id=?1",
+        rusqlite::params![product_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn is_bundle(conn: &Connection, product_id: i32) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM product_bundles WHERE bundle_id = ?1)",
+        [product_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Componentes del pack; vacío si el producto no es un pack.
+fn bundle_components(conn: &Connection, bundle_id: i32) -> Result<Vec<BundleComponent>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT b.component_id, p.name, p.sku, b.quantity,
+                    COALESCE((SELECT SUM(CASE WHEN m.type='ingreso' THEN m.quantity WHEN m.type='egreso' THEN -m.quantity ELSE 0 END)
+                              FROM stock_movements m WHERE m.product_id = b.component_id), 0)
+             FROM product_bundles b
+             JOIN products p ON p.id = b.component_id
+             WHERE b.bundle_id = ?1
+             ORDER BY p.name, b.component_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let components = stmt
+        .query_map([bundle_id], |row| {
+            Ok(BundleComponent {
+                component_id: row.get(0)?,
+                name: row.get(1)?,
+                sku: row.get(2)?,
+                quantity: row.get(3)?,
+                stock: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(components)
+}
+
+/// Componentes de un pack con su stock; vacío si el producto no es un pack.
+#[tauri::command]
+fn get_product_bundle(state: State<AppState>, bundle_id: i32) -> Result<Vec<BundleComponent>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    bundle_components(&conn, bundle_id)
+}
+
+/// Define (o reemplaza) los componentes de un pack. El pack no guarda stock:
+/// cada venta descuenta `cantidad × unidades vendidas` de cada componente.
+/// Con la lista vacía el producto deja de ser un pack.
+#[tauri::command]
+fn set_product_bundle<R: Runtime>(
+    window: Window<R>,
+    state: State<AppState>,
+    bundle_id: i32,
+    components: Vec<BundleComponentInput>,
+) -> Result<Vec<BundleComponent>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let deleted: bool = conn
+        .query_row("SELECT deleted_at IS NOT NULL FROM products WHERE id = ?1", [bundle_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Producto no encontrado".to_string(),
+            e => e.to_string(),
+        })?;
+    if deleted {
+        return Err("El producto fue eliminado del catálogo".to_string());
+    }
+    if !components.is_empty() {
+        let in_bundle: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM product_bundles WHERE component_id = ?1)",
+                [bundle_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if in_bundle {
+            return Err("El producto es componente de otro pack; un pack no puede contener packs".to_string());
+        }
+        let stock = product_stock(&conn, bundle_id)?;
+        if stock.abs() > QUANTITY_EPSILON {
+            return Err(format!(
+                "El producto tiene stock propio ({}); un pack descuenta el de sus componentes",
+                stock
+            ));
+        }
+    }
+    let mut seen = Vec::new();
+    for component in &components {
+        if component.component_id == bundle_id {
+            return Err("Un pack no puede contenerse a sí mismo".to_string());
+        }
+        if seen.contains(&component.component_id) {
+            return Err("Un componente aparece más de una vez en el pack".to_string());
+        }
+        seen.push(component.component_id);
+        if component.quantity <= 0.0 {
+            return Err("La cantidad de cada componente debe ser mayor a cero".to_string());
+        }
+        validate_quantity(&conn, component.component_id, component.quantity)?;
+        if is_bundle(&conn, component.component_id)? {
+            return Err("Un pack no puede contener otro pack".to_string());
+        }
+    }
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+    let result: Result<(), String> = (|| {
+        conn.execute("DELETE FROM product_bundles WHERE bundle_id = ?1", [bundle_id])
+            .map_err(|e| e.to_string())?;
+        for component in &components {
+            conn.execute(
+                "INSERT INTO product_bundles (bundle_id, component_id, quantity) VALUES (?1, ?2, ?3)",
+                rusqlite::params![bundle_id, component.component_id, component.quantity],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", [])
+                .map_err(|e| e.to_string())?;
+            emit_data_changed(&window, "product_bundles", Some(bundle_id as i64));
+            bundle_components(&conn, bundle_id)
+        }
+        Err(err) => {
+            let _ = conn.execute("ROLLBACK", []);
+            Err(err)
+        }
+    }
+}
+
+// ============================================
+// ROL AUDITOR (SOLO LECTURA)
+// ============================================
+
+/// Rol integrado para auditores externos: consulta y exporta todo, pero no
+/// puede ejecutar ningún comando que modifique datos.
+const ROLE_AUDITOR: &str = "Auditor";
+
+/// Comandos de solo lectura que no siguen la convención `get_*`/`export_*`.
+/// Un comando nuevo que no modifique datos y tenga otro prefijo debe
+/// agregarse aquí para que el Auditor pueda usarlo.
+const AUDITOR_EXTRA_COMMANDS: &[&str] = &[
+    "verify_login",
+    "verify_report_consistency",
+    "logout",
+    "check_credit",
+    "calculate_price",
+    "suggest_stock_levels",
+    "check_for_updates",
+    "list_attachments",
+    "is_first_run",
+    "search_products",
+    "search_products_quick",
+    "find_probable_duplicates",
+    "inspect_csv",
+];
+
+/// Permisos del Auditor: todos los `get_*` y `export_*` más las excepciones
+/// de `AUDITOR_EXTRA_COMMANDS`.
+fn auditor_can_run(command: &str) -> bool {
+    command.starts_with("get_") || command.starts_with("export_") || AUDITOR_EXTRA_COMMANDS.contains(&command)
+}
+
+/// Control de acceso previo a cualquier comando. Con una sesión de Auditor
+/// rechaza los comandos que modifican datos y deja constancia de cada
+/// exportación en el log de auditoría.
+fn check_command_access<R: Runtime>(message: &tauri::InvokeMessage<R>) -> Result<(), String> {
+    let window = message.window();
+    let state = window.state::<AppState>();
+    let session = match window_session(&state, window.label()) {
+        Some(session) if session.role == ROLE_AUDITOR => session,
+        _ => return Ok(()),
+    };
+    let command = message.command();
+    if !auditor_can_run(command) {
+        return Err(format!(
+            "El rol Auditor es de solo lectura: no puede ejecutar {}",
+            command
+        ));
+    }
+    if command.starts_with("export_") {
+        let conn = state.db.lock().map_err(|e| e.to_string())?;
+        audit(&conn, Some(session.user_id), "exportar", command, None, &message.payload().to_string(), None)?;
+    }
+    Ok(())
+}
+
+/// Antepone `check_command_access` al manejador de comandos.
+fn guard_commands<R, F>(handler: F) -> impl Fn(tauri::Invoke<R>) + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(tauri::Invoke<R>) + Send + Sync + 'static,
+{
+    move |invoke| match check_command_access(&invoke.message) {
+        Ok(()) => handler(invoke),
+        Err(e) => invoke.resolver.reject(e),
+    }
+}
+
+/// Declara la lista única de comandos: genera `COMMANDS` y el manejador de
+/// `invoke` a partir de los mismos nombres, así no pueden desincronizarse.
+macro_rules! app_commands {
+    ($($command:ident),* $(,)?) => {
+        /// Nombres de todos los comandos registrados. Las pruebas lo recorren
+        /// para verificar los permisos de cada rol.
+        pub const COMMANDS: &[&str] = &[$(stringify!($command)),*];
+
+        fn command_handler<R: Runtime>() -> impl Fn(tauri::Invoke<R>) + Send + Sync + 'static {
+            guard_commands(tauri::generate_handler![$($command),*])
+        }
+    };
+}
+
+app_commands![
+    get_products,
+    add_product,
+    update_product,
+    delete_product,
+    get_stock_movements,
+    add_stock_movement,
+    get_sales,
+    get_sales_detailed,
+    get_next_receipt_number,
+    get_sales_by_payment_method,
+    get_sales_by_channel,
+    get_sales_by_user,
+    get_sales_heatmap,
+    register_sale_payment,
+    get_sale_payments,
+    get_pending_receivables,
+    get_customers,
+    search_customers,
+    add_customer,
+    update_customer,
+    delete_customer,
+    get_customer_history,
+    create_quote,
+    update_quote,
+    get_quotes,
+    convert_quote_to_sale,
+    export_quote,
+    add_sale,
+    create_sale,
+    update_sale,
+    void_sale,
+    get_cash_movements,
+    add_cash_movement,
+    get_cash_summary,
+    get_sales_by_product,
+    get_sales_trend,
+    get_sales_totals,
+    get_tax_summary,
+    get_stock_balances,
+    get_product_stats,
+    get_low_stock_products,
+    get_products_by_supplier,
+    get_overstock_products,
+    export_overstock_report,
+    get_expiring_products,
+    export_expiry_report,
+    export_inventory_report,
+    export_catalog_zip,
+    export_sales_report,
+    export_top_products_report,
+    export_sales_by_channel_report,
+    export_sales_by_user_report,
+    export_tax_report,
+    export_stock_movements_report,
+    export_profitability_report,
+    export_financial_report,
+    export_all_reports,
+    get_users,
+    add_user,
+    update_user,
+    delete_user,
+    verify_login,
+    reset_database,
+    archive_old_data,
+    logout,
+    get_active_sessions,
+    force_logout,
+    issue_store_credit,
+    check_credit,
+    get_outstanding_deposits,
+    refund_deposit,
+    repair_timestamps,
+    get_settings,
+    update_setting,
+    get_notifications,
+    mark_notification_read,
+    get_velocity_alerts,
+    calculate_price,
+    format_money,
+    compute_sale_totals,
+    bulk_update_prices,
+    import_supplier_prices,
+    backup_database,
+    import_sales_csv,
+    import_products_xlsx,
+    set_product_tags,
+    get_product_tags,
+    get_products_by_tag,
+    start_import,
+    import_next_chunk,
+    cancel_import,
+    resume_import,
+    get_import_jobs,
+    get_import_job,
+    get_promotions,
+    get_active_promotions,
+    add_promotion,
+    update_promotion,
+    delete_promotion,
+    get_data_quality_report,
+    export_data_quality_report,
+    repair_data_quality,
+    get_health_report,
+    get_webhooks,
+    add_webhook,
+    update_webhook,
+    delete_webhook,
+    test_webhook,
+    get_webhook_deliveries,
+    retry_webhook_delivery,
+    get_closed_periods,
+    close_period,
+    reopen_period,
+    get_audit_log,
+    get_below_cost_sales,
+    set_opening_balance,
+    export_product_dossier,
+    get_running_exports,
+    suggest_stock_levels,
+    get_product_seasonality,
+    get_product_by_id,
+    export_catalog_snapshot,
+    rebuild_daily_summaries,
+    verify_report_consistency,
+    search_products,
+    search_products_quick,
+    get_product_by_sku,
+    sell_by_sku,
+    generate_internal_barcode,
+    inspect_csv,
+    get_app_info,
+    check_for_updates,
+    purge_product,
+    add_attachment,
+    list_attachments,
+    delete_attachment,
+    find_probable_duplicates,
+    resolve_duplicate,
+    get_upcoming_installments,
+    get_payment_schedule,
+    register_installment_payment,
+    set_seller_target,
+    get_seller_leaderboard,
+    is_first_run,
+    complete_initial_setup,
+    save_product_image,
+    get_product_thumbnail_path,
+    get_product_image,
+    regenerate_thumbnails,
+    add_product_variant,
+    clone_product,
+    get_product_variants,
+    get_product_families,
+    import_users_csv,
+    get_product_bundle,
+    set_product_bundle,
+    get_price_history,
+    get_price_overrides,
+    export_price_overrides,
+    export_sql_dump,
+    import_sql_dump,
+    get_categories,
+    add_category,
+    update_category,
+    delete_category,
+    get_brands,
+    add_brand,
+    update_brand,
+    delete_brand,
+    get_price_lists,
+    add_price_list,
+    update_price_list,
+    delete_price_list,
+    get_product_prices,
+    get_product_price,
+    set_product_price,
+    set_exchange_rate,
+    get_exchange_rates,
+    prune_history,
+];
+
+/// Registra el estado y todos los comandos en `builder`. La aplicación y las
+/// pruebas de integración (sobre `tauri::test::mock_builder`) comparten esta
+/// configuración, así las pruebas ejercitan los mismos comandos que la interfaz.
+pub fn configure<R: Runtime>(builder: tauri::Builder<R>, db: Connection) -> tauri::Builder<R> {
+    builder
+        .manage(AppState::new(db))
+        .invoke_handler(command_handler())
+}
+
+pub fn run() {
+    let db = init_database().expect("Failed to initialize database");
+
+    configure(tauri::Builder::default(), db)
+        .setup(|app| {
+            start_scheduler(app.handle());
+            start_webhook_worker(app.handle());
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,58 @@
+//! Versión de la aplicación y consulta de actualizaciones.
+
+mod common;
+
+use common::TestApp;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+/// Sirve `manifest` a una sola petición HTTP y devuelve la dirección.
+fn serve_manifest(manifest: Value) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request);
+        let body = manifest.to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+    format!("http://{}/vitasport.json", addr)
+}
+
+#[test]
+fn app_info_is_reported_alone_and_in_the_health_report() {
+    let app = TestApp::with_admin();
+    let info: Value = app.invoke("get_app_info", json!({})).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["schema_version"], 35);
+    assert_eq!(info["build_date"].as_str().unwrap().len(), 10);
+
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["app"], info);
+}
+
+#[test]
+fn update_check_compares_the_manifest_version() {
+    let app = TestApp::with_admin();
+    let err = app.invoke::<Value>("check_for_updates", json!({})).unwrap_err();
+    assert_eq!(err, "No hay una dirección de actualizaciones configurada (update_manifest_url)");
+
+    let url = serve_manifest(json!({ "version": "v1.10.0", "url": "https://example.com/vitasport.msi", "notes": "Mejoras" }));
+    app.set_setting("update_manifest_url", &url);
+    let check: Value = app.invoke("check_for_updates", json!({})).unwrap();
+    assert_eq!(check["current_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(check["latest_version"], "v1.10.0");
+    assert_eq!(check["update_available"], true);
+    assert_eq!(check["download_url"], "https://example.com/vitasport.msi");
+
+    app.set_setting("update_manifest_url", &serve_manifest(json!({ "version": "0.9" })));
+    let check: Value = app.invoke("check_for_updates", json!({})).unwrap();
+    assert_eq!(check["update_available"], false);
+}
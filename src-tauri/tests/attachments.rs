@@ -0,0 +1,95 @@
+//! Adjuntos de ventas, movimientos, compras y productos: el archivo se copia
+//! a la carpeta de adjuntos (ver `common::attachments_dir`).
+
+mod common;
+
+use common::{attachments_dir, export_dir, ProductFactory, SaleFactory, TestApp, UserFactory};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Archivo de origen en la carpeta de exportación (con espacios y tildes).
+fn source_file(name: &str, content: &[u8]) -> PathBuf {
+    let path = export_dir().join(name);
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn attachments_are_copied_listed_audited_and_deleted() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    let sale = SaleFactory::new(whey, 1.0).create(&app).unwrap();
+    let sale_id = sale["id"].as_i64().unwrap();
+
+    let source = source_file("factura proveedor Nº 12.pdf", b"%PDF-1.4 factura");
+    let added: Value = app
+        .invoke("add_attachment", json!({ "entity": "sales", "id": sale_id, "sourcePath": source.to_str().unwrap() }))
+        .unwrap();
+    fs::remove_file(&source).unwrap();
+    assert_eq!(added["filename"], "factura proveedor Nº 12.pdf");
+    assert_eq!(added["mime_type"], "application/pdf");
+    assert_eq!(added["uploaded_by"], "admin");
+    let stored = Path::new(added["stored_path"].as_str().unwrap());
+    assert_eq!(stored.parent().unwrap(), attachments_dir());
+    assert_eq!(fs::read(stored).unwrap(), b"%PDF-1.4 factura");
+
+    let listed: Vec<Value> = app.invoke("list_attachments", json!({ "entity": "sales", "id": sale_id })).unwrap();
+    assert_eq!(listed, vec![added.clone()]);
+    let dossier = app.export("export_product_dossier", json!({ "productId": whey }));
+    assert!(dossier.contains(&format!(",sales,{},factura proveedor Nº 12.pdf,admin", sale_id)), "{}", dossier);
+
+    // Solo quien lo subió o un Administrador lo puede borrar
+    let seller = UserFactory::new("vendedor").role("Vendedor").create_and_login(&app);
+    let err = app
+        .invoke_in::<()>(&seller, "delete_attachment", json!({ "id": added["id"] }))
+        .unwrap_err();
+    assert_eq!(err, "Solo quien subió el adjunto o un Administrador puede eliminarlo");
+    app.invoke::<()>("delete_attachment", json!({ "id": added["id"] })).unwrap();
+    assert!(!stored.exists());
+    let listed: Vec<Value> = app.invoke("list_attachments", json!({ "entity": "sales", "id": sale_id })).unwrap();
+    assert!(listed.is_empty());
+
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    let actions: Vec<&str> = log
+        .iter()
+        .filter(|e| e["entity"] == "attachments")
+        .map(|e| e["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions.len(), 2);
+    assert!(actions.contains(&"add_attachment") && actions.contains(&"delete_attachment"));
+}
+
+#[test]
+fn attachments_are_validated_before_copying() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let movement = app.add_stock(whey, 5.0);
+    let attach = |entity: &str, id: i64, path: &Path| {
+        app.invoke::<Value>("add_attachment", json!({ "entity": entity, "id": id, "sourcePath": path.to_str().unwrap() }))
+    };
+
+    let photo = source_file("producto dañado.jpg", b"\xFF\xD8\xFF\xE0 foto");
+    assert_eq!(attach("stock_movements", movement, &photo).unwrap()["mime_type"], "image/jpeg");
+    let err = attach("users", 1, &photo).unwrap_err();
+    assert_eq!(err, "No se pueden adjuntar archivos a users. Usa: sales, stock_movements, purchases, products");
+    let err = attach("sales", 99, &photo).unwrap_err();
+    assert_eq!(err, "No existe el registro 99 de sales");
+
+    let renamed = source_file("planilla.pdf", b"MZ ejecutable");
+    let err = attach("products", whey, &renamed).unwrap_err();
+    assert!(err.ends_with("no corresponde a un archivo PDF"), "{}", err);
+    let text = source_file("notas.txt", b"texto");
+    let err = attach("products", whey, &text).unwrap_err();
+    assert!(err.starts_with("Tipo de archivo no admitido"), "{}", err);
+    let big = source_file("escaneo enorme.png", &[b"\x89PNG".as_slice(), &vec![0u8; 10 * 1024 * 1024]].concat());
+    let err = attach("products", whey, &big).unwrap_err();
+    assert!(err.ends_with("supera el máximo de 10 MB"), "{}", err);
+    for path in [photo, renamed, text, big] {
+        fs::remove_file(path).unwrap();
+    }
+
+    let listed: Vec<Value> = app.invoke("list_attachments", json!({ "entity": "products", "id": whey })).unwrap();
+    assert!(listed.is_empty());
+}
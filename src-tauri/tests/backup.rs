@@ -0,0 +1,66 @@
+//! Volcado SQL: lo que exporta `export_sql_dump` debe poder restaurarse en
+//! una instalación nueva con exactamente las mismas filas.
+
+mod common;
+
+use common::{ProductFactory, SaleFactory, TestApp, ADMIN_PASSWORD};
+use serde_json::{json, Value};
+use std::fs;
+
+#[test]
+fn sql_dump_round_trips_into_a_fresh_install() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Barra \"Choco\" l'original\nedición 2024")
+        .sku("BAR-'01")
+        .unit("kg")
+        .with("flavor", json!("Línea 1; DROP TABLE products; --\r\nLínea 2 \\ ñ"))
+        .create(&app)
+        .unwrap();
+    app.add_stock(product_id, 12.5);
+    SaleFactory::new(product_id, 2.0).total(199.99).override_price().create(&app).unwrap();
+
+    let dump: Value = app.invoke("export_sql_dump", json!({})).unwrap();
+    let path = dump["path"].as_str().unwrap().to_string();
+    assert!(path.ends_with(".sql"));
+    let sql = fs::read_to_string(&path).unwrap();
+    assert!(sql.starts_with("-- VitaSport SQL dump"));
+    assert!(sql.contains("l''original"));
+    let tables = dump["tables"].as_array().unwrap();
+    let rows_of = |name: &str| tables.iter().find(|t| t["name"] == name).unwrap()["rows"].as_i64().unwrap();
+    assert_eq!(rows_of("products"), 1);
+    assert_eq!(rows_of("sales"), 1);
+
+    let restored = TestApp::first_run();
+    let imported: Value = restored.invoke("import_sql_dump", json!({ "path": path })).unwrap();
+    assert_eq!(imported["tables"], dump["tables"]);
+
+    // La base restaurada se usa con normalidad
+    restored.login(&restored.main, "admin", ADMIN_PASSWORD).unwrap();
+    let product: Value = restored.invoke("get_product_by_id", json!({ "id": product_id })).unwrap();
+    assert_eq!(product["name"], "Barra \"Choco\" l'original\nedición 2024");
+    assert_eq!(product["flavor"], "Línea 1; DROP TABLE products; --\r\nLínea 2 \\ ñ");
+    assert_eq!(restored.stock_of(product_id), 10.5);
+
+    // Y el volcado de la copia es idéntico al original
+    let again: Value = restored.invoke("export_sql_dump", json!({})).unwrap();
+    assert_eq!(again["tables"], dump["tables"]);
+    fs::remove_file(again["path"].as_str().unwrap()).unwrap();
+
+    // Una base con datos no se sobrescribe
+    let err = app.invoke::<Value>("import_sql_dump", json!({ "path": path })).unwrap_err();
+    assert!(err.contains("ya tiene datos"), "{}", err);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn import_sql_dump_rejects_other_files() {
+    let app = TestApp::first_run();
+    let path = common::export_dir().join("no_es_un_volcado.sql");
+    fs::write(&path, "DROP TABLE users;").unwrap();
+    let err = app
+        .invoke::<Value>("import_sql_dump", json!({ "path": path.to_str().unwrap() }))
+        .unwrap_err();
+    assert_eq!(err, "El archivo no es un volcado SQL de VitaSport");
+    fs::remove_file(&path).unwrap();
+    let _: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+}
@@ -0,0 +1,369 @@
+//! Caja: saldo inicial, resumen y reporte financiero.
+
+mod common;
+
+use common::{days_ago, days_ahead, ProductFactory, SaleFactory, TestApp, UserFactory, SALE_DATE};
+use serde_json::{json, Value};
+
+fn add_expense(app: &TestApp, amount: f64, date: &str) {
+    app.invoke::<i64>(
+        "add_cash_movement",
+        json!({ "movement": { "movement_type": "egreso", "amount": amount, "movement_date": date } }),
+    )
+    .unwrap();
+}
+
+#[test]
+fn opening_balance_counts_in_balance_but_not_in_income() {
+    let app = TestApp::with_admin();
+    app.invoke::<()>("set_opening_balance", json!({ "amount": 350.0, "date": "2024-03-01" }))
+        .unwrap();
+    let product_id = ProductFactory::new("Proteína").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    SaleFactory::new(product_id, 1.0).date("2024-03-05").create(&app).unwrap();
+    add_expense(&app, 20.0, "2024-03-06");
+
+    let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+    assert_eq!(summary["opening_balance"], 350.0);
+    assert_eq!(summary["total_income"], 100.0);
+    assert_eq!(summary["total_expense"], 20.0);
+    assert_eq!(summary["balance"], 430.0);
+
+    // El reporte de abril arrastra el saldo de marzo como saldo inicial
+    let csv = app.export(
+        "export_financial_report",
+        json!({ "startDate": "2024-04-01", "endDate": "2024-04-30" }),
+    );
+    assert!(csv.contains("opening,Saldo inicial,430.00\n"), "{}", csv);
+    assert!(csv.contains("summary,Balance,430.00\n"), "{}", csv);
+
+    // Y el de marzo parte del saldo inicial configurado
+    let csv = app.export(
+        "export_financial_report",
+        json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }),
+    );
+    assert!(csv.contains("opening,Saldo inicial,350.00\n"), "{}", csv);
+    assert!(csv.contains("summary,Total ingresos,100.00\n"), "{}", csv);
+}
+
+#[test]
+fn changing_opening_balance_after_movements_requires_admin_and_is_audited() {
+    let app = TestApp::with_admin();
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+
+    // Sin movimientos, cualquier usuario puede registrarlo
+    app.invoke_in::<()>(&seller, "set_opening_balance", json!({ "amount": 300.0, "date": "2024-03-01" }))
+        .unwrap();
+
+    add_expense(&app, 10.0, "2024-03-02");
+    let err = app
+        .invoke_in::<()>(&seller, "set_opening_balance", json!({ "amount": 350.0, "date": "2024-03-01" }))
+        .unwrap_err();
+    assert!(err.contains("solo un Administrador"), "{}", err);
+
+    app.invoke::<()>("set_opening_balance", json!({ "amount": 350.0, "date": "2024-03-01" }))
+        .unwrap();
+    let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+    assert_eq!(summary["balance"], 340.0);
+
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({ "flag": "saldo_inicial" })).unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0]["details"], "Saldo inicial: 300.00 al 2024-03-01 → 350.00 al 2024-03-01");
+}
+
+#[test]
+fn opening_balance_is_not_a_regular_cash_movement() {
+    let app = TestApp::with_admin();
+    let err = app
+        .invoke::<i64>(
+            "add_cash_movement",
+            json!({ "movement": { "movement_type": "saldo_inicial", "amount": 350.0, "movement_date": "2024-03-01" } }),
+        )
+        .unwrap_err();
+    assert!(err.starts_with("Tipo de movimiento inválido"), "{}", err);
+
+    let err = app
+        .invoke::<()>("set_opening_balance", json!({ "amount": -5.0, "date": "2024-03-01" }))
+        .unwrap_err();
+    assert_eq!(err, "El saldo inicial no puede ser negativo");
+}
+
+#[test]
+fn sale_totals_preview_includes_the_tax_like_the_stored_sale() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").prices(118.0, 60.0).with("tax_rate", json!(18)).create(&app).unwrap();
+    let fruit = ProductFactory::new("Plátano").prices(2.5, 1.0).with("tax_rate", json!(0)).create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+
+    let preview: Value = app
+        .invoke(
+            "compute_sale_totals",
+            json!({ "items": [
+                { "product_id": whey, "quantity": 2.0, "unit_price": 118.0 },
+                { "product_id": fruit, "quantity": 4.0, "unit_price": 2.5 },
+            ] }),
+        )
+        .unwrap();
+    assert_eq!(preview["lines"][0]["tax_amount"], 36.0);
+    assert_eq!(preview["lines"][1]["tax_amount"], 0.0);
+    assert_eq!(preview["total"], 246.0);
+    assert_eq!(preview["tax_amount"], 36.0);
+    assert_eq!(preview["net_total"], 210.0);
+
+    let sale = SaleFactory::new(whey, 2.0).with("sale_price", Value::Null).create(&app).unwrap();
+    assert_eq!(sale["tax_amount"], preview["lines"][0]["tax_amount"]);
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales[0]["sale_price"], preview["lines"][0]["total"]);
+
+    // Una línea sin producto usa la tasa indicada
+    let loose: Value = app
+        .invoke("compute_sale_totals", json!({ "items": [{ "quantity": 1.0, "unit_price": 110.0 }], "tax": 10.0 }))
+        .unwrap();
+    assert_eq!(loose["total"], 110.0);
+    assert_eq!(loose["tax_amount"], 10.0);
+}
+
+#[test]
+fn sales_store_the_included_tax_of_each_product() {
+    let app = TestApp::with_admin();
+    app.invoke::<()>("update_setting", json!({ "key": "default_tax_rate", "value": "10" })).unwrap();
+    let whey = ProductFactory::new("Whey 2kg").with("tax_rate", json!(18)).create(&app).unwrap();
+    let fruit = ProductFactory::new("Plátano").with("tax_rate", json!(0)).create(&app).unwrap();
+    let shaker = ProductFactory::new("Shaker").create(&app).unwrap();
+    for id in [whey, fruit, shaker] {
+        app.add_stock(id, 5.0);
+    }
+
+    let sale = SaleFactory::new(whey, 1.0).total(118.0).override_price().create(&app).unwrap();
+    assert_eq!(sale["tax_amount"], 18.0);
+    let sale = SaleFactory::new(fruit, 2.0).total(5.0).override_price().create(&app).unwrap();
+    assert_eq!(sale["tax_amount"], 0.0);
+    // Sin tasa propia usa default_tax_rate
+    let sale = SaleFactory::new(shaker, 1.0).total(33.0).override_price().create(&app).unwrap();
+    assert_eq!(sale["tax_amount"], 3.0);
+
+    let totals: Value = app.invoke("get_sales_totals", json!({})).unwrap();
+    assert_eq!(totals["total_revenue"], 156.0);
+    assert_eq!(totals["tax_collected"], 21.0);
+    assert_eq!(totals["net_revenue"], 135.0);
+    let csv = app.export("export_financial_report", json!({}));
+    assert!(csv.contains("income,Ingresos por ventas,156.00\ntax,Ventas sin impuesto,135.00\ntax,Impuesto cobrado,21.00\n"), "{}", csv);
+
+    let err = ProductFactory::new("Gel").with("tax_rate", json!(1.5e3)).create(&app).unwrap_err();
+    assert_eq!(err, "tax_rate: La tasa de impuesto debe ser un porcentaje entre 0 y 100 (1500); usa 18 para el 18%");
+    app.invoke::<()>("update_setting", json!({ "key": "default_tax_rate", "value": "118" })).unwrap();
+    let err = SaleFactory::new(shaker, 1.0).create(&app).unwrap_err();
+    assert!(err.starts_with("La tasa de impuesto configurada (118) no es válida"), "{}", err);
+}
+
+#[test]
+fn tax_summary_splits_taxed_and_exempt_sales_by_month() {
+    let app = TestApp::with_admin();
+    app.invoke::<()>("update_setting", json!({ "key": "default_tax_rate", "value": "18" })).unwrap();
+    let whey = ProductFactory::new("Whey 2kg").create(&app).unwrap();
+    let fruit = ProductFactory::new("Plátano").with("tax_rate", json!(0)).create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    app.add_stock(fruit, 5.0);
+    SaleFactory::new(whey, 1.0).total(118.0).date("2024-03-10").override_price().create(&app).unwrap();
+    SaleFactory::new(fruit, 2.0).total(50.0).date("2024-03-12").override_price().create(&app).unwrap();
+    SaleFactory::new(whey, 2.0).total(236.0).date("2024-04-05").override_price().create(&app).unwrap();
+
+    // Cambiar la tasa no reescribe lo ya vendido
+    app.invoke::<()>("update_setting", json!({ "key": "default_tax_rate", "value": "10" })).unwrap();
+    let months: Vec<Value> = app
+        .invoke("get_tax_summary", json!({ "startDate": "2024-03-01", "endDate": "2024-04-30" }))
+        .unwrap();
+    assert_eq!(
+        months,
+        vec![
+            json!({ "month": "2024-03", "sales_count": 2, "taxable_base": 100.0, "tax_collected": 18.0, "exempt_sales": 50.0, "total": 168.0 }),
+            json!({ "month": "2024-04", "sales_count": 1, "taxable_base": 200.0, "tax_collected": 36.0, "exempt_sales": 0.0, "total": 236.0 }),
+        ]
+    );
+    let april: Vec<Value> = app.invoke("get_tax_summary", json!({ "startDate": "2024-04-01" })).unwrap();
+    assert_eq!(april.len(), 1);
+
+    let csv = app.export("export_tax_report", json!({}));
+    assert_eq!(
+        csv,
+        "month,sales_count,taxable_base,tax_collected,exempt_sales,total\n\
+         2024-03,2,100.00,18.00,50.00,168.00\n\
+         2024-04,1,200.00,36.00,0.00,236.00\n"
+    );
+}
+
+#[test]
+fn only_cash_sales_count_as_drawer_cash() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.invoke::<()>("set_opening_balance", json!({ "amount": 50.0, "date": "2024-03-01" })).unwrap();
+
+    SaleFactory::new(whey, 1.0).total(100.0).create(&app).unwrap();
+    SaleFactory::new(whey, 1.0).total(100.0).with("payment_method", json!(" Tarjeta ")).create(&app).unwrap();
+    SaleFactory::new(whey, 2.0).total(200.0).with("payment_method", json!("yape")).create(&app).unwrap();
+    let err = SaleFactory::new(whey, 1.0).with("payment_method", json!("cheque")).create(&app).unwrap_err();
+    assert_eq!(err, "Medio de pago 'cheque' no válido. Usa efectivo, tarjeta, yape, transferencia");
+    // La lista es configurable
+    app.invoke::<()>("update_setting", json!({ "key": "payment_methods", "value": "efectivo, Cheque" })).unwrap();
+    SaleFactory::new(whey, 1.0).total(100.0).with("payment_method", json!("cheque")).create(&app).unwrap();
+
+    let breakdown: Vec<Value> = app.invoke("get_sales_by_payment_method", json!({ "startDate": SALE_DATE, "endDate": SALE_DATE })).unwrap();
+    let rows: Vec<(&str, i64, f64)> = breakdown
+        .iter()
+        .map(|r| (r["payment_method"].as_str().unwrap(), r["sales_count"].as_i64().unwrap(), r["total_revenue"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(rows, [("yape", 1, 200.0), ("cheque", 1, 100.0), ("efectivo", 1, 100.0), ("tarjeta", 1, 100.0)]);
+
+    let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+    assert_eq!(summary["total_income"], 100.0);
+    assert_eq!(summary["balance"], 150.0);
+    let csv = app.export("export_financial_report", json!({}));
+    assert!(csv.contains("income,Ingresos por ventas,500.00\n"), "{}", csv);
+    assert!(csv.contains("adjustment,Ventas cobradas sin efectivo,-400.00\n"), "{}", csv);
+    assert!(csv.contains("summary,Balance,150.00\n"), "{}", csv);
+}
+
+#[test]
+fn gift_card_sales_paid_without_cash_are_not_subtracted_twice() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    for code in ["GC-TARJETA", "GC-EFECTIVO"] {
+        app.invoke::<Value>("issue_store_credit", json!({ "amount": 100.0, "code": code })).unwrap();
+    }
+    let today = days_ago(0);
+    SaleFactory::new(whey, 1.0)
+        .date(&today)
+        .with("store_credit_code", json!("GC-TARJETA"))
+        .with("payment_method", json!("tarjeta"))
+        .create(&app)
+        .unwrap();
+    SaleFactory::new(whey, 1.0).date(&today).with("store_credit_code", json!("GC-EFECTIVO")).create(&app).unwrap();
+
+    // En el cajón solo está lo cobrado al emitir las dos tarjetas
+    let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+    assert_eq!(summary["balance"], 200.0);
+    let csv = app.export("export_financial_report", json!({}));
+    assert!(csv.contains("adjustment,Ventas cobradas sin efectivo,-100.00\n"), "{}", csv);
+    assert!(csv.contains("summary,Balance,200.00\n"), "{}", csv);
+    // El saldo arrastrado a un rango posterior tampoco los resta dos veces
+    let csv = app.export("export_financial_report", json!({ "startDate": days_ahead(1), "endDate": days_ahead(2) }));
+    assert!(csv.contains("opening,Saldo inicial,200.00\n"), "{}", csv);
+    assert!(csv.contains("summary,Balance,200.00\n"), "{}", csv);
+}
+
+#[test]
+fn gift_card_codes_match_regardless_of_case_and_spaces() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    let issued: Value = app.invoke("issue_store_credit", json!({ "amount": 150.0, "code": " gc-mama " })).unwrap();
+    assert_eq!(issued["code"], "GC-MAMA");
+
+    let credit: Value = app.invoke("check_credit", json!({ "code": "gc-mama " })).unwrap();
+    assert_eq!(credit["balance"], 150.0);
+    SaleFactory::new(whey, 1.0).with("store_credit_code", json!(" Gc-Mama")).create(&app).unwrap();
+    let credit: Value = app.invoke("check_credit", json!({ "code": "GC-MAMA" })).unwrap();
+    assert_eq!(credit["balance"], 50.0);
+}
+
+#[test]
+fn archiving_does_not_change_the_drawer_balance() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.invoke::<()>("set_opening_balance", json!({ "amount": 50.0, "date": "2024-03-01" })).unwrap();
+    SaleFactory::new(whey, 1.0).date("2024-03-02").with("deposit_amount", json!(10.0)).create(&app).unwrap();
+    let credit = SaleFactory::new(whey, 1.0)
+        .date("2024-03-03")
+        .with("is_credit", json!(true))
+        .with("customer", json!("Gimnasio Titán"))
+        .with("amount_paid", json!(30.0))
+        .create(&app)
+        .unwrap();
+    // Cobro posterior al corte de una venta que sí se archiva
+    app.invoke::<Vec<Value>>("register_sale_payment", json!({ "saleId": credit["id"], "amount": 20.0 })).unwrap();
+    app.invoke::<Value>("issue_store_credit", json!({ "amount": 100.0, "code": "GC-ARCHIVO" })).unwrap();
+    SaleFactory::new(whey, 1.0).date("2024-03-04").with("store_credit_code", json!("GC-ARCHIVO")).create(&app).unwrap();
+    add_expense(&app, 15.0, "2024-03-05");
+
+    let balance = |app: &TestApp| -> (Value, String) {
+        let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+        let csv = app.export("export_financial_report", json!({ "includeArchive": true }));
+        let line = csv.lines().find(|l| l.starts_with("summary,Balance,")).unwrap().to_string();
+        (summary["balance"].clone(), line)
+    };
+    let before = balance(&app);
+    assert_eq!(before.0, 295.0);
+
+    let seller = UserFactory::new("vendedor").create_and_login(&app);
+    let err = app
+        .invoke_in::<Value>(&seller, "archive_old_data", json!({ "beforeDate": "2024-03-10", "dryRun": false }))
+        .unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+
+    let report: Value =
+        app.invoke("archive_old_data", json!({ "beforeDate": "2024-03-10", "dryRun": false })).unwrap();
+    assert_eq!(report["sales"], 3);
+    assert_eq!(report["sale_payments"], 1);
+    assert_eq!(balance(&app), before);
+}
+
+#[test]
+fn credit_sales_enter_the_drawer_as_they_are_paid() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    let credit = |qty: f64, total: f64, date: &str| {
+        SaleFactory::new(whey, qty).total(total).date(date).with("is_credit", json!(true))
+    };
+
+    let err = credit(1.0, 100.0, SALE_DATE).create(&app).unwrap_err();
+    assert_eq!(err, "Una venta al crédito necesita el cliente");
+    let err = credit(1.0, 100.0, SALE_DATE)
+        .with("customer", json!("Gimnasio Titán"))
+        .with("amount_paid", json!(150.0))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(err, "El pago inicial debe estar entre 0 y el total de la venta (100.00)");
+
+    let first = credit(2.0, 200.0, &days_ago(40))
+        .with("customer", json!("Gimnasio Titán"))
+        .with("amount_paid", json!(50.0))
+        .create(&app)
+        .unwrap();
+    let first = first["id"].as_i64().unwrap();
+    credit(1.0, 100.0, &days_ago(5)).with("customer", json!("gimnasio titán ")).create(&app).unwrap();
+    let cash_sale = SaleFactory::new(whey, 1.0).total(100.0).create(&app).unwrap();
+    let cash = |app: &TestApp| app.invoke::<Value>("get_cash_summary", json!({})).unwrap()["total_income"].clone();
+    // Solo el pago inicial y la venta de contado
+    assert_eq!(cash(&app), 150.0);
+
+    let pay = |amount: f64, method: &str| {
+        app.invoke::<Vec<Value>>("register_sale_payment", json!({ "saleId": first, "amount": amount, "paymentMethod": method }))
+    };
+    assert_eq!(pay(200.0, "efectivo").unwrap_err(), "El pago (200.00) supera lo pendiente (150.00)");
+    let err = app
+        .invoke::<Vec<Value>>("register_sale_payment", json!({ "saleId": cash_sale["id"], "amount": 10.0 }))
+        .unwrap_err();
+    assert_eq!(err, format!("La venta #{} no es al crédito", cash_sale["id"]));
+    assert_eq!(pay(100.0, "yape").unwrap().len(), 2);
+    assert_eq!(cash(&app), 150.0);
+
+    let receivables: Vec<Value> = app.invoke("get_pending_receivables", json!({})).unwrap();
+    assert_eq!(receivables.len(), 1);
+    assert_eq!(receivables[0]["customer"], "Gimnasio Titán");
+    assert_eq!(receivables[0]["sales_count"], 2);
+    assert_eq!(receivables[0]["outstanding"], 150.0);
+    assert_eq!(receivables[0]["oldest_sale_date"], days_ago(40));
+    assert_eq!(receivables[0]["days_overdue"], 10);
+
+    let payments = pay(50.0, "efectivo").unwrap();
+    let amounts: Vec<f64> = payments.iter().map(|p| p["amount"].as_f64().unwrap()).collect();
+    assert_eq!(amounts, [50.0, 100.0, 50.0]);
+    assert_eq!(cash(&app), 200.0);
+    let receivables: Vec<Value> = app.invoke("get_pending_receivables", json!({})).unwrap();
+    assert_eq!(receivables[0]["outstanding"], 100.0);
+    assert_eq!(receivables[0]["days_overdue"], 0);
+}
@@ -0,0 +1,324 @@
+//! Utilidades compartidas por las pruebas de integración: una app Tauri
+//! simulada (sin webview) con la base de datos en memoria, fábricas de datos
+//! y comparación de exportaciones contra archivos de referencia.
+#![allow(dead_code)]
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tauri::api::ipc::CallbackFn;
+use tauri::test::{get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY};
+use tauri::{App, InvokePayload, Manager, Window, WindowBuilder, WindowUrl};
+
+/// Fecha fija para las ventas de prueba, así las exportaciones no dependen del día.
+pub const SALE_DATE: &str = "2024-03-15";
+
+/// Contraseña del admin después de la configuración inicial.
+pub const ADMIN_PASSWORD: &str = "clave-admin";
+
+/// Fecha de hace `days` días (AAAA-MM-DD), para datos que dependen de hoy.
+pub fn days_ago(days: i64) -> String {
+    days_ahead(-days)
+}
+
+/// Fecha dentro de `days` días (AAAA-MM-DD).
+pub fn days_ahead(days: i64) -> String {
+    rusqlite::Connection::open_in_memory()
+        .and_then(|conn| conn.query_row("SELECT date('now', ?1)", [format!("{:+} days", days)], |row| row.get(0)))
+        .expect("No se pudo calcular la fecha")
+}
+
+/// App con el mismo estado y los mismos comandos que la real, sobre
+/// `MockRuntime` y una base en memoria.
+pub struct TestApp {
+    app: App<MockRuntime>,
+    pub main: Window<MockRuntime>,
+}
+
+impl TestApp {
+    /// Instalación nueva: solo existe el usuario admin por defecto y falta la
+    /// configuración inicial.
+    pub fn first_run() -> TestApp {
+        TestApp::from_database(vitasport_lib::init_memory_database().expect("No se pudo crear la base en memoria"))
+    }
+
+    /// App sobre una base ya preparada, p. ej. para simular una de una versión
+    /// anterior.
+    pub fn from_database(db: rusqlite::Connection) -> TestApp {
+        export_dir();
+        attachments_dir();
+        images_dir();
+        let app = vitasport_lib::configure(mock_builder(), db)
+            .build(mock_context(noop_assets()))
+            .expect("No se pudo construir la app de prueba");
+        let main = app.get_window("main").expect("Falta la ventana principal");
+        TestApp { app, main }
+    }
+
+    /// Base con la configuración inicial completa: solo existe el usuario
+    /// admin, con la contraseña `ADMIN_PASSWORD`.
+    pub fn new() -> TestApp {
+        let app = TestApp::first_run();
+        app.invoke::<()>(
+            "complete_initial_setup",
+            json!({ "setup": { "company_name": "VitaSport Pruebas", "admin_password": ADMIN_PASSWORD } }),
+        )
+        .expect("No se pudo completar la configuración inicial");
+        app
+    }
+
+    /// Igual que `new`, con el admin conectado en la ventana principal.
+    pub fn with_admin() -> TestApp {
+        let app = TestApp::new();
+        app.login(&app.main, "admin", ADMIN_PASSWORD).expect("El admin debe poder entrar");
+        app
+    }
+
+    /// Abre otra ventana (cada ventana tiene su propia sesión).
+    pub fn open_window(&self, label: &str) -> Window<MockRuntime> {
+        WindowBuilder::new(&self.app, label, WindowUrl::default())
+            .build()
+            .expect("No se pudo abrir la ventana")
+    }
+
+    pub fn login(&self, window: &Window<MockRuntime>, username: &str, password: &str) -> Result<Value, String> {
+        self.invoke_in(window, "verify_login", json!({ "username": username, "password": password }))
+    }
+
+    /// Invoca un comando desde la ventana principal, como lo haría `invoke()` en la interfaz.
+    /// Los argumentos van en camelCase.
+    pub fn invoke<T: DeserializeOwned>(&self, cmd: &str, args: Value) -> Result<T, String> {
+        self.invoke_in(&self.main, cmd, args)
+    }
+
+    pub fn invoke_in<T: DeserializeOwned>(&self, window: &Window<MockRuntime>, cmd: &str, args: Value) -> Result<T, String> {
+        let payload = InvokePayload {
+            cmd: cmd.into(),
+            tauri_module: None,
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            inner: args,
+            invoke_key: Some(INVOKE_KEY.into()),
+        };
+        match get_ipc_response::<Value>(window, payload) {
+            Ok(value) => Ok(serde_json::from_value(value)
+                .unwrap_or_else(|e| panic!("Respuesta inesperada de `{}`: {}", cmd, e))),
+            Err(Value::String(message)) => Err(message),
+            Err(other) => Err(other.to_string()),
+        }
+    }
+
+    /// Ingresa stock a un producto y devuelve el id del movimiento.
+    pub fn add_stock(&self, product_id: i64, quantity: f64) -> i64 {
+        self.invoke(
+            "add_stock_movement",
+            json!({ "movement": { "product_id": product_id, "movement_type": "ingreso", "quantity": quantity } }),
+        )
+        .expect("No se pudo ingresar stock")
+    }
+
+    pub fn stock_of(&self, product_id: i64) -> f64 {
+        let balances: Vec<Value> = self.invoke("get_stock_balances", json!({})).unwrap();
+        balances
+            .iter()
+            .find(|b| b["product_id"] == product_id)
+            .and_then(|b| b["current_stock"].as_f64())
+            .unwrap_or(0.0)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) {
+        self.invoke::<()>("update_setting", json!({ "key": key, "value": value }))
+            .expect("No se pudo guardar el ajuste");
+    }
+
+    /// Ejecuta un comando de exportación y devuelve el contenido del archivo
+    /// generado. Los nombres llevan la hora en segundos, así que las
+    /// exportaciones se serializan para que dos pruebas no pisen el mismo archivo.
+    pub fn export(&self, cmd: &str, args: Value) -> String {
+        static EXPORTS: Mutex<()> = Mutex::new(());
+        let _guard = EXPORTS.lock().unwrap_or_else(|e| e.into_inner());
+        let path: String = self.invoke(cmd, args).unwrap_or_else(|e| panic!("Falló `{}`: {}", cmd, e));
+        let content = fs::read_to_string(&path).expect("No se pudo leer la exportación");
+        let _ = fs::remove_file(&path);
+        content
+    }
+}
+
+/// Carpeta temporal de exportaciones, una por proceso de pruebas. Lleva
+/// espacios y caracteres no ASCII, como las carpetas de usuario de Windows
+/// tipo `C:\Users\Señorita Pérez`.
+pub fn export_dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("vitasport pruebas Señorita Pérez {}", std::process::id()));
+        fs::create_dir_all(&dir).expect("No se pudo crear la carpeta de exportaciones");
+        std::env::set_var("VITASPORT_EXPORT_DIR", &dir);
+        dir
+    })
+}
+
+/// Carpeta temporal donde la app copia los adjuntos, una por proceso de pruebas.
+pub fn attachments_dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("vitasport adjuntos {}", std::process::id()));
+        fs::create_dir_all(&dir).expect("No se pudo crear la carpeta de adjuntos");
+        std::env::set_var("VITASPORT_ATTACHMENTS_DIR", &dir);
+        dir
+    })
+}
+
+/// Compara una exportación con `tests/golden/<name>`. Con `UPDATE_GOLDEN=1`
+/// reescribe el archivo de referencia en lugar de comparar.
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).expect("No se pudo escribir el archivo de referencia");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!("Falta {} (genéralo con UPDATE_GOLDEN=1 cargo test)", path.display())
+    });
+    assert_eq!(
+        actual.replace("\r\n", "\n"),
+        expected.replace("\r\n", "\n"),
+        "La exportación no coincide con {}",
+        name
+    );
+}
+
+// ============================================
+// FÁBRICAS
+// ============================================
+
+/// Producto con precio 100 y costo 60; los demás campos se ajustan con los métodos.
+pub struct ProductFactory(Value);
+
+impl ProductFactory {
+    pub fn new(name: &str) -> ProductFactory {
+        ProductFactory(json!({ "name": name, "sale_price": 100.0, "cost_price": 60.0 }))
+    }
+
+    pub fn sku(self, sku: &str) -> ProductFactory {
+        self.with("sku", json!(sku))
+    }
+
+    pub fn prices(self, sale_price: f64, cost_price: f64) -> ProductFactory {
+        self.with("sale_price", json!(sale_price)).with("cost_price", json!(cost_price))
+    }
+
+    pub fn category(self, category: &str) -> ProductFactory {
+        self.with("category", json!(category))
+    }
+
+    pub fn unit(self, unit: &str) -> ProductFactory {
+        self.with("unit", json!(unit))
+    }
+
+    /// Cualquier otro campo de `Product`.
+    pub fn with(mut self, field: &str, value: Value) -> ProductFactory {
+        self.0[field] = value;
+        self
+    }
+
+    pub fn create(self, app: &TestApp) -> Result<i64, String> {
+        let created: Value = app.invoke("add_product", json!({ "product": self.0 }))?;
+        Ok(created["id"].as_i64().expect("add_product sin id"))
+    }
+}
+
+/// Usuario con contraseña `clave123` y rol Vendedor.
+pub struct UserFactory {
+    pub username: String,
+    pub password: String,
+    role: String,
+}
+
+impl UserFactory {
+    pub fn new(username: &str) -> UserFactory {
+        UserFactory {
+            username: username.to_string(),
+            password: "clave123".to_string(),
+            role: "Vendedor".to_string(),
+        }
+    }
+
+    pub fn role(mut self, role: &str) -> UserFactory {
+        self.role = role.to_string();
+        self
+    }
+
+    pub fn create(&self, app: &TestApp) -> Result<i64, String> {
+        app.invoke(
+            "add_user",
+            json!({
+                "username": self.username,
+                "fullname": format!("Usuario {}", self.username),
+                "password": self.password,
+                "role": self.role,
+            }),
+        )
+    }
+
+    /// Crea el usuario y abre su sesión en una ventana nueva con su nombre.
+    pub fn create_and_login(&self, app: &TestApp) -> Window<MockRuntime> {
+        self.create(app).expect("No se pudo crear el usuario");
+        let window = app.open_window(&self.username);
+        app.login(&window, &self.username, &self.password).expect("El usuario nuevo debe poder entrar");
+        window
+    }
+}
+
+/// Venta de una línea al precio de lista del producto (100 por unidad) en `SALE_DATE`.
+pub struct SaleFactory(Value);
+
+impl SaleFactory {
+    pub fn new(product_id: i64, quantity: f64) -> SaleFactory {
+        SaleFactory(json!({
+            "product_id": product_id,
+            "quantity": quantity,
+            "sale_price": 100.0 * quantity,
+            "sale_date": SALE_DATE,
+        }))
+    }
+
+    /// Total de la línea (ya con descuento).
+    pub fn total(self, total: f64) -> SaleFactory {
+        self.with("sale_price", json!(total))
+    }
+
+    /// Autoriza un total distinto del de lista; solo lo acepta un Administrador.
+    pub fn override_price(self) -> SaleFactory {
+        self.with("override", json!(true))
+    }
+
+    pub fn date(self, date: &str) -> SaleFactory {
+        self.with("sale_date", json!(date))
+    }
+
+    pub fn with(mut self, field: &str, value: Value) -> SaleFactory {
+        self.0[field] = value;
+        self
+    }
+
+    pub fn create(self, app: &TestApp) -> Result<Value, String> {
+        app.invoke("add_sale", json!({ "sale": self.0 }))
+    }
+
+    pub fn create_in(self, app: &TestApp, window: &Window<MockRuntime>) -> Result<Value, String> {
+        app.invoke_in(window, "add_sale", json!({ "sale": self.0 }))
+    }
+}
+
+/// Carpeta temporal de imágenes de productos, una por proceso de pruebas.
+pub fn images_dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("vitasport imagenes {}", std::process::id()));
+        fs::create_dir_all(&dir).expect("No se pudo crear la carpeta de imágenes");
+        std::env::set_var("VITASPORT_IMAGES_DIR", &dir);
+        dir
+    })
+}
@@ -0,0 +1,192 @@
+//! Rutas de error de los comandos: deben rechazar la operación con un
+//! mensaje claro y no dejar datos a medias.
+
+mod common;
+
+use common::{ProductFactory, SaleFactory, TestApp};
+use serde_json::{json, Value};
+
+#[test]
+fn sale_without_enough_stock_is_rejected_and_rolled_back() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Glutamina 500g").create(&app).unwrap();
+    app.add_stock(product_id, 2.0);
+
+    let err = SaleFactory::new(product_id, 3.0).create(&app).unwrap_err();
+    assert_eq!(err, "Stock insuficiente. Disponible: 2, solicitado: 3");
+
+    assert_eq!(app.stock_of(product_id), 2.0);
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert!(sales.is_empty());
+}
+
+#[test]
+fn duplicate_sku_is_rejected() {
+    let app = TestApp::with_admin();
+    ProductFactory::new("BCAA Limón").sku("BCAA-01").create(&app).unwrap();
+
+    let err = ProductFactory::new("BCAA Naranja").sku("BCAA-01").create(&app).unwrap_err();
+    assert_eq!(err, "El SKU ya existe. Usa otro SKU o edita el producto existente.");
+
+    let products: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    assert_eq!(products.len(), 1);
+}
+
+#[test]
+fn invalid_dates_are_rejected() {
+    let app = TestApp::with_admin();
+
+    let err = app
+        .invoke::<Value>("archive_old_data", json!({ "beforeDate": "2023-02-29" }))
+        .unwrap_err();
+    assert_eq!(err, "Fecha de corte inválida. Usa el formato AAAA-MM-DD.");
+
+    let err = app
+        .invoke::<Value>("archive_old_data", json!({ "beforeDate": "15/03/2024" }))
+        .unwrap_err();
+    assert_eq!(err, "Fecha de corte inválida. Usa el formato AAAA-MM-DD.");
+
+    let err = app.invoke::<()>("close_period", json!({ "month": "2024-13" })).unwrap_err();
+    assert_eq!(err, "Mes inválido. Usa el formato AAAA-MM");
+
+    let err = app
+        .invoke::<Value>("issue_store_credit", json!({ "amount": 50.0, "expiryDate": "2024-04-31" }))
+        .unwrap_err();
+    assert_eq!(err, "Fecha de vencimiento inválida. Usa el formato AAAA-MM-DD.");
+}
+
+#[test]
+fn sale_in_closed_period_needs_adjustment_permission() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Multivitamínico").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    app.invoke::<()>("close_period", json!({ "month": "2024-03" })).unwrap();
+
+    let seller = common::UserFactory::new("caja2").create_and_login(&app);
+    let err = SaleFactory::new(product_id, 1.0).create_in(&app, &seller).unwrap_err();
+    assert!(err.starts_with("El periodo 2024-03 está cerrado"), "{}", err);
+    assert_eq!(app.stock_of(product_id), 5.0);
+}
+
+#[test]
+fn wrong_credentials_do_not_open_a_session() {
+    let app = TestApp::new();
+    assert_eq!(app.login(&app.main, "admin", "otra").unwrap_err(), "Contraseña incorrecta");
+    assert_eq!(app.login(&app.main, "nadie", "admin").unwrap_err(), "Usuario no encontrado");
+
+    let err = app.invoke::<Value>("get_active_sessions", json!({})).unwrap_err();
+    assert_eq!(err, "No hay una sesión activa en esta ventana");
+}
+
+#[test]
+fn fractional_quantity_on_unit_product_is_rejected() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Shaker").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+
+    let err = SaleFactory::new(product_id, 1.5).create(&app).unwrap_err();
+    assert_eq!(err, "El producto se vende por unidad; la cantidad debe ser entera");
+}
+
+#[test]
+fn product_validation_reports_every_problem_at_once() {
+    let app = TestApp::with_admin();
+    let err = ProductFactory::new("   ")
+        .prices(-10.0, -2.5)
+        .with("deposit_amount", json!(-1.0))
+        .with("expiry_date", json!("31/02/2025"))
+        .with("min_stock", json!(50))
+        .with("max_stock", json!(10))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(
+        err.lines().collect::<Vec<_>>(),
+        vec![
+            "name: El nombre es obligatorio",
+            "sale_price: El precio de venta no puede ser negativo (-10)",
+            "cost_price: El costo no puede ser negativo (-2.5)",
+            "deposit_amount: El depósito no puede ser negativo (-1)",
+            "expiry_date: La fecha de vencimiento '31/02/2025' no es válida. Usa el formato AAAA-MM-DD.",
+            "max_stock: El stock máximo (10) no puede ser menor que el mínimo (50)",
+        ]
+    );
+
+    // Fechas con formato correcto pero inexistentes tampoco pasan
+    let err = ProductFactory::new("Creatina").with("expiry_date", json!("2025-02-30")).create(&app).unwrap_err();
+    assert!(err.starts_with("expiry_date:"), "{}", err);
+
+    // Las mismas reglas al editar
+    let product_id = ProductFactory::new("Creatina").prices(90.0, 60.0).with("expiry_date", json!("2028-02-29")).create(&app).unwrap();
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": product_id })).unwrap();
+    product["name"] = json!("");
+    product["sale_price"] = json!(-1.0);
+    let err = app.invoke::<()>("update_product", json!({ "product": product })).unwrap_err();
+    let fields: Vec<&str> = err.lines().map(|l| l.split(':').next().unwrap()).collect();
+    assert_eq!(fields, vec!["name", "sale_price"]);
+}
+
+#[test]
+fn inconsistent_stock_levels_are_rejected_per_field() {
+    let app = TestApp::with_admin();
+    let err = ProductFactory::new("Colágeno")
+        .with("min_stock", json!(50))
+        .with("max_stock", json!(10))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(err, "max_stock: El stock máximo (10) no puede ser menor que el mínimo (50)");
+
+    let err = ProductFactory::new("Colágeno")
+        .with("min_stock", json!(-1))
+        .with("max_stock", json!(-5))
+        .create(&app)
+        .unwrap_err();
+    let fields: Vec<&str> = err.lines().map(|l| l.split(':').next().unwrap()).collect();
+    assert_eq!(fields, vec!["min_stock", "max_stock"]);
+
+    // Un máximo en 0 significa "sin máximo"
+    let product_id = ProductFactory::new("Colágeno")
+        .with("min_stock", json!(5))
+        .with("max_stock", json!(0))
+        .create(&app)
+        .unwrap();
+
+    let mut product: Value = app
+        .invoke::<Vec<Value>>("get_products", json!({}))
+        .unwrap()
+        .into_iter()
+        .find(|p| p["id"] == product_id)
+        .unwrap();
+    product["max_stock"] = json!(3);
+    let err = app.invoke::<()>("update_product", json!({ "product": product })).unwrap_err();
+    assert!(err.starts_with("max_stock:"), "{}", err);
+}
+
+#[test]
+fn sale_dates_are_validated_and_normalized() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Caseína 1kg").create(&app).unwrap();
+    app.add_stock(product_id, 20.0);
+    let sell = |date: &str| SaleFactory::new(product_id, 1.0).date(date).create(&app);
+
+    assert_eq!(
+        sell("15/01/2025").unwrap_err(),
+        "Fecha de venta inválida: '15/01/2025'. Usa AAAA-MM-DD o AAAA-MM-DDTHH:MM:SS"
+    );
+    assert!(sell("").unwrap_err().starts_with("Fecha de venta inválida"));
+    assert!(sell("2024-02-30").unwrap_err().starts_with("Fecha de venta inválida"));
+    assert!(sell("2024-03-04 25:00").unwrap_err().starts_with("Fecha de venta inválida"));
+    assert!(sell("2999-01-01").unwrap_err().starts_with("La fecha de venta 2999-01-01 está en el futuro"));
+
+    let with_time = sell("2024-03-04 09:15").unwrap();
+    sell(" 2024-03-05 ").unwrap();
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    let mut dates: Vec<&str> = sales.iter().map(|s| s["sale_date"].as_str().unwrap()).collect();
+    dates.sort();
+    assert_eq!(dates, ["2024-03-04T09:15:00.000Z", "2024-03-05"]);
+    assert_eq!(app.stock_of(product_id), 18.0);
+
+    let mut sale = sales.into_iter().find(|s| s["id"] == with_time["id"]).unwrap();
+    sale["sale_date"] = json!("04/03/2024");
+    let err = app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap_err();
+    assert!(err.starts_with("Fecha de venta inválida"));
+}
@@ -0,0 +1,900 @@
+//! Flujos completos a través de los comandos: inicio de sesión, alta de
+//! producto, ingreso de stock, venta y exportación.
+
+mod common;
+
+use common::{days_ago, days_ahead, ProductFactory, SaleFactory, TestApp, UserFactory, ADMIN_PASSWORD, SALE_DATE};
+use serde_json::{json, Value};
+
+#[test]
+fn login_add_product_stock_sell_export() {
+    let app = TestApp::new();
+
+    let user = app.login(&app.main, "admin", ADMIN_PASSWORD).unwrap();
+    assert_eq!(user["role"], "Administrador");
+
+    let product_id = ProductFactory::new("Proteína Whey 1kg")
+        .sku("WHEY-1KG")
+        .category("Proteínas")
+        .create(&app)
+        .unwrap();
+    app.add_stock(product_id, 10.0);
+    assert_eq!(app.stock_of(product_id), 10.0);
+
+    let sale = SaleFactory::new(product_id, 3.0).create(&app).unwrap();
+    assert!(sale["id"].as_i64().unwrap() > 0);
+    assert_eq!(sale["below_cost"], false);
+    assert_eq!(app.stock_of(product_id), 7.0);
+
+    let totals: Value = app
+        .invoke("get_sales_totals", json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }))
+        .unwrap();
+    assert_eq!(totals["total_units"], 3.0);
+    assert_eq!(totals["total_revenue"], 300.0);
+
+    let csv = app.export(
+        "export_sales_report",
+        json!({ "startDate": SALE_DATE, "endDate": SALE_DATE }),
+    );
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1], format!("{},{},3,300.00,,,{},1,,,V-000001,efectivo,,", sale["id"], product_id, SALE_DATE));
+}
+
+#[test]
+fn sales_are_attributed_to_the_window_session() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+
+    let seller = UserFactory::new("caja1");
+    let seller_window = seller.create_and_login(&app);
+    SaleFactory::new(product_id, 1.0).create_in(&app, &seller_window).unwrap();
+    SaleFactory::new(product_id, 1.0).create(&app).unwrap();
+
+    let users: Vec<Value> = app.invoke("get_users", json!({})).unwrap();
+    let seller_id = users.iter().find(|u| u["username"] == "caja1").unwrap()["id"].clone();
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    let mut created_by: Vec<Value> = sales.iter().map(|s| s["created_by"].clone()).collect();
+    created_by.sort_by_key(|v| v.as_i64());
+    assert_eq!(created_by, vec![json!(1), seller_id]);
+
+    // El vendedor no puede cambiar ajustes aunque el admin esté conectado en otra ventana
+    let err = app
+        .invoke_in::<()>(&seller_window, "update_setting", json!({ "key": "below_cost_policy", "value": "bloquear" }))
+        .unwrap_err();
+    assert!(err.contains("Solo un Administrador"), "{}", err);
+}
+
+#[test]
+fn weighed_products_accept_fractional_quantities() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Avena a granel")
+        .unit("kg")
+        .prices(8.0, 5.0)
+        .create(&app)
+        .unwrap();
+    app.add_stock(product_id, 2.5);
+
+    SaleFactory::new(product_id, 0.75).total(6.0).create(&app).unwrap();
+    assert!((app.stock_of(product_id) - 1.75).abs() < 1e-9);
+}
+
+#[test]
+fn logout_closes_only_the_window_session() {
+    let app = TestApp::with_admin();
+    let other = app.open_window("reportes");
+    app.login(&other, "admin", ADMIN_PASSWORD).unwrap();
+
+    app.invoke_in::<()>(&other, "logout", json!({})).unwrap();
+    let sessions: Vec<Value> = app.invoke("get_active_sessions", json!({})).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["window_label"], "main");
+}
+
+#[test]
+fn admin_lists_and_closes_sessions_of_other_windows() {
+    let app = TestApp::with_admin();
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+
+    let sessions: Vec<Value> = app.invoke("get_active_sessions", json!({})).unwrap();
+    let labels: Vec<&str> = sessions.iter().map(|s| s["window_label"].as_str().unwrap()).collect();
+    assert_eq!(labels, ["caja1", "main"]);
+    assert_eq!(sessions[0]["username"], "caja1");
+    let seller_session = sessions[0]["session_id"].clone();
+    let admin_session = sessions[1]["session_id"].clone();
+
+    let only_admin = "Solo un Administrador puede realizar esta acción";
+    assert_eq!(app.invoke_in::<Value>(&seller, "get_active_sessions", json!({})).unwrap_err(), only_admin);
+    let err = app
+        .invoke_in::<()>(&seller, "force_logout", json!({ "sessionId": admin_session }))
+        .unwrap_err();
+    assert_eq!(err, only_admin);
+    assert_eq!(app.invoke_in::<()>(&seller, "reset_database", json!({})).unwrap_err(), only_admin);
+
+    app.invoke::<()>("force_logout", json!({ "sessionId": seller_session })).unwrap();
+    let sessions: Vec<Value> = app.invoke("get_active_sessions", json!({})).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["window_label"], "main");
+    let err = app.invoke::<()>("force_logout", json!({ "sessionId": seller_session })).unwrap_err();
+    assert_eq!(err, "Sesión no encontrada");
+    let err = app.invoke_in::<Value>(&seller, "get_active_sessions", json!({})).unwrap_err();
+    assert_eq!(err, "No hay una sesión activa en esta ventana");
+}
+
+#[test]
+fn only_an_admin_manages_users_and_roles_must_exist() {
+    let app = TestApp::with_admin();
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+    let kiosk = app.open_window("kiosco");
+    let new_user = |role: &str| {
+        json!({ "username": "intruso", "fullname": "Intruso", "password": "clave123", "role": role })
+    };
+
+    let err = app.invoke_in::<i64>(&kiosk, "add_user", new_user("Administrador")).unwrap_err();
+    assert_eq!(err, "No hay una sesión activa en esta ventana");
+    let err = app.invoke_in::<i64>(&seller, "add_user", new_user("Administrador")).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+    let err = app.invoke::<i64>("add_user", new_user("Superusuario")).unwrap_err();
+    assert_eq!(err, "Rol desconocido: 'Superusuario'. Roles válidos: Administrador, Vendedor, Almacenero, Auditor");
+
+    let id: i64 = app.invoke("add_user", new_user(" vendedor ")).unwrap();
+    let users: Vec<Value> = app.invoke("get_users", json!({})).unwrap();
+    assert_eq!(users.iter().find(|u| u["id"] == id).unwrap()["role"], "Vendedor");
+    let err = app
+        .invoke_in::<()>(&seller, "delete_user", json!({ "id": id }))
+        .unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+
+    // Un cambio de rol alcanza a la sesión abierta; borrar al usuario la cierra
+    let seller_id = users.iter().find(|u| u["username"] == "caja1").unwrap()["id"].clone();
+    app.invoke::<()>(
+        "update_user",
+        json!({ "id": seller_id, "username": "caja1", "fullname": "Caja 1", "role": "Administrador" }),
+    )
+    .unwrap();
+    assert_eq!(app.invoke_in::<Vec<Value>>(&seller, "get_active_sessions", json!({})).unwrap().len(), 2);
+    app.invoke::<()>("delete_user", json!({ "id": seller_id })).unwrap();
+    let err = app.invoke_in::<Value>(&seller, "get_active_sessions", json!({})).unwrap_err();
+    assert_eq!(err, "No hay una sesión activa en esta ventana");
+}
+
+#[test]
+fn double_submitted_sale_is_found_and_resolved_by_hand() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    let sell = |date: &str| SaleFactory::new(whey, 1.0).date(date).create(&app).unwrap()["id"].as_i64().unwrap();
+    let first = sell("2024-03-15T14:03:22.120Z");
+    let second = sell("2024-03-15T14:03:23.480Z");
+    sell("2024-03-15T16:00:00.000Z");
+    // Con fecha sola (importadas) no se comparan
+    sell("2024-03-15");
+    sell("2024-03-15");
+    assert_eq!(app.stock_of(whey), 5.0);
+
+    let pairs: Vec<Value> = app.invoke("find_probable_duplicates", json!({ "windowSeconds": 5 })).unwrap();
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(pairs[0]["first"]["id"], first);
+    assert_eq!(pairs[0]["second"]["id"], second);
+    assert_eq!(pairs[0]["seconds_apart"], 1.36);
+    assert_eq!(pairs[0]["created_by"], "admin");
+    assert!(pairs[0]["second"]["movement_id"].is_i64());
+    assert_ne!(pairs[0]["first"]["movement_id"], pairs[0]["second"]["movement_id"]);
+    // Buscar no borra nada
+    assert_eq!(app.stock_of(whey), 5.0);
+
+    let err = app
+        .invoke::<()>("resolve_duplicate", json!({ "keepId": first, "removeId": 99 }))
+        .unwrap_err();
+    assert_eq!(err, "Venta #99 no encontrada");
+    app.invoke::<()>("resolve_duplicate", json!({ "keepId": first, "removeId": second })).unwrap();
+    assert_eq!(app.stock_of(whey), 6.0);
+    let pairs: Vec<Value> = app.invoke("find_probable_duplicates", json!({ "windowSeconds": 5 })).unwrap();
+    assert!(pairs.is_empty());
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    let entry = log.iter().find(|e| e["action"] == "resolve_duplicate").unwrap();
+    assert_eq!(entry["entity_id"], second);
+}
+
+#[test]
+fn installment_payments_go_to_the_oldest_open_installment() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Bicicleta fija").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+
+    let err = SaleFactory::new(product_id, 3.0)
+        .date(&days_ago(40))
+        .with("installments", json!([{ "due_date": days_ago(10), "amount": 100.0 }]))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(err, "Las cuotas suman 100.00 y el total de la venta es 300.00");
+
+    let sale = SaleFactory::new(product_id, 3.0)
+        .date(&days_ago(40))
+        .with("customer", json!("Laura Gómez"))
+        .with(
+            "installments",
+            json!([
+                { "due_date": days_ago(10), "amount": 100.0 },
+                { "due_date": days_ago(0), "amount": 100.0 },
+                { "due_date": "2999-01-01", "amount": 100.0 },
+            ]),
+        )
+        .create(&app)
+        .unwrap();
+    let sale_id = sale["id"].as_i64().unwrap();
+
+    let upcoming: Vec<Value> = app.invoke("get_upcoming_installments", json!({ "days": 30 })).unwrap();
+    assert_eq!(upcoming.len(), 2);
+    assert_eq!(upcoming[0]["overdue"], true);
+    assert_eq!(upcoming[0]["customer"], "Laura Gómez");
+    assert_eq!(upcoming[1]["overdue"], false);
+
+    // 150 cubren la cuota vencida y la mitad de la siguiente
+    let schedule: Vec<Value> = app
+        .invoke("register_installment_payment", json!({ "saleId": sale_id, "amount": 150.0 }))
+        .unwrap();
+    let pending: Vec<f64> = schedule.iter().map(|c| c["pending"].as_f64().unwrap()).collect();
+    assert_eq!(pending, vec![0.0, 50.0, 100.0]);
+    assert!(schedule[0]["paid_at"].is_string());
+    assert!(schedule[1]["paid_at"].is_null());
+
+    let err = app
+        .invoke::<Vec<Value>>("register_installment_payment", json!({ "saleId": sale_id, "amount": 200.0 }))
+        .unwrap_err();
+    assert_eq!(err, "El pago (200.00) supera lo pendiente (150.00)");
+
+    let upcoming: Vec<Value> = app.invoke("get_upcoming_installments", json!({})).unwrap();
+    assert_eq!(upcoming.len(), 1);
+    assert_eq!(upcoming[0]["pending"], 50.0);
+}
+
+#[test]
+fn first_run_setup_replaces_the_default_admin_password() {
+    let app = TestApp::first_run();
+    assert!(app.invoke::<bool>("is_first_run", json!({})).unwrap());
+
+    let err = app.login(&app.main, "admin", "admin").unwrap_err();
+    assert!(err.starts_with("La contraseña predeterminada del administrador está deshabilitada"), "{}", err);
+
+    let setup = |password: &str, template: &str| {
+        app.invoke::<()>(
+            "complete_initial_setup",
+            json!({ "setup": {
+                "company_name": "VitaSport Centro",
+                "company_phone": "011 4444-5555",
+                "admin_password": password,
+                "template": template,
+            } }),
+        )
+    };
+    assert_eq!(
+        setup("corta", "").unwrap_err(),
+        "La contraseña del administrador debe tener al menos 8 caracteres"
+    );
+    assert!(setup("una-clave-larga", "ferreteria").unwrap_err().starts_with("Plantilla desconocida: ferreteria"));
+    setup("una-clave-larga", "suplementos").unwrap();
+
+    assert!(!app.invoke::<bool>("is_first_run", json!({})).unwrap());
+    assert_eq!(setup("otra-clave-larga", "").unwrap_err(), "La configuración inicial ya se completó");
+    assert_eq!(app.login(&app.main, "admin", "admin").unwrap_err(), "Contraseña incorrecta");
+    app.login(&app.main, "admin", "una-clave-larga").unwrap();
+
+    let settings: Value = app.invoke("get_settings", json!({})).unwrap();
+    assert_eq!(settings["company_name"], "VitaSport Centro");
+    assert_eq!(settings["company_phone"], "011 4444-5555");
+    assert!(settings["catalog_categories"].as_str().unwrap().starts_with("Proteínas, Creatinas"));
+    assert_eq!(settings["setup_completed"], "true");
+}
+
+#[test]
+fn reset_database_clears_priced_products_and_returns_to_the_setup_wizard() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 3.0);
+    SaleFactory::new(whey, 1.0).create(&app).unwrap();
+
+    app.invoke::<()>("reset_database", json!({})).unwrap();
+    assert!(app.invoke::<bool>("is_first_run", json!({})).unwrap());
+    app.invoke::<()>(
+        "complete_initial_setup",
+        json!({ "setup": { "company_name": "VitaSport Centro", "admin_password": ADMIN_PASSWORD } }),
+    )
+    .unwrap();
+    app.login(&app.main, "admin", ADMIN_PASSWORD).unwrap();
+    assert!(app.invoke::<Vec<Value>>("get_products", json!({})).unwrap().is_empty());
+    // La lista minorista vuelve a crearse, vacía
+    let lists: Vec<Value> = app.invoke("get_price_lists", json!({})).unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0]["name"], "Minorista");
+    assert_eq!(lists[0]["product_count"], 0);
+}
+
+#[test]
+fn upgraded_base_past_its_grace_period_falls_back_to_the_setup_wizard() {
+    // Base de una versión sin asistente, abierta por primera vez hace 40 días
+    let db = vitasport_lib::init_memory_database().unwrap();
+    db.execute("INSERT INTO products (name, sale_price) VALUES ('Whey 1kg', 100)", []).unwrap();
+    db.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('setup_grace_from', date('now', '-40 days'))",
+        [],
+    )
+    .unwrap();
+    let app = TestApp::from_database(db);
+
+    let err = app.login(&app.main, "admin", "admin").unwrap_err();
+    assert!(err.starts_with("La contraseña predeterminada del administrador está deshabilitada"), "{}", err);
+    assert!(app.invoke::<bool>("is_first_run", json!({})).unwrap());
+
+    app.invoke::<()>(
+        "complete_initial_setup",
+        json!({ "setup": { "company_name": "VitaSport Centro", "admin_password": "una-clave-larga" } }),
+    )
+    .unwrap();
+    assert!(!app.invoke::<bool>("is_first_run", json!({})).unwrap());
+    app.login(&app.main, "admin", "una-clave-larga").unwrap();
+}
+
+#[test]
+fn bundle_sales_deduct_component_stock() {
+    let app = TestApp::with_admin();
+    let shaker = ProductFactory::new("Shaker").create(&app).unwrap();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let creatine = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    let pack = ProductFactory::new("Pack Inicio").prices(900.0, 500.0).create(&app).unwrap();
+    app.add_stock(shaker, 10.0);
+    app.add_stock(whey, 3.0);
+    app.add_stock(creatine, 1.0);
+
+    let components: Vec<Value> = app
+        .invoke(
+            "set_product_bundle",
+            json!({ "bundleId": pack, "components": [
+                { "component_id": shaker, "quantity": 1 },
+                { "component_id": whey, "quantity": 1 },
+                { "component_id": creatine, "quantity": 1 },
+            ] }),
+        )
+        .unwrap();
+    assert_eq!(components.len(), 3);
+    let err = app
+        .invoke::<i64>(
+            "add_stock_movement",
+            json!({ "movement": { "product_id": pack, "movement_type": "ingreso", "quantity": 5 } }),
+        )
+        .unwrap_err();
+    assert_eq!(err, "Un pack no tiene stock propio; registra el movimiento en sus componentes");
+
+    // Dos packs: falta creatina y nada se descuenta
+    let err = SaleFactory::new(pack, 2.0).total(1800.0).create(&app).unwrap_err();
+    assert_eq!(err, "Stock insuficiente para el pack. Creatina 300g: disponible 1, necesario 2");
+    assert_eq!(app.stock_of(shaker), 10.0);
+
+    let sale = SaleFactory::new(pack, 1.0).total(900.0).create(&app).unwrap();
+    assert_eq!(app.stock_of(shaker), 9.0);
+    assert_eq!(app.stock_of(whey), 2.0);
+    assert_eq!(app.stock_of(creatine), 0.0);
+    let balances: Vec<Value> = app.invoke("get_stock_balances", json!({})).unwrap();
+    assert!(balances.iter().all(|b| b["product_id"] != pack));
+
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.len(), 1);
+    assert_eq!(sales[0]["id"], sale["id"]);
+    assert_eq!(sales[0]["product_id"], pack);
+}
+
+#[test]
+fn wholesale_sales_take_the_price_from_the_price_list() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").prices(250.0, 150.0).create(&app).unwrap();
+    let shaker = ProductFactory::new("Shaker").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.add_stock(shaker, 10.0);
+
+    // La lista minorista se crea con los precios de venta de la ficha
+    let lists: Vec<Value> = app.invoke("get_price_lists", json!({})).unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0]["name"], "Minorista");
+    let retail = lists[0]["id"].as_i64().unwrap();
+    let wholesale: i64 = app.invoke("add_price_list", json!({ "priceList": { "name": "  Mayorista " } })).unwrap();
+    let err = app.invoke::<i64>("add_price_list", json!({ "priceList": { "name": "mayorista" } })).unwrap_err();
+    assert_eq!(err, "Ya existe la lista de precios Mayorista");
+
+    app.invoke::<()>("set_product_price", json!({ "productId": whey, "priceListId": wholesale, "price": 200.0 })).unwrap();
+    let err = app
+        .invoke::<()>("set_product_price", json!({ "productId": whey, "priceListId": retail, "price": 1.0 }))
+        .unwrap_err();
+    assert_eq!(err, "Los precios de la lista Minorista se cambian con el precio de venta del producto");
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    product["sale_price"] = json!(260.0);
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    let price = |list: i64| app.invoke::<f64>("get_product_price", json!({ "productId": whey, "priceListId": list }));
+    assert_eq!(price(retail), Ok(260.0));
+    assert_eq!(price(wholesale), Ok(200.0));
+
+    let sell = |product_id: i64, price_list_id: Option<i64>| -> Result<Value, String> {
+        app.invoke(
+            "add_sale",
+            json!({ "sale": {
+                "product_id": product_id,
+                "quantity": 2.0,
+                "discount": 10.0,
+                "sale_date": SALE_DATE,
+                "price_list_id": price_list_id,
+            } }),
+        )
+    };
+    let sale = sell(whey, Some(wholesale)).unwrap();
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    let stored = sales.iter().find(|s| s["id"] == sale["id"]).unwrap();
+    assert_eq!(stored["sale_price"], 360.0);
+    assert_eq!(stored["price_list_id"], wholesale);
+    // El precio de la lista es la referencia: no cuenta como precio modificado
+    let overrides: Vec<Value> = app.invoke("get_price_overrides", json!({})).unwrap();
+    assert!(overrides.is_empty());
+
+    assert_eq!(sell(shaker, Some(wholesale)).unwrap_err(), "El producto no tiene precio en la lista Mayorista");
+    // Sin lista ni precio se cobra el de la ficha
+    let sale = sell(shaker, None).unwrap();
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.iter().find(|s| s["id"] == sale["id"]).unwrap()["sale_price"], 180.0);
+    let err = app.invoke::<()>("delete_price_list", json!({ "id": wholesale })).unwrap_err();
+    assert_eq!(err, "La lista Mayorista se usó en 1 venta; no se puede eliminar");
+    let err = app.invoke::<()>("delete_price_list", json!({ "id": retail })).unwrap_err();
+    assert_eq!(err, "La lista Minorista no se puede eliminar");
+}
+
+#[test]
+fn receipts_are_numbered_per_series_without_gaps() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    let next = |series: Value| app.invoke::<String>("get_next_receipt_number", json!({ "series": series })).unwrap();
+    assert_eq!(next(Value::Null), "V-000001");
+
+    let first = SaleFactory::new(whey, 1.0).create(&app).unwrap();
+    assert_eq!(first["receipt_number"], "V-000001");
+    // La venta rechazada no consume número
+    SaleFactory::new(whey, 50.0).create(&app).unwrap_err();
+    assert_eq!(next(Value::Null), "V-000002");
+    let receipt: Value = app
+        .invoke(
+            "create_sale",
+            json!({ "sale": { "sale_date": SALE_DATE, "receipt_series": "b", "items": [{ "product_id": whey, "quantity": 1.0 }] } }),
+        )
+        .unwrap();
+    assert_eq!(receipt["receipt_number"], "B-000001");
+    assert_eq!(next(json!("B")), "B-000002");
+    let second = SaleFactory::new(whey, 1.0).create(&app).unwrap();
+    assert_eq!(second["receipt_number"], "V-000002");
+
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    let mut numbers: Vec<&str> = sales.iter().map(|s| s["receipt_number"].as_str().unwrap()).collect();
+    numbers.sort();
+    assert_eq!(numbers, ["B-000001", "V-000001", "V-000002"]);
+    let err = app.invoke::<String>("get_next_receipt_number", json!({ "series": "F-01" })).unwrap_err();
+    assert_eq!(err, "Serie de comprobante 'F-01' no válida: usa de 1 a 4 letras o números");
+}
+
+#[test]
+fn multi_item_sales_share_one_receipt() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let bar = ProductFactory::new("Barra proteica").prices(12.5, 7.0).create(&app).unwrap();
+    app.add_stock(whey, 3.0);
+    app.add_stock(bar, 10.0);
+
+    let create = |items: Value| -> Result<Value, String> {
+        app.invoke(
+            "create_sale",
+            json!({ "sale": { "customer": "Gimnasio Titán", "channel": "tienda", "sale_date": SALE_DATE, "override": true, "items": items } }),
+        )
+    };
+    // Se informan todos los faltantes y no se descuenta nada
+    let err = create(json!([
+        { "product_id": whey, "quantity": 2.0 },
+        { "product_id": bar, "quantity": 20.0 },
+        { "product_id": whey, "quantity": 2.0 },
+    ]))
+    .unwrap_err();
+    assert_eq!(err, "Stock insuficiente. Whey 1kg: disponible 3, solicitado 4; Barra proteica: disponible 10, solicitado 20");
+    assert_eq!(app.stock_of(whey), 3.0);
+    assert_eq!(create(json!([])).unwrap_err(), "La venta no tiene productos");
+
+    let receipt = create(json!([
+        { "product_id": whey, "quantity": 2.0, "discount": 10.0 },
+        { "product_id": bar, "quantity": 4.0, "unit_price": 12.0 },
+    ]))
+    .unwrap();
+    assert_eq!(receipt["customer"], "Gimnasio Titán");
+    assert_eq!(receipt["total"], 228.0);
+    let items = receipt["items"].as_array().unwrap();
+    let lines: Vec<(i64, f64, f64)> = items
+        .iter()
+        .map(|i| (i["product_id"].as_i64().unwrap(), i["unit_price"].as_f64().unwrap(), i["line_total"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(lines, vec![(whey, 100.0, 180.0), (bar, 12.0, 48.0)]);
+    assert_eq!(app.stock_of(whey), 1.0);
+    assert_eq!(app.stock_of(bar), 6.0);
+    // Cada línea es también una venta con su egreso
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.len(), 2);
+    assert!(sales.iter().any(|s| s["id"] == items[0]["id"] && s["sale_price"] == 180.0));
+
+    // add_sale sigue funcionando: un comprobante de una línea
+    let single = SaleFactory::new(whey, 1.0).total(100.0).create(&app).unwrap();
+    assert_ne!(single["header_id"], receipt["id"]);
+    let totals: Value = app.invoke("get_sales_totals", json!({})).unwrap();
+    assert_eq!(totals["total_units"], 7.0);
+    assert_eq!(totals["total_revenue"], 328.0);
+    let by_product: Vec<Value> = app.invoke("get_sales_by_product", json!({})).unwrap();
+    assert_eq!(by_product[0]["product_id"], whey);
+    assert_eq!(by_product[0]["total_qty"], 3.0);
+    assert_eq!(by_product[0]["total_revenue"], 280.0);
+}
+
+#[test]
+fn editing_a_sale_reconciles_its_stock() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let creatine = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.add_stock(creatine, 2.0);
+    let sold = SaleFactory::new(whey, 2.0).total(200.0).create(&app).unwrap();
+    let stored = |id: &Value| -> Value {
+        let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+        sales.into_iter().find(|s| s["id"] == *id).unwrap()
+    };
+    let edit = |changes: Value| -> Result<Value, String> {
+        let mut sale = stored(&sold["id"]);
+        for (field, value) in changes.as_object().unwrap() {
+            sale[field] = value.clone();
+        }
+        app.invoke("update_sale", json!({ "sale": sale }))
+    };
+
+    edit(json!({ "quantity": 5.0, "sale_price": 500.0 })).unwrap();
+    assert_eq!(app.stock_of(whey), 5.0);
+    let err = edit(json!({ "quantity": 20.0 })).unwrap_err();
+    assert_eq!(err, "Stock insuficiente. Disponible: 5, solicitado: 15");
+    assert_eq!(app.stock_of(whey), 5.0);
+
+    // Otro producto: el stock vuelve al anterior y sale del nuevo
+    let err = edit(json!({ "product_id": creatine, "quantity": 3.0 })).unwrap_err();
+    assert_eq!(err, "Stock insuficiente. Disponible: 2, solicitado: 3");
+    // Otro precio que el de lista necesita la autorización, también al editar
+    let err = edit(json!({ "product_id": creatine, "quantity": 2.0, "sale_price": 180.0 })).unwrap_err();
+    assert!(err.starts_with("El precio de la venta debe ser 200.00"), "{}", err);
+    edit(json!({ "product_id": creatine, "quantity": 2.0, "sale_price": 180.0, "override": true })).unwrap();
+    assert_eq!(app.stock_of(whey), 10.0);
+    assert_eq!(app.stock_of(creatine), 0.0);
+    let sale = stored(&sold["id"]);
+    assert_eq!(sale["product_id"], creatine);
+    assert_eq!(sale["sale_price"], 180.0);
+    let by_product: Vec<Value> = app.invoke("get_sales_by_product", json!({})).unwrap();
+    assert_eq!(by_product.len(), 1);
+    assert_eq!(by_product[0]["product_id"], creatine);
+    assert_eq!(by_product[0]["total_revenue"], 180.0);
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    let entries: Vec<&Value> = log.iter().filter(|e| e["action"] == "update_sale").collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries.iter().filter(|e| e["entity_id"] == sold["id"]).count(), 2);
+
+    // En un periodo cerrado solo edita quien tiene el permiso de ajuste
+    app.invoke::<()>("close_period", json!({ "month": "2024-03" })).unwrap();
+    let mut sale = stored(&sold["id"]);
+    sale["customer"] = json!("Gimnasio Titán");
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+    let err = app.invoke_in::<Value>(&seller, "update_sale", json!({ "sale": sale })).unwrap_err();
+    assert!(err.starts_with("El periodo 2024-03 está cerrado"), "{}", err);
+    app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap();
+    assert_eq!(app.stock_of(creatine), 0.0);
+
+    // Cada ajuste queda con el comando que lo hizo
+    app.invoke::<Vec<Value>>("void_sale", json!({ "saleId": sold["id"], "reason": "Venta duplicada" })).unwrap();
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({ "flag": "ajuste_post_cierre" })).unwrap();
+    let actions: Vec<&Value> = log.iter().filter(|e| e["entity_id"] == sold["id"]).map(|e| &e["action"]).collect();
+    assert_eq!(actions, ["void_sale", "update_sale"]);
+}
+
+#[test]
+fn voided_sales_restore_stock_and_leave_the_reports() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    let today = days_ago(0);
+    let wrong = SaleFactory::new(whey, 3.0).total(300.0).date(&today).create(&app).unwrap();
+    SaleFactory::new(whey, 1.0).total(100.0).date(&today).create(&app).unwrap();
+    assert_eq!(app.stock_of(whey), 6.0);
+
+    let void = |reason: &str| app.invoke::<Vec<Value>>("void_sale", json!({ "saleId": wrong["id"], "reason": reason }));
+    assert_eq!(void("  ").unwrap_err(), "Indica el motivo de la anulación");
+    let balances = void("Producto equivocado").unwrap();
+    assert_eq!(balances, vec![json!({ "product_id": whey, "current_stock": 9.0 })]);
+    assert_eq!(app.stock_of(whey), 9.0);
+    assert_eq!(void("Otra vez").unwrap_err(), format!("La venta #{} ya fue anulada", wrong["id"]));
+
+    let totals: Value = app.invoke("get_sales_totals", json!({})).unwrap();
+    assert_eq!(totals["total_units"], 1.0);
+    assert_eq!(totals["total_revenue"], 100.0);
+    let by_product: Vec<Value> = app.invoke("get_sales_by_product", json!({})).unwrap();
+    assert_eq!(by_product[0]["total_revenue"], 100.0);
+    let trend: Vec<Value> = app.invoke("get_sales_trend", json!({})).unwrap();
+    assert_eq!(trend.last().unwrap()["total_revenue"], 100.0);
+    let csv = app.export("export_sales_report", json!({ "startDate": today, "endDate": today }));
+    assert_eq!(csv.lines().count(), 2, "{}", csv);
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+
+    // El ingreso compensatorio cita la venta y el motivo
+    let movements: Vec<Value> = app.invoke("get_stock_movements", json!({})).unwrap();
+    let note = format!("Anulación de la venta #{}: Producto equivocado", wrong["id"]);
+    assert!(movements.iter().any(|m| m["movement_type"] == "ingreso" && m["note"] == note.as_str()));
+    let err = app.invoke::<Value>("update_sale", json!({ "sale": { "id": wrong["id"], "product_id": whey, "quantity": 1.0, "sale_date": today } }));
+    assert_eq!(err.unwrap_err(), format!("La venta #{} está anulada", wrong["id"]));
+}
+
+#[test]
+fn registered_customers_follow_their_sales() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    let add = |customer: Value| app.invoke::<i64>("add_customer", json!({ "customer": customer }));
+    let gym = add(json!({ "name": "  Gimnasio   Titán ", "document_id": "20123456789", "phone": "987654321" })).unwrap();
+    let walk_in = add(json!({ "name": "Ana Pérez", "email": "" })).unwrap();
+    let err = add(json!({ "name": "Titán SAC", "document_id": "20123456789" })).unwrap_err();
+    assert_eq!(err, "El documento 20123456789 ya es del cliente Gimnasio Titán");
+    assert_eq!(add(json!({ "name": "Luis", "email": "luis" })).unwrap_err(), "Correo inválido: luis");
+
+    let search = |query: &str| -> Vec<String> {
+        let found: Vec<Value> = app.invoke("search_customers", json!({ "query": query })).unwrap();
+        found.iter().map(|c| c["name"].as_str().unwrap().to_string()).collect()
+    };
+    assert_eq!(search("titán"), ["Gimnasio Titán"]);
+    assert_eq!(search("98765"), ["Gimnasio Titán"]);
+    assert!(search("%").is_empty());
+
+    // El nombre registrado reemplaza al texto libre
+    SaleFactory::new(whey, 1.0).with("customer", json!("otro")).with("customer_id", json!(gym)).create(&app).unwrap();
+    let err = SaleFactory::new(whey, 1.0).with("customer_id", json!(999)).create(&app).unwrap_err();
+    assert_eq!(err, "Cliente #999 no encontrado");
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales[0]["customer_id"], gym);
+    assert_eq!(sales[0]["customer"], "Gimnasio Titán");
+
+    app.invoke::<()>(
+        "update_customer",
+        json!({ "customer": { "id": gym, "name": "Gimnasio Titán Norte", "document_id": "20123456789" } }),
+    )
+    .unwrap();
+    let csv = app.export("export_sales_report", json!({}));
+    assert!(csv.lines().nth(1).unwrap().ends_with(&format!("Gimnasio Titán Norte,V-000001,efectivo,{},", gym)), "{}", csv);
+
+    // Con ventas queda desactivado; sin ventas se borra
+    assert!(!app.invoke::<bool>("delete_customer", json!({ "id": gym })).unwrap());
+    assert!(app.invoke::<bool>("delete_customer", json!({ "id": walk_in })).unwrap());
+    let active: Vec<Value> = app.invoke("get_customers", json!({})).unwrap();
+    assert!(active.is_empty());
+    let all: Vec<Value> = app.invoke("get_customers", json!({ "includeInactive": true })).unwrap();
+    assert_eq!(all.len(), 1);
+    assert_eq!((all[0]["active"].as_bool(), all[0]["sales_count"].as_i64()), (Some(false), Some(1)));
+    assert!(search("titán").is_empty());
+    let err = SaleFactory::new(whey, 1.0).with("customer_id", json!(gym)).create(&app).unwrap_err();
+    assert_eq!(err, "El cliente Gimnasio Titán Norte está desactivado");
+}
+
+#[test]
+fn customer_history_pages_sales_and_summarizes_them() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").prices(100.0, 60.0).category("Proteínas").create(&app).unwrap();
+    let creatine = ProductFactory::new("Creatina 300g").prices(80.0, 40.0).category("Creatinas").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.add_stock(creatine, 10.0);
+    let gym: i64 = app.invoke("add_customer", json!({ "customer": { "name": "Gimnasio Titán" } })).unwrap();
+
+    SaleFactory::new(creatine, 1.0).total(80.0).date(&days_ago(42)).with("customer_id", json!(gym)).create(&app).unwrap();
+    // Un comprobante de dos líneas es una sola compra
+    app.invoke::<Value>(
+        "create_sale",
+        json!({ "sale": { "customer_id": gym, "sale_date": days_ago(3), "items": [
+            { "product_id": whey, "quantity": 1.0 },
+            { "product_id": creatine, "quantity": 2.0 },
+        ] } }),
+    )
+    .unwrap();
+    let voided = SaleFactory::new(whey, 5.0).total(500.0).date(&days_ago(1)).with("customer_id", json!(gym)).create(&app).unwrap();
+    app.invoke::<Vec<Value>>("void_sale", json!({ "saleId": voided["id"], "reason": "Cliente equivocado" })).unwrap();
+    SaleFactory::new(whey, 1.0).total(100.0).with("customer", json!("Gimnasio Titán")).create(&app).unwrap();
+
+    let history = |limit: i32, offset: i32| -> Value {
+        app.invoke("get_customer_history", json!({ "customerId": gym, "limit": limit, "offset": offset })).unwrap()
+    };
+    let first = history(2, 0);
+    assert_eq!(first["name"], "Gimnasio Titán");
+    assert_eq!(
+        first["summary"],
+        json!({ "lifetime_revenue": 340.0, "purchases": 2, "last_purchase_date": days_ago(3), "favorite_category": "Creatinas" })
+    );
+    assert_eq!(first["sales"]["total_count"], 3);
+    let names: Vec<&str> = first["sales"]["items"].as_array().unwrap().iter().map(|s| s["product_name"].as_str().unwrap()).collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"Whey 1kg"));
+    let last = history(2, 2);
+    assert_eq!(last["sales"]["items"][0]["product_name"], "Creatina 300g");
+    assert_eq!(last["sales"]["items"][0]["sale_date"], days_ago(42));
+
+    let walk_in: i64 = app.invoke("add_customer", json!({ "customer": { "name": "Ana Pérez" } })).unwrap();
+    let empty: Value = app.invoke("get_customer_history", json!({ "customerId": walk_in })).unwrap();
+    assert_eq!(
+        empty["summary"],
+        json!({ "lifetime_revenue": 0.0, "purchases": 0, "last_purchase_date": null, "favorite_category": null })
+    );
+    let err = app.invoke::<Value>("get_customer_history", json!({ "customerId": 999 })).unwrap_err();
+    assert_eq!(err, "Cliente no encontrado");
+}
+
+#[test]
+fn scanning_a_code_sells_the_product_in_one_call() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").sku("WHEY-1").prices(150.0, 90.0).create(&app).unwrap();
+    app.add_stock(whey, 3.0);
+
+    let sold: Value = app
+        .invoke("sell_by_sku", json!({ "sku": " WHEY-1\n", "quantity": 2.0, "paymentMethod": "efectivo" }))
+        .unwrap();
+    assert_eq!(sold["product_id"], whey);
+    assert_eq!(sold["product_name"], "Whey 1kg");
+    assert_eq!(sold["sale_price"], 300.0);
+    assert_eq!(sold["remaining_stock"], 1.0);
+    assert!(sold["receipt_number"].as_str().unwrap().starts_with("V-"));
+    assert_eq!(app.stock_of(whey), 1.0);
+
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.len(), 1);
+    assert_eq!(sales[0]["id"], sold["sale_id"]);
+    assert_eq!(sales[0]["created_by"], 1);
+    assert!(sales[0]["sale_date"].as_str().unwrap().ends_with('Z'));
+
+    // El lector distingue un código desconocido de la falta de stock
+    let err = app.invoke::<Value>("sell_by_sku", json!({ "sku": "WHEY-2" })).unwrap_err();
+    assert_eq!(err, "Código no registrado: WHEY-2");
+    let err = app.invoke::<Value>("sell_by_sku", json!({ "sku": "WHEY-1", "quantity": 2.0 })).unwrap_err();
+    assert_eq!(err, "Stock insuficiente. Disponible: 1, solicitado: 2");
+    assert_eq!(app.stock_of(whey), 1.0);
+}
+
+#[test]
+fn sale_notes_are_stored_edited_and_exported() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+
+    let sold = SaleFactory::new(whey, 1.0)
+        .date(SALE_DATE)
+        .with("note", json!("  Cliente pidió factura, entrega \"el viernes\" "))
+        .create(&app)
+        .unwrap();
+    app.invoke::<Value>(
+        "create_sale",
+        json!({ "sale": { "sale_date": SALE_DATE, "note": "Para regalo", "items": [{ "product_id": whey, "quantity": 1.0 }] } }),
+    )
+    .unwrap();
+    let err = SaleFactory::new(whey, 1.0).with("note", json!("x".repeat(1001))).create(&app).unwrap_err();
+    assert_eq!(err, "La nota de la venta admite como máximo 1000 caracteres (tiene 1001)");
+    assert_eq!(app.stock_of(whey), 8.0);
+
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    let stored = |id: &Value| sales.iter().find(|s| s["id"] == *id).unwrap().clone();
+    assert_eq!(stored(&sold["id"])["note"], "Cliente pidió factura, entrega \"el viernes\"");
+    assert!(sales.iter().any(|s| s["note"] == "Para regalo"));
+
+    let csv = app.export("export_sales_report", json!({}));
+    let line = csv.lines().find(|l| l.starts_with(&format!("{},", sold["id"]))).unwrap();
+    assert!(line.ends_with(",\"Cliente pidió factura, entrega \"\"el viernes\"\"\""), "{}", line);
+
+    // Una nota en blanco la borra
+    let mut sale = stored(&sold["id"]);
+    sale["note"] = json!("   ");
+    app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap();
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.iter().find(|s| s["id"] == sold["id"]).unwrap()["note"], Value::Null);
+}
+
+#[test]
+fn quotes_become_sales_once_and_expire() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").sku("WHEY-1").create(&app).unwrap();
+    let bar = ProductFactory::new("Barra proteica").prices(12.5, 7.0).create(&app).unwrap();
+    app.add_stock(whey, 3.0);
+    app.add_stock(bar, 10.0);
+
+    // Cotizar no descuenta stock y la validez por defecto es futura
+    let quote: Value = app
+        .invoke(
+            "create_quote",
+            json!({ "quote": { "customer": "Gimnasio Titán", "note": "Precio por 15 días", "items": [
+                { "product_id": whey, "quantity": 2.0, "discount": 10.0 },
+            ] } }),
+        )
+        .unwrap();
+    let id = quote["id"].as_i64().unwrap();
+    assert_eq!(quote["quote_number"], format!("COT-{:06}", id));
+    assert_eq!(quote["status"], "borrador");
+    assert_eq!(quote["total"], 180.0);
+    assert!(quote["valid_until"].as_str().unwrap() > days_ahead(14).as_str());
+    assert_eq!(app.stock_of(whey), 3.0);
+
+    let mut edit = quote.clone();
+    edit["status"] = json!("enviada");
+    edit["items"] = json!([
+        { "product_id": whey, "quantity": 2.0, "discount": 10.0 },
+        { "product_id": bar, "quantity": 4.0, "unit_price": 12.0 },
+    ]);
+    let quote: Value = app.invoke("update_quote", json!({ "quote": edit })).unwrap();
+    assert_eq!(quote["status"], "enviada");
+    assert_eq!(quote["total"], 228.0);
+    assert_eq!(quote["items"].as_array().unwrap().len(), 2);
+    edit["status"] = json!("pagada");
+    let err = app.invoke::<Value>("update_quote", json!({ "quote": edit })).unwrap_err();
+    assert_eq!(err, "Estado de cotización 'pagada' no válido. Usa borrador, enviada, aceptada, vencida");
+    edit["status"] = json!("enviada");
+    edit["items"] = json!([]);
+    let err = app.invoke::<Value>("update_quote", json!({ "quote": edit })).unwrap_err();
+    assert_eq!(err, "La cotización no tiene productos");
+
+    let csv = app.export("export_quote", json!({ "quoteId": id }));
+    assert!(csv.starts_with(&format!("[quote]\nfield,value\nquote_number,COT-{:06}\nstatus,enviada\n", id)), "{}", csv);
+    assert!(csv.contains("\ntotal,228.00\n"));
+    assert!(csv.contains(&format!("[items]\nproduct_id,sku,name,quantity,unit_price,discount,line_total\n{},WHEY-1,Whey 1kg,2,100.00,10,180.00\n", whey)));
+
+    // Pasada la validez aparece vencida y no se puede vender
+    let mut expired = quote.clone();
+    expired["valid_until"] = json!(days_ago(1));
+    app.invoke::<Value>("update_quote", json!({ "quote": expired })).unwrap();
+    let listed: Vec<Value> = app.invoke("get_quotes", json!({ "status": "vencida" })).unwrap();
+    assert_eq!(listed.len(), 1);
+    let err = app.invoke::<Value>("convert_quote_to_sale", json!({ "quoteId": id })).unwrap_err();
+    assert_eq!(
+        err,
+        format!("La cotización COT-{:06} está vencida (válida hasta el {}); reactívala antes de venderla", id, days_ago(1))
+    );
+
+    // Reactivada, se vende con sus precios una sola vez; la barra va bajo lista
+    let mut active = quote.clone();
+    active["status"] = json!("aceptada");
+    app.invoke::<Value>("update_quote", json!({ "quote": active })).unwrap();
+    let err = app.invoke::<Value>("convert_quote_to_sale", json!({ "quoteId": id })).unwrap_err();
+    assert!(err.starts_with("El precio de la venta debe ser 50.00"), "{}", err);
+    assert_eq!(app.stock_of(whey), 3.0);
+    let receipt: Value = app
+        .invoke("convert_quote_to_sale", json!({ "quoteId": id, "paymentMethod": "efectivo", "priceOverride": true }))
+        .unwrap();
+    assert_eq!(receipt["customer"], "Gimnasio Titán");
+    assert_eq!(receipt["total"], 228.0);
+    assert!(receipt["receipt_number"].as_str().unwrap().starts_with("V-"));
+    assert_eq!(app.stock_of(whey), 1.0);
+    assert_eq!(app.stock_of(bar), 6.0);
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert!(sales.iter().all(|s| s["note"] == format!("Cotización COT-{:06}", id)));
+
+    let converted: Vec<Value> = app.invoke("get_quotes", json!({ "status": "convertida" })).unwrap();
+    assert_eq!(converted[0]["sale_id"], receipt["id"]);
+    assert_eq!(converted[0]["receipt_number"], receipt["receipt_number"]);
+    let err = app.invoke::<Value>("convert_quote_to_sale", json!({ "quoteId": id })).unwrap_err();
+    assert_eq!(err, format!("La cotización COT-{:06} ya se convirtió en la venta {}", id, receipt["receipt_number"].as_str().unwrap()));
+    let err = app.invoke::<Value>("update_quote", json!({ "quote": active })).unwrap_err();
+    assert_eq!(err, format!("La cotización COT-{:06} ya se convirtió en venta y no se puede editar", id));
+
+    // Sin stock la conversión no deja rastro
+    let short: Value = app
+        .invoke("create_quote", json!({ "quote": { "items": [{ "product_id": whey, "quantity": 2.0 }] } }))
+        .unwrap();
+    let err = app.invoke::<Value>("convert_quote_to_sale", json!({ "quoteId": short["id"] })).unwrap_err();
+    assert!(err.starts_with("Stock insuficiente"), "{}", err);
+    let listed: Vec<Value> = app.invoke("get_quotes", json!({ "status": "borrador" })).unwrap();
+    assert_eq!(listed[0]["sale_id"], Value::Null);
+    assert_eq!(app.stock_of(whey), 1.0);
+}
@@ -0,0 +1,207 @@
+//! Imágenes de productos: la foto se copia a la carpeta de imágenes (ver
+//! `common::images_dir`) como `product_<id>.<ext>`, junto con una miniatura
+//! de 200 px; el producto guarda la ruta relativa a esa carpeta.
+
+mod common;
+
+use common::{export_dir, images_dir, ProductFactory, TestApp};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Foto PNG de `width`×`height` en la carpeta de exportación.
+/// El formato sale de la extensión de `name`.
+fn photo(name: &str, width: u32, height: u32) -> PathBuf {
+    let path = export_dir().join(name);
+    image::RgbImage::from_pixel(width, height, image::Rgb([200, 30, 30]))
+        .save(&path)
+        .unwrap();
+    path
+}
+
+#[test]
+fn saving_an_image_stores_a_thumbnail_next_to_it() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+
+    let source = photo("foto whey.png", 1600, 800);
+    let saved: Value = app
+        .invoke("save_product_image", json!({ "productId": whey, "sourcePath": source.to_str().unwrap() }))
+        .unwrap();
+    fs::remove_file(&source).unwrap();
+    assert_eq!(saved["image_path"], format!("imagenes/product_{}.png", whey));
+    let image_path = Path::new(saved["path"].as_str().unwrap()).to_path_buf();
+    let thumbnail_path = Path::new(saved["thumbnail_path"].as_str().unwrap()).to_path_buf();
+    assert_eq!(image_path, images_dir().join(format!("product_{}.png", whey)));
+    assert_eq!(thumbnail_path.parent().unwrap(), images_dir());
+    assert_eq!(image::image_dimensions(&image_path).unwrap(), (1600, 800));
+    assert_eq!(image::image_dimensions(&thumbnail_path).unwrap(), (200, 100));
+
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    assert_eq!(product["image_path"], saved["image_path"]);
+    assert_eq!(product["thumbnail_path"], saved["thumbnail_path"]);
+    let thumbnail: Option<String> = app.invoke("get_product_thumbnail_path", json!({ "productId": whey })).unwrap();
+    assert_eq!(thumbnail.as_deref(), saved["thumbnail_path"].as_str());
+    let image: Option<String> = app.invoke("get_product_image", json!({ "productId": whey })).unwrap();
+    assert_eq!(image.as_deref(), saved["path"].as_str());
+
+    // Una foto nueva reemplaza los archivos anteriores
+    let source = photo("foto whey 2.jpg", 300, 300);
+    let replaced: Value = app
+        .invoke("save_product_image", json!({ "productId": whey, "sourcePath": source.to_str().unwrap() }))
+        .unwrap();
+    fs::remove_file(&source).unwrap();
+    assert_eq!(replaced["image_path"], format!("imagenes/product_{}.jpg", whey));
+    assert!(!image_path.exists());
+    assert_eq!(image::image_dimensions(&thumbnail_path).unwrap(), (200, 200));
+
+    // Al eliminar el producto se borran sus archivos
+    app.invoke::<()>("delete_product", json!({ "id": whey })).unwrap();
+    assert!(!Path::new(replaced["path"].as_str().unwrap()).exists());
+    assert!(!thumbnail_path.exists());
+    let image: Option<String> = app.invoke("get_product_image", json!({ "productId": whey })).unwrap();
+    assert_eq!(image, None);
+}
+
+#[test]
+fn oversized_and_non_image_files_are_rejected() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+
+    let pdf = export_dir().join("factura.pdf");
+    fs::write(&pdf, "%PDF-1.4").unwrap();
+    let err = app
+        .invoke::<Value>("save_product_image", json!({ "productId": whey, "sourcePath": pdf.to_str().unwrap() }))
+        .unwrap_err();
+    assert!(err.ends_with("solo se admiten imágenes .png, .jpg, .jpeg, .webp"), "{}", err);
+    fs::remove_file(&pdf).unwrap();
+
+    app.set_setting("product_image_max_mb", "0.001");
+    let source = photo("foto grande.png", 400, 400);
+    let err = app
+        .invoke::<Value>("save_product_image", json!({ "productId": whey, "sourcePath": source.to_str().unwrap() }))
+        .unwrap_err();
+    fs::remove_file(&source).unwrap();
+    assert!(err.ends_with("supera el máximo de 0.001 MB"), "{}", err);
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    assert!(product["image_path"].is_null());
+}
+
+#[test]
+fn corrupt_images_are_reported_instead_of_panicking() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+
+    let text = export_dir().join("lista de precios.png");
+    fs::write(&text, "no soy una imagen").unwrap();
+    let err = app
+        .invoke::<Value>("save_product_image", json!({ "productId": whey, "sourcePath": text.to_str().unwrap() }))
+        .unwrap_err();
+    assert!(err.ends_with("formato de imagen no admitido. Usa PNG, JPEG o WebP."), "{}", err);
+
+    let mut truncated = fs::read(photo("cortada.png", 50, 50)).unwrap();
+    truncated.truncate(60);
+    let truncated_path = export_dir().join("cortada.png");
+    fs::write(&truncated_path, truncated).unwrap();
+    let err = app
+        .invoke::<Value>("save_product_image", json!({ "productId": whey, "sourcePath": truncated_path.to_str().unwrap() }))
+        .unwrap_err();
+    assert!(err.contains("la imagen está dañada"), "{}", err);
+
+    // Mantenimiento: una imagen cargada a mano sin miniatura y otra dañada
+    let creatine_photo = photo("creatina.png", 400, 400);
+    let creatine = ProductFactory::new("Creatina 300g")
+        .with("image_path", json!(creatine_photo.to_str().unwrap()))
+        .create(&app)
+        .unwrap();
+    let broken = ProductFactory::new("Glutamina")
+        .with("image_path", json!(truncated_path.to_str().unwrap()))
+        .create(&app)
+        .unwrap();
+    let report: Value = app.invoke("regenerate_thumbnails", json!({})).unwrap();
+    assert_eq!(report["generated"], 1);
+    assert_eq!(report["failed"].as_array().unwrap().len(), 1);
+    assert_eq!(report["failed"][0]["product_id"], broken);
+    let thumbnail: Option<String> = app.invoke("get_product_thumbnail_path", json!({ "productId": creatine })).unwrap();
+    assert_eq!(image::image_dimensions(thumbnail.unwrap()).unwrap(), (200, 200));
+
+    let report: Value = app.invoke("regenerate_thumbnails", json!({})).unwrap();
+    assert_eq!(report["generated"], 0);
+    for file in [text, truncated_path, creatine_photo] {
+        fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn cloned_products_share_the_photo_unless_asked_to_copy_it() {
+    let app = TestApp::with_admin();
+    // Ids distintos de los de las otras pruebas, que usan la misma carpeta de imágenes
+    for name in ["Shaker", "Toalla", "Guantes", "Cinturón"] {
+        ProductFactory::new(name).create(&app).unwrap();
+    }
+    let whey = ProductFactory::new("Whey 1kg Vainilla").create(&app).unwrap();
+    let source = photo("foto clon.png", 400, 400);
+    let saved: Value = app
+        .invoke("save_product_image", json!({ "productId": whey, "sourcePath": source.to_str().unwrap() }))
+        .unwrap();
+    fs::remove_file(&source).unwrap();
+
+    let clone = |copy: bool| -> Value {
+        let created: Value = app
+            .invoke("clone_product", json!({ "sourceId": whey, "overrides": { "name": "Whey 1kg Chocolate" }, "copyImage": copy }))
+            .unwrap();
+        app.invoke("get_product_by_id", json!({ "id": created["id"] })).unwrap()
+    };
+    let shared = clone(false);
+    assert_eq!(shared["image_path"], saved["image_path"]);
+    assert_eq!(shared["thumbnail_path"], saved["thumbnail_path"]);
+
+    let copied = clone(true);
+    assert_eq!(copied["image_path"], format!("imagenes/product_{}.png", copied["id"]));
+    assert_eq!(
+        Path::new(copied["thumbnail_path"].as_str().unwrap()),
+        images_dir().join(format!("product_{}_mini.jpg", copied["id"]))
+    );
+    assert!(Path::new(copied["thumbnail_path"].as_str().unwrap()).exists());
+
+    // Eliminar el clon que comparte la foto no la borra del original
+    app.invoke::<()>("delete_product", json!({ "id": shared["id"] })).unwrap();
+    assert!(Path::new(saved["path"].as_str().unwrap()).exists());
+    app.invoke::<()>("delete_product", json!({ "id": whey })).unwrap();
+    assert!(!Path::new(saved["path"].as_str().unwrap()).exists());
+    app.invoke::<()>("delete_product", json!({ "id": copied["id"] })).unwrap();
+}
+
+#[test]
+fn catalog_zip_bundles_the_inventory_and_lists_missing_photos() {
+    use std::io::Read;
+
+    let app = TestApp::with_admin();
+    let bar = photo("foto barra catálogo.png", 120, 80);
+    let missing = export_dir().join("no existe.jpg");
+    ProductFactory::new("Barra Avena").sku("BAR/01").with("image_path", json!(bar.to_str().unwrap())).create(&app).unwrap();
+    let lost = ProductFactory::new("Creatina 300g").sku("CRE-300").with("image_path", json!(missing.to_str().unwrap())).create(&app).unwrap();
+    ProductFactory::new("Shaker").sku("SHK-1").create(&app).unwrap();
+
+    let path: String = app.invoke("export_catalog_zip", json!({})).unwrap();
+    assert!(path.ends_with(".zip"));
+    let mut zip = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+    let mut names: Vec<&str> = zip.file_names().collect();
+    names.sort();
+    assert_eq!(names, ["images/BAR_01.png", "inventory.csv", "manifest.txt"]);
+
+    let read = |zip: &mut zip::ZipArchive<fs::File>, name: &str| -> Vec<u8> {
+        let mut content = Vec::new();
+        zip.by_name(name).unwrap().read_to_end(&mut content).unwrap();
+        content
+    };
+    assert_eq!(read(&mut zip, "images/BAR_01.png"), fs::read(&bar).unwrap());
+    let inventory = String::from_utf8(read(&mut zip, "inventory.csv")).unwrap();
+    assert!(inventory.starts_with("id,sku,name,"));
+    assert_eq!(inventory.lines().count(), 4);
+    let manifest = String::from_utf8(read(&mut zip, "manifest.txt")).unwrap();
+    assert!(manifest.contains("Imágenes incluidas: 1\nImágenes no encontradas: 1\n"));
+    assert!(manifest.contains(&format!("{}\tCRE-300\t{}", lost, missing.display())));
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&bar).unwrap();
+}
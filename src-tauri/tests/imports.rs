@@ -0,0 +1,265 @@
+//! Importación de CSV: inspección previa y mapeo de columnas confirmado.
+
+mod common;
+
+use common::{export_dir, ProductFactory, TestApp, UserFactory};
+use serde_json::{json, Value};
+use std::fs;
+
+#[test]
+fn inspect_csv_sniffs_latin1_and_semicolons_and_suggests_a_mapping() {
+    let app = TestApp::with_admin();
+    let path = export_dir().join("pedidos tienda online.csv");
+    // Excel en Windows: Latin-1 y punto y coma
+    let content = "Código;Descripción;Cant.;Importe;Fecha\nCRE-1;Creatina;2;200,50;2024-03-15\nWHEY-2;Proteína;1;350;2024-03-16\n";
+    let latin1: Vec<u8> = content.chars().map(|c| c as u8).collect();
+    fs::write(&path, latin1).unwrap();
+
+    let inspection: Value = app
+        .invoke("inspect_csv", json!({ "path": path.to_str().unwrap(), "expectedSchema": "sales" }))
+        .unwrap();
+    assert_eq!(inspection["encoding"], "Latin-1");
+    assert_eq!(inspection["delimiter"], ";");
+    assert_eq!(inspection["headers"], json!(["Código", "Descripción", "Cant.", "Importe", "Fecha"]));
+    assert_eq!(inspection["column_types"], json!(["texto", "texto", "entero", "decimal", "fecha"]));
+    assert_eq!(inspection["total_rows"], 2);
+    assert_eq!(inspection["sample_rows"][1][1], "Proteína");
+
+    let suggested = |field: &str| -> Value {
+        inspection["mapping"].as_array().unwrap().iter().find(|m| m["field"] == field).unwrap()["column"].clone()
+    };
+    assert_eq!(suggested("sku"), "Código");
+    assert_eq!(suggested("sale_date"), "Fecha");
+    assert!(suggested("customer").is_null());
+    assert_eq!(inspection["missing_required"], json!(["quantity", "sale_price"]));
+
+    // Sin mapeo el importador no reconoce las columnas; con el confirmado, sí
+    let product_id = ProductFactory::new("Creatina").sku("CRE-1").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    let err = app
+        .invoke::<Value>("import_sales_csv", json!({ "path": path.to_str().unwrap() }))
+        .unwrap_err();
+    assert_eq!(err, "Falta la columna quantity");
+    let mapping = json!({ "sku": "Código", "quantity": "Cant.", "sale_price": "Importe", "sale_date": "Fecha" });
+    let report: Value = app
+        .invoke("import_sales_csv", json!({ "path": path.to_str().unwrap(), "mapping": mapping }))
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(report["valid_rows"], 1);
+    assert_eq!(report["errors"][0]["message"], "SKU no encontrado: 'WHEY-2'");
+}
+
+#[test]
+fn supplier_price_list_reads_thousands_separators_and_rejects_ambiguous_amounts() {
+    let app = TestApp::with_admin();
+    for (name, sku) in [("Creatina", "CRE-1"), ("Proteína", "WHEY-2"), ("Barra", "BAR-3"), ("Glutamina", "GLU-4")] {
+        ProductFactory::new(name).sku(sku).create(&app).unwrap();
+    }
+    let path = export_dir().join("lista proveedor.csv");
+    fs::write(
+        &path,
+        "Código;Descripción;Costo\nCRE-1;Creatina;1.234,50\nWHEY-2;Proteína;1,234.50\nBAR-3;Barra;S/. 12.50\nGLU-4;Glutamina;12.5.0\n",
+    )
+    .unwrap();
+
+    let report: Value = app
+        .invoke(
+            "import_supplier_prices",
+            json!({ "supplier": "Nutrimax", "path": path.to_str().unwrap(), "dryRun": true }),
+        )
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    let costs: Vec<(&str, f64)> = report["matched"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["sku"].as_str().unwrap(), m["new_cost"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(costs, vec![("CRE-1", 1234.5), ("WHEY-2", 1234.5)]);
+    assert_eq!(
+        report["errors"],
+        json!(["Línea 4: costo inválido 'S/. 12.50'", "Línea 5: costo inválido '12.5.0'"])
+    );
+}
+
+#[test]
+fn sales_import_runs_in_chunks_that_survive_cancel_and_resume() {
+    let app = TestApp::with_admin();
+    app.set_setting("import_chunk_rows", "2");
+    let product_id = ProductFactory::new("Creatina").sku("CRE-1").create(&app).unwrap();
+    app.add_stock(product_id, 20.0);
+    let path = export_dir().join("pedidos por tramos.csv");
+    fs::write(
+        &path,
+        "sku,quantity,sale_price,sale_date\nCRE-1,1,100,2024-03-15\nNOPE,1,100,2024-03-15\nCRE-1,1,100,2024-03-15\nCRE-1,1,100,2024-03-16\nCRE-1,1,100,2024-03-16\n",
+    )
+    .unwrap();
+    let file = path.to_str().unwrap();
+    let sales_count = || app.invoke::<Vec<Value>>("get_sales", json!({})).unwrap().len();
+
+    let job: Value = app.invoke("start_import", json!({ "kind": "sales", "path": file })).unwrap();
+    let job_id = job["id"].as_i64().unwrap();
+    assert_eq!((job["status"].as_str(), job["total_rows"].as_i64()), (Some("en_curso"), Some(5)));
+    assert_eq!(sales_count(), 0);
+
+    let job: Value = app.invoke("import_next_chunk", json!({ "jobId": job_id })).unwrap();
+    assert_eq!((job["processed_rows"].as_i64(), job["imported"].as_i64()), (Some(2), Some(1)));
+    assert_eq!(sales_count(), 1);
+
+    // Cancelar conserva el tramo confirmado y frena los siguientes
+    let job: Value = app.invoke("cancel_import", json!({ "jobId": job_id })).unwrap();
+    assert_eq!(job["status"], "cancelada");
+    let err = app.invoke::<Value>("import_next_chunk", json!({ "jobId": job_id })).unwrap_err();
+    assert!(err.contains("cancelada"), "{}", err);
+    // Una importación cancelada no bloquea el archivo
+    let other: Value = app.invoke("start_import", json!({ "kind": "sales", "path": file })).unwrap();
+    let other_id = other["id"].as_i64().unwrap();
+    app.invoke::<Value>("cancel_import", json!({ "jobId": other_id })).unwrap();
+
+    // Reanudar sigue después del último tramo, sin repetir filas
+    let job: Value = app.invoke("resume_import", json!({ "jobId": job_id })).unwrap();
+    assert_eq!((job["status"].as_str(), job["processed_rows"].as_i64()), (Some("en_curso"), Some(2)));
+    let mut job = job;
+    while job["status"] == "en_curso" {
+        job = app.invoke("import_next_chunk", json!({ "jobId": job_id })).unwrap();
+    }
+    assert_eq!(job["status"], "completada");
+    assert_eq!((job["imported"].as_i64(), job["error_count"].as_i64()), (Some(4), Some(1)));
+    assert!(job["finished_at"].is_string());
+    assert_eq!(sales_count(), 4);
+    assert_eq!(app.stock_of(product_id), 16.0);
+
+    let detail: Value = app.invoke("get_import_job", json!({ "jobId": job_id })).unwrap();
+    let chunks = detail["chunks"].as_array().unwrap();
+    let lines: Vec<(i64, i64, i64)> = chunks
+        .iter()
+        .map(|c| (c["first_line"].as_i64().unwrap(), c["last_line"].as_i64().unwrap(), c["imported"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(lines, vec![(2, 3, 1), (4, 5, 2), (6, 6, 1)]);
+    assert_eq!(chunks[0]["errors"][0]["line"], 3);
+    assert_eq!(chunks[0]["errors"][0]["message"], "SKU no encontrado: 'NOPE'");
+
+    let history: Vec<Value> = app.invoke("get_import_jobs", json!({})).unwrap();
+    assert_eq!(history.iter().map(|j| j["id"].as_i64().unwrap()).collect::<Vec<_>>(), vec![other_id, job_id]);
+
+    // El mismo archivo no se importa dos veces, tampoco por import_sales_csv
+    let err = app.invoke::<Value>("start_import", json!({ "kind": "sales", "path": file })).unwrap_err();
+    assert_eq!(err, format!("Este archivo ya se importó (importación #{})", job_id));
+    let err = app
+        .invoke::<Value>("import_sales_csv", json!({ "path": file, "dryRun": false }))
+        .unwrap_err();
+    assert!(err.starts_with("Este archivo ya se importó"), "{}", err);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(sales_count(), 4);
+}
+
+#[test]
+fn user_import_is_all_or_nothing_and_returns_generated_passwords() {
+    let app = TestApp::with_admin();
+    UserFactory::new("ana").create(&app).unwrap();
+    let path = export_dir().join("usuarios sucursales.csv");
+    let import = |content: &str, dry_run: bool| -> Result<Value, String> {
+        fs::write(&path, content).unwrap();
+        app.invoke("import_users_csv", json!({ "path": path.to_str().unwrap(), "dryRun": dry_run }))
+    };
+
+    let report = import(
+        "usuario,nombre,rol,clave\nANA,Ana Pérez,Vendedor,\nbeto,Beto Ruiz,Cajero,\ncarla,Carla Díaz,vendedor,\nCarla,Otra Carla,Almacenero,\n",
+        false,
+    )
+    .unwrap();
+    let messages: Vec<&str> = report["errors"].as_array().unwrap().iter().map(|e| e["message"].as_str().unwrap()).collect();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0], "El usuario 'ANA' ya existe");
+    assert!(messages[1].starts_with("Rol desconocido: 'Cajero'"));
+    assert_eq!(messages[2], "Usuario repetido en el archivo");
+    assert_eq!(report["imported"], 0);
+    let users: Vec<Value> = app.invoke("get_users", json!({})).unwrap();
+    assert_eq!(users.len(), 2);
+
+    let content = "usuario,nombre,rol,clave\ncarla,Carla Díaz,vendedor,\ndiego,Diego Sosa,Almacenero,temporal-123\n";
+    let report = import(content, true).unwrap();
+    assert_eq!(report["valid_rows"], 2);
+    assert!(report["users"][0]["generated_password"].is_null());
+    let report = import(content, false).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["users"][0]["role"], "Vendedor");
+    assert!(report["users"][1]["generated_password"].is_null());
+    let generated = report["users"][0]["generated_password"].as_str().unwrap().to_string();
+    assert_eq!(generated.len(), 10);
+
+    let window = app.open_window("carla");
+    let user = app.login(&window, "carla", &generated).unwrap();
+    assert_eq!(user["must_change_password"], true);
+    let user = app.login(&window, "diego", "temporal-123").unwrap();
+    assert_eq!(user["must_change_password"], true);
+    assert_eq!(user["role"], "Almacenero");
+}
+
+#[test]
+fn product_xlsx_import_keeps_text_skus_and_reports_excel_rows() {
+    use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+    let app = TestApp::with_admin();
+    let path = export_dir().join("catálogo proveedor.xlsx");
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let date = Format::new().set_num_format("dd/mm/yyyy");
+    for (col, header) in ["SKU", "Nombre", "Precio", "Vencimiento", "Stock mínimo"].iter().enumerate() {
+        sheet.write_string(0, col as u16, *header).unwrap();
+    }
+    sheet.write_string(1, 0, "00123").unwrap();
+    sheet.write_string(1, 1, "Proteína Whey Ñandú").unwrap();
+    sheet.write_number(1, 2, 120.5).unwrap();
+    sheet.write_datetime_with_format(1, 3, &ExcelDateTime::from_ymd(2027, 3, 1).unwrap(), &date).unwrap();
+    sheet.write_number(1, 4, 5).unwrap();
+    sheet.write_number(2, 0, 4567).unwrap();
+    sheet.write_string(2, 1, "Barra Avena").unwrap();
+    sheet.write_number(2, 2, 8).unwrap();
+    // La fila 4 queda vacía
+    sheet.write_string(4, 0, "X1").unwrap();
+    sheet.write_number(4, 2, -3).unwrap();
+    sheet.write_string(5, 0, "X2").unwrap();
+    sheet.write_string(5, 1, "Creatina").unwrap();
+    sheet.write_string(5, 2, "abc").unwrap();
+    sheet.write_number(5, 4, 2.5).unwrap();
+    workbook.save(&path).unwrap();
+    let import = |dry_run: bool| -> Value {
+        app.invoke("import_products_xlsx", json!({ "path": path.to_str().unwrap(), "dryRun": dry_run })).unwrap()
+    };
+
+    let report = import(true);
+    assert_eq!(report["valid_rows"], 2);
+    assert_eq!(report["imported"], 0);
+    let errors: Vec<(u64, &str)> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["line"].as_u64().unwrap(), e["message"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (5, "name: El nombre es obligatorio"),
+            (5, "sale_price: El precio de venta no puede ser negativo (-3)"),
+            (6, "sale_price: 'abc' no es un número"),
+            (6, "min_stock: debe ser un número entero (2.5)"),
+        ]
+    );
+    let listed: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    assert!(listed.is_empty());
+
+    let report = import(false);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(report["imported"], 2);
+    let listed: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    let whey = listed.iter().find(|p| p["sku"] == "00123").unwrap();
+    assert_eq!(whey["name"], "Proteína Whey Ñandú");
+    assert_eq!(whey["sale_price"], 120.5);
+    assert_eq!(whey["expiry_date"], "2027-03-01");
+    assert_eq!(whey["min_stock"], 5);
+    let bar = listed.iter().find(|p| p["name"] == "Barra Avena").unwrap();
+    assert_eq!(bar["sku"], "4567");
+    assert_eq!(app.stock_of(whey["id"].as_i64().unwrap()), 0.0);
+}
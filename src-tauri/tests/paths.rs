@@ -0,0 +1,50 @@
+//! Rutas con espacios y caracteres no ASCII (carpetas de usuario como
+//! `C:\Users\Señorita Pérez`). La carpeta de exportación de las pruebas ya
+//! tiene un nombre así; ver `common::export_dir`.
+
+mod common;
+
+use common::{export_dir, ProductFactory, TestApp};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn exports_and_backups_keep_non_ascii_paths_intact() {
+    let app = TestApp::with_admin();
+    ProductFactory::new("Proteína de guisante").create(&app).unwrap();
+
+    let report: String = app.invoke("export_inventory_report", json!({})).unwrap();
+    let backup: String = app.invoke("backup_database", json!({})).unwrap();
+    for path in [&report, &backup] {
+        let path = Path::new(path);
+        assert_eq!(path.parent().unwrap(), export_dir());
+        assert!(path.is_file(), "{}", path.display());
+        assert!(!path.to_str().unwrap().contains('\u{FFFD}'));
+    }
+    assert!(fs::read_to_string(&report).unwrap().contains("Proteína de guisante"));
+    assert!(fs::metadata(&backup).unwrap().len() > 0);
+    fs::remove_file(&report).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+fn csv_import_reads_non_ascii_paths_and_names_them_on_error() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina").sku("CRE-1").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+
+    let path = export_dir().join("ventas año 2024 (copia).csv");
+    fs::write(&path, "sku,quantity,sale_price,sale_date\nCRE-1,2,200,2024-03-15\n").unwrap();
+    let report: Value = app
+        .invoke("import_sales_csv", json!({ "path": path.to_str().unwrap(), "dryRun": true }))
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(report["valid_rows"], 1);
+
+    let missing = export_dir().join("no existe ñandú.csv");
+    let err = app
+        .invoke::<Value>("import_sales_csv", json!({ "path": missing.to_str().unwrap() }))
+        .unwrap_err();
+    assert!(err.contains(missing.to_str().unwrap()), "{}", err);
+}
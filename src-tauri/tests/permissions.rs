@@ -0,0 +1,124 @@
+//! Permisos por rol. El Auditor puede consultar y exportar pero ningún
+//! comando que modifique datos debe aceptar su sesión.
+
+mod common;
+
+use common::{export_dir, ProductFactory, TestApp, UserFactory};
+use serde_json::{json, Value};
+use std::fs;
+use vitasport_lib::COMMANDS;
+
+/// Comandos sin prefijo `get_`/`export_` que el Auditor puede ejecutar porque
+/// no modifican datos. Es la especificación, escrita aparte de la lista de la
+/// aplicación: si un comando entra o sale de ella, esta prueba lo señala.
+const AUDITOR_MAY_RUN: &[&str] = &[
+    "calculate_price",
+    "check_credit",
+    "check_for_updates",
+    "find_probable_duplicates",
+    "inspect_csv",
+    "is_first_run",
+    "list_attachments",
+    "logout",
+    "search_products",
+    "search_products_quick",
+    "suggest_stock_levels",
+    "verify_login",
+    "verify_report_consistency",
+];
+
+fn auditor_rejection(command: &str) -> String {
+    format!("El rol Auditor es de solo lectura: no puede ejecutar {}", command)
+}
+
+#[test]
+fn auditor_session_is_rejected_by_every_mutating_command() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 500g").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    let auditor = UserFactory::new("contador").role("Auditor").create_and_login(&app);
+
+    // Se prueba cada comando con la sesión del Auditor y se mira qué acepta;
+    // `logout` va al final porque cierra esa sesión
+    let mut commands: Vec<&str> =
+        COMMANDS.iter().copied().filter(|c| !c.starts_with("get_") && !c.starts_with("export_")).collect();
+    commands.sort_by_key(|c| *c == "logout");
+    let (mut accepted, mut rejected) = (Vec::new(), Vec::new());
+    for command in commands {
+        match app.invoke_in::<Value>(&auditor, command, json!({})) {
+            Err(err) if err == auditor_rejection(command) => rejected.push(command),
+            _ => accepted.push(command),
+        }
+    }
+    accepted.sort_unstable();
+    assert_eq!(accepted, AUDITOR_MAY_RUN);
+    assert!(rejected.contains(&"add_sale") && rejected.contains(&"reset_database"));
+
+    // Nada cambió
+    assert_eq!(app.stock_of(product_id), 5.0);
+    let products: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    assert_eq!(products.len(), 1);
+}
+
+#[test]
+fn auditor_searches_and_inspects_without_changing_anything() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 500g").sku("CRE-500").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    let auditor = UserFactory::new("contador").role("Auditor").create_and_login(&app);
+
+    let found: Value = app.invoke_in(&auditor, "search_products", json!({ "query": "creatina" })).unwrap();
+    assert_eq!(found["total"], 1);
+    let hits: Vec<Value> = app.invoke_in(&auditor, "search_products_quick", json!({ "query": "CRE-500" })).unwrap();
+    assert_eq!(hits[0]["id"], product_id);
+    let pairs: Vec<Value> = app.invoke_in(&auditor, "find_probable_duplicates", json!({ "windowSeconds": 5 })).unwrap();
+    assert!(pairs.is_empty());
+
+    let path = export_dir().join("auditoria ventas.csv");
+    fs::write(&path, "Código,Cant.,Importe,Fecha\nCRE-500,1,100,2024-03-15\n").unwrap();
+    let inspection: Value = app
+        .invoke_in(&auditor, "inspect_csv", json!({ "path": path.to_str().unwrap(), "expectedSchema": "sales" }))
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(inspection["total_rows"], 1);
+
+    // Resolver un duplicado sigue siendo de un Administrador
+    let err = app
+        .invoke_in::<()>(&auditor, "resolve_duplicate", json!({ "keepId": 1, "removeId": 2 }))
+        .unwrap_err();
+    assert_eq!(err, auditor_rejection("resolve_duplicate"));
+    assert_eq!(app.stock_of(product_id), 5.0);
+}
+
+#[test]
+fn auditor_can_read_everything() {
+    let app = TestApp::with_admin();
+    let auditor = UserFactory::new("contador").role("Auditor").create_and_login(&app);
+
+    for command in COMMANDS.iter().filter(|c| c.starts_with("get_")) {
+        // Algunos piden argumentos; lo que importa es que el rol no los bloquee
+        if let Err(err) = app.invoke_in::<Value>(&auditor, command, json!({})) {
+            assert!(!err.contains("Auditor"), "{}: {}", command, err);
+        }
+    }
+    let log: Vec<Value> = app.invoke_in(&auditor, "get_audit_log", json!({})).unwrap();
+    assert!(log.iter().all(|e| e["action"] != "exportar"));
+}
+
+#[test]
+fn auditor_exports_are_recorded_in_the_audit_log() {
+    let app = TestApp::with_admin();
+    let auditor = UserFactory::new("contador").role("Auditor").create_and_login(&app);
+
+    let path: String = app
+        .invoke_in(&auditor, "export_sales_report", json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }))
+        .unwrap();
+    let _ = std::fs::remove_file(path);
+
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    let exports: Vec<&Value> = log.iter().filter(|e| e["action"] == "exportar").collect();
+    assert_eq!(exports.len(), 1);
+    assert_eq!(exports[0]["username"], "contador");
+    assert_eq!(exports[0]["entity"], "export_sales_report");
+    assert!(exports[0]["details"].as_str().unwrap().contains("2024-03-01"));
+}
@@ -0,0 +1,920 @@
+//! Consultas y validaciones de productos.
+
+mod common;
+
+use common::{days_ago, days_ahead, ProductFactory, SaleFactory, TestApp, UserFactory};
+use serde_json::{json, Value};
+
+#[test]
+fn get_product_by_id_returns_the_same_row_as_get_products() {
+    let app = TestApp::with_admin();
+    ProductFactory::new("Otro producto").create(&app).unwrap();
+    let product_id = ProductFactory::new("Glutamina 300g")
+        .sku("GLU-300")
+        .with("image_path", json!("imagenes/glutamina.png"))
+        .with("max_stock", json!(40))
+        .with("allowed_channels", json!(["tienda"]))
+        .create(&app)
+        .unwrap();
+
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": product_id })).unwrap();
+    let listed: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    let expected = listed.into_iter().find(|p| p["id"] == product_id).unwrap();
+    assert_eq!(product, expected);
+    assert_eq!(product["image_path"], "imagenes/glutamina.png");
+    assert_eq!(product["max_stock"], 40);
+    assert_eq!(product["allowed_channels"], json!(["tienda"]));
+}
+
+#[test]
+fn opening_stock_comes_from_initial_stock_not_max_stock() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").with("max_stock", json!(40)).create(&app).unwrap();
+    assert_eq!(app.stock_of(whey), 0.0);
+
+    let bar = ProductFactory::new("Barra proteica")
+        .with("max_stock", json!(40))
+        .with("initial_stock", json!(15))
+        .create(&app)
+        .unwrap();
+    assert_eq!(app.stock_of(bar), 15.0);
+    let movements: Vec<Value> = app.invoke("get_stock_movements", json!({})).unwrap();
+    assert_eq!(movements.len(), 1);
+    assert_eq!(movements[0]["product_id"], bar);
+    assert_eq!(movements[0]["movement_type"], "ingreso");
+    assert_eq!(movements[0]["note"], "stock inicial");
+
+    // No es parte de la ficha: editar el producto no vuelve a ingresar stock
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": bar })).unwrap();
+    assert!(product.get("initial_stock").is_none());
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    assert_eq!(app.stock_of(bar), 15.0);
+
+    let err = ProductFactory::new("Creatina").with("initial_stock", json!(-3)).create(&app).unwrap_err();
+    assert_eq!(err, "initial_stock: El stock inicial no puede ser negativo (-3)");
+}
+
+#[test]
+fn get_product_by_id_reports_missing_products() {
+    let app = TestApp::with_admin();
+    let err = app.invoke::<Value>("get_product_by_id", json!({ "id": 42 })).unwrap_err();
+    assert_eq!(err, "Producto no encontrado");
+}
+
+#[test]
+fn search_products_filters_and_paginates() {
+    let app = TestApp::with_admin();
+    for (name, sku, brand, category) in [
+        ("Proteína Whey Vainilla", "WHEY-VAN", "Optimum", "Proteínas"),
+        ("Proteína Whey Chocolate", "WHEY-CHO", "Optimum", "Proteínas"),
+        ("Creatina 300g", "CRE-300", "MuscleTech", "Suplementos"),
+        ("Barra 100% avena", "BAR-AV", "Nature", "Snacks"),
+    ] {
+        ProductFactory::new(name)
+            .sku(sku)
+            .category(category)
+            .with("brand", json!(brand))
+            .with("status", json!("activo"))
+            .create(&app)
+            .unwrap();
+    }
+    let search = |args: Value| -> (Vec<String>, i64) {
+        let result: Value = app.invoke("search_products", args).unwrap();
+        let names = result["products"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap().to_string()).collect();
+        (names, result["total"].as_i64().unwrap())
+    };
+
+    // Nombre, SKU o marca, sin distinguir mayúsculas y sin espacios de más
+    assert_eq!(search(json!({ "query": "  whey " })).1, 2);
+    assert_eq!(search(json!({ "query": "cre-300" })).0, vec!["Creatina 300g"]);
+    assert_eq!(search(json!({ "query": "muscletech" })).1, 1);
+    // % se busca literalmente
+    assert_eq!(search(json!({ "query": "100%" })).0, vec!["Barra 100% avena"]);
+    assert_eq!(search(json!({ "query": "%" })).1, 1);
+
+    // Solo filtros, y paginación con el total de coincidencias
+    assert_eq!(search(json!({ "category": "Proteínas", "status": "activo" })).1, 2);
+    let (page, total) = search(json!({ "limit": 3, "offset": 3 }));
+    assert_eq!(total, 4);
+    assert_eq!(page, vec!["Proteína Whey Vainilla"]);
+    assert_eq!(search(json!({ "query": "whey", "status": "inactivo" })).1, 0);
+}
+
+#[test]
+fn search_products_reports_stock_a_bundle_can_promise() {
+    let app = TestApp::with_admin();
+    let shaker = ProductFactory::new("Shaker 600ml").create(&app).unwrap();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let pack = ProductFactory::new("Pack inicio").create(&app).unwrap();
+    app.add_stock(shaker, 7.0);
+    app.add_stock(whey, 5.0);
+    app.invoke::<Value>(
+        "set_product_bundle",
+        json!({ "bundleId": pack, "components": [
+            { "component_id": shaker, "quantity": 1 },
+            { "component_id": whey, "quantity": 2 },
+        ] }),
+    )
+    .unwrap();
+
+    let result: Value = app.invoke("search_products", json!({})).unwrap();
+    let stock = |id: i64| result["stock"].as_array().unwrap().iter().find(|s| s["product_id"] == id).unwrap().clone();
+    assert_eq!(result["stock"].as_array().unwrap().len(), 3);
+    assert_eq!(stock(whey), json!({ "product_id": whey, "physical": 5.0, "available": 5.0 }));
+    // 5 kg de whey alcanzan para dos packs de 2
+    assert_eq!(stock(pack), json!({ "product_id": pack, "physical": 0.0, "available": 2.0 }));
+}
+
+#[test]
+fn quick_search_ranks_exact_sku_then_name_prefix_then_substring() {
+    let app = TestApp::with_admin();
+    let bar = ProductFactory::new("Barra proteica").sku("BAR-01").prices(12.5, 7.0).create(&app).unwrap();
+    let whey = ProductFactory::new("Proteína whey").sku("WH-01").create(&app).unwrap();
+    let pro = ProductFactory::new("Pack Pro").sku("PRO").create(&app).unwrap();
+    let protein = ProductFactory::new("Protein bar").sku("PB-01").create(&app).unwrap();
+    ProductFactory::new("Creatina").sku("CRE-01").create(&app).unwrap();
+    app.add_stock(bar, 4.0);
+
+    let ids = |query: &str| -> Vec<i64> {
+        let hits: Vec<Value> = app.invoke("search_products_quick", json!({ "query": query, "limit": 10 })).unwrap();
+        hits.iter().map(|h| h["id"].as_i64().unwrap()).collect()
+    };
+    // SKU exacto, nombres que empiezan con "pro" y luego los que lo contienen
+    assert_eq!(ids("pro"), [pro, protein, whey, bar]);
+    assert_eq!(ids("  BAR-01 "), [bar]);
+    assert!(ids("%").is_empty());
+    assert!(ids("").is_empty());
+
+    let hits: Vec<Value> = app.invoke("search_products_quick", json!({ "query": "barra", "limit": 1 })).unwrap();
+    assert_eq!(hits, [json!({ "id": bar, "sku": "BAR-01", "name": "Barra proteica", "price": 12.5, "available": 4.0 })]);
+}
+
+#[test]
+fn quick_search_stays_fast_on_a_large_catalog() {
+    let app = TestApp::with_admin();
+    let flavors = ["Vainilla", "Chocolate", "Frutilla", "Cookies", "Banana"];
+    for i in 0..10_000 {
+        ProductFactory::new(&format!("Producto {} {}", flavors[i % flavors.len()], i))
+            .sku(&format!("SKU-{:05}", i))
+            .create(&app)
+            .unwrap();
+    }
+
+    // Mediana de varias búsquedas cortas, para no medir una pausa del sistema
+    let mut times = Vec::new();
+    for query in ["pr", "va", "cho", "sku", "42"].iter().cycle().take(25) {
+        let start = std::time::Instant::now();
+        let hits: Vec<Value> = app.invoke("search_products_quick", json!({ "query": query, "limit": 20 })).unwrap();
+        times.push(start.elapsed());
+        assert!(!hits.is_empty(), "{}", query);
+    }
+    times.sort();
+    let median = times[times.len() / 2];
+    assert!(median.as_millis() < 20, "search_products_quick tardó {:?}", median);
+}
+
+#[test]
+fn scanned_code_resolves_to_its_product() {
+    let app = TestApp::with_admin();
+    ProductFactory::new("Creatina 300g").sku("7791234500012").create(&app).unwrap();
+    ProductFactory::new("Creatina 500g").sku("7791234500029").create(&app).unwrap();
+
+    let product: Value = app.invoke("get_product_by_sku", json!({ "code": " 7791234500012\n" })).unwrap();
+    assert_eq!(product["name"], "Creatina 300g");
+    assert_eq!(product["allowed_channels"], json!([]));
+
+    // Coincidencia exacta: un prefijo no alcanza
+    let err = app.invoke::<Value>("get_product_by_sku", json!({ "code": "779123450001" })).unwrap_err();
+    assert_eq!(err, "Código no registrado: 779123450001");
+}
+
+#[test]
+fn barcodes_are_validated_and_internal_ones_are_generated() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").with("barcode", json!(" 4006381333931 ")).create(&app).unwrap();
+    let bulk = ProductFactory::new("Avena a granel").unit("kg").create(&app).unwrap();
+
+    // El lector puede enviar el SKU o el código de barras
+    let product: Value = app.invoke("get_product_by_sku", json!({ "code": "4006381333931" })).unwrap();
+    assert_eq!(product["id"], whey);
+    assert_eq!(product["barcode"], "4006381333931");
+
+    let err = ProductFactory::new("Caseína").with("barcode", json!("4006381333932")).create(&app).unwrap_err();
+    assert_eq!(err, "El código de barras 4006381333932 no es un EAN-13 válido: el dígito verificador debería ser 1");
+    let err = ProductFactory::new("Caseína").with("barcode", json!("4006381333931")).create(&app).unwrap_err();
+    assert_eq!(err, "El código de barras 4006381333931 ya está asignado a Whey 1kg");
+    // Otros formatos se guardan tal cual
+    ProductFactory::new("Shaker").with("barcode", json!("SHK-600")).create(&app).unwrap();
+
+    let barcode: String = app.invoke("generate_internal_barcode", json!({ "productId": bulk })).unwrap();
+    assert_eq!(barcode.len(), 13);
+    assert!(barcode.starts_with("200"), "{}", barcode);
+    let product: Value = app.invoke("get_product_by_sku", json!({ "code": barcode })).unwrap();
+    assert_eq!(product["id"], bulk);
+    let err = app.invoke::<String>("generate_internal_barcode", json!({ "productId": bulk })).unwrap_err();
+    assert_eq!(err, format!("El producto ya tiene el código de barras {}", barcode));
+
+    // Editar el producto conserva su código y no choca consigo mismo
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    product["name"] = json!("Whey 1kg vainilla");
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    assert_eq!(product["barcode"], "4006381333931");
+}
+
+#[test]
+fn deleted_products_leave_the_catalog_but_keep_their_history() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").sku("WHEY-1").create(&app).unwrap();
+    let bar = ProductFactory::new("Barra proteica").sku("BAR-1").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+    SaleFactory::new(whey, 1.0).total(500.0).date("2024-03-02").override_price().create(&app).unwrap();
+
+    app.invoke::<()>("delete_product", json!({ "id": whey })).unwrap();
+    let listed: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["id"], bar);
+    let err = app.invoke::<Value>("get_product_by_sku", json!({ "code": "WHEY-1" })).unwrap_err();
+    assert_eq!(err, "Código no registrado: WHEY-1");
+    let found: Value = app.invoke("search_products", json!({ "query": "whey" })).unwrap();
+    assert_eq!(found["total"], 0);
+    let err = SaleFactory::new(whey, 1.0).create(&app).unwrap_err();
+    assert_eq!(err, "El producto fue eliminado del catálogo");
+    let err = app.invoke::<()>("delete_product", json!({ "id": whey })).unwrap_err();
+    assert_eq!(err, "Producto no encontrado");
+
+    // Los reportes viejos siguen mostrando el nombre
+    let report = app.export("export_profitability_report", json!({}));
+    assert!(report.contains("Whey 1kg"), "{}", report);
+
+    // Solo el Administrador ve los eliminados
+    let all: Vec<Value> = app.invoke("get_products", json!({ "includeDeleted": true })).unwrap();
+    let deleted = all.iter().find(|p| p["id"] == whey).unwrap();
+    assert!(deleted["deleted_at"].is_string());
+    let seller = UserFactory::new("vendedor").role("Vendedor").create_and_login(&app);
+    let err = app
+        .invoke_in::<Value>(&seller, "get_products", json!({ "includeDeleted": true }))
+        .unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+}
+
+#[test]
+fn purge_only_removes_products_without_references() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").sku("WHEY-1").create(&app).unwrap();
+    let typo = ProductFactory::new("Wehy 1kg").sku("WEHY-1").create(&app).unwrap();
+    app.add_stock(whey, 5.0);
+
+    let err = app.invoke::<()>("purge_product", json!({ "id": whey })).unwrap_err();
+    assert_eq!(err, "El producto tiene 1 movimiento asociado");
+    assert_eq!(app.stock_of(whey), 5.0);
+
+    app.invoke::<()>("delete_product", json!({ "id": typo })).unwrap();
+    let err = ProductFactory::new("Whey 1kg").sku("WEHY-1").create(&app).unwrap_err();
+    assert_eq!(err, "El SKU pertenece a un producto eliminado. Púrgalo antes de reutilizar el SKU.");
+    app.invoke::<()>("purge_product", json!({ "id": typo })).unwrap();
+    let err = app.invoke::<Value>("get_product_by_id", json!({ "id": typo })).unwrap_err();
+    assert_eq!(err, "Producto no encontrado");
+    ProductFactory::new("Whey 1kg").sku("WEHY-1").create(&app).unwrap();
+}
+
+#[test]
+fn forced_purge_cascades_and_is_audited() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    let bar = ProductFactory::new("Barra proteica").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    app.add_stock(whey, 5.0);
+    app.add_stock(bar, 5.0);
+    SaleFactory::new(whey, 1.0).total(500.0).date("2024-03-02").override_price().create(&app).unwrap();
+    app.invoke::<Value>("issue_store_credit", json!({ "amount": 1000.0, "code": "GC-WHEY" })).unwrap();
+    SaleFactory::new(whey, 2.0)
+        .total(1000.0)
+        .date("2024-03-03")
+        .override_price()
+        .with("store_credit_code", json!("GC-WHEY"))
+        .create(&app)
+        .unwrap();
+    SaleFactory::new(bar, 1.0).total(80.0).date("2024-03-03").override_price().create(&app).unwrap();
+    app.invoke::<Value>("archive_old_data", json!({ "beforeDate": "2024-03-03", "dryRun": false })).unwrap();
+    app.invoke::<()>("close_period", json!({ "month": "2024-03" })).unwrap();
+
+    // Los movimientos incluyen los egresos de las ventas
+    let err = app.invoke::<()>("purge_product", json!({ "id": whey })).unwrap_err();
+    assert_eq!(err, "El producto tiene 2 ventas y 4 movimientos asociados");
+
+    let seller = UserFactory::new("vendedor").role("Vendedor").create_and_login(&app);
+    let err = app
+        .invoke_in::<()>(&seller, "purge_product", json!({ "id": whey, "force": true }))
+        .unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+
+    app.invoke::<()>("purge_product", json!({ "id": whey, "force": true })).unwrap();
+    assert_eq!(app.stock_of(whey), 0.0);
+    assert_eq!(app.stock_of(bar), 4.0);
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    let entry = log.iter().find(|e| e["action"] == "purge_product" && e["flag"] == "purga_forzada").unwrap();
+    assert_eq!(entry["entity_id"], whey);
+    assert!(entry["details"].as_str().unwrap().contains("Whey 1kg"));
+    assert!(log.iter().any(|e| e["entity_id"] == whey && e["flag"] == "ajuste_post_cierre" && e["action"] == "purge_product"));
+    // El saldo canjeado en la venta borrada vuelve a la tarjeta
+    let credit: Value = app.invoke("check_credit", json!({ "code": "GC-WHEY" })).unwrap();
+    assert_eq!(credit["balance"], 1000.0);
+}
+
+#[test]
+fn missing_sku_is_generated_from_the_category_prefix() {
+    let app = TestApp::with_admin();
+    let create = |product: Value| -> Value { app.invoke("add_product", json!({ "product": product })).unwrap() };
+
+    let first = create(json!({ "name": "Whey 1kg", "sale_price": 100.0, "category": "Proteínas" }));
+    let second = create(json!({ "name": "Caseína 1kg", "sale_price": 100.0, "category": "Proteínas", "sku": "  " }));
+    assert_eq!(first["sku"], "PRO-00001");
+    assert_eq!(second["sku"], "PRO-00002");
+    assert_eq!(create(json!({ "name": "Shaker", "sale_price": 10.0 }))["sku"], "SKU-00001");
+
+    // Un SKU explícito se respeta y no altera la secuencia
+    assert_eq!(create(json!({ "name": "Whey 2kg", "sale_price": 180.0, "category": "Proteínas", "sku": "PRO-X" }))["sku"], "PRO-X");
+    app.set_setting("sku_prefix.Proteínas", "PROT");
+    assert_eq!(create(json!({ "name": "Whey 5kg", "sale_price": 400.0, "category": "Proteínas" }))["sku"], "PROT00001");
+
+    let product: Value = app.invoke("get_product_by_sku", json!({ "code": "PRO-00002" })).unwrap();
+    assert_eq!(product["id"], second["id"]);
+}
+
+#[test]
+fn variants_roll_up_into_their_product_family() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey Gold")
+        .category("Proteínas")
+        .with("brand", json!("Optimum"))
+        .create(&app)
+        .unwrap();
+    let add_variant = |parent_id: i64, name: &str| -> Result<i64, String> {
+        let created: Value = app.invoke(
+            "add_product_variant",
+            json!({ "parentId": parent_id, "product": { "name": name, "sale_price": 100.0 } }),
+        )?;
+        Ok(created["id"].as_i64().unwrap())
+    };
+    let small = add_variant(whey, "Whey Gold 1lb Vainilla").unwrap();
+    let large = add_variant(whey, "Whey Gold 5lb Chocolate").unwrap();
+    let creatine = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    assert_eq!(
+        add_variant(small, "Whey Gold 1lb Fresa").unwrap_err(),
+        "Una variante no puede tener variantes; usa su producto padre"
+    );
+
+    let variants: Vec<Value> = app.invoke("get_product_variants", json!({ "parentId": whey })).unwrap();
+    let names: Vec<&str> = variants.iter().map(|v| v["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Whey Gold 1lb Vainilla", "Whey Gold 5lb Chocolate"]);
+    assert!(variants.iter().all(|v| v["parent_id"] == whey && v["brand"] == "Optimum" && v["category"] == "Proteínas"));
+
+    // Ventas y stock siguen en la variante
+    app.add_stock(small, 10.0);
+    app.add_stock(large, 4.0);
+    app.add_stock(creatine, 3.0);
+    SaleFactory::new(small, 2.0).total(200.0).create(&app).unwrap();
+    SaleFactory::new(large, 1.0).total(450.0).override_price().create(&app).unwrap();
+    SaleFactory::new(creatine, 1.0).total(300.0).override_price().create(&app).unwrap();
+    assert_eq!(app.stock_of(small), 8.0);
+
+    let families: Vec<Value> = app.invoke("get_product_families", json!({})).unwrap();
+    assert_eq!(families.len(), 2);
+    let family = families.iter().find(|f| f["product_id"] == whey).unwrap();
+    assert_eq!(family["variant_count"], 2);
+    assert_eq!(family["stock"], 11.0);
+    assert_eq!(family["units_sold"], 3.0);
+    assert_eq!(family["revenue"], 650.0);
+
+    let by_product = |group: bool| -> Vec<(i64, f64)> {
+        let rows: Vec<Value> = app
+            .invoke("get_sales_by_product", json!({ "limit": 10, "groupByFamily": group }))
+            .unwrap();
+        rows.iter().map(|r| (r["product_id"].as_i64().unwrap(), r["total_revenue"].as_f64().unwrap())).collect()
+    };
+    assert_eq!(by_product(false), [(large, 450.0), (creatine, 300.0), (small, 200.0)]);
+    assert_eq!(by_product(true), [(whey, 650.0), (creatine, 300.0)]);
+}
+
+#[test]
+fn price_changes_are_kept_in_the_history() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Whey 1kg").category("Proteínas").prices(100.0, 60.0).create(&app).unwrap();
+    let update = |window: Option<&_>, changes: Value| {
+        let mut product: Value = app.invoke("get_product_by_id", json!({ "id": product_id })).unwrap();
+        for (field, value) in changes.as_object().unwrap() {
+            product[field] = value.clone();
+        }
+        match window {
+            Some(window) => app.invoke_in::<()>(window, "update_product", json!({ "product": product })),
+            None => app.invoke::<()>("update_product", json!({ "product": product })),
+        }
+        .unwrap();
+    };
+
+    // Sin cambios de precio no hay filas
+    update(None, json!({ "name": "Whey Gold 1kg", "location": "A1" }));
+    let history: Vec<Value> = app.invoke("get_price_history", json!({ "productId": product_id })).unwrap();
+    assert!(history.is_empty());
+
+    update(None, json!({ "cost_price": 70.0 }));
+    let seller = UserFactory::new("vendedor").create_and_login(&app);
+    update(Some(&seller), json!({ "sale_price": 120.0 }));
+    let changes: Vec<Value> = app
+        .invoke("bulk_update_prices", json!({ "category": "Proteínas", "percent": 10.0, "dryRun": false }))
+        .unwrap();
+    let new_price = changes[0]["new_price"].as_f64().unwrap();
+
+    let history: Vec<Value> = app.invoke("get_price_history", json!({ "productId": product_id })).unwrap();
+    let timeline: Vec<(&str, f64, f64, &str)> = history
+        .iter()
+        .map(|h| {
+            (
+                h["field"].as_str().unwrap(),
+                h["old_value"].as_f64().unwrap(),
+                h["new_value"].as_f64().unwrap(),
+                h["changed_by_name"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        timeline,
+        [
+            ("sale_price", 120.0, new_price, "admin"),
+            ("sale_price", 100.0, 120.0, "vendedor"),
+            ("cost_price", 60.0, 70.0, "admin"),
+        ]
+    );
+    assert_eq!(history[0]["source"], "ajuste masivo +10%");
+}
+
+#[test]
+fn categories_ignore_case_and_can_be_merged() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").category("Proteinas").create(&app).unwrap();
+    let isolate = ProductFactory::new("Isolate 2lb").category(" proteinas").create(&app).unwrap();
+    let casein = ProductFactory::new("Caseína").category("Proteínas").create(&app).unwrap();
+    for product in [whey, isolate, casein] {
+        app.add_stock(product, 5.0);
+        SaleFactory::new(product, 1.0).create(&app).unwrap();
+    }
+
+    let categories: Vec<Value> = app.invoke("get_categories", json!({})).unwrap();
+    let summary: Vec<(&str, i64)> =
+        categories.iter().map(|c| (c["name"].as_str().unwrap(), c["product_count"].as_i64().unwrap())).collect();
+    assert_eq!(summary, [("Proteinas", 2), ("Proteínas", 1)]);
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": isolate })).unwrap();
+    assert_eq!(product["category"], "Proteinas");
+    assert_eq!(product["category_id"], categories[0]["id"]);
+    let totals: Value = app
+        .invoke("get_sales_totals", json!({ "startDate": "2024-03-01", "endDate": "2024-03-31", "category": "PROTEINAS" }))
+        .unwrap();
+    assert_eq!(totals["total_units"], 2.0);
+
+    let err = app.invoke::<i64>("add_category", json!({ "category": { "name": "proteínas " } })).unwrap_err();
+    assert_eq!(err, "Ya existe la categoría Proteínas");
+    let err = app.invoke::<()>("delete_category", json!({ "id": categories[0]["id"] })).unwrap_err();
+    assert_eq!(err, "La categoría Proteinas tiene 2 productos; elige otra categoría para reasignarlos");
+
+    // Unir las dos: los productos y los filtros quedan en la que se conserva
+    app.invoke::<()>("delete_category", json!({ "id": categories[0]["id"], "reassignTo": categories[1]["id"] }))
+        .unwrap();
+    app.invoke::<()>(
+        "update_category",
+        json!({ "category": { "id": categories[1]["id"], "name": "Proteína en polvo", "sort_order": 1 } }),
+    )
+    .unwrap();
+    let categories: Vec<Value> = app.invoke("get_categories", json!({})).unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0]["product_count"], 3);
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    assert_eq!(product["category"], "Proteína en polvo");
+    let totals: Value = app
+        .invoke("get_sales_totals", json!({ "startDate": "2024-03-01", "endDate": "2024-03-31", "category": "proteína en polvo" }))
+        .unwrap();
+    assert_eq!(totals["total_units"], 3.0);
+}
+
+#[test]
+fn brands_collapse_case_and_spacing_and_filter_the_catalog() {
+    let app = TestApp::with_admin();
+    let brand = |name: &str| json!(name);
+    let whey = ProductFactory::new("Whey Gold").with("brand", brand("Optimum Nutrition")).create(&app).unwrap();
+    let casein = ProductFactory::new("Casein").with("brand", brand(" optimum   nutrition")).create(&app).unwrap();
+    let creatine = ProductFactory::new("Creatina").with("brand", brand("MuscleTech")).create(&app).unwrap();
+
+    let brands: Vec<Value> = app.invoke("get_brands", json!({})).unwrap();
+    let summary: Vec<(&str, i64)> =
+        brands.iter().map(|b| (b["name"].as_str().unwrap(), b["product_count"].as_i64().unwrap())).collect();
+    assert_eq!(summary, [("MuscleTech", 1), ("Optimum Nutrition", 2)]);
+    let optimum = brands[1]["id"].as_i64().unwrap();
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": casein })).unwrap();
+    assert_eq!(product["brand"], "Optimum Nutrition");
+    assert_eq!(product["brand_id"], optimum);
+
+    let listed: Vec<Value> = app.invoke("get_products", json!({ "brandId": optimum })).unwrap();
+    let mut ids: Vec<i64> = listed.iter().map(|p| p["id"].as_i64().unwrap()).collect();
+    ids.sort();
+    assert_eq!(ids, [whey, casein]);
+    let found: Value = app.invoke("search_products", json!({ "brandId": optimum, "query": "gold" })).unwrap();
+    assert_eq!(found["total"], 1);
+
+    let err = app.invoke::<i64>("add_brand", json!({ "brand": { "name": "OPTIMUM nutrition" } })).unwrap_err();
+    assert_eq!(err, "Ya existe la marca Optimum Nutrition");
+    let err = app.invoke::<()>("delete_brand", json!({ "id": optimum })).unwrap_err();
+    assert_eq!(err, "La marca Optimum Nutrition tiene 2 productos; elige otra marca para reasignarlos");
+
+    let muscletech = brands[0]["id"].as_i64().unwrap();
+    app.invoke::<()>("delete_brand", json!({ "id": optimum, "reassignTo": muscletech })).unwrap();
+    app.invoke::<()>("update_brand", json!({ "brand": { "id": muscletech, "name": "MuscleTech  Pro" } })).unwrap();
+    let listed: Vec<Value> = app.invoke("get_products", json!({ "brandId": muscletech })).unwrap();
+    assert_eq!(listed.len(), 3);
+    assert!(listed.iter().all(|p| p["brand"] == "MuscleTech Pro"), "{:?}", listed);
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": creatine })).unwrap();
+    assert_eq!(product["brand_id"], muscletech);
+}
+
+#[test]
+fn only_active_products_can_be_sold_unless_an_admin_allows_it() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").with("status", json!(" activo ")).create(&app).unwrap();
+    let bar = ProductFactory::new("Barra").create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    let status_of = |id: i64| app.invoke::<Value>("get_product_by_id", json!({ "id": id })).unwrap()["status"].clone();
+    assert_eq!(status_of(whey), "activo");
+    assert_eq!(status_of(bar), "activo");
+
+    let err = ProductFactory::new("Creatina").with("status", json!("Agotado")).create(&app).unwrap_err();
+    assert_eq!(err, "Estado inválido: 'Agotado'. Usa activo, inactivo, descontinuado, eliminado");
+
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    product["status"] = json!("DESCONTINUADO");
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    assert_eq!(status_of(whey), "descontinuado");
+
+    let listed: Vec<Value> = app.invoke("get_products", json!({ "status": "descontinuado" })).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["id"], whey);
+    let err = app.invoke::<Vec<Value>>("get_products", json!({ "status": "Agotado" })).unwrap_err();
+    assert!(err.starts_with("Estado inválido"), "{}", err);
+
+    let err = SaleFactory::new(whey, 1.0).create(&app).unwrap_err();
+    assert_eq!(err, "El producto está en estado descontinuado: no se puede vender");
+    let seller = UserFactory::new("vendedor").create_and_login(&app);
+    let err = SaleFactory::new(whey, 1.0).with("allow_inactive", json!(true)).create_in(&app, &seller).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede vender un producto que no está activo");
+    assert_eq!(app.stock_of(whey), 10.0);
+
+    SaleFactory::new(whey, 1.0).with("allow_inactive", json!(true)).create(&app).unwrap();
+    assert_eq!(app.stock_of(whey), 9.0);
+}
+
+#[test]
+fn boxes_are_converted_to_base_units_when_stock_comes_in() {
+    let app = TestApp::with_admin();
+    let bars = ProductFactory::new("Barra proteica")
+        .sku("BAR-12")
+        .with("purchase_unit", json!(" Caja "))
+        .with("conversion_factor", json!(12.0))
+        .create(&app)
+        .unwrap();
+
+    let movement = |quantity: f64, unit: &str| {
+        app.invoke::<i64>(
+            "add_stock_movement",
+            json!({ "movement": { "product_id": bars, "movement_type": "ingreso", "quantity": quantity, "unit": unit } }),
+        )
+    };
+    movement(2.0, "caja").unwrap();
+    movement(6.0, "unidad").unwrap();
+    assert_eq!(app.stock_of(bars), 30.0);
+    let err = movement(1.0, "pallet").unwrap_err();
+    assert_eq!(err, "Unidad 'pallet' no válida para este producto. Usa unidad o caja");
+    // Se sigue vendiendo por unidad
+    SaleFactory::new(bars, 3.0).create(&app).unwrap();
+    assert_eq!(app.stock_of(bars), 27.0);
+
+    let csv = app.export("export_inventory_report", json!({}));
+    let line = csv.lines().find(|l| l.contains("BAR-12")).unwrap();
+    assert!(line.contains(",27,unidad,caja,2.25,"), "{}", line);
+
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": bars })).unwrap();
+    assert_eq!(product["purchase_unit"], "caja");
+    product["conversion_factor"] = json!(0.0);
+    let err = app.invoke::<()>("update_product", json!({ "product": product })).unwrap_err();
+    assert_eq!(err, "El factor de conversión debe ser mayor a 0 (se recibió 0)");
+    let err = ProductFactory::new("Shaker")
+        .with("purchase_unit", json!("caja"))
+        .with("conversion_factor", json!(-6.0))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(err, "El factor de conversión debe ser mayor a 0 (se recibió -6)");
+}
+
+#[test]
+fn tags_are_normalized_and_filter_with_and_semantics() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey vegana").sku("WHEY-V").create(&app).unwrap();
+    let bar = ProductFactory::new("Barra de avena").sku("BAR-AV").create(&app).unwrap();
+    ProductFactory::new("Caseína").create(&app).unwrap();
+
+    let tags: Vec<String> = app
+        .invoke("set_product_tags", json!({ "productId": whey, "tags": [" Vegano ", "sin-lactosa", "VEGANO", ""] }))
+        .unwrap();
+    assert_eq!(tags, vec!["sin-lactosa", "vegano"]);
+    app.invoke::<Vec<String>>("set_product_tags", json!({ "productId": bar, "tags": ["vegano", "promo-navidad"] }))
+        .unwrap();
+    let tags: Vec<String> = app.invoke("get_product_tags", json!({ "productId": bar })).unwrap();
+    assert_eq!(tags, vec!["promo-navidad", "vegano"]);
+
+    let tagged: Vec<Value> = app.invoke("get_products_by_tag", json!({ "tag": "VEGANO" })).unwrap();
+    let names: Vec<&str> = tagged.iter().map(|p| p["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["Barra de avena", "Whey vegana"]);
+
+    let search = |tags: Value| -> Vec<i64> {
+        let result: Value = app.invoke("search_products", json!({ "tags": tags })).unwrap();
+        result["products"].as_array().unwrap().iter().map(|p| p["id"].as_i64().unwrap()).collect()
+    };
+    assert_eq!(search(json!(["vegano"])), vec![bar, whey]);
+    assert_eq!(search(json!(["Vegano", "sin-lactosa"])), vec![whey]);
+    assert_eq!(search(json!(["sin-lactosa", "promo-navidad"])), Vec::<i64>::new());
+    assert_eq!(search(json!([])).len(), 3);
+
+    let csv = app.export("export_inventory_report", json!({}));
+    let line = csv.lines().find(|l| l.contains("WHEY-V")).unwrap();
+    assert!(line.ends_with(",sin-lactosa;vegano,,"), "{}", line);
+
+    // Quitar todas las etiquetas
+    app.invoke::<Vec<String>>("set_product_tags", json!({ "productId": bar, "tags": [] })).unwrap();
+    assert_eq!(search(json!(["vegano"])), vec![whey]);
+}
+
+#[test]
+fn description_and_internal_notes_round_trip() {
+    let app = TestApp::with_admin();
+    let id = ProductFactory::new("Pre-entreno 300g")
+        .sku("PRE-300")
+        .with("description", json!("Ingredientes: cafeína, beta-alanina.\nTomar 20 minutos antes."))
+        .with("internal_notes", json!("Proveedor avisa cambio de fórmula"))
+        .create(&app)
+        .unwrap();
+
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": id })).unwrap();
+    assert_eq!(product["description"], "Ingredientes: cafeína, beta-alanina.\nTomar 20 minutos antes.");
+    assert_eq!(product["internal_notes"], "Proveedor avisa cambio de fórmula");
+
+    product["internal_notes"] = Value::Null;
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    let products: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    let product = products.iter().find(|p| p["id"] == id).unwrap();
+    assert_eq!(product["internal_notes"], Value::Null);
+    assert!(product["description"].as_str().unwrap().starts_with("Ingredientes"));
+
+    // Las notas internas no salen en el inventario exportado
+    let csv = app.export("export_inventory_report", json!({}));
+    assert!(csv.contains(",\"Ingredientes: cafeína, beta-alanina.\nTomar 20 minutos antes.\"\n"), "{}", csv);
+    assert!(!csv.contains("Proveedor"));
+}
+
+#[test]
+fn get_products_pages_and_sorts_on_request() {
+    let app = TestApp::with_admin();
+    for (name, price) in [("Creatina", 90.0), ("avena", 25.0), ("Barra", 12.5), ("Whey", 250.0), ("Dextrosa", 30.0)] {
+        ProductFactory::new(name).prices(price, price / 2.0).create(&app).unwrap();
+    }
+
+    // Sin parámetros sigue siendo la lista completa en orden de alta
+    let all: Vec<Value> = app.invoke("get_products", json!({})).unwrap();
+    let names: Vec<&str> = all.iter().map(|p| p["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Creatina", "avena", "Barra", "Whey", "Dextrosa"]);
+
+    let page: Value = app.invoke("get_products", json!({ "sortBy": "name", "limit": 2, "offset": 1 })).unwrap();
+    assert_eq!(page["total_count"], 5);
+    let names: Vec<&str> = page["items"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Barra", "Creatina"]);
+
+    let page: Value = app.invoke("get_products", json!({ "sortBy": "sale_price", "sortDir": "DESC", "limit": 1 })).unwrap();
+    assert_eq!(page["items"][0]["name"], "Whey");
+
+    let err = app.invoke::<Value>("get_products", json!({ "sortBy": "name; DROP TABLE products" })).unwrap_err();
+    assert!(err.starts_with("Orden 'name; DROP TABLE products' no válido"), "{}", err);
+    let err = app.invoke::<Value>("get_products", json!({ "sortDir": "sideways" })).unwrap_err();
+    assert_eq!(err, "Dirección 'sideways' no válida. Usa asc o desc");
+}
+
+#[test]
+fn low_stock_products_come_from_one_query_over_balances() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").category("Proteínas").with("min_stock", json!(5)).create(&app).unwrap();
+    let bar = ProductFactory::new("Barra").category("Snacks").with("min_stock", json!(10)).create(&app).unwrap();
+    let ok = ProductFactory::new("Creatina").category("Proteínas").with("min_stock", json!(2)).create(&app).unwrap();
+    let untracked = ProductFactory::new("Shaker").create(&app).unwrap();
+    let retired = ProductFactory::new("Gel viejo")
+        .with("min_stock", json!(3))
+        .with("status", json!("descontinuado"))
+        .create(&app)
+        .unwrap();
+    app.add_stock(whey, 5.0);
+    app.add_stock(bar, 4.0);
+    app.add_stock(ok, 8.0);
+
+    let low = |args: Value| -> Vec<(i64, f64, f64)> {
+        let rows: Vec<Value> = app.invoke("get_low_stock_products", args).unwrap();
+        rows.iter()
+            .map(|r| (r["product_id"].as_i64().unwrap(), r["current_stock"].as_f64().unwrap(), r["shortfall"].as_f64().unwrap()))
+            .collect()
+    };
+    // Justo en el mínimo también cuenta; sin min_stock no se considera
+    assert_eq!(low(json!({})), vec![(bar, 4.0, 6.0), (whey, 5.0, 0.0)]);
+    assert_eq!(low(json!({ "category": "proteínas" })), vec![(whey, 5.0, 0.0)]);
+    assert_eq!(low(json!({ "onlyActive": false })), vec![(bar, 4.0, 6.0), (retired, 0.0, 3.0), (whey, 5.0, 0.0)]);
+    assert!(!low(json!({ "onlyActive": false })).iter().any(|r| r.0 == untracked || r.0 == ok));
+}
+
+#[test]
+fn product_stats_summarize_the_catalog() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").sku("WHEY-2").prices(250.0, 180.0).with("min_stock", json!(5)).create(&app).unwrap();
+    let bar = ProductFactory::new("Barra").sku("BAR-1").prices(12.5, 7.25).with("image_path", json!("/fotos/barra.png")).create(&app).unwrap();
+    ProductFactory::new("Shaker").with("cost_price", Value::Null).with("min_stock", json!(1)).create(&app).unwrap();
+    let retired = ProductFactory::new("Gel viejo")
+        .sku("GEL-1")
+        .prices(8.0, 4.0)
+        .with("min_stock", json!(3))
+        .with("status", json!("descontinuado"))
+        .create(&app)
+        .unwrap();
+    let gone = ProductFactory::new("Eliminado").create(&app).unwrap();
+    app.add_stock(whey, 4.0);
+    app.add_stock(bar, 10.0);
+    app.add_stock(retired, 2.0);
+    app.invoke::<()>("delete_product", json!({ "id": gone })).unwrap();
+    // El SKU se genera al crear; queda vacío si lo borran al editar
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": bar })).unwrap();
+    product["sku"] = Value::Null;
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+
+    let stats: Value = app.invoke("get_product_stats", json!({})).unwrap();
+    assert_eq!(stats["total"], 4);
+    assert_eq!(stats["active"], 3);
+    assert_eq!(stats["inactive"], 1);
+    assert_eq!(stats["without_sku"], 1);
+    assert_eq!(stats["without_image"], 3);
+    assert_eq!(stats["without_cost"], 1);
+    // 4 × 180 + 10 × 7.25 + 2 × 4
+    assert_eq!(stats["catalog_value"], 800.5);
+    let low: Vec<Value> = app.invoke("get_low_stock_products", json!({})).unwrap();
+    assert_eq!(stats["below_min_stock"], low.len());
+    assert_eq!(low.len(), 2);
+}
+
+#[test]
+fn products_carry_a_default_supplier_for_reorders() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg")
+        .with("default_supplier", json!("  Distribuidora Andina "))
+        .with("min_stock", json!(5))
+        .create(&app)
+        .unwrap();
+    let bar = ProductFactory::new("Barra").with("default_supplier", json!("distribuidora andina")).create(&app).unwrap();
+    let loose = ProductFactory::new("Shaker").with("default_supplier", json!("  ")).with("min_stock", json!(1)).create(&app).unwrap();
+
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": whey })).unwrap();
+    assert_eq!(product["default_supplier"], "Distribuidora Andina");
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": loose })).unwrap();
+    assert!(product["default_supplier"].is_null());
+
+    let by_supplier = |supplier: &str| -> Vec<i64> {
+        let products: Vec<Value> = app.invoke("get_products_by_supplier", json!({ "supplier": supplier })).unwrap();
+        products.iter().map(|p| p["id"].as_i64().unwrap()).collect()
+    };
+    assert_eq!(by_supplier("DISTRIBUIDORA ANDINA"), vec![bar, whey]);
+    assert!(by_supplier("Nutrimax").is_empty());
+    assert!(app.invoke::<Value>("get_products_by_supplier", json!({ "supplier": " " })).is_err());
+
+    let mut product: Value = app.invoke("get_product_by_id", json!({ "id": bar })).unwrap();
+    product["default_supplier"] = json!("Nutrimax");
+    app.invoke::<()>("update_product", json!({ "product": product })).unwrap();
+    assert_eq!(by_supplier("nutrimax"), vec![bar]);
+
+    // La lista de reposición dice a quién pedir
+    let low: Vec<Value> = app.invoke("get_low_stock_products", json!({})).unwrap();
+    let suppliers: Vec<(i64, Value)> = low.iter().map(|r| (r["product_id"].as_i64().unwrap(), r["default_supplier"].clone())).collect();
+    assert_eq!(suppliers, vec![(whey, json!("Distribuidora Andina")), (loose, Value::Null)]);
+}
+
+#[test]
+fn overstock_products_value_the_excess_at_cost() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 2kg").prices(250.0, 180.0).with("max_stock", json!(10)).create(&app).unwrap();
+    let bar = ProductFactory::new("Barra, chocolate").prices(12.5, 7.25).with("max_stock", json!(20)).create(&app).unwrap();
+    let at_max = ProductFactory::new("Creatina").prices(90.0, 60.0).with("max_stock", json!(5)).create(&app).unwrap();
+    let no_max = ProductFactory::new("Shaker").prices(15.0, 8.0).create(&app).unwrap();
+    app.add_stock(whey, 12.0);
+    app.add_stock(bar, 60.0);
+    app.add_stock(at_max, 5.0);
+    app.add_stock(no_max, 100.0);
+
+    let rows: Vec<Value> = app.invoke("get_overstock_products", json!({})).unwrap();
+    let summary: Vec<(i64, f64, f64)> = rows
+        .iter()
+        .map(|r| (r["product_id"].as_i64().unwrap(), r["excess"].as_f64().unwrap(), r["excess_value"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(summary, vec![(whey, 2.0, 360.0), (bar, 40.0, 290.0)]);
+
+    let csv = app.export("export_overstock_report", json!({}));
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "product_id,sku,name,current_stock,max_stock,excess,cost_price,excess_value");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].ends_with(",\"Barra, chocolate\",60,20,40,7.25,290.00"), "{}", lines[2]);
+}
+
+#[test]
+fn expiring_products_split_expired_and_unreadable_dates() {
+    let app = TestApp::with_admin();
+    let expiring = |name: &str, date: &str| ProductFactory::new(name).with("expiry_date", json!(date)).create(&app).unwrap();
+    let gel = expiring("Gel energético", &days_ahead(10));
+    let whey = expiring("Whey 2kg", &days_ahead(3));
+    let old = expiring("Creatina", &days_ago(2));
+    let later = expiring("Omega 3", &days_ahead(90));
+    ProductFactory::new("Shaker").create(&app).unwrap();
+    app.add_stock(whey, 6.0);
+    app.add_stock(old, 2.0);
+
+    let report: Value = app.invoke("get_expiring_products", json!({})).unwrap();
+    let ids = |key: &str| -> Vec<i64> { report[key].as_array().unwrap().iter().map(|p| p["product_id"].as_i64().unwrap()).collect() };
+    assert_eq!(ids("expiring"), vec![whey, gel]);
+    assert_eq!(ids("expired"), vec![old]);
+    // Las fechas ilegibles solo pueden venir de datos anteriores a la validación
+    assert_eq!(ids("invalid_dates"), Vec::<i64>::new());
+    assert_eq!(report["expiring"][0]["days_left"], 3);
+    assert_eq!(report["expiring"][0]["current_stock"], 6.0);
+    assert_eq!(report["expired"][0]["days_left"], -2);
+
+    let report: Value = app.invoke("get_expiring_products", json!({ "days": 120 })).unwrap();
+    assert!(report["expiring"].as_array().unwrap().iter().any(|p| p["product_id"] == later));
+
+    let csv = app.export("export_expiry_report", json!({}));
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "status,product_id,sku,name,expiry_date,days_left,current_stock");
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with(&format!("vencido,{},", old)), "{}", lines[1]);
+    assert!(lines[3].starts_with(&format!("por_vencer,{},", gel)), "{}", lines[3]);
+    assert!(lines[3].ends_with(&format!(",Gel energético,{},10,0", days_ahead(10))), "{}", lines[3]);
+}
+
+#[test]
+fn clone_product_copies_the_sheet_but_not_sku_or_stock() {
+    let app = TestApp::with_admin();
+    let vanilla = ProductFactory::new("Whey 1kg Vainilla")
+        .sku("WHEY-VAI")
+        .category("Proteínas")
+        .prices(150.0, 95.0)
+        .with("flavor", json!("Vainilla"))
+        .with("brand", json!("Optimum"))
+        .with("max_stock", json!(12))
+        .with("initial_stock", json!(12))
+        .with("description", json!("Proteína de suero"))
+        .with("barcode", json!("7750000000014"))
+        .create(&app)
+        .unwrap();
+    app.invoke::<Vec<String>>("set_product_tags", json!({ "productId": vanilla, "tags": ["proteina"] })).unwrap();
+    assert_eq!(app.stock_of(vanilla), 12.0);
+
+    let created: Value = app
+        .invoke(
+            "clone_product",
+            json!({ "sourceId": vanilla, "overrides": { "name": "Whey 1kg Chocolate", "flavor": "Chocolate" } }),
+        )
+        .unwrap();
+    let chocolate = created["id"].as_i64().unwrap();
+    assert_ne!(created["sku"], "WHEY-VAI");
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": chocolate })).unwrap();
+    assert_eq!(product["name"], "Whey 1kg Chocolate");
+    assert_eq!(product["flavor"], "Chocolate");
+    assert_eq!(product["brand"], "Optimum");
+    assert_eq!(product["category"], "Proteínas");
+    assert_eq!(product["sale_price"], 150.0);
+    assert_eq!(product["max_stock"], 12);
+    assert_eq!(product["description"], "Proteína de suero");
+    assert_eq!(product["barcode"], Value::Null);
+    // Sin ingreso automático de stock
+    assert_eq!(app.stock_of(chocolate), 0.0);
+    let tags: Vec<String> = app.invoke("get_product_tags", json!({ "productId": chocolate })).unwrap();
+    assert_eq!(tags, ["proteina"]);
+
+    let created: Value = app
+        .invoke("clone_product", json!({ "sourceId": vanilla, "overrides": { "name": "", "sku": "WHEY-FRE" } }))
+        .unwrap();
+    assert_eq!(created["sku"], "WHEY-FRE");
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": created["id"] })).unwrap();
+    assert_eq!(product["name"], "Whey 1kg Vainilla");
+
+    let err = app
+        .invoke::<Value>("clone_product", json!({ "sourceId": vanilla, "overrides": { "name": "Otro", "sku": "WHEY-VAI" } }))
+        .unwrap_err();
+    assert_eq!(err, "El SKU ya existe. Usa otro SKU o edita el producto existente.");
+}
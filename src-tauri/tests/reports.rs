@@ -0,0 +1,981 @@
+//! Cálculos de reportes y precios. Las exportaciones CSV se comparan con los
+//! archivos de `tests/golden`; si un cambio las modifica a propósito, se
+//! regeneran con `UPDATE_GOLDEN=1 cargo test` y se revisa el diff.
+
+mod common;
+
+use common::{assert_golden, days_ago, days_ahead, ProductFactory, SaleFactory, TestApp, UserFactory, ADMIN_PASSWORD};
+use serde_json::{json, Value};
+
+/// Dos productos con stock y cuatro ventas en marzo y abril de 2024.
+fn sales_fixture() -> (TestApp, i64, i64) {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Proteína Whey 2kg")
+        .sku("WHEY-2KG")
+        .category("Proteínas")
+        .prices(250.0, 180.0)
+        .with("brand", json!("Optimum"))
+        .with("min_stock", json!(2))
+        .create(&app)
+        .unwrap();
+    let bar = ProductFactory::new("Barra proteica, chocolate")
+        .sku("BAR-CHOC")
+        .category("Snacks")
+        .prices(12.5, 7.0)
+        .with("description", json!("Cobertura de chocolate, 20 g de proteína.\nConsumir después de entrenar."))
+        .create(&app)
+        .unwrap();
+    app.add_stock(whey, 10.0);
+    app.add_stock(bar, 48.0);
+
+    SaleFactory::new(whey, 2.0).total(500.0).date("2024-03-02").create(&app).unwrap();
+    SaleFactory::new(bar, 12.0)
+        .total(135.0)
+        .date("2024-03-10")
+        .with("discount", json!(10.0))
+        .with("channel", json!("tienda"))
+        .create(&app)
+        .unwrap();
+    SaleFactory::new(whey, 1.0)
+        .total(250.0)
+        .date("2024-03-28")
+        .with("customer", json!("Gimnasio \"Titán\", sede norte"))
+        .create(&app)
+        .unwrap();
+    SaleFactory::new(bar, 6.0).total(75.0).date("2024-04-03").create(&app).unwrap();
+    (app, whey, bar)
+}
+
+#[test]
+fn sales_report_matches_golden() {
+    let (app, ..) = sales_fixture();
+    let csv = app.export(
+        "export_sales_report",
+        json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }),
+    );
+    assert_golden("sales_report_2024-03.csv", &csv);
+}
+
+#[test]
+fn inventory_report_matches_golden() {
+    let (app, ..) = sales_fixture();
+    let csv = app.export("export_inventory_report", json!({}));
+    assert_golden("inventory_report.csv", &csv);
+}
+
+#[test]
+fn sales_totals_by_range_and_category() {
+    let (app, whey, bar) = sales_fixture();
+
+    let march: Value = app
+        .invoke("get_sales_totals", json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }))
+        .unwrap();
+    assert_eq!(march["total_units"], 15.0);
+    assert_eq!(march["total_revenue"], 885.0);
+
+    let snacks: Value = app
+        .invoke("get_sales_totals", json!({ "category": "Snacks" }))
+        .unwrap();
+    assert_eq!(snacks["total_units"], 18.0);
+    assert_eq!(snacks["total_revenue"], 210.0);
+
+    let by_product: Vec<Value> = app.invoke("get_sales_by_product", json!({})).unwrap();
+    let revenue_of = |id: i64| {
+        by_product.iter().find(|p| p["product_id"] == id).unwrap()["total_revenue"].as_f64().unwrap()
+    };
+    assert_eq!(revenue_of(whey), 750.0);
+    assert_eq!(revenue_of(bar), 210.0);
+
+    assert_eq!(app.stock_of(whey), 7.0);
+    assert_eq!(app.stock_of(bar), 30.0);
+}
+
+/// Ejemplos calculados a mano para la regla de redondeo de `calculate_price`.
+#[test]
+fn sales_history_filters_and_pages() {
+    let (app, whey, bar) = sales_fixture();
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+    SaleFactory::new(bar, 2.0).total(25.0).date("2024-03-20").create_in(&app, &seller).unwrap();
+    let ids = |page: &Value| page["items"].as_array().unwrap().iter().map(|s| s["sale_date"].as_str().unwrap()[..10].to_string()).collect::<Vec<_>>();
+
+    // Sin paginar sigue devolviendo la lista
+    let latest: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(latest.len(), 5);
+    let page: Value = app.invoke("get_sales", json!({ "limit": 2, "offset": 1 })).unwrap();
+    assert_eq!(page["total_count"], 5);
+    assert_eq!(ids(&page), ["2024-03-28", "2024-03-20"]);
+
+    let page: Value = app
+        .invoke("get_sales", json!({ "startDate": "2024-03-10", "endDate": "2024-03-28", "limit": 10 }))
+        .unwrap();
+    assert_eq!(ids(&page), ["2024-03-28", "2024-03-20", "2024-03-10"]);
+    let page: Value = app.invoke("get_sales", json!({ "productId": whey, "offset": 0 })).unwrap();
+    assert_eq!(ids(&page), ["2024-03-28", "2024-03-02"]);
+    let page: Value = app.invoke("get_sales", json!({ "channel": " Tienda ", "limit": 10 })).unwrap();
+    assert_eq!(ids(&page), ["2024-03-10"]);
+    let users: Vec<Value> = app.invoke("get_users", json!({})).unwrap();
+    let seller_id = users.iter().find(|u| u["username"] == "caja1").unwrap()["id"].clone();
+    let page: Value = app.invoke("get_sales", json!({ "createdBy": seller_id, "limit": 10 })).unwrap();
+    assert_eq!(page["total_count"], 1);
+    assert_eq!(ids(&page), ["2024-03-20"]);
+}
+
+#[test]
+fn detailed_sales_carry_product_and_seller() {
+    let (app, whey, _) = sales_fixture();
+    app.invoke::<()>("delete_product", json!({ "id": whey })).unwrap();
+
+    let page: Value = app.invoke("get_sales_detailed", json!({ "endDate": "2024-03-31", "limit": 2 })).unwrap();
+    assert_eq!(page["total_count"], 3);
+    let latest = &page["items"][0];
+    assert_eq!(latest["product_name"], "Proteína Whey 2kg");
+    assert_eq!(latest["sku"], "WHEY-2KG");
+    assert_eq!(latest["category"], "Proteínas");
+    assert_eq!(latest["product_deleted"], true);
+    assert_eq!(latest["seller"], "admin");
+    let bar = &page["items"][1];
+    assert_eq!(bar["product_name"], "Barra proteica, chocolate");
+    assert_eq!(bar["product_deleted"], false);
+    assert_eq!(bar["channel"], "tienda");
+}
+
+#[test]
+fn price_rounding_rules() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Pre entreno").prices(37.42, 20.0).create(&app).unwrap();
+    let price = |quantity: f64, discount: f64| -> Value {
+        app.invoke(
+            "calculate_price",
+            json!({ "productId": product_id, "quantity": quantity, "discount": discount }),
+        )
+        .unwrap()
+    };
+
+    // Sin regla configurada se redondea al centavo
+    let result = price(1.0, 0.0);
+    assert_eq!(result["total"], 37.42);
+    assert_eq!(result["rounded"], false);
+
+    // 37.42 al 0.50 más cercano: 37.50
+    app.set_setting("price_rounding_step", "0.50");
+    let result = price(1.0, 0.0);
+    assert_eq!(result["total"], 37.5);
+    assert_eq!(result["unrounded_total"], 37.42);
+    assert_eq!(result["rounded"], true);
+
+    // 3 × 37.42 = 112.26, menos 10% = 101.034 → 101.03 → 101.00
+    let result = price(3.0, 10.0);
+    assert_eq!(result["total"], 101.0);
+
+    // 2 × 37.42 = 74.84 hacia abajo al 1.00: 74.00
+    app.set_setting("price_rounding_step", "1.00");
+    app.set_setting("price_rounding_mode", "down");
+    assert_eq!(price(2.0, 0.0)["total"], 74.0);
+
+    // 37.42 hacia arriba al 0.10: 37.50; 37.50 ya es múltiplo y no cambia
+    app.set_setting("price_rounding_step", "0.10");
+    app.set_setting("price_rounding_mode", "up");
+    assert_eq!(price(1.0, 0.0)["total"], 37.5);
+    let result = app
+        .invoke::<Value>(
+            "calculate_price",
+            json!({ "productId": product_id, "quantity": 1.0, "unitPrice": 37.5 }),
+        )
+        .unwrap();
+    assert_eq!(result["total"], 37.5);
+    assert_eq!(result["rounded"], false);
+}
+
+#[test]
+fn money_is_formatted_from_whole_cents() {
+    let app = TestApp::with_admin();
+    let format = |amount: f64, currency: Option<&str>| -> String {
+        app.invoke("format_money", json!({ "amount": amount, "currency": currency })).unwrap()
+    };
+    assert_eq!(format(1234.5, None), "S/ 1,234.50");
+    // 1.005 es 1.00499… en binario; {:.2} lo escribiría 1.00
+    assert_eq!(format(1.005, Some("usd")), "$ 1.01");
+    assert_eq!(format(-0.125, Some("EUR")), "-€ 0.13");
+    assert_eq!(format(1_000_000.0, Some("CLP")), "CLP 1,000,000.00");
+    app.set_setting("currency", "USD");
+    assert_eq!(format(12.0, None), "$ 12.00");
+}
+
+/// Propiedad: para precios, cantidades y descuentos variados, el total que
+/// anticipa `compute_sale_totals` es exactamente el que calcula y guarda
+/// `add_sale` con el precio de lista, y el total de varias líneas es la suma
+/// de sus totales.
+#[test]
+fn sale_totals_preview_never_drifts_from_the_stored_sale() {
+    let app = TestApp::with_admin();
+    app.set_setting("price_rounding_step", "0.10");
+
+    // Generador congruencial fijo: los casos son variados pero reproducibles
+    let mut seed: u64 = 0x5eed;
+    let mut next = |modulo: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % modulo
+    };
+    let mut items = Vec::new();
+    let mut discounts = Vec::new();
+    let mut expected = Vec::new();
+    for case in 0..150 {
+        let unit_price = (next(50_000) + 1) as f64 / 100.0;
+        let quantity = (next(12) + 1) as f64;
+        let discount = next(500) as f64 / 10.0;
+        let product_id = ProductFactory::new(&format!("Granel {}", case)).prices(unit_price, 0.0).create(&app).unwrap();
+        app.add_stock(product_id, quantity);
+        let preview: Value = app
+            .invoke(
+                "compute_sale_totals",
+                json!({ "items": [{ "product_id": product_id, "quantity": quantity, "unit_price": unit_price }], "discounts": [discount] }),
+            )
+            .unwrap();
+        let total = preview["total"].as_f64().unwrap();
+        assert_eq!(preview["lines"][0]["total"].as_f64().unwrap(), total);
+        // Sin `sale_price`: el total lo calcula el servidor
+        let sale = SaleFactory::new(product_id, quantity)
+            .with("sale_price", Value::Null)
+            .with("discount", json!(discount))
+            .create(&app)
+            .unwrap();
+        items.push(json!({ "quantity": quantity, "unit_price": unit_price }));
+        discounts.push(discount);
+        expected.push((sale["id"].clone(), total));
+    }
+
+    let page: Value = app.invoke("get_sales", json!({ "limit": 500 })).unwrap();
+    let stored = page["items"].as_array().unwrap();
+    assert_eq!(stored.len(), expected.len());
+    for (id, total) in &expected {
+        let sale = stored.iter().find(|s| s["id"] == *id).unwrap();
+        assert_eq!(sale["sale_price"].as_f64().unwrap(), *total, "venta {}", id);
+    }
+
+    let cart: Value = app
+        .invoke("compute_sale_totals", json!({ "items": items, "discounts": discounts, "tax": 18.0 }))
+        .unwrap();
+    let cents = |v: &Value| (v.as_f64().unwrap() * 100.0).round() as i64;
+    let line_cents: i64 = expected.iter().map(|(_, t)| (t * 100.0).round() as i64).sum();
+    // El impuesto va incluido: no cambia el total, solo lo separa del neto
+    assert_eq!(cents(&cart["total"]), line_cents);
+    assert_eq!(cents(&cart["total"]), cents(&cart["net_total"]) + cents(&cart["tax_amount"]));
+    assert_eq!(cents(&cart["subtotal"]) - cents(&cart["discount_total"]), cents(&cart["total"]));
+
+    // Un descuento fuera de rango no se recorta en la vista previa: se
+    // rechaza igual que al guardar la venta
+    let product_id = ProductFactory::new("Granel fuera de rango").prices(100.0, 0.0).create(&app).unwrap();
+    app.add_stock(product_id, 1.0);
+    let err = app
+        .invoke::<Value>(
+            "compute_sale_totals",
+            json!({ "items": [{ "product_id": product_id, "quantity": 1.0, "unit_price": 100.0 }], "discounts": [150.0] }),
+        )
+        .unwrap_err();
+    assert_eq!(err, "Línea 1: El descuento es un porcentaje entre 0 y 100 (150); usa 10 para el 10%");
+    let err = SaleFactory::new(product_id, 1.0)
+        .with("sale_price", Value::Null)
+        .with("discount", json!(150.0))
+        .create(&app)
+        .unwrap_err();
+    assert_eq!(err, "El descuento es un porcentaje entre 0 y 100 (150); usa 10 para el 10%");
+    assert_eq!(app.stock_of(product_id), 1.0);
+}
+
+#[test]
+fn product_dossier_has_every_section() {
+    let (app, whey, _) = sales_fixture();
+    let csv = app.export("export_product_dossier", json!({ "productId": whey }));
+    let section = |name: &str| -> Vec<String> {
+        csv.split("\n\n")
+            .find(|s| s.starts_with(&format!("[{}]\n", name)))
+            .unwrap_or_else(|| panic!("Falta la sección {}", name))
+            .lines()
+            .skip(2)
+            .map(str::to_string)
+            .collect()
+    };
+
+    let product = section("product");
+    assert!(product.contains(&"sku,WHEY-2KG".to_string()));
+    assert!(product.contains(&"current_stock,7".to_string()));
+
+    let kardex = section("stock_movements");
+    let balances: Vec<&str> = kardex.iter().map(|l| l.split(',').nth(4).unwrap()).collect();
+    assert_eq!(balances, vec!["10", "8", "7"]);
+
+    let sales = section("sales");
+    assert_eq!(sales.len(), 2);
+    assert!(sales[0].starts_with("2024-03-02,1,2,500.00,"), "{}", sales[0]);
+    assert!(section("purchases").is_empty());
+    assert!(section("price_history").is_empty());
+
+    let err = app.invoke::<String>("export_product_dossier", json!({ "productId": 999 })).unwrap_err();
+    assert_eq!(err, "Producto no encontrado");
+}
+
+#[test]
+fn repeated_exports_do_not_overwrite_each_other() {
+    let (app, ..) = sales_fixture();
+    let first: Value = app.invoke("export_all_reports", json!({})).unwrap();
+    let second: Value = app.invoke("export_all_reports", json!({})).unwrap();
+    assert_eq!(first["already_running"], false);
+    assert_eq!(second["already_running"], false);
+    assert_ne!(first["job_id"], second["job_id"]);
+
+    // Las dos tandas suelen caer en el mismo segundo: ningún archivo se repite
+    let mut paths: Vec<String> = [&first, &second]
+        .iter()
+        .flat_map(|r| r["paths"].as_array().unwrap().iter())
+        .map(|p| p.as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(paths.len(), 12);
+    for path in &paths {
+        std::fs::remove_file(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    }
+    paths.sort();
+    paths.dedup();
+    assert_eq!(paths.len(), 12);
+
+    let running: Vec<Value> = app.invoke("get_running_exports", json!({})).unwrap();
+    assert!(running.is_empty());
+}
+
+#[test]
+fn stock_levels_are_suggested_from_velocity_and_lot_size() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Caseína 1kg").create(&app).unwrap();
+    for lot in [20.0, 30.0, 40.0] {
+        app.add_stock(product_id, lot);
+    }
+    // 9 unidades en los últimos 90 días; la venta de hace 120 días no cuenta
+    SaleFactory::new(product_id, 5.0).date(&days_ago(10)).create(&app).unwrap();
+    SaleFactory::new(product_id, 4.0).date(&days_ago(60)).create(&app).unwrap();
+    SaleFactory::new(product_id, 8.0).date(&days_ago(120)).create(&app).unwrap();
+
+    let suggestion: Value = app.invoke("suggest_stock_levels", json!({ "productId": product_id })).unwrap();
+    assert!((suggestion["daily_sales"].as_f64().unwrap() - 0.1).abs() < 1e-9);
+    assert_eq!(suggestion["lead_days"], 7.0);
+    assert_eq!(suggestion["typical_lot"], 30.0);
+    // 0.1 u/día × 7 días × 1.5 = 1.05 → 2; más un lote de 30
+    assert_eq!(suggestion["min_stock"], 2);
+    assert_eq!(suggestion["max_stock"], 32);
+
+    let err = app.invoke::<Value>("suggest_stock_levels", json!({ "productId": 999 })).unwrap_err();
+    assert_eq!(err, "Producto no encontrado");
+}
+
+#[test]
+fn seasonality_aligns_this_year_with_last_year() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Quemador de grasa").create(&app).unwrap();
+    app.add_stock(product_id, 50.0);
+    SaleFactory::new(product_id, 3.0).date(&days_ago(0)).create(&app).unwrap();
+    SaleFactory::new(product_id, 2.0).date(&days_ago(0)).create(&app).unwrap();
+    SaleFactory::new(product_id, 4.0).date(&days_ago(400)).create(&app).unwrap();
+
+    let months: Vec<Value> = app
+        .invoke("get_product_seasonality", json!({ "productId": product_id }))
+        .unwrap();
+    assert_eq!(months.len(), 12);
+    let latest = &months[11];
+    assert_eq!(latest["current_month"], days_ago(0)[..7]);
+    assert_eq!(latest["current_units"], 5.0);
+
+    // La primera venta (hace ~13 meses) marca el inicio: antes, vacío; después, ceros
+    let first = &days_ago(400)[..7];
+    for month in &months {
+        let previous = month["previous_month"].as_str().unwrap();
+        if previous < first {
+            assert!(month["previous_units"].is_null(), "{}", previous);
+        } else if previous == first {
+            assert_eq!(month["previous_units"], 4.0);
+        } else {
+            assert_eq!(month["previous_units"], 0.0, "{}", previous);
+        }
+        assert!(!month["current_units"].is_null());
+    }
+}
+
+#[test]
+fn sales_report_split_by_month_covers_every_month_in_range() {
+    let (app, ..) = sales_fixture();
+    let files: Vec<Value> = app
+        .invoke(
+            "export_sales_report",
+            json!({ "startDate": "2024-02-01", "endDate": "2024-04-30", "splitBy": "month" }),
+        )
+        .unwrap();
+    let summary: Vec<(&str, u64)> = files
+        .iter()
+        .map(|f| (f["month"].as_str().unwrap(), f["rows"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(summary, vec![("2024-02", 0), ("2024-03", 3), ("2024-04", 1)]);
+
+    let contents: Vec<String> = files
+        .iter()
+        .map(|f| std::fs::read_to_string(f["path"].as_str().unwrap()).unwrap())
+        .collect();
+    let folder = std::path::Path::new(files[0]["path"].as_str().unwrap()).parent().unwrap().to_path_buf();
+    std::fs::remove_dir_all(&folder).unwrap();
+
+    // Febrero queda con solo el encabezado; marzo coincide con el reporte de siempre
+    assert_eq!(contents[0].lines().count(), 1);
+    assert_golden("sales_report_2024-03.csv", &contents[1]);
+    assert!(contents[2].contains(",2024-04-03,"));
+
+    let err = app
+        .invoke::<Value>("export_sales_report", json!({ "splitBy": "week" }))
+        .unwrap_err();
+    assert_eq!(err, "split_by inválido: 'week'. Usa month o none");
+}
+
+#[test]
+fn catalog_snapshot_lists_products_that_existed_on_the_date() {
+    let (app, whey, _) = sales_fixture();
+    let retired = ProductFactory::new("Glutamina 300g")
+        .prices(90.0, 60.0)
+        .with("status", json!("descontinuado"))
+        .create(&app)
+        .unwrap();
+    app.add_stock(retired, 4.0);
+
+    let today = days_ago(0);
+    let csv = app.export("export_catalog_snapshot", json!({ "date": today }));
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "snapshot_date,id,sku,name,category,status,archived,sale_price,cost_price,stock,valuation");
+    assert_eq!(lines.len(), 4);
+    let row = |id: i64| lines.iter().find(|l| l.split(',').nth(1) == Some(&id.to_string())).unwrap().to_string();
+    assert_eq!(row(whey), format!("{},{},WHEY-2KG,Proteína Whey 2kg,Proteínas,activo,0,250.00,180.00,7,1260.00", today, whey));
+    assert!(row(retired).ends_with(",descontinuado,1,90.00,60.00,4,240.00"), "{}", row(retired));
+
+    // El día anterior ningún producto existía todavía
+    let csv = app.export("export_catalog_snapshot", json!({ "date": days_ago(1) }));
+    assert_eq!(csv.lines().count(), 1);
+
+    let err = app.invoke::<String>("export_catalog_snapshot", json!({ "date": "30/06/2024" })).unwrap_err();
+    assert_eq!(err, "Fecha inválida. Usa el formato AAAA-MM-DD.");
+}
+
+#[test]
+fn sales_trend_reads_daily_summaries_and_stays_consistent() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 300g").category("Suplementos").create(&app).unwrap();
+    let other = ProductFactory::new("Shaker").create(&app).unwrap();
+    app.add_stock(product_id, 50.0);
+    app.add_stock(other, 10.0);
+    SaleFactory::new(product_id, 1.0).total(100.0).date(&days_ago(0)).create(&app).unwrap();
+    SaleFactory::new(product_id, 2.0).total(200.0).date(&days_ago(2)).create(&app).unwrap();
+    SaleFactory::new(other, 1.0).total(15.0).date(&days_ago(2)).override_price().create(&app).unwrap();
+    SaleFactory::new(product_id, 1.0).total(100.0).date(&days_ago(40)).create(&app).unwrap();
+
+    let trend = |args: Value| -> Vec<(String, i64, f64)> {
+        let points: Vec<Value> = app.invoke("get_sales_trend", args).unwrap();
+        points
+            .iter()
+            .map(|p| (p["date"].as_str().unwrap().to_string(), p["sales_count"].as_i64().unwrap(), p["total_revenue"].as_f64().unwrap()))
+            .collect()
+    };
+    assert_eq!(trend(json!({})), vec![(days_ago(2), 2, 215.0), (days_ago(0), 1, 100.0)]);
+
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+
+    // Los días archivados solo aparecen si se pide el archivo
+    app.invoke::<Value>("archive_old_data", json!({ "beforeDate": days_ago(1), "dryRun": false })).unwrap();
+    assert_eq!(trend(json!({})), vec![(days_ago(0), 1, 100.0)]);
+    assert_eq!(trend(json!({ "includeArchive": true })).len(), 2);
+
+    // Eliminar un producto conserva sus ventas y los días ya calculados
+    app.invoke::<()>("delete_product", json!({ "id": other })).unwrap();
+    assert_eq!(trend(json!({ "includeArchive": true }))[0].2, 215.0);
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+
+    assert_eq!(app.invoke::<usize>("rebuild_daily_summaries", json!({})).unwrap(), 3);
+    assert_eq!(trend(json!({ "days": 60, "includeArchive": true })).len(), 3);
+}
+
+#[test]
+fn future_dated_sales_go_to_the_attention_bucket() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    app.add_stock(product_id, 10.0);
+    SaleFactory::new(product_id, 1.0).total(100.0).date(&days_ago(0)).create(&app).unwrap();
+    let err = SaleFactory::new(product_id, 2.0).date("2999-01-01").create(&app).unwrap_err();
+    assert_eq!(err, format!("La fecha de venta 2999-01-01 está en el futuro; se admite hasta el {}", days_ahead(1)));
+    // Venta de antes de esa validación, registrada con el reloj del equipo
+    // adelantado: llega con un volcado
+    SaleFactory::new(product_id, 2.0).total(50.0).date("2024-01-07").override_price().create(&app).unwrap();
+    let dump: Value = app.invoke("export_sql_dump", json!({})).unwrap();
+    let path = dump["path"].as_str().unwrap().to_string();
+    let sql = std::fs::read_to_string(&path).unwrap().replace("'2024-01-07'", "'2999-01-01'");
+    std::fs::write(&path, sql).unwrap();
+    let app = TestApp::first_run();
+    app.invoke::<Value>("import_sql_dump", json!({ "path": path })).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    app.login(&app.main, "admin", ADMIN_PASSWORD).unwrap();
+    assert_eq!(app.invoke::<usize>("rebuild_daily_summaries", json!({})).unwrap(), 2);
+
+    let points: Vec<Value> = app.invoke("get_sales_trend", json!({})).unwrap();
+    let days: Vec<&str> = points.iter().map(|p| p["date"].as_str().unwrap()).collect();
+    assert_eq!(days, [days_ago(0).as_str(), "atención"]);
+    assert_eq!(points[1]["total_revenue"], 50.0);
+
+    let health: Value = app.invoke("get_health_report", json!({})).unwrap();
+    assert_eq!(health["daily_summary_mismatches"], json!([]));
+
+    let report: Value = app.invoke("get_data_quality_report", json!({})).unwrap();
+    let issue = report["issues"].as_array().unwrap().iter().find(|i| i["check"] == "sales_future_date").unwrap();
+    assert_eq!(issue["count"], 1);
+    assert_eq!(issue["fixable"], false);
+}
+
+#[test]
+fn report_paths_agree_on_revenue_and_units() {
+    let (app, whey, _) = sales_fixture();
+    // Una venta de hoy para que la tendencia también lea la tabla en vivo
+    SaleFactory::new(whey, 1.0).total(245.5).date(&days_ago(0)).override_price().create(&app).unwrap();
+    app.invoke::<Value>("archive_old_data", json!({ "beforeDate": "2024-03-20", "dryRun": false })).unwrap();
+
+    let check: Value = app
+        .invoke("verify_report_consistency", json!({ "startDate": "2024-03-01", "endDate": days_ago(0) }))
+        .unwrap();
+    assert_eq!(check["mismatches"], json!([]));
+    assert_eq!(check["consistent"], true);
+    let paths = check["paths"].as_array().unwrap();
+    let sources: Vec<&str> = paths.iter().map(|p| p["source"].as_str().unwrap()).collect();
+    assert_eq!(sources, ["get_sales_totals", "get_sales_trend", "export_sales_report", "daily_sales_summary"]);
+    for path in paths {
+        assert_eq!(path["revenue"], 1205.5, "{}", path["source"]);
+    }
+    assert_eq!(paths[2]["tickets"], 5);
+    assert_eq!(paths[3]["units"], 22.0);
+
+    let err = app
+        .invoke::<Value>("verify_report_consistency", json!({ "startDate": "2024-04-01", "endDate": "2024-03-01" }))
+        .unwrap_err();
+    assert_eq!(err, "Rango de fechas inválido. Usa el formato AAAA-MM-DD.");
+}
+
+#[test]
+fn seller_leaderboard_hides_other_sellers_amounts() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    app.add_stock(product_id, 20.0);
+    let ana = UserFactory::new("ana");
+    let ana_window = ana.create_and_login(&app);
+    let beto = UserFactory::new("beto");
+    let beto_window = beto.create_and_login(&app);
+
+    // Ana y Beto empatan en ingresos de marzo; desempata la cantidad de tickets
+    SaleFactory::new(product_id, 1.0).create_in(&app, &ana_window).unwrap();
+    SaleFactory::new(product_id, 2.0).create_in(&app, &ana_window).unwrap();
+    SaleFactory::new(product_id, 3.0).create_in(&app, &beto_window).unwrap();
+    SaleFactory::new(product_id, 1.0).date("2024-04-02").create_in(&app, &beto_window).unwrap();
+
+    let users: Vec<Value> = app.invoke("get_users", json!({})).unwrap();
+    let id_of = |name: &str| users.iter().find(|u| u["username"] == name).unwrap()["id"].as_i64().unwrap();
+    app.invoke::<()>("set_seller_target", json!({ "userId": id_of("beto"), "monthlyTarget": 600.0 }))
+        .unwrap();
+
+    let board: Vec<Value> = app.invoke("get_seller_leaderboard", json!({ "period": "2024-03" })).unwrap();
+    let names: Vec<&str> = board.iter().map(|e| e["username"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["ana", "beto"]);
+    assert_eq!(board[0]["tickets"], 2);
+    assert_eq!(board[0]["net_revenue"], 300.0);
+    assert_eq!(board[0]["average_ticket"], 150.0);
+    assert_eq!(board[1]["target_progress"], 50.0);
+
+    // Beto ve su fila completa, pero de Ana solo la posición y los tickets
+    let board: Vec<Value> = app
+        .invoke_in(&beto_window, "get_seller_leaderboard", json!({ "period": "2024-03" }))
+        .unwrap();
+    assert!(board[0]["net_revenue"].is_null());
+    assert!(board[0]["average_ticket"].is_null());
+    assert_eq!(board[1]["net_revenue"], 300.0);
+    assert_eq!(board[1]["monthly_target"], 600.0);
+
+    app.set_setting("leaderboard_show_amounts", "true");
+    let board: Vec<Value> = app
+        .invoke_in(&beto_window, "get_seller_leaderboard", json!({ "period": "2024-03" }))
+        .unwrap();
+    assert_eq!(board[0]["net_revenue"], 300.0);
+}
+
+#[test]
+fn manual_prices_are_listed_as_overrides() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Whey 1kg").prices(100.0, 60.0).create(&app).unwrap();
+    app.add_stock(product_id, 10.0);
+    let seller = UserFactory::new("cajero").create_and_login(&app);
+
+    // Precio de lista y descuento registrado: no es un cambio de precio
+    SaleFactory::new(product_id, 2.0).total(200.0).create_in(&app, &seller).unwrap();
+    SaleFactory::new(product_id, 1.0).total(90.0).with("discount", json!(10.0)).create_in(&app, &seller).unwrap();
+    // Sin precio se cobra el de lista
+    let listed = SaleFactory::new(product_id, 1.0).with("sale_price", Value::Null).create_in(&app, &seller).unwrap();
+    // Solo el Administrador cobra otro precio, y marcándolo
+    let err = SaleFactory::new(product_id, 1.0).total(95.0).create(&app).unwrap_err();
+    assert_eq!(err, "El precio de la venta debe ser 100.00, el de lista. Para cobrar otro precio un Administrador debe marcar override");
+    let err = SaleFactory::new(product_id, 1.0).total(95.0).override_price().create_in(&app, &seller).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede cambiar el precio de venta. Precio permitido: 100.00");
+    assert_eq!(app.stock_of(product_id), 6.0);
+    // Cinco por ciento menos, por debajo del umbral de aviso
+    let small = SaleFactory::new(product_id, 1.0).total(95.0).override_price().create(&app).unwrap();
+    let large = SaleFactory::new(product_id, 2.0).total(150.0).override_price().create(&app).unwrap();
+
+    let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+    assert_eq!(sales.iter().find(|s| s["id"] == listed["id"]).unwrap()["sale_price"], 100.0);
+    let overrides: Vec<Value> = app.invoke("get_price_overrides", json!({})).unwrap();
+    let ids: Vec<&Value> = overrides.iter().map(|o| &o["sale_id"]).collect();
+    assert_eq!(ids, [&large["id"], &small["id"]]);
+    assert_eq!(overrides[0]["reference_price"], 100.0);
+    assert_eq!(overrides[0]["charged_price"], 75.0);
+    assert_eq!(overrides[0]["delta"], -25.0);
+    assert_eq!(overrides[0]["username"], "admin");
+
+    let notifications: Vec<Value> = app.invoke("get_notifications", json!({})).unwrap();
+    let alerts: Vec<&Value> = notifications.iter().filter(|n| n["kind"] == "price_override").collect();
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0]["message"].as_str().unwrap().contains(&format!("Venta #{}", large["id"])));
+
+    let path: String = app.invoke("export_price_overrides", json!({})).unwrap();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.contains(",100.00,75.00,-25.00,-25.0,,admin,admin"));
+    assert_eq!(overrides[0]["approved_by_username"], "admin");
+
+    // El Administrador corrige el precio de una venta del cajero: queda quién la
+    // cargó y quién autorizó el cambio
+    let mut sale = sales.iter().find(|s| s["id"] == listed["id"]).unwrap().clone();
+    sale["sale_price"] = json!(80.0);
+    sale["override"] = json!(true);
+    app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap();
+    let overrides: Vec<Value> = app.invoke("get_price_overrides", json!({})).unwrap();
+    let edited = overrides.iter().find(|o| o["sale_id"] == listed["id"]).unwrap();
+    assert_eq!(edited["username"], "cajero");
+    assert_eq!(edited["approved_by_username"], "admin");
+
+    // Un descuento sobre el tope configurado también necesita la autorización
+    app.set_setting("max_discount_percent", "20");
+    let discounted = || SaleFactory::new(product_id, 1.0).total(70.0).with("discount", json!(30.0));
+    let err = discounted().create_in(&app, &seller).unwrap_err();
+    assert_eq!(err, "Un descuento mayor al 20% necesita que un Administrador marque override");
+    let err = discounted().override_price().create_in(&app, &seller).unwrap_err();
+    assert_eq!(err, "Un descuento mayor al 20% necesita que un Administrador marque override");
+    let approved = discounted().override_price().create(&app).unwrap();
+    let overrides: Vec<Value> = app.invoke("get_price_overrides", json!({})).unwrap();
+    let listed = overrides.iter().find(|o| o["sale_id"] == approved["id"]).unwrap();
+    assert_eq!(listed["discount"], 30.0);
+    assert_eq!(listed["delta"], 0.0);
+    assert_eq!(listed["approved_by_username"], "admin");
+}
+
+#[test]
+fn prune_history_keeps_unread_notifications_and_asks_before_touching_the_audit_log() {
+    let app = TestApp::with_admin();
+    let product_id = ProductFactory::new("Whey 1kg").prices(100.0, 60.0).create(&app).unwrap();
+    app.add_stock(product_id, 10.0);
+    for _ in 0..2 {
+        SaleFactory::new(product_id, 1.0).total(50.0).override_price().create(&app).unwrap();
+    }
+    let notifications: Vec<Value> = app.invoke("get_notifications", json!({})).unwrap();
+    assert_eq!(notifications.len(), 2);
+    app.invoke::<()>("mark_notification_read", json!({ "id": notifications[0]["id"] })).unwrap();
+
+    // Retención de menos de un segundo (created_at tiene precisión de
+    // segundos): tras dos segundos todo lo anterior queda vencido
+    app.set_setting("retention_days.notifications", "0.00001");
+    app.set_setting("retention_days.audit_log", "0.00001");
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let report: Value = app.invoke("prune_history", json!({})).unwrap();
+    assert_eq!(report["tables"][0]["table"], "notifications");
+    assert_eq!(report["tables"][0]["deleted"], 1);
+    assert_eq!(report["tables"][1]["table"], "audit_log");
+    assert!(report["tables"][1]["cutoff"].is_null());
+    let remaining: Vec<Value> = app.invoke("get_notifications", json!({})).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0]["read"], false);
+
+    let before: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    assert!(!before.is_empty());
+    let report: Value = app.invoke("prune_history", json!({ "confirmAuditLog": true })).unwrap();
+    assert_eq!(report["tables"][1]["deleted"], before.len());
+    let log: Vec<Value> = app.invoke("get_audit_log", json!({})).unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0]["action"], "prune_history");
+    assert!(log[0]["details"].as_str().unwrap().starts_with(&format!("Se eliminaron {} registros", before.len())));
+
+    let seller = UserFactory::new("cajero").create_and_login(&app);
+    let err = app.invoke_in::<Value>(&seller, "prune_history", json!({})).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+}
+
+#[test]
+fn imported_costs_use_the_exchange_rate_of_each_sale_date() {
+    let app = TestApp::with_admin();
+    let creatine = ProductFactory::new("Creatina importada")
+        .prices(100.0, 20.0)
+        .with("currency", json!("usd"))
+        .create(&app)
+        .unwrap();
+    let whey = ProductFactory::new("Whey importado")
+        .prices(100.0, 10.0)
+        .with("currency", json!("USD"))
+        .create(&app)
+        .unwrap();
+    let local = ProductFactory::new("Barra local").with("currency", json!("PEN")).create(&app).unwrap();
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": creatine })).unwrap();
+    assert_eq!(product["currency"], "USD");
+    let product: Value = app.invoke("get_product_by_id", json!({ "id": local })).unwrap();
+    assert!(product["currency"].is_null());
+
+    let err = app
+        .invoke::<()>("set_exchange_rate", json!({ "currency": "PEN", "rate": 1.0, "date": "2024-03-01" }))
+        .unwrap_err();
+    assert_eq!(err, "PEN es la moneda local; no necesita tipo de cambio");
+    let err = app
+        .invoke::<()>("set_exchange_rate", json!({ "currency": "USD", "rate": 0.0, "date": "2024-03-01" }))
+        .unwrap_err();
+    assert_eq!(err, "El tipo de cambio debe ser mayor a 0");
+    app.invoke::<()>("set_exchange_rate", json!({ "currency": "USD", "rate": 3.5, "date": "2024-03-01" })).unwrap();
+    app.invoke::<()>("set_exchange_rate", json!({ "currency": "usd", "rate": 4.0, "date": "2024-04-01" })).unwrap();
+    let rates: Vec<Value> = app.invoke("get_exchange_rates", json!({ "currency": "USD" })).unwrap();
+    assert_eq!(rates.len(), 2);
+    assert_eq!(rates[0]["rate_date"], "2024-04-01");
+
+    app.add_stock(creatine, 5.0);
+    app.add_stock(whey, 5.0);
+    SaleFactory::new(creatine, 1.0).total(100.0).date("2024-03-10").create(&app).unwrap();
+    SaleFactory::new(creatine, 1.0).total(100.0).date("2024-04-05").create(&app).unwrap();
+    // Anterior al primer tipo de cambio registrado
+    SaleFactory::new(whey, 1.0).total(100.0).date("2024-02-15").create(&app).unwrap();
+
+    let csv = app.export("export_profitability_report", json!({}));
+    let row = |name: &str| csv.lines().find(|l| l.contains(name)).unwrap().split(',').skip(3).collect::<Vec<_>>().join(",");
+    assert!(csv.starts_with("product_id,sku,name,unit_cost,total_qty_sold,total_revenue,estimated_total_cost,gross_profit,margin_percent,cost_currency,warning\n"));
+    // 20 USD a 3.50 y a 4.00; el costo unitario usa el cambio de hoy
+    assert_eq!(row("Creatina importada"), "80.00,2,200.00,150.00,50.00,25,USD,");
+    assert_eq!(row("Whey importado"), "40.00,1,100.00,,,,USD,Sin tipo de cambio USD para 1 venta");
+    assert_eq!(row("Barra local"), "60.00,0,0.00,0.00,0.00,,PEN,");
+}
+
+#[test]
+fn multi_unit_discounted_sales_count_their_line_total_everywhere() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").prices(100.0, 60.0).create(&app).unwrap();
+    app.add_stock(whey, 10.0);
+    let today = days_ago(0);
+    // 5 × 100 con 10% de descuento: la línea vale 450, no 100 ni 500
+    let receipt: Value = app
+        .invoke(
+            "create_sale",
+            json!({ "sale": { "sale_date": today, "items": [{ "product_id": whey, "quantity": 5.0, "discount": 10.0 }] } }),
+        )
+        .unwrap();
+    assert_eq!(receipt["total"], 450.0);
+    SaleFactory::new(whey, 2.0).total(200.0).date(&today).create(&app).unwrap();
+
+    let totals: Value = app.invoke("get_sales_totals", json!({})).unwrap();
+    assert_eq!(totals["total_units"], 7.0);
+    assert_eq!(totals["total_revenue"], 650.0);
+    let by_product: Vec<Value> = app.invoke("get_sales_by_product", json!({})).unwrap();
+    assert_eq!(by_product[0]["total_revenue"], 650.0);
+    let trend: Vec<Value> = app.invoke("get_sales_trend", json!({})).unwrap();
+    assert_eq!(trend.last().unwrap()["total_revenue"], 650.0);
+    let csv = app.export("export_profitability_report", json!({}));
+    let row = csv.lines().find(|l| l.contains("Whey 1kg")).unwrap();
+    assert!(row.ends_with(",Whey 1kg,60.00,7,650.00,420.00,230.00,35,PEN,"), "{}", row);
+}
+
+#[test]
+fn discount_is_a_percentage_already_netted_from_revenue() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").prices(100.0, 60.0).create(&app).unwrap();
+    app.add_stock(whey, 20.0);
+    let today = days_ago(0);
+    let sell = |discount: f64| -> Result<Value, String> {
+        app.invoke(
+            "create_sale",
+            json!({ "sale": { "sale_date": today, "items": [{ "product_id": whey, "quantity": 5.0, "discount": discount }] } }),
+        )
+    };
+    // 10 es el 10%; 0.10 es el 0.1%, no el 10%
+    assert_eq!(sell(10.0).unwrap()["total"], 450.0);
+    assert_eq!(sell(0.10).unwrap()["total"], 499.5);
+    let err = sell(150.0).unwrap_err();
+    assert_eq!(err, "El descuento es un porcentaje entre 0 y 100 (150); usa 10 para el 10%");
+    let err = SaleFactory::new(whey, 1.0).with("discount", json!(-5.0)).create(&app).unwrap_err();
+    assert!(err.starts_with("El descuento es un porcentaje"), "{}", err);
+
+    let totals: Value = app.invoke("get_sales_totals", json!({})).unwrap();
+    assert_eq!(totals["total_revenue"], 949.5);
+    assert_eq!(totals["total_discounts"], 50.5);
+    let csv = app.export("export_financial_report", json!({}));
+    assert!(csv.contains("income,Ingresos por ventas,949.50\n"), "{}", csv);
+    assert!(csv.contains("discount,Descuentos otorgados,50.50\n"), "{}", csv);
+    let summary: Value = app.invoke("get_cash_summary", json!({})).unwrap();
+    assert_eq!(summary["total_income"], 949.5);
+    // 10 unidades a 60 de costo contra 949.50 cobrados, no 1000
+    let csv = app.export("export_profitability_report", json!({}));
+    let row = csv.lines().find(|l| l.contains("Whey 1kg")).unwrap();
+    assert!(row.ends_with(",Whey 1kg,60.00,10,949.50,600.00,349.50,37,PEN,"), "{}", row);
+}
+
+#[test]
+fn sales_by_channel_share_the_revenue() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").category("Proteínas").create(&app).unwrap();
+    let creatine = ProductFactory::new("Creatina 300g").category("Creatinas").create(&app).unwrap();
+    app.add_stock(whey, 20.0);
+    app.add_stock(creatine, 20.0);
+    let sell = |product: i64, quantity: f64, total: f64, channel: Value| {
+        SaleFactory::new(product, quantity).total(total).with("channel", channel).override_price().create(&app).unwrap();
+    };
+    sell(whey, 2.0, 300.0, json!("tienda"));
+    sell(creatine, 1.0, 100.0, json!(" Tienda "));
+    sell(whey, 1.0, 300.0, json!("instagram"));
+    sell(creatine, 3.0, 150.0, Value::Null);
+    sell(creatine, 1.0, 50.0, json!(""));
+
+    let channels: Vec<Value> = app.invoke("get_sales_by_channel", json!({})).unwrap();
+    assert_eq!(
+        channels,
+        vec![
+            json!({ "channel": "tienda", "sales_count": 2, "total_units": 3.0, "total_revenue": 400.0, "share_percent": 44.44 }),
+            json!({ "channel": "instagram", "sales_count": 1, "total_units": 1.0, "total_revenue": 300.0, "share_percent": 33.33 }),
+            json!({ "channel": "sin canal", "sales_count": 2, "total_units": 4.0, "total_revenue": 200.0, "share_percent": 22.22 }),
+        ]
+    );
+    let creatines: Vec<Value> = app.invoke("get_sales_by_channel", json!({ "category": "Creatinas" })).unwrap();
+    let shares: Vec<(&str, f64)> = creatines.iter().map(|c| (c["channel"].as_str().unwrap(), c["share_percent"].as_f64().unwrap())).collect();
+    assert_eq!(shares, [("sin canal", 66.67), ("tienda", 33.33)]);
+
+    let csv = app.export("export_sales_by_channel_report", json!({}));
+    assert_eq!(
+        csv,
+        "channel,sales_count,total_units,total_revenue,share_percent\n\
+         tienda,2,3,400.00,44.44\n\
+         instagram,1,1,300.00,33.33\n\
+         sin canal,2,4,200.00,22.22\n"
+    );
+}
+
+#[test]
+fn sales_by_user_keep_sales_without_a_seller() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 20.0);
+    let ana = UserFactory::new("ana").create_and_login(&app);
+    let kiosk = app.open_window("kiosko");
+    SaleFactory::new(whey, 2.0).create_in(&app, &ana).unwrap();
+    SaleFactory::new(whey, 2.0).create_in(&app, &ana).unwrap();
+    SaleFactory::new(whey, 1.0).total(120.0).override_price().create(&app).unwrap();
+    SaleFactory::new(whey, 3.0).create_in(&app, &kiosk).unwrap();
+    // Las anuladas no cuentan para la comisión
+    let voided = SaleFactory::new(whey, 5.0).create_in(&app, &ana).unwrap();
+    app.invoke::<Vec<Value>>("void_sale", json!({ "saleId": voided["id"], "reason": "Doble registro" })).unwrap();
+
+    let rows: Vec<Value> = app.invoke("get_sales_by_user", json!({})).unwrap();
+    let summary: Vec<(Value, &str, i64, f64, f64)> = rows
+        .iter()
+        .map(|r| {
+            (
+                r["user_id"].clone(),
+                r["username"].as_str().unwrap(),
+                r["sales_count"].as_i64().unwrap(),
+                r["total_units"].as_f64().unwrap(),
+                r["total_revenue"].as_f64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (rows[0]["user_id"].clone(), "ana", 2, 4.0, 400.0),
+            (Value::Null, "sin usuario", 1, 3.0, 300.0),
+            (json!(1), "admin", 1, 1.0, 120.0),
+        ]
+    );
+    assert_eq!(rows[0]["fullname"], "Usuario ana");
+
+    let csv = app.export("export_sales_by_user_report", json!({}));
+    let ana_id = rows[0]["user_id"].as_i64().unwrap();
+    assert_eq!(
+        csv,
+        format!(
+            "user_id,username,fullname,sales_count,total_units,total_revenue\n\
+             {},ana,Usuario ana,2,4,400.00\n\
+             ,sin usuario,,1,3,300.00\n\
+             1,admin,Administrador del Sistema,1,1,120.00\n",
+            ana_id
+        )
+    );
+}
+
+#[test]
+fn sales_heatmap_buckets_by_weekday_and_hour() {
+    let app = TestApp::with_admin();
+    let whey = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(whey, 20.0);
+    // 2024-03-04 es lunes y 2024-03-10, domingo
+    for date in ["2024-03-04 09:15:00", "2024-03-04T09:50", "2024-03-11 18:05:00", "2024-03-04", "2024-03-10 09:00:00"] {
+        SaleFactory::new(whey, 1.0).date(date).create(&app).unwrap();
+    }
+    SaleFactory::new(whey, 2.0).date("2024-03-10 20:30:00").create(&app).unwrap();
+
+    let heatmap = |args: Value| -> Vec<(i64, String, Value, i64, f64)> {
+        let buckets: Vec<Value> = app.invoke("get_sales_heatmap", args).unwrap();
+        buckets
+            .iter()
+            .map(|b| {
+                (
+                    b["weekday"].as_i64().unwrap(),
+                    b["weekday_name"].as_str().unwrap().to_string(),
+                    b["hour"].clone(),
+                    b["sales_count"].as_i64().unwrap(),
+                    b["total_revenue"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    let by_hour = heatmap(json!({ "startDate": "2024-03-01", "endDate": "2024-03-31" }));
+    assert_eq!(
+        by_hour,
+        [
+            (1, "lunes".to_string(), json!(9), 2, 200.0),
+            (1, "lunes".to_string(), json!(18), 1, 100.0),
+            (1, "lunes".to_string(), Value::Null, 1, 100.0),
+            (7, "domingo".to_string(), json!(9), 1, 100.0),
+            (7, "domingo".to_string(), json!(20), 1, 200.0),
+        ]
+    );
+    let by_day = heatmap(json!({ "startDate": "2024-03-05", "endDate": "2024-03-31", "groupBy": "weekday" }));
+    assert_eq!(by_day, [(1, "lunes".to_string(), Value::Null, 1, 100.0), (7, "domingo".to_string(), Value::Null, 2, 300.0)]);
+    let err = app.invoke::<Vec<Value>>("get_sales_heatmap", json!({ "groupBy": "month" })).unwrap_err();
+    assert_eq!(err, "group_by inválido: 'month'. Usa hour o weekday");
+}
+
+#[test]
+fn free_text_with_commas_is_quoted_in_every_export() {
+    let app = TestApp::with_admin();
+    let bar = ProductFactory::new("Barra, chocolate").sku("BARRA-1").category("Snacks, dulces").create(&app).unwrap();
+    app.add_stock(bar, 5.0);
+    SaleFactory::new(bar, 1.0).create(&app).unwrap();
+    let voided = SaleFactory::new(bar, 1.0).create(&app).unwrap();
+    app.invoke::<Vec<Value>>("void_sale", json!({ "saleId": voided["id"], "reason": "Cliente se arrepintió, pagó con otra tarjeta" }))
+        .unwrap();
+
+    let csv = app.export("export_stock_movements_report", json!({}));
+    let note = format!(",\"Anulación de la venta #{}: Cliente se arrepintió, pagó con otra tarjeta\",", voided["id"]);
+    assert!(csv.contains(&note), "{}", csv);
+
+    let csv = app.export("export_top_products_report", json!({}));
+    assert!(csv.contains(&format!("\n{},BARRA-1,\"Barra, chocolate\",\"Snacks, dulces\",1,100.00\n", bar)), "{}", csv);
+
+    let csv = app.export("export_profitability_report", json!({}));
+    assert!(csv.contains(&format!("\n{},BARRA-1,\"Barra, chocolate\",60.00,1,", bar)), "{}", csv);
+}
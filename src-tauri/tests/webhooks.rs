@@ -0,0 +1,229 @@
+//! Webhooks: firma, registro de entregas, reintentos y eventos encolados.
+
+mod common;
+
+use common::{ProductFactory, SaleFactory, TestApp, UserFactory};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{channel, Receiver};
+
+/// Atiende una petición HTTP por cada código de `statuses`, respondiendo con
+/// ese código, y entrega cada petición recibida (cabeceras y cuerpo) por el
+/// canal. Devuelve la dirección del servidor.
+fn serve_webhook(statuses: Vec<u16>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, requests) = channel();
+    std::thread::spawn(move || {
+        for status in statuses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut chunk = [0u8; 1024];
+            // Cabeceras y luego el cuerpo que anuncia Content-Length
+            let body_len = loop {
+                let read = stream.read(&mut chunk).unwrap();
+                request.extend_from_slice(&chunk[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    break end + 4 + length;
+                }
+            };
+            while request.len() < body_len {
+                let read = stream.read(&mut chunk).unwrap();
+                request.extend_from_slice(&chunk[..read]);
+            }
+            let response = format!("HTTP/1.1 {} Prueba\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            stream.write_all(response.as_bytes()).unwrap();
+            sender.send(String::from_utf8(request).unwrap()).unwrap();
+        }
+    });
+    (format!("http://{}/hooks/vitasport", addr), requests)
+}
+
+/// Valor de la cabecera `name` de una petición recibida por `serve_webhook`.
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    let (head, _) = request.split_once("\r\n\r\n").unwrap();
+    head.lines().find_map(|l| {
+        let (key, value) = l.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+fn body(request: &str) -> Value {
+    serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap()
+}
+
+/// Segundos de `from` a `to`, dos fechas "AAAA-MM-DD HH:MM:SS" de SQLite.
+fn seconds_between(from: &Value, to: &Value) -> i64 {
+    rusqlite::Connection::open_in_memory()
+        .and_then(|conn| {
+            conn.query_row(
+                "SELECT strftime('%s', ?2) - strftime('%s', ?1)",
+                [from.as_str().unwrap(), to.as_str().unwrap()],
+                |row| row.get(0),
+            )
+        })
+        .unwrap()
+}
+
+fn add_webhook(app: &TestApp, url: &str, secret: Option<&str>, events: &[&str]) -> i64 {
+    app.invoke(
+        "add_webhook",
+        json!({ "webhook": { "url": url, "secret": secret, "events": events, "min_amount": null, "active": true } }),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_webhook_signs_the_body_and_is_logged_without_retries() {
+    let app = TestApp::with_admin();
+    let (url, requests) = serve_webhook(vec![200, 500]);
+    let webhook = add_webhook(&app, &url, Some("clave-firma"), &["sale.created"]);
+
+    let delivery: Value = app.invoke("test_webhook", json!({ "id": webhook })).unwrap();
+    assert_eq!(delivery["event"], "test");
+    assert_eq!(delivery["status"], "entregado");
+    assert_eq!(delivery["status_code"], 200);
+    assert_eq!(delivery["attempts"], 1);
+
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("POST /hooks/vitasport "), "{}", request);
+    assert_eq!(header(&request, "X-VitaSport-Event"), Some("test"));
+    let raw_body = request.split_once("\r\n\r\n").unwrap().1;
+    let expected: String = hmac_sha256::HMAC::mac(raw_body.as_bytes(), b"clave-firma")
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(header(&request, "X-VitaSport-Signature"), Some(format!("sha256={}", expected).as_str()));
+    assert_eq!(body(&request)["event"], "test");
+
+    // Una prueba fallida queda registrada, pero no se reintenta sola
+    let failed: Value = app.invoke("test_webhook", json!({ "id": webhook })).unwrap();
+    requests.recv().unwrap();
+    assert_eq!(failed["status"], "fallido");
+    assert_eq!(failed["status_code"], 500);
+    assert_eq!(failed["error"], "HTTP 500");
+    assert!(failed["next_attempt_at"].is_null());
+
+    let deliveries: Vec<Value> = app.invoke("get_webhook_deliveries", json!({ "webhookId": webhook })).unwrap();
+    let ids: Vec<&Value> = deliveries.iter().map(|d| &d["id"]).collect();
+    assert_eq!(ids, [&failed["id"], &delivery["id"]]);
+    let other: Vec<Value> = app.invoke("get_webhook_deliveries", json!({ "webhookId": webhook + 1 })).unwrap();
+    assert!(other.is_empty());
+    let limited: Vec<Value> = app.invoke("get_webhook_deliveries", json!({ "limit": 1 })).unwrap();
+    assert_eq!(limited[0]["id"], failed["id"]);
+
+    let seller = UserFactory::new("caja1").create_and_login(&app);
+    let err = app.invoke_in::<Value>(&seller, "get_webhook_deliveries", json!({})).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+    let err = app.invoke_in::<Value>(&seller, "test_webhook", json!({ "id": webhook })).unwrap_err();
+    assert_eq!(err, "Solo un Administrador puede realizar esta acción");
+}
+
+#[test]
+fn failed_deliveries_back_off_until_they_give_up() {
+    let app = TestApp::with_admin();
+    let (url, requests) = serve_webhook(vec![500, 503, 500, 500, 500, 200]);
+    let webhook = add_webhook(&app, &url, None, &["sale.created"]);
+    let product_id = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(product_id, 5.0);
+    let sale = SaleFactory::new(product_id, 1.0).create(&app).unwrap();
+
+    let deliveries: Vec<Value> = app.invoke("get_webhook_deliveries", json!({ "webhookId": webhook })).unwrap();
+    assert_eq!(deliveries.len(), 1);
+    let queued = &deliveries[0];
+    assert_eq!(queued["event"], "sale.created");
+    assert_eq!(queued["status"], "pendiente");
+    assert_eq!(queued["attempts"], 0);
+    let retry = || app.invoke::<Value>("retry_webhook_delivery", json!({ "id": queued["id"] }));
+
+    // Cada fallo programa el siguiente intento más lejos
+    for (attempt, delay) in [60, 300, 1800, 7200].into_iter().enumerate() {
+        let delivery = retry().unwrap();
+        assert_eq!(delivery["status"], "pendiente");
+        assert_eq!(delivery["attempts"], attempt + 1);
+        assert_eq!(seconds_between(&delivery["last_attempt_at"], &delivery["next_attempt_at"]), delay);
+    }
+    let request = requests.recv().unwrap();
+    assert_eq!(header(&request, "X-VitaSport-Event"), Some("sale.created"));
+    assert_eq!(header(&request, "X-VitaSport-Signature"), None);
+    assert_eq!(body(&request)["data"]["sale_id"], sale["id"]);
+
+    // Agotados los reintentos queda fallida; a mano todavía se puede reenviar
+    let given_up = retry().unwrap();
+    assert_eq!(given_up["status"], "fallido");
+    assert_eq!(given_up["attempts"], 5);
+    assert_eq!(given_up["status_code"], 500);
+    assert!(given_up["next_attempt_at"].is_null());
+    let delivered = retry().unwrap();
+    assert_eq!(delivered["status"], "entregado");
+    assert_eq!(delivered["attempts"], 6);
+    assert!(delivered["error"].is_null());
+    assert_eq!(retry().unwrap_err(), "La entrega ya se realizó");
+    assert_eq!(requests.iter().count(), 5);
+}
+
+#[test]
+fn out_of_stock_is_queued_only_when_the_stock_runs_out() {
+    let app = TestApp::with_admin();
+    let webhook = add_webhook(&app, "https://example.com/hooks", None, &["product.out_of_stock"]);
+    let product_id = ProductFactory::new("Creatina 300g").create(&app).unwrap();
+    app.add_stock(product_id, 2.0);
+    let alerts = || -> Vec<Value> {
+        let deliveries: Vec<Value> = app.invoke("get_webhook_deliveries", json!({ "webhookId": webhook })).unwrap();
+        deliveries.into_iter().filter(|d| d["event"] == "product.out_of_stock").collect()
+    };
+
+    let first = SaleFactory::new(product_id, 1.0).create(&app).unwrap();
+    assert!(alerts().is_empty());
+    let last = SaleFactory::new(product_id, 1.0).create(&app).unwrap();
+    assert_eq!(alerts().len(), 1);
+
+    let stored = |id: &Value| -> Value {
+        let sales: Vec<Value> = app.invoke("get_sales", json!({})).unwrap();
+        sales.into_iter().find(|s| s["id"] == *id).unwrap()
+    };
+    // Corregir la venta sin tocar la cantidad no vuelve a avisar
+    let mut sale = stored(&last["id"]);
+    sale["note"] = json!("Cliente frecuente");
+    app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap();
+    assert_eq!(alerts().len(), 1);
+
+    // Repuesto y vendido otra vez: es un nuevo quiebre
+    app.add_stock(product_id, 1.0);
+    let mut sale = stored(&first["id"]);
+    sale["quantity"] = json!(2.0);
+    sale["sale_price"] = json!(200.0);
+    app.invoke::<Value>("update_sale", json!({ "sale": sale })).unwrap();
+    assert_eq!(app.stock_of(product_id), 0.0);
+    assert_eq!(alerts().len(), 2);
+}
+
+#[test]
+fn resolved_deliveries_are_pruned_but_pending_ones_are_kept() {
+    let app = TestApp::with_admin();
+    let (url, requests) = serve_webhook(vec![200]);
+    let webhook = add_webhook(&app, &url, None, &["sale.created"]);
+    app.invoke::<Value>("test_webhook", json!({ "id": webhook })).unwrap();
+    requests.recv().unwrap();
+    let product_id = ProductFactory::new("Whey 1kg").create(&app).unwrap();
+    app.add_stock(product_id, 1.0);
+    SaleFactory::new(product_id, 1.0).create(&app).unwrap();
+
+    // Retención de menos de un segundo, como en la poda de notificaciones
+    app.set_setting("retention_days.webhook_deliveries", "0.00001");
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    let report: Value = app.invoke("prune_history", json!({})).unwrap();
+    let pruned = report["tables"].as_array().unwrap().iter().find(|t| t["table"] == "webhook_deliveries").unwrap();
+    assert_eq!(pruned["deleted"], 1);
+
+    let deliveries: Vec<Value> = app.invoke("get_webhook_deliveries", json!({})).unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["status"], "pendiente");
+}
@@ -0,0 +1,98 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api';
+import { RefreshCw } from 'lucide-react';
+import Modal from './Modal';
+import Button from './Button';
+
+interface AppInfo {
+  version: string;
+  git_hash?: string | null;
+  build_date?: string | null;
+  schema_version: number;
+}
+
+interface UpdateCheck {
+  current_version: string;
+  latest_version: string;
+  update_available: boolean;
+  download_url?: string | null;
+  notes?: string | null;
+}
+
+interface AboutDialogProps {
+  isOpen: boolean;
+  onClose: () => void;
+}
+
+/**
+ * Ventana "Acerca de": versión exacta del build para los reportes de errores
+ * y consulta manual de actualizaciones (no descarga nada)
+ */
+export default function AboutDialog({ isOpen, onClose }: AboutDialogProps) {
+  const [info, setInfo] = useState<AppInfo | null>(null);
+  const [update, setUpdate] = useState<UpdateCheck | null>(null);
+  const [checking, setChecking] = useState(false);
+  const [updateError, setUpdateError] = useState('');
+
+  useEffect(() => {
+    if (!isOpen || !(typeof window !== 'undefined' && '__TAURI__' in window)) return;
+    setUpdate(null);
+    setUpdateError('');
+    invoke<AppInfo>('get_app_info').then(setInfo).catch(() => setInfo(null));
+  }, [isOpen]);
+
+  const handleCheckUpdates = async () => {
+    setChecking(true);
+    setUpdateError('');
+    try {
+      setUpdate(await invoke<UpdateCheck>('check_for_updates'));
+    } catch (e) {
+      setUpdate(null);
+      setUpdateError(String(e));
+    } finally {
+      setChecking(false);
+    }
+  };
+
+  const rows: [string, string][] = info
+    ? [
+        ['Versión', info.version],
+        ['Compilación', info.git_hash || 'sin datos'],
+        ['Fecha del build', info.build_date || 'sin datos'],
+        ['Esquema de la base', String(info.schema_version)],
+      ]
+    : [];
+
+  return (
+    <Modal isOpen={isOpen} onClose={onClose} title="Acerca de VitaSport" size="sm">
+      <div className="space-y-4">
+        <dl className="grid grid-cols-2 gap-2 text-sm">
+          {rows.map(([label, value]) => (
+            <div key={label} className="contents">
+              <dt className="text-gray-500 dark:text-gray-400">{label}</dt>
+              <dd className="font-mono text-gray-800 dark:text-gray-100">{value}</dd>
+            </div>
+          ))}
+        </dl>
+
+        <Button variant="secondary" icon={RefreshCw} onClick={handleCheckUpdates} disabled={checking}>
+          {checking ? 'Buscando…' : 'Buscar actualizaciones'}
+        </Button>
+        {update && (
+          <p className="text-sm text-gray-700 dark:text-gray-300">
+            {update.update_available
+              ? `Hay una versión nueva: ${update.latest_version}.`
+              : `Ya tienes la última versión (${update.current_version}).`}
+            {update.update_available && update.download_url && (
+              <span className="block break-all text-gray-500 dark:text-gray-400">{update.download_url}</span>
+            )}
+            {update.update_available && update.notes && (
+              <span className="block mt-1">{update.notes}</span>
+            )}
+          </p>
+        )}
+        {updateError && <p className="text-sm text-red-600 dark:text-red-400">{updateError}</p>}
+      </div>
+    </Modal>
+  );
+}
@@ -0,0 +1,110 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api';
+import { open } from '@tauri-apps/api/dialog';
+import { Paperclip, Trash2 } from 'lucide-react';
+import Modal from './Modal';
+import Button from './Button';
+
+interface Attachment {
+  id: number;
+  entity: string;
+  entity_id: number;
+  filename: string;
+  stored_path: string;
+  mime_type: string;
+  size: number;
+  uploaded_by?: string | null;
+  created_at: string;
+}
+
+interface AttachmentsDialogProps {
+  /** Tabla del registro: sales, stock_movements, purchases o products */
+  entity: string;
+  entityId: number | null;
+  title: string;
+  onClose: () => void;
+}
+
+/**
+ * Adjuntos de un registro (foto de un producto dañado, factura en PDF…).
+ * El backend copia el archivo a la carpeta de datos de la aplicación.
+ */
+export default function AttachmentsDialog({ entity, entityId, title, onClose }: AttachmentsDialogProps) {
+  const [attachments, setAttachments] = useState<Attachment[]>([]);
+  const [error, setError] = useState('');
+
+  const load = async () => {
+    if (entityId == null) return;
+    try {
+      setAttachments(await invoke<Attachment[]>('list_attachments', { entity, id: entityId }));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  useEffect(() => {
+    setError('');
+    setAttachments([]);
+    if (entityId != null && '__TAURI__' in window) load();
+  }, [entity, entityId]);
+
+  const handleAdd = async () => {
+    const selected = await open({
+      multiple: false,
+      filters: [{ name: 'PDF o imagen', extensions: ['pdf', 'png', 'jpg', 'jpeg', 'webp'] }],
+    });
+    if (typeof selected !== 'string') return;
+    setError('');
+    try {
+      await invoke('add_attachment', { entity, id: entityId, sourcePath: selected });
+      await load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleDelete = async (attachment: Attachment) => {
+    if (!window.confirm(`¿Eliminar el adjunto "${attachment.filename}"?`)) return;
+    setError('');
+    try {
+      await invoke('delete_attachment', { id: attachment.id });
+      await load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <Modal isOpen={entityId != null} onClose={onClose} title={title} size="md">
+      <div className="space-y-4">
+        {attachments.length === 0 ? (
+          <p className="text-sm text-gray-500 dark:text-gray-400">Sin adjuntos</p>
+        ) : (
+          <ul className="divide-y divide-gray-100 dark:divide-gray-700">
+            {attachments.map((a) => (
+              <li key={a.id} className="flex items-center justify-between gap-3 py-2">
+                <div className="min-w-0">
+                  <p className="text-sm font-medium text-gray-900 dark:text-gray-100 truncate">{a.filename}</p>
+                  <p className="text-xs text-gray-500 dark:text-gray-400">
+                    {(a.size / 1024).toFixed(0)} KB · {a.uploaded_by || 'sin usuario'} · {a.created_at}
+                  </p>
+                </div>
+                <button
+                  onClick={() => handleDelete(a)}
+                  className="p-2 text-red-600 dark:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/30 rounded-lg transition-colors"
+                  title="Eliminar adjunto"
+                >
+                  <Trash2 size={16} />
+                </button>
+              </li>
+            ))}
+          </ul>
+        )}
+        {error && <p className="text-sm text-red-600 dark:text-red-400">{error}</p>}
+        <Button variant="secondary" icon={Paperclip} onClick={handleAdd}>
+          Adjuntar archivo
+        </Button>
+      </div>
+    </Modal>
+  );
+}
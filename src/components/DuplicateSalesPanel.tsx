@@ -0,0 +1,100 @@
+import { useState } from 'react';
+import { invoke } from '@tauri-apps/api';
+import { Copy } from 'lucide-react';
+import Button from './Button';
+
+interface DuplicateSale {
+  id: number;
+  sale_date: string;
+  movement_id?: number | null;
+}
+
+interface DuplicatePair {
+  product_id: number;
+  product_name?: string | null;
+  quantity: number;
+  sale_price: number;
+  created_by?: string | null;
+  seconds_apart: number;
+  first: DuplicateSale;
+  second: DuplicateSale;
+}
+
+/**
+ * Ventas duplicadas por doble clic (anteriores a la protección contra envíos
+ * repetidos). Solo lista pares sospechosos; cada uno se confirma a mano.
+ */
+export default function DuplicateSalesPanel() {
+  const [windowSeconds, setWindowSeconds] = useState(10);
+  const [pairs, setPairs] = useState<DuplicatePair[] | null>(null);
+  const [error, setError] = useState('');
+
+  const search = async () => {
+    setError('');
+    try {
+      setPairs(await invoke<DuplicatePair[]>('find_probable_duplicates', { windowSeconds }));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const resolve = async (pair: DuplicatePair) => {
+    const confirmed = window.confirm(
+      `Se conservará la venta #${pair.first.id} y se eliminará la #${pair.second.id} ` +
+        `(${pair.product_name ?? `Producto #${pair.product_id}`}, ${pair.quantity} u.) con su movimiento de stock.\n\n¿Continuar?`
+    );
+    if (!confirmed) return;
+    setError('');
+    try {
+      await invoke('resolve_duplicate', { keepId: pair.first.id, removeId: pair.second.id });
+      await search();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="bg-white rounded-lg shadow p-6">
+      <div className="flex items-center gap-3 mb-4">
+        <Copy className="text-primary-600" size={24} />
+        <h2 className="text-xl font-semibold">Ventas duplicadas</h2>
+      </div>
+      <div className="flex items-end gap-3 mb-4">
+        <div>
+          <label className="block text-sm font-medium text-gray-700 mb-2">Segundos entre ventas</label>
+          <input
+            type="number"
+            min={1}
+            max={3600}
+            value={windowSeconds}
+            onChange={(e) => setWindowSeconds(Number(e.target.value))}
+            className="w-32 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500"
+          />
+        </div>
+        <Button variant="secondary" onClick={search}>Buscar</Button>
+      </div>
+      {error && <p className="text-sm text-red-600 mb-3">{error}</p>}
+      {pairs && pairs.length === 0 && <p className="text-sm text-gray-600">No se encontraron ventas duplicadas.</p>}
+      {pairs && pairs.length > 0 && (
+        <ul className="divide-y divide-gray-100">
+          {pairs.map((pair) => (
+            <li key={`${pair.first.id}-${pair.second.id}`} className="flex items-center justify-between gap-3 py-2">
+              <div className="text-sm">
+                <p className="font-medium text-gray-900">
+                  #{pair.first.id} y #{pair.second.id} · {pair.product_name ?? `Producto #${pair.product_id}`}
+                </p>
+                <p className="text-xs text-gray-500">
+                  {pair.quantity} u. · ${pair.sale_price.toLocaleString()} · {pair.created_by || 'sin usuario'} ·{' '}
+                  {pair.seconds_apart} s de diferencia
+                </p>
+              </div>
+              <Button variant="danger" onClick={() => resolve(pair)}>
+                Eliminar #{pair.second.id}
+              </Button>
+            </li>
+          ))}
+        </ul>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,125 @@
+import { useState } from 'react';
+import { invoke } from '@tauri-apps/api';
+
+interface FirstRunWizardProps {
+  /** Se llama cuando el backend confirmó la configuración */
+  onCompleted: () => void;
+}
+
+const TEMPLATES = [
+  { value: '', label: 'Sin plantilla' },
+  { value: 'suplementos', label: 'Tienda de suplementos' },
+  { value: 'gimnasio', label: 'Gimnasio con kiosco' },
+];
+
+const inputClass =
+  'w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-transparent bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100';
+
+/**
+ * Asistente de primera ejecución: datos de la empresa, contraseña nueva del
+ * usuario admin (la predeterminada no sirve para entrar) y plantilla de
+ * categorías y canales.
+ */
+export default function FirstRunWizard({ onCompleted }: FirstRunWizardProps) {
+  const [companyName, setCompanyName] = useState('');
+  const [companyPhone, setCompanyPhone] = useState('');
+  const [companyAddress, setCompanyAddress] = useState('');
+  const [password, setPassword] = useState('');
+  const [confirmation, setConfirmation] = useState('');
+  const [template, setTemplate] = useState('suplementos');
+  const [error, setError] = useState('');
+  const [saving, setSaving] = useState(false);
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (password !== confirmation) {
+      setError('Las contraseñas no coinciden');
+      return;
+    }
+    setError('');
+    setSaving(true);
+    try {
+      await invoke('complete_initial_setup', {
+        setup: {
+          company_name: companyName,
+          company_phone: companyPhone || null,
+          company_address: companyAddress || null,
+          admin_password: password,
+          template: template || null,
+        },
+      });
+      onCompleted();
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <form onSubmit={handleSubmit} className="space-y-4">
+      <p className="text-sm text-gray-600 dark:text-gray-400">
+        Antes de empezar, completa los datos de tu negocio y elige la contraseña del usuario <strong>admin</strong>.
+      </p>
+      <div>
+        <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Nombre de la empresa *</label>
+        <input value={companyName} onChange={(e) => setCompanyName(e.target.value)} required className={inputClass} />
+      </div>
+      <div className="grid grid-cols-2 gap-3">
+        <div>
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Teléfono</label>
+          <input value={companyPhone} onChange={(e) => setCompanyPhone(e.target.value)} className={inputClass} />
+        </div>
+        <div>
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Dirección</label>
+          <input value={companyAddress} onChange={(e) => setCompanyAddress(e.target.value)} className={inputClass} />
+        </div>
+      </div>
+      <div className="grid grid-cols-2 gap-3">
+        <div>
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Contraseña nueva *</label>
+          <input
+            type="password"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            required
+            minLength={8}
+            autoComplete="new-password"
+            className={inputClass}
+          />
+        </div>
+        <div>
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Repetir contraseña *</label>
+          <input
+            type="password"
+            value={confirmation}
+            onChange={(e) => setConfirmation(e.target.value)}
+            required
+            autoComplete="new-password"
+            className={inputClass}
+          />
+        </div>
+      </div>
+      <div>
+        <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Categorías y canales iniciales</label>
+        <select value={template} onChange={(e) => setTemplate(e.target.value)} className={inputClass}>
+          {TEMPLATES.map((t) => (
+            <option key={t.value} value={t.value}>{t.label}</option>
+          ))}
+        </select>
+      </div>
+      {error && (
+        <div className="p-3 bg-red-50 dark:bg-red-900/20 border border-red-200 dark:border-red-800/50 rounded-xl">
+          <p className="text-sm text-red-700 dark:text-red-400 text-center">{error}</p>
+        </div>
+      )}
+      <button
+        type="submit"
+        disabled={saving}
+        className="w-full py-3 bg-gradient-to-r from-blue-600 to-purple-600 hover:from-blue-700 hover:to-purple-700 text-white font-semibold rounded-xl shadow-lg transition-all duration-200 disabled:opacity-50"
+      >
+        {saving ? 'Guardando…' : 'Comenzar'}
+      </button>
+    </form>
+  );
+}
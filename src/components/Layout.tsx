@@ -1,3 +1,4 @@
+import { useState } from 'react';
 import { Outlet, Link, useLocation, useNavigate } from 'react-router-dom';
 import { 
   LayoutDashboard, 
@@ -6,10 +7,12 @@ import {
   FileText,
   Users,
   LogOut,
-  Settings
+  Settings,
+  Info
 } from 'lucide-react';
 import { useDarkMode } from '../hooks/useDarkMode';
 import { useAuth } from '../contexts/AuthContext';
+import AboutDialog from './AboutDialog';
 
 const menuItems = [
   { path: '/dashboard', icon: LayoutDashboard, label: 'Dashboard' },
@@ -28,6 +31,7 @@ export default function Layout() {
   const location = useLocation();
   const navigate = useNavigate();
   const { username, user, logout } = useAuth();
+  const [isAboutOpen, setIsAboutOpen] = useState(false);
   
   // Detectar modo oscuro del sistema automáticamente
   useDarkMode();
@@ -47,6 +51,10 @@ export default function Layout() {
     if (role === 'Almacenero') {
       return ['/dashboard', '/inventory', '/reports'].includes(item.path);
     }
+    if (role === 'Auditor') {
+      // Solo lectura: el backend rechaza cualquier cambio
+      return item.path !== '/users' && item.path !== '/settings';
+    }
     // Si no hay rol definido, por seguridad oculta Usuarios y Configuración
     return item.path !== '/users' && item.path !== '/settings';
   });
@@ -60,7 +68,7 @@ export default function Layout() {
           <p className="text-sm text-gray-400 dark:text-gray-500 mt-1">Sistema de Inventario</p>
         </div>
         
-        <nav className="p-4 flex-1 overflow-y-auto pb-32">
+        <nav className="p-4 flex-1 overflow-y-auto pb-40">
           {visibleItems.map((item) => {
             const Icon = item.icon;
             const isActive = location.pathname === item.path;
@@ -106,6 +114,13 @@ export default function Layout() {
             <LogOut size={16} />
             <span className="text-sm font-medium">Cerrar Sesión</span>
           </button>
+          <button
+            onClick={() => setIsAboutOpen(true)}
+            className="w-full flex items-center justify-center gap-2 mt-2 text-xs text-gray-400 hover:text-white transition-colors"
+          >
+            <Info size={14} />
+            <span>Acerca de</span>
+          </button>
         </div>
       </aside>
 
@@ -115,6 +130,8 @@ export default function Layout() {
           <Outlet />
         </div>
       </main>
+
+      <AboutDialog isOpen={isAboutOpen} onClose={() => setIsAboutOpen(false)} />
     </div>
   );
 }
@@ -1,8 +1,10 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api';
 import Button from './Button';
 
 interface ProductFormData {
   sku?: string;
+  barcode?: string;
   name: string;
   brand?: string;
   category?: string;
@@ -15,20 +17,31 @@ interface ProductFormData {
   lot_number?: string;
   min_stock?: number;
   max_stock?: number;
+  /** Solo al crear: unidades del ingreso "stock inicial" */
+  initial_stock?: number;
   location?: string;
   status?: string;
+  unit?: string;
 }
 
+/** Categorías sugeridas mientras no hay ninguna cargada (o fuera de Tauri) */
+const DEFAULT_CATEGORIES = ['Suplementos', 'Proteínas', 'Aminoácidos', 'Energéticos', 'Vitaminas', 'Snacks', 'Accesorios'];
+
 interface ProductFormProps {
   initialData?: ProductFormData;
+  /** Id del producto en edición; habilita la sugerencia de mínimo y máximo */
+  productId?: number;
+  /** SKU con el que empieza un producto nuevo (p. ej. un código escaneado) */
+  defaultSku?: string;
   onSubmit: (data: ProductFormData) => void;
   onCancel: () => void;
 }
 
-export default function ProductForm({ initialData, onSubmit, onCancel }: ProductFormProps) {
+export default function ProductForm({ initialData, productId, defaultSku, onSubmit, onCancel }: ProductFormProps) {
   const [formData, setFormData] = useState<ProductFormData>(initialData || {
     name: '',
-    sku: '',
+    sku: defaultSku || '',
+    barcode: '',
     brand: '',
     category: '',
     presentation: '',
@@ -40,9 +53,25 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
     lot_number: '',
     min_stock: 5,
     max_stock: 0,
+    initial_stock: 0,
     location: '',
-    status: 'Activo',
+    status: 'activo',
+    unit: 'unidad',
   });
+  const [categories, setCategories] = useState<string[]>(DEFAULT_CATEGORIES);
+  const [brands, setBrands] = useState<string[]>([]);
+
+  useEffect(() => {
+    if (!(typeof window !== 'undefined' && '__TAURI__' in window)) return;
+    invoke<{ name: string }[]>('get_categories')
+      .then((list) => {
+        if (list.length > 0) setCategories(list.map((c) => c.name));
+      })
+      .catch(() => {});
+    invoke<{ name: string }[]>('get_brands')
+      .then((list) => setBrands(list.map((b) => b.name)))
+      .catch(() => {});
+  }, []);
 
   const handleSubmit = (e: React.FormEvent) => {
     e.preventDefault();
@@ -53,15 +82,53 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
       alert('⚠️ El campo "Nombre" es obligatorio');
       return;
     }
+
+    // Validación: un máximo en 0 significa "sin máximo"
+    const min = formData.min_stock ?? 0;
+    const max = formData.max_stock ?? 0;
+    if (min < 0 || max < 0 || (formData.initial_stock ?? 0) < 0) {
+      alert('⚠️ El stock mínimo, el máximo y el inicial no pueden ser negativos');
+      return;
+    }
+    if (max > 0 && min > max) {
+      alert(`⚠️ El stock máximo (${max}) no puede ser menor que el mínimo (${min})`);
+      return;
+    }
     
     onSubmit(formData);
   };
 
+  /**
+   * Prellena mínimo y máximo con la sugerencia calculada a partir de las ventas
+   */
+  const handleSuggestStock = async () => {
+    if (!productId) return;
+    try {
+      const suggestion = await invoke<{ min_stock: number; max_stock: number; daily_sales: number }>('suggest_stock_levels', { productId });
+      setFormData(prev => ({ ...prev, min_stock: suggestion.min_stock, max_stock: suggestion.max_stock }));
+    } catch (error) {
+      alert(`❌ No se pudo calcular la sugerencia:\n\n${String(error)}`);
+    }
+  };
+
+  /**
+   * Asigna un EAN-13 interno al producto (ya queda guardado) y lo muestra en el formulario
+   */
+  const handleGenerateBarcode = async () => {
+    if (!productId) return;
+    try {
+      const barcode = await invoke<string>('generate_internal_barcode', { productId });
+      setFormData(prev => ({ ...prev, barcode }));
+    } catch (error) {
+      alert(`❌ No se pudo generar el código de barras:\n\n${String(error)}`);
+    }
+  };
+
   const handleChange = (e: React.ChangeEvent<HTMLInputElement | HTMLSelectElement>) => {
     const { name, value } = e.target;
     setFormData(prev => ({
       ...prev,
-      [name]: name === 'sale_price' || name === 'cost_price' || name === 'min_stock' || name === 'max_stock' ? Number(value) : value
+      [name]: name === 'sale_price' || name === 'cost_price' || name === 'min_stock' || name === 'max_stock' || name === 'initial_stock' ? Number(value) : value
     }));
   };
 
@@ -79,10 +146,32 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
             value={formData.sku}
             onChange={handleChange}
             className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
-            placeholder="Ej: PROT-001"
+            placeholder={productId ? 'Ej: PROT-001' : 'Vacío: se genera según la categoría'}
           />
         </div>
 
+        {/* Código de barras */}
+        <div>
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+            Código de barras
+          </label>
+          <div className="flex gap-2">
+            <input
+              type="text"
+              name="barcode"
+              value={formData.barcode ?? ''}
+              onChange={handleChange}
+              className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
+              placeholder="EAN-13 del proveedor"
+            />
+            {productId && !formData.barcode && (
+              <Button type="button" variant="secondary" onClick={handleGenerateBarcode}>
+                Generar
+              </Button>
+            )}
+          </div>
+        </div>
+
         {/* Nombre */}
         <div>
           <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
@@ -107,11 +196,17 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
           <input
             type="text"
             name="brand"
+            list="product-form-brands"
             value={formData.brand}
             onChange={handleChange}
             className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
             placeholder="Ej: Optimum Nutrition"
           />
+          <datalist id="product-form-brands">
+            {brands.map((name) => (
+              <option key={name} value={name} />
+            ))}
+          </datalist>
         </div>
 
         {/* Categoría */}
@@ -126,13 +221,12 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
             className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
           >
             <option value="">Seleccionar categoría</option>
-            <option value="Suplementos">Suplementos</option>
-            <option value="Proteínas">Proteínas</option>
-            <option value="Aminoácidos">Aminoácidos</option>
-            <option value="Energéticos">Energéticos</option>
-            <option value="Vitaminas">Vitaminas</option>
-            <option value="Snacks">Snacks</option>
-            <option value="Accesorios">Accesorios</option>
+            {formData.category && !categories.includes(formData.category) && (
+              <option value={formData.category}>{formData.category}</option>
+            )}
+            {categories.map((name) => (
+              <option key={name} value={name}>{name}</option>
+            ))}
           </select>
         </div>
 
@@ -269,8 +363,34 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
             className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
             placeholder="0"
           />
+          {productId && (
+            <button
+              type="button"
+              onClick={handleSuggestStock}
+              className="mt-1 text-xs text-primary-600 hover:underline"
+            >
+              Sugerir mínimo y máximo según ventas
+            </button>
+          )}
         </div>
 
+        {/* Stock Inicial: solo al crear; después el stock se mueve con ingresos */}
+        {!productId && (
+          <div>
+            <label className="block text-sm font-medium text-gray-700 mb-1">
+              Stock Inicial
+            </label>
+            <input
+              type="number"
+              name="initial_stock"
+              value={formData.initial_stock ?? 0}
+              onChange={handleChange}
+              className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+              placeholder="0"
+            />
+          </div>
+        )}
+
         {/* Ubicación */}
         <div>
           <label className="block text-sm font-medium text-gray-700 mb-1">
@@ -286,6 +406,24 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
           />
         </div>
 
+        {/* Unidad de medida */}
+        <div>
+          <label className="block text-sm font-medium text-gray-700 mb-1">
+            Unidad de medida
+          </label>
+          <select
+            name="unit"
+            value={formData.unit || 'unidad'}
+            onChange={handleChange}
+            className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+          >
+            <option value="unidad">Unidad</option>
+            <option value="kg">Kilogramo (kg)</option>
+            <option value="g">Gramo (g)</option>
+            <option value="porción">Porción</option>
+          </select>
+        </div>
+
         {/* Estado */}
         <div>
           <label className="block text-sm font-medium text-gray-700 mb-1">
@@ -297,9 +435,10 @@ export default function ProductForm({ initialData, onSubmit, onCancel }: Product
             onChange={handleChange}
             className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-primary-500 focus:border-transparent"
           >
-            <option value="Activo">Activo</option>
-            <option value="Inactivo">Inactivo</option>
-            <option value="Descontinuado">Descontinuado</option>
+            <option value="activo">Activo</option>
+            <option value="inactivo">Inactivo</option>
+            <option value="descontinuado">Descontinuado</option>
+            <option value="eliminado">Eliminado</option>
           </select>
         </div>
       </div>
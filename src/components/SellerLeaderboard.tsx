@@ -0,0 +1,78 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api';
+import { Trophy } from 'lucide-react';
+
+interface LeaderboardEntry {
+  rank: number;
+  user_id: number;
+  username: string;
+  fullname?: string | null;
+  tickets: number;
+  net_revenue?: number | null;
+  average_ticket?: number | null;
+  monthly_target?: number | null;
+  target_progress?: number | null;
+}
+
+/**
+ * Ranking mensual del equipo. Los importes de otros vendedores llegan vacíos
+ * cuando el rol no puede verlos; se muestra solo la posición y el avance.
+ */
+export default function SellerLeaderboard() {
+  const [period, setPeriod] = useState(() => new Date().toISOString().slice(0, 7));
+  const [entries, setEntries] = useState<LeaderboardEntry[]>([]);
+  const [error, setError] = useState('');
+
+  useEffect(() => {
+    if (!(typeof window !== 'undefined' && '__TAURI__' in window)) return;
+    setError('');
+    invoke<LeaderboardEntry[]>('get_seller_leaderboard', { period })
+      .then(setEntries)
+      .catch((e) => {
+        setEntries([]);
+        setError(String(e));
+      });
+  }, [period]);
+
+  return (
+    <div className="bg-white dark:bg-gray-800 rounded-xl shadow-sm border border-gray-100 dark:border-gray-700 p-6">
+      <div className="flex items-center justify-between mb-4">
+        <div className="flex items-center gap-2">
+          <Trophy className="text-amber-500" size={20} />
+          <h2 className="text-lg font-semibold text-gray-800 dark:text-gray-200">Ranking de Vendedores</h2>
+        </div>
+        <input
+          type="month"
+          value={period}
+          onChange={(e) => setPeriod(e.target.value)}
+          className="px-3 py-1.5 text-sm border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
+        />
+      </div>
+      {error && <p className="text-sm text-red-600 dark:text-red-400">{error}</p>}
+      {!error && entries.length === 0 && <p className="text-sm text-gray-500 dark:text-gray-400">Sin ventas en el mes</p>}
+      <ul className="divide-y divide-gray-100 dark:divide-gray-700">
+        {entries.map((e) => (
+          <li key={e.user_id} className="py-2 flex items-center gap-3">
+            <span className="w-6 text-right font-semibold text-gray-500 dark:text-gray-400">{e.rank}</span>
+            <div className="flex-1 min-w-0">
+              <p className="text-sm font-medium text-gray-900 dark:text-gray-100 truncate">{e.fullname || e.username}</p>
+              <p className="text-xs text-gray-500 dark:text-gray-400">
+                {e.tickets} {e.tickets === 1 ? 'ticket' : 'tickets'}
+                {e.net_revenue != null && ` · $${e.net_revenue.toLocaleString()}`}
+                {e.average_ticket != null && ` · promedio $${e.average_ticket.toLocaleString()}`}
+              </p>
+              {e.target_progress != null && (
+                <div className="mt-1 h-1.5 bg-gray-100 dark:bg-gray-700 rounded-full overflow-hidden">
+                  <div className="h-full bg-primary-500" style={{ width: `${Math.min(e.target_progress, 100)}%` }} />
+                </div>
+              )}
+            </div>
+            {e.target_progress != null && (
+              <span className="text-xs font-medium text-gray-600 dark:text-gray-300">{e.target_progress}%</span>
+            )}
+          </li>
+        ))}
+      </ul>
+    </div>
+  );
+}
@@ -8,6 +8,9 @@ interface User {
   fullname?: string;
 }
 
+/** Inicio del error de verify_login cuando la contraseña por defecto ya no sirve */
+export const DEFAULT_PASSWORD_DISABLED = 'La contraseña predeterminada del administrador está deshabilitada';
+
 interface AuthContextType {
   isAuthenticated: boolean;
   username: string | null;
@@ -99,6 +102,10 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       }
     } catch (error) {
       console.error('❌ Error en login:', error);
+      // No es un error de credenciales: la pantalla de login muestra el motivo
+      if (typeof error === 'string' && error.startsWith(DEFAULT_PASSWORD_DISABLED)) {
+        throw error;
+      }
       return false;
     }
   };
@@ -107,6 +114,11 @@ export function AuthProvider({ children }: { children: ReactNode }) {
    * Cierra la sesión del usuario actual
    */
   const logout = () => {
+    // Cerrar también la sesión de esta ventana en el backend
+    if (typeof window !== 'undefined' && '__TAURI__' in window) {
+      invoke('logout').catch((error) => console.error('❌ Error cerrando sesión:', error));
+    }
+
     setUsername(null);
     setUser(null);
     setIsAuthenticated(false);
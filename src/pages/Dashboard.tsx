@@ -2,12 +2,14 @@ import { useState, useEffect } from 'react';
 import { Package, TrendingUp, AlertTriangle, DollarSign, ShoppingCart } from 'lucide-react';
 import { ResponsiveContainer, BarChart, Bar, XAxis, YAxis, Tooltip, CartesianGrid, AreaChart, Area } from 'recharts';
 import StatCard from '../components/StatCard';
+import SellerLeaderboard from '../components/SellerLeaderboard';
 import { invoke } from '@tauri-apps/api';
 
 interface Product {
   id?: number;
   name: string;
   min_stock?: number;
+  max_stock?: number;
   category?: string;
 }
 
@@ -36,12 +38,23 @@ interface SalesTrendPoint {
 interface SalesTotals {
   total_units: number;
   total_revenue: number;
+  total_discounts?: number;
+}
+
+interface Installment {
+  id: number;
+  sale_id: number;
+  pending: number;
+  overdue: boolean;
 }
 
 interface DashboardStats {
   totalProducts: number;
   activeProducts: number;
   lowStockProducts: number;
+  misconfiguredStock: number;
+  overdueInstallments: number;
+  overduePending: number;
   totalSales: number;
   totalRevenue: number;
 }
@@ -51,6 +64,9 @@ export default function Dashboard() {
     totalProducts: 0,
     activeProducts: 0,
     lowStockProducts: 0,
+    misconfiguredStock: 0,
+    overdueInstallments: 0,
+    overduePending: 0,
     totalSales: 0,
     totalRevenue: 0,
   });
@@ -91,13 +107,14 @@ export default function Dashboard() {
         const start_date = fmt(start);
         const end_date = fmt(end);
 
-        const [products, sales, balances, top, tr, tot] = await Promise.all([
+        const [products, sales, balances, top, tr, tot, installments] = await Promise.all([
           invoke<Product[]>('get_products'),
           invoke<Sale[]>('get_sales'),
           invoke<StockBalance[]>('get_stock_balances'),
           invoke<SalesByProduct[]>('get_sales_by_product', { start_date, end_date, order_by: orderBy, category: selectedCategory || null, limit: 5 }),
           invoke<SalesTrendPoint[]>('get_sales_trend', { days: rangeDays }),
           invoke<SalesTotals>('get_sales_totals', { start_date, end_date, category: selectedCategory || null }),
+          invoke<Installment[]>('get_upcoming_installments', { days: 0 }),
         ]);
 
         // Calcular estadísticas desde los datos reales
@@ -112,6 +129,12 @@ export default function Dashboard() {
           const current = balanceMap.get(p.id) ?? 0;
           return current < min;
         }).length;
+        // Mínimo mayor al máximo (un máximo en 0 es "sin máximo"): el aviso de stock bajo no es confiable
+        const misconfiguredStock = products.filter((p) => {
+          const max = Number(p.max_stock) || 0;
+          return max > 0 && (Number(p.min_stock) || 0) > max;
+        }).length;
+        const overdue = installments.filter((i) => i.overdue);
         const totalSales = sales.length;
         const totalRevenue = sales.reduce((sum, sale) => sum + sale.sale_price, 0);
 
@@ -119,6 +142,9 @@ export default function Dashboard() {
           totalProducts,
           activeProducts,
           lowStockProducts,
+          misconfiguredStock,
+          overdueInstallments: overdue.length,
+          overduePending: overdue.reduce((sum, i) => sum + i.pending, 0),
           totalSales,
           totalRevenue,
         });
@@ -138,6 +164,9 @@ export default function Dashboard() {
           totalProducts: 0,
           activeProducts: 0,
           lowStockProducts: 0,
+          misconfiguredStock: 0,
+          overdueInstallments: 0,
+          overduePending: 0,
           totalSales: 0,
           totalRevenue: 0,
         });
@@ -155,6 +184,7 @@ export default function Dashboard() {
         totalProducts: 0,
         activeProducts: 0,
         lowStockProducts: 0,
+        misconfiguredStock: 0,
         totalSales: 0,
         totalRevenue: 0,
       });
@@ -276,6 +306,7 @@ export default function Dashboard() {
           </div>
           <div className="pt-4 text-xs text-gray-600 dark:text-gray-400">
             Total unidades: {totals.total_units.toLocaleString()} · Total ingresos: ${totals.total_revenue.toLocaleString()}
+            {totals.total_discounts ? ` · Descuentos: $${totals.total_discounts.toLocaleString()}` : ''}
           </div>
         </div>
 
@@ -325,6 +356,8 @@ export default function Dashboard() {
         </div>
       </div>
 
+      <SellerLeaderboard />
+
       {/* Alertas con diseño mejorado y modo oscuro */}
       <div className="bg-white dark:bg-gray-800 rounded-xl shadow-sm border border-gray-100 dark:border-gray-700 p-6">
         <div className="flex items-center justify-between mb-4">
@@ -360,6 +393,34 @@ export default function Dashboard() {
             </div>
           )}
           
+          {stats.misconfiguredStock > 0 && (
+            <div className="p-4 bg-amber-50 dark:bg-amber-900/20 border border-amber-200 dark:border-amber-800/50 rounded-lg">
+              <div className="flex items-start">
+                <AlertTriangle className="text-amber-500 dark:text-amber-400 mr-3 mt-0.5 flex-shrink-0" size={20} />
+                <div>
+                  <p className="font-medium text-amber-900 dark:text-amber-300 text-sm">Mínimo y Máximo Inconsistentes</p>
+                  <p className="text-xs text-amber-700 dark:text-amber-400 mt-1">
+                    {stats.misconfiguredStock} {stats.misconfiguredStock === 1 ? 'producto tiene' : 'productos tienen'} el stock mínimo por encima del máximo
+                  </p>
+                </div>
+              </div>
+            </div>
+          )}
+
+          {stats.overdueInstallments > 0 && (
+            <div className="p-4 bg-red-50 dark:bg-red-900/20 border border-red-200 dark:border-red-800/50 rounded-lg">
+              <div className="flex items-start">
+                <DollarSign className="text-red-500 dark:text-red-400 mr-3 mt-0.5 flex-shrink-0" size={20} />
+                <div>
+                  <p className="font-medium text-red-900 dark:text-red-300 text-sm">Cuotas Vencidas</p>
+                  <p className="text-xs text-red-700 dark:text-red-400 mt-1">
+                    {stats.overdueInstallments} {stats.overdueInstallments === 1 ? 'cuota vencida' : 'cuotas vencidas'} por ${stats.overduePending.toLocaleString()}
+                  </p>
+                </div>
+              </div>
+            </div>
+          )}
+
           {stats.totalProducts === 0 && (
             <div className="p-4 bg-blue-50 dark:bg-blue-900/20 border border-blue-200 dark:border-blue-800/50 rounded-lg">
               <div className="flex items-start">
@@ -1,24 +1,33 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { useNavigate } from 'react-router-dom';
+import { invoke } from '@tauri-apps/api';
 import { useAuth } from '../contexts/AuthContext';
 import { Lock, User, LogIn } from 'lucide-react';
+import FirstRunWizard from '../components/FirstRunWizard';
 
 /**
  * Página de inicio de sesión
  * 
- * CREDENCIALES POR DEFECTO:
- * Usuario: admin
- * Contraseña: admin
+ * En una instalación nueva (o en una base actualizada cuyo período de gracia
+ * venció) muestra primero el asistente de configuración inicial: la
+ * contraseña por defecto del admin no sirve para entrar.
  */
 export default function Login() {
   const [username, setUsername] = useState('');
   const [password, setPassword] = useState('');
   const [error, setError] = useState('');
   const [isLoading, setIsLoading] = useState(false);
+  const [firstRun, setFirstRun] = useState(false);
   
   const { login } = useAuth();
   const navigate = useNavigate();
 
+  useEffect(() => {
+    if (typeof window !== 'undefined' && '__TAURI__' in window) {
+      invoke<boolean>('is_first_run').then(setFirstRun).catch(() => setFirstRun(false));
+    }
+  }, []);
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     setError('');
@@ -27,14 +36,22 @@ export default function Login() {
     // Simular pequeño delay para mejor UX
     await new Promise(resolve => setTimeout(resolve, 500));
 
-    // Login ahora es async
-    const success = await login(username, password);
-    
-    if (success) {
-      navigate('/dashboard');
-    } else {
-      setError('Usuario o contraseña incorrectos');
+    try {
+      // Login ahora es async
+      const success = await login(username, password);
+
+      if (success) {
+        navigate('/dashboard');
+      } else {
+        setError('Usuario o contraseña incorrectos');
+        setPassword('');
+      }
+    } catch (message) {
+      // Contraseña por defecto deshabilitada: se explica y, si la base aún no
+      // completó la configuración inicial, se abre el asistente
+      setError(String(message));
       setPassword('');
+      invoke<boolean>('is_first_run').then(setFirstRun).catch(() => setFirstRun(false));
     }
     
     setIsLoading(false);
@@ -62,7 +79,14 @@ export default function Login() {
             <p className="text-sm text-gray-600 dark:text-gray-400">Sistema de Gestión de Inventario</p>
           </div>
 
-          {/* Formulario */}
+          {firstRun ? (
+            <FirstRunWizard
+              onCompleted={() => {
+                setFirstRun(false);
+                setUsername('admin');
+              }}
+            />
+          ) : (
           <form onSubmit={handleSubmit} className="space-y-5">
             {/* Usuario */}
             <div>
@@ -128,19 +152,7 @@ export default function Login() {
               )}
             </button>
           </form>
-
-          {/* Footer con credenciales por defecto */}
-          <div className="mt-6 pt-6 border-t border-gray-200 dark:border-gray-700">
-            <div className="bg-blue-50 dark:bg-blue-900/20 border border-blue-200 dark:border-blue-800/50 rounded-xl p-4">
-              <p className="text-xs text-blue-900 dark:text-blue-300 font-semibold mb-2">
-                🔑 Credenciales por defecto:
-              </p>
-              <div className="space-y-1 text-xs text-blue-700 dark:text-blue-400">
-                <p><span className="font-medium">Usuario:</span> admin</p>
-                <p><span className="font-medium">Contraseña:</span> admin</p>
-              </div>
-            </div>
-          </div>
+          )}
         </div>
 
         {/* Versión */}
@@ -1,9 +1,13 @@
 import { useState, useEffect } from 'react';
-import { Plus, Search, Edit, Trash2, Package } from 'lucide-react';
+import { useSearchParams } from 'react-router-dom';
+import { Plus, Search, Edit, Trash2, Package, XCircle, ImagePlus } from 'lucide-react';
 import Button from '../components/Button';
 import Modal from '../components/Modal';
 import ProductForm from '../components/ProductForm';
 import { invoke } from '@tauri-apps/api';
+import { convertFileSrc } from '@tauri-apps/api/tauri';
+import { open } from '@tauri-apps/api/dialog';
+import { useAuth } from '../contexts/AuthContext';
 
 interface Product {
   id?: number;
@@ -17,6 +21,7 @@ interface Product {
   flavor?: string;
   weight?: string;
   image_path?: string;
+  thumbnail_path?: string | null;
   expiry_date?: string;
   lot_number?: string;
   min_stock?: number;
@@ -24,6 +29,8 @@ interface Product {
   location?: string;
   status?: string;
   current_stock?: number;
+  deleted_at?: string | null;
+  parent_id?: number | null;
 }
 
 interface StockBalance {
@@ -43,11 +50,16 @@ export default function Products() {
   const [products, setProducts] = useState<Product[]>([]);
   const [isModalOpen, setIsModalOpen] = useState(false);
   const [editingProduct, setEditingProduct] = useState<Product | null>(null);
+  const [newSku, setNewSku] = useState('');
+  const [searchParams, setSearchParams] = useSearchParams();
   const [loading, setLoading] = useState(false);
   const [isStockModalOpen, setIsStockModalOpen] = useState(false);
   const [targetProduct, setTargetProduct] = useState<Product | null>(null);
   const [stockQty, setStockQty] = useState<number>(0);
   const [stockNote, setStockNote] = useState<string>('');
+  const { user } = useAuth();
+  const isAdmin = user?.role === 'Administrador';
+  const [showDeleted, setShowDeleted] = useState(false);
 
   /**
    * Carga la lista de productos desde la base de datos SQLite
@@ -66,7 +78,7 @@ export default function Products() {
       if (isTauriEnvironment()) {
         // MODO TAURI: Invocar comandos de Rust para obtener productos y saldos de stock
         const [prods, balances] = await Promise.all([
-          invoke<Product[]>('get_products'),
+          invoke<Product[]>('get_products', showDeleted ? { includeDeleted: true } : {}),
           invoke<StockBalance[]>('get_stock_balances'),
         ]);
         const balanceMap = new Map<number, number>(
@@ -104,6 +116,10 @@ export default function Products() {
     if (!targetProduct) return;
     const current = targetProduct.current_stock ?? 0;
     const max = targetProduct.max_stock ?? 0;
+    if (max > 0 && (targetProduct.min_stock ?? 0) > max) {
+      alert('⚠️ El stock mínimo es mayor al máximo. Corrige la configuración del producto antes de reabastecer.');
+      return;
+    }
     const delta = max - current;
     if (delta <= 0) {
       alert('El stock ya está en el máximo');
@@ -142,10 +158,21 @@ export default function Products() {
     }
   };
 
-  // Cargar productos al montar el componente
+  // Cargar productos al montar el componente y al mostrar u ocultar eliminados
   useEffect(() => {
     loadProducts();
-  }, []);
+  }, [showDeleted]);
+
+  // Código escaneado en Ventas que no existe: abrir el alta con ese SKU
+  useEffect(() => {
+    const sku = searchParams.get('nuevo_sku');
+    if (sku) {
+      setEditingProduct(null);
+      setNewSku(sku);
+      setIsModalOpen(true);
+      setSearchParams({}, { replace: true });
+    }
+  }, [searchParams, setSearchParams]);
 
   const filteredProducts = products.filter(product => {
     const matchesSearch = product.name.toLowerCase().includes(searchTerm.toLowerCase()) ||
@@ -160,6 +187,7 @@ export default function Products() {
    */
   const handleAddProduct = () => {
     setEditingProduct(null);
+    setNewSku('');
     setIsModalOpen(true);
   };
 
@@ -172,6 +200,24 @@ export default function Products() {
     setIsModalOpen(true);
   };
 
+  /**
+   * Elige una foto para el producto; el backend la copia y genera la
+   * miniatura que se muestra en la tabla
+   */
+  const handleChooseImage = async (id: number) => {
+    const selected = await open({
+      multiple: false,
+      filters: [{ name: 'Imagen', extensions: ['png', 'jpg', 'jpeg', 'webp'] }],
+    });
+    if (typeof selected !== 'string') return;
+    try {
+      await invoke('save_product_image', { productId: id, sourcePath: selected });
+      await loadProducts();
+    } catch (error) {
+      window.alert(String(error));
+    }
+  };
+
   /**
    * Elimina un producto de la base de datos
    * Solicita confirmación antes de eliminar
@@ -180,7 +226,7 @@ export default function Products() {
    * @returns {Promise<void>}
    */
   const handleDeleteProduct = async (id: number) => {
-    if (window.confirm('¿Estás seguro de eliminar este producto?')) {
+    if (window.confirm('¿Estás seguro de eliminar este producto?\n\nDejará de aparecer en el catálogo y en ventas; su historial se conserva.')) {
       try {
         // Solo intentar eliminar si Tauri está disponible
         if (isTauriEnvironment()) {
@@ -198,6 +244,39 @@ export default function Products() {
     }
   };
 
+  /**
+   * Borra definitivamente un producto eliminado (solo Administrador).
+   * Si tiene ventas, movimientos o compras el backend lo rechaza con las
+   * cantidades; se puede forzar el borrado de todo ese historial.
+   *
+   * @param {Product} product - Producto eliminado a purgar
+   * @returns {Promise<void>}
+   */
+  const handlePurgeProduct = async (product: Product) => {
+    if (!product.id || !isTauriEnvironment()) return;
+    if (!window.confirm(`¿Borrar definitivamente "${product.name}"? Esta acción no se puede deshacer.`)) return;
+    try {
+      await invoke('purge_product', { id: product.id });
+      await loadProducts();
+    } catch (error) {
+      const msg = String(error);
+      if (!msg.startsWith('El producto tiene')) {
+        window.alert(msg);
+        return;
+      }
+      const forced = window.confirm(
+        `${msg}.\n\nConviene dejarlo eliminado: así los reportes conservan su historial.\n¿Borrar también todo ese historial?`
+      );
+      if (!forced) return;
+      try {
+        await invoke('purge_product', { id: product.id, force: true });
+        await loadProducts();
+      } catch (forceError) {
+        window.alert(String(forceError));
+      }
+    }
+  };
+
   /**
    * Guarda un producto (nuevo o editado) en la base de datos
    * Decide entre crear o actualizar según si hay ID
@@ -232,8 +311,8 @@ export default function Products() {
         } else {
           // CREAR nuevo producto
           console.log('➕ Creando nuevo producto');
-          await invoke('add_product', { product: data });
-          console.info('✅ Nuevo producto agregado');
+          const created = await invoke<{ id: number; sku: string }>('add_product', { product: data });
+          console.info(`✅ Nuevo producto agregado (SKU ${created.sku})`);
         }
         
         // Recargar la lista de productos
@@ -292,6 +371,12 @@ export default function Products() {
               <option value="Energéticos">Energéticos</option>
               <option value="Vitaminas">Vitaminas</option>
             </select>
+            {isAdmin && (
+              <label className="flex items-center gap-2 text-sm text-gray-600 dark:text-gray-400 whitespace-nowrap">
+                <input type="checkbox" checked={showDeleted} onChange={(e) => setShowDeleted(e.target.checked)} />
+                Mostrar eliminados
+              </label>
+            )}
           </div>
         </div>
 
@@ -353,7 +438,20 @@ export default function Products() {
                       {product.sku || <span className="text-gray-400 dark:text-gray-600">-</span>}
                     </td>
                     <td className="px-5 py-4 text-sm font-medium text-gray-900 dark:text-gray-100">
+                      {product.thumbnail_path && (
+                        <img
+                          src={convertFileSrc(product.thumbnail_path)}
+                          alt=""
+                          loading="lazy"
+                          className="inline-block w-8 h-8 mr-2 rounded object-cover align-middle"
+                        />
+                      )}
                       {product.name}
+                      {product.deleted_at && (
+                        <span className="ml-2 inline-flex px-2 py-0.5 text-xs font-medium rounded-full bg-gray-100 dark:bg-gray-700 text-gray-500 dark:text-gray-400">
+                          Eliminado
+                        </span>
+                      )}
                     </td>
                     <td className="px-5 py-4 text-sm text-gray-600 dark:text-gray-400">
                       {product.brand || <span className="text-gray-400 dark:text-gray-600">-</span>}
@@ -417,6 +515,17 @@ export default function Products() {
                       {product.expiry_date || <span className="text-gray-400 dark:text-gray-600">-</span>}
                     </td>
                     <td className="px-5 py-4 text-right">
+                      {product.deleted_at ? (
+                      <div className="flex justify-end gap-1">
+                        <button
+                          onClick={() => handlePurgeProduct(product)}
+                          className="p-2 text-red-600 dark:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/30 rounded-lg transition-colors"
+                          title="Borrar definitivamente"
+                        >
+                          <XCircle size={16} />
+                        </button>
+                      </div>
+                      ) : (
                       <div className="flex justify-end gap-1">
                         <button 
                           onClick={() => openStockModal(product)}
@@ -432,6 +541,13 @@ export default function Products() {
                         >
                           <Edit size={16} />
                         </button>
+                        <button 
+                          onClick={() => product.id && handleChooseImage(product.id)}
+                          className="p-2 text-purple-600 dark:text-purple-400 hover:bg-purple-50 dark:hover:bg-purple-900/30 rounded-lg transition-colors"
+                          title="Foto del producto"
+                        >
+                          <ImagePlus size={16} />
+                        </button>
                         <button 
                           onClick={() => product.id && handleDeleteProduct(product.id)}
                           className="p-2 text-red-600 dark:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/30 rounded-lg transition-colors"
@@ -440,6 +556,7 @@ export default function Products() {
                           <Trash2 size={16} />
                         </button>
                       </div>
+                      )}
                     </td>
                   </tr>
                 ))}
@@ -458,6 +575,8 @@ export default function Products() {
       >
         <ProductForm
           initialData={editingProduct || undefined}
+          productId={editingProduct?.id}
+          defaultSku={newSku}
           onSubmit={handleSubmitProduct}
           onCancel={() => setIsModalOpen(false)}
         />
@@ -4,17 +4,22 @@ import Button from '../components/Button';
 import { invoke } from '@tauri-apps/api';
 
 export default function Reports() {
-  const [reportType, setReportType] = useState<'Ventas' | 'Inventario' | 'Financiero'>('Ventas');
+  const [reportType, setReportType] = useState<'Ventas' | 'Inventario' | 'Financiero' | 'Catálogo'>('Ventas');
   const [startDate, setStartDate] = useState<string>('');
   const [endDate, setEndDate] = useState<string>('');
+  const [splitByMonth, setSplitByMonth] = useState<boolean>(false);
   /**
    * Maneja la exportación de todos los reportes
    */
   const handleExportAll = async () => {
     try {
       if (typeof window !== 'undefined' && '__TAURI__' in window) {
-        const paths = await invoke<string[]>('export_all_reports');
-        alert(`✅ Reportes exportados en CSV:\n\n${paths.join('\n')}`);
+        const result = await invoke<{ job_id: string; already_running: boolean; paths: string[] }>('export_all_reports');
+        if (result.already_running) {
+          alert(`⏳ Ya hay una exportación en curso (trabajo ${result.job_id}). Espera a que termine.`);
+          return;
+        }
+        alert(`✅ Reportes exportados en CSV:\n\n${result.paths.join('\n')}`);
       } else {
         alert('⚠️ Ejecuta en modo Tauri para exportar reportes (npm run tauri:dev)');
       }
@@ -67,7 +72,15 @@ export default function Reports() {
         alert('⚠️ Ejecuta en modo Tauri para exportar reportes (npm run tauri:dev)');
         return;
       }
-      if (reportType === 'Ventas') {
+      if (reportType === 'Ventas' && splitByMonth) {
+        const files = await invoke<{ month: string; path: string; rows: number }[]>('export_sales_report', {
+          startDate: startDate || null,
+          endDate: endDate || null,
+          splitBy: 'month',
+        });
+        const list = files.map((f) => `${f.month}: ${f.rows} ventas`).join('\n');
+        alert(`✅ Reporte de Ventas exportado en ${files.length} archivos:\n\n${list}`);
+      } else if (reportType === 'Ventas') {
         const p = await invoke<string>('export_sales_report', {
           start_date: startDate || null,
           end_date: endDate || null,
@@ -76,6 +89,13 @@ export default function Reports() {
       } else if (reportType === 'Inventario') {
         const p = await invoke<string>('export_inventory_report');
         alert(`✅ Reporte de Inventario exportado:\n${p}`);
+      } else if (reportType === 'Catálogo') {
+        if (!endDate) {
+          alert('⚠️ Elige en Fecha Fin el día del catálogo');
+          return;
+        }
+        const p = await invoke<string>('export_catalog_snapshot', { date: endDate });
+        alert(`✅ Catálogo al ${endDate} exportado:\n${p}`);
       } else {
         const p = await invoke<string>('export_financial_report', {
           start_date: startDate || null,
@@ -133,6 +153,7 @@ export default function Reports() {
               <option value="Ventas">Ventas</option>
               <option value="Inventario">Inventario</option>
               <option value="Financiero">Financiero</option>
+              <option value="Catálogo">Catálogo a una fecha (precios, costo y stock)</option>
             </select>
           </div>
           <div>
@@ -160,6 +181,12 @@ export default function Reports() {
             </div>
           </div>
         </div>
+        {reportType === 'Ventas' && (
+          <label className="flex items-center gap-2 mb-4 text-sm text-gray-700 dark:text-gray-300">
+            <input type="checkbox" checked={splitByMonth} onChange={(e) => setSplitByMonth(e.target.checked)} />
+            Un archivo por mes
+          </label>
+        )}
         <Button icon={FileText} onClick={handleGenerateReport}>Generar Reporte</Button>
       </div>
     </div>
@@ -1,8 +1,11 @@
 import { useState, useEffect } from 'react';
+import { useNavigate } from 'react-router-dom';
 import { invoke } from '@tauri-apps/api';
-import { Plus, DollarSign, ShoppingBag, TrendingUp, Package } from 'lucide-react';
+import { Plus, DollarSign, ShoppingBag, TrendingUp, Package, Paperclip } from 'lucide-react';
 import Button from '../components/Button';
 import Modal from '../components/Modal';
+import AttachmentsDialog from '../components/AttachmentsDialog';
+import { useAuth } from '../contexts/AuthContext';
 
 interface Sale {
   id?: number;
@@ -13,6 +16,8 @@ interface Sale {
   channel?: string;
   sale_date?: string;
   created_by?: number;
+  product_name?: string;
+  seller?: string;
 }
 
 interface Product {
@@ -20,14 +25,66 @@ interface Product {
   name: string;
   sale_price?: number;
   brand?: string;
+  unit?: string;
 }
 
+interface PriceCalculation {
+  subtotal: number;
+  discount_amount: number;
+  unrounded_total: number;
+  total: number;
+  rounded: boolean;
+  original_price?: number | null;
+  promotion_id?: number | null;
+  promotion_name?: string | null;
+}
+
+interface SaleResult {
+  id: number;
+  below_cost: boolean;
+  warning?: string | null;
+}
+
+interface Promotion {
+  id: number;
+  name: string;
+  ends_at: string;
+}
+
+/** Los productos por peso admiten cantidades fraccionarias */
+const isWeighed = (product?: Product) => product?.unit === 'kg' || product?.unit === 'g';
+
+/**
+ * Formatea una cantidad con su unidad de medida
+ */
+const formatQuantity = (quantity: number, product?: Product) => {
+  const unit = product?.unit || 'unidad';
+  if (isWeighed(product)) {
+    return `${quantity.toLocaleString(undefined, { maximumFractionDigits: 3 })} ${unit}`;
+  }
+  if (unit === 'porción') {
+    return `${quantity} ${quantity === 1 ? 'porción' : 'porciones'}`;
+  }
+  return `${quantity} unidades`;
+};
+
 /**
  * Página de Ventas
  * Muestra el historial de ventas desde la base de datos SQLite
  */
+/** Productos que muestra el selector de la venta */
+const PICKER_LIMIT = 50;
+
+/** Stock de un producto según search_products; en los packs, los que se pueden armar */
+interface ProductAvailability {
+  product_id: number;
+  physical: number;
+  available: number;
+}
+
 export default function Sales() {
   const [sales, setSales] = useState<Sale[]>([]);
+  const [attachmentsSaleId, setAttachmentsSaleId] = useState<number | null>(null);
   const [loading, setLoading] = useState(true);
   const [stats, setStats] = useState({
     today: 0,
@@ -37,27 +94,87 @@ export default function Sales() {
   const [isModalOpen, setIsModalOpen] = useState(false);
   const [products, setProducts] = useState<Product[]>([]);
   const [productMap, setProductMap] = useState<Record<number, Product>>({});
+  const [productQuery, setProductQuery] = useState('');
+  const [productTotal, setProductTotal] = useState(0);
+  const [availability, setAvailability] = useState<Record<number, number>>({});
+  const navigate = useNavigate();
+  const { user } = useAuth();
+  const isAdmin = user?.role === 'Administrador';
   const pageSize = 10;
   const [page, setPage] = useState(0);
   const [hasNextPage, setHasNextPage] = useState(false);
-  const [form, setForm] = useState<{ product_id: number; quantity: number; sale_price: number; discount?: number; channel?: string }>(
-    { product_id: 0, quantity: 1, sale_price: 0, discount: 0, channel: 'Tienda' }
+  const [form, setForm] = useState<{ product_id: number; quantity: number; sale_price: number; discount?: number; channel?: string; payment_method?: string; override: boolean }>(
+    { product_id: 0, quantity: 1, sale_price: 0, discount: 0, channel: 'Tienda', payment_method: 'efectivo', override: false }
   );
 
+  const [quote, setQuote] = useState<PriceCalculation | null>(null);
+  const [activePromotions, setActivePromotions] = useState<Promotion[]>([]);
+
+  /**
+   * Precio ajustado por un Administrador con override; null si es el de
+   * catálogo, para que el backend aplique la promoción vigente
+   */
+  const unitPriceOverride = () => {
+    const catalog = productMap[form.product_id]?.sale_price;
+    if (!form.override) return null;
+    return catalog != null && Number(form.sale_price) === catalog ? null : form.sale_price;
+  };
+
   useEffect(() => {
     loadSales(page);
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [page]);
 
+  // Total calculado por el backend con la regla de redondeo configurada
+  useEffect(() => {
+    if (!isModalOpen || !form.product_id || form.quantity <= 0 || form.sale_price <= 0) {
+      setQuote(null);
+      return;
+    }
+    if (typeof window !== 'undefined' && '__TAURI__' in window) {
+      invoke<PriceCalculation>('calculate_price', {
+        productId: form.product_id,
+        quantity: form.quantity,
+        unitPrice: unitPriceOverride(),
+        discount: form.discount || 0,
+      })
+        .then(setQuote)
+        .catch(() => setQuote(null));
+    }
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [isModalOpen, form.product_id, form.quantity, form.sale_price, form.discount, productMap]);
+
+  /**
+   * Busca productos para el selector; con catálogos grandes solo se cargan
+   * las primeras coincidencias
+   */
+  const searchPickerProducts = async (query: string) => {
+    const result = await invoke<{ products: Product[]; stock: ProductAvailability[]; total: number }>('search_products', {
+      query,
+      limit: PICKER_LIMIT,
+    });
+    setProducts(result.products);
+    setProductTotal(result.total);
+    setAvailability(prev => ({
+      ...prev,
+      ...Object.fromEntries(result.stock.map(s => [s.product_id, s.available])),
+    }));
+    setProductMap(prev => ({
+      ...prev,
+      ...Object.fromEntries(result.products.filter(p => p.id != null).map(p => [Number(p.id), p])),
+    }));
+    return result.products;
+  };
+
   const handleNewSale = async () => {
     setIsModalOpen(true);
+    setProductQuery('');
     try {
       if (typeof window !== 'undefined' && '__TAURI__' in window) {
-        const result = await invoke<Product[]>('get_products');
-        setProducts(result);
-        setProductMap(Object.fromEntries(result.filter(p => p.id != null).map(p => [Number(p.id), p])));
+        const result = await searchPickerProducts('');
         const first = result[0];
-        setForm(prev => ({ ...prev, product_id: first?.id || 0, sale_price: first?.sale_price || 0 }));
+        setForm(prev => ({ ...prev, product_id: first?.id || 0, sale_price: first?.sale_price || 0, override: false }));
+        setActivePromotions(await invoke<Promotion[]>('get_active_promotions'));
       } else {
         setProducts([]);
       }
@@ -66,12 +183,63 @@ export default function Sales() {
     }
   };
 
+  /**
+   * Enter en el buscador (lo que envía el lector de códigos de barras):
+   * selecciona el producto con ese SKU exacto u ofrece darlo de alta
+   */
+  const handleScan = async (e: React.KeyboardEvent<HTMLInputElement>) => {
+    const code = productQuery.trim();
+    if (e.key !== 'Enter' || !code) return;
+    e.preventDefault();
+    try {
+      const product = await invoke<Product>('get_product_by_sku', { code });
+      setProducts([product]);
+      setProductTotal(1);
+      setProductMap(prev => ({ ...prev, [Number(product.id)]: product }));
+      setForm(prev => ({ ...prev, product_id: product.id || 0, sale_price: product.sale_price || 0 }));
+    } catch (error) {
+      const msg = String(error);
+      if (msg.startsWith('Código no registrado')) {
+        if (confirm(`El código ${code} no corresponde a ningún producto. ¿Crear un producto con este SKU?`)) {
+          setIsModalOpen(false);
+          navigate(`/inventory?nuevo_sku=${encodeURIComponent(code)}`);
+        }
+      } else {
+        alert(msg);
+      }
+    }
+  };
+
+  // Nueva búsqueda al escribir; si el producto elegido no está en el resultado se toma el primero
+  useEffect(() => {
+    if (!isModalOpen || !(typeof window !== 'undefined' && '__TAURI__' in window)) return;
+    const timer = setTimeout(() => {
+      searchPickerProducts(productQuery)
+        .then(result => {
+          setForm(prev => result.length === 0 || result.some(p => p.id === prev.product_id)
+            ? prev
+            : { ...prev, product_id: result[0].id || 0, sale_price: result[0].sale_price || 0 });
+        })
+        .catch(() => setProducts([]));
+    }, 250);
+    return () => clearTimeout(timer);
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [productQuery]);
+
   const handleChange = (e: React.ChangeEvent<HTMLInputElement | HTMLSelectElement>) => {
     const { name, value } = e.target;
     if (name === 'product_id') {
       const pid = Number(value);
       const p = products.find(pr => pr.id === pid);
       setForm(prev => ({ ...prev, product_id: pid, sale_price: p?.sale_price || 0 }));
+    } else if (name === 'override') {
+      // Sin override vuelve el precio de catálogo
+      const checked = (e.target as HTMLInputElement).checked;
+      setForm(prev => ({
+        ...prev,
+        override: checked,
+        sale_price: checked ? prev.sale_price : productMap[prev.product_id]?.sale_price || 0,
+      }));
     } else if (name === 'quantity' || name === 'sale_price' || name === 'discount') {
       setForm(prev => ({ ...prev, [name]: Number(value) } as any));
     } else {
@@ -92,24 +260,29 @@ export default function Sales() {
     const discPct = Math.max(0, Math.min(100, Number(form.discount) || 0));
     try {
       if (typeof window !== 'undefined' && '__TAURI__' in window) {
-        const unit = Number(form.sale_price) || 0;
-        const qty = Number(form.quantity) || 0;
-        const subtotal = unit * qty;
-        const total = Math.max(0, Math.round(subtotal * (1 - discPct / 100)));
+        const quoted = await invoke<PriceCalculation>('calculate_price', {
+          productId: form.product_id,
+          quantity: form.quantity,
+          unitPrice: unitPriceOverride(),
+          discount: discPct,
+        });
+        const total = quoted.total;
         const payload = {
           product_id: form.product_id,
           quantity: form.quantity,
           sale_price: total,
           discount: discPct,
+          promotion_id: quoted.promotion_id ?? null,
           channel: form.channel,
+          payment_method: form.payment_method,
           sale_date: new Date().toISOString(),
-          created_by: null,
+          override: isAdmin && form.override,
         };
-        await invoke('add_sale', { sale: payload });
+        const result = await invoke<SaleResult>('add_sale', { sale: payload });
         setIsModalOpen(false);
         setPage(0); // recargar desde la primera página
         await loadSales(0);
-        alert('Venta registrada');
+        alert(result.warning ? `Venta registrada\n⚠️ ${result.warning}` : 'Venta registrada');
       } else {
         alert('Ejecuta la app con backend para registrar ventas');
       }
@@ -135,14 +308,15 @@ export default function Sales() {
       if (typeof window !== 'undefined' && '__TAURI__' in window) {
         const limit = pageSize;
         const offset = pageIndex * pageSize;
-        const [result, prods] = await Promise.all([
-          invoke<Sale[]>('get_sales', { limit, offset }),
+        const [salesPage, prods] = await Promise.all([
+          invoke<{ items: Sale[]; total_count: number }>('get_sales_detailed', { limit, offset }),
           invoke<Product[]>('get_products'),
         ]);
+        const result = salesPage.items;
         setSales(result);
         setProducts(prods);
         setProductMap(Object.fromEntries(prods.filter(p => p.id != null).map(p => [Number(p.id), p])));
-        setHasNextPage(result.length === pageSize);
+        setHasNextPage(offset + result.length < salesPage.total_count);
 
         const today = new Date().toISOString().split('T')[0];
         const currentMonth = new Date().getMonth();
@@ -192,24 +366,49 @@ export default function Sales() {
         size="md"
       >
         <form onSubmit={handleSubmitSale} className="space-y-4">
+          {activePromotions.length > 0 && (
+            <div className="bg-amber-50 dark:bg-amber-900/20 border border-amber-200 dark:border-amber-800 rounded-lg p-3 text-sm text-amber-800 dark:text-amber-300">
+              Promociones vigentes: {activePromotions.map(p => p.name).join(', ')}
+            </div>
+          )}
           <div>
             <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Producto</label>
+            <input
+              type="search"
+              placeholder="Buscar por nombre, SKU o marca"
+              value={productQuery}
+              onChange={(e) => setProductQuery(e.target.value)}
+              onKeyDown={handleScan}
+              className="w-full px-3 py-2 mb-2 border border-gray-300 dark:border-gray-600 rounded-lg"
+            />
             <select name="product_id" value={form.product_id} onChange={handleChange} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg">
               {products.map(p => (
                 <option key={p.id} value={p.id}>
                   {p.name}{p.brand ? ` - ${p.brand}` : ''}
+                  {p.id != null && availability[Number(p.id)] != null ? ` · disp. ${availability[Number(p.id)]}` : ''}
                 </option>
               ))}
             </select>
+            {productTotal > products.length && (
+              <p className="mt-1 text-xs text-gray-500 dark:text-gray-400">
+                Mostrando {products.length} de {productTotal}; escribe para acotar la búsqueda
+              </p>
+            )}
           </div>
           <div className="grid grid-cols-1 md:grid-cols-3 gap-4">
             <div>
               <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Cantidad</label>
-              <input type="number" name="quantity" value={form.quantity} onChange={handleChange} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg" />
+              <input type="number" name="quantity" min="0" step={isWeighed(productMap[form.product_id]) ? '0.001' : '1'} value={form.quantity} onChange={handleChange} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg" />
             </div>
             <div>
               <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Precio x Unidad</label>
-              <input type="number" step="0.01" name="sale_price" value={form.sale_price} onChange={handleChange} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg" />
+              <input type="number" step="0.01" name="sale_price" value={form.sale_price} onChange={handleChange} readOnly={!form.override} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg read-only:bg-gray-100 dark:read-only:bg-gray-800" />
+              {isAdmin && (
+                <label className="mt-1 flex items-center gap-2 text-xs text-gray-600 dark:text-gray-400">
+                  <input type="checkbox" name="override" checked={form.override} onChange={handleChange} />
+                  Autorizar otro precio o descuento mayor
+                </label>
+              )}
             </div>
             <div>
               <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Descuento (%)</label>
@@ -224,6 +423,15 @@ export default function Sales() {
               <option value="Redes">Redes</option>
             </select>
           </div>
+          <div>
+            <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">Medio de pago</label>
+            <select name="payment_method" value={form.payment_method} onChange={handleChange} className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg">
+              <option value="efectivo">Efectivo</option>
+              <option value="tarjeta">Tarjeta</option>
+              <option value="yape">Yape</option>
+              <option value="transferencia">Transferencia</option>
+            </select>
+          </div>
           <div className="bg-gray-50 dark:bg-gray-900/30 border border-gray-200 dark:border-gray-700 rounded-lg p-4 text-sm">
             <div className="flex justify-between text-gray-700 dark:text-gray-300">
               <span>Subtotal</span>
@@ -236,6 +444,7 @@ export default function Sales() {
             <div className="flex justify-between text-gray-900 dark:text-gray-100 font-semibold mt-2">
               <span>Total</span>
               <span>{(() => {
+                if (quote) return `$${quote.total.toLocaleString()}`;
                 const unit = Number(form.sale_price) || 0;
                 const qty = Number(form.quantity) || 0;
                 const subtotal = unit * qty;
@@ -244,6 +453,16 @@ export default function Sales() {
                 return `$${total.toLocaleString()}`;
               })()}</span>
             </div>
+            {quote?.promotion_name && (
+              <div className="text-right text-xs text-amber-600 dark:text-amber-400 mt-1">
+                {quote.promotion_name} (antes ${quote.original_price?.toLocaleString()} c/u)
+              </div>
+            )}
+            {quote?.rounded && (
+              <div className="text-right text-xs text-gray-500 dark:text-gray-400 mt-1">
+                redondeado desde ${quote.unrounded_total.toFixed(2)}
+              </div>
+            )}
           </div>
           <div className="flex justify-end gap-3 pt-2">
             <Button type="button" variant="secondary" onClick={() => setIsModalOpen(false)}>Cancelar</Button>
@@ -335,6 +554,7 @@ export default function Sales() {
                   <th className="px-5 py-3.5 text-left text-xs font-semibold text-gray-600 dark:text-gray-400 uppercase tracking-wider">Cantidad</th>
                   <th className="px-5 py-3.5 text-left text-xs font-semibold text-gray-600 dark:text-gray-400 uppercase tracking-wider">Total</th>
                   <th className="px-5 py-3.5 text-left text-xs font-semibold text-gray-600 dark:text-gray-400 uppercase tracking-wider">Canal</th>
+                  <th className="px-5 py-3.5"></th>
                 </tr>
               </thead>
               <tbody className="divide-y divide-gray-100 dark:divide-gray-700">
@@ -348,14 +568,14 @@ export default function Sales() {
                     </td>
                     <td className="px-5 py-4">
                       <div className="text-sm font-medium text-gray-900 dark:text-gray-100">
-                        {productMap[sale.product_id]?.name ?? `Producto #${sale.product_id}`}
+                        {sale.product_name ?? productMap[sale.product_id]?.name ?? `Producto #${sale.product_id}`}
                       </div>
                       <div className="text-xs text-gray-500 dark:text-gray-400">
                         {productMap[sale.product_id]?.brand || '-'}
                       </div>
                     </td>
                     <td className="px-5 py-4 text-sm text-gray-600 dark:text-gray-400">
-                      {sale.quantity} unidades
+                      {formatQuantity(sale.quantity, productMap[sale.product_id])}
                     </td>
                     <td className="px-5 py-4 text-sm font-semibold text-gray-900 dark:text-gray-100">
                       ${sale.sale_price.toLocaleString()}
@@ -365,6 +585,15 @@ export default function Sales() {
                         {sale.channel || 'Tienda'}
                       </span>
                     </td>
+                    <td className="px-5 py-4 text-right">
+                      <button
+                        onClick={() => sale.id && setAttachmentsSaleId(sale.id)}
+                        className="p-2 text-gray-500 dark:text-gray-400 hover:bg-gray-100 dark:hover:bg-gray-700 rounded-lg transition-colors"
+                        title="Adjuntos"
+                      >
+                        <Paperclip size={16} />
+                      </button>
+                    </td>
                   </tr>
                 ))}
               </tbody>
@@ -396,6 +625,13 @@ export default function Sales() {
           </div>
         )}
       </div>
+
+      <AttachmentsDialog
+        entity="sales"
+        entityId={attachmentsSaleId}
+        title={`Adjuntos de la venta #${attachmentsSaleId ?? ''}`}
+        onClose={() => setAttachmentsSaleId(null)}
+      />
     </div>
   );
 }
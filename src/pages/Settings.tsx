@@ -2,6 +2,7 @@ import { User, Bell, Shield, Database, Globe } from 'lucide-react';
 import Button from '../components/Button';
 import { invoke } from '@tauri-apps/api';
 import { useAuth } from '../contexts/AuthContext';
+import DuplicateSalesPanel from '../components/DuplicateSalesPanel';
 
 const isTauriEnvironment = () => {
   if (typeof window === 'undefined') return false;
@@ -19,19 +20,33 @@ export default function Settings() {
     }
 
     const confirmed = window.confirm(
-      '⚠️ Esta acción borrará TODOS los datos (productos, ventas, movimientos de stock y caja, usuarios) y dejará solo el usuario admin/admin.\n\n¿Seguro que quieres continuar?'
+      '⚠️ Esta acción borrará TODOS los datos (productos, ventas, movimientos de stock y caja, usuarios) y dejará solo el usuario admin, que deberá pasar de nuevo por la configuración inicial.\n\n¿Seguro que quieres continuar?'
     );
     if (!confirmed) return;
 
     try {
       await invoke('reset_database');
-      alert('Base de datos limpiada correctamente.\n\nUsuario por defecto: admin / admin');
+      alert('Base de datos limpiada correctamente.\n\nAl volver a entrar se mostrará la configuración inicial.');
     } catch (error) {
       console.error('Error al limpiar la base de datos:', error);
       alert('Error al limpiar la base de datos. Revisa la consola para más detalles.');
     }
   };
 
+  const handleRegenerateThumbnails = async () => {
+    if (!isTauriEnvironment()) {
+      alert('Esta acción solo está disponible en la app de escritorio (Tauri).');
+      return;
+    }
+    try {
+      const report = await invoke<{ generated: number; failed: { name: string; error: string }[] }>('regenerate_thumbnails');
+      const failures = report.failed.map((f) => `• ${f.name}: ${f.error}`).join('\n');
+      alert(`Miniaturas generadas: ${report.generated}` + (failures ? `\n\nNo se pudieron generar:\n${failures}` : ''));
+    } catch (error) {
+      alert(String(error));
+    }
+  };
+
   if (user?.role !== 'Administrador') {
     return (
       <div className="p-6 bg-white dark:bg-gray-800 rounded-xl shadow-sm border border-gray-100 dark:border-gray-700">
@@ -133,12 +148,18 @@ export default function Settings() {
           <div className="flex gap-3">
             <Button variant="secondary">Exportar Datos</Button>
             <Button variant="secondary">Importar Datos</Button>
+            <Button variant="secondary" onClick={handleRegenerateThumbnails}>
+              Regenerar Miniaturas
+            </Button>
             <Button variant="danger" onClick={handleResetDatabase}>
               Limpiar Base de Datos
             </Button>
           </div>
         </div>
 
+        {/* Ventas duplicadas */}
+        <DuplicateSalesPanel />
+
         {/* Idioma */}
         <div className="bg-white rounded-lg shadow p-6">
           <div className="flex items-center gap-3 mb-4">
@@ -1,6 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api';
-import { Plus, Edit, Trash2, Users as UsersIcon } from 'lucide-react';
+import { Plus, Edit, Trash2, Target, Users as UsersIcon } from 'lucide-react';
 import Button from '../components/Button';
 import Modal from '../components/Modal';
 import { useAuth } from '../contexts/AuthContext';
@@ -107,6 +107,20 @@ export default function Users() {
     setIsModalOpen(true);
   };
 
+  /**
+   * Fija la meta mensual del ranking de vendedores (vacío la quita)
+   */
+  const handleSetTarget = async (user: User) => {
+    const input = prompt(`Meta mensual de ventas para ${user.fullname || user.username} (vacío = sin meta):`);
+    if (input === null || !user.id) return;
+    const monthlyTarget = input.trim() === '' ? null : Number(input);
+    try {
+      await invoke('set_seller_target', { userId: user.id, monthlyTarget });
+    } catch (error) {
+      alert(String(error));
+    }
+  };
+
   /**
    * Elimina un usuario
    */
@@ -245,6 +259,13 @@ export default function Users() {
                       >
                         <Edit size={16} />
                       </button>
+                      <button 
+                        onClick={() => handleSetTarget(user)}
+                        className="p-2 text-amber-600 dark:text-amber-400 hover:bg-amber-50 dark:hover:bg-amber-900/30 rounded-lg transition-colors"
+                        title="Meta mensual"
+                      >
+                        <Target size={16} />
+                      </button>
                       <button 
                         onClick={() => user.id && handleDeleteUser(user.id)}
                         className="p-2 text-red-600 dark:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/30 rounded-lg transition-colors"
@@ -357,6 +378,7 @@ export default function Users() {
               <option value="Administrador">Administrador</option>
               <option value="Vendedor">Vendedor</option>
               <option value="Almacenero">Almacenero</option>
+              <option value="Auditor">Auditor (solo lectura)</option>
             </select>
           </div>
           <div className="flex justify-end gap-3 pt-4 border-t border-gray-200 dark:border-gray-700">